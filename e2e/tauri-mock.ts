@@ -15,6 +15,43 @@ export async function injectTauriMocks(page: import("@playwright/test").Page) {
       modified: number | null;
     };
 
+    const fakeVolumes = [
+      {
+        name: "Macintosh HD",
+        mountPoint: "/",
+        totalBytes: 500_000_000_000,
+        freeBytes: 120_000_000_000,
+        isRemovable: false,
+        isNetwork: false,
+        available: true,
+      },
+    ];
+
+    const locations: {
+      recentPaths: string[];
+      favorites: { name: string; path: string }[];
+    } = { recentPaths: [], favorites: [] };
+    const recordRecent = (path: string) => {
+      const others = locations.recentPaths.filter((p) => p !== path);
+      locations.recentPaths = [path, ...others].slice(0, 20);
+    };
+    const fakeKnownFolders = () =>
+      [
+        ["home", HOME],
+        ["documents", `${HOME}/Documents`],
+        ["downloads", `${HOME}/Downloads`],
+        ["leftRoot", leftRoot],
+        ["rightRoot", rightRoot],
+        ["temp", "/tmp"],
+      ]
+        .filter(([, path]) => path)
+        .map(([id, path]) => ({
+          id,
+          name: path!.split("/").pop(),
+          path,
+          exists: path! in fakeFS,
+        }));
+
     const fakeFS: Record<string, FakeEntry[]> = {
       [HOME]: [
         { name: "Desktop", kind: "dir", size: 0, modified: 1700000000000 },
@@ -91,6 +128,13 @@ export async function injectTauriMocks(page: import("@playwright/test").Page) {
 
     let leftRoot: string | null = null;
     let rightRoot: string | null = null;
+    let readOnly = false;
+    let sessions: {
+      name: string;
+      leftRoot: string | null;
+      rightRoot: string | null;
+      options: unknown;
+    }[] = [];
     let lastDiffs: any[] = [];
     let lastSummary: any = null;
 
@@ -125,36 +169,91 @@ export async function injectTauriMocks(page: import("@playwright/test").Page) {
         return id;
       },
       invoke: async (cmd: string, args: any = {}) => {
+        const toBrowseEntry = (e: FakeEntry) => ({
+          ...e,
+          fileCount: null,
+          mode: null,
+          owner: null,
+          group: null,
+          hidden: e.name.startsWith("."),
+          error: null,
+        });
         // Core app commands
         if (cmd === "init_browse") {
           const entries = fakeFS[HOME] || [];
           return {
             home: HOME,
-            entries: [...entries].sort((a, b) => {
+            entries: entries.map(toBrowseEntry).sort((a, b) => {
               if (a.kind === "dir" && b.kind !== "dir") return -1;
               if (a.kind !== "dir" && b.kind === "dir") return 1;
               return a.name.toLowerCase().localeCompare(b.name.toLowerCase());
             }),
+            volumes: fakeVolumes,
+            knownFolders: fakeKnownFolders(),
           };
         }
 
+        if (cmd === "get_known_folders") return fakeKnownFolders();
+
+        if (cmd === "get_locations") return locations;
+
+        if (cmd === "add_favorite") {
+          const path = args.path as string;
+          const name = (args.name as string) || path.split("/").pop() || path;
+          const existing = locations.favorites.find((f) => f.path === path);
+          if (existing) existing.name = name;
+          else locations.favorites.push({ name, path });
+          return locations;
+        }
+
+        if (cmd === "remove_favorite") {
+          locations.favorites = locations.favorites.filter((f) => f.path !== args.path);
+          return locations;
+        }
+
+        if (cmd === "list_volumes") return fakeVolumes;
+
         if (cmd === "list_directory") {
           const entries = fakeFS[args.path];
           if (!entries) throw new Error(`Not a directory: ${args.path}`);
-          return [...entries].sort((a, b) => {
+          const listed = args.includeHidden === false
+            ? entries.filter((e) => !e.name.startsWith("."))
+            : entries;
+          recordRecent(args.path);
+          return listed.map(toBrowseEntry).sort((a, b) => {
             if (a.kind === "dir" && b.kind !== "dir") return -1;
             if (a.kind !== "dir" && b.kind === "dir") return 1;
             return a.name.toLowerCase().localeCompare(b.name.toLowerCase());
           });
         }
 
+        if (cmd === "resolve_path") {
+          const path = String(args.input).trim().replace(/^~(?=\/|$)/, HOME);
+          return { path, exists: path in fakeFS, isDir: path in fakeFS };
+        }
+
+        if (cmd === "complete_path") {
+          const partial = String(args.partial).trim().replace(/^~(?=\/|$)/, HOME);
+          const slash = partial.lastIndexOf("/");
+          const dir = partial.slice(0, slash) || "/";
+          const prefix = partial.slice(slash + 1).toLowerCase();
+          return (fakeFS[dir] ?? [])
+            .filter((e) => e.name.toLowerCase().startsWith(prefix))
+            .filter((e) => e.kind === "dir" || args.includeFiles)
+            .map((e) => `${dir === "/" ? "" : dir}/${e.name}${e.kind === "dir" ? "/" : ""}`)
+            .slice(0, args.max);
+        }
+
         if (cmd === "set_root") {
           if (args.side === "left") leftRoot = args.path;
           if (args.side === "right") rightRoot = args.path;
+          recordRecent(args.path);
           return null;
         }
 
         if (cmd === "start_compare") {
+          if (args.leftPath) leftRoot = args.leftPath;
+          if (args.rightPath) rightRoot = args.rightPath;
           const leftEntries = fakeFS[leftRoot!] || [];
           const rightEntries = fakeFS[rightRoot!] || [];
 
@@ -162,6 +261,8 @@ export async function injectTauriMocks(page: import("@playwright/test").Page) {
             emitTauriEvent("scan-progress", {
               side: "left",
               entriesScanned: leftEntries.length,
+              bytesScanned: 0,
+              currentPath: null,
               phase: "done",
             });
           }, 1);
@@ -170,6 +271,8 @@ export async function injectTauriMocks(page: import("@playwright/test").Page) {
             emitTauriEvent("scan-progress", {
               side: "right",
               entriesScanned: rightEntries.length,
+              bytesScanned: 0,
+              currentPath: null,
               phase: "done",
             });
           }, 2);
@@ -194,6 +297,7 @@ export async function injectTauriMocks(page: import("@playwright/test").Page) {
                 size: e.size,
                 modified: e.modified,
                 symlinkTarget: null,
+                mode: null,
               });
 
               if (l && !r) {
@@ -248,6 +352,14 @@ export async function injectTauriMocks(page: import("@playwright/test").Page) {
               same,
               metaDiff,
               errors: 0,
+              pending: 0,
+              bytesOnlyLeft: 0,
+              bytesOnlyRight: 0,
+              bytesModified: 0,
+              uniqueBytesOnlyLeft: 0,
+              uniqueBytesOnlyRight: 0,
+              uniqueBytesModified: 0,
+              depthLimited: 0,
             };
 
             emitTauriEvent("compare-done", { summary: lastSummary });
@@ -386,7 +498,13 @@ export async function injectTauriMocks(page: import("@playwright/test").Page) {
               totalLeft: leftEntries.length,
               totalRight: rightEntries.length,
               onlyLeft, onlyRight, typeMismatch, same, metaDiff, errors: 0,
+              pending: pendingDirs.length,
+              bytesOnlyLeft: 0, bytesOnlyRight: 0, bytesModified: 0,
+              uniqueBytesOnlyLeft: 0, uniqueBytesOnlyRight: 0, uniqueBytesModified: 0,
+              depthLimited: 0,
             },
+            errorLeft: null,
+            errorRight: null,
           };
         }
 
@@ -400,13 +518,22 @@ export async function injectTauriMocks(page: import("@playwright/test").Page) {
             setTimeout(() => {
               if ((window as any).__dirResolveCancelled) return;
               const isSame = dirsAreSameFn(dir.leftPath, dir.rightPath);
-              const totalSize = calcTotalSizeFn(dir.leftPath);
+              const leftSize = calcTotalSizeFn(dir.leftPath);
+              const rightSize = calcTotalSizeFn(dir.rightPath);
               emitTauriEvent("dir-status-resolved", {
                 name: dir.name,
                 status: isSame ? "same" : "modified",
                 leftPath: args.leftPath,
                 rightPath: args.rightPath,
-                totalSize,
+                leftSize,
+                rightSize,
+                leftUniqueSize: leftSize,
+                rightUniqueSize: rightSize,
+                // The mock does not walk the trees for exact counts
+                onlyLeft: 0,
+                onlyRight: 0,
+                modified: isSame ? 0 : 1,
+                depthLimited: 0,
               });
             }, (i + 1) * 10);
           });
@@ -423,14 +550,106 @@ export async function injectTauriMocks(page: import("@playwright/test").Page) {
           return null;
         }
 
+        // Directory sizes are never resolved, so browse panes keep showing a dash
+        if (cmd === "resolve_dir_sizes") return null;
+        if (cmd === "cancel_dir_sizes") return null;
+        if (cmd === "clear_dir_size_cache") return null;
+
+        if (cmd === "search_files") {
+          emitTauriEvent("search-done", { root: args.root, total: 0, truncated: false });
+          return { total: 0, truncated: false };
+        }
+        if (cmd === "search_content") {
+          const summary = {
+            total: 0,
+            truncated: false,
+            filesSearched: 0,
+            skippedBinary: 0,
+            skippedLarge: 0,
+            errors: [],
+          };
+          emitTauriEvent("content-search-done", { root: args.root, summary });
+          return summary;
+        }
+        if (cmd === "cancel_search") return null;
+
         if (cmd === "cancel_compare") return null;
+        if (cmd === "watch_roots") return null;
+        if (cmd === "watch_directory" || cmd === "unwatch_directory") return null;
         if (cmd === "get_diffs") return lastDiffs;
         if (cmd === "get_summary") return lastSummary;
         if (cmd === "export_report") return null;
         if (cmd === "open_file") return null;
-        if (cmd === "copy_entry") return null;
-        if (cmd === "copy_entry_overwrite") return null;
+        if (cmd === "preview_file") {
+          return { isBinary: false, encoding: "utf8", truncated: false, text: "", byteLength: 0 };
+        }
+        if (cmd === "diff_file_contents") {
+          return { isBinary: false, tooLarge: false, hunks: [], truncated: false, leftSize: 0, rightSize: 0 };
+        }
+        if (cmd === "compare_files") {
+          return { same: true, leftSize: 0, rightSize: 0, firstDifferenceOffset: null, method: "content" };
+        }
+        if (cmd === "cancel_compare_files") return null;
+        if (cmd === "compute_checksums") return { checksums: [], errors: [] };
+        if (cmd === "cancel_checksums") return null;
+        if (cmd === "find_duplicates") {
+          return { groups: 0, duplicateFiles: 0, wastedBytes: 0, unreadable: 0 };
+        }
+        if (cmd === "cancel_duplicates") return null;
+        if (cmd === "copy_entry" || cmd === "copy_entry_overwrite") {
+          return { dest: args.destDir, filesCloned: 0, filesCopied: 1, merged: null, skipped: [] };
+        }
         if (cmd === "move_entry") return null;
+        if (cmd === "duplicate_entry") return args.path + " copy";
+        if (cmd === "set_permissions") return { changed: 1, errors: [] };
+        if (cmd === "extract_archive") return { extracted: 0, skipped: [], rejected: [] };
+        if (cmd === "create_archive") {
+          const dest = args.destPath;
+          return { dest, files: 0, dirs: 0, symlinks: 0, bytes: 0, archiveBytes: 0, skipped: [] };
+        }
+        if (cmd === "set_modified" || cmd === "copy_times") {
+          const modified = args.modifiedMs ?? 0;
+          return { kind: "file", size: 0, modified, symlinkTarget: null, mode: null };
+        }
+        if (cmd === "set_read_only") {
+          readOnly = args.enabled;
+          return null;
+        }
+        if (cmd === "get_read_only") return readOnly;
+        if (cmd === "save_session") {
+          const session = {
+            name: String(args.name).trim(),
+            leftRoot: leftRoot,
+            rightRoot: rightRoot,
+            options: args.options ?? {},
+          };
+          const index = sessions.findIndex((s) => s.name === session.name);
+          if (index >= 0) sessions[index] = session;
+          else sessions.push(session);
+          return null;
+        }
+        if (cmd === "list_sessions") return sessions;
+        if (cmd === "load_session") {
+          const session = sessions.find((s) => s.name === args.name);
+          if (!session) {
+            throw { code: "invalidArgument", message: `No session named ${args.name}`, path: null };
+          }
+          leftRoot = session.leftRoot;
+          rightRoot = session.rightRoot;
+          return session;
+        }
+        if (cmd === "delete_session") {
+          const before = sessions.length;
+          sessions = sessions.filter((s) => s.name !== args.name);
+          return sessions.length !== before;
+        }
+        if (cmd === "get_undo_stack") return [];
+        if (cmd === "undo_last_operation") {
+          throw { code: "other", message: "Nothing to undo", path: null };
+        }
+        if (cmd === "get_volume_info") {
+          return { totalBytes: 0, freeBytes: 0, filesystem: null };
+        }
         if (cmd === "create_directory") {
           const parentEntries = fakeFS[args.parentPath];
           if (parentEntries) {
@@ -444,9 +663,23 @@ export async function injectTauriMocks(page: import("@playwright/test").Page) {
           }
           return null;
         }
+        if (cmd === "create_file") {
+          const parentEntries = fakeFS[args.parentPath];
+          if (parentEntries) {
+            parentEntries.push({
+              name: args.name,
+              kind: "file",
+              size: args.initialContent?.length ?? 0,
+              modified: Date.now(),
+            });
+          }
+          return args.parentPath + "/" + args.name;
+        }
         if (cmd === "delete_entry") return null;
         if (cmd === "load_app_state") return null;
         if (cmd === "save_app_state") return null;
+        if (cmd === "load_window_state") return null;
+        if (cmd === "save_window_state") return null;
 
         // Terminal commands (side-aware: "left" or "right")
         if (cmd === "spawn_terminal") {
@@ -471,6 +704,8 @@ export async function injectTauriMocks(page: import("@playwright/test").Page) {
           return null;
         }
         if (cmd === "resize_terminal") return null;
+        if (cmd === "get_terminal_cwd") return null;
+        if (cmd === "get_terminal_buffer") return "";
         if (cmd === "kill_terminal") {
           const sideKey = `__terminalAlive_${args.side}`;
           (window as any)[sideKey] = false;
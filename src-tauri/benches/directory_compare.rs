@@ -0,0 +1,63 @@
+//! Single-level directory compare and recursive directory resolution on
+//! deterministic synthetic trees. Run with `cargo bench -p split-commander`.
+
+use std::sync::atomic::AtomicBool;
+use std::sync::Mutex;
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use split_commander_lib::bench::{
+    compare_directory_impl, dirs_are_same_recursive_counted, DirResolveCache,
+};
+use splitcommander_core::fixtures::{self, FixtureSpec};
+use splitcommander_core::scan::KeyMode;
+use splitcommander_core::sort::SortOrder;
+
+fn spec() -> FixtureSpec {
+    // Wide top level (like a home or project dir) over a moderately deep tree
+    FixtureSpec {
+        seed: 0x5C_0002,
+        depth: 3,
+        fan_out: 6,
+        files_per_dir: 40,
+        max_file_size: 8 * 1024,
+        ..Default::default()
+    }
+}
+
+fn bench_compare_directory(c: &mut Criterion) {
+    let fixture = fixtures::generate(&spec()).expect("generate fixture");
+    let left = fixture.left.to_string_lossy().to_string();
+    let right = fixture.right.to_string_lossy().to_string();
+
+    c.bench_function("compare_directory_impl/uncached", |b| {
+        b.iter(|| {
+            let cache = Mutex::new(DirResolveCache::default());
+            compare_directory_impl(
+                &left,
+                &right,
+                KeyMode::default(),
+                SortOrder::default(),
+                &cache,
+            )
+        })
+    });
+}
+
+fn bench_dir_resolver(c: &mut Criterion) {
+    let fixture = fixtures::generate(&spec()).expect("generate fixture");
+    let left = fixture.left.to_string_lossy().to_string();
+    let right = fixture.right.to_string_lossy().to_string();
+    let cancel = AtomicBool::new(false);
+
+    let mut group = c.benchmark_group("dir_resolver");
+    group.sample_size(20);
+    group.bench_function("dirs_are_same_recursive_counted", |b| {
+        b.iter(|| {
+            dirs_are_same_recursive_counted(&left, &right, KeyMode::default(), None, &cancel)
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, bench_compare_directory, bench_dir_resolver);
+criterion_main!(benches);
@@ -0,0 +1,58 @@
+//! Scan and compare throughput on deterministic synthetic trees.
+//! Run with `cargo bench -p splitcommander-core`.
+
+use std::sync::atomic::AtomicBool;
+
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+use splitcommander_core::compare::compare;
+use splitcommander_core::fixtures::{self, FixtureSpec};
+use splitcommander_core::ignore::IgnoreRules;
+use splitcommander_core::model::CompareMode;
+use splitcommander_core::scan::scan_directory;
+
+fn spec() -> FixtureSpec {
+    // 1 + 5 + 25 + 125 + 625 dirs with 24 entries each: ~19k entries per side
+    FixtureSpec {
+        seed: 0x5C_0001,
+        depth: 4,
+        fan_out: 5,
+        files_per_dir: 24,
+        max_file_size: 16 * 1024,
+        ..Default::default()
+    }
+}
+
+fn bench_scan(c: &mut Criterion) {
+    let fixture = fixtures::generate(&spec()).expect("generate fixture");
+    let rules = IgnoreRules::new(&[]);
+    let cancel = AtomicBool::new(false);
+    let entries = fixture.stats.dirs + fixture.stats.files + fixture.stats.symlinks;
+
+    let mut group = c.benchmark_group("scan");
+    group.throughput(Throughput::Elements(entries as u64));
+    group.sample_size(20);
+    group.bench_function("scan_directory", |b| {
+        b.iter(|| scan_directory(&fixture.left, &rules, &[], &cancel, &|_| {}).unwrap())
+    });
+    group.finish();
+}
+
+fn bench_compare(c: &mut Criterion) {
+    let fixture = fixtures::generate(&spec()).expect("generate fixture");
+    let rules = IgnoreRules::new(&[]);
+    let cancel = AtomicBool::new(false);
+    let left = scan_directory(&fixture.left, &rules, &[], &cancel, &|_| {}).unwrap();
+    let right = scan_directory(&fixture.right, &rules, &[], &cancel, &|_| {}).unwrap();
+
+    let mut group = c.benchmark_group("compare");
+    group.throughput(Throughput::Elements((left.count + right.count) as u64));
+    for mode in [CompareMode::Structure, CompareMode::Smart] {
+        group.bench_function(format!("{:?}", mode), |b| {
+            b.iter(|| compare(&left, &right, mode, &cancel).unwrap())
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_scan, bench_compare);
+criterion_main!(benches);
@@ -0,0 +1,1636 @@
+//! Packing a selection of files and folders into a zip or tar.gz archive, unpacking
+//! such archives again, and listing one as a tree that can be compared like a folder.
+
+use std::cell::Cell;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs::{self, File};
+use std::io::{self, Cursor, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::{Duration, Instant};
+
+use chrono::{Datelike, TimeZone, Timelike};
+use filetime::FileTime;
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use serde::Serialize;
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipWriter};
+
+use crate::error::CommandError;
+use crate::fileops::{self, ConflictPolicy};
+use crate::ignore::IgnoreRules;
+use crate::model::{self, mode_bits, EntryMeta};
+use crate::scan::{self, KeyMode, ScanError, ScanOptions, ScanProgress, ScanResult};
+use crate::security;
+use crate::source::TreeSource;
+
+/// Minimum time between two progress reports while a file is being stored.
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(100);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArchiveFormat {
+    Zip,
+    TarGz,
+}
+
+impl ArchiveFormat {
+    /// Parses a format name as sent by the frontend: "zip", or "tar.gz" / "tgz".
+    pub fn parse(name: &str) -> Result<Self, CommandError> {
+        match name {
+            "zip" => Ok(Self::Zip),
+            "tar.gz" | "tgz" => Ok(Self::TarGz),
+            _ => Err(CommandError::invalid_argument(format!(
+                "Unknown archive format: {}",
+                name
+            ))),
+        }
+    }
+
+    /// Recognizes an archive by its first bytes, whatever its name says.
+    pub fn detect(path: &Path) -> Result<Self, CommandError> {
+        let mut magic = [0u8; 4];
+        let read = File::open(path).and_then(|mut file| {
+            let mut filled = 0;
+            while filled < magic.len() {
+                match file.read(&mut magic[filled..])? {
+                    0 => break,
+                    n => filled += n,
+                }
+            }
+            Ok(filled)
+        });
+        let read = read.map_err(|e| {
+            CommandError::io(format!("Cannot read {}", path.display()), path, &e)
+        })?;
+        match &magic[..read] {
+            // Local file header, or the end record of an empty archive
+            [b'P', b'K', 3, 4] | [b'P', b'K', 5, 6] => Ok(Self::Zip),
+            [0x1f, 0x8b, ..] => Ok(Self::TarGz),
+            _ => Err(CommandError::invalid_argument(format!(
+                "Not a zip or tar.gz archive: {}",
+                path.display()
+            ))),
+        }
+    }
+}
+
+/// What went into an archive.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ArchiveReport {
+    /// Where the archive was written; differs from the requested path after a rename
+    pub dest: PathBuf,
+    pub files: usize,
+    pub dirs: usize,
+    pub symlinks: usize,
+    /// Uncompressed size of the files stored
+    pub bytes: u64,
+    /// Size of the archive itself
+    pub archive_bytes: u64,
+    /// Entries that were left out, with the reason
+    pub skipped: Vec<ScanError>,
+}
+
+/// Packs `sources` into a new archive at `dest`. Entries are named by their path
+/// below the deepest folder containing all sources, so a selection from one folder
+/// ends up at the top level of the archive. Symlinks are stored as links, never
+/// followed; fifos, sockets and devices are skipped and listed in the report.
+///
+/// `policy` decides what happens if `dest` exists, as for copies; "merge" is rejected.
+/// The archive is written to a temp file that replaces `dest` only once complete, so
+/// a failed or cancelled run leaves nothing behind. `dest` may lie in a folder being
+/// packed, which then leaves the archive out, but may not be one of the sources.
+///
+/// - `progress`: called with the file bytes stored so far, their total, and the name
+///   of the entry being stored
+pub fn create_archive(
+    sources: &[PathBuf],
+    dest: &Path,
+    format: ArchiveFormat,
+    policy: ConflictPolicy,
+    cancel_flag: &AtomicBool,
+    progress: &dyn Fn(u64, u64, &str),
+) -> Result<ArchiveReport, CommandError> {
+    let sources = outermost(sources);
+    if sources.is_empty() {
+        return Err(CommandError::invalid_argument("Nothing to archive"));
+    }
+    let dest_dir = dest
+        .parent()
+        .filter(|p| !p.as_os_str().is_empty() && dest.file_name().is_some())
+        .ok_or_else(|| CommandError::invalid_argument("Invalid archive path"))?;
+    for src in &sources {
+        if fs::symlink_metadata(src).is_err() {
+            return Err(CommandError::not_found(
+                format!("Source does not exist: {}", src.display()),
+                src,
+            ));
+        }
+    }
+
+    let dest = match policy {
+        ConflictPolicy::Fail if fs::symlink_metadata(dest).is_ok() => {
+            return Err(CommandError::already_exists(
+                format!("Destination already exists: {}", dest.display()),
+                dest,
+            ))
+        }
+        ConflictPolicy::Fail | ConflictPolicy::Overwrite => dest.to_path_buf(),
+        ConflictPolicy::Rename => fileops::free_dest(dest, dest_dir)?,
+        ConflictPolicy::Merge => {
+            return Err(CommandError::invalid_argument("An archive cannot be merged into"))
+        }
+    };
+    // The archive may go into a folder it packs, which then skips it, but it cannot
+    // replace one of the entries it is made of
+    let excluded: Vec<PathBuf> = [dest.clone(), fileops::atomic_temp_path(&dest)]
+        .iter()
+        .filter_map(|path| resolved_entry(path))
+        .collect();
+    let resolved_dest = resolved_entry(&dest);
+    if let Some(src) = sources
+        .iter()
+        .find(|src| resolved_dest.is_some() && resolved_entry(src) == resolved_dest)
+    {
+        return Err(CommandError::invalid_argument(format!(
+            "Cannot archive {} into itself",
+            src.display()
+        )));
+    }
+
+    // Nothing is packed while the sources are measured; the total grows as they are
+    let measuring = |_, bytes| progress(0, bytes, "");
+    let totals = scan::measure_paths(&sources, &IgnoreRules::new(&[]), cancel_flag, &measuring)
+        .map_err(|e| cancelled_or(cancel_flag, e))?;
+    let base = common_base(&sources);
+    let mut packer = Packer {
+        report: ArchiveReport {
+            dest: dest.clone(),
+            files: 0,
+            dirs: 0,
+            symlinks: 0,
+            bytes: 0,
+            archive_bytes: 0,
+            skipped: Vec::new(),
+        },
+        total: totals.bytes,
+        reported_at: None,
+        excluded,
+        cancel_flag,
+        progress,
+    };
+
+    let mut report = fileops::write_atomic_with(&dest, |file| {
+        match format {
+            ArchiveFormat::Zip => {
+                let mut sink = ZipSink(ZipWriter::new(file));
+                packer.pack_all(&mut sink, &sources, &base)?;
+                sink.0.finish().map_err(|e| write_error(&dest, io::Error::other(e)))?;
+            }
+            ArchiveFormat::TarGz => {
+                let gz = GzEncoder::new(file, Compression::default());
+                let mut sink = TarSink(tar::Builder::new(gz));
+                packer.pack_all(&mut sink, &sources, &base)?;
+                sink.0
+                    .into_inner()
+                    .and_then(GzEncoder::finish)
+                    .map_err(|e| write_error(&dest, e))?;
+            }
+        }
+        Ok(packer.report)
+    })?;
+    report.archive_bytes = fs::metadata(&dest).map(|m| m.len()).unwrap_or(0);
+    Ok(report)
+}
+
+/// `path` with its parent resolved; a link at `path` itself is left as it is.
+fn resolved_entry(path: &Path) -> Option<PathBuf> {
+    let parent = path.parent().filter(|p| !p.as_os_str().is_empty())?;
+    Some(fs::canonicalize(parent).ok()?.join(path.file_name()?))
+}
+
+/// `sources` without duplicates and without paths inside another source, which
+/// would otherwise be stored twice.
+fn outermost(sources: &[PathBuf]) -> Vec<PathBuf> {
+    let mut sorted = sources.to_vec();
+    sorted.sort();
+    let mut kept: Vec<PathBuf> = Vec::with_capacity(sorted.len());
+    for src in sorted {
+        if !kept.iter().any(|k| src.starts_with(k)) {
+            kept.push(src);
+        }
+    }
+    kept
+}
+
+/// The deepest directory containing every source.
+fn common_base(sources: &[PathBuf]) -> PathBuf {
+    let mut base = sources[0].parent().map(Path::to_path_buf).unwrap_or_default();
+    for src in &sources[1..] {
+        while !src.starts_with(&base) && base.pop() {}
+    }
+    base
+}
+
+fn cancelled_or(cancel_flag: &AtomicBool, message: String) -> CommandError {
+    if cancel_flag.load(Ordering::Relaxed) {
+        CommandError::cancelled("Archive cancelled")
+    } else {
+        message.into()
+    }
+}
+
+fn write_error(dest: &Path, e: io::Error) -> CommandError {
+    CommandError::io(format!("Cannot write {}", dest.display()), dest, &e)
+}
+
+/// An archive being written; names use `/` and directory names end with one.
+trait ArchiveSink {
+    fn add_dir(&mut self, name: &str, meta: &fs::Metadata) -> io::Result<()>;
+    fn add_file(&mut self, name: &str, meta: &fs::Metadata, data: &mut dyn Read) -> io::Result<()>;
+    fn add_symlink(&mut self, name: &str, target: &Path, meta: &fs::Metadata) -> io::Result<()>;
+}
+
+struct ZipSink<W: Write + io::Seek>(ZipWriter<W>);
+
+impl<W: Write + io::Seek> ZipSink<W> {
+    fn options(meta: &fs::Metadata) -> SimpleFileOptions {
+        let default_mode = if meta.is_dir() { 0o755 } else { 0o644 };
+        SimpleFileOptions::default()
+            .compression_method(CompressionMethod::Deflated)
+            .unix_permissions(mode_bits(meta).unwrap_or(default_mode))
+            .last_modified_time(zip_time(meta))
+            .large_file(meta.len() >= u32::MAX as u64)
+    }
+}
+
+impl<W: Write + io::Seek> ArchiveSink for ZipSink<W> {
+    fn add_dir(&mut self, name: &str, meta: &fs::Metadata) -> io::Result<()> {
+        self.0.add_directory(name, Self::options(meta)).map_err(io::Error::other)
+    }
+
+    fn add_file(
+        &mut self,
+        name: &str,
+        meta: &fs::Metadata,
+        data: &mut dyn Read,
+    ) -> io::Result<()> {
+        self.0.start_file(name, Self::options(meta)).map_err(io::Error::other)?;
+        io::copy(data, &mut self.0).map(|_| ())
+    }
+
+    fn add_symlink(&mut self, name: &str, target: &Path, meta: &fs::Metadata) -> io::Result<()> {
+        let target = target.to_string_lossy().replace('\\', "/");
+        self.0
+            .add_symlink(name, target, Self::options(meta))
+            .map_err(io::Error::other)
+    }
+}
+
+/// Zip stores local time with two-second precision, from 1980 on.
+fn zip_time(meta: &fs::Metadata) -> zip::DateTime {
+    let Ok(modified) = meta.modified() else {
+        return zip::DateTime::default();
+    };
+    let local = chrono::DateTime::<chrono::Local>::from(modified);
+    zip::DateTime::from_date_and_time(
+        local.year().clamp(1980, 2107) as u16,
+        local.month() as u8,
+        local.day() as u8,
+        local.hour() as u8,
+        local.minute() as u8,
+        local.second() as u8,
+    )
+    .unwrap_or_default()
+}
+
+struct TarSink<W: Write>(tar::Builder<W>);
+
+impl<W: Write> TarSink<W> {
+    fn header(meta: &fs::Metadata, entry_type: tar::EntryType) -> tar::Header {
+        let mut header = tar::Header::new_gnu();
+        header.set_metadata(meta);
+        header.set_entry_type(entry_type);
+        header.set_size(0);
+        header
+    }
+}
+
+impl<W: Write> ArchiveSink for TarSink<W> {
+    fn add_dir(&mut self, name: &str, meta: &fs::Metadata) -> io::Result<()> {
+        let mut header = Self::header(meta, tar::EntryType::Directory);
+        self.0.append_data(&mut header, name, io::empty())
+    }
+
+    fn add_file(
+        &mut self,
+        name: &str,
+        meta: &fs::Metadata,
+        data: &mut dyn Read,
+    ) -> io::Result<()> {
+        let mut header = Self::header(meta, tar::EntryType::Regular);
+        header.set_size(meta.len());
+        // The header promises this many bytes, even if the file grows meanwhile
+        let mut data = data.take(meta.len());
+        self.0.append_data(&mut header, name, &mut data)?;
+        // A file that shrank was padded to its promised size; the entry would be corrupt
+        if data.limit() > 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                format!("{} shrank while it was being archived", name),
+            ));
+        }
+        Ok(())
+    }
+
+    fn add_symlink(&mut self, name: &str, target: &Path, meta: &fs::Metadata) -> io::Result<()> {
+        let mut header = Self::header(meta, tar::EntryType::Symlink);
+        self.0.append_link(&mut header, name, target)
+    }
+}
+
+struct Packer<'a> {
+    report: ArchiveReport,
+    total: u64,
+    reported_at: Option<Instant>,
+    /// The archive being written and its temp file, as [`resolved_entry`] gives them
+    excluded: Vec<PathBuf>,
+    cancel_flag: &'a AtomicBool,
+    progress: &'a dyn Fn(u64, u64, &str),
+}
+
+impl Packer<'_> {
+    fn pack_all(
+        &mut self,
+        sink: &mut dyn ArchiveSink,
+        sources: &[PathBuf],
+        base: &Path,
+    ) -> Result<(), CommandError> {
+        for src in sources {
+            let rel = src.strip_prefix(base).unwrap_or(src);
+            let name = rel
+                .components()
+                .map(|c| c.as_os_str().to_string_lossy())
+                .collect::<Vec<_>>()
+                .join("/");
+            self.pack(sink, src, &name)?;
+        }
+        Ok(())
+    }
+
+    fn pack(
+        &mut self,
+        sink: &mut dyn ArchiveSink,
+        path: &Path,
+        name: &str,
+    ) -> Result<(), CommandError> {
+        if self.cancel_flag.load(Ordering::Relaxed) {
+            return Err(CommandError::cancelled("Archive cancelled"));
+        }
+        let read_error =
+            |e: io::Error| CommandError::io(format!("Cannot read {}", path.display()), path, &e);
+        let meta = fs::symlink_metadata(path).map_err(read_error)?;
+        let stored = |e: io::Error| self.store_error(path, e);
+
+        if meta.file_type().is_symlink() {
+            let target = fs::read_link(path).map_err(read_error)?;
+            sink.add_symlink(name, &target, &meta).map_err(stored)?;
+            self.report.symlinks += 1;
+        } else if meta.is_dir() {
+            sink.add_dir(&format!("{}/", name), &meta).map_err(stored)?;
+            self.report.dirs += 1;
+            let mut children = fs::read_dir(path)
+                .and_then(|rd| rd.collect::<io::Result<Vec<_>>>())
+                .map_err(read_error)?;
+            children.sort_by_key(|entry| entry.file_name());
+            for child in children {
+                let child_name = format!("{}/{}", name, child.file_name().to_string_lossy());
+                self.pack(sink, &child.path(), &child_name)?;
+            }
+        } else if meta.is_file() && self.is_archive_itself(path) {
+            // Written into a folder it packs; storing it would copy it into itself
+        } else if meta.is_file() {
+            (self.progress)(self.report.bytes, self.total, name);
+            let mut data = Counted {
+                inner: File::open(path).map_err(read_error)?,
+                packer: self,
+                name,
+            };
+            let added = sink.add_file(name, &meta, &mut data);
+            added.map_err(|e| self.store_error(path, e))?;
+            self.report.files += 1;
+        } else {
+            self.report.skipped.push(ScanError {
+                path: path.display().to_string(),
+                message: "Not a file, folder or link; cannot be archived".to_string(),
+            });
+        }
+        Ok(())
+    }
+
+    fn is_archive_itself(&self, path: &Path) -> bool {
+        // Names are compared first, so only a likely match has its parent resolved
+        self.excluded.iter().any(|ex| {
+            path.file_name() == ex.file_name() && resolved_entry(path).is_some_and(|p| p == *ex)
+        })
+    }
+
+    /// A failure while storing `path`, which is a cancellation if one was requested.
+    fn store_error(&self, path: &Path, e: io::Error) -> CommandError {
+        if self.cancel_flag.load(Ordering::Relaxed) {
+            CommandError::cancelled("Archive cancelled")
+        } else {
+            CommandError::io(format!("Cannot archive {}", path.display()), path, &e)
+        }
+    }
+}
+
+/// File contents on their way into the archive: counted, reported and cancellable.
+struct Counted<'p, 'a> {
+    inner: File,
+    packer: &'p mut Packer<'a>,
+    name: &'p str,
+}
+
+impl Read for Counted<'_, '_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let packer = &mut *self.packer;
+        if packer.cancel_flag.load(Ordering::Relaxed) {
+            return Err(io::Error::other("cancelled"));
+        }
+        let n = self.inner.read(buf)?;
+        packer.report.bytes += n as u64;
+        if packer.reported_at.is_none_or(|at| at.elapsed() >= PROGRESS_INTERVAL) {
+            packer.reported_at = Some(Instant::now());
+            (packer.progress)(packer.report.bytes, packer.total, self.name);
+        }
+        Ok(n)
+    }
+}
+
+/// What came out of an archive.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExtractReport {
+    /// Files, folders and links created or overwritten
+    pub extracted: usize,
+    /// Entries left out: existing ones under the "fail" policy, kinds that cannot be
+    /// extracted, and ones that failed to write
+    pub skipped: Vec<ScanError>,
+    /// Entries whose path would end up outside the destination (`../`, absolute paths,
+    /// links leading out), by their name in the archive; nothing is written for them
+    pub rejected: Vec<ScanError>,
+}
+
+/// Unpacks the zip or tar.gz archive at `archive` (told apart by content) into
+/// `dest_dir`. Every entry's output path is confined to `dest_dir`, through existing
+/// symlinks too, and symlink entries may only point inside it; entries breaking
+/// that are rejected and reported while the rest is extracted.
+///
+/// Existing folders are merged into. For an existing file or link, `policy` decides:
+/// "fail" skips the entry, "overwrite" and "merge" replace it, "rename" extracts next
+/// to it as `name (1).ext`. A cancelled extraction keeps what was extracted so far
+/// but not the file being written.
+///
+/// - `progress`: called with bytes processed so far, their total, and the current
+///   entry. For zip these are uncompressed bytes; a tar.gz has no index, so its
+///   compressed bytes read are counted instead.
+pub fn extract_archive(
+    archive: &Path,
+    dest_dir: &Path,
+    policy: ConflictPolicy,
+    cancel_flag: &AtomicBool,
+    progress: &dyn Fn(u64, u64, &str),
+) -> Result<ExtractReport, CommandError> {
+    let format = ArchiveFormat::detect(archive)?;
+    if !dest_dir.is_dir() {
+        return Err(CommandError::invalid_argument(format!(
+            "Destination is not a directory: {}",
+            dest_dir.display()
+        )));
+    }
+    let mut unpacker = Unpacker {
+        dest_dir,
+        policy,
+        cancel_flag,
+        report: ExtractReport::default(),
+        linked_through: HashSet::new(),
+    };
+    read_entries(archive, format, progress, &mut |entry, data| {
+        unpacker.entry(entry, data)?;
+        Ok(true)
+    })?;
+    Ok(unpacker.report)
+}
+
+/// One archive entry as its header describes it.
+struct RawEntry {
+    /// As stored, before any checks
+    name: String,
+    kind: EntryKind,
+    size: u64,
+    mode: Option<u32>,
+    mtime: Option<FileTime>,
+}
+
+/// Calls `visit` with each entry of `archive` in stored order and a reader over its
+/// data, until `visit` returns false. `progress` is as for [`extract_archive`].
+fn read_entries(
+    archive: &Path,
+    format: ArchiveFormat,
+    progress: &dyn Fn(u64, u64, &str),
+    visit: &mut dyn FnMut(RawEntry, &mut dyn Read) -> Result<bool, CommandError>,
+) -> Result<(), CommandError> {
+    let read_error =
+        |e: io::Error| CommandError::io(format!("Cannot read {}", archive.display()), archive, &e);
+    let corrupt = |e: zip::result::ZipError| read_error(io::Error::other(e));
+    let file = File::open(archive).map_err(read_error)?;
+
+    match format {
+        ArchiveFormat::Zip => {
+            let mut zip = zip::ZipArchive::new(file).map_err(corrupt)?;
+            let mut total = 0;
+            for i in 0..zip.len() {
+                total += zip.by_index_raw(i).map_err(corrupt)?.size();
+            }
+            let mut done = 0;
+            for i in 0..zip.len() {
+                let mut entry = zip.by_index(i).map_err(corrupt)?;
+                let name = entry.name().to_string();
+                progress(done, total, &name);
+                done += entry.size();
+                let kind = if entry.is_dir() {
+                    EntryKind::Dir
+                } else if entry.is_symlink() {
+                    let mut target = String::new();
+                    entry.read_to_string(&mut target).map_err(read_error)?;
+                    EntryKind::Symlink(target)
+                } else {
+                    EntryKind::File
+                };
+                let raw = RawEntry {
+                    name,
+                    kind,
+                    size: entry.size(),
+                    mode: entry.unix_mode(),
+                    mtime: entry.last_modified().and_then(zip_mtime),
+                };
+                if !visit(raw, &mut entry)? {
+                    return Ok(());
+                }
+            }
+            progress(total, total, "");
+        }
+        ArchiveFormat::TarGz => {
+            let total = file.metadata().map(|m| m.len()).unwrap_or(0);
+            let position = Cell::new(0);
+            let tracked = Tracked {
+                inner: file,
+                position: &position,
+            };
+            let mut tar = tar::Archive::new(GzDecoder::new(tracked));
+            for entry in tar.entries().map_err(read_error)? {
+                let mut entry = entry.map_err(read_error)?;
+                let name = String::from_utf8_lossy(&entry.path_bytes()).to_string();
+                progress(position.get(), total, &name);
+                let header = entry.header();
+                let kind = match header.entry_type() {
+                    tar::EntryType::Directory => EntryKind::Dir,
+                    tar::EntryType::Regular | tar::EntryType::Continuous => EntryKind::File,
+                    tar::EntryType::Symlink => EntryKind::Symlink(
+                        entry
+                            .link_name_bytes()
+                            .map(|t| String::from_utf8_lossy(&t).to_string())
+                            .unwrap_or_default(),
+                    ),
+                    tar::EntryType::Link => EntryKind::Unsupported("Hard links are not supported"),
+                    _ => EntryKind::Unsupported("Not a file, folder or link"),
+                };
+                let raw = RawEntry {
+                    name,
+                    kind,
+                    size: entry.size(),
+                    mode: header.mode().ok(),
+                    mtime: header
+                        .mtime()
+                        .ok()
+                        .map(|secs| FileTime::from_unix_time(secs as i64, 0)),
+                };
+                if !visit(raw, &mut entry)? {
+                    return Ok(());
+                }
+            }
+            progress(total, total, "");
+        }
+    }
+    Ok(())
+}
+
+/// Zip times are local times without a zone.
+fn zip_mtime(time: zip::DateTime) -> Option<FileTime> {
+    let local = chrono::Local
+        .with_ymd_and_hms(
+            time.year() as i32,
+            time.month() as u32,
+            time.day() as u32,
+            time.hour() as u32,
+            time.minute() as u32,
+            time.second() as u32,
+        )
+        .earliest()?;
+    Some(FileTime::from_unix_time(local.timestamp(), 0))
+}
+
+/// The compressed file under a tar.gz, counting how far it has been read.
+struct Tracked<'a> {
+    inner: File,
+    position: &'a Cell<u64>,
+}
+
+impl Read for Tracked<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.position.set(self.position.get() + n as u64);
+        Ok(n)
+    }
+}
+
+enum EntryKind {
+    Dir,
+    File,
+    /// With the link's target as stored
+    Symlink(String),
+    Unsupported(&'static str),
+}
+
+/// What became of one archive entry.
+enum Placed {
+    Extracted,
+    /// A folder that already exists, or the archive root
+    Present,
+    Skipped(String),
+    Rejected(String),
+}
+
+struct Unpacker<'a> {
+    dest_dir: &'a Path,
+    policy: ConflictPolicy,
+    cancel_flag: &'a AtomicBool,
+    report: ExtractReport,
+    /// Every path the links extracted so far pass through, so no later link can be
+    /// placed on their way
+    linked_through: HashSet<String>,
+}
+
+impl Unpacker<'_> {
+    fn entry(&mut self, entry: RawEntry, data: &mut dyn Read) -> Result<(), CommandError> {
+        if self.cancel_flag.load(Ordering::Relaxed) {
+            return Err(CommandError::cancelled("Extraction cancelled"));
+        }
+        let RawEntry {
+            name,
+            kind,
+            mode,
+            mtime,
+            ..
+        } = entry;
+        let note = |message: String| ScanError {
+            path: name.clone(),
+            message,
+        };
+        match self.place(&name, kind, mode, mtime, data)? {
+            Placed::Extracted => self.report.extracted += 1,
+            Placed::Present => {}
+            Placed::Skipped(message) => self.report.skipped.push(note(message)),
+            Placed::Rejected(message) => self.report.rejected.push(note(message)),
+        }
+        Ok(())
+    }
+
+    /// Writes one entry. Errors are only returned for a cancellation; anything else
+    /// wrong with the entry skips it.
+    fn place(
+        &mut self,
+        name: &str,
+        kind: EntryKind,
+        mode: Option<u32>,
+        mtime: Option<FileTime>,
+        data: &mut dyn Read,
+    ) -> Result<Placed, CommandError> {
+        let rel_path = match entry_rel_path(name) {
+            Ok(Some(rel_path)) => rel_path,
+            // The archive's own root, as in "./"
+            Ok(None) => return Ok(Placed::Present),
+            Err(message) => return Ok(Placed::Rejected(message)),
+        };
+        if let EntryKind::Unsupported(message) = kind {
+            return Ok(Placed::Skipped(message.to_string()));
+        }
+        if let EntryKind::Symlink(target) = &kind {
+            // Names alone only tell where a link leads while the paths it goes through
+            // are folders, so links to or through other links are refused, whichever
+            // of them is extracted first
+            let rejected = |message: String| Ok(Placed::Rejected(message));
+            let Some(walked) = link_walk(&rel_path, target) else {
+                return rejected(format!("Link leads outside: {}", target));
+            };
+            let is_link = |path: &String| {
+                fs::symlink_metadata(self.dest_dir.join(path))
+                    .is_ok_and(|meta| meta.file_type().is_symlink())
+            };
+            if walked.iter().any(is_link) {
+                return rejected(format!("Link leads through a link: {}", target));
+            }
+            if self.linked_through.contains(&rel_path) {
+                return rejected("Another link leads through it".to_string());
+            }
+            self.linked_through.extend(walked);
+        }
+        let parent = match fileops::confined_parent(self.dest_dir, &rel_path, true) {
+            Ok(parent) => parent,
+            Err(e) => return Ok(Placed::Rejected(e.to_string())),
+        };
+        let mut target = parent.join(rel_path.rsplit('/').next().unwrap_or(&rel_path));
+
+        if let Ok(existing) = fs::symlink_metadata(&target) {
+            let skipped = |message: &str| Ok(Placed::Skipped(message.to_string()));
+            match (existing.is_dir(), &kind) {
+                (true, EntryKind::Dir) => return Ok(Placed::Present),
+                (true, _) => return skipped("A folder of that name exists"),
+                (false, EntryKind::Dir) => return skipped("A file of that name exists"),
+                _ => {}
+            }
+            match self.policy {
+                ConflictPolicy::Fail => return skipped("Already exists"),
+                ConflictPolicy::Overwrite | ConflictPolicy::Merge => {
+                    if let Err(e) = fs::remove_file(&target) {
+                        let e = CommandError::io("Cannot replace", &target, &e);
+                        return skipped(&e.to_string());
+                    }
+                }
+                ConflictPolicy::Rename => match fileops::free_dest(&target, &parent) {
+                    Ok(free) => target = free,
+                    Err(e) => return skipped(&e.to_string()),
+                },
+            }
+        }
+
+        let written = match kind {
+            EntryKind::Dir => fs::create_dir(&target)
+                .map_err(|e| CommandError::io("Cannot create folder", &target, &e)),
+            EntryKind::File => self.write_file(&target, data, mode, mtime),
+            EntryKind::Symlink(link) => create_link(&link, &target)
+                .map_err(|e| CommandError::io("Cannot create link", &target, &e)),
+            EntryKind::Unsupported(_) => unreachable!("skipped above"),
+        };
+        match written {
+            Ok(()) => Ok(Placed::Extracted),
+            Err(e) if e.code() == "cancelled" => Err(e),
+            Err(e) => Ok(Placed::Skipped(e.to_string())),
+        }
+    }
+
+    /// Writes a new file, never through an existing link, removing it if the write
+    /// fails or is cancelled.
+    fn write_file(
+        &self,
+        target: &Path,
+        data: &mut dyn Read,
+        mode: Option<u32>,
+        mtime: Option<FileTime>,
+    ) -> Result<(), CommandError> {
+        let mut file = fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(target)
+            .map_err(|e| CommandError::io("Cannot create file", target, &e))?;
+        let mut data = Cancellable {
+            inner: data,
+            cancel_flag: self.cancel_flag,
+        };
+        if let Err(e) = io::copy(&mut data, &mut file) {
+            drop(file);
+            let _ = fs::remove_file(target);
+            if self.cancel_flag.load(Ordering::Relaxed) {
+                return Err(CommandError::cancelled("Extraction cancelled"));
+            }
+            return Err(CommandError::io("Cannot extract", target, &e));
+        }
+        drop(file);
+        #[cfg(unix)]
+        if let Some(mode) = mode {
+            use std::os::unix::fs::PermissionsExt;
+            // Permission bits only; setuid and friends are not restored
+            let _ = fs::set_permissions(target, fs::Permissions::from_mode(mode & 0o777));
+        }
+        #[cfg(not(unix))]
+        let _ = mode;
+        if let Some(mtime) = mtime {
+            let _ = filetime::set_file_mtime(target, mtime);
+        }
+        Ok(())
+    }
+}
+
+struct Cancellable<'a> {
+    inner: &'a mut dyn Read,
+    cancel_flag: &'a AtomicBool,
+}
+
+impl Read for Cancellable<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.cancel_flag.load(Ordering::Relaxed) {
+            return Err(io::Error::other("cancelled"));
+        }
+        self.inner.read(buf)
+    }
+}
+
+/// An entry name as a `/`-separated path relative to the destination, without `.`
+/// components or a trailing `/`; `None` for the archive root. Absolute names and
+/// ones climbing out with `..` are refused.
+fn entry_rel_path(name: &str) -> Result<Option<String>, String> {
+    let name = name.replace('\\', "/");
+    let first = name.split('/').next().unwrap_or("");
+    if name.starts_with('/') || first.ends_with(':') {
+        return Err("Absolute path".to_string());
+    }
+    security::check_relative_path(&name).map_err(|e| e.to_string())?;
+    let parts: Vec<&str> = name.split('/').filter(|p| !p.is_empty() && *p != ".").collect();
+    Ok((!parts.is_empty()).then(|| parts.join("/")))
+}
+
+/// The paths, relative to the destination, that a link at `rel_path` pointing to
+/// `target` goes through on the way to what it points at, that one last. `None` when
+/// it leads outside the destination. Judged from the names alone.
+fn link_walk(rel_path: &str, target: &str) -> Option<Vec<String>> {
+    let target = target.replace('\\', "/");
+    if target.is_empty() || target.starts_with('/') || target.contains(':') {
+        return None;
+    }
+    let mut at: Vec<&str> = rel_path.split('/').collect();
+    at.pop();
+    let mut walked = Vec::new();
+    for part in target.split('/') {
+        match part {
+            "" | "." => {}
+            ".." => {
+                at.pop()?;
+            }
+            _ => {
+                at.push(part);
+                walked.push(at.join("/"));
+            }
+        }
+    }
+    Some(walked)
+}
+
+#[cfg(unix)]
+fn create_link(target: &str, path: &Path) -> io::Result<()> {
+    std::os::unix::fs::symlink(target, path)
+}
+
+#[cfg(windows)]
+fn create_link(target: &str, path: &Path) -> io::Result<()> {
+    std::os::windows::fs::symlink_file(target.replace('/', "\\"), path)
+}
+
+/// An archive file seen as a read-only tree, so it can stand on either side of a
+/// compare like a folder. Entries are listed from their headers once, on the first
+/// scan or stat, and the listing is kept for the life of the source. Contents are only
+/// decompressed when a file is opened, and then read whole, which for a tar.gz means
+/// going through the archive up to that file.
+pub struct ArchiveSource {
+    path: PathBuf,
+    format: ArchiveFormat,
+    index: Mutex<Option<Arc<ArchiveIndex>>>,
+}
+
+/// What listing an archive found, kept by [`ArchiveSource`].
+struct ArchiveIndex {
+    entries: BTreeMap<String, EntryMeta>,
+    errors: Vec<ScanError>,
+    /// Where each listed file or link sits in the archive, counting every entry
+    positions: HashMap<String, usize>,
+}
+
+impl ArchiveSource {
+    /// Fails unless `path` is a zip or tar.gz archive.
+    pub fn new(path: impl Into<PathBuf>) -> Result<Self, CommandError> {
+        let path = path.into();
+        let format = ArchiveFormat::detect(&path)?;
+        Ok(Self {
+            path,
+            format,
+            index: Mutex::new(None),
+        })
+    }
+
+    /// The archive's listing, read on the first call. A cancelled or failed listing is
+    /// not kept, so the next call tries again.
+    fn index(&self, cancel_flag: &AtomicBool) -> Result<Arc<ArchiveIndex>, CommandError> {
+        let mut index = self.index.lock().unwrap_or_else(PoisonError::into_inner);
+        if let Some(index) = index.as_ref() {
+            return Ok(Arc::clone(index));
+        }
+        let listed = Arc::new(self.list(cancel_flag)?);
+        *index = Some(Arc::clone(&listed));
+        Ok(listed)
+    }
+
+    /// Every entry by its relative path, with the folders deeper entries imply, and
+    /// the entries that cannot be listed. A name stored twice is listed as its last
+    /// copy, the one extracting would leave behind.
+    fn list(&self, cancel_flag: &AtomicBool) -> Result<ArchiveIndex, CommandError> {
+        let mut listed = BTreeMap::new();
+        let mut errors = Vec::new();
+        let mut positions = HashMap::new();
+        let mut position = 0;
+        read_entries(&self.path, self.format, &|_, _, _| {}, &mut |entry, _| {
+            if cancel_flag.load(Ordering::Relaxed) {
+                return Err(CommandError::cancelled("Scan cancelled"));
+            }
+            position += 1;
+            let unlisted = |message: String| ScanError {
+                path: entry.name.clone(),
+                message,
+            };
+            let rel_path = match entry_rel_path(&entry.name) {
+                Ok(Some(rel_path)) => rel_path,
+                Ok(None) => return Ok(true),
+                Err(message) => {
+                    errors.push(unlisted(message));
+                    return Ok(true);
+                }
+            };
+            let (kind, size, symlink_target) = match entry.kind {
+                EntryKind::Dir => (model::EntryKind::Dir, 0, None),
+                EntryKind::File => (model::EntryKind::File, entry.size, None),
+                EntryKind::Symlink(target) => {
+                    (model::EntryKind::Symlink, target.len() as u64, Some(target))
+                }
+                EntryKind::Unsupported(message) => {
+                    errors.push(unlisted(message.to_string()));
+                    return Ok(true);
+                }
+            };
+            let mut end = 0;
+            while let Some(pos) = rel_path[end..].find('/') {
+                end += pos;
+                listed
+                    .entry(rel_path[..end].to_string())
+                    .or_insert_with(folder_meta);
+                end += 1;
+            }
+            let meta = EntryMeta {
+                kind,
+                size,
+                modified: entry
+                    .mtime
+                    .and_then(|t| u64::try_from(t.unix_seconds()).ok())
+                    .map(|secs| secs * 1000),
+                symlink_target,
+                mode: entry.mode.map(|mode| mode & 0o7777),
+                link: None,
+            };
+            positions.insert(rel_path.clone(), position - 1);
+            listed.insert(rel_path, meta);
+            Ok(true)
+        })?;
+        Ok(ArchiveIndex {
+            entries: listed,
+            errors,
+            positions,
+        })
+    }
+}
+
+fn folder_meta() -> EntryMeta {
+    EntryMeta {
+        kind: model::EntryKind::Dir,
+        size: 0,
+        modified: None,
+        symlink_target: None,
+        mode: None,
+        link: None,
+    }
+}
+
+impl TreeSource for ArchiveSource {
+    fn root_label(&self) -> String {
+        self.path.to_string_lossy().to_string()
+    }
+
+    /// Links are listed as links even with `follow_links`, since their targets are
+    /// only names inside the archive.
+    fn scan(
+        &self,
+        ignore_rules: &IgnoreRules,
+        excluded_dirs: &[PathBuf],
+        options: &ScanOptions,
+        cancel_flag: &AtomicBool,
+        progress_callback: &dyn Fn(&ScanProgress),
+    ) -> Result<ScanResult, String> {
+        let index = self.index(cancel_flag).map_err(|e| e.to_string())?;
+        let (listed, errors) = (index.entries.clone(), index.errors.clone());
+        let excluded: Vec<&Path> = excluded_dirs
+            .iter()
+            .filter_map(|p| p.strip_prefix(&self.path).ok())
+            .collect();
+
+        let mut entries = HashMap::new();
+        let mut originals = HashMap::new();
+        let mut case_collisions = HashMap::new();
+        let mut pruned = Vec::new();
+        let mut depth_limited = Vec::new();
+        for (rel_path, meta) in listed {
+            if let Some(dir) = excluded.iter().find(|d| Path::new(&rel_path).starts_with(d)) {
+                if *dir == Path::new(&rel_path) {
+                    pruned.push(rel_path);
+                }
+                continue;
+            }
+            if ignore_rules.is_ignored(&rel_path)
+                || (options.skip_hidden && rel_path.split('/').any(scan::is_hidden))
+            {
+                continue;
+            }
+            let depth = rel_path.split('/').count();
+            match options.max_depth {
+                Some(max) if depth > max => continue,
+                Some(max) if depth == max && meta.kind == model::EntryKind::Dir => {
+                    depth_limited.push(rel_path.clone());
+                }
+                _ => {}
+            }
+            scan::insert_entry(
+                &mut entries,
+                &mut originals,
+                &mut case_collisions,
+                KeyMode::default().key(&rel_path),
+                rel_path,
+                meta,
+            );
+        }
+
+        let count = entries.len();
+        let result = ScanResult {
+            entries,
+            originals,
+            count,
+            errors,
+            excluded_dirs: pruned,
+            depth_limited,
+            // Entry names are stored exactly, whatever the archive was made on
+            case_sensitive: true,
+            case_collisions,
+            key_mode: KeyMode::default(),
+        };
+        progress_callback(&ScanProgress {
+            entries: count,
+            bytes: result.file_bytes(),
+            current_path: None,
+        });
+        Ok(result)
+    }
+
+    fn open(&self, rel_path: &str) -> io::Result<Box<dyn Read + Send>> {
+        let index = self
+            .index(&AtomicBool::new(false))
+            .map_err(|e| io::Error::other(e.to_string()))?;
+        let not_found = || {
+            io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("No file {} in {}", rel_path, self.path.display()),
+            )
+        };
+        let is_file = index
+            .entries
+            .get(rel_path)
+            .is_some_and(|meta| meta.kind == model::EntryKind::File);
+        let Some(&wanted) = index.positions.get(rel_path).filter(|_| is_file) else {
+            return Err(not_found());
+        };
+        // Entries before the wanted one are passed over, not read; reading stops at it
+        let mut position = 0;
+        let mut found = None;
+        read_entries(&self.path, self.format, &|_, _, _| {}, &mut |_, data| {
+            if position < wanted {
+                position += 1;
+                return Ok(true);
+            }
+            let mut bytes = Vec::new();
+            data.read_to_end(&mut bytes)
+                .map_err(|e| CommandError::io("Cannot read", &self.path, &e))?;
+            found = Some(bytes);
+            Ok(false)
+        })
+        .map_err(|e| io::Error::other(e.to_string()))?;
+        match found {
+            Some(bytes) => Ok(Box::new(Cursor::new(bytes))),
+            None => Err(not_found()),
+        }
+    }
+
+    fn stat(&self, rel_path: &str) -> io::Result<EntryMeta> {
+        if rel_path.is_empty() {
+            return Ok(folder_meta());
+        }
+        let index = self
+            .index(&AtomicBool::new(false))
+            .map_err(|e| io::Error::other(e.to_string()))?;
+        index
+            .entries
+            .get(rel_path)
+            .cloned()
+            .ok_or_else(|| io::Error::from(io::ErrorKind::NotFound))
+    }
+}
+
+/// Lists the archive at `path` with default scan options, keyed like a scanned
+/// folder, so it can be passed to [`crate::compare::compare`] as either side.
+pub fn scan_archive(path: &Path) -> Result<ScanResult, CommandError> {
+    let source = ArchiveSource::new(path)?;
+    let never = AtomicBool::new(false);
+    let rules = IgnoreRules::new(&[]);
+    Ok(source.scan(&rules, &[], &ScanOptions::default(), &never, &|_| {})?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compare::compare;
+    use crate::model::{CompareMode, DiffKind};
+    use crate::source::LocalSource;
+    use crate::verify;
+
+    fn test_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("sc_archive_{}", name));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn sample_tree(dir: &Path) -> Vec<PathBuf> {
+        fs::create_dir_all(dir.join("photos/2024")).unwrap();
+        fs::write(dir.join("photos/2024/a.jpg"), vec![7u8; 300_000]).unwrap();
+        fs::write(dir.join("notes.txt"), "hello").unwrap();
+        #[cfg(unix)]
+        std::os::unix::fs::symlink("notes.txt", dir.join("latest")).unwrap();
+        let mut sources = vec![dir.join("photos"), dir.join("notes.txt")];
+        if cfg!(unix) {
+            sources.push(dir.join("latest"));
+        }
+        sources
+    }
+
+    fn pack(sources: &[PathBuf], dest: &Path, format: ArchiveFormat) -> ArchiveReport {
+        let never = AtomicBool::new(false);
+        create_archive(sources, dest, format, ConflictPolicy::Fail, &never, &|_, _, _| {})
+            .unwrap()
+    }
+
+    #[test]
+    fn test_zip_keeps_structure_and_links() {
+        let dir = test_dir("zip");
+        let sources = sample_tree(&dir.join("src"));
+        let dest = dir.join("out.zip");
+
+        let report = pack(&sources, &dest, ArchiveFormat::Zip);
+        assert_eq!((report.files, report.dirs, report.bytes), (2, 2, 300_005));
+        assert!(report.archive_bytes > 0 && report.archive_bytes < 300_000);
+
+        let mut zip = zip::ZipArchive::new(File::open(&dest).unwrap()).unwrap();
+        let mut names: Vec<_> = zip.file_names().map(str::to_string).collect();
+        names.sort();
+        let mut expected = vec!["notes.txt", "photos/", "photos/2024/", "photos/2024/a.jpg"];
+        if cfg!(unix) {
+            expected.insert(0, "latest");
+        }
+        assert_eq!(names, expected);
+        let mut notes = String::new();
+        zip.by_name("notes.txt").unwrap().read_to_string(&mut notes).unwrap();
+        assert_eq!(notes, "hello");
+        #[cfg(unix)]
+        assert!(zip.by_name("latest").unwrap().is_symlink());
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_tar_gz_round_trip() {
+        let dir = test_dir("tar_gz");
+        let sources = sample_tree(&dir.join("src"));
+        let dest = dir.join("out.tar.gz");
+
+        let report = pack(&sources, &dest, ArchiveFormat::TarGz);
+        assert_eq!(report.files, 2);
+
+        let gz = flate2::read::GzDecoder::new(File::open(&dest).unwrap());
+        let mut entries = BTreeMap::new();
+        for entry in tar::Archive::new(gz).entries().unwrap() {
+            let mut entry = entry.unwrap();
+            let name = entry.path().unwrap().to_string_lossy().to_string();
+            let mut data = Vec::new();
+            entry.read_to_end(&mut data).unwrap();
+            entries.insert(name, (entry.header().entry_type(), data.len()));
+        }
+        assert_eq!(entries["photos/2024/a.jpg"], (tar::EntryType::Regular, 300_000));
+        assert_eq!(entries["notes.txt"].1, 5);
+        #[cfg(unix)]
+        assert_eq!(entries["latest"].0, tar::EntryType::Symlink);
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_existing_destination_follows_policy() {
+        let dir = test_dir("policy");
+        fs::write(dir.join("a.txt"), "a").unwrap();
+        fs::write(dir.join("out.zip"), "not a zip").unwrap();
+        let sources = [dir.join("a.txt")];
+        let never = AtomicBool::new(false);
+        let run = |policy| {
+            let dest = dir.join("out.zip");
+            create_archive(&sources, &dest, ArchiveFormat::Zip, policy, &never, &|_, _, _| {})
+        };
+
+        assert_eq!(run(ConflictPolicy::Fail).unwrap_err().code(), "alreadyExists");
+        assert_eq!(run(ConflictPolicy::Rename).unwrap().dest, dir.join("out (1).zip"));
+        run(ConflictPolicy::Overwrite).unwrap();
+        assert!(zip::ZipArchive::new(File::open(dir.join("out.zip")).unwrap()).is_ok());
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_cancel_leaves_no_archive() {
+        let dir = test_dir("cancel");
+        fs::write(dir.join("big.bin"), vec![1u8; 2 * 1024 * 1024]).unwrap();
+        let dest = dir.join("out.zip");
+        let cancel = AtomicBool::new(false);
+
+        let result = create_archive(
+            &[dir.join("big.bin")],
+            &dest,
+            ArchiveFormat::Zip,
+            ConflictPolicy::Fail,
+            &cancel,
+            &|_, _, _| cancel.store(true, Ordering::Relaxed),
+        );
+        assert_eq!(result.unwrap_err().code(), "cancelled");
+        assert!(!dest.exists());
+        assert!(!fileops::atomic_temp_path(&dest).exists());
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_archive_inside_source_skips_itself() {
+        let dir = test_dir("into_self");
+        fs::create_dir_all(dir.join("src")).unwrap();
+        fs::write(dir.join("src/a.txt"), "a").unwrap();
+        let never = AtomicBool::new(false);
+
+        for format in [ArchiveFormat::Zip, ArchiveFormat::TarGz] {
+            let dest = dir.join("src/out");
+            // Overwriting an earlier archive in the same place skips that one too
+            fs::write(&dest, "old").unwrap();
+            let policy = ConflictPolicy::Overwrite;
+            let sources = [dir.join("src")];
+            let report =
+                create_archive(&sources, &dest, format, policy, &never, &|_, _, _| {}).unwrap();
+            assert_eq!((report.files, report.bytes), (1, 1), "{:?}", format);
+        }
+
+        // It cannot replace an entry it is made of
+        let file = dir.join("src/a.txt");
+        let err = create_archive(
+            std::slice::from_ref(&file),
+            &file,
+            ArchiveFormat::Zip,
+            ConflictPolicy::Overwrite,
+            &never,
+            &|_, _, _| {},
+        )
+        .unwrap_err();
+        assert_eq!(err.code(), "invalidArgument");
+        assert_eq!(fs::read_to_string(&file).unwrap(), "a");
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_tar_entry_of_shrunk_file_fails() {
+        let dir = test_dir("tar_shrunk");
+        fs::write(dir.join("a.txt"), "0123456789").unwrap();
+        let meta = fs::metadata(dir.join("a.txt")).unwrap();
+
+        let mut sink = TarSink(tar::Builder::new(Vec::new()));
+        let err = sink.add_file("a.txt", &meta, &mut &b"0123"[..]).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+        assert!(sink.add_file("a.txt", &meta, &mut &b"0123456789"[..]).is_ok());
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_common_base_and_nested_sources() {
+        let sources = [
+            PathBuf::from("/data/a/x.txt"),
+            PathBuf::from("/data/b"),
+            PathBuf::from("/data/b/inner"),
+        ];
+        let kept = outermost(&sources);
+        assert_eq!(kept, [PathBuf::from("/data/a/x.txt"), PathBuf::from("/data/b")]);
+        assert_eq!(common_base(&kept), PathBuf::from("/data"));
+    }
+
+    fn unpack(archive: &Path, dest: &Path, policy: ConflictPolicy) -> ExtractReport {
+        fs::create_dir_all(dest).unwrap();
+        let never = AtomicBool::new(false);
+        extract_archive(archive, dest, policy, &never, &|_, _, _| {}).unwrap()
+    }
+
+    fn rejected_names(report: &ExtractReport) -> Vec<&str> {
+        report.rejected.iter().map(|r| r.path.as_str()).collect()
+    }
+
+    #[test]
+    fn test_extract_round_trip_by_content() {
+        let dir = test_dir("extract_round_trip");
+        let sources = sample_tree(&dir.join("src"));
+        for format in [ArchiveFormat::Zip, ArchiveFormat::TarGz] {
+            // The name says nothing about the format
+            let archive = dir.join(format!("{:?}.bin", format));
+            pack(&sources, &archive, format);
+            let out = dir.join(format!("{:?}", format));
+
+            let report = unpack(&archive, &out, ConflictPolicy::Fail);
+            assert!(report.rejected.is_empty() && report.skipped.is_empty(), "{:?}", report);
+            assert_eq!(report.extracted, if cfg!(unix) { 5 } else { 4 });
+            assert_eq!(fs::read(out.join("photos/2024/a.jpg")).unwrap(), vec![7u8; 300_000]);
+            #[cfg(unix)]
+            assert_eq!(fs::read_link(out.join("latest")).unwrap(), Path::new("notes.txt"));
+        }
+        let err = extract_archive(
+            &dir.join("src/notes.txt"),
+            &dir,
+            ConflictPolicy::Fail,
+            &AtomicBool::new(false),
+            &|_, _, _| {},
+        )
+        .unwrap_err();
+        assert_eq!(err.code(), "invalidArgument");
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_zip_slip_entries_rejected() {
+        let dir = test_dir("zip_slip");
+        let archive = dir.join("evil.zip");
+        let mut zip = ZipWriter::new(File::create(&archive).unwrap());
+        let options = SimpleFileOptions::default();
+        for name in ["../../evil.txt", "/abs.txt", "good/../../x.txt", "C:/win.txt", "good/a.txt"] {
+            zip.start_file(name, options).unwrap();
+            zip.write_all(b"payload").unwrap();
+        }
+        zip.add_symlink("good/escape", "../../outside", options).unwrap();
+        zip.add_symlink("good/sibling", "a.txt", options).unwrap();
+        zip.finish().unwrap();
+
+        let dest = dir.join("out/dest");
+        let report = unpack(&archive, &dest, ConflictPolicy::Fail);
+        assert_eq!(
+            rejected_names(&report),
+            ["../../evil.txt", "/abs.txt", "good/../../x.txt", "C:/win.txt", "good/escape"]
+        );
+        assert_eq!(fs::read_to_string(dest.join("good/a.txt")).unwrap(), "payload");
+        assert!(!dir.join("evil.txt").exists() && !dir.join("out/evil.txt").exists());
+        assert!(fs::symlink_metadata(dest.join("good/escape")).is_err());
+        // good/a.txt and the link to it; their folder is made on the way
+        #[cfg(unix)]
+        assert_eq!(report.extracted, 2);
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_tar_slip_entries_rejected() {
+        let dir = test_dir("tar_slip");
+        let archive = dir.join("evil.tar.gz");
+        let gz = GzEncoder::new(File::create(&archive).unwrap(), Compression::default());
+        let mut builder = tar::Builder::new(gz);
+        // The tar writer refuses `..` itself, so the names go straight into the header
+        for name in ["../evil.txt", "ok.txt"] {
+            let mut header = tar::Header::new_old();
+            header.as_old_mut().name[..name.len()].copy_from_slice(name.as_bytes());
+            header.set_size(4);
+            header.set_mode(0o644);
+            header.set_cksum();
+            builder.append(&header, &b"data"[..]).unwrap();
+        }
+        builder.into_inner().unwrap().finish().unwrap();
+
+        let dest = dir.join("dest");
+        let report = unpack(&archive, &dest, ConflictPolicy::Fail);
+        assert_eq!(rejected_names(&report), ["../evil.txt"]);
+        assert_eq!(report.extracted, 1);
+        assert!(!dir.join("evil.txt").exists());
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_extract_through_existing_link_rejected() {
+        let dir = test_dir("extract_via_link");
+        fs::create_dir_all(dir.join("outside")).unwrap();
+        fs::create_dir_all(dir.join("dest")).unwrap();
+        std::os::unix::fs::symlink(dir.join("outside"), dir.join("dest/out")).unwrap();
+        let archive = dir.join("a.zip");
+        let mut zip = ZipWriter::new(File::create(&archive).unwrap());
+        zip.start_file("out/pwned.txt", SimpleFileOptions::default()).unwrap();
+        zip.write_all(b"x").unwrap();
+        zip.finish().unwrap();
+
+        let report = unpack(&archive, &dir.join("dest"), ConflictPolicy::Overwrite);
+        assert_eq!(rejected_names(&report), ["out/pwned.txt"]);
+        assert!(!dir.join("outside/pwned.txt").exists());
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_chained_links_rejected() {
+        let dir = test_dir("chained_links");
+        let options = SimpleFileOptions::default();
+        // Each link stays inside by name, but a/b leads to the destination itself, so
+        // a/b/.. would be the folder above it
+        for (name, links) in [
+            ("link_first.zip", [("a/b", ".."), ("up", "a/b/..")]),
+            ("link_last.zip", [("up", "a/b/.."), ("a/b", "..")]),
+        ] {
+            let archive = dir.join(name);
+            let mut zip = ZipWriter::new(File::create(&archive).unwrap());
+            zip.add_directory("a", options).unwrap();
+            for (link, target) in links {
+                zip.add_symlink(link, target, options).unwrap();
+            }
+            zip.finish().unwrap();
+
+            let dest = dir.join(name).with_extension("out");
+            let report = unpack(&archive, &dest, ConflictPolicy::Fail);
+            assert_eq!(report.rejected.len(), 1, "{} {:?}", name, report);
+            assert_eq!(report.rejected[0].path, links[1].0);
+            assert!(fs::symlink_metadata(dest.join(links[1].0)).is_err());
+        }
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_extract_conflict_policy() {
+        let dir = test_dir("extract_conflict");
+        fs::write(dir.join("a.txt"), "new").unwrap();
+        let archive = dir.join("a.zip");
+        pack(&[dir.join("a.txt")], &archive, ArchiveFormat::Zip);
+        let dest = dir.join("dest");
+        fs::create_dir_all(&dest).unwrap();
+        fs::write(dest.join("a.txt"), "old").unwrap();
+
+        let report = unpack(&archive, &dest, ConflictPolicy::Fail);
+        assert_eq!((report.extracted, report.skipped.len()), (0, 1));
+        unpack(&archive, &dest, ConflictPolicy::Rename);
+        assert_eq!(fs::read_to_string(dest.join("a (1).txt")).unwrap(), "new");
+        assert_eq!(fs::read_to_string(dest.join("a.txt")).unwrap(), "old");
+        unpack(&archive, &dest, ConflictPolicy::Overwrite);
+        assert_eq!(fs::read_to_string(dest.join("a.txt")).unwrap(), "new");
+
+        // With every numbered name taken, the entry is skipped rather than the
+        // extraction failing
+        for n in 2..=fileops::MAX_NUMBERED_NAMES {
+            fs::write(dest.join(format!("a ({}).txt", n)), "taken").unwrap();
+        }
+        let report = unpack(&archive, &dest, ConflictPolicy::Rename);
+        assert_eq!((report.extracted, report.skipped.len()), (0, 1));
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    fn diff_kinds(result: &crate::compare::CompareResult) -> BTreeMap<&str, DiffKind> {
+        result.diffs.iter().map(|d| (d.rel_path.as_str(), d.diff_kind)).collect()
+    }
+
+    #[test]
+    fn test_folder_compares_against_its_archive() {
+        let dir = test_dir("compare_folder");
+        let src = dir.join("src");
+        let sources = sample_tree(&src);
+        let never = AtomicBool::new(false);
+        let rules = IgnoreRules::new(&[]);
+        for format in [ArchiveFormat::Zip, ArchiveFormat::TarGz] {
+            let archive = dir.join(format!("{:?}.bin", format));
+            pack(&sources, &archive, format);
+            let folder = LocalSource::new(&src)
+                .scan(&rules, &[], &ScanOptions::default(), &never, &|_| {})
+                .unwrap();
+            let packed = scan_archive(&archive).unwrap();
+            assert_eq!(packed.count, folder.count);
+            assert_eq!(packed.originals["photos/2024/a.jpg"], "photos/2024/a.jpg");
+
+            for mode in [CompareMode::Structure, CompareMode::Smart] {
+                let result = compare(&folder, &packed, mode, &never).unwrap();
+                assert!(
+                    result.diffs.iter().all(|d| d.diff_kind == DiffKind::Same),
+                    "{:?} {:?}",
+                    mode,
+                    diff_kinds(&result)
+                );
+            }
+
+            // Contents are read through the archive
+            let (folder_source, source) =
+                (LocalSource::new(&src), ArchiveSource::new(&archive).unwrap());
+            let verified =
+                verify::verify_trees(&folder_source, &source, &rules, 10, &never, &|_, _| {})
+                    .unwrap();
+            assert!(verified.passed && verified.files_compared == 2, "{:?}", verified);
+        }
+
+        fs::write(src.join("notes.txt"), "hello again").unwrap();
+        fs::write(src.join("extra.txt"), "x").unwrap();
+        let folder = LocalSource::new(&src)
+            .scan(&rules, &[], &ScanOptions::default(), &never, &|_| {})
+            .unwrap();
+        let packed = scan_archive(&dir.join("Zip.bin")).unwrap();
+        let smart = compare(&folder, &packed, CompareMode::Smart, &never).unwrap();
+        let kinds = diff_kinds(&smart);
+        assert_eq!(kinds["notes.txt"], DiffKind::MetaDiff);
+        assert_eq!(kinds["extra.txt"], DiffKind::OnlyLeft);
+        assert_eq!(kinds["photos/2024"], DiffKind::Same);
+        let structure = compare(&folder, &packed, CompareMode::Structure, &never).unwrap();
+        assert_eq!(diff_kinds(&structure)["notes.txt"], DiffKind::Same);
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_archive_listing_implies_folders_and_flags_bad_names() {
+        let dir = test_dir("listing");
+        let archive = dir.join("a.zip");
+        let mut zip = ZipWriter::new(File::create(&archive).unwrap());
+        for name in ["./deep/er/File.TXT", "../up.txt", "deep/er/File.TXT"] {
+            zip.start_file(name, SimpleFileOptions::default()).unwrap();
+            zip.write_all(name.as_bytes()).unwrap();
+        }
+        zip.finish().unwrap();
+
+        let scan = scan_archive(&archive).unwrap();
+        let mut keys: Vec<_> = scan.entries.keys().map(String::as_str).collect();
+        keys.sort();
+        assert_eq!(keys, ["deep", "deep/er", "deep/er/file.txt"]);
+        assert_eq!(scan.originals["deep/er/file.txt"], "deep/er/File.TXT");
+        // The later copy of a repeated name wins
+        assert_eq!(scan.entries["deep/er/file.txt"].size, 16);
+        assert_eq!(scan.errors.len(), 1);
+        assert_eq!(scan.errors[0].path, "../up.txt");
+
+        let source = ArchiveSource::new(&archive).unwrap();
+        let mut data = String::new();
+        source.open("deep/er/File.TXT").unwrap().read_to_string(&mut data).unwrap();
+        assert_eq!(data, "deep/er/File.TXT");
+        assert_eq!(source.stat("deep").unwrap().kind, model::EntryKind::Dir);
+        assert_eq!(source.open("deep").err().unwrap().kind(), io::ErrorKind::NotFound);
+        assert!(ArchiveSource::new(dir.join("missing.zip")).is_err());
+
+        // The listing is read once per source, not on every stat
+        fs::write(&archive, b"no longer a zip").unwrap();
+        assert_eq!(source.stat("deep/er/File.TXT").unwrap().size, 16);
+        let never = AtomicBool::new(false);
+        let options = ScanOptions::default();
+        let rescan = source
+            .scan(&IgnoreRules::new(&[]), &[], &options, &never, &|_| {})
+            .unwrap();
+        assert_eq!(rescan.count, 3);
+        assert!(ArchiveSource::new(&archive).is_err());
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_link_targets_judged_by_name() {
+        assert_eq!(link_walk("a/b/link", "../c").unwrap(), ["a/c"]);
+        assert_eq!(link_walk("a/link", "./b/../c").unwrap(), ["a/b", "a/c"]);
+        assert!(link_walk("a/link", "../../c").is_none());
+        assert!(link_walk("link", "/etc/passwd").is_none());
+        assert_eq!(entry_rel_path("./a//b/").unwrap(), Some("a/b".to_string()));
+        assert_eq!(entry_rel_path("./").unwrap(), None);
+    }
+}
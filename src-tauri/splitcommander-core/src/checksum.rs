@@ -0,0 +1,252 @@
+//! Checksums of selected files, streamed in chunks so file size does not matter.
+
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use xxhash_rust::xxh3::Xxh3;
+
+use crate::error::CommandError;
+use crate::scan::ScanError;
+
+const CHUNK_SIZE: usize = 1024 * 1024;
+
+/// Files at least this large report progress while they are hashed.
+pub const PROGRESS_MIN_SIZE: u64 = 4 * 1024 * 1024;
+
+/// Least time between progress reports for one file.
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(100);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum HashAlgorithm {
+    Sha256,
+    /// 64-bit XXH3; fast, but not for detecting tampering
+    Xxh3,
+}
+
+impl HashAlgorithm {
+    /// Parses `"sha256"` or `"xxh3"`, in any case.
+    pub fn parse(name: &str) -> Result<Self, CommandError> {
+        match name.to_ascii_lowercase().as_str() {
+            "sha256" | "sha-256" => Ok(Self::Sha256),
+            "xxh3" => Ok(Self::Xxh3),
+            _ => Err(CommandError::invalid_argument(format!(
+                "Unknown checksum algorithm: {} (expected sha256 or xxh3)",
+                name
+            ))),
+        }
+    }
+}
+
+enum Hasher {
+    Sha256(Sha256),
+    Xxh3(Box<Xxh3>),
+}
+
+impl Hasher {
+    fn new(algorithm: HashAlgorithm) -> Self {
+        match algorithm {
+            HashAlgorithm::Sha256 => Self::Sha256(Sha256::new()),
+            HashAlgorithm::Xxh3 => Self::Xxh3(Box::new(Xxh3::new())),
+        }
+    }
+
+    fn update(&mut self, bytes: &[u8]) {
+        match self {
+            Self::Sha256(h) => h.update(bytes),
+            Self::Xxh3(h) => h.update(bytes),
+        }
+    }
+
+    fn hex(self) -> String {
+        match self {
+            Self::Sha256(h) => h.finalize().iter().map(|b| format!("{:02x}", b)).collect(),
+            Self::Xxh3(h) => format!("{:016x}", h.digest()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FileChecksum {
+    /// As passed in
+    pub path: String,
+    /// Lowercase hex digest
+    pub hex: String,
+    /// Bytes hashed
+    pub bytes: u64,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ChecksumReport {
+    pub checksums: Vec<FileChecksum>,
+    /// Paths that could not be hashed, including directories
+    pub errors: Vec<ScanError>,
+}
+
+/// Hashes each of `paths` with `algorithm`, in order. A path that is a directory or
+/// cannot be read goes to the report's `errors` and the rest are still hashed;
+/// directories are rejected rather than hashed as trees.
+///
+/// - `progress`: called with the path, bytes hashed and file size while hashing files
+///   of at least [`PROGRESS_MIN_SIZE`], at most every 100 ms
+pub fn compute_checksums(
+    paths: &[String],
+    algorithm: HashAlgorithm,
+    cancel_flag: &AtomicBool,
+    progress: &dyn Fn(&str, u64, u64),
+) -> Result<ChecksumReport, String> {
+    let mut report = ChecksumReport::default();
+    for path in paths {
+        match hash_file(Path::new(path), algorithm, cancel_flag, &|done, total| {
+            progress(path, done, total)
+        }) {
+            Ok((hex, bytes)) => report.checksums.push(FileChecksum {
+                path: path.clone(),
+                hex,
+                bytes,
+            }),
+            Err(_) if cancel_flag.load(Ordering::Relaxed) => {
+                return Err("Checksum cancelled".to_string());
+            }
+            Err(e) => report.errors.push(ScanError {
+                path: path.clone(),
+                message: e.to_string(),
+            }),
+        }
+    }
+    Ok(report)
+}
+
+pub(crate) fn hash_file(
+    path: &Path,
+    algorithm: HashAlgorithm,
+    cancel_flag: &AtomicBool,
+    progress: &dyn Fn(u64, u64),
+) -> io::Result<(String, u64)> {
+    // Checked before opening: opening a FIFO blocks until something writes to it. A
+    // link is followed on purpose, so the check is made on what it points at.
+    let mut meta = std::fs::symlink_metadata(path)?;
+    if meta.file_type().is_symlink() {
+        meta = std::fs::metadata(path)?;
+    }
+    if meta.is_dir() {
+        return Err(io::Error::other(
+            "Is a directory; checksums are computed for files only",
+        ));
+    }
+    if !meta.is_file() {
+        return Err(io::Error::other(
+            "Not a regular file; checksums are computed for files only",
+        ));
+    }
+    let mut file = File::open(path)?;
+    let size = meta.len();
+
+    let mut hasher = Hasher::new(algorithm);
+    let mut buf = vec![0u8; CHUNK_SIZE];
+    let mut hashed: u64 = 0;
+    let mut reported_at: Option<Instant> = None;
+    loop {
+        if cancel_flag.load(Ordering::Relaxed) {
+            return Err(io::Error::new(io::ErrorKind::Interrupted, "cancelled"));
+        }
+        let n = match file.read(&mut buf) {
+            Ok(0) => break,
+            Ok(n) => n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        };
+        hasher.update(&buf[..n]);
+        hashed += n as u64;
+        if size >= PROGRESS_MIN_SIZE
+            && reported_at.is_none_or(|at| at.elapsed() >= PROGRESS_INTERVAL)
+        {
+            reported_at = Some(Instant::now());
+            progress(hashed, size);
+        }
+    }
+    Ok((hasher.hex(), hashed))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn checksums(paths: &[String], algorithm: &str) -> ChecksumReport {
+        let algorithm = HashAlgorithm::parse(algorithm).unwrap();
+        compute_checksums(paths, algorithm, &AtomicBool::new(false), &|_, _, _| {}).unwrap()
+    }
+
+    #[test]
+    fn test_known_digests() {
+        let path = std::env::temp_dir().join("sc_checksum_abc.txt");
+        std::fs::write(&path, b"abc").unwrap();
+        let paths = [path.to_string_lossy().to_string()];
+        let sha = checksums(&paths, "SHA256");
+        let xxh = checksums(&paths, "xxh3");
+        let _ = std::fs::remove_file(&path);
+
+        assert_eq!(
+            sha.checksums[0].hex,
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+        assert_eq!(sha.checksums[0].bytes, 3);
+        assert_eq!(xxh.checksums[0].hex, "78af5f94892f3950");
+    }
+
+    #[test]
+    fn test_directories_and_missing_files_reported() {
+        let dir = std::env::temp_dir().join("sc_checksum_dir");
+        std::fs::create_dir_all(&dir).unwrap();
+        let paths = [
+            dir.to_string_lossy().to_string(),
+            dir.join("missing.bin").to_string_lossy().to_string(),
+        ];
+        let report = checksums(&paths, "sha256");
+        let _ = std::fs::remove_dir_all(&dir);
+
+        assert!(report.checksums.is_empty());
+        assert_eq!(report.errors.len(), 2);
+        assert!(report.errors[0].message.starts_with("Is a directory"));
+    }
+
+    #[cfg(any(target_os = "linux", target_os = "macos"))]
+    #[test]
+    fn test_fifo_rejected_without_blocking() {
+        let dir = std::env::temp_dir().join("sc_checksum_fifo");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let fifo = dir.join("pipe");
+        let c_path = std::ffi::CString::new(fifo.to_string_lossy().as_bytes()).unwrap();
+        assert_eq!(unsafe { libc::mkfifo(c_path.as_ptr(), 0o600) }, 0);
+        let report = checksums(&[fifo.to_string_lossy().to_string()], "xxh3");
+        let _ = std::fs::remove_dir_all(&dir);
+
+        assert!(report.checksums.is_empty());
+        assert!(report.errors[0].message.starts_with("Not a regular file"));
+    }
+
+    #[test]
+    fn test_unknown_algorithm_rejected() {
+        assert_eq!(HashAlgorithm::parse("md5").unwrap_err().code(), "invalidArgument");
+    }
+
+    #[test]
+    fn test_cancelled() {
+        let path = std::env::temp_dir().join("sc_checksum_cancel.txt");
+        std::fs::write(&path, b"abc").unwrap();
+        let paths = [path.to_string_lossy().to_string()];
+        let cancel = AtomicBool::new(true);
+        let result = compute_checksums(&paths, HashAlgorithm::Sha256, &cancel, &|_, _, _| {});
+        let _ = std::fs::remove_file(&path);
+
+        assert_eq!(result.unwrap_err(), "Checksum cancelled");
+    }
+}
@@ -0,0 +1,1085 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use serde::Serialize;
+
+use crate::model::*;
+use crate::scan::ScanResult;
+
+pub struct CompareResult {
+    pub diffs: Vec<DiffItem>,
+    pub summary: CompareSummary,
+}
+
+/// One page of a diff list, plus how many entries matched the filter overall.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DiffPage {
+    pub items: Vec<DiffItem>,
+    pub total: usize,
+}
+
+/// Slices `diffs` (already in path order) to `offset..offset + limit` after filtering.
+///
+/// - `kinds`: keep only these kinds; `None` keeps all
+/// - `path_filter`: keep paths containing this substring, ignoring case
+/// - `limit`: `None` returns everything from `offset` on
+pub fn page_diffs(
+    diffs: &[DiffItem],
+    offset: usize,
+    limit: Option<usize>,
+    kinds: Option<&[DiffKind]>,
+    path_filter: Option<&str>,
+) -> DiffPage {
+    let needle = path_filter
+        .filter(|f| !f.is_empty())
+        .map(|f| f.to_lowercase());
+    let matches = |d: &&DiffItem| {
+        kinds.is_none_or(|k| k.contains(&d.diff_kind))
+            && needle
+                .as_ref()
+                .is_none_or(|n| d.rel_path.to_lowercase().contains(n.as_str()))
+    };
+
+    let mut total = 0;
+    let mut items = Vec::new();
+    let end = limit.map_or(usize::MAX, |l| offset.saturating_add(l));
+    for diff in diffs.iter().filter(matches) {
+        if total >= offset && total < end {
+            items.push(diff.clone());
+        }
+        total += 1;
+    }
+    DiffPage { items, total }
+}
+
+/// Tunables for [`compare_with_options`].
+#[derive(Debug, Clone)]
+pub struct CompareOptions {
+    /// Modification times this close (in ms) count as equal. FAT/exFAT store times with
+    /// 2 s granularity, so copies onto them would otherwise look newer or older.
+    pub mtime_tolerance_ms: u64,
+    /// Keep Same entries in the diff list. When off they are only counted in the
+    /// summary, which saves two cloned `EntryMeta`s per identical file.
+    pub include_same: bool,
+}
+
+impl Default for CompareOptions {
+    fn default() -> Self {
+        Self {
+            mtime_tolerance_ms: 2000,
+            include_same: true,
+        }
+    }
+}
+
+/// Compares two scan results with default options, producing a diff list and summary.
+pub fn compare(
+    left: &ScanResult,
+    right: &ScanResult,
+    mode: CompareMode,
+    cancel_flag: &AtomicBool,
+) -> Result<CompareResult, String> {
+    compare_with_options(left, right, mode, &CompareOptions::default(), cancel_flag)
+}
+
+/// Compares two scan results, producing a diff list and summary.
+pub fn compare_with_options(
+    left: &ScanResult,
+    right: &ScanResult,
+    mode: CompareMode,
+    options: &CompareOptions,
+    cancel_flag: &AtomicBool,
+) -> Result<CompareResult, String> {
+    let mut diffs = Vec::new();
+    let mut summary = CompareSummary {
+        total_left: left.entries.len() + hidden_variants(left),
+        total_right: right.entries.len() + hidden_variants(right),
+        excluded_by_self: left.excluded_dirs.len() + right.excluded_dirs.len(),
+        depth_limited: depth_limited(left, right),
+        left_case_sensitive: Some(left.case_sensitive),
+        right_case_sensitive: Some(right.case_sensitive),
+        ..Default::default()
+    };
+    if left.key_mode != right.key_mode {
+        return Err("Cannot compare scans keyed with different matching rules".to_string());
+    }
+    if left.case_sensitive != right.case_sensitive && !left.key_mode.case_sensitive {
+        let (sensitive, insensitive) = if left.case_sensitive {
+            ("left", "right")
+        } else {
+            ("right", "left")
+        };
+        summary.warnings.push(format!(
+            "The {} root is case-sensitive but the {} root is not; names that differ only by case are reported as case collisions",
+            sensitive, insensitive
+        ));
+    }
+    if summary.depth_limited > 0 {
+        summary.warnings.push(format!(
+            "{} directories lie at the depth limit; their contents were not compared",
+            summary.depth_limited
+        ));
+    }
+
+    // Paths that could not be read on either side: key → (path, "Side: message" list)
+    let mut scan_errors: HashMap<String, (String, Vec<String>)> = HashMap::new();
+    for (side, scan) in [("Left", left), ("Right", right)] {
+        for error in &scan.errors {
+            scan_errors
+                .entry(scan.key_mode.key(&error.path))
+                .or_insert_with(|| (error.path.clone(), Vec::new()))
+                .1
+                .push(format!("{}: {}", side, error.message));
+        }
+    }
+
+    let all_keys: HashSet<&String> = left.entries.keys().chain(right.entries.keys()).collect();
+
+    for key in &all_keys {
+        if cancel_flag.load(Ordering::Relaxed) {
+            return Err("Compare cancelled".to_string());
+        }
+
+        if let Some((path, messages)) = scan_errors.remove(*key) {
+            let original_path = left.originals.get(*key).cloned().unwrap_or(path);
+            diffs.push(error_item(
+                original_path,
+                left.entries.get(*key).cloned(),
+                right.entries.get(*key).cloned(),
+                messages,
+                &mut summary,
+            ));
+            continue;
+        }
+
+        if left.case_collisions.contains_key(*key) || right.case_collisions.contains_key(*key) {
+            compare_case_variants(key, left, right, mode, options, &mut summary, &mut diffs);
+            continue;
+        }
+
+        let left_entry = left.entries.get(*key);
+        let right_entry = right.entries.get(*key);
+
+        // Prefer left original path, fall back to right
+        let original_path = left
+            .originals
+            .get(*key)
+            .or_else(|| right.originals.get(*key))
+            .cloned()
+            .unwrap_or_else(|| key.to_string());
+
+        let diff = match (left_entry, right_entry) {
+            (Some(l), None) => {
+                summary.only_left += 1;
+                DiffItem {
+                    rel_path: original_path,
+                    diff_kind: DiffKind::OnlyLeft,
+                    left: Some(l.clone()),
+                    right: None,
+                    error_message: None,
+                    newer: None,
+                }
+            }
+            (None, Some(r)) => {
+                summary.only_right += 1;
+                DiffItem {
+                    rel_path: original_path,
+                    diff_kind: DiffKind::OnlyRight,
+                    left: None,
+                    right: Some(r.clone()),
+                    error_message: None,
+                    newer: None,
+                }
+            }
+            (Some(l), Some(r)) => classify_pair(&original_path, l, r, mode, options, &mut summary),
+            (None, None) => unreachable!(),
+        };
+
+        if options.include_same || diff.diff_kind != DiffKind::Same {
+            diffs.push(diff);
+        }
+    }
+
+    // Errors for paths that never made it into a listing (e.g. unreadable directories)
+    for (path, messages) in scan_errors.into_values() {
+        diffs.push(error_item(path, None, None, messages, &mut summary));
+    }
+
+    // Sort diffs by path for consistent output
+    diffs.sort_by_key(|d| d.rel_path.to_lowercase());
+    summary.count_bytes(&diffs);
+
+    Ok(CompareResult { diffs, summary })
+}
+
+fn error_item(
+    rel_path: String,
+    left: Option<EntryMeta>,
+    right: Option<EntryMeta>,
+    messages: Vec<String>,
+    summary: &mut CompareSummary,
+) -> DiffItem {
+    summary.errors += 1;
+    DiffItem {
+        rel_path,
+        diff_kind: DiffKind::Error,
+        left,
+        right,
+        error_message: Some(messages.join("; ")),
+        newer: None,
+    }
+}
+
+/// Directories cut off by the depth limit on either side, each counted once.
+fn depth_limited(left: &ScanResult, right: &ScanResult) -> usize {
+    [left, right]
+        .iter()
+        .flat_map(|scan| scan.depth_limited.iter().map(|path| scan.key_mode.key(path)))
+        .collect::<HashSet<_>>()
+        .len()
+}
+
+/// Variants beyond the first for every colliding key; `entries` only counts one.
+fn hidden_variants(scan: &ScanResult) -> usize {
+    scan.case_collisions.values().map(|v| v.len() - 1).sum()
+}
+
+/// All (original path, metadata) entries a side has under `key`.
+fn case_variants(scan: &ScanResult, key: &str) -> Vec<(String, EntryMeta)> {
+    if let Some(variants) = scan.case_collisions.get(key) {
+        return variants.clone();
+    }
+    match (scan.originals.get(key), scan.entries.get(key)) {
+        (Some(original), Some(meta)) => vec![(original.clone(), meta.clone())],
+        _ => Vec::new(),
+    }
+}
+
+/// Pairs up entries whose names differ only by case. Exact-name matches are compared
+/// normally. A leftover variant is a case collision when the other side holds another
+/// spelling of it (under case-insensitive matching they name the same entry, so copying
+/// it across would clobber that one), and one-sided otherwise. No variant is dropped.
+fn compare_case_variants(
+    key: &str,
+    left: &ScanResult,
+    right: &ScanResult,
+    mode: CompareMode,
+    options: &CompareOptions,
+    summary: &mut CompareSummary,
+    diffs: &mut Vec<DiffItem>,
+) {
+    let left_variants = case_variants(left, key);
+    let mut right_variants = case_variants(right, key);
+    let mut left_unmatched = Vec::new();
+
+    for (path, meta) in left_variants {
+        match right_variants.iter().position(|(p, _)| *p == path) {
+            Some(i) => {
+                let (_, right_meta) = right_variants.remove(i);
+                let diff = classify_pair(&path, &meta, &right_meta, mode, options, summary);
+                if options.include_same || diff.diff_kind != DiffKind::Same {
+                    diffs.push(diff);
+                }
+            }
+            None => left_unmatched.push((path, meta)),
+        }
+    }
+
+    let others_left = right.entries.contains_key(key);
+    let others_right = left.entries.contains_key(key);
+    for (path, meta) in left_unmatched {
+        diffs.push(unmatched_variant(path, Some(meta), None, others_left, summary));
+    }
+    for (path, meta) in right_variants {
+        diffs.push(unmatched_variant(path, None, Some(meta), others_right, summary));
+    }
+}
+
+fn unmatched_variant(
+    rel_path: String,
+    left: Option<EntryMeta>,
+    right: Option<EntryMeta>,
+    collides: bool,
+    summary: &mut CompareSummary,
+) -> DiffItem {
+    let (diff_kind, error_message) = if collides {
+        summary.case_collisions += 1;
+        let message =
+            "Another entry on the opposite side has the same name in a different case or Unicode form";
+        (DiffKind::CaseCollision, Some(message.to_string()))
+    } else if left.is_some() {
+        summary.only_left += 1;
+        (DiffKind::OnlyLeft, None)
+    } else {
+        summary.only_right += 1;
+        (DiffKind::OnlyRight, None)
+    };
+    DiffItem {
+        rel_path,
+        diff_kind,
+        left,
+        right,
+        error_message,
+        newer: None,
+    }
+}
+
+fn classify_pair(
+    rel_path: &str,
+    left: &EntryMeta,
+    right: &EntryMeta,
+    mode: CompareMode,
+    options: &CompareOptions,
+    summary: &mut CompareSummary,
+) -> DiffItem {
+    let item = |diff_kind, newer| DiffItem {
+        rel_path: rel_path.to_string(),
+        diff_kind,
+        left: Some(left.clone()),
+        right: Some(right.clone()),
+        error_message: None,
+        newer,
+    };
+
+    // Type mismatch (applies in all modes)
+    if left.kind != right.kind {
+        summary.type_mismatch += 1;
+        return item(DiffKind::TypeMismatch, None);
+    }
+
+    // Structure mode: same kind = same. Directories are always Same in the other
+    // modes too, since their size/mtime are not meaningful.
+    if mode == CompareMode::Structure || left.kind == EntryKind::Dir {
+        summary.same += 1;
+        return item(DiffKind::Same, None);
+    }
+    // Fifos, sockets and devices have no contents to compare; saying Same would claim
+    // more than is known
+    if left.kind.is_special() {
+        summary.special += 1;
+        return item(DiffKind::Special, None);
+    }
+
+    let newer = newer_side(left, right, options.mtime_tolerance_ms);
+    let differs = left.size != right.size
+        || left.symlink_target != right.symlink_target
+        // Timestamp mode: a file touched on one side counts as changed. Symlink times
+        // are not carried by most copy tools, so links are judged by target alone.
+        || (mode == CompareMode::Timestamp && left.kind == EntryKind::File && newer.is_some());
+
+    if !differs {
+        summary.same += 1;
+        return item(DiffKind::Same, None);
+    }
+    summary.meta_diff += 1;
+    match newer {
+        Some(Side::Left) => summary.left_newer += 1,
+        Some(Side::Right) => summary.right_newer += 1,
+        None => {}
+    }
+    item(DiffKind::MetaDiff, newer)
+}
+
+/// Which side was modified later, if the times differ by more than `tolerance_ms`.
+fn newer_side(left: &EntryMeta, right: &EntryMeta, tolerance_ms: u64) -> Option<Side> {
+    let (l, r) = (left.modified?, right.modified?);
+    if l.abs_diff(r) <= tolerance_ms {
+        None
+    } else if l > r {
+        Some(Side::Left)
+    } else {
+        Some(Side::Right)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ignore::IgnoreRules;
+    use crate::scan::{insert_entry, scan_directory, KeyMode, ScanOptions};
+    use crate::source::memory::MemorySource;
+    use crate::scan::ScanError;
+    use crate::source::{LocalSource, TreeSource};
+
+    fn make_scan(entries: Vec<(&str, EntryMeta)>) -> ScanResult {
+        let mut map = HashMap::new();
+        let mut originals = HashMap::new();
+        let mut case_collisions = HashMap::new();
+        let key_mode = KeyMode::default();
+        for (path, meta) in &entries {
+            insert_entry(
+                &mut map,
+                &mut originals,
+                &mut case_collisions,
+                key_mode.key(path),
+                path.to_string(),
+                meta.clone(),
+            );
+        }
+        ScanResult {
+            count: map.len(),
+            entries: map,
+            originals,
+            errors: vec![],
+            excluded_dirs: vec![],
+            depth_limited: vec![],
+            case_sensitive: true,
+            case_collisions,
+            key_mode,
+        }
+    }
+
+    fn exact(scan: ScanResult) -> ScanResult {
+        scan.rekey(KeyMode {
+            case_sensitive: true,
+            ..Default::default()
+        })
+    }
+
+    fn file_meta(size: u64, mtime: u64) -> EntryMeta {
+        EntryMeta {
+            kind: EntryKind::File,
+            size,
+            modified: Some(mtime),
+            symlink_target: None,
+            mode: None,
+            link: None,
+        }
+    }
+
+    fn dir_meta() -> EntryMeta {
+        EntryMeta {
+            kind: EntryKind::Dir,
+            size: 0,
+            modified: Some(1000),
+            symlink_target: None,
+            mode: None,
+            link: None,
+        }
+    }
+
+    fn no_cancel() -> AtomicBool {
+        AtomicBool::new(false)
+    }
+
+    /// Scans `left` and `right` through both the in-memory backend and the local
+    /// filesystem (after materializing them), returning one compare result per backend.
+    fn compare_both_backends(
+        name: &str,
+        left: MemorySource,
+        right: MemorySource,
+        options: &ScanOptions,
+    ) -> Vec<CompareResult> {
+        let dir = std::env::temp_dir().join(format!("sc_compare_{}", name));
+        let _ = std::fs::remove_dir_all(&dir);
+        left.materialize(&dir.join("left")).unwrap();
+        right.materialize(&dir.join("right")).unwrap();
+
+        let rules = IgnoreRules::new(&[]);
+        let cancel = no_cancel();
+        let sources: [(Box<dyn TreeSource>, Box<dyn TreeSource>); 2] = [
+            (Box::new(left), Box::new(right)),
+            (
+                Box::new(LocalSource::new(dir.join("left"))),
+                Box::new(LocalSource::new(dir.join("right"))),
+            ),
+        ];
+        let results = sources
+            .iter()
+            .map(|(l, r)| {
+                let l = l.scan(&rules, &[], options, &cancel, &|_| {}).unwrap();
+                let r = r.scan(&rules, &[], options, &cancel, &|_| {}).unwrap();
+                compare(&l, &r, CompareMode::Smart, &cancel).unwrap()
+            })
+            .collect();
+
+        let _ = std::fs::remove_dir_all(&dir);
+        results
+    }
+
+    #[test]
+    fn test_backends_agree_on_tree_diff() {
+        let left = MemorySource::new()
+            .file("same.txt", "same")
+            .file("Docs/changed.md", "short")
+            .file("only_left.txt", "x")
+            .dir("item");
+        let right = MemorySource::new()
+            .file("same.txt", "same")
+            .file("Docs/changed.md", "much longer")
+            .file("only_right.txt", "y")
+            .file("item", "now a file");
+
+        let options = ScanOptions::default();
+        for result in compare_both_backends("backends", left, right, &options) {
+            assert_eq!(result.summary.only_left, 1);
+            assert_eq!(result.summary.only_right, 1);
+            assert_eq!(result.summary.type_mismatch, 1);
+            assert_eq!(result.summary.meta_diff, 1);
+            // same.txt and the Docs directory
+            assert_eq!(result.summary.same, 2);
+            let changed = result.diffs.iter().find(|d| d.rel_path == "Docs/changed.md");
+            assert_eq!(changed.unwrap().diff_kind, DiffKind::MetaDiff);
+        }
+    }
+
+    #[test]
+    fn test_identical_files() {
+        let left = make_scan(vec![("file.txt", file_meta(100, 1000))]);
+        let right = make_scan(vec![("file.txt", file_meta(100, 1000))]);
+        let cancel = no_cancel();
+
+        let result = compare(&left, &right, CompareMode::Smart, &cancel).unwrap();
+        assert_eq!(result.summary.same, 1);
+        assert_eq!(result.diffs[0].diff_kind, DiffKind::Same);
+    }
+
+    #[test]
+    fn test_only_left() {
+        let left = make_scan(vec![("file.txt", file_meta(100, 1000))]);
+        let right = make_scan(vec![]);
+        let cancel = no_cancel();
+
+        let result = compare(&left, &right, CompareMode::Smart, &cancel).unwrap();
+        assert_eq!(result.summary.only_left, 1);
+        assert_eq!(result.diffs[0].diff_kind, DiffKind::OnlyLeft);
+    }
+
+    #[test]
+    fn test_only_right() {
+        let left = make_scan(vec![]);
+        let right = make_scan(vec![("file.txt", file_meta(100, 1000))]);
+        let cancel = no_cancel();
+
+        let result = compare(&left, &right, CompareMode::Smart, &cancel).unwrap();
+        assert_eq!(result.summary.only_right, 1);
+        assert_eq!(result.diffs[0].diff_kind, DiffKind::OnlyRight);
+    }
+
+    #[test]
+    fn test_type_mismatch() {
+        let left = make_scan(vec![("item", file_meta(100, 1000))]);
+        let right = make_scan(vec![("item", dir_meta())]);
+        let cancel = no_cancel();
+
+        let result = compare(&left, &right, CompareMode::Smart, &cancel).unwrap();
+        assert_eq!(result.summary.type_mismatch, 1);
+        assert_eq!(result.diffs[0].diff_kind, DiffKind::TypeMismatch);
+    }
+
+    #[test]
+    fn test_meta_diff_size() {
+        let left = make_scan(vec![("file.txt", file_meta(100, 1000))]);
+        let right = make_scan(vec![("file.txt", file_meta(200, 1000))]);
+        let cancel = no_cancel();
+
+        let result = compare(&left, &right, CompareMode::Smart, &cancel).unwrap();
+        assert_eq!(result.summary.meta_diff, 1);
+        assert_eq!(result.diffs[0].diff_kind, DiffKind::MetaDiff);
+    }
+
+    #[test]
+    fn test_same_size_different_mtime_is_same() {
+        let left = make_scan(vec![("file.txt", file_meta(100, 1000))]);
+        let right = make_scan(vec![("file.txt", file_meta(100, 2000))]);
+        let cancel = no_cancel();
+
+        let result = compare(&left, &right, CompareMode::Smart, &cancel).unwrap();
+        assert_eq!(result.summary.same, 1);
+        assert_eq!(result.diffs[0].diff_kind, DiffKind::Same);
+    }
+
+    #[test]
+    fn test_newer_side_reported_for_size_change() {
+        let left = make_scan(vec![("file.txt", file_meta(100, 10_000))]);
+        let right = make_scan(vec![("file.txt", file_meta(200, 1000))]);
+        let cancel = no_cancel();
+
+        let result = compare(&left, &right, CompareMode::Smart, &cancel).unwrap();
+        assert_eq!(result.diffs[0].newer, Some(Side::Left));
+        assert_eq!(result.summary.left_newer, 1);
+        assert_eq!(result.summary.right_newer, 0);
+    }
+
+    #[test]
+    fn test_newer_tolerance_boundary() {
+        let cancel = no_cancel();
+        let options = CompareOptions::default();
+        let run = |right_mtime: u64| {
+            let left = make_scan(vec![("file.txt", file_meta(100, 10_000))]);
+            let right = make_scan(vec![("file.txt", file_meta(200, right_mtime))]);
+            compare_with_options(&left, &right, CompareMode::Smart, &options, &cancel).unwrap()
+        };
+
+        // Exactly at the tolerance: still considered simultaneous
+        assert_eq!(run(12_000).diffs[0].newer, None);
+        assert_eq!(run(8_000).diffs[0].newer, None);
+        // One millisecond past it on either side
+        assert_eq!(run(12_001).diffs[0].newer, Some(Side::Right));
+        assert_eq!(run(7_999).diffs[0].newer, Some(Side::Left));
+        // Unknown times never pick a side
+        let left = make_scan(vec![("file.txt", file_meta(100, 10_000))]);
+        let mut unknown = file_meta(200, 0);
+        unknown.modified = None;
+        let right = make_scan(vec![("file.txt", unknown)]);
+        let result = compare(&left, &right, CompareMode::Smart, &cancel).unwrap();
+        assert_eq!(result.diffs[0].newer, None);
+    }
+
+    #[test]
+    fn test_timestamp_mode_flags_touched_files() {
+        let cancel = no_cancel();
+        let options = CompareOptions {
+            mtime_tolerance_ms: 0,
+            ..Default::default()
+        };
+        let left = make_scan(vec![
+            ("touched.txt", file_meta(100, 5000)),
+            ("same.txt", file_meta(100, 5000)),
+        ]);
+        let right = make_scan(vec![
+            ("touched.txt", file_meta(100, 5001)),
+            ("same.txt", file_meta(100, 5000)),
+        ]);
+
+        let smart = compare_with_options(&left, &right, CompareMode::Smart, &options, &cancel);
+        assert_eq!(smart.unwrap().summary.same, 2);
+
+        let result =
+            compare_with_options(&left, &right, CompareMode::Timestamp, &options, &cancel).unwrap();
+        assert_eq!(result.summary.meta_diff, 1);
+        assert_eq!(result.summary.right_newer, 1);
+        let touched = result.diffs.iter().find(|d| d.rel_path == "touched.txt").unwrap();
+        assert_eq!(touched.diff_kind, DiffKind::MetaDiff);
+        assert_eq!(touched.newer, Some(Side::Right));
+    }
+
+    #[test]
+    fn test_structure_mode_ignores_metadata() {
+        let left = make_scan(vec![("file.txt", file_meta(100, 1000))]);
+        let right = make_scan(vec![("file.txt", file_meta(200, 2000))]);
+        let cancel = no_cancel();
+
+        let result = compare(&left, &right, CompareMode::Structure, &cancel).unwrap();
+        assert_eq!(result.summary.same, 1);
+        assert_eq!(result.diffs[0].diff_kind, DiffKind::Same);
+    }
+
+    #[test]
+    fn test_dirs_always_same_in_smart() {
+        let left = make_scan(vec![("mydir", dir_meta())]);
+        let right_dir = EntryMeta {
+            kind: EntryKind::Dir,
+            size: 4096,
+            modified: Some(9999),
+            symlink_target: None,
+            mode: None,
+            link: None,
+        };
+        let right = make_scan(vec![("mydir", right_dir)]);
+        let cancel = no_cancel();
+
+        let result = compare(&left, &right, CompareMode::Smart, &cancel).unwrap();
+        assert_eq!(result.summary.same, 1);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_sockets_classified_as_special() {
+        let base = std::env::temp_dir().join("sc_compare_special");
+        let _ = std::fs::remove_dir_all(&base);
+        let (left_dir, right_dir) = (base.join("left"), base.join("right"));
+        for side in [&left_dir, &right_dir] {
+            std::fs::create_dir_all(side).unwrap();
+        }
+        let _sockets = [
+            std::os::unix::net::UnixListener::bind(left_dir.join("run.sock")).unwrap(),
+            std::os::unix::net::UnixListener::bind(left_dir.join("both.sock")).unwrap(),
+            std::os::unix::net::UnixListener::bind(right_dir.join("both.sock")).unwrap(),
+        ];
+        std::fs::write(right_dir.join("run.sock"), "").unwrap();
+
+        let rules = IgnoreRules::new(&[]);
+        let cancel = no_cancel();
+        let left = scan_directory(&left_dir, &rules, &[], &cancel, &|_| {}).unwrap();
+        let right = scan_directory(&right_dir, &rules, &[], &cancel, &|_| {}).unwrap();
+        let result = compare(&left, &right, CompareMode::Timestamp, &cancel).unwrap();
+        let _ = std::fs::remove_dir_all(&base);
+
+        assert_eq!(left.entries["run.sock"].kind, EntryKind::Socket);
+        let kind_of = |path: &str| {
+            let diff = result.diffs.iter().find(|d| d.rel_path == path).unwrap();
+            diff.diff_kind
+        };
+        // A socket never matches a regular file, and two sockets are not compared
+        assert_eq!(kind_of("run.sock"), DiffKind::TypeMismatch);
+        assert_eq!(kind_of("both.sock"), DiffKind::Special);
+        assert_eq!((result.summary.special, result.summary.same), (1, 0));
+        let structure = compare(&left, &right, CompareMode::Structure, &cancel).unwrap();
+        let both = structure.diffs.iter().find(|d| d.rel_path == "both.sock");
+        assert_eq!(both.map(|d| d.diff_kind), Some(DiffKind::Same));
+    }
+
+    #[test]
+    fn test_summary_counts() {
+        let left = make_scan(vec![
+            ("same.txt", file_meta(100, 1000)),
+            ("left_only.txt", file_meta(50, 500)),
+            ("changed.txt", file_meta(100, 1000)),
+        ]);
+        let right = make_scan(vec![
+            ("same.txt", file_meta(100, 1000)),
+            ("right_only.txt", file_meta(75, 750)),
+            ("changed.txt", file_meta(200, 2000)),
+        ]);
+        let cancel = no_cancel();
+
+        let result = compare(&left, &right, CompareMode::Smart, &cancel).unwrap();
+        assert_eq!(result.summary.same, 1);
+        assert_eq!(result.summary.only_left, 1);
+        assert_eq!(result.summary.only_right, 1);
+        assert_eq!(result.summary.meta_diff, 1);
+        assert_eq!(result.summary.total_left, 3);
+        assert_eq!(result.summary.total_right, 3);
+    }
+
+    #[test]
+    fn test_summary_byte_sums() {
+        let big_dir = EntryMeta {
+            size: 4096,
+            ..dir_meta()
+        };
+        let left = make_scan(vec![
+            ("same.txt", file_meta(100, 1000)),
+            ("left_only.txt", file_meta(50, 500)),
+            ("left_dir", big_dir.clone()),
+            ("left_dir/inner.bin", file_meta(25, 500)),
+            ("changed.txt", file_meta(100, 1000)),
+        ]);
+        let right = make_scan(vec![
+            ("same.txt", file_meta(100, 1000)),
+            ("right_only.txt", file_meta(75, 750)),
+            ("changed.txt", file_meta(300, 2000)),
+        ]);
+        let cancel = no_cancel();
+
+        let result = compare(&left, &right, CompareMode::Smart, &cancel).unwrap();
+        // The directory's own size is not content
+        assert_eq!(result.summary.bytes_only_left, 75);
+        assert_eq!(result.summary.bytes_only_right, 75);
+        assert_eq!(result.summary.bytes_modified, 300);
+        assert_eq!(result.summary.pending, 0);
+
+        let mut recounted = result.summary.clone();
+        recounted.recount(&result.diffs[..1]);
+        assert_eq!(recounted.bytes_modified, 300);
+        assert_eq!(recounted.bytes_only_left, 0);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_hard_links_counted_once_in_unique_bytes() {
+        let base = std::env::temp_dir().join("sc_compare_hard_links");
+        let _ = std::fs::remove_dir_all(&base);
+        let (left_dir, right_dir) = (base.join("left"), base.join("right"));
+        std::fs::create_dir_all(left_dir.join("snap")).unwrap();
+        std::fs::create_dir_all(&right_dir).unwrap();
+        std::fs::write(left_dir.join("data.bin"), vec![0u8; 1000]).unwrap();
+        std::fs::hard_link(left_dir.join("data.bin"), left_dir.join("copy.bin")).unwrap();
+        std::fs::hard_link(left_dir.join("data.bin"), left_dir.join("snap/data.bin")).unwrap();
+
+        let rules = IgnoreRules::new(&[]);
+        let cancel = no_cancel();
+        let left = scan_directory(&left_dir, &rules, &[], &cancel, &|_| {}).unwrap();
+        let right = scan_directory(&right_dir, &rules, &[], &cancel, &|_| {}).unwrap();
+        let result = compare(&left, &right, CompareMode::Smart, &cancel).unwrap();
+        let _ = std::fs::remove_dir_all(&base);
+
+        assert_eq!(result.summary.bytes_only_left, 3000);
+        assert_eq!(result.summary.unique_bytes_only_left, 1000);
+    }
+
+    #[test]
+    fn test_case_collision_against_insensitive_side() {
+        // Right is case-sensitive and holds both spellings; left can only hold one
+        let mut left = make_scan(vec![("foo", file_meta(10, 1000))]);
+        left.case_sensitive = false;
+        let right = make_scan(vec![("Foo", file_meta(20, 1000)), ("foo", file_meta(10, 1000))]);
+        let cancel = no_cancel();
+
+        let result = compare(&left, &right, CompareMode::Smart, &cancel).unwrap();
+        assert_eq!(result.diffs.len(), 2);
+        let exact = result.diffs.iter().find(|d| d.rel_path == "foo").unwrap();
+        assert_eq!(exact.diff_kind, DiffKind::Same);
+        let other = result.diffs.iter().find(|d| d.rel_path == "Foo").unwrap();
+        assert_eq!(other.diff_kind, DiffKind::CaseCollision);
+        assert_eq!(other.right.as_ref().unwrap().size, 20);
+        assert!(other.error_message.is_some());
+
+        assert_eq!(result.summary.case_collisions, 1);
+        assert_eq!(result.summary.total_right, 2);
+        assert_eq!(result.summary.left_case_sensitive, Some(false));
+        assert_eq!(result.summary.right_case_sensitive, Some(true));
+        assert_eq!(result.summary.warnings.len(), 1);
+        assert!(result.summary.warnings[0].contains("case"));
+    }
+
+    #[test]
+    fn test_case_variants_on_both_sensitive_sides() {
+        let left = make_scan(vec![("A.txt", file_meta(1, 0)), ("a.txt", file_meta(2, 0))]);
+        let right = make_scan(vec![("A.txt", file_meta(1, 0))]);
+        let cancel = no_cancel();
+
+        let result = compare(&left, &right, CompareMode::Smart, &cancel).unwrap();
+        assert!(result.summary.warnings.is_empty());
+        assert_eq!(result.summary.same, 1);
+        // Matching ignores case, so the spare spelling collides with the right's "A.txt"
+        assert_eq!(result.summary.case_collisions, 1);
+        assert_eq!(result.diffs.len(), 2);
+
+        // Matching by exact name keeps both spellings apart
+        let left = exact(left);
+        let right = exact(right);
+        let result = compare(&left, &right, CompareMode::Smart, &cancel).unwrap();
+        assert_eq!(result.summary.same, 1);
+        assert_eq!(result.summary.only_left, 1);
+        assert_eq!(result.summary.case_collisions, 0);
+    }
+
+    #[test]
+    fn test_colliding_variants_without_counterpart_are_one_sided() {
+        let left = make_scan(vec![("Readme", file_meta(1, 0)), ("README", file_meta(2, 0))]);
+        let right = make_scan(vec![]);
+        let cancel = no_cancel();
+
+        let result = compare(&left, &right, CompareMode::Smart, &cancel).unwrap();
+        assert_eq!(result.summary.only_left, 2);
+        assert_eq!(result.summary.case_collisions, 0);
+        assert_eq!(result.summary.total_left, 2);
+    }
+
+    #[test]
+    fn test_exact_keys_compare_distinguishes_case() {
+        let left = exact(make_scan(vec![
+            ("Readme.md", file_meta(10, 0)),
+            ("README.md", file_meta(20, 0)),
+            ("docs", dir_meta()),
+        ]));
+        let right = exact(make_scan(vec![
+            ("README.md", file_meta(20, 0)),
+            ("Docs", dir_meta()),
+        ]));
+        let cancel = no_cancel();
+
+        assert_eq!(left.entries.len(), 3);
+        let result = compare(&left, &right, CompareMode::Smart, &cancel).unwrap();
+        let kind_of = |path: &str| {
+            result.diffs.iter().find(|d| d.rel_path == path).unwrap().diff_kind
+        };
+        assert_eq!(kind_of("README.md"), DiffKind::Same);
+        assert_eq!(kind_of("Readme.md"), DiffKind::OnlyLeft);
+        assert_eq!(kind_of("docs"), DiffKind::OnlyLeft);
+        assert_eq!(kind_of("Docs"), DiffKind::OnlyRight);
+        assert_eq!(result.summary.total_left, 3);
+    }
+
+    #[test]
+    fn test_mixed_key_modes_rejected() {
+        let left = exact(make_scan(vec![("a", file_meta(1, 0))]));
+        let right = make_scan(vec![("a", file_meta(1, 0))]);
+        let result = compare(&left, &right, CompareMode::Smart, &no_cancel());
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_depth_limit_flags_cut_off_dirs() {
+        // Four levels; the only difference sits at the bottom
+        let tree = |deep: &str| {
+            MemorySource::new()
+                .file("top.txt", "top")
+                .file("a/mid.txt", "mid")
+                .file("a/b/c/deep.txt", deep)
+        };
+        let options = ScanOptions {
+            max_depth: Some(2),
+            ..Default::default()
+        };
+        let results = compare_both_backends("depth", tree("short"), tree("longer"), &options);
+        for result in results {
+            let s = &result.summary;
+            assert_eq!(s.total_left, 4);
+            assert_eq!(s.meta_diff, 0);
+            assert_eq!(s.depth_limited, 1);
+            assert_eq!(s.warnings.len(), 1);
+            assert!(result.diffs.iter().all(|d| !d.rel_path.contains("c")));
+        }
+    }
+
+    #[test]
+    fn test_unicode_forms_match() {
+        // "café" precomposed (NFC) on the left, decomposed (NFD, as macOS stores it) on the right
+        let nfc = "caf\u{e9}.txt";
+        let nfd = "cafe\u{301}.txt";
+        let left = make_scan(vec![(nfc, file_meta(10, 0)), ("dir/\u{c5}", file_meta(1, 0))]);
+        let right = make_scan(vec![(nfd, file_meta(20, 0)), ("dir/A\u{30a}", file_meta(1, 0))]);
+        let cancel = no_cancel();
+
+        let result = compare(&left, &right, CompareMode::Smart, &cancel).unwrap();
+        assert_eq!(result.diffs.len(), 2);
+        assert_eq!(result.summary.meta_diff, 1);
+        assert_eq!(result.summary.same, 1);
+        assert_eq!(result.summary.only_left + result.summary.only_right, 0);
+        // The on-disk name is kept for file operations
+        assert!(left.originals.values().any(|p| p == nfc));
+        assert!(right.originals.values().any(|p| p == nfd));
+
+        // Byte-level matching keeps them apart
+        let raw = KeyMode {
+            normalize_unicode: false,
+            ..Default::default()
+        };
+        let result =
+            compare(&left.rekey(raw), &right.rekey(raw), CompareMode::Smart, &cancel).unwrap();
+        assert_eq!(result.summary.only_left, 2);
+        assert_eq!(result.summary.only_right, 2);
+    }
+
+    #[test]
+    fn test_scan_errors_become_error_diffs() {
+        let mut left = make_scan(vec![
+            ("locked", dir_meta()),
+            ("ok.txt", file_meta(1, 0)),
+        ]);
+        left.errors.push(ScanError {
+            path: "locked".to_string(),
+            message: "Permission denied".to_string(),
+        });
+        left.errors.push(ScanError {
+            path: "gone/file.txt".to_string(),
+            message: "No such file".to_string(),
+        });
+        let mut right = make_scan(vec![
+            ("locked", dir_meta()),
+            ("locked/inner.txt", file_meta(1, 0)),
+            ("ok.txt", file_meta(1, 0)),
+        ]);
+        right.errors.push(ScanError {
+            path: "locked".to_string(),
+            message: "Permission denied".to_string(),
+        });
+        let cancel = no_cancel();
+
+        let result = compare(&left, &right, CompareMode::Smart, &cancel).unwrap();
+        assert_eq!(result.summary.errors, 2);
+        assert_eq!(result.summary.same, 1);
+        let locked = result.diffs.iter().find(|d| d.rel_path == "locked").unwrap();
+        assert_eq!(locked.diff_kind, DiffKind::Error);
+        assert!(locked.left.is_some() && locked.right.is_some());
+        let message = locked.error_message.as_deref().unwrap();
+        assert!(message.contains("Left: Permission denied"));
+        assert!(message.contains("Right: Permission denied"));
+        let gone = result.diffs.iter().find(|d| d.rel_path == "gone/file.txt").unwrap();
+        assert_eq!(gone.diff_kind, DiffKind::Error);
+        assert!(gone.left.is_none());
+    }
+
+    #[test]
+    fn test_unreadable_subdir_reported() {
+        let base = std::env::temp_dir().join("sc_compare_unreadable");
+        let _ = std::fs::remove_dir_all(&base);
+        let (left_dir, right_dir) = (base.join("left"), base.join("right"));
+        for dir in [&left_dir, &right_dir] {
+            std::fs::create_dir_all(dir.join("private")).unwrap();
+            std::fs::write(dir.join("private/secret.txt"), "s").unwrap();
+        }
+
+        let rules = IgnoreRules::new(&[]);
+        let cancel = no_cancel();
+        let mut left = scan_directory(&left_dir, &rules, &[], &cancel, &|_| {}).unwrap();
+        let right = scan_directory(&right_dir, &rules, &[], &cancel, &|_| {}).unwrap();
+        let _ = std::fs::remove_dir_all(&base);
+        // What a folder that cannot be listed leaves in a scan, whoever runs the test:
+        // the folder itself, none of its contents, and an error for it
+        left.entries.retain(|key, _| !key.starts_with("private/"));
+        left.errors.push(ScanError {
+            path: "private".to_string(),
+            message: "Permission denied".to_string(),
+        });
+        let result = compare(&left, &right, CompareMode::Smart, &cancel).unwrap();
+
+        assert_eq!(result.summary.errors, 1);
+        let private = result.diffs.iter().find(|d| d.rel_path == "private").unwrap();
+        assert_eq!(private.diff_kind, DiffKind::Error);
+        assert!(private.left.is_some() && private.right.is_some());
+        assert_eq!(private.error_message.as_deref(), Some("Left: Permission denied"));
+        let secret = result.diffs.iter().find(|d| d.rel_path == "private/secret.txt");
+        assert_eq!(secret.unwrap().diff_kind, DiffKind::OnlyRight);
+    }
+
+    #[test]
+    fn test_page_diffs_filters_and_slices() {
+        let left = make_scan(vec![
+            ("a.txt", file_meta(1, 0)),
+            ("B/c.txt", file_meta(1, 0)),
+            ("b/d.txt", file_meta(1, 0)),
+            ("e.txt", file_meta(1, 0)),
+        ]);
+        let right = make_scan(vec![("a.txt", file_meta(1, 0)), ("e.txt", file_meta(2, 0))]);
+        let result = compare(&left, &right, CompareMode::Smart, &no_cancel()).unwrap();
+        let paths = |page: &DiffPage| {
+            page.items.iter().map(|d| d.rel_path.clone()).collect::<Vec<_>>()
+        };
+
+        let all = page_diffs(&result.diffs, 0, None, None, None);
+        assert_eq!(all.total, 4);
+        assert_eq!(paths(&all), vec!["a.txt", "B/c.txt", "b/d.txt", "e.txt"]);
+
+        let page = page_diffs(&result.diffs, 1, Some(2), None, None);
+        assert_eq!(page.total, 4);
+        assert_eq!(paths(&page), vec!["B/c.txt", "b/d.txt"]);
+
+        let only_left = page_diffs(&result.diffs, 0, Some(10), Some(&[DiffKind::OnlyLeft]), None);
+        assert_eq!(only_left.total, 2);
+
+        // Substring match ignores case
+        let filtered = page_diffs(&result.diffs, 0, None, None, Some("b/"));
+        assert_eq!(paths(&filtered), vec!["B/c.txt", "b/d.txt"]);
+
+        let past_end = page_diffs(&result.diffs, 10, Some(5), None, None);
+        assert!(past_end.items.is_empty());
+        assert_eq!(past_end.total, 4);
+    }
+
+    #[test]
+    fn test_exclude_same_keeps_only_differences() {
+        let mut left_files = Vec::new();
+        let mut right_files = Vec::new();
+        for i in 0..1000 {
+            let path = format!("dir/file{}.txt", i);
+            left_files.push((path.clone(), file_meta(10, 1000)));
+            right_files.push((path, file_meta(10, 1000)));
+        }
+        left_files.push(("extra.txt".to_string(), file_meta(1, 0)));
+        right_files.push(("changed.txt".to_string(), file_meta(1, 0)));
+        left_files.push(("changed.txt".to_string(), file_meta(2, 0)));
+        fn as_refs(files: &[(String, EntryMeta)]) -> Vec<(&str, EntryMeta)> {
+            files.iter().map(|(p, m)| (p.as_str(), m.clone())).collect()
+        }
+        let left = make_scan(as_refs(&left_files));
+        let right = make_scan(as_refs(&right_files));
+        let options = CompareOptions {
+            include_same: false,
+            ..Default::default()
+        };
+
+        let result =
+            compare_with_options(&left, &right, CompareMode::Smart, &options, &no_cancel()).unwrap();
+        let s = &result.summary;
+        assert_eq!(s.same, 1000);
+        assert_eq!(s.total_left, 1002);
+        assert_eq!(result.diffs.len(), s.only_left + s.only_right + s.meta_diff);
+        assert_eq!(result.diffs.len(), 2);
+        assert!(result.diffs.iter().all(|d| d.diff_kind != DiffKind::Same));
+    }
+}
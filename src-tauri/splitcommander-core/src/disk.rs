@@ -0,0 +1,569 @@
+//! Disk usage queries for the volume holding a path, and the mounted volumes a user
+//! might want to browse.
+
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+
+use crate::error::CommandError;
+
+/// Size and free space of the volume holding a path.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VolumeInfo {
+    pub total_bytes: u64,
+    /// Available to the current user, which can be less than what is unused
+    pub free_bytes: u64,
+    /// E.g. "apfs", "ext4"; `None` when the platform does not say
+    pub filesystem: Option<String>,
+}
+
+/// A mounted volume, drive or network share.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Volume {
+    pub name: String,
+    pub mount_point: String,
+    /// `None` when the volume is unavailable or the platform does not say
+    pub total_bytes: Option<u64>,
+    pub free_bytes: Option<u64>,
+    pub is_removable: bool,
+    pub is_network: bool,
+    /// False when asking about the volume failed or took too long, e.g. a stale network
+    /// mount
+    pub available: bool,
+}
+
+/// A volume as the mount table describes it, before it is asked for its size.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Mount {
+    name: String,
+    mount_point: PathBuf,
+    is_removable: bool,
+    is_network: bool,
+}
+
+/// The mounted volumes worth offering as pane roots: the system volume, other disks,
+/// removable media and network shares, leaving out system-internal mounts. Each is
+/// asked for its size on its own thread; one that has not answered within `timeout`
+/// is reported unavailable, and its thread left to finish or hang on its own.
+pub fn list_volumes(timeout: Duration) -> io::Result<Vec<Volume>> {
+    Ok(probe_volumes(mounts()?, timeout, volume_info_impl))
+}
+
+fn probe_volumes(
+    mounts: Vec<Mount>,
+    timeout: Duration,
+    info: fn(&Path) -> io::Result<VolumeInfo>,
+) -> Vec<Volume> {
+    let answers: Vec<_> = mounts
+        .iter()
+        .map(|mount| {
+            let (tx, rx) = mpsc::channel();
+            let path = mount.mount_point.clone();
+            std::thread::spawn(move || {
+                let _ = tx.send(info(&path));
+            });
+            rx
+        })
+        .collect();
+
+    let deadline = Instant::now() + timeout;
+    mounts
+        .into_iter()
+        .zip(answers)
+        .map(|(mount, answer)| {
+            let wait = deadline.saturating_duration_since(Instant::now());
+            let info = answer.recv_timeout(wait).ok();
+            let available = match &info {
+                Some(info) => info.is_ok() || is_unsupported(info),
+                None => false,
+            };
+            let info = info.and_then(Result::ok);
+            Volume {
+                name: mount.name,
+                mount_point: mount.mount_point.to_string_lossy().into_owned(),
+                total_bytes: info.as_ref().map(|i| i.total_bytes),
+                free_bytes: info.as_ref().map(|i| i.free_bytes),
+                is_removable: mount.is_removable,
+                is_network: mount.is_network,
+                available,
+            }
+        })
+        .collect()
+}
+
+/// A platform without a size query still lists its volumes as available.
+fn is_unsupported(info: &io::Result<VolumeInfo>) -> bool {
+    matches!(info, Err(e) if e.kind() == io::ErrorKind::Unsupported)
+}
+
+/// Filesystems reached over the network.
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+const NETWORK_FILESYSTEMS: &[&str] = &[
+    "nfs", "nfs4", "cifs", "smb3", "smbfs", "afpfs", "webdav", "fuse.sshfs", "9p",
+];
+
+/// Mounted block devices from `/proc/mounts`, plus network shares. Boot partitions,
+/// snaps and container layers are left out.
+#[cfg(target_os = "linux")]
+fn mounts() -> io::Result<Vec<Mount>> {
+    let table = std::fs::read_to_string("/proc/mounts")?;
+    Ok(parse_mounts(&table, removable_block_device))
+}
+
+#[cfg(target_os = "linux")]
+fn parse_mounts(table: &str, removable: impl Fn(&str) -> bool) -> Vec<Mount> {
+    let mut mounts: Vec<Mount> = Vec::new();
+    for line in table.lines() {
+        let mut fields = line.split_whitespace();
+        let (Some(device), Some(mount_point), Some(fstype)) =
+            (fields.next(), fields.next(), fields.next())
+        else {
+            continue;
+        };
+        let mount_point = unescape_mount_path(mount_point);
+        let is_network = NETWORK_FILESYSTEMS.contains(&fstype);
+        let on_media = mount_point.starts_with("/run/media/");
+        // By whole components, so `/dev` does not take `/devel` along
+        let internal = ["/boot", "/snap", "/var/lib", "/run", "/sys", "/proc", "/dev"]
+            .iter()
+            .any(|prefix| Path::new(&mount_point).starts_with(prefix));
+        let wanted = mount_point == "/"
+            || is_network
+            || (device.starts_with("/dev/") && fstype != "squashfs" && (on_media || !internal));
+        if !wanted || mounts.iter().any(|m| m.mount_point == Path::new(&mount_point)) {
+            continue;
+        }
+        let is_removable =
+            !is_network && (on_media || mount_point.starts_with("/media/") || removable(device));
+        mounts.push(Mount {
+            name: mount_name(Path::new(&mount_point)),
+            mount_point: PathBuf::from(mount_point),
+            is_removable,
+            is_network,
+        });
+    }
+    mounts
+}
+
+/// `/proc/mounts` writes spaces, tabs, newlines and backslashes as octal escapes.
+#[cfg(target_os = "linux")]
+fn unescape_mount_path(raw: &str) -> String {
+    let mut out = String::with_capacity(raw.len());
+    let mut rest = raw;
+    while let Some(i) = rest.find('\\') {
+        out.push_str(&rest[..i]);
+        let code = rest.get(i + 1..i + 4).and_then(|digits| u8::from_str_radix(digits, 8).ok());
+        match code {
+            Some(byte) => {
+                out.push(byte as char);
+                rest = &rest[i + 4..];
+            }
+            None => {
+                out.push('\\');
+                rest = &rest[i + 1..];
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+/// Whether the kernel flags the disk behind a `/dev/...` partition as removable.
+#[cfg(target_os = "linux")]
+fn removable_block_device(device: &str) -> bool {
+    let Some(name) = device.strip_prefix("/dev/") else {
+        return false;
+    };
+    // A partition's sysfs folder sits inside its disk's, which has the flag
+    let Ok(sys) = std::fs::canonicalize(Path::new("/sys/class/block").join(name)) else {
+        return false;
+    };
+    [sys.join("removable"), sys.join("../removable")]
+        .iter()
+        .any(|flag| std::fs::read_to_string(flag).is_ok_and(|v| v.trim() == "1"))
+}
+
+/// The startup volume plus everything under `/Volumes`, where macOS mounts other disks
+/// and shares. The startup volume is listed there too, as a link to `/`.
+#[cfg(target_os = "macos")]
+fn mounts() -> io::Result<Vec<Mount>> {
+    let fstypes = mount_table()?;
+    let mut root = Mount {
+        name: "/".to_string(),
+        mount_point: PathBuf::from("/"),
+        is_removable: false,
+        is_network: false,
+    };
+    let mut others = Vec::new();
+    for entry in std::fs::read_dir("/Volumes")?.flatten() {
+        let name = entry.file_name().to_string_lossy().into_owned();
+        if entry.file_type().is_ok_and(|t| t.is_symlink()) {
+            if std::fs::read_link(entry.path()).is_ok_and(|target| target == Path::new("/")) {
+                root.name = name;
+            }
+            continue;
+        }
+        let path = entry.path();
+        let is_network = fstypes
+            .get(&path)
+            .is_some_and(|t| NETWORK_FILESYSTEMS.contains(&t.as_str()));
+        others.push(Mount {
+            name,
+            mount_point: path,
+            is_removable: !is_network,
+            is_network,
+        });
+    }
+    others.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(std::iter::once(root).chain(others).collect())
+}
+
+/// Mount points and their filesystem types, from the kernel's cached mount table.
+/// `MNT_NOWAIT` keeps any filesystem from being asked, so a hung share cannot block it.
+#[cfg(target_os = "macos")]
+fn mount_table() -> io::Result<std::collections::HashMap<PathBuf, String>> {
+    use std::ffi::{CStr, OsStr};
+    use std::os::unix::ffi::OsStrExt;
+
+    let mut table: *mut libc::statfs = std::ptr::null_mut();
+    // SAFETY: table is a valid out-pointer for the array getmntinfo manages
+    let count = unsafe { libc::getmntinfo(&mut table, libc::MNT_NOWAIT) };
+    if count <= 0 || table.is_null() {
+        return Err(io::Error::last_os_error());
+    }
+    // SAFETY: getmntinfo filled `count` entries, valid until its next call
+    let entries = unsafe { std::slice::from_raw_parts(table, count as usize) };
+    Ok(entries
+        .iter()
+        .map(|stat| {
+            // SAFETY: the kernel fills both names NUL-terminated
+            let (mount_point, fstype) = unsafe {
+                (
+                    CStr::from_ptr(stat.f_mntonname.as_ptr()),
+                    CStr::from_ptr(stat.f_fstypename.as_ptr()),
+                )
+            };
+            (
+                PathBuf::from(OsStr::from_bytes(mount_point.to_bytes())),
+                fstype.to_string_lossy().into_owned(),
+            )
+        })
+        .collect())
+}
+
+/// The drive letters Windows reports, with their kind.
+#[cfg(windows)]
+fn mounts() -> io::Result<Vec<Mount>> {
+    const DRIVE_REMOVABLE: u32 = 2;
+    const DRIVE_REMOTE: u32 = 4;
+    const DRIVE_CDROM: u32 = 5;
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn GetLogicalDrives() -> u32;
+        fn GetDriveTypeW(root: *const u16) -> u32;
+    }
+
+    // SAFETY: takes no arguments and only returns a bit mask
+    let mask = unsafe { GetLogicalDrives() };
+    if mask == 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok((0..26u8)
+        .filter(|bit| mask & (1 << bit) != 0)
+        .map(|bit| {
+            let root = format!("{}:\\", (b'A' + bit) as char);
+            let wide: Vec<u16> = root.encode_utf16().chain(std::iter::once(0)).collect();
+            // SAFETY: wide is a NUL-terminated UTF-16 string
+            let kind = unsafe { GetDriveTypeW(wide.as_ptr()) };
+            Mount {
+                name: root.trim_end_matches('\\').to_string(),
+                mount_point: PathBuf::from(root),
+                is_removable: kind == DRIVE_REMOVABLE || kind == DRIVE_CDROM,
+                is_network: kind == DRIVE_REMOTE,
+            }
+        })
+        .collect())
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos", windows)))]
+fn mounts() -> io::Result<Vec<Mount>> {
+    Ok(vec![Mount {
+        name: "/".to_string(),
+        mount_point: PathBuf::from("/"),
+        is_removable: false,
+        is_network: false,
+    }])
+}
+
+/// The last part of a mount point, or the whole of a root like `/`.
+#[cfg(target_os = "linux")]
+fn mount_name(mount_point: &Path) -> String {
+    mount_point
+        .file_name()
+        .map_or_else(|| mount_point.to_string_lossy(), |name| name.to_string_lossy())
+        .into_owned()
+}
+
+/// Where free space comes from; tests substitute a fixed amount.
+pub trait SpaceProvider {
+    fn available_space(&self, path: &Path) -> io::Result<u64>;
+}
+
+/// The real volume, via [`available_space`].
+pub struct SystemSpace;
+
+impl SpaceProvider for SystemSpace {
+    fn available_space(&self, path: &Path) -> io::Result<u64> {
+        available_space(path)
+    }
+}
+
+/// Bytes available to the current user on the volume containing `path`.
+/// `path` need not exist yet; its nearest existing ancestor is queried instead.
+pub fn available_space(path: &Path) -> io::Result<u64> {
+    volume_info(path).map(|info| info.free_bytes)
+}
+
+/// Describes the volume containing `path`, which, as for [`available_space`], need not
+/// exist yet.
+pub fn volume_info(path: &Path) -> io::Result<VolumeInfo> {
+    let existing = path
+        .ancestors()
+        .find(|p| !p.as_os_str().is_empty() && p.exists())
+        .unwrap_or(Path::new("."));
+    volume_info_impl(existing)
+}
+
+/// Fails with `insufficientSpace` when `dest`'s volume has less than `required` bytes
+/// free. Platforms without a free-space query pass, so the operation is just attempted.
+pub fn ensure_space(
+    provider: &dyn SpaceProvider,
+    dest: &Path,
+    required: u64,
+) -> Result<(), CommandError> {
+    match provider.available_space(dest) {
+        Ok(available) if available < required => {
+            Err(CommandError::insufficient_space(dest, required, available))
+        }
+        _ => Ok(()),
+    }
+}
+
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+fn volume_info_impl(path: &Path) -> io::Result<VolumeInfo> {
+    use std::ffi::CString;
+    use std::os::unix::ffi::OsStrExt;
+
+    let c_path = CString::new(path.as_os_str().as_bytes())
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+    let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+    // SAFETY: c_path is NUL-terminated and stat is a valid out-pointer
+    if unsafe { libc::statvfs(c_path.as_ptr(), &mut stat) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    #[allow(clippy::unnecessary_cast)]
+    Ok(VolumeInfo {
+        total_bytes: stat.f_blocks as u64 * stat.f_frsize as u64,
+        free_bytes: stat.f_bavail as u64 * stat.f_frsize as u64,
+        filesystem: filesystem_name(&c_path),
+    })
+}
+
+#[cfg(target_os = "macos")]
+fn filesystem_name(c_path: &std::ffi::CStr) -> Option<String> {
+    let mut stat: libc::statfs = unsafe { std::mem::zeroed() };
+    // SAFETY: c_path is NUL-terminated and stat is a valid out-pointer
+    if unsafe { libc::statfs(c_path.as_ptr(), &mut stat) } != 0 {
+        return None;
+    }
+    // SAFETY: the kernel fills f_fstypename with a NUL-terminated name
+    let name = unsafe { std::ffi::CStr::from_ptr(stat.f_fstypename.as_ptr()) };
+    Some(name.to_string_lossy().into_owned())
+}
+
+/// Linux only reports a magic number; the common ones are named, others left out.
+#[cfg(target_os = "linux")]
+fn filesystem_name(c_path: &std::ffi::CStr) -> Option<String> {
+    let mut stat: libc::statfs = unsafe { std::mem::zeroed() };
+    // SAFETY: c_path is NUL-terminated and stat is a valid out-pointer
+    if unsafe { libc::statfs(c_path.as_ptr(), &mut stat) } != 0 {
+        return None;
+    }
+    // The magic numbers fit in 32 bits, whatever width f_type has here
+    let name = match stat.f_type as u32 {
+        0xEF53 => "ext4",
+        0x9123_683E => "btrfs",
+        0x5846_5342 => "xfs",
+        0x2FC1_2FC1 => "zfs",
+        0x0102_1994 => "tmpfs",
+        0x794C_7630 => "overlay",
+        0x6969 => "nfs",
+        0xFF53_4D42 => "cifs",
+        0xFE53_4D42 => "smb2",
+        0x4D44 => "vfat",
+        0x2011_BAB0 => "exfat",
+        0x5346_544E => "ntfs",
+        0x6573_5546 => "fuse",
+        _ => return None,
+    };
+    Some(name.to_string())
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+fn volume_info_impl(_path: &Path) -> io::Result<VolumeInfo> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "free space query not supported on this platform",
+    ))
+}
+
+/// Formats a byte count as megabytes, rounded up, for messages.
+pub fn format_mb(bytes: u64) -> String {
+    format!("{} MB", bytes.div_ceil(1024 * 1024))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(any(target_os = "linux", target_os = "macos"))]
+    #[test]
+    fn test_available_space_for_missing_path_uses_ancestor() {
+        let dir = std::env::temp_dir();
+        let existing = available_space(&dir).unwrap();
+        let missing = available_space(&dir.join("sc_disk_missing/deeper/file.json")).unwrap();
+        assert!(existing > 0);
+        // Same volume; allow for other processes writing in between
+        assert!(missing.abs_diff(existing) < 1024 * 1024 * 1024);
+    }
+
+    #[cfg(any(target_os = "linux", target_os = "macos"))]
+    #[test]
+    fn test_volume_info_totals() {
+        let info = volume_info(&std::env::temp_dir()).unwrap();
+        assert!(info.total_bytes > 0);
+        assert!(info.free_bytes <= info.total_bytes);
+    }
+
+    struct Fixed(u64);
+
+    impl SpaceProvider for Fixed {
+        fn available_space(&self, _path: &Path) -> io::Result<u64> {
+            Ok(self.0)
+        }
+    }
+
+    struct Unsupported;
+
+    impl SpaceProvider for Unsupported {
+        fn available_space(&self, _path: &Path) -> io::Result<u64> {
+            Err(io::ErrorKind::Unsupported.into())
+        }
+    }
+
+    #[test]
+    fn test_ensure_space() {
+        let dest = Path::new("/backup/photos");
+        assert!(ensure_space(&Fixed(100), dest, 100).is_ok());
+        assert!(ensure_space(&Unsupported, dest, u64::MAX).is_ok());
+
+        let err = ensure_space(&Fixed(99), dest, 100).unwrap_err();
+        assert_eq!(err.code(), "insufficientSpace");
+        assert!(matches!(
+            err,
+            CommandError::InsufficientSpace {
+                required: 100,
+                available: 99,
+                ..
+            }
+        ));
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_parse_mounts_keeps_user_volumes() {
+        let table = "\
+sysfs /sys sysfs rw,nosuid 0 0
+proc /proc proc rw 0 0
+/dev/nvme0n1p2 / ext4 rw,relatime 0 0
+/dev/nvme0n1p1 /boot/efi vfat rw 0 0
+/dev/loop3 /snap/core/123 squashfs ro 0 0
+tmpfs /run/user/1000 tmpfs rw 0 0
+/dev/sdb1 /run/media/ana/USB\\040Stick vfat rw 0 0
+/dev/sdc1 /mnt/data ext4 rw 0 0
+/dev/sdc1 /mnt/data ext4 rw 0 0
+nas:/export /mnt/nas nfs4 rw 0 0
+/dev/sdd1 /devel ext4 rw 0 0
+/dev/sdd2 /system ext4 rw 0 0
+";
+        let mounts = parse_mounts(table, |device| device == "/dev/sdc1");
+        let summary: Vec<_> = mounts
+            .iter()
+            .map(|m| (m.name.as_str(), m.is_removable, m.is_network))
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                ("/", false, false),
+                ("USB Stick", true, false),
+                ("data", true, false),
+                ("nas", false, true),
+                ("devel", false, false),
+                ("system", false, false),
+            ]
+        );
+        assert_eq!(mounts[1].mount_point, Path::new("/run/media/ana/USB Stick"));
+    }
+
+    #[test]
+    fn test_hung_volume_is_marked_unavailable() {
+        let mount = |name: &str| Mount {
+            name: name.to_string(),
+            mount_point: PathBuf::from(format!("/{}", name)),
+            is_removable: false,
+            is_network: true,
+        };
+        fn answer(path: &Path) -> io::Result<VolumeInfo> {
+            match path.to_str() {
+                Some("/stale") => std::thread::sleep(Duration::from_secs(5)),
+                Some("/gone") => return Err(io::ErrorKind::NotFound.into()),
+                _ => {}
+            }
+            Ok(VolumeInfo {
+                total_bytes: 100,
+                free_bytes: 40,
+                filesystem: None,
+            })
+        }
+
+        let started = Instant::now();
+        let volumes = probe_volumes(
+            vec![mount("stale"), mount("ok"), mount("gone")],
+            Duration::from_millis(200),
+            answer,
+        );
+        assert!(started.elapsed() < Duration::from_secs(2));
+        let state: Vec<_> = volumes.iter().map(|v| (v.available, v.free_bytes)).collect();
+        assert_eq!(state, [(false, None), (true, Some(40)), (false, None)]);
+        assert_eq!(volumes[1].mount_point, "/ok");
+    }
+
+    #[cfg(any(target_os = "linux", target_os = "macos"))]
+    #[test]
+    fn test_list_volumes_includes_root() {
+        let volumes = list_volumes(Duration::from_secs(2)).unwrap();
+        assert!(volumes.iter().any(|v| v.mount_point == "/"));
+    }
+
+    #[test]
+    fn test_format_mb_rounds_up() {
+        assert_eq!(format_mb(0), "0 MB");
+        assert_eq!(format_mb(1), "1 MB");
+        assert_eq!(format_mb(3 * 1024 * 1024), "3 MB");
+    }
+}
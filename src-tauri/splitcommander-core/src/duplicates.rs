@@ -0,0 +1,227 @@
+//! Finds files with identical contents under one or more roots.
+//!
+//! Candidates are narrowed in three passes, from cheap to thorough: files of equal
+//! size, then equal hashes of their first and last 64 KiB, then equal SHA-256 of the
+//! whole file. Only the last pass reads files in full.
+
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use rayon::prelude::*;
+use serde::Serialize;
+use xxhash_rust::xxh3::xxh3_64;
+
+use crate::checksum::{hash_file, HashAlgorithm};
+use crate::ignore::IgnoreRules;
+use crate::model::EntryKind;
+use crate::scan::scan_directory;
+
+/// Bytes read from each end of a file for the partial hash.
+const EDGE_LEN: u64 = 64 * 1024;
+
+/// Files with the same contents.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DuplicateGroup {
+    /// SHA-256 of the contents, lowercase hex
+    pub hash: String,
+    /// Size of each file
+    pub size: u64,
+    /// Absolute paths, sorted; one per set of hard links
+    pub paths: Vec<String>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DuplicateSummary {
+    pub groups: usize,
+    /// Files beyond the first in each group
+    pub duplicate_files: usize,
+    /// Bytes freed by keeping one file per group
+    pub wasted_bytes: u64,
+    /// Candidates that could not be read, and so were left out
+    pub unreadable: usize,
+}
+
+struct Candidate {
+    path: PathBuf,
+    size: u64,
+}
+
+/// Looks for duplicate files under `roots`, skipping what `ignore_rules` matches and
+/// empty files. Each group goes to `on_group` as soon as it is confirmed, from one of
+/// at most `workers` threads. Hard links to one file count as a single file, listed
+/// under the first path found.
+pub fn find_duplicates(
+    roots: &[PathBuf],
+    ignore_rules: &IgnoreRules,
+    workers: usize,
+    cancel_flag: &AtomicBool,
+    on_group: &(dyn Fn(DuplicateGroup) + Sync),
+) -> Result<DuplicateSummary, String> {
+    let mut by_size: HashMap<u64, Vec<Candidate>> = HashMap::new();
+    let mut seen_links = HashSet::new();
+    for root in roots {
+        let scan = scan_directory(root, ignore_rules, &[], cancel_flag, &|_| {})?;
+        for (rel_path, meta) in scan.listed() {
+            if meta.kind != EntryKind::File || meta.size == 0 {
+                continue;
+            }
+            if meta.link.is_some_and(|link| !seen_links.insert(link)) {
+                continue;
+            }
+            by_size.entry(meta.size).or_default().push(Candidate {
+                path: root.join(rel_path),
+                size: meta.size,
+            });
+        }
+    }
+    let buckets: Vec<Vec<Candidate>> = by_size.into_values().filter(|c| c.len() > 1).collect();
+
+    let pool = rayon::ThreadPoolBuilder::new()
+        .num_threads(workers.max(1))
+        .build()
+        .map_err(|e| e.to_string())?;
+    let summary = pool.install(|| {
+        buckets
+            .into_par_iter()
+            .map(|bucket| confirm_bucket(bucket, cancel_flag, on_group))
+            .reduce(DuplicateSummary::default, |a, b| DuplicateSummary {
+                groups: a.groups + b.groups,
+                duplicate_files: a.duplicate_files + b.duplicate_files,
+                wasted_bytes: a.wasted_bytes + b.wasted_bytes,
+                unreadable: a.unreadable + b.unreadable,
+            })
+    });
+
+    if cancel_flag.load(Ordering::Relaxed) {
+        return Err("Duplicate search cancelled".to_string());
+    }
+    Ok(summary)
+}
+
+/// Splits same-size candidates by partial and then full hash, reporting every set of
+/// two or more that survives.
+fn confirm_bucket(
+    bucket: Vec<Candidate>,
+    cancel_flag: &AtomicBool,
+    on_group: &(dyn Fn(DuplicateGroup) + Sync),
+) -> DuplicateSummary {
+    let mut summary = DuplicateSummary::default();
+    let mut by_edges: HashMap<u64, Vec<Candidate>> = HashMap::new();
+    for candidate in bucket {
+        if cancel_flag.load(Ordering::Relaxed) {
+            return summary;
+        }
+        match edge_hash(&candidate.path, candidate.size) {
+            Ok(hash) => by_edges.entry(hash).or_default().push(candidate),
+            Err(_) => summary.unreadable += 1,
+        }
+    }
+
+    for candidates in by_edges.into_values().filter(|c| c.len() > 1) {
+        let mut by_hash: HashMap<String, Vec<Candidate>> = HashMap::new();
+        for candidate in candidates {
+            match hash_file(&candidate.path, HashAlgorithm::Sha256, cancel_flag, &|_, _| {}) {
+                Ok((hash, _)) => by_hash.entry(hash).or_default().push(candidate),
+                Err(_) if cancel_flag.load(Ordering::Relaxed) => return summary,
+                Err(_) => summary.unreadable += 1,
+            }
+        }
+        for (hash, group) in by_hash.into_iter().filter(|(_, g)| g.len() > 1) {
+            let size = group[0].size;
+            let mut paths: Vec<String> =
+                group.iter().map(|c| c.path.to_string_lossy().to_string()).collect();
+            paths.sort();
+            summary.groups += 1;
+            summary.duplicate_files += paths.len() - 1;
+            summary.wasted_bytes += size * (paths.len() as u64 - 1);
+            on_group(DuplicateGroup { hash, size, paths });
+        }
+    }
+    summary
+}
+
+/// Hash of the first and last [`EDGE_LEN`] bytes; the whole file if it is smaller.
+fn edge_hash(path: &Path, size: u64) -> io::Result<u64> {
+    let mut file = File::open(path)?;
+    let mut bytes = Vec::new();
+    (&mut file).take(EDGE_LEN).read_to_end(&mut bytes)?;
+    if size > EDGE_LEN {
+        file.seek(SeekFrom::Start(size.saturating_sub(EDGE_LEN).max(EDGE_LEN)))?;
+        file.take(EDGE_LEN).read_to_end(&mut bytes)?;
+    }
+    Ok(xxh3_64(&bytes))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    fn write(path: &Path, contents: &[u8]) {
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, contents).unwrap();
+    }
+
+    fn find(roots: &[PathBuf]) -> (Vec<DuplicateGroup>, DuplicateSummary) {
+        let groups = Mutex::new(Vec::new());
+        let cancel = AtomicBool::new(false);
+        let summary = find_duplicates(roots, &IgnoreRules::new(&[]), 2, &cancel, &|group| {
+            groups.lock().unwrap().push(group);
+        })
+        .unwrap();
+        (groups.into_inner().unwrap(), summary)
+    }
+
+    #[test]
+    fn test_groups_identical_files_across_roots() {
+        let base = std::env::temp_dir().join("sc_duplicates");
+        let _ = std::fs::remove_dir_all(&base);
+        let (left, right) = (base.join("l"), base.join("r"));
+        let big: Vec<u8> = (0..200_000u32).map(|i| (i % 251) as u8).collect();
+        let mut big_changed = big.clone();
+        // Same edges, different middle: only the full hash tells them apart
+        big_changed[100_000] ^= 1;
+        write(&left.join("a.txt"), b"same");
+        write(&left.join("sub/b.txt"), b"same");
+        write(&right.join("c.txt"), b"same");
+        write(&right.join("d.txt"), b"diff");
+        write(&left.join("big.bin"), &big);
+        write(&right.join("big.bin"), &big_changed);
+        write(&left.join("empty1"), b"");
+        write(&left.join("empty2"), b"");
+
+        let (groups, summary) = find(&[left.clone(), right.clone()]);
+        let _ = std::fs::remove_dir_all(&base);
+
+        assert_eq!(groups.len(), 1);
+        let group = &groups[0];
+        assert_eq!(group.size, 4);
+        let expected: Vec<String> =
+            [left.join("a.txt"), left.join("sub/b.txt"), right.join("c.txt")]
+            .iter()
+            .map(|p| p.to_string_lossy().to_string())
+            .collect();
+        assert_eq!(group.paths, expected);
+        assert_eq!((summary.groups, summary.duplicate_files, summary.wasted_bytes), (1, 2, 8));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_hard_links_are_one_file() {
+        let root = std::env::temp_dir().join("sc_duplicates_links");
+        let _ = std::fs::remove_dir_all(&root);
+        write(&root.join("a.txt"), b"linked");
+        std::fs::hard_link(root.join("a.txt"), root.join("b.txt")).unwrap();
+
+        let (groups, summary) = find(std::slice::from_ref(&root));
+        let _ = std::fs::remove_dir_all(&root);
+
+        assert!(groups.is_empty());
+        assert_eq!(summary, DuplicateSummary::default());
+    }
+}
@@ -0,0 +1,343 @@
+//! Error type returned to the frontend by commands and file operations.
+//!
+//! Serialized as `{ "code": "alreadyExists", "message": "...", "path": "..." | null }`.
+//! `code` is stable so the UI can offer the right follow-up (e.g. overwrite), and
+//! `message` is the human-readable text shown in toasts. `insufficientSpace` also
+//! carries `required` and `available` byte counts.
+
+use std::io;
+use std::path::Path;
+
+use serde::Serialize;
+
+use crate::disk::format_mb;
+use crate::security::SecurityError;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, thiserror::Error)]
+#[serde(tag = "code", rename_all = "camelCase")]
+pub enum CommandError {
+    #[error("{message}")]
+    NotFound {
+        message: String,
+        path: Option<String>,
+    },
+    #[error("{message}")]
+    AlreadyExists {
+        message: String,
+        path: Option<String>,
+    },
+    #[error("{message}")]
+    PermissionDenied {
+        message: String,
+        path: Option<String>,
+    },
+    /// Another run of the same job is still going
+    #[error("{message}")]
+    Busy {
+        message: String,
+        path: Option<String>,
+    },
+    #[error("{message}")]
+    Cancelled {
+        message: String,
+        path: Option<String>,
+    },
+    #[error("{message}")]
+    InvalidArgument {
+        message: String,
+        path: Option<String>,
+    },
+    #[error("{message}")]
+    Io {
+        message: String,
+        path: Option<String>,
+    },
+    /// A copied file read back differently from its source; the copy was removed
+    #[error("{message}")]
+    VerificationFailed {
+        message: String,
+        path: Option<String>,
+    },
+    /// The destination volume has less free space than the operation needs
+    #[error("{message}")]
+    InsufficientSpace {
+        message: String,
+        path: Option<String>,
+        required: u64,
+        available: u64,
+    },
+    /// Read-only mode is on, so nothing may be changed
+    #[error("{message}")]
+    ReadOnlyMode {
+        message: String,
+        path: Option<String>,
+    },
+    /// A background task panicked or was aborted before it could report a result
+    #[error("{message}")]
+    TaskFailed {
+        message: String,
+        path: Option<String>,
+    },
+    /// Anything without a more specific code, e.g. "no comparison result yet"
+    #[error("{message}")]
+    Other {
+        message: String,
+        path: Option<String>,
+    },
+}
+
+impl CommandError {
+    pub fn not_found(message: impl Into<String>, path: &Path) -> Self {
+        Self::NotFound {
+            message: message.into(),
+            path: Some(path.display().to_string()),
+        }
+    }
+
+    pub fn already_exists(message: impl Into<String>, path: &Path) -> Self {
+        Self::AlreadyExists {
+            message: message.into(),
+            path: Some(path.display().to_string()),
+        }
+    }
+
+    pub fn cancelled(message: impl Into<String>) -> Self {
+        Self::Cancelled {
+            message: message.into(),
+            path: None,
+        }
+    }
+
+    pub fn invalid_argument(message: impl Into<String>) -> Self {
+        Self::InvalidArgument {
+            message: message.into(),
+            path: None,
+        }
+    }
+
+    pub fn read_only_mode(message: impl Into<String>) -> Self {
+        Self::ReadOnlyMode {
+            message: message.into(),
+            path: None,
+        }
+    }
+
+    pub fn task_failed(message: impl Into<String>) -> Self {
+        Self::TaskFailed {
+            message: message.into(),
+            path: None,
+        }
+    }
+
+    pub fn insufficient_space(path: &Path, required: u64, available: u64) -> Self {
+        Self::InsufficientSpace {
+            message: format!(
+                "Not enough space at {}: need ~{}, {} available",
+                path.display(),
+                format_mb(required),
+                format_mb(available)
+            ),
+            path: Some(path.display().to_string()),
+            required,
+            available,
+        }
+    }
+
+    /// Wraps an I/O failure on `path` as "`context`: `err`", picking the code from the
+    /// error kind.
+    pub fn io(context: impl std::fmt::Display, path: &Path, err: &io::Error) -> Self {
+        let message = format!("{}: {}", context, err);
+        let path = Some(path.display().to_string());
+        match err.kind() {
+            io::ErrorKind::NotFound => Self::NotFound { message, path },
+            io::ErrorKind::AlreadyExists => Self::AlreadyExists { message, path },
+            io::ErrorKind::PermissionDenied => Self::PermissionDenied { message, path },
+            _ => Self::Io { message, path },
+        }
+    }
+
+    /// The stable wire code, e.g. `"alreadyExists"`.
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::NotFound { .. } => "notFound",
+            Self::AlreadyExists { .. } => "alreadyExists",
+            Self::PermissionDenied { .. } => "permissionDenied",
+            Self::Busy { .. } => "busy",
+            Self::Cancelled { .. } => "cancelled",
+            Self::InvalidArgument { .. } => "invalidArgument",
+            Self::Io { .. } => "io",
+            Self::VerificationFailed { .. } => "verificationFailed",
+            Self::InsufficientSpace { .. } => "insufficientSpace",
+            Self::ReadOnlyMode { .. } => "readOnlyMode",
+            Self::TaskFailed { .. } => "taskFailed",
+            Self::Other { .. } => "other",
+        }
+    }
+
+    /// Rewrites the message, keeping the code and path; e.g. to say which side failed.
+    pub fn map_message(mut self, f: impl FnOnce(&str) -> String) -> Self {
+        let message = match &mut self {
+            Self::NotFound { message, .. }
+            | Self::AlreadyExists { message, .. }
+            | Self::PermissionDenied { message, .. }
+            | Self::Busy { message, .. }
+            | Self::Cancelled { message, .. }
+            | Self::InvalidArgument { message, .. }
+            | Self::Io { message, .. }
+            | Self::VerificationFailed { message, .. }
+            | Self::InsufficientSpace { message, .. }
+            | Self::ReadOnlyMode { message, .. }
+            | Self::TaskFailed { message, .. }
+            | Self::Other { message, .. } => message,
+        };
+        *message = f(message);
+        self
+    }
+
+    pub fn path(&self) -> Option<&str> {
+        match self {
+            Self::NotFound { path, .. }
+            | Self::AlreadyExists { path, .. }
+            | Self::PermissionDenied { path, .. }
+            | Self::Busy { path, .. }
+            | Self::Cancelled { path, .. }
+            | Self::InvalidArgument { path, .. }
+            | Self::Io { path, .. }
+            | Self::VerificationFailed { path, .. }
+            | Self::InsufficientSpace { path, .. }
+            | Self::ReadOnlyMode { path, .. }
+            | Self::TaskFailed { path, .. }
+            | Self::Other { path, .. } => path.as_deref(),
+        }
+    }
+}
+
+/// Plain messages from code that has no structured error yet.
+impl From<String> for CommandError {
+    fn from(message: String) -> Self {
+        Self::Other {
+            message,
+            path: None,
+        }
+    }
+}
+
+impl From<&str> for CommandError {
+    fn from(message: &str) -> Self {
+        message.to_string().into()
+    }
+}
+
+impl From<SecurityError> for CommandError {
+    fn from(err: SecurityError) -> Self {
+        let message = err.to_string();
+        match err {
+            SecurityError::EscapedRoot { target, .. } => Self::PermissionDenied {
+                message,
+                path: Some(target.display().to_string()),
+            },
+            SecurityError::TraversalAttempt { path } => Self::InvalidArgument {
+                message,
+                path: Some(path),
+            },
+            SecurityError::IoError { path, source } => Self::io("IO error", &path, &source),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_wire_shape() {
+        let err = CommandError::already_exists("Destination already exists: /d/a", Path::new("/d/a"));
+        assert_eq!(
+            serde_json::to_value(&err).unwrap(),
+            serde_json::json!({
+                "code": "alreadyExists",
+                "message": "Destination already exists: /d/a",
+                "path": "/d/a",
+            })
+        );
+        assert_eq!(err.to_string(), "Destination already exists: /d/a");
+
+        let other = CommandError::from("No comparison result available");
+        assert_eq!(
+            serde_json::to_value(&other).unwrap(),
+            serde_json::json!({
+                "code": "other",
+                "message": "No comparison result available",
+                "path": null,
+            })
+        );
+    }
+
+    #[test]
+    fn test_codes_match_serialized_tag() {
+        let path = Path::new("/x");
+        let errors = [
+            CommandError::not_found("m", path),
+            CommandError::already_exists("m", path),
+            CommandError::io("m", path, &io::Error::from(io::ErrorKind::PermissionDenied)),
+            CommandError::Busy {
+                message: "m".to_string(),
+                path: None,
+            },
+            CommandError::cancelled("m"),
+            CommandError::invalid_argument("m"),
+            CommandError::insufficient_space(path, 2, 1),
+            CommandError::io("m", path, &io::Error::other("boom")),
+            CommandError::task_failed("m"),
+            CommandError::from("m"),
+        ];
+        for err in errors {
+            assert_eq!(serde_json::to_value(&err).unwrap()["code"], err.code());
+        }
+    }
+
+    #[test]
+    fn test_io_error_kinds_map_to_codes() {
+        let path = Path::new("/missing");
+        let err = CommandError::io(
+            "Cannot read /missing",
+            path,
+            &io::Error::from(io::ErrorKind::NotFound),
+        );
+        assert_eq!(err.code(), "notFound");
+        assert_eq!(err.path(), Some("/missing"));
+        assert!(err.to_string().starts_with("Cannot read /missing: "));
+    }
+
+    #[test]
+    fn test_map_message_keeps_code_and_path() {
+        let err = CommandError::io(
+            "Cannot read /l",
+            Path::new("/l"),
+            &io::Error::from(io::ErrorKind::PermissionDenied),
+        )
+        .map_message(|m| format!("Left side: {}", m));
+        assert_eq!(err.code(), "permissionDenied");
+        assert_eq!(err.path(), Some("/l"));
+        assert!(err.to_string().starts_with("Left side: Cannot read /l: "));
+    }
+
+    #[test]
+    fn test_security_errors_convert() {
+        let escaped: CommandError = SecurityError::EscapedRoot {
+            root: PathBuf::from("/root"),
+            target: PathBuf::from("/etc"),
+        }
+        .into();
+        assert_eq!(escaped.code(), "permissionDenied");
+        assert_eq!(escaped.path(), Some("/etc"));
+
+        let traversal: CommandError = SecurityError::TraversalAttempt {
+            path: "../x".to_string(),
+        }
+        .into();
+        assert_eq!(traversal.code(), "invalidArgument");
+        assert_eq!(traversal.to_string(), "Path traversal attempt detected: ../x");
+    }
+}
@@ -0,0 +1,1327 @@
+use std::cell::Cell;
+use std::collections::{BTreeMap, HashSet};
+use std::io::{self, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use serde::ser::{Error as _, SerializeSeq};
+use serde::{Deserialize, Serialize, Serializer};
+
+use crate::model::{CompareMode, CompareSummary, DiffItem, DiffKind, EntryKind, EntryMeta, Side};
+
+const REPORT_VERSION: &str = "0.1.0";
+
+/// Entries between two progress reports while streaming a report.
+const PROGRESS_EVERY: usize = 1000;
+
+/// HTML reports list at most this many diffs; browsers struggle with larger tables.
+pub const HTML_MAX_ROWS: usize = 50_000;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ExportFormat {
+    #[default]
+    Json,
+    /// Self-contained page for reading and sharing
+    Html,
+    /// Differences only, grouped by kind, for pasting into issues
+    Markdown,
+    /// POSIX shell script that copies left → right; see [`generate_sync_script`]
+    SyncScript,
+}
+
+/// Streams a report in `format` to `out`. Returns the number of bytes written.
+///
+/// - `full_summary`: the unfiltered summary when `diffs` is a subset (JSON reports only)
+/// - `filter`: how `diffs` was picked when it is a subset (JSON reports only)
+/// - `delete_extras`: sync scripts also remove right-only entries
+/// - `progress`: called with (entries written, bytes written) every so many diffs and once at the end
+#[allow(clippy::too_many_arguments)]
+pub fn write_report<W: Write>(
+    format: ExportFormat,
+    out: W,
+    left_root: &str,
+    right_root: &str,
+    mode: CompareMode,
+    summary: &CompareSummary,
+    full_summary: Option<&CompareSummary>,
+    filter: Option<&ReportFilter>,
+    delete_extras: bool,
+    diffs: &[DiffItem],
+    cancel_flag: &AtomicBool,
+    progress: &dyn Fn(usize, u64),
+) -> Result<u64, String> {
+    let write_all = |mut out: W, text: String| {
+        out.write_all(text.as_bytes()).map_err(|e| e.to_string())?;
+        out.flush().map_err(|e| e.to_string())?;
+        progress(diffs.len(), text.len() as u64);
+        Ok(text.len() as u64)
+    };
+    match format {
+        ExportFormat::Json => write_json_report(
+            out,
+            left_root,
+            right_root,
+            mode,
+            summary,
+            full_summary,
+            filter,
+            diffs,
+            cancel_flag,
+            progress,
+        ),
+        ExportFormat::Html => write_html_report(
+            out,
+            left_root,
+            right_root,
+            mode,
+            summary,
+            diffs,
+            cancel_flag,
+            progress,
+        ),
+        ExportFormat::Markdown => write_all(
+            out,
+            generate_markdown_report(left_root, right_root, mode, summary, diffs),
+        ),
+        ExportFormat::SyncScript => write_all(
+            out,
+            generate_sync_script(left_root, right_root, diffs, delete_extras),
+        ),
+    }
+}
+
+/// Clones the diffs matching `kinds` (all when `None`) that lie at or under
+/// `path_prefix`, e.g. `src` or `src/` selects `src` and everything below it.
+pub fn filter_diffs(
+    diffs: &[DiffItem],
+    kinds: Option<&[DiffKind]>,
+    path_prefix: Option<&str>,
+) -> Vec<DiffItem> {
+    let prefix = path_prefix
+        .map(|p| p.trim_matches('/'))
+        .filter(|p| !p.is_empty());
+    diffs
+        .iter()
+        .filter(|d| kinds.is_none_or(|k| k.contains(&d.diff_kind)))
+        .filter(|d| {
+            prefix.is_none_or(|p| {
+                d.rel_path
+                    .strip_prefix(p)
+                    .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+            })
+        })
+        .cloned()
+        .collect()
+}
+
+/// Rough upper bound on the size of a report for `diffs`, for free-space checks.
+pub fn estimate_report_size(format: ExportFormat, diffs: &[DiffItem]) -> u64 {
+    match format {
+        ExportFormat::Json => estimate_json_report_size(diffs),
+        // A row is smaller than a JSON entry, but escaping can grow a path up to 6x
+        ExportFormat::Html => {
+            let shown = &diffs[..diffs.len().min(HTML_MAX_ROWS)];
+            estimate_json_report_size(shown)
+                + shown.iter().map(|d| 5 * d.rel_path.len() as u64).sum::<u64>()
+        }
+        ExportFormat::Markdown => estimate_json_report_size(diffs),
+        // At most a mkdir and a copy naming the path twice per entry
+        ExportFormat::SyncScript => {
+            4096 + diffs
+                .iter()
+                .map(|d| 64 + 16 * d.rel_path.len() as u64)
+                .sum::<u64>()
+        }
+    }
+}
+
+/// How the diffs of a report were picked from the whole comparison, with
+/// [`filter_diffs`].
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReportFilter {
+    /// Diff kinds kept; every kind when `None`
+    pub kinds: Option<Vec<DiffKind>>,
+    /// Folder whose diffs were kept; everywhere when `None`
+    pub path_prefix: Option<String>,
+}
+
+impl ReportFilter {
+    fn covers(&self, rel_path: &str) -> bool {
+        match self.path_prefix.as_deref().map(|p| p.trim_matches('/')) {
+            Some(prefix) if !prefix.is_empty() => rel_path
+                .strip_prefix(prefix)
+                .is_some_and(|rest| rest.is_empty() || rest.starts_with('/')),
+            _ => true,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExportReport {
+    pub version: String,
+    pub left_root: String,
+    pub right_root: String,
+    pub mode: CompareMode,
+    pub summary: CompareSummary,
+    /// Summary of the whole comparison when `summary` covers a filtered subset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub full_summary: Option<CompareSummary>,
+    /// How the diffs were picked when they are a filtered subset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub filter: Option<ReportFilter>,
+    pub diffs: Vec<DiffItem>,
+    #[serde(default)]
+    pub generated_at: String,
+}
+
+impl ExportReport {
+    /// Whether the report would list `rel_path` if it differed in any way, so that its
+    /// absence means the path was the same or gone. That takes a report that kept
+    /// every kind of diff, Same entries included, for the folder the path is in.
+    fn could_list(&self, rel_path: &str) -> bool {
+        let Some(full_summary) = &self.full_summary else {
+            return !crate::refresh::omits_same(&self.summary, &self.diffs);
+        };
+        // Filtered by a version that did not record how
+        let Some(filter) = &self.filter else {
+            return false;
+        };
+        let lists_same =
+            full_summary.same == 0 || self.diffs.iter().any(|d| d.diff_kind == DiffKind::Same);
+        filter.kinds.is_none() && lists_same && filter.covers(rel_path)
+    }
+}
+
+pub fn generate_json_report(
+    left_root: &str,
+    right_root: &str,
+    mode: CompareMode,
+    summary: CompareSummary,
+    diffs: Vec<DiffItem>,
+) -> Result<String, String> {
+    let report = ExportReport {
+        version: REPORT_VERSION.to_string(),
+        left_root: left_root.to_string(),
+        right_root: right_root.to_string(),
+        mode,
+        summary,
+        full_summary: None,
+        filter: None,
+        diffs,
+        generated_at: chrono::Utc::now().to_rfc3339(),
+    };
+    serde_json::to_string_pretty(&report).map_err(|e| e.to_string())
+}
+
+/// Parses a JSON report written by this or an earlier version. Fields added after a
+/// report was written take their defaults; reports from a newer format are rejected.
+pub fn read_json_report<R: io::Read>(reader: R) -> Result<ExportReport, String> {
+    let value: serde_json::Value = serde_json::from_reader(io::BufReader::new(reader))
+        .map_err(|e| format!("Not a valid report: {}", e))?;
+    let version = value
+        .get("version")
+        .and_then(serde_json::Value::as_str)
+        .ok_or("Not a SplitCommander report: no version field")?;
+    check_report_version(version)?;
+    serde_json::from_value(value).map_err(|e| format!("Not a valid report: {}", e))
+}
+
+/// Accepts any report whose major.minor is at most [`REPORT_VERSION`]'s; patch
+/// releases never change the format.
+fn check_report_version(version: &str) -> Result<(), String> {
+    let major_minor = |v: &str| -> Option<(u64, u64)> {
+        let mut parts = v.split('.');
+        Some((parts.next()?.parse().ok()?, parts.next()?.parse().ok()?))
+    };
+    let current = major_minor(REPORT_VERSION).expect("REPORT_VERSION is major.minor.patch");
+    match major_minor(version) {
+        Some(v) if v <= current => Ok(()),
+        Some(_) => Err(format!(
+            "Report version {} is newer than this app supports ({}); update SplitCommander to open it",
+            version, REPORT_VERSION
+        )),
+        None => Err(format!("Unrecognized report version '{}'", version)),
+    }
+}
+
+/// A path whose diff kind differs between two reports; `None` means the report has no
+/// entry for it.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReportChange {
+    pub rel_path: String,
+    pub old_kind: Option<DiffKind>,
+    pub new_kind: Option<DiffKind>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReportComparison {
+    pub old_left_root: String,
+    pub old_right_root: String,
+    pub new_left_root: String,
+    pub new_right_root: String,
+    /// The reports were taken of different roots; entries were still matched by path
+    pub roots_differ: bool,
+    /// A report leaves entries out, by a filter or by omitting Same entries, so a path
+    /// missing from it was only reported as appeared or disappeared where it would
+    /// have been listed
+    pub partial: bool,
+    pub old_generated_at: String,
+    pub new_generated_at: String,
+    pub changes: Vec<ReportChange>,
+}
+
+/// Lists, in path order, every path that appeared, disappeared or changed kind
+/// between `old` and `new`. Entries are matched on `rel_path` alone. A path listed by
+/// only one report counts only where the other would have listed it too (see
+/// [`ReportComparison::partial`]).
+pub fn compare_reports(old: &ExportReport, new: &ExportReport) -> ReportComparison {
+    let mut kinds: BTreeMap<&str, (Option<DiffKind>, Option<DiffKind>)> = BTreeMap::new();
+    for diff in &old.diffs {
+        kinds.entry(&diff.rel_path).or_default().0 = Some(diff.diff_kind);
+    }
+    for diff in &new.diffs {
+        kinds.entry(&diff.rel_path).or_default().1 = Some(diff.diff_kind);
+    }
+    let changes = kinds
+        .into_iter()
+        .filter(|(rel_path, (old_kind, new_kind))| {
+            old_kind != new_kind
+                && (old_kind.is_some() || old.could_list(rel_path))
+                && (new_kind.is_some() || new.could_list(rel_path))
+        })
+        .map(|(rel_path, (old_kind, new_kind))| ReportChange {
+            rel_path: rel_path.to_string(),
+            old_kind,
+            new_kind,
+        })
+        .collect();
+
+    ReportComparison {
+        old_left_root: old.left_root.clone(),
+        old_right_root: old.right_root.clone(),
+        new_left_root: new.left_root.clone(),
+        new_right_root: new.right_root.clone(),
+        roots_differ: old.left_root != new.left_root || old.right_root != new.right_root,
+        partial: [old, new].iter().any(|report| !report.could_list("")),
+        old_generated_at: old.generated_at.clone(),
+        new_generated_at: new.generated_at.clone(),
+        changes,
+    }
+}
+
+/// Streams the same JSON as [`generate_json_report`] to `out` without building it in
+/// memory first. Returns the number of bytes written.
+///
+/// - `full_summary`: the unfiltered summary, written as `fullSummary` when `diffs` is a subset
+/// - `filter`: how `diffs` was picked, written as `filter` when it is a subset
+/// - `progress`: called with (entries written, bytes written) every so many diffs and once at the end
+#[allow(clippy::too_many_arguments)]
+pub fn write_json_report<W: Write>(
+    out: W,
+    left_root: &str,
+    right_root: &str,
+    mode: CompareMode,
+    summary: &CompareSummary,
+    full_summary: Option<&CompareSummary>,
+    filter: Option<&ReportFilter>,
+    diffs: &[DiffItem],
+    cancel_flag: &AtomicBool,
+    progress: &dyn Fn(usize, u64),
+) -> Result<u64, String> {
+    let written = Cell::new(0u64);
+    let mut out = io::BufWriter::new(CountingWriter {
+        inner: out,
+        written: &written,
+    });
+    let report = StreamedReport {
+        version: REPORT_VERSION,
+        left_root,
+        right_root,
+        mode,
+        summary,
+        full_summary,
+        filter,
+        diffs: StreamedDiffs {
+            diffs,
+            written: &written,
+            cancel_flag,
+            progress,
+        },
+        generated_at: chrono::Utc::now().to_rfc3339(),
+    };
+    serde_json::to_writer_pretty(&mut out, &report).map_err(|e| e.to_string())?;
+    out.flush().map_err(|e| e.to_string())?;
+    progress(diffs.len(), written.get());
+    Ok(written.get())
+}
+
+/// Rough upper bound on the size of a JSON report for `diffs`, for free-space checks.
+pub fn estimate_json_report_size(diffs: &[DiffItem]) -> u64 {
+    // Pretty-printed entry with both sides present, excluding the path itself
+    const PER_ENTRY: u64 = 400;
+    const HEADER: u64 = 4096;
+    diffs
+        .iter()
+        .map(|d| {
+            let message = d.error_message.as_ref().map_or(0, |m| m.len());
+            let targets: usize = [&d.left, &d.right]
+                .iter()
+                .filter_map(|m| m.as_ref()?.symlink_target.as_ref())
+                .map(|t| t.len())
+                .sum();
+            PER_ENTRY + (d.rel_path.len() + message + targets) as u64
+        })
+        .sum::<u64>()
+        + HEADER
+}
+
+/// Borrowing twin of [`ExportReport`]; field order must stay identical.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct StreamedReport<'a> {
+    version: &'a str,
+    left_root: &'a str,
+    right_root: &'a str,
+    mode: CompareMode,
+    summary: &'a CompareSummary,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    full_summary: Option<&'a CompareSummary>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    filter: Option<&'a ReportFilter>,
+    diffs: StreamedDiffs<'a>,
+    generated_at: String,
+}
+
+struct StreamedDiffs<'a> {
+    diffs: &'a [DiffItem],
+    written: &'a Cell<u64>,
+    cancel_flag: &'a AtomicBool,
+    progress: &'a dyn Fn(usize, u64),
+}
+
+impl Serialize for StreamedDiffs<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut seq = serializer.serialize_seq(Some(self.diffs.len()))?;
+        for (i, diff) in self.diffs.iter().enumerate() {
+            if i > 0 && i.is_multiple_of(PROGRESS_EVERY) {
+                if self.cancel_flag.load(Ordering::Relaxed) {
+                    return Err(S::Error::custom("Export cancelled"));
+                }
+                // Bytes still sitting in the BufWriter are not counted yet
+                (self.progress)(i, self.written.get());
+            }
+            seq.serialize_element(diff)?;
+        }
+        seq.end()
+    }
+}
+
+struct CountingWriter<'a, W> {
+    inner: W,
+    written: &'a Cell<u64>,
+}
+
+impl<W: Write> Write for CountingWriter<'_, W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = self.inner.write(buf)?;
+        self.written.set(self.written.get() + n as u64);
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+const HTML_STYLE: &str = "body{font-family:system-ui,sans-serif;margin:2em;color:#222}\
+h1{font-size:1.4em}table{border-collapse:collapse;margin-bottom:1.5em}\
+th,td{border:1px solid #ccc;padding:4px 8px;text-align:left}\
+th{background:#f3f3f3}#diffs th{cursor:pointer}#diffs td.num{text-align:right}\
+.notice{background:#fff4ce;padding:8px;border:1px solid #e0c060}";
+
+/// Click-to-sort for the diff table; numeric columns sort by their `data-v` value.
+const HTML_SCRIPT: &str = "document.querySelectorAll('#diffs th').forEach(function(th,i){\
+th.addEventListener('click',function(){var tb=document.querySelector('#diffs tbody');\
+var asc=th.dataset.dir!=='asc';th.dataset.dir=asc?'asc':'desc';\
+var key=function(r){var c=r.cells[i];return c.dataset.v!==undefined?Number(c.dataset.v):c.textContent.toLowerCase();};\
+Array.from(tb.rows).sort(function(a,b){var x=key(a),y=key(b);return (x<y?-1:x>y?1:0)*(asc?1:-1);})\
+.forEach(function(r){tb.appendChild(r);});});});";
+
+/// Writes a single-file HTML report: roots, mode and time, a summary table, and a
+/// sortable table of at most [`HTML_MAX_ROWS`] diffs. Every path and message is
+/// escaped, so file names cannot inject markup. Returns the number of bytes written.
+#[allow(clippy::too_many_arguments)]
+pub fn write_html_report<W: Write>(
+    out: W,
+    left_root: &str,
+    right_root: &str,
+    mode: CompareMode,
+    summary: &CompareSummary,
+    diffs: &[DiffItem],
+    cancel_flag: &AtomicBool,
+    progress: &dyn Fn(usize, u64),
+) -> Result<u64, String> {
+    let written = Cell::new(0u64);
+    let mut out = io::BufWriter::new(CountingWriter {
+        inner: out,
+        written: &written,
+    });
+    let io_err = |e: io::Error| e.to_string();
+
+    write!(
+        out,
+        "<!DOCTYPE html>\n<html><head><meta charset=\"utf-8\"><title>SplitCommander report</title>\
+         <style>{}</style></head><body>\n<h1>Comparison report</h1>\n<table>\n",
+        HTML_STYLE
+    )
+    .map_err(io_err)?;
+    let header = [
+        ("Left", escape_html(left_root)),
+        ("Right", escape_html(right_root)),
+        ("Mode", mode_label(mode).to_string()),
+        ("Generated", chrono::Utc::now().to_rfc3339()),
+    ];
+    for (label, value) in header {
+        writeln!(out, "<tr><th>{}</th><td>{}</td></tr>", label, value).map_err(io_err)?;
+    }
+    writeln!(out, "</table>\n<h2>Summary</h2>\n<table>").map_err(io_err)?;
+    let counts = [
+        ("Entries on left", summary.total_left),
+        ("Entries on right", summary.total_right),
+        ("Same", summary.same),
+        ("Modified", summary.meta_diff),
+        ("Left newer", summary.left_newer),
+        ("Right newer", summary.right_newer),
+        ("Only on left", summary.only_left),
+        ("Only on right", summary.only_right),
+        ("Type mismatch", summary.type_mismatch),
+        ("Case collisions", summary.case_collisions),
+        ("Errors", summary.errors),
+    ];
+    for (label, count) in counts {
+        writeln!(out, "<tr><th>{}</th><td>{}</td></tr>", label, count).map_err(io_err)?;
+    }
+    writeln!(out, "</table>").map_err(io_err)?;
+    for warning in &summary.warnings {
+        writeln!(out, "<p class=\"notice\">{}</p>", escape_html(warning)).map_err(io_err)?;
+    }
+
+    let shown = diffs.len().min(HTML_MAX_ROWS);
+    writeln!(out, "<h2>Differences</h2>").map_err(io_err)?;
+    if shown < diffs.len() {
+        writeln!(
+            out,
+            "<p class=\"notice\">Showing the first {} of {} entries; {} omitted.</p>",
+            shown,
+            diffs.len(),
+            diffs.len() - shown
+        )
+        .map_err(io_err)?;
+    }
+    writeln!(
+        out,
+        "<table id=\"diffs\"><thead><tr><th>Path</th><th>Status</th><th>Left size</th>\
+         <th>Left modified</th><th>Right size</th><th>Right modified</th><th>Note</th></tr></thead>\n<tbody>"
+    )
+    .map_err(io_err)?;
+    for (i, diff) in diffs[..shown].iter().enumerate() {
+        if i > 0 && i.is_multiple_of(PROGRESS_EVERY) {
+            if cancel_flag.load(Ordering::Relaxed) {
+                return Err("Export cancelled".to_string());
+            }
+            progress(i, written.get());
+        }
+        write!(
+            out,
+            "<tr><td>{}</td><td>{}</td>",
+            escape_html(&diff.rel_path),
+            kind_label(diff.diff_kind)
+        )
+        .map_err(io_err)?;
+        for meta in [&diff.left, &diff.right] {
+            write_meta_cells(&mut out, meta.as_ref()).map_err(io_err)?;
+        }
+        let note = match (&diff.error_message, diff.newer) {
+            (Some(message), _) => escape_html(message),
+            (None, Some(Side::Left)) => "Left is newer".to_string(),
+            (None, Some(Side::Right)) => "Right is newer".to_string(),
+            (None, None) => String::new(),
+        };
+        writeln!(out, "<td>{}</td></tr>", note).map_err(io_err)?;
+    }
+    writeln!(
+        out,
+        "</tbody></table>\n<script>{}</script>\n</body></html>",
+        HTML_SCRIPT
+    )
+    .map_err(io_err)?;
+    out.flush().map_err(io_err)?;
+    progress(shown, written.get());
+    Ok(written.get())
+}
+
+fn write_meta_cells(out: &mut impl Write, meta: Option<&EntryMeta>) -> io::Result<()> {
+    let Some(meta) = meta else {
+        return write!(out, "<td class=\"num\" data-v=\"-1\"></td><td></td>");
+    };
+    let modified = meta
+        .modified
+        .and_then(|ms| chrono::DateTime::from_timestamp_millis(ms as i64))
+        .map(|t| t.format("%Y-%m-%d %H:%M:%S").to_string())
+        .unwrap_or_default();
+    write!(
+        out,
+        "<td class=\"num\" data-v=\"{}\">{}</td><td>{}</td>",
+        meta.size, meta.size, modified
+    )
+}
+
+fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn kind_label(kind: DiffKind) -> &'static str {
+    match kind {
+        DiffKind::OnlyLeft => "Only left",
+        DiffKind::OnlyRight => "Only right",
+        DiffKind::TypeMismatch => "Type mismatch",
+        DiffKind::Same => "Same",
+        DiffKind::MetaDiff => "Modified",
+        DiffKind::Error => "Error",
+        DiffKind::CaseCollision => "Case collision",
+        DiffKind::Special => "Special file",
+    }
+}
+
+fn mode_label(mode: CompareMode) -> &'static str {
+    match mode {
+        CompareMode::Structure => "Structure",
+        CompareMode::Smart => "Smart",
+        CompareMode::Timestamp => "Timestamp",
+    }
+}
+
+/// Renders a POSIX shell script that makes `right_root` match `left_root` for the
+/// given diffs: left-only and modified files are copied with `cp -p` (parents created
+/// with `mkdir -p`), left-only directories are created, and with `delete_extras`
+/// right-only entries are removed. Type mismatches, case collisions and errors need a
+/// decision and are only counted. Nothing runs until the user executes the script.
+pub fn generate_sync_script(
+    left_root: &str,
+    right_root: &str,
+    diffs: &[DiffItem],
+    delete_extras: bool,
+) -> String {
+    let mut body = String::new();
+    let mut made_dirs: HashSet<&str> = HashSet::new();
+    let mut removed_dirs: Vec<&str> = Vec::new();
+    let (mut copies, mut dirs, mut removals, mut skipped) = (0, 0, 0, 0);
+
+    for diff in diffs {
+        let path = diff.rel_path.as_str();
+        match (diff.diff_kind, diff.left.as_ref(), diff.right.as_ref()) {
+            (DiffKind::OnlyLeft, Some(meta), _) if meta.kind == EntryKind::Dir => {
+                if made_dirs.insert(path) {
+                    body.push_str(&format!("mkdir -p \"$R\"/{}\n", shell_quote(path)));
+                    dirs += 1;
+                }
+            }
+            (DiffKind::OnlyLeft | DiffKind::MetaDiff, Some(meta), _)
+                if meta.kind != EntryKind::Dir =>
+            {
+                if let Some((parent, _)) = path.rsplit_once('/') {
+                    if made_dirs.insert(parent) {
+                        body.push_str(&format!("mkdir -p \"$R\"/{}\n", shell_quote(parent)));
+                    }
+                }
+                let quoted = shell_quote(path);
+                match (meta.kind, &meta.symlink_target) {
+                    (EntryKind::Symlink, Some(target)) => body.push_str(&format!(
+                        "rm -f \"$R\"/{q} && ln -s {} \"$R\"/{q}\n",
+                        shell_quote(target),
+                        q = quoted
+                    )),
+                    _ => body.push_str(&format!("cp -p \"$L\"/{q} \"$R\"/{q}\n", q = quoted)),
+                }
+                copies += 1;
+            }
+            // Directory timestamps alone don't need a copy
+            (DiffKind::MetaDiff, Some(_), _) => {}
+            (DiffKind::OnlyRight, _, Some(meta)) if delete_extras => {
+                // Children of a removed directory go with it
+                if removed_dirs.iter().any(|dir| {
+                    path.strip_prefix(dir)
+                        .is_some_and(|rest| rest.starts_with('/'))
+                }) {
+                    continue;
+                }
+                if meta.kind == EntryKind::Dir {
+                    body.push_str(&format!("rm -rf \"$R\"/{}\n", shell_quote(path)));
+                    removed_dirs.push(path);
+                } else {
+                    body.push_str(&format!("rm -f \"$R\"/{}\n", shell_quote(path)));
+                }
+                removals += 1;
+            }
+            (DiffKind::Same | DiffKind::Special | DiffKind::OnlyRight, _, _) => {}
+            _ => skipped += 1,
+        }
+    }
+
+    let mut script = String::from("#!/bin/sh\n");
+    script.push_str("# SplitCommander sync script: makes the right tree match the left.\n");
+    script.push_str(&format!("# Generated: {}\n", chrono::Utc::now().to_rfc3339()));
+    script.push_str(&format!(
+        "# Dry run summary: {} files to copy, {} directories to create, {} entries to delete\n",
+        copies, dirs, removals
+    ));
+    if skipped > 0 {
+        script.push_str(&format!(
+            "# {} entries need manual review (type mismatches, case collisions, errors)\n",
+            skipped
+        ));
+    }
+    script.push_str("# Review the commands below before running.\nset -e\n\n");
+    script.push_str(&format!("L={}\n", shell_quote(left_root)));
+    script.push_str(&format!("R={}\n\n", shell_quote(right_root)));
+    script.push_str(&body);
+    script
+}
+
+/// Single-quotes `text` for a POSIX shell; embedded quotes become `'\''`.
+fn shell_quote(text: &str) -> String {
+    format!("'{}'", text.replace('\'', r"'\''"))
+}
+
+/// Groups in a Markdown report, in order; Same entries are never listed.
+const MARKDOWN_GROUPS: [(DiffKind, &str); 7] = [
+    (DiffKind::OnlyLeft, "Only in left"),
+    (DiffKind::OnlyRight, "Only in right"),
+    (DiffKind::MetaDiff, "Modified"),
+    (DiffKind::TypeMismatch, "Type mismatch"),
+    (DiffKind::CaseCollision, "Case collisions"),
+    (DiffKind::Special, "Special files (not compared)"),
+    (DiffKind::Error, "Errors"),
+];
+
+/// Renders the non-Same diffs as Markdown tables grouped by kind, below the same
+/// metadata the JSON report carries. Empty groups are left out.
+pub fn generate_markdown_report(
+    left_root: &str,
+    right_root: &str,
+    mode: CompareMode,
+    summary: &CompareSummary,
+    diffs: &[DiffItem],
+) -> String {
+    let mut md = String::new();
+    md.push_str("# Comparison report\n\n");
+    md.push_str(&format!("- **Version:** {}\n", REPORT_VERSION));
+    md.push_str(&format!("- **Left:** `{}`\n", escape_code(left_root)));
+    md.push_str(&format!("- **Right:** `{}`\n", escape_code(right_root)));
+    md.push_str(&format!("- **Mode:** {}\n", mode_label(mode)));
+    md.push_str(&format!("- **Generated:** {}\n\n", chrono::Utc::now().to_rfc3339()));
+    md.push_str(&format!(
+        "{} left, {} right: {} same, {} modified, {} only in left, {} only in right, \
+         {} type mismatches, {} case collisions, {} errors\n",
+        summary.total_left,
+        summary.total_right,
+        summary.same,
+        summary.meta_diff,
+        summary.only_left,
+        summary.only_right,
+        summary.type_mismatch,
+        summary.case_collisions,
+        summary.errors
+    ));
+    for warning in &summary.warnings {
+        md.push_str(&format!("\n> {}\n", escape_markdown(warning)));
+    }
+
+    for (kind, title) in MARKDOWN_GROUPS {
+        let group: Vec<&DiffItem> = diffs.iter().filter(|d| d.diff_kind == kind).collect();
+        if group.is_empty() {
+            continue;
+        }
+        md.push_str(&format!("\n## {} ({})\n\n", title, group.len()));
+        md.push_str("| Path | Left | Right | Note |\n|---|---|---|---|\n");
+        for diff in group {
+            let note = match (&diff.error_message, diff.newer) {
+                (Some(message), _) => escape_markdown(message),
+                (None, Some(Side::Left)) => "Left is newer".to_string(),
+                (None, Some(Side::Right)) => "Right is newer".to_string(),
+                (None, None) => String::new(),
+            };
+            md.push_str(&format!(
+                "| {} | {} | {} | {} |\n",
+                escape_markdown(&diff.rel_path),
+                markdown_meta(diff.left.as_ref()),
+                markdown_meta(diff.right.as_ref()),
+                note
+            ));
+        }
+    }
+    md
+}
+
+fn markdown_meta(meta: Option<&EntryMeta>) -> String {
+    match meta {
+        Some(meta) if meta.kind == EntryKind::Dir => "dir".to_string(),
+        Some(meta) => format!("{} B", meta.size),
+        None => String::new(),
+    }
+}
+
+/// Escapes text for a table cell: pipes and backticks would break the row or open a
+/// code span, and a leading `#` reads as a heading in some renderers.
+fn escape_markdown(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for (i, c) in text.chars().enumerate() {
+        match c {
+            '\\' | '|' | '`' => {
+                escaped.push('\\');
+                escaped.push(c);
+            }
+            '#' if i == 0 => escaped.push_str("\\#"),
+            '\n' | '\r' => escaped.push(' '),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Roots go in code spans, where backslash escapes don't apply; backticks are the
+/// only character that can end the span early.
+fn escape_code(text: &str) -> String {
+    text.replace('`', "'")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::*;
+
+    #[test]
+    fn test_generate_json_report() {
+        let summary = CompareSummary {
+            total_left: 1,
+            total_right: 1,
+            same: 1,
+            ..Default::default()
+        };
+        let diffs = vec![DiffItem {
+            rel_path: "test.txt".to_string(),
+            diff_kind: DiffKind::Same,
+            left: Some(EntryMeta {
+                kind: EntryKind::File,
+                size: 10,
+                modified: Some(1000),
+                symlink_target: None,
+                mode: None,
+                link: None,
+            }),
+            right: Some(EntryMeta {
+                kind: EntryKind::File,
+                size: 10,
+                modified: Some(1000),
+                symlink_target: None,
+                mode: None,
+                link: None,
+            }),
+            error_message: None,
+            newer: None,
+        }];
+
+        let json = generate_json_report("/left", "/right", CompareMode::Smart, summary, diffs);
+        assert!(json.is_ok());
+        let json = json.unwrap();
+        assert!(json.contains("\"version\": \"0.1.0\""));
+        assert!(json.contains("\"leftRoot\": \"/left\""));
+        assert!(json.contains("generatedAt"));
+    }
+
+    #[test]
+    fn test_report_includes_scan_errors() {
+        let summary = CompareSummary {
+            errors: 1,
+            ..Default::default()
+        };
+        let diffs = vec![DiffItem {
+            rel_path: "private".to_string(),
+            diff_kind: DiffKind::Error,
+            left: None,
+            right: None,
+            error_message: Some("Left: Permission denied".to_string()),
+            newer: None,
+        }];
+
+        let json =
+            generate_json_report("/left", "/right", CompareMode::Smart, summary, diffs).unwrap();
+        let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(value["summary"]["errors"], 1);
+        assert_eq!(value["diffs"][0]["diffKind"], "error");
+        assert_eq!(value["diffs"][0]["errorMessage"], "Left: Permission denied");
+    }
+
+    fn sample_diffs(n: usize) -> Vec<DiffItem> {
+        (0..n)
+            .map(|i| DiffItem {
+                rel_path: format!("dir/file_{}.txt", i),
+                diff_kind: DiffKind::OnlyLeft,
+                left: Some(EntryMeta {
+                    kind: EntryKind::File,
+                    size: i as u64,
+                    modified: Some(1000),
+                    symlink_target: None,
+                    mode: None,
+                    link: None,
+                }),
+                right: None,
+                error_message: None,
+                newer: None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_streamed_report_matches_generated() {
+        let diffs = sample_diffs(2500);
+        let summary = CompareSummary {
+            only_left: diffs.len(),
+            ..Default::default()
+        };
+        let ticks = std::cell::RefCell::new(Vec::new());
+        let mut out = Vec::new();
+        let cancel = AtomicBool::new(false);
+        let bytes = write_json_report(
+            &mut out,
+            "/left",
+            "/right",
+            CompareMode::Smart,
+            &summary,
+            None,
+            None,
+            &diffs,
+            &cancel,
+            &|entries, bytes| ticks.borrow_mut().push((entries, bytes)),
+        )
+        .unwrap();
+
+        let generated = generate_json_report(
+            "/left",
+            "/right",
+            CompareMode::Smart,
+            summary,
+            diffs.clone(),
+        )
+        .unwrap();
+        let strip = |json: &str| -> serde_json::Value {
+            let mut value: serde_json::Value = serde_json::from_str(json).unwrap();
+            value.as_object_mut().unwrap().remove("generatedAt");
+            value
+        };
+        let streamed = String::from_utf8(out).unwrap();
+        assert_eq!(strip(&streamed), strip(&generated));
+        assert_eq!(bytes, streamed.len() as u64);
+        assert!(estimate_json_report_size(&diffs) >= bytes);
+
+        let ticks = ticks.into_inner();
+        assert_eq!(
+            ticks.iter().map(|t| t.0).collect::<Vec<_>>(),
+            vec![1000, 2000, 2500]
+        );
+        assert_eq!(ticks.last().unwrap().1, bytes);
+    }
+
+    #[test]
+    fn test_streamed_report_cancel() {
+        let diffs = sample_diffs(1500);
+        let cancel = AtomicBool::new(true);
+        let result = write_json_report(
+            Vec::new(),
+            "/left",
+            "/right",
+            CompareMode::Smart,
+            &CompareSummary::default(),
+            None,
+            None,
+            &diffs,
+            &cancel,
+            &|_, _| {},
+        );
+        assert!(result.unwrap_err().contains("cancelled"));
+    }
+
+    fn html_report(diffs: &[DiffItem]) -> String {
+        let mut out = Vec::new();
+        let cancel = AtomicBool::new(false);
+        let summary = CompareSummary {
+            only_left: diffs.len(),
+            ..Default::default()
+        };
+        write_html_report(
+            &mut out,
+            "/left",
+            "/right",
+            CompareMode::Smart,
+            &summary,
+            diffs,
+            &cancel,
+            &|_, _| {},
+        )
+        .unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn test_html_report_escapes_paths() {
+        let mut diffs = sample_diffs(1);
+        diffs[0].rel_path = "<script>alert('x')</script>.txt".to_string();
+        let html = html_report(&diffs);
+        assert!(html.contains("&lt;script&gt;alert(&#39;x&#39;)&lt;/script&gt;.txt"));
+        assert!(!html.contains("<script>alert"));
+        assert!(html.contains("<td>Only left</td>"));
+        assert!(!html.contains("omitted"));
+    }
+
+    #[test]
+    fn test_html_report_truncates_large_lists() {
+        let diffs = sample_diffs(HTML_MAX_ROWS + 25);
+        let html = html_report(&diffs);
+        assert!(html.contains(&format!(
+            "Showing the first {} of {} entries; 25 omitted.",
+            HTML_MAX_ROWS,
+            HTML_MAX_ROWS + 25
+        )));
+        assert_eq!(html.matches("<tr><td>").count(), HTML_MAX_ROWS);
+        assert!(estimate_report_size(ExportFormat::Html, &diffs) >= html.len() as u64);
+    }
+
+    #[test]
+    fn test_markdown_report_groups_and_escapes() {
+        let mut diffs = sample_diffs(2);
+        diffs[0].rel_path = "#notes|a`b`.md".to_string();
+        diffs[1].diff_kind = DiffKind::Same;
+        diffs.push(DiffItem {
+            rel_path: "broken".to_string(),
+            diff_kind: DiffKind::Error,
+            left: None,
+            right: None,
+            error_message: Some("Left: Permission denied".to_string()),
+            newer: None,
+        });
+        let summary = CompareSummary {
+            only_left: 1,
+            same: 1,
+            errors: 1,
+            ..Default::default()
+        };
+
+        let md = generate_markdown_report("/left", "/right", CompareMode::Smart, &summary, &diffs);
+        assert!(md.contains("- **Left:** `/left`"));
+        assert!(md.contains("- **Mode:** Smart"));
+        assert!(md.contains("## Only in left (1)"));
+        assert!(md.contains("## Errors (1)"));
+        assert!(!md.contains("Only in right"));
+        assert!(md.contains("| \\#notes\\|a\\`b\\`.md | 0 B |  |  |"));
+        assert!(md.contains("| broken |  |  | Left: Permission denied |"));
+        // Same entries are not listed
+        assert!(!md.contains("file_1.txt"));
+    }
+
+    #[test]
+    fn test_filtered_report_keeps_full_summary() {
+        let mut diffs = sample_diffs(6);
+        diffs[0].rel_path = "src/a.txt".to_string();
+        diffs[1].rel_path = "src/b.txt".to_string();
+        diffs[1].diff_kind = DiffKind::MetaDiff;
+        diffs[2].rel_path = "src/c.txt".to_string();
+        diffs[2].diff_kind = DiffKind::Same;
+        diffs[3].rel_path = "srcx/d.txt".to_string();
+        diffs[4].diff_kind = DiffKind::MetaDiff;
+        let mut full = CompareSummary {
+            warnings: vec!["case".to_string()],
+            ..Default::default()
+        };
+        full.recount(&diffs);
+
+        let kinds = [DiffKind::OnlyLeft, DiffKind::MetaDiff];
+        let subset = filter_diffs(&diffs, Some(&kinds), Some("src/"));
+        let filter = ReportFilter {
+            kinds: Some(kinds.to_vec()),
+            path_prefix: Some("src/".to_string()),
+        };
+        let mut summary = full.clone();
+        summary.recount(&subset);
+
+        let mut out = Vec::new();
+        let cancel = AtomicBool::new(false);
+        write_json_report(
+            &mut out,
+            "/left",
+            "/right",
+            CompareMode::Smart,
+            &summary,
+            Some(&full),
+            Some(&filter),
+            &subset,
+            &cancel,
+            &|_, _| {},
+        )
+        .unwrap();
+        let value: serde_json::Value = serde_json::from_slice(&out).unwrap();
+        let paths: Vec<&str> = value["diffs"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|d| d["relPath"].as_str().unwrap())
+            .collect();
+        assert_eq!(paths, vec!["src/a.txt", "src/b.txt"]);
+        assert_eq!(value["summary"]["onlyLeft"], 1);
+        assert_eq!(value["summary"]["metaDiff"], 1);
+        assert_eq!(value["summary"]["same"], 0);
+        assert_eq!(value["summary"]["totalLeft"], 2);
+        assert_eq!(value["summary"]["warnings"][0], "case");
+        assert_eq!(value["fullSummary"]["onlyLeft"], 3);
+        assert_eq!(value["fullSummary"]["metaDiff"], 2);
+        assert_eq!(value["fullSummary"]["same"], 1);
+        assert_eq!(value["fullSummary"]["totalLeft"], 6);
+        assert_eq!(
+            value["filter"]["kinds"],
+            serde_json::json!(["onlyLeft", "metaDiff"])
+        );
+        assert_eq!(value["filter"]["pathPrefix"], "src/");
+
+        // Unfiltered reports carry no fullSummary
+        let json = generate_json_report("/l", "/r", CompareMode::Smart, full, diffs).unwrap();
+        assert!(!json.contains("fullSummary"));
+    }
+
+    #[test]
+    fn test_report_round_trip() {
+        let mut diffs = sample_diffs(1200);
+        diffs[3].diff_kind = DiffKind::MetaDiff;
+        diffs[3].newer = Some(Side::Right);
+        let mut summary = CompareSummary::default();
+        summary.recount(&diffs);
+        let mut out = Vec::new();
+        let cancel = AtomicBool::new(false);
+        write_json_report(
+            &mut out,
+            "/left",
+            "/right",
+            CompareMode::Timestamp,
+            &summary,
+            None,
+            None,
+            &diffs,
+            &cancel,
+            &|_, _| {},
+        )
+        .unwrap();
+
+        let report = read_json_report(out.as_slice()).unwrap();
+        assert_eq!(report.left_root, "/left");
+        assert_eq!(report.mode, CompareMode::Timestamp);
+        assert_eq!(report.summary.only_left, summary.only_left);
+        assert_eq!(report.summary.right_newer, 1);
+        let page = |d: &[DiffItem]| {
+            serde_json::to_value(crate::compare::page_diffs(d, 0, None, None, None)).unwrap()
+        };
+        assert_eq!(page(&report.diffs), page(&diffs));
+    }
+
+    #[test]
+    fn test_read_report_versions() {
+        // Written before newer, errorMessage-free and summary extras existed
+        let old = r#"{"version":"0.0.9","leftRoot":"/l","rightRoot":"/r","mode":"smart",
+            "summary":{"totalLeft":1,"totalRight":0,"onlyLeft":1,"onlyRight":0,
+            "typeMismatch":0,"same":0,"metaDiff":0,"errors":0},
+            "diffs":[{"relPath":"a","diffKind":"onlyLeft",
+            "left":{"kind":"file","size":1,"modified":null,"symlinkTarget":null},"right":null}]}"#;
+        let report = read_json_report(old.as_bytes()).unwrap();
+        assert_eq!(report.diffs[0].newer, None);
+        assert!(report.summary.warnings.is_empty());
+        assert!(report.full_summary.is_none());
+
+        let future = old.replace("0.0.9", "0.2.0");
+        let err = read_json_report(future.as_bytes()).err().unwrap();
+        assert!(err.contains("newer than this app supports"), "{}", err);
+
+        let err = read_json_report(&b"{\"diffs\": []}"[..]).err().unwrap();
+        assert!(err.contains("no version"), "{}", err);
+    }
+
+    #[test]
+    fn test_compare_reports() {
+        let report = |left_root: &str, entries: &[(&str, DiffKind)]| ExportReport {
+            version: REPORT_VERSION.to_string(),
+            left_root: left_root.to_string(),
+            right_root: "/right".to_string(),
+            mode: CompareMode::Smart,
+            summary: CompareSummary::default(),
+            full_summary: None,
+            filter: None,
+            diffs: entries
+                .iter()
+                .map(|(path, kind)| DiffItem {
+                    rel_path: path.to_string(),
+                    diff_kind: *kind,
+                    left: None,
+                    right: None,
+                    error_message: None,
+                    newer: None,
+                })
+                .collect(),
+            generated_at: String::new(),
+        };
+        let old = report(
+            "/left",
+            &[
+                ("a.txt", DiffKind::Same),
+                ("b.txt", DiffKind::OnlyLeft),
+                ("gone.txt", DiffKind::MetaDiff),
+            ],
+        );
+        let new = report(
+            "/left",
+            &[
+                ("a.txt", DiffKind::MetaDiff),
+                ("b.txt", DiffKind::OnlyLeft),
+                ("added.txt", DiffKind::OnlyRight),
+            ],
+        );
+
+        let result = compare_reports(&old, &new);
+        assert!(!result.roots_differ);
+        let changes: Vec<_> = result
+            .changes
+            .iter()
+            .map(|c| (c.rel_path.as_str(), c.old_kind, c.new_kind))
+            .collect();
+        assert_eq!(
+            changes,
+            vec![
+                ("a.txt", Some(DiffKind::Same), Some(DiffKind::MetaDiff)),
+                ("added.txt", None, Some(DiffKind::OnlyRight)),
+                ("gone.txt", Some(DiffKind::MetaDiff), None),
+            ]
+        );
+
+        let moved = report("/elsewhere", &[("a.txt", DiffKind::Same)]);
+        let result = compare_reports(&old, &moved);
+        assert!(result.roots_differ);
+        let value = serde_json::to_value(&result).unwrap();
+        assert_eq!(value["newLeftRoot"], "/elsewhere");
+        assert_eq!(value["changes"][0]["oldKind"], "onlyLeft");
+        assert_eq!(value["changes"][0]["newKind"], serde_json::Value::Null);
+        assert_eq!(value["partial"], false);
+
+        let paths = |result: &ReportComparison| -> Vec<String> {
+            result.changes.iter().map(|c| c.rel_path.clone()).collect()
+        };
+        // Without its Same entries, a report cannot tell a path that became the same
+        // from one that is gone
+        let mut without_same = report(
+            "/left",
+            &[("a.txt", DiffKind::MetaDiff), ("b.txt", DiffKind::OnlyLeft)],
+        );
+        without_same.summary.same = 1;
+        without_same.summary.meta_diff = 1;
+        without_same.summary.only_left = 1;
+        let result = compare_reports(&old, &without_same);
+        assert!(result.partial);
+        assert_eq!(paths(&result), ["a.txt"]);
+        assert_eq!(paths(&compare_reports(&without_same, &old)), ["a.txt"]);
+
+        // A report kept to one folder only speaks for that folder
+        let mut in_docs = report("/left", &[("docs/new.txt", DiffKind::OnlyRight)]);
+        in_docs.full_summary = Some(CompareSummary::default());
+        in_docs.filter = Some(ReportFilter {
+            kinds: None,
+            path_prefix: Some("docs".to_string()),
+        });
+        let mut docs_before = report("/left", &[("docs/old.txt", DiffKind::OnlyLeft)]);
+        docs_before.diffs.extend(old.diffs.iter().cloned());
+        let result = compare_reports(&docs_before, &in_docs);
+        assert!(result.partial);
+        assert_eq!(paths(&result), ["docs/new.txt", "docs/old.txt"]);
+
+        // Filtered by kind, or by an older version that did not say how: only paths
+        // both list are compared
+        in_docs.filter = None;
+        assert_eq!(
+            paths(&compare_reports(&docs_before, &in_docs)),
+            ["docs/new.txt"]
+        );
+    }
+
+    #[test]
+    fn test_sync_script_quotes_and_orders_commands() {
+        let file = |size| EntryMeta {
+            kind: EntryKind::File,
+            size,
+            modified: None,
+            symlink_target: None,
+            mode: None,
+            link: None,
+        };
+        let dir = EntryMeta {
+            kind: EntryKind::Dir,
+            ..file(0)
+        };
+        let item = |path: &str, kind, left: Option<EntryMeta>, right: Option<EntryMeta>| DiffItem {
+            rel_path: path.to_string(),
+            diff_kind: kind,
+            left,
+            right,
+            error_message: None,
+            newer: None,
+        };
+        let diffs = vec![
+            item("docs/it's here.txt", DiffKind::MetaDiff, Some(file(2)), Some(file(1))),
+            item("new", DiffKind::OnlyLeft, Some(dir.clone()), None),
+            item("new/a.txt", DiffKind::OnlyLeft, Some(file(1)), None),
+            item("old", DiffKind::OnlyRight, None, Some(dir.clone())),
+            item("old/b.txt", DiffKind::OnlyRight, None, Some(file(1))),
+            item("same.txt", DiffKind::Same, Some(file(1)), Some(file(1))),
+            item("x", DiffKind::TypeMismatch, Some(file(1)), Some(dir)),
+        ];
+
+        let script = generate_sync_script("/src/it's", "/dst", &diffs, true);
+        assert!(script.starts_with("#!/bin/sh\n"));
+        assert!(script.contains("L='/src/it'\\''s'\nR='/dst'\n"));
+        assert!(script.contains(
+            "# Dry run summary: 2 files to copy, 1 directories to create, 1 entries to delete"
+        ));
+        assert!(script.contains("# 1 entries need manual review"));
+        let commands: Vec<&str> = script.lines().filter(|l| l.contains("\"$")).collect();
+        assert_eq!(
+            commands,
+            vec![
+                "mkdir -p \"$R\"/'docs'",
+                "cp -p \"$L\"/'docs/it'\\''s here.txt' \"$R\"/'docs/it'\\''s here.txt'",
+                "mkdir -p \"$R\"/'new'",
+                "cp -p \"$L\"/'new/a.txt' \"$R\"/'new/a.txt'",
+                "rm -rf \"$R\"/'old'",
+            ]
+        );
+
+        let kept = generate_sync_script("/l", "/r", &diffs, false);
+        assert!(!kept.contains("rm -"));
+    }
+}
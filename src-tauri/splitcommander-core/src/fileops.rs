@@ -0,0 +1,2426 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::AtomicBool;
+
+use filetime::FileTime;
+use serde::Serialize;
+
+use crate::checksum::{self, HashAlgorithm};
+use crate::error::CommandError;
+use crate::model::EntryMeta;
+use crate::scan::ScanError;
+use crate::security;
+use crate::source;
+
+/// Extension of the hidden file each file copy is written to before it is renamed into
+/// place (see [`part_path`]). One is left behind only by a copy that was cut off.
+pub const PART_FILE_EXT: &str = "scpart";
+
+/// Where a copy landed and how its file data was produced. Cloned files share
+/// storage with the source (copy-on-write) instead of having their bytes duplicated.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CopyOutcome {
+    pub dest: PathBuf,
+    pub files_cloned: usize,
+    pub files_copied: usize,
+    /// What a [`copy_entry_merge`] did with each path; `None` for other copies
+    pub merged: Option<MergeSummary>,
+    /// Source entries inside a copied directory that could not be copied, with the
+    /// reason; empty when everything arrived (and always with `fail_fast`). A copy
+    /// that fails verification is never listed here: the copy fails instead.
+    pub skipped: Vec<ScanError>,
+    /// What a dry run found the copy would do; `None` when it was made
+    pub plan: Option<EntryPlan>,
+}
+
+impl CopyOutcome {
+    fn new(dest: PathBuf) -> Self {
+        Self {
+            dest,
+            files_cloned: 0,
+            files_copied: 0,
+            merged: None,
+            skipped: Vec::new(),
+            plan: None,
+        }
+    }
+
+    /// The outcome of a dry run: nothing copied, `plan` set.
+    pub fn planned(plan: EntryPlan) -> Self {
+        Self {
+            dest: plan.destination.clone(),
+            plan: Some(plan),
+            ..Self::new(PathBuf::new())
+        }
+    }
+}
+
+/// What a copy, move or delete would do, worked out by its dry run after the same
+/// checks the real operation makes before changing anything.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EntryPlan {
+    /// Entry copied or moved; `None` for a delete
+    pub source: Option<PathBuf>,
+    /// Entry written (under the free name a rename would pick), or deleted
+    pub destination: PathBuf,
+    /// File data copied, moved or deleted
+    pub bytes: u64,
+    /// The destination exists and would be replaced or merged into
+    pub conflict: bool,
+}
+
+/// How the copy functions write each entry.
+#[derive(Clone, Copy, Default)]
+pub struct CopyOptions<'a> {
+    /// Carry over modification times and Unix permissions to each copied entry
+    pub preserve_metadata: bool,
+    /// Re-read each copied file and compare its hash with the source's; a mismatch
+    /// removes the copy and fails with `verificationFailed`
+    pub verify: bool,
+    /// Called with each copied file just before it is verified
+    pub on_verify: Option<&'a dyn Fn(&Path)>,
+    /// Abort a directory copy on the first entry that fails, leaving what was copied so
+    /// far. Otherwise failing entries are listed in [`CopyOutcome::skipped`] and the
+    /// copy goes on; when any of them failed verification, it then fails with the
+    /// first of those, since the copy cannot be trusted.
+    pub fail_fast: bool,
+}
+
+impl CopyOptions<'_> {
+    /// For copies that must be complete or fail: the source of a move is removed
+    /// afterwards, and a duplicate has nowhere to report skipped files.
+    fn preserving() -> Self {
+        Self {
+            preserve_metadata: true,
+            fail_fast: true,
+            ..Self::default()
+        }
+    }
+}
+
+/// Destination paths touched by a merge, by what happened to them. A directory that
+/// did not exist before is listed once under `copied`, not file by file.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MergeSummary {
+    pub copied: Vec<String>,
+    pub overwritten: Vec<String>,
+    /// Entries that could not be copied, with the reason
+    pub skipped: Vec<ScanError>,
+    /// A file on one side and a directory (or a link) on the other; left as they were
+    pub conflicts: Vec<String>,
+}
+
+/// Copies a file or directory recursively from `src` to `dest_dir/<src_name>`.
+/// Fails if destination already exists. Files are cloned when the filesystem supports
+/// it (APFS, Btrfs, XFS) and copied byte-for-byte otherwise.
+/// See [`CopyOptions`] for metadata and verification.
+pub fn copy_entry(
+    src: &Path,
+    dest_dir: &Path,
+    options: &CopyOptions,
+) -> Result<CopyOutcome, CommandError> {
+    let dest = checked_dest(src, dest_dir, ConflictPolicy::Fail, "copy")?;
+    let mut outcome = CopyOutcome::new(dest.clone());
+    copy_path(src, &dest, options, &mut outcome)?;
+
+    Ok(outcome)
+}
+
+/// Most numbered names tried for a new entry before giving up.
+pub(crate) const MAX_NUMBERED_NAMES: usize = 1000;
+
+/// Copies a file or directory next to itself under the first free name of
+/// `report copy.txt`, `report copy 2.txt`, ... and returns the new path. Directories
+/// are copied recursively; modification times and permissions are kept.
+pub fn duplicate_entry(src: &Path) -> Result<PathBuf, CommandError> {
+    let parent = src
+        .parent()
+        .ok_or_else(|| CommandError::invalid_argument("Invalid source path"))?;
+    let dest = numbered_dest(src, parent, |stem, n, ext| match n {
+        1 => format!("{} copy{}", stem, ext),
+        _ => format!("{} copy {}{}", stem, n, ext),
+    })?;
+
+    copy_path(src, &dest, &CopyOptions::preserving(), &mut CopyOutcome::new(dest.clone()))?;
+    Ok(dest)
+}
+
+/// What to do when the destination of a copy or move already exists.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ConflictPolicy {
+    /// Leave both alone and report `alreadyExists`
+    #[default]
+    Fail,
+    /// Replace the existing entry
+    Overwrite,
+    /// Keep both, giving the new entry the first free name of `name (1).ext`, ...
+    Rename,
+    /// Merge directories, overwriting same-named files; copies only, see
+    /// [`copy_entry_merge`]
+    Merge,
+}
+
+impl ConflictPolicy {
+    /// Parses `"fail"`, `"overwrite"`, `"rename"` or `"merge"`.
+    pub fn parse(name: &str) -> Result<Self, CommandError> {
+        match name {
+            "fail" => Ok(Self::Fail),
+            "overwrite" => Ok(Self::Overwrite),
+            "rename" => Ok(Self::Rename),
+            "merge" => Ok(Self::Merge),
+            _ => Err(CommandError::invalid_argument(format!(
+                "Unknown conflict policy: {} (expected fail, overwrite, rename or merge)",
+                name
+            ))),
+        }
+    }
+}
+
+/// [`copy_entry`], with `policy` deciding what happens if `dest_dir/<src_name>` exists.
+/// A renamed directory is copied whole under its new name.
+pub fn copy_entry_on_conflict(
+    src: &Path,
+    dest_dir: &Path,
+    options: &CopyOptions,
+    policy: ConflictPolicy,
+) -> Result<CopyOutcome, CommandError> {
+    match policy {
+        ConflictPolicy::Fail => copy_entry(src, dest_dir, options),
+        ConflictPolicy::Overwrite => copy_entry_overwrite(src, dest_dir, options),
+        ConflictPolicy::Merge => copy_entry_merge(src, dest_dir, options),
+        ConflictPolicy::Rename => {
+            let dest = checked_dest(src, dest_dir, policy, "copy")?;
+            let mut outcome = CopyOutcome::new(dest.clone());
+            copy_path(src, &dest, options, &mut outcome)?;
+            Ok(outcome)
+        }
+    }
+}
+
+/// [`move_entry`], with `policy` deciding what happens if `dest_dir/<src_name>` exists.
+/// Returns where the entry ended up.
+pub fn move_entry_on_conflict(
+    src: &Path,
+    dest_dir: &Path,
+    policy: ConflictPolicy,
+) -> Result<PathBuf, CommandError> {
+    match policy {
+        ConflictPolicy::Fail => move_entry(src, dest_dir),
+        ConflictPolicy::Merge => checked_move_dest(src, dest_dir, policy),
+        ConflictPolicy::Overwrite => {
+            let dest = checked_move_dest(src, dest_dir, policy)?;
+            if fs::symlink_metadata(&dest).is_err() {
+                return move_path(src, dest);
+            }
+            let mut renamed = false;
+            replace_existing(&dest, || {
+                if fs::rename(src, &dest).is_ok() {
+                    renamed = true;
+                    return Ok(());
+                }
+                let mut outcome = CopyOutcome::new(dest.clone());
+                copy_path(src, &dest, &CopyOptions::preserving(), &mut outcome)
+            })?;
+            if !renamed {
+                remove_path(src).map_err(|e| CommandError::io("Remove source failed", src, &e))?;
+            }
+            Ok(dest)
+        }
+        ConflictPolicy::Rename => move_path(src, checked_move_dest(src, dest_dir, policy)?),
+    }
+}
+
+/// Where a copy or move of `src` into `dest_dir` lands under `policy`, after the checks
+/// made before changing anything: not into itself, and the destination neither taken
+/// (failing) nor the source itself (overwriting or merging).
+fn checked_dest(
+    src: &Path,
+    dest_dir: &Path,
+    policy: ConflictPolicy,
+    action: &str,
+) -> Result<PathBuf, CommandError> {
+    let dest = dest_dir.join(source_name(src)?);
+    check_not_into_self(src, dest_dir, action)?;
+    match policy {
+        ConflictPolicy::Fail if dest.exists() => Err(CommandError::already_exists(
+            format!("Destination already exists: {}", dest.display()),
+            &dest,
+        )),
+        ConflictPolicy::Fail => Ok(dest),
+        ConflictPolicy::Rename => free_dest(src, dest_dir),
+        // Overwriting the source with itself would delete it before copying
+        ConflictPolicy::Overwrite | ConflictPolicy::Merge if is_same_entry(src, &dest) => {
+            Err(CommandError::invalid_argument(format!(
+                "Source and destination are the same: {}",
+                dest.display()
+            )))
+        }
+        ConflictPolicy::Overwrite | ConflictPolicy::Merge => Ok(dest),
+    }
+}
+
+/// [`checked_dest`] for a move, which cannot merge, nor replace a folder the source
+/// lies in: that would delete the source with it.
+fn checked_move_dest(
+    src: &Path,
+    dest_dir: &Path,
+    policy: ConflictPolicy,
+) -> Result<PathBuf, CommandError> {
+    if policy == ConflictPolicy::Merge {
+        return Err(CommandError::invalid_argument(
+            "Merging is supported for copies only",
+        ));
+    }
+    let dest = checked_dest(src, dest_dir, policy, "move")?;
+    let inside_dest = src
+        .parent()
+        .is_some_and(|parent| security::validate_confinement(&dest, parent).is_ok());
+    if policy == ConflictPolicy::Overwrite && is_real_dir(&dest) && inside_dest {
+        return Err(CommandError::invalid_argument(format!(
+            "Cannot move {} over {}, which contains it",
+            src.display(),
+            dest.display()
+        )));
+    }
+    Ok(dest)
+}
+
+/// What [`copy_entry_on_conflict`] would do, without copying.
+pub fn plan_copy_on_conflict(
+    src: &Path,
+    dest_dir: &Path,
+    policy: ConflictPolicy,
+) -> Result<EntryPlan, CommandError> {
+    let dest = checked_dest(src, dest_dir, policy, "copy")?;
+    Ok(planned_transfer(src, dest))
+}
+
+/// What [`move_entry_on_conflict`] would do, without moving.
+pub fn plan_move_on_conflict(
+    src: &Path,
+    dest_dir: &Path,
+    policy: ConflictPolicy,
+) -> Result<EntryPlan, CommandError> {
+    let dest = checked_move_dest(src, dest_dir, policy)?;
+    Ok(planned_transfer(src, dest))
+}
+
+/// What [`delete_entry`] would do, without deleting: fails as it would when `target`
+/// is gone.
+pub fn plan_delete(target: &Path) -> Result<EntryPlan, CommandError> {
+    fs::symlink_metadata(target).map_err(|e| CommandError::io("Delete failed", target, &e))?;
+    Ok(EntryPlan {
+        source: None,
+        destination: target.to_path_buf(),
+        bytes: tree_bytes(target),
+        conflict: false,
+    })
+}
+
+fn planned_transfer(src: &Path, dest: PathBuf) -> EntryPlan {
+    EntryPlan {
+        source: Some(src.to_path_buf()),
+        bytes: tree_bytes(src),
+        conflict: fs::symlink_metadata(&dest).is_ok(),
+        destination: dest,
+    }
+}
+
+/// Sum of file sizes at or below `path`; links count as themselves, never followed.
+pub(crate) fn tree_bytes(path: &Path) -> u64 {
+    match fs::symlink_metadata(path) {
+        Ok(meta) if meta.is_dir() => fs::read_dir(path)
+            .map(|rd| rd.flatten().map(|entry| tree_bytes(&entry.path())).sum())
+            .unwrap_or(0),
+        Ok(meta) => meta.len(),
+        Err(_) => 0,
+    }
+}
+
+fn source_name(src: &Path) -> Result<&std::ffi::OsStr, CommandError> {
+    src.file_name()
+        .ok_or_else(|| CommandError::invalid_argument("Invalid source path"))
+}
+
+/// `dest_dir/<src_name>` if nothing is there, else the first free `name (n).ext`.
+pub(crate) fn free_dest(src: &Path, dest_dir: &Path) -> Result<PathBuf, CommandError> {
+    let dest = dest_dir.join(source_name(src)?);
+    if fs::symlink_metadata(&dest).is_err() {
+        return Ok(dest);
+    }
+    numbered_dest(src, dest_dir, |stem, n, ext| format!("{} ({}){}", stem, n, ext))
+}
+
+/// The first path in `dir` named by `numbered(stem, n, ext)` for n = 1, 2, ... that
+/// does not exist, where stem and extension come from `src`'s name. A directory's
+/// dots are part of its name, and a dotfile's leading dot is no extension.
+fn numbered_dest(
+    src: &Path,
+    dir: &Path,
+    numbered: impl Fn(&str, usize, &str) -> String,
+) -> Result<PathBuf, CommandError> {
+    let name = source_name(src)?.to_string_lossy().to_string();
+    let meta = fs::symlink_metadata(src)
+        .map_err(|e| CommandError::io(format!("Cannot read {}", src.display()), src, &e))?;
+    let (stem, ext) = match name.rfind('.') {
+        Some(i) if i > 0 && !meta.is_dir() => name.split_at(i),
+        _ => (name.as_str(), ""),
+    };
+
+    (1..=MAX_NUMBERED_NAMES)
+        .map(|n| dir.join(numbered(stem, n, ext)))
+        .find(|candidate| fs::symlink_metadata(candidate).is_err())
+        .ok_or_else(|| {
+            CommandError::already_exists(
+                format!("Too many entries named like {} already exist", name),
+                &dir.join(&name),
+            )
+        })
+}
+
+/// Copies a file or directory into `dest_dir/<src_name>`, merging with what is there:
+/// directories present on both sides are merged recursively, same-named files are
+/// overwritten, and existing entries the source does not have are left alone. A path
+/// that is a directory on one side only is reported as a conflict and skipped, as is
+/// anything that cannot be copied; the rest of the merge goes on. Entries skipped
+/// inside newly copied directories are listed in [`MergeSummary::skipped`] too.
+pub fn copy_entry_merge(
+    src: &Path,
+    dest_dir: &Path,
+    options: &CopyOptions,
+) -> Result<CopyOutcome, CommandError> {
+    let dest = checked_dest(src, dest_dir, ConflictPolicy::Merge, "copy")?;
+    let mut outcome = CopyOutcome::new(dest.clone());
+    let mut summary = MergeSummary::default();
+    merge_path(src, &dest, options, &mut outcome, &mut summary);
+    summary.skipped.append(&mut outcome.skipped);
+    outcome.merged = Some(summary);
+    Ok(outcome)
+}
+
+fn merge_path(
+    src: &Path,
+    dest: &Path,
+    options: &CopyOptions,
+    outcome: &mut CopyOutcome,
+    summary: &mut MergeSummary,
+) {
+    let dest_name = dest.display().to_string();
+    let skip = |summary: &mut MergeSummary, message: String| {
+        summary.skipped.push(ScanError {
+            path: dest_name.clone(),
+            message,
+        })
+    };
+    let src_is_dir = match fs::symlink_metadata(src) {
+        Ok(meta) => meta.is_dir(),
+        Err(e) => return skip(summary, e.to_string()),
+    };
+    // Links are never followed on either side, so a link to a directory is no directory
+    let dest_is_dir = match fs::symlink_metadata(dest) {
+        Ok(meta) => meta.is_dir(),
+        Err(_) => {
+            return match copy_path(src, dest, options, outcome) {
+                Ok(()) => summary.copied.push(dest_name.clone()),
+                Err(e) => skip(summary, e.to_string()),
+            };
+        }
+    };
+
+    match (src_is_dir, dest_is_dir) {
+        (true, true) => {
+            let entries = fs::read_dir(src).and_then(|rd| rd.collect::<io::Result<Vec<_>>>());
+            let entries = match entries {
+                Ok(entries) => entries,
+                Err(e) => return skip(summary, e.to_string()),
+            };
+            for entry in entries {
+                let name = entry.file_name();
+                merge_path(&entry.path(), &dest.join(&name), options, outcome, summary);
+            }
+        }
+        (false, false) => {
+            match copy_over(src, dest, options, outcome) {
+                Ok(()) => summary.overwritten.push(dest_name.clone()),
+                Err(e) => skip(summary, e.to_string()),
+            }
+        }
+        _ => summary.conflicts.push(dest_name.clone()),
+    }
+}
+
+/// Copies a file or directory from `src` to `dest_dir/<src_name>`, overwriting if
+/// destination exists. The existing entry is only replaced once the copy is complete, so
+/// a failed or unverified copy leaves it alone.
+pub fn copy_entry_overwrite(
+    src: &Path,
+    dest_dir: &Path,
+    options: &CopyOptions,
+) -> Result<CopyOutcome, CommandError> {
+    let dest = checked_dest(src, dest_dir, ConflictPolicy::Overwrite, "copy")?;
+
+    // An existing destination (a symlink is replaced, never its target) stays in place
+    // until the copy is complete and verified
+    let mut outcome = CopyOutcome::new(dest.clone());
+    if fs::symlink_metadata(&dest).is_ok() {
+        copy_over(src, &dest, options, &mut outcome)?;
+    } else {
+        copy_path(src, &dest, options, &mut outcome)?;
+    }
+
+    Ok(outcome)
+}
+
+/// Copies `src_root/<rel_path>` to the same relative path under `dest_root`, creating
+/// missing parent directories and replacing an existing entry there.
+pub fn copy_rel_entry(
+    src_root: &Path,
+    dest_root: &Path,
+    rel_path: &str,
+    options: &CopyOptions,
+) -> Result<CopyOutcome, CommandError> {
+    let (src, _) = plan_rel_copy(src_root, dest_root, rel_path)?;
+    let dest_dir = confined_parent(dest_root, rel_path, true)?;
+    copy_entry_overwrite(&src, &dest_dir, options)
+}
+
+/// Source and destination of a [`copy_rel_entry`], after the checks it makes before
+/// changing anything; nothing is created. A copy that passes here fails later only if
+/// the filesystem changes in between or the copy itself goes wrong.
+pub fn plan_rel_copy(
+    src_root: &Path,
+    dest_root: &Path,
+    rel_path: &str,
+) -> Result<(PathBuf, PathBuf), CommandError> {
+    let name = rel_path.rsplit('/').next().unwrap_or(rel_path);
+    let src = confined_parent(src_root, rel_path, false)?.join(name);
+    if fs::symlink_metadata(&src).is_err() {
+        return Err(CommandError::not_found(
+            format!("Source does not exist: {}", src.display()),
+            &src,
+        ));
+    }
+    let dest_dir = planned_parent(dest_root, rel_path)?;
+    let existing = dest_dir.ancestors().find(|a| a.exists()).unwrap_or(dest_root);
+    check_not_into_self(&src, existing, "copy")?;
+    let dest = dest_dir.join(name);
+    if is_same_entry(&src, &dest) {
+        return Err(CommandError::invalid_argument(format!(
+            "Source and destination are the same: {}",
+            dest.display()
+        )));
+    }
+    Ok((src, dest))
+}
+
+/// Resolves `rel_path` under `root`, creating the missing parent directories when
+/// `create_parents` is set. The parent is confined to the root before anything is
+/// created and again afterwards; the entry itself is not resolved, so a symlink is
+/// handled as a link rather than by where it points.
+pub(crate) fn confined_parent(
+    root: &Path,
+    rel_path: &str,
+    create_parents: bool,
+) -> Result<PathBuf, CommandError> {
+    if create_parents {
+        let parent = planned_parent(root, rel_path)?;
+        if !parent.exists() {
+            fs::create_dir_all(&parent).map_err(|e| {
+                CommandError::io(format!("Cannot create {}", parent.display()), &parent, &e)
+            })?;
+        }
+        security::validate_confinement(root, &parent)?;
+        return Ok(parent);
+    }
+    let parent = lexical_parent(root, rel_path)?;
+    security::validate_confinement(root, &parent)?;
+    Ok(parent)
+}
+
+/// The parent [`confined_parent`] would create, checked the same way but left alone:
+/// its nearest existing ancestor must be inside the root.
+pub(crate) fn planned_parent(root: &Path, rel_path: &str) -> Result<PathBuf, CommandError> {
+    let parent = lexical_parent(root, rel_path)?;
+    let existing = parent.ancestors().find(|a| a.exists()).unwrap_or(root);
+    security::validate_confinement(root, existing)?;
+    Ok(parent)
+}
+
+fn lexical_parent(root: &Path, rel_path: &str) -> Result<PathBuf, CommandError> {
+    security::check_relative_path(rel_path)?;
+    let target = root.join(rel_path);
+    target
+        .parent()
+        .filter(|p| p.starts_with(root) && target != *root)
+        .map(Path::to_path_buf)
+        .ok_or_else(|| CommandError::invalid_argument(format!("Invalid path: {}", rel_path)))
+}
+
+/// Moves a file or directory from `src` to `dest_dir/<src_name>`.
+/// Uses `fs::rename` when possible, falls back to copy+delete for cross-filesystem moves.
+pub fn move_entry(src: &Path, dest_dir: &Path) -> Result<PathBuf, CommandError> {
+    move_path(src, checked_dest(src, dest_dir, ConflictPolicy::Fail, "move")?)
+}
+
+/// Moves `src` to exactly `dest`, which must not exist yet, as [`move_entry`] does.
+pub fn move_to(src: &Path, dest: &Path) -> Result<PathBuf, CommandError> {
+    if fs::symlink_metadata(dest).is_ok() {
+        return Err(CommandError::already_exists(
+            format!("Destination already exists: {}", dest.display()),
+            dest,
+        ));
+    }
+    if let Some(parent) = dest.parent() {
+        check_not_into_self(src, parent, "move")?;
+    }
+    move_path(src, dest.to_path_buf())
+}
+
+fn move_path(src: &Path, dest: PathBuf) -> Result<PathBuf, CommandError> {
+    // Try rename first (instant on same filesystem)
+    match fs::rename(src, &dest) {
+        Ok(()) => Ok(dest),
+        Err(_) => {
+            // Cross-filesystem: copy then delete, keeping times as a rename would
+            let mut outcome = CopyOutcome::new(dest.clone());
+            copy_path(src, &dest, &CopyOptions::preserving(), &mut outcome)?;
+            remove_path(src).map_err(|e| CommandError::io("Remove source failed", src, &e))?;
+            Ok(dest)
+        }
+    }
+}
+
+/// Creates a new directory inside `parent` with the given `name`.
+/// Rejects names containing `..` traversal via security checks.
+pub fn create_directory(parent: &Path, name: &str) -> Result<PathBuf, CommandError> {
+    security::check_relative_path(name)?;
+
+    let new_dir = parent.join(name);
+    if new_dir.exists() {
+        return Err(CommandError::already_exists(
+            format!("Already exists: {}", new_dir.display()),
+            &new_dir,
+        ));
+    }
+
+    fs::create_dir(&new_dir)
+        .map_err(|e| CommandError::io("Cannot create directory", &new_dir, &e))?;
+    Ok(new_dir)
+}
+
+/// Creates an empty file, or one holding `initial_content`, inside `parent`. `name` must
+/// be a single path component; an existing entry of that name is never overwritten.
+pub fn create_file(
+    parent: &Path,
+    name: &str,
+    initial_content: Option<&str>,
+) -> Result<PathBuf, CommandError> {
+    check_entry_name(name)?;
+
+    let new_file = parent.join(name);
+    let context = "Cannot create file";
+    // create_new rather than an exists() check, so a racing creator is not clobbered
+    let mut file = fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(&new_file)
+        .map_err(|e| {
+            if e.kind() == io::ErrorKind::AlreadyExists {
+                CommandError::already_exists(
+                    format!("Already exists: {}", new_file.display()),
+                    &new_file,
+                )
+            } else {
+                CommandError::io(context, &new_file, &e)
+            }
+        })?;
+    if let Some(content) = initial_content {
+        use std::io::Write;
+        file.write_all(content.as_bytes())
+            .map_err(|e| CommandError::io(context, &new_file, &e))?;
+    }
+    Ok(new_file)
+}
+
+/// A name for a new or renamed entry: one path component, not empty.
+fn check_entry_name(name: &str) -> Result<(), CommandError> {
+    if name.is_empty() || name == "." {
+        return Err(CommandError::invalid_argument("Name cannot be empty"));
+    }
+    if name.contains('/') || name.contains('\\') {
+        return Err(CommandError::invalid_argument(format!(
+            "Name cannot contain path separators: {}",
+            name
+        )));
+    }
+    security::check_relative_path(name)?;
+    Ok(())
+}
+
+/// Renames a file or directory in place to `new_name` within its parent directory.
+/// Case-only renames (`readme.md` -> `README.md`) go through a temporary name so they
+/// also work on case-insensitive filesystems, where the destination already "exists".
+pub fn rename_entry(path: &Path, new_name: &str) -> Result<PathBuf, CommandError> {
+    check_entry_name(new_name)?;
+
+    let parent = path
+        .parent()
+        .ok_or_else(|| CommandError::invalid_argument("Invalid source path"))?;
+    let old_name = path
+        .file_name()
+        .ok_or_else(|| CommandError::invalid_argument("Invalid source path"))?
+        .to_string_lossy()
+        .to_string();
+    let dest = parent.join(new_name);
+
+    if old_name == new_name {
+        return Ok(dest);
+    }
+
+    if fs::symlink_metadata(&dest).is_ok() {
+        if !is_same_entry(path, &dest) {
+            return Err(CommandError::already_exists(
+                format!("Destination already exists: {}", dest.display()),
+                &dest,
+            ));
+        }
+        // Case-only rename on a case-insensitive filesystem: the destination resolves
+        // to the source itself, so hop through a unique temporary name.
+        let tmp = unique_temp_name(parent, &old_name, "rename");
+        fs::rename(path, &tmp).map_err(|e| CommandError::io("Rename failed", path, &e))?;
+        if let Err(e) = fs::rename(&tmp, &dest) {
+            let _ = fs::rename(&tmp, path);
+            return Err(CommandError::io("Rename failed", path, &e));
+        }
+        return Ok(dest);
+    }
+
+    fs::rename(path, &dest).map_err(|e| CommandError::io("Rename failed", path, &e))?;
+    Ok(dest)
+}
+
+/// What [`set_permissions`] did below the path it was given.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PermissionsOutcome {
+    /// Entries whose permissions differ from before; ones already matching are not counted
+    pub changed: usize,
+    /// Entries inside the folder that could not be read or changed, with the reason
+    pub errors: Vec<ScanError>,
+}
+
+/// Sets the Unix permission bits of `path` to `mode`, and with `recursive` of everything
+/// below it. With `conditional_execute` (`chmod X`), the execute bits of `mode` only go
+/// to directories and to files that already had an execute bit; other files get `mode`
+/// without them. Symlinks are skipped rather than followed. Off Unix, only the
+/// read-only attribute exists: it is set when `mode` has no write bit.
+///
+/// Failing on `path` itself is an error; failures below it are collected and the rest
+/// is still changed. A directory is changed before its contents when `mode` lets its
+/// owner list and enter it, and after them otherwise, so a restrictive mode does not
+/// lock the walk out.
+pub fn set_permissions(
+    path: &Path,
+    mode: u32,
+    recursive: bool,
+    conditional_execute: bool,
+) -> Result<PermissionsOutcome, CommandError> {
+    if mode > 0o7777 {
+        return Err(CommandError::invalid_argument(format!("Invalid mode: {:o}", mode)));
+    }
+    let mut outcome = PermissionsOutcome::default();
+    let walk = PermissionsWalk {
+        mode,
+        recursive,
+        conditional_execute,
+    };
+    walk.apply(path, &mut outcome)?;
+    Ok(outcome)
+}
+
+struct PermissionsWalk {
+    mode: u32,
+    recursive: bool,
+    conditional_execute: bool,
+}
+
+impl PermissionsWalk {
+    fn apply(&self, path: &Path, outcome: &mut PermissionsOutcome) -> Result<(), CommandError> {
+        let meta = fs::symlink_metadata(path)
+            .map_err(|e| CommandError::io(format!("Cannot read {}", path.display()), path, &e))?;
+        if meta.file_type().is_symlink() {
+            return Ok(());
+        }
+        if !(self.recursive && meta.is_dir()) {
+            return self.change(path, &meta, outcome);
+        }
+
+        let enterable = self.mode & 0o500 == 0o500;
+        if enterable {
+            self.change(path, &meta, outcome)?;
+        }
+        let read_error =
+            |e: io::Error| CommandError::io(format!("Cannot read {}", path.display()), path, &e);
+        for entry in fs::read_dir(path).map_err(read_error)? {
+            let (child, applied) = match entry {
+                Ok(entry) => {
+                    let child = entry.path();
+                    let applied = self.apply(&child, outcome);
+                    (child, applied)
+                }
+                Err(e) => (path.to_path_buf(), Err(read_error(e))),
+            };
+            if let Err(e) = applied {
+                outcome.errors.push(ScanError {
+                    path: child.display().to_string(),
+                    message: e.to_string(),
+                });
+            }
+        }
+        if !enterable {
+            self.change(path, &meta, outcome)?;
+        }
+        Ok(())
+    }
+
+    fn change(
+        &self,
+        path: &Path,
+        meta: &fs::Metadata,
+        outcome: &mut PermissionsOutcome,
+    ) -> Result<(), CommandError> {
+        let Some(permissions) = self.target(meta) else {
+            return Ok(());
+        };
+        fs::set_permissions(path, permissions).map_err(|e| {
+            CommandError::io(format!("Cannot set permissions on {}", path.display()), path, &e)
+        })?;
+        outcome.changed += 1;
+        Ok(())
+    }
+
+    /// The permissions `meta`'s entry should get, or `None` if it already has them.
+    #[cfg(unix)]
+    fn target(&self, meta: &fs::Metadata) -> Option<fs::Permissions> {
+        use std::os::unix::fs::PermissionsExt;
+        let current = meta.permissions().mode() & 0o7777;
+        let executable = meta.is_dir() || current & 0o111 != 0;
+        let mode = if self.conditional_execute && !executable {
+            self.mode & !0o111
+        } else {
+            self.mode
+        };
+        (mode != current).then(|| fs::Permissions::from_mode(mode))
+    }
+
+    #[cfg(not(unix))]
+    fn target(&self, meta: &fs::Metadata) -> Option<fs::Permissions> {
+        let readonly = self.mode & 0o222 == 0;
+        let mut permissions = meta.permissions();
+        (permissions.readonly() != readonly).then(|| {
+            permissions.set_readonly(readonly);
+            permissions
+        })
+    }
+}
+
+/// Sets the modification time of `path` to `modified_ms` (epoch milliseconds), keeping
+/// its access time. A symlink gets the time itself rather than its target, where the
+/// platform allows that. Returns the entry's metadata afterwards.
+pub fn set_modified(path: &Path, modified_ms: u64) -> Result<EntryMeta, CommandError> {
+    let meta = read_meta(path)?;
+    let secs = (modified_ms / 1000) as i64;
+    let nanos = (modified_ms % 1000) as u32 * 1_000_000;
+    let atime = FileTime::from_last_access_time(&meta);
+    set_times(path, &meta, atime, FileTime::from_unix_time(secs, nanos))?;
+    Ok(source::entry_meta(path, &read_meta(path)?))
+}
+
+/// Gives `to` the access and modification times of `from`, neither of which is
+/// followed if it is a symlink. Returns the metadata of `to` afterwards.
+pub fn copy_times(from: &Path, to: &Path) -> Result<EntryMeta, CommandError> {
+    let from_meta = read_meta(from)?;
+    let to_meta = read_meta(to)?;
+    let atime = FileTime::from_last_access_time(&from_meta);
+    let mtime = FileTime::from_last_modification_time(&from_meta);
+    set_times(to, &to_meta, atime, mtime)?;
+    Ok(source::entry_meta(to, &read_meta(to)?))
+}
+
+fn read_meta(path: &Path) -> Result<fs::Metadata, CommandError> {
+    fs::symlink_metadata(path)
+        .map_err(|e| CommandError::io(format!("Cannot read {}", path.display()), path, &e))
+}
+
+fn set_times(
+    path: &Path,
+    meta: &fs::Metadata,
+    atime: FileTime,
+    mtime: FileTime,
+) -> Result<(), CommandError> {
+    let set = if meta.file_type().is_symlink() {
+        filetime::set_symlink_file_times(path, atime, mtime)
+    } else {
+        filetime::set_file_times(path, atime, mtime)
+    };
+    set.map_err(|e| CommandError::io(format!("Cannot set times on {}", path.display()), path, &e))
+}
+
+/// Returns true if both paths refer to the same directory entry (without following symlinks).
+#[cfg(unix)]
+fn is_same_entry(a: &Path, b: &Path) -> bool {
+    use std::os::unix::fs::MetadataExt;
+    match (fs::symlink_metadata(a), fs::symlink_metadata(b)) {
+        (Ok(ma), Ok(mb)) => ma.dev() == mb.dev() && ma.ino() == mb.ino(),
+        _ => false,
+    }
+}
+
+/// Returns true if both paths refer to the same directory entry. Windows volumes are
+/// case-insensitive, so an existing destination differing only in case is the source.
+#[cfg(not(unix))]
+fn is_same_entry(a: &Path, b: &Path) -> bool {
+    match (a.file_name(), b.file_name()) {
+        (Some(na), Some(nb)) => {
+            na.to_string_lossy().to_lowercase() == nb.to_string_lossy().to_lowercase()
+        }
+        _ => false,
+    }
+}
+
+/// Picks a hidden sibling name for `name`, tagged with what it is for, that does not
+/// currently exist in `parent`.
+fn unique_temp_name(parent: &Path, name: &str, tag: &str) -> PathBuf {
+    let mut n = 0u32;
+    loop {
+        let candidate = parent.join(format!(".{}.sc-{}-{}", name, tag, n));
+        if fs::symlink_metadata(&candidate).is_err() {
+            return candidate;
+        }
+        n += 1;
+    }
+}
+
+/// Hidden sibling that a copy of a file to `dest` is written to, `.<name>.<n>.scpart`
+/// with the first `n` that is free.
+pub fn part_path(dest: &Path) -> PathBuf {
+    let name = dest.file_name().unwrap_or_default().to_string_lossy();
+    let parent = dest.parent().unwrap_or(Path::new(""));
+    let mut n = 0u32;
+    loop {
+        let candidate = parent.join(format!(".{}.{}.{}", name, n, PART_FILE_EXT));
+        if fs::symlink_metadata(&candidate).is_err() {
+            return candidate;
+        }
+        n += 1;
+    }
+}
+
+#[cfg(test)]
+thread_local! {
+    /// Called with each part file once it is written, before it is renamed
+    static PART_WRITTEN: std::cell::Cell<Option<fn(&Path)>> =
+        const { std::cell::Cell::new(None) };
+}
+
+/// Clones or copies the file `src` to `dest` through a [`part_path`], so that `dest`
+/// only ever appears complete. A copy that fails removes its part file.
+fn copy_file(src: &Path, dest: &Path, outcome: &mut CopyOutcome) -> Result<(), CommandError> {
+    let part = part_path(dest);
+    let cloned = clone_file(src, &part).is_ok();
+    let written = if cloned {
+        Ok(())
+    } else {
+        fs::copy(src, &part).map(|_| ())
+    };
+    let copied = written.and_then(|()| {
+        #[cfg(test)]
+        if let Some(hook) = PART_WRITTEN.get() {
+            hook(&part);
+        }
+        fs::rename(&part, dest)
+    });
+    if let Err(e) = copied {
+        let _ = fs::remove_file(&part);
+        return Err(CommandError::io(format!("Copy {} failed", src.display()), src, &e));
+    }
+    if cloned {
+        outcome.files_cloned += 1;
+    } else {
+        outcome.files_copied += 1;
+    }
+    Ok(())
+}
+
+/// Copies `src` over the existing `dest`. The copy is written (and verified) under a
+/// hidden sibling name first and only then takes the place of `dest`, so a copy that
+/// fails leaves `dest` as it was.
+fn copy_over(
+    src: &Path,
+    dest: &Path,
+    options: &CopyOptions,
+    outcome: &mut CopyOutcome,
+) -> Result<(), CommandError> {
+    let (parent, name) = match (dest.parent(), dest.file_name()) {
+        (Some(parent), Some(name)) => (parent, name.to_string_lossy()),
+        _ => return Err(CommandError::invalid_argument("Invalid destination path")),
+    };
+    let staged = unique_temp_name(parent, &name, "copy");
+    let copied = copy_path(src, &staged, options, outcome).and_then(|()| {
+        replace_existing(dest, || {
+            fs::rename(&staged, dest).map_err(|e| CommandError::io("Cannot replace", dest, &e))
+        })
+    });
+    if copied.is_err() && fs::symlink_metadata(&staged).is_ok() {
+        let _ = remove_path(&staged);
+    }
+    copied
+}
+
+/// Puts a new entry at the existing `dest` through `write` without losing the old one
+/// if that fails. The old entry is renamed to a hidden sibling first; if `write` fails,
+/// whatever it left at `dest` is removed and the old entry is put back, otherwise the
+/// old entry is removed.
+fn replace_existing(
+    dest: &Path,
+    write: impl FnOnce() -> Result<(), CommandError>,
+) -> Result<(), CommandError> {
+    let (parent, name) = match (dest.parent(), dest.file_name()) {
+        (Some(parent), Some(name)) => (parent, name.to_string_lossy()),
+        _ => return Err(CommandError::invalid_argument("Invalid destination path")),
+    };
+    let aside = unique_temp_name(parent, &name, "replaced");
+    fs::rename(dest, &aside).map_err(|e| CommandError::io("Cannot replace", dest, &e))?;
+    if let Err(e) = write() {
+        if fs::symlink_metadata(dest).is_ok() {
+            let _ = remove_path(dest);
+        }
+        let _ = fs::rename(&aside, dest);
+        return Err(e);
+    }
+    remove_path(&aside).map_err(|e| CommandError::io("Cannot remove the replaced entry", &aside, &e))
+}
+
+/// Sibling that [`write_atomic`] fills before renaming it over `path`: `<name>.tmp`.
+pub fn atomic_temp_path(path: &Path) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(".tmp");
+    path.with_file_name(name)
+}
+
+/// Replaces `path` with `bytes` so that readers see either the old file or the complete
+/// new one, never a truncated mix. See [`write_atomic_with`].
+pub fn write_atomic(path: &Path, bytes: &[u8]) -> Result<(), CommandError> {
+    use std::io::Write;
+    write_atomic_with(path, |file| {
+        file.write_all(bytes)
+            .map_err(|e| CommandError::io(format!("Cannot write {}", path.display()), path, &e))
+    })
+}
+
+/// Streams new contents for `path` through `write` into [`atomic_temp_path`], flushes
+/// them to disk and renames the temp file over `path`. If `write` fails, the temp file
+/// is removed and `path` keeps its old contents.
+pub fn write_atomic_with<T>(
+    path: &Path,
+    write: impl FnOnce(&mut fs::File) -> Result<T, CommandError>,
+) -> Result<T, CommandError> {
+    let tmp = atomic_temp_path(path);
+    let result = (|| {
+        let mut file = fs::File::create(&tmp)
+            .map_err(|e| CommandError::io(format!("Cannot create {}", tmp.display()), &tmp, &e))?;
+        let value = write(&mut file)?;
+        file.sync_all()
+            .map_err(|e| CommandError::io(format!("Cannot flush {}", tmp.display()), &tmp, &e))?;
+        drop(file);
+        replace_file(&tmp, path)
+            .map_err(|e| CommandError::io(format!("Cannot replace {}", path.display()), path, &e))?;
+        Ok(value)
+    })();
+    if result.is_err() {
+        let _ = fs::remove_file(&tmp);
+    }
+    result
+}
+
+#[cfg(not(windows))]
+fn replace_file(from: &Path, to: &Path) -> io::Result<()> {
+    fs::rename(from, to)?;
+    // Persist the rename itself; not every filesystem lets a directory be synced
+    if let Some(parent) = to.parent().filter(|p| !p.as_os_str().is_empty()) {
+        if let Ok(dir) = fs::File::open(parent) {
+            let _ = dir.sync_all();
+        }
+    }
+    Ok(())
+}
+
+/// `rename` replaces an existing file on Windows too, but fails with access denied
+/// when the target is read-only; clear that and retry once.
+#[cfg(windows)]
+fn replace_file(from: &Path, to: &Path) -> io::Result<()> {
+    match fs::rename(from, to) {
+        Err(e) if e.kind() == io::ErrorKind::PermissionDenied => {
+            let mut perms = fs::metadata(to)?.permissions();
+            if !perms.readonly() {
+                return Err(e);
+            }
+            #[allow(clippy::permissions_set_readonly_false)]
+            perms.set_readonly(false);
+            fs::set_permissions(to, perms)?;
+            fs::rename(from, to)
+        }
+        result => result,
+    }
+}
+
+/// Deletes a file or directory (recursively for directories).
+/// A symlink is removed itself; the directory it points at is left untouched.
+pub fn delete_entry(target: &Path) -> Result<(), CommandError> {
+    remove_path(target).map_err(|e| CommandError::io("Delete failed", target, &e))
+}
+
+/// Removes a path without following symlinks: real directories recursively,
+/// everything else (files, links to files or directories) as a single entry.
+fn remove_path(path: &Path) -> io::Result<()> {
+    let meta = fs::symlink_metadata(path)?;
+    if meta.is_dir() {
+        return fs::remove_dir_all(path);
+    }
+    match fs::remove_file(path) {
+        // Directory symlinks and junctions on Windows are removed as directories
+        #[cfg(windows)]
+        Err(_) if meta.file_type().is_symlink() => fs::remove_dir(path),
+        result => result,
+    }
+}
+
+/// Copies a single path: directories recursively, symlinks as symlinks, files by content.
+/// With `preserve`, source times and permissions are applied once the contents are written.
+fn copy_path(
+    src: &Path,
+    dest: &Path,
+    options: &CopyOptions,
+    outcome: &mut CopyOutcome,
+) -> Result<(), CommandError> {
+    let meta = fs::symlink_metadata(src)
+        .map_err(|e| CommandError::io(format!("Cannot read {}", src.display()), src, &e))?;
+    if meta.file_type().is_symlink() {
+        copy_symlink(src, dest)?;
+    } else if meta.is_dir() {
+        copy_dir_recursive(src, dest, options, outcome)?;
+    } else {
+        copy_file(src, dest, outcome)?;
+        if options.verify {
+            verify_copy(src, dest, options)?;
+        }
+    }
+    if options.preserve_metadata {
+        apply_metadata(dest, &meta)?;
+    }
+    Ok(())
+}
+
+/// Hashes `src` and its copy `dest`, removing `dest` if they differ.
+fn verify_copy(src: &Path, dest: &Path, options: &CopyOptions) -> Result<(), CommandError> {
+    if let Some(on_verify) = options.on_verify {
+        on_verify(dest);
+    }
+    let never = AtomicBool::new(false);
+    let hash = |path: &Path| {
+        checksum::hash_file(path, HashAlgorithm::Xxh3, &never, &|_, _| {})
+            .map(|(hex, _)| hex)
+            .map_err(|e| CommandError::io(format!("Cannot verify {}", path.display()), path, &e))
+    };
+    if hash(src)? != hash(dest)? {
+        let _ = fs::remove_file(dest);
+        return Err(CommandError::VerificationFailed {
+            message: format!("Verification failed: the copy of {} differs from it", src.display()),
+            path: Some(src.display().to_string()),
+        });
+    }
+    Ok(())
+}
+
+/// Applies the source mtime/atime and (for non-links) permissions to `dest`.
+/// Times go first: a read-only mode could otherwise block setting them on Windows.
+fn apply_metadata(dest: &Path, meta: &fs::Metadata) -> Result<(), CommandError> {
+    let atime = FileTime::from_last_access_time(meta);
+    let mtime = FileTime::from_last_modification_time(meta);
+    if meta.file_type().is_symlink() {
+        // Not every platform can set times on the link itself; the link is still usable
+        let _ = filetime::set_symlink_file_times(dest, atime, mtime);
+        return Ok(());
+    }
+    filetime::set_file_times(dest, atime, mtime)
+        .map_err(|e| {
+            CommandError::io(format!("Cannot set times on {}", dest.display()), dest, &e)
+        })?;
+    fs::set_permissions(dest, meta.permissions())
+        .map_err(|e| {
+            CommandError::io(format!("Cannot set permissions on {}", dest.display()), dest, &e)
+        })
+}
+
+/// Rejects copying or moving a directory into itself or one of its descendants.
+/// Both paths are canonicalized, so symlinked aliases of the source are caught too.
+/// Symlinks are copied as links, so they can safely go anywhere.
+pub(crate) fn check_not_into_self(
+    src: &Path,
+    dest_dir: &Path,
+    action: &str,
+) -> Result<(), CommandError> {
+    if !is_real_dir(src) {
+        return Ok(());
+    }
+    match security::validate_confinement(src, dest_dir) {
+        Ok(_) => Err(CommandError::invalid_argument(format!(
+            "Cannot {} {} into itself or one of its subdirectories",
+            action,
+            src.display()
+        ))),
+        Err(_) => Ok(()),
+    }
+}
+
+/// True for real directories; false for files and for symlinks (even to directories).
+fn is_real_dir(path: &Path) -> bool {
+    fs::symlink_metadata(path)
+        .map(|m| m.is_dir())
+        .unwrap_or(false)
+}
+
+fn copy_dir_recursive(
+    src: &Path,
+    dest: &Path,
+    options: &CopyOptions,
+    outcome: &mut CopyOutcome,
+) -> Result<(), CommandError> {
+    fs::create_dir(dest)
+        .map_err(|e| CommandError::io(format!("Cannot create {}", dest.display()), dest, &e))?;
+
+    let read_error =
+        |e: io::Error| CommandError::io(format!("Cannot read {}", src.display()), src, &e);
+    let mut failed_verification = None;
+    for entry in fs::read_dir(src).map_err(read_error)? {
+        // An unreadable listing entry has no path of its own, so it is charged to `src`
+        let (path, copied) = match entry {
+            Ok(entry) => {
+                let path = entry.path();
+                let copied = copy_path(&path, &dest.join(entry.file_name()), options, outcome);
+                (path, copied)
+            }
+            Err(e) => (src.to_path_buf(), Err(read_error(e))),
+        };
+        match copied {
+            Ok(()) => {}
+            Err(e) if options.fail_fast => return Err(e),
+            Err(e) if e.code() == "verificationFailed" => {
+                failed_verification.get_or_insert(e);
+            }
+            Err(e) => outcome.skipped.push(ScanError {
+                path: path.display().to_string(),
+                message: e.to_string(),
+            }),
+        }
+    }
+    failed_verification.map_or(Ok(()), Err)
+}
+
+/// Creates `dest` as a copy-on-write clone of `src` via the Linux `FICLONE` ioctl.
+/// Fails (leaving nothing behind) when the filesystem cannot share extents, e.g.
+/// across volumes or on ext4, so the caller can fall back to a full copy.
+#[cfg(target_os = "linux")]
+fn clone_file(src: &Path, dest: &Path) -> io::Result<()> {
+    use std::os::unix::io::AsRawFd;
+    // _IOW(0x94, 9, int) from <linux/fs.h>
+    const FICLONE: u64 = 0x4004_9409;
+
+    let src_file = fs::File::open(src)?;
+    let dest_file = fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(dest)?;
+    // SAFETY: both descriptors are open for the duration of the call
+    let rc = unsafe { libc::ioctl(dest_file.as_raw_fd(), FICLONE as _, src_file.as_raw_fd()) };
+    if rc == -1 {
+        let err = io::Error::last_os_error();
+        drop(dest_file);
+        let _ = fs::remove_file(dest);
+        return Err(err);
+    }
+    // Match `fs::copy`, which carries the permission bits over
+    fs::set_permissions(dest, src_file.metadata()?.permissions())
+}
+
+/// Creates `dest` as an APFS clone of `src` via `clonefile(2)`, which also carries
+/// over permissions and timestamps. Fails on non-APFS volumes and across volumes.
+#[cfg(target_os = "macos")]
+fn clone_file(src: &Path, dest: &Path) -> io::Result<()> {
+    use std::ffi::CString;
+    use std::os::unix::ffi::OsStrExt;
+    // From <sys/clonefile.h>; not exported by the libc crate
+    const CLONE_NOFOLLOW: u32 = 0x0001;
+
+    let src_c = CString::new(src.as_os_str().as_bytes())?;
+    let dest_c = CString::new(dest.as_os_str().as_bytes())?;
+    // SAFETY: both pointers are valid NUL-terminated strings for the duration of the call
+    let rc = unsafe { libc::clonefile(src_c.as_ptr(), dest_c.as_ptr(), CLONE_NOFOLLOW) };
+    if rc == -1 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+fn clone_file(_src: &Path, _dest: &Path) -> io::Result<()> {
+    Err(io::ErrorKind::Unsupported.into())
+}
+
+/// Recreates the symlink at `dest` with the same target text (relative targets stay
+/// relative). Dangling links are copied as dangling links.
+fn copy_symlink(src: &Path, dest: &Path) -> Result<(), CommandError> {
+    let target = fs::read_link(src)
+        .map_err(|e| CommandError::io(format!("Cannot read link {}", src.display()), src, &e))?;
+    create_symlink(src, &target, dest)
+        .map_err(|e| CommandError::io(format!("Cannot create link {}", dest.display()), dest, &e))
+}
+
+#[cfg(unix)]
+fn create_symlink(_src: &Path, target: &Path, dest: &Path) -> std::io::Result<()> {
+    std::os::unix::fs::symlink(target, dest)
+}
+
+#[cfg(windows)]
+fn create_symlink(src: &Path, target: &Path, dest: &Path) -> std::io::Result<()> {
+    // Windows distinguishes file and directory links; follow the source to decide,
+    // treating dangling links as file links.
+    if fs::metadata(src).map(|m| m.is_dir()).unwrap_or(false) {
+        std::os::windows::fs::symlink_dir(target, dest)
+    } else {
+        std::os::windows::fs::symlink_file(target, dest)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PLAIN: CopyOptions = CopyOptions {
+        preserve_metadata: false,
+        verify: false,
+        on_verify: None,
+        fail_fast: false,
+    };
+    const PRESERVING: CopyOptions = CopyOptions {
+        preserve_metadata: true,
+        ..PLAIN
+    };
+
+    fn test_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("sc_fileops_{}", name));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_copy_file() {
+        let dir = test_dir("copy_file");
+        fs::create_dir_all(dir.join("src")).unwrap();
+        fs::create_dir_all(dir.join("dst")).unwrap();
+        fs::write(dir.join("src/test.txt"), "hello").unwrap();
+
+        let result = copy_entry(&dir.join("src/test.txt"), &dir.join("dst"), &PLAIN);
+        assert!(result.is_ok());
+        assert_eq!(result.unwrap().dest, dir.join("dst/test.txt"));
+        assert!(dir.join("dst/test.txt").exists());
+        assert_eq!(fs::read_to_string(dir.join("dst/test.txt")).unwrap(), "hello");
+        // Source still exists
+        assert!(dir.join("src/test.txt").exists());
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_copy_goes_through_a_part_file() {
+        let dir = test_dir("part_file");
+        fs::create_dir_all(dir.join("src")).unwrap();
+        fs::create_dir_all(dir.join("dst")).unwrap();
+        fs::write(dir.join("src/a.txt"), "hello").unwrap();
+        let parts = |dir: &Path| -> Vec<PathBuf> {
+            let mut parts: Vec<PathBuf> = fs::read_dir(dir)
+                .unwrap()
+                .map(|e| e.unwrap().path())
+                .filter(|p| p.extension().is_some_and(|ext| ext == PART_FILE_EXT))
+                .collect();
+            parts.sort();
+            parts
+        };
+
+        // The data lands in the part file first; the destination name appears only
+        // once it is complete
+        PART_WRITTEN.set(Some(|part| {
+            assert_eq!(fs::read_to_string(part).unwrap(), "hello");
+            assert!(!part.with_file_name("a.txt").exists());
+        }));
+        copy_entry(&dir.join("src/a.txt"), &dir.join("dst"), &PLAIN).unwrap();
+        assert_eq!(fs::read_to_string(dir.join("dst/a.txt")).unwrap(), "hello");
+        assert!(parts(&dir.join("dst")).is_empty());
+
+        // A copy that fails removes its part file
+        PART_WRITTEN.set(Some(|part| {
+            fs::create_dir_all(part.with_file_name("a.txt").join("in the way")).unwrap();
+        }));
+        let fail_fast = CopyOptions {
+            fail_fast: true,
+            ..PLAIN
+        };
+        assert!(copy_entry(&dir.join("src"), &dir.join("dst"), &fail_fast).is_err());
+        assert!(dir.join("dst/src/a.txt").is_dir());
+        assert!(parts(&dir.join("dst/src")).is_empty());
+
+        // A copy cut off before the rename leaves the part file, and nothing under the
+        // destination name; the next copy picks another part name
+        fs::create_dir(dir.join("cut")).unwrap();
+        PART_WRITTEN.set(Some(|_| panic!("cut off")));
+        let copy = || copy_entry(&dir.join("src/a.txt"), &dir.join("cut"), &PLAIN);
+        assert!(std::panic::catch_unwind(copy).is_err());
+        let left_behind = parts(&dir.join("cut"));
+        assert_eq!(left_behind, [dir.join("cut/.a.txt.0.scpart")]);
+        assert_eq!(fs::read_to_string(&left_behind[0]).unwrap(), "hello");
+        assert!(!dir.join("cut/a.txt").exists());
+        assert_eq!(part_path(&dir.join("cut/a.txt")), dir.join("cut/.a.txt.1.scpart"));
+        PART_WRITTEN.set(None);
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_copy_dir_recursive() {
+        let dir = test_dir("copy_dir");
+        fs::create_dir_all(dir.join("src/sub")).unwrap();
+        fs::write(dir.join("src/a.txt"), "aaa").unwrap();
+        fs::write(dir.join("src/sub/b.txt"), "bbb").unwrap();
+        fs::create_dir_all(dir.join("dst")).unwrap();
+
+        let result = copy_entry(&dir.join("src"), &dir.join("dst"), &PLAIN);
+        assert!(result.is_ok());
+        assert!(dir.join("dst/src/a.txt").exists());
+        assert!(dir.join("dst/src/sub/b.txt").exists());
+        assert_eq!(fs::read_to_string(dir.join("dst/src/sub/b.txt")).unwrap(), "bbb");
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_copy_collision() {
+        let dir = test_dir("copy_collision");
+        fs::create_dir_all(dir.join("src")).unwrap();
+        fs::create_dir_all(dir.join("dst")).unwrap();
+        fs::write(dir.join("src/test.txt"), "hello").unwrap();
+        fs::write(dir.join("dst/test.txt"), "existing").unwrap();
+
+        let result = copy_entry(&dir.join("src/test.txt"), &dir.join("dst"), &PLAIN);
+        let err = result.unwrap_err();
+        assert_eq!(err.code(), "alreadyExists");
+        assert!(err.to_string().contains("already exists"));
+        // Original not overwritten
+        assert_eq!(fs::read_to_string(dir.join("dst/test.txt")).unwrap(), "existing");
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_copy_dir_preserves_symlinks() {
+        use std::os::unix::fs::symlink;
+
+        let dir = test_dir("copy_symlinks");
+        fs::create_dir_all(dir.join("src/real_dir")).unwrap();
+        fs::create_dir_all(dir.join("dst")).unwrap();
+        fs::write(dir.join("src/a.txt"), "aaa").unwrap();
+        fs::write(dir.join("src/real_dir/inner.txt"), "inner").unwrap();
+        symlink("a.txt", dir.join("src/rel_link")).unwrap();
+        symlink(dir.join("src/real_dir"), dir.join("src/abs_dir_link")).unwrap();
+        symlink("does/not/exist", dir.join("src/dangling")).unwrap();
+
+        let result = copy_entry(&dir.join("src"), &dir.join("dst"), &PLAIN);
+        assert!(result.is_ok(), "{:?}", result);
+
+        for (name, target) in [
+            ("rel_link", PathBuf::from("a.txt")),
+            ("abs_dir_link", dir.join("src/real_dir")),
+            ("dangling", PathBuf::from("does/not/exist")),
+        ] {
+            let copied = dir.join("dst/src").join(name);
+            let meta = fs::symlink_metadata(&copied).unwrap();
+            assert!(meta.file_type().is_symlink(), "{} should be a symlink", name);
+            assert_eq!(fs::read_link(&copied).unwrap(), target);
+        }
+        // Relative link still resolves inside the copy
+        assert_eq!(fs::read_to_string(dir.join("dst/src/rel_link")).unwrap(), "aaa");
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_copy_dir_into_itself_rejected() {
+        let dir = test_dir("copy_into_self");
+        fs::create_dir_all(dir.join("src/sub")).unwrap();
+        fs::write(dir.join("src/a.txt"), "aaa").unwrap();
+
+        let direct = copy_entry(&dir.join("src"), &dir.join("src"), &PLAIN);
+        assert!(direct.unwrap_err().to_string().contains("into itself"));
+
+        let nested = copy_entry_overwrite(&dir.join("src"), &dir.join("src/sub"), &PLAIN);
+        assert!(nested.unwrap_err().to_string().contains("into itself"));
+        assert!(!dir.join("src/sub/src").exists());
+
+        let moved = move_entry(&dir.join("src"), &dir.join("src/sub"));
+        assert!(moved.unwrap_err().to_string().contains("into itself"));
+        assert!(dir.join("src/a.txt").exists());
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_copy_dir_into_symlinked_alias_rejected() {
+        let dir = test_dir("copy_into_alias");
+        fs::create_dir_all(dir.join("src/sub")).unwrap();
+        std::os::unix::fs::symlink(dir.join("src"), dir.join("alias")).unwrap();
+
+        let result = copy_entry(&dir.join("src"), &dir.join("alias/sub"), &PLAIN);
+        assert!(result.unwrap_err().to_string().contains("into itself"));
+        assert!(!dir.join("src/sub/src").exists());
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_copy_overwrite_onto_itself_rejected() {
+        let dir = test_dir("copy_overwrite_self");
+        fs::write(dir.join("file.txt"), "keep me").unwrap();
+
+        let result = copy_entry_overwrite(&dir.join("file.txt"), &dir, &PLAIN);
+        assert!(result.is_err());
+        assert_eq!(fs::read_to_string(dir.join("file.txt")).unwrap(), "keep me");
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_delete_symlink_to_dir_keeps_target() {
+        let dir = test_dir("delete_symlink");
+        fs::create_dir_all(dir.join("real_dir")).unwrap();
+        fs::write(dir.join("real_dir/file.txt"), "precious").unwrap();
+        std::os::unix::fs::symlink(dir.join("real_dir"), dir.join("link")).unwrap();
+
+        delete_entry(&dir.join("link")).unwrap();
+        assert!(fs::symlink_metadata(dir.join("link")).is_err());
+        assert!(dir.join("real_dir/file.txt").exists());
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_copy_overwrite_replaces_symlink_not_target() {
+        let dir = test_dir("overwrite_symlink");
+        fs::create_dir_all(dir.join("real_dir")).unwrap();
+        fs::write(dir.join("real_dir/file.txt"), "precious").unwrap();
+        fs::create_dir_all(dir.join("dst")).unwrap();
+        std::os::unix::fs::symlink(dir.join("real_dir"), dir.join("dst/item")).unwrap();
+        fs::write(dir.join("item"), "new").unwrap();
+
+        copy_entry_overwrite(&dir.join("item"), &dir.join("dst"), &PLAIN).unwrap();
+        assert_eq!(fs::read_to_string(dir.join("dst/item")).unwrap(), "new");
+        assert!(dir.join("real_dir/file.txt").exists());
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_copy_preserves_mtime() {
+        let dir = test_dir("preserve_mtime");
+        fs::create_dir_all(dir.join("src/sub")).unwrap();
+        fs::write(dir.join("src/sub/file.txt"), "data").unwrap();
+        fs::create_dir_all(dir.join("dst")).unwrap();
+        let old = FileTime::from_unix_time(1_500_000_000, 0);
+        filetime::set_file_mtime(dir.join("src/sub/file.txt"), old).unwrap();
+        filetime::set_file_mtime(dir.join("src/sub"), old).unwrap();
+        filetime::set_file_mtime(dir.join("src"), old).unwrap();
+
+        copy_entry(&dir.join("src"), &dir.join("dst"), &PRESERVING).unwrap();
+        for rel in ["src", "src/sub", "src/sub/file.txt"] {
+            let meta = fs::metadata(dir.join("dst").join(rel)).unwrap();
+            let copied = FileTime::from_last_modification_time(&meta);
+            assert!((copied.unix_seconds() - old.unix_seconds()).abs() <= 1, "{}", rel);
+        }
+
+        // Without the option the copy gets fresh times
+        copy_entry_overwrite(&dir.join("src"), &dir.join("dst"), &PLAIN).unwrap();
+        let meta = fs::metadata(dir.join("dst/src/sub/file.txt")).unwrap();
+        assert!(FileTime::from_last_modification_time(&meta).unix_seconds() > old.unix_seconds() + 1);
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_copy_preserves_executable_mode() {
+        use std::os::unix::fs::PermissionsExt;
+        let dir = test_dir("preserve_mode");
+        fs::create_dir_all(dir.join("dst")).unwrap();
+        fs::write(dir.join("run.sh"), "#!/bin/sh\n").unwrap();
+        fs::set_permissions(dir.join("run.sh"), fs::Permissions::from_mode(0o750)).unwrap();
+
+        copy_entry(&dir.join("run.sh"), &dir.join("dst"), &PRESERVING).unwrap();
+        let mode = fs::metadata(dir.join("dst/run.sh")).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o750);
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_copy_outcome_counts_every_file() {
+        let dir = test_dir("copy_outcome");
+        fs::create_dir_all(dir.join("src/sub")).unwrap();
+        fs::create_dir_all(dir.join("dst")).unwrap();
+        fs::write(dir.join("src/a.bin"), vec![1u8; 64 * 1024]).unwrap();
+        fs::write(dir.join("src/sub/b.bin"), vec![2u8; 64 * 1024]).unwrap();
+
+        let outcome = copy_entry(&dir.join("src"), &dir.join("dst"), &PLAIN).unwrap();
+        // Cloned or copied depends on the filesystem under temp_dir; both must be counted
+        assert_eq!(outcome.files_cloned + outcome.files_copied, 2);
+        assert_eq!(fs::read(dir.join("dst/src/a.bin")).unwrap(), vec![1u8; 64 * 1024]);
+        assert_eq!(fs::read(dir.join("dst/src/sub/b.bin")).unwrap(), vec![2u8; 64 * 1024]);
+        assert!(outcome.skipped.is_empty());
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_unreadable_file_skipped_unless_fail_fast() {
+        let dir = test_dir("copy_unreadable");
+        fs::create_dir_all(dir.join("src")).unwrap();
+        fs::create_dir_all(dir.join("dst")).unwrap();
+        fs::create_dir_all(dir.join("strict")).unwrap();
+        fs::write(dir.join("src/a.txt"), "a").unwrap();
+        fs::write(dir.join("src/z.txt"), "z").unwrap();
+        // A socket cannot be opened for reading, whoever runs the test
+        let locked = dir.join("src/locked.sock");
+        let _listener = std::os::unix::net::UnixListener::bind(&locked).unwrap();
+
+        let outcome = copy_entry(&dir.join("src"), &dir.join("dst"), &PLAIN).unwrap();
+        let strict = CopyOptions {
+            fail_fast: true,
+            ..PLAIN
+        };
+        let failed = copy_entry(&dir.join("src"), &dir.join("strict"), &strict);
+        let copied = ["a.txt", "z.txt"].map(|name| fs::read(dir.join("dst/src").join(name)).ok());
+        let _ = fs::remove_dir_all(&dir);
+
+        assert_eq!(outcome.skipped.len(), 1);
+        assert_eq!(outcome.skipped[0].path, locked.display().to_string());
+        assert_eq!(copied, [Some(b"a".to_vec()), Some(b"z".to_vec())]);
+        assert!(failed.is_err());
+    }
+
+    #[test]
+    fn test_failed_verification_fails_the_copy_without_fail_fast() {
+        let dir = test_dir("copy_verify_not_skipped");
+        fs::create_dir_all(dir.join("src/sub")).unwrap();
+        fs::create_dir_all(dir.join("dst")).unwrap();
+        fs::write(dir.join("src/a.txt"), "a").unwrap();
+        fs::write(dir.join("src/sub/b.txt"), "b").unwrap();
+
+        let corrupt = |path: &Path| {
+            if path.ends_with("b.txt") {
+                fs::write(path, "damaged!").unwrap();
+            }
+        };
+        let options = CopyOptions {
+            verify: true,
+            on_verify: Some(&corrupt),
+            ..PLAIN
+        };
+        let err = copy_entry(&dir.join("src"), &dir.join("dst"), &options).unwrap_err();
+        let a = fs::read_to_string(dir.join("dst/src/a.txt")).unwrap();
+        let b_left = dir.join("dst/src/sub/b.txt").exists();
+        let _ = fs::remove_dir_all(&dir);
+
+        assert_eq!(err.code(), "verificationFailed");
+        // The rest of the folder is still copied; only the bad copy is removed
+        assert_eq!(a, "a");
+        assert!(!b_left);
+    }
+
+    #[test]
+    fn test_clone_failure_leaves_no_partial_file() {
+        let dir = test_dir("clone_fallback");
+        fs::write(dir.join("src.txt"), "data").unwrap();
+
+        if clone_file(&dir.join("src.txt"), &dir.join("clone.txt")).is_ok() {
+            assert_eq!(fs::read_to_string(dir.join("clone.txt")).unwrap(), "data");
+        } else {
+            assert!(fs::symlink_metadata(dir.join("clone.txt")).is_err());
+        }
+        // Cloning into an existing destination never clobbers it
+        fs::write(dir.join("existing.txt"), "keep").unwrap();
+        assert!(clone_file(&dir.join("src.txt"), &dir.join("existing.txt")).is_err());
+        assert_eq!(fs::read_to_string(dir.join("existing.txt")).unwrap(), "keep");
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_move_file() {
+        let dir = test_dir("move_file");
+        fs::create_dir_all(dir.join("src")).unwrap();
+        fs::create_dir_all(dir.join("dst")).unwrap();
+        fs::write(dir.join("src/test.txt"), "hello").unwrap();
+
+        let result = move_entry(&dir.join("src/test.txt"), &dir.join("dst"));
+        assert!(result.is_ok());
+        assert!(dir.join("dst/test.txt").exists());
+        assert_eq!(fs::read_to_string(dir.join("dst/test.txt")).unwrap(), "hello");
+        // Source removed
+        assert!(!dir.join("src/test.txt").exists());
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_move_collision() {
+        let dir = test_dir("move_collision");
+        fs::create_dir_all(dir.join("src")).unwrap();
+        fs::create_dir_all(dir.join("dst")).unwrap();
+        fs::write(dir.join("src/test.txt"), "new").unwrap();
+        fs::write(dir.join("dst/test.txt"), "existing").unwrap();
+
+        let result = move_entry(&dir.join("src/test.txt"), &dir.join("dst"));
+        assert!(result.is_err());
+        // Source still exists, dest not overwritten
+        assert!(dir.join("src/test.txt").exists());
+        assert_eq!(fs::read_to_string(dir.join("dst/test.txt")).unwrap(), "existing");
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_copy_rename_policy_numbers_files() {
+        let dir = test_dir("copy_rename");
+        fs::create_dir_all(dir.join("src")).unwrap();
+        fs::create_dir_all(dir.join("dst")).unwrap();
+        fs::write(dir.join("src/notes.txt"), "new").unwrap();
+        fs::write(dir.join("dst/notes.txt"), "existing").unwrap();
+
+        let src = dir.join("src/notes.txt");
+        let first = copy_entry_on_conflict(&src, &dir.join("dst"), &PLAIN, ConflictPolicy::Rename)
+            .unwrap();
+        let second = copy_entry_on_conflict(&src, &dir.join("dst"), &PLAIN, ConflictPolicy::Rename)
+            .unwrap();
+        assert_eq!(first.dest, dir.join("dst/notes (1).txt"));
+        assert_eq!(second.dest, dir.join("dst/notes (2).txt"));
+        assert_eq!(fs::read_to_string(dir.join("dst/notes.txt")).unwrap(), "existing");
+        assert_eq!(fs::read_to_string(&second.dest).unwrap(), "new");
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_move_rename_policy_renames_top_level_dir() {
+        let dir = test_dir("move_rename_dir");
+        fs::create_dir_all(dir.join("src/photos.2024")).unwrap();
+        fs::create_dir_all(dir.join("dst/photos.2024")).unwrap();
+        fs::write(dir.join("src/photos.2024/a.jpg"), "a").unwrap();
+        fs::write(dir.join("dst/photos.2024/b.jpg"), "b").unwrap();
+
+        let dest = move_entry_on_conflict(
+            &dir.join("src/photos.2024"),
+            &dir.join("dst"),
+            ConflictPolicy::Rename,
+        )
+        .unwrap();
+        assert_eq!(dest, dir.join("dst/photos.2024 (1)"));
+        assert!(dest.join("a.jpg").is_file());
+        assert!(!dest.join("b.jpg").exists());
+        assert!(!dir.join("dst/photos.2024/a.jpg").exists());
+        assert!(!dir.join("src/photos.2024").exists());
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_overwrite_policy_replaces_destination() {
+        let dir = test_dir("policy_overwrite");
+        fs::create_dir_all(dir.join("src")).unwrap();
+        fs::create_dir_all(dir.join("dst")).unwrap();
+        fs::write(dir.join("src/a.txt"), "copied").unwrap();
+        fs::write(dir.join("src/b.txt"), "moved").unwrap();
+        fs::write(dir.join("dst/a.txt"), "old").unwrap();
+        fs::write(dir.join("dst/b.txt"), "old").unwrap();
+
+        let dst = dir.join("dst");
+        let copied =
+            copy_entry_on_conflict(&dir.join("src/a.txt"), &dst, &PLAIN, ConflictPolicy::Overwrite)
+                .unwrap();
+        let moved =
+            move_entry_on_conflict(&dir.join("src/b.txt"), &dst, ConflictPolicy::Overwrite)
+                .unwrap();
+        assert_eq!(copied.dest, dst.join("a.txt"));
+        assert_eq!(fs::read_to_string(dst.join("a.txt")).unwrap(), "copied");
+        assert_eq!(moved, dst.join("b.txt"));
+        assert_eq!(fs::read_to_string(dst.join("b.txt")).unwrap(), "moved");
+        assert!(!dir.join("src/b.txt").exists());
+        let onto_itself =
+            move_entry_on_conflict(&dst.join("b.txt"), &dst, ConflictPolicy::Overwrite);
+        assert!(onto_itself.is_err());
+        assert!(dst.join("b.txt").exists());
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_move_overwrite_keeps_destination_on_failure() {
+        let dir = test_dir("move_overwrite_safe");
+        fs::create_dir_all(dir.join("dst/box/box")).unwrap();
+        fs::write(dir.join("dst/box/box/inner.txt"), "inner").unwrap();
+        fs::write(dir.join("dst/gone.txt"), "old").unwrap();
+
+        let dst = dir.join("dst");
+        let into_ancestor =
+            move_entry_on_conflict(&dst.join("box/box"), &dst, ConflictPolicy::Overwrite);
+        let missing =
+            move_entry_on_conflict(&dir.join("src/gone.txt"), &dst, ConflictPolicy::Overwrite);
+        let mut names: Vec<_> = fs::read_dir(&dst)
+            .unwrap()
+            .map(|e| e.unwrap().file_name().to_string_lossy().to_string())
+            .collect();
+        names.sort();
+
+        assert_eq!(into_ancestor.unwrap_err().code(), "invalidArgument");
+        assert_eq!(fs::read_to_string(dst.join("box/box/inner.txt")).unwrap(), "inner");
+        assert_eq!(missing.unwrap_err().code(), "notFound");
+        assert_eq!(fs::read_to_string(dst.join("gone.txt")).unwrap(), "old");
+        assert_eq!(names, ["box", "gone.txt"]);
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_merge_keeps_unrelated_files() {
+        let dir = test_dir("merge");
+        fs::create_dir_all(dir.join("src/2024/trip")).unwrap();
+        fs::create_dir_all(dir.join("dst/2024/old")).unwrap();
+        fs::write(dir.join("src/2024/a.jpg"), "new a").unwrap();
+        fs::write(dir.join("src/2024/trip/b.jpg"), "b").unwrap();
+        fs::write(dir.join("dst/2024/a.jpg"), "old a").unwrap();
+        fs::write(dir.join("dst/2024/keep.jpg"), "keep").unwrap();
+        fs::write(dir.join("dst/2024/old/c.jpg"), "c").unwrap();
+
+        let dest = dir.join("dst/2024");
+        let outcome = copy_entry_merge(&dir.join("src/2024"), &dir.join("dst"), &PLAIN).unwrap();
+        let summary = outcome.merged.unwrap();
+        assert_eq!(fs::read_to_string(dest.join("a.jpg")).unwrap(), "new a");
+        assert_eq!(fs::read_to_string(dest.join("trip/b.jpg")).unwrap(), "b");
+        assert_eq!(fs::read_to_string(dest.join("keep.jpg")).unwrap(), "keep");
+        assert_eq!(fs::read_to_string(dest.join("old/c.jpg")).unwrap(), "c");
+        let name = |p: PathBuf| p.display().to_string();
+        assert_eq!(summary.overwritten, [name(dest.join("a.jpg"))]);
+        assert_eq!(summary.copied, [name(dest.join("trip"))]);
+        assert!(summary.conflicts.is_empty() && summary.skipped.is_empty());
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_merge_reports_type_conflicts_and_continues() {
+        let dir = test_dir("merge_conflict");
+        fs::create_dir_all(dir.join("src/photos/raw")).unwrap();
+        fs::create_dir_all(dir.join("dst/photos/notes.txt")).unwrap();
+        fs::write(dir.join("src/photos/raw/x.cr2"), "x").unwrap();
+        fs::write(dir.join("src/photos/notes.txt"), "n").unwrap();
+        fs::write(dir.join("src/photos/z.jpg"), "z").unwrap();
+        fs::write(dir.join("dst/photos/raw"), "a file").unwrap();
+
+        let outcome = copy_entry_on_conflict(
+            &dir.join("src/photos"),
+            &dir.join("dst"),
+            &PLAIN,
+            ConflictPolicy::Merge,
+        )
+        .unwrap();
+        let summary = outcome.merged.unwrap();
+        let mut conflicts = summary.conflicts.clone();
+        conflicts.sort();
+        let name = |rel: &str| dir.join("dst/photos").join(rel).display().to_string();
+        assert_eq!(conflicts, [name("notes.txt"), name("raw")]);
+        assert_eq!(summary.copied, [name("z.jpg")]);
+        assert_eq!(fs::read_to_string(dir.join("dst/photos/raw")).unwrap(), "a file");
+        assert!(dir.join("dst/photos/notes.txt").is_dir());
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_merge_keeps_file_whose_replacement_fails() {
+        let dir = test_dir("merge_failed_file");
+        fs::create_dir_all(dir.join("src/docs")).unwrap();
+        fs::create_dir_all(dir.join("dst/docs")).unwrap();
+        fs::write(dir.join("src/docs/a.txt"), "new").unwrap();
+        fs::write(dir.join("dst/docs/a.txt"), "old").unwrap();
+
+        // A copy that reads back wrong fails, standing in for any failed write
+        let corrupt = |path: &Path| fs::write(path, "damaged!").unwrap();
+        let options = CopyOptions {
+            verify: true,
+            on_verify: Some(&corrupt),
+            ..CopyOptions::default()
+        };
+        let outcome = copy_entry_merge(&dir.join("src/docs"), &dir.join("dst"), &options).unwrap();
+        let summary = outcome.merged.unwrap();
+        let names: Vec<_> = fs::read_dir(dir.join("dst/docs"))
+            .unwrap()
+            .map(|e| e.unwrap().file_name())
+            .collect();
+
+        assert_eq!(summary.skipped.len(), 1);
+        assert!(summary.overwritten.is_empty());
+        assert_eq!(fs::read_to_string(dir.join("dst/docs/a.txt")).unwrap(), "old");
+        assert_eq!(names, ["a.txt"]);
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_dry_runs_match_the_real_operations() {
+        let dir = test_dir("dry_runs");
+        fs::create_dir_all(dir.join("src/box")).unwrap();
+        fs::write(dir.join("src/box/a.txt"), "aaa").unwrap();
+        fs::write(dir.join("src/b.txt"), "bb").unwrap();
+        let (src, dst) = (dir.join("src"), dir.join("dst"));
+        let reset = || {
+            let _ = fs::remove_dir_all(&dst);
+            fs::create_dir_all(dst.join("box")).unwrap();
+            fs::write(dst.join("b.txt"), "old").unwrap();
+        };
+
+        let policies = [
+            ConflictPolicy::Fail,
+            ConflictPolicy::Overwrite,
+            ConflictPolicy::Rename,
+            ConflictPolicy::Merge,
+        ];
+        for policy in policies {
+            for name in ["box", "b.txt"] {
+                reset();
+                let before = listing(&dir);
+                let plan = plan_copy_on_conflict(&src.join(name), &dst, policy);
+                assert_eq!(listing(&dir), before, "{:?} copy plan changed something", policy);
+                let moved = plan_move_on_conflict(&src.join(name), &dst, policy);
+                assert_eq!(listing(&dir), before, "{:?} move plan changed something", policy);
+                if policy == ConflictPolicy::Merge {
+                    assert_eq!(moved.unwrap_err().code(), "invalidArgument");
+                }
+
+                let copied = copy_entry_on_conflict(&src.join(name), &dst, &PLAIN, policy);
+                match (plan, copied) {
+                    (Ok(plan), Ok(outcome)) => {
+                        assert_eq!(plan.destination, outcome.dest);
+                        assert_eq!(plan.source.as_deref(), Some(src.join(name).as_path()));
+                        assert_eq!(plan.conflict, policy != ConflictPolicy::Rename);
+                        assert_eq!(plan.bytes, if name == "box" { 3 } else { 2 });
+                    }
+                    (Err(planned), Err(real)) => assert_eq!(planned.code(), real.code()),
+                    (plan, copied) => panic!("{:?}: plan {:?}, copy {:?}", policy, plan, copied),
+                }
+            }
+        }
+        // Into itself is refused by the plan as by the copy
+        let into_self = plan_copy_on_conflict(&src, &src.join("box"), ConflictPolicy::Overwrite);
+        assert_eq!(into_self.unwrap_err().code(), "invalidArgument");
+
+        let planned = plan_delete(&src.join("box")).unwrap();
+        assert_eq!((planned.source, planned.bytes), (None, 3));
+        assert!(src.join("box/a.txt").exists());
+        delete_entry(&src.join("box")).unwrap();
+        assert!(plan_delete(&src.join("box")).is_err());
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    fn listing(dir: &Path) -> Vec<(PathBuf, Vec<u8>)> {
+        let mut found = Vec::new();
+        for entry in fs::read_dir(dir).unwrap() {
+            let path = entry.unwrap().path();
+            if path.is_dir() {
+                found.push((path.clone(), Vec::new()));
+                found.extend(listing(&path));
+            } else {
+                found.push((path.clone(), fs::read(&path).unwrap()));
+            }
+        }
+        found.sort();
+        found
+    }
+
+    #[test]
+    fn test_verified_copy_checks_each_file() {
+        let dir = test_dir("copy_verify");
+        fs::create_dir_all(dir.join("src/sub")).unwrap();
+        fs::create_dir_all(dir.join("dst")).unwrap();
+        fs::write(dir.join("src/a.txt"), "a").unwrap();
+        fs::write(dir.join("src/sub/b.txt"), "b").unwrap();
+
+        let verified = std::cell::RefCell::new(Vec::new());
+        let record = |path: &Path| verified.borrow_mut().push(path.to_path_buf());
+        let options = CopyOptions {
+            verify: true,
+            on_verify: Some(&record),
+            ..CopyOptions::default()
+        };
+        copy_entry(&dir.join("src"), &dir.join("dst"), &options).unwrap();
+        let mut verified = verified.into_inner();
+        verified.sort();
+        assert_eq!(verified, [dir.join("dst/src/a.txt"), dir.join("dst/src/sub/b.txt")]);
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_corrupted_copy_fails_verification() {
+        let dir = test_dir("copy_verify_corrupt");
+        fs::create_dir_all(dir.join("dst")).unwrap();
+        fs::write(dir.join("photo.raw"), "original").unwrap();
+        fs::write(dir.join("dst/photo.raw"), "old").unwrap();
+
+        // Stands in for a disk that writes something other than what it was given
+        let corrupt = |path: &Path| fs::write(path, "damaged!").unwrap();
+        let options = CopyOptions {
+            verify: true,
+            on_verify: Some(&corrupt),
+            ..CopyOptions::default()
+        };
+        let err = copy_entry_overwrite(&dir.join("photo.raw"), &dir.join("dst"), &options)
+            .unwrap_err();
+        assert_eq!(err.code(), "verificationFailed");
+        assert!(err.to_string().starts_with("Verification failed"));
+        assert_eq!(fs::read_to_string(dir.join("dst/photo.raw")).unwrap(), "old");
+        assert_eq!(fs::read_dir(dir.join("dst")).unwrap().count(), 1);
+        assert_eq!(fs::read_to_string(dir.join("photo.raw")).unwrap(), "original");
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_duplicate_entry_numbering() {
+        let dir = test_dir("duplicate");
+        fs::write(dir.join("report.txt"), "r").unwrap();
+
+        let names: Vec<_> = (0..3)
+            .map(|_| duplicate_entry(&dir.join("report.txt")).unwrap())
+            .map(|p| p.file_name().unwrap().to_string_lossy().to_string())
+            .collect();
+        assert_eq!(names, ["report copy.txt", "report copy 2.txt", "report copy 3.txt"]);
+        assert_eq!(fs::read_to_string(dir.join("report copy 3.txt")).unwrap(), "r");
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_duplicate_entry_without_extension() {
+        let dir = test_dir("duplicate_noext");
+        fs::write(dir.join("Makefile"), "all:").unwrap();
+        fs::write(dir.join(".bashrc"), "set -e").unwrap();
+        fs::create_dir_all(dir.join("site.v1/sub")).unwrap();
+        fs::write(dir.join("site.v1/sub/index.html"), "<p>").unwrap();
+
+        let makefile = duplicate_entry(&dir.join("Makefile")).unwrap();
+        let dotfile = duplicate_entry(&dir.join(".bashrc")).unwrap();
+        let folder = duplicate_entry(&dir.join("site.v1")).unwrap();
+        assert_eq!(makefile, dir.join("Makefile copy"));
+        assert_eq!(dotfile, dir.join(".bashrc copy"));
+        assert_eq!(folder, dir.join("site.v1 copy"));
+        assert_eq!(fs::read_to_string(folder.join("sub/index.html")).unwrap(), "<p>");
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_create_directory() {
+        let dir = test_dir("mkdir");
+
+        let result = create_directory(&dir, "new_folder");
+        assert!(result.is_ok());
+        assert!(dir.join("new_folder").is_dir());
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_create_directory_traversal_rejected() {
+        let dir = test_dir("mkdir_traversal");
+
+        let result = create_directory(&dir, "../escape");
+        assert!(result.is_err());
+        assert!(!dir.join("../escape").exists());
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_create_directory_collision() {
+        let dir = test_dir("mkdir_collision");
+        fs::create_dir(dir.join("existing")).unwrap();
+
+        let result = create_directory(&dir, "existing");
+        let err = result.unwrap_err();
+        assert_eq!(err.code(), "alreadyExists");
+        assert!(err.to_string().contains("Already exists"));
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_create_file() {
+        let dir = test_dir("touch");
+
+        let empty = create_file(&dir, "empty.txt", None).unwrap();
+        let templated = create_file(&dir, "notes.md", Some("# Notes\n")).unwrap();
+        assert_eq!(empty, dir.join("empty.txt"));
+        assert_eq!(fs::metadata(&empty).unwrap().len(), 0);
+        assert_eq!(fs::read_to_string(&templated).unwrap(), "# Notes\n");
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_create_file_traversal_rejected() {
+        let dir = test_dir("touch_traversal");
+
+        for name in ["../escape.txt", "sub/file.txt", "", ".."] {
+            let err = create_file(&dir, name, None).unwrap_err();
+            assert_eq!(err.code(), "invalidArgument", "{}", name);
+        }
+        assert!(!dir.join("../escape.txt").exists());
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_create_file_collision() {
+        let dir = test_dir("touch_collision");
+        fs::write(dir.join("existing.txt"), "keep").unwrap();
+        fs::create_dir(dir.join("folder")).unwrap();
+
+        for name in ["existing.txt", "folder"] {
+            let err = create_file(&dir, name, Some("new")).unwrap_err();
+            assert_eq!(err.code(), "alreadyExists");
+        }
+        assert_eq!(fs::read_to_string(dir.join("existing.txt")).unwrap(), "keep");
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_rename_file() {
+        let dir = test_dir("rename_file");
+        fs::write(dir.join("old.txt"), "hello").unwrap();
+
+        let result = rename_entry(&dir.join("old.txt"), "new.txt");
+        assert_eq!(result.unwrap(), dir.join("new.txt"));
+        assert!(!dir.join("old.txt").exists());
+        assert_eq!(fs::read_to_string(dir.join("new.txt")).unwrap(), "hello");
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_rename_case_only() {
+        let dir = test_dir("rename_case");
+        fs::write(dir.join("readme.md"), "docs").unwrap();
+
+        let result = rename_entry(&dir.join("readme.md"), "README.md");
+        assert!(result.is_ok());
+        // Works on both case-insensitive (APFS) and case-sensitive volumes
+        let names: Vec<String> = fs::read_dir(&dir)
+            .unwrap()
+            .map(|e| e.unwrap().file_name().to_string_lossy().to_string())
+            .collect();
+        assert_eq!(names, vec!["README.md".to_string()]);
+        assert_eq!(fs::read_to_string(dir.join("README.md")).unwrap(), "docs");
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_rename_collision() {
+        let dir = test_dir("rename_collision");
+        fs::write(dir.join("a.txt"), "aaa").unwrap();
+        fs::write(dir.join("b.txt"), "bbb").unwrap();
+
+        let result = rename_entry(&dir.join("a.txt"), "b.txt");
+        let err = result.unwrap_err();
+        assert_eq!(err.code(), "alreadyExists");
+        assert!(err.to_string().contains("already exists"));
+        // Neither file was touched
+        assert_eq!(fs::read_to_string(dir.join("a.txt")).unwrap(), "aaa");
+        assert_eq!(fs::read_to_string(dir.join("b.txt")).unwrap(), "bbb");
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_rename_rejects_separators() {
+        let dir = test_dir("rename_separators");
+        fs::write(dir.join("a.txt"), "aaa").unwrap();
+
+        assert!(rename_entry(&dir.join("a.txt"), "sub/b.txt").is_err());
+        assert!(rename_entry(&dir.join("a.txt"), "..").is_err());
+        assert!(rename_entry(&dir.join("a.txt"), "").is_err());
+        assert!(dir.join("a.txt").exists());
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_delete_file() {
+        let dir = test_dir("delete_file");
+        fs::write(dir.join("doomed.txt"), "bye").unwrap();
+
+        let result = delete_entry(&dir.join("doomed.txt"));
+        assert!(result.is_ok());
+        assert!(!dir.join("doomed.txt").exists());
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_copy_overwrite_file() {
+        let dir = test_dir("copy_overwrite");
+        fs::create_dir_all(dir.join("src")).unwrap();
+        fs::create_dir_all(dir.join("dst")).unwrap();
+        fs::write(dir.join("src/test.txt"), "new content").unwrap();
+        fs::write(dir.join("dst/test.txt"), "old content").unwrap();
+
+        let result = copy_entry_overwrite(&dir.join("src/test.txt"), &dir.join("dst"), &PLAIN);
+        assert!(result.is_ok());
+        assert_eq!(fs::read_to_string(dir.join("dst/test.txt")).unwrap(), "new content");
+        assert!(dir.join("src/test.txt").exists());
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_copy_overwrite_no_existing() {
+        let dir = test_dir("copy_overwrite_new");
+        fs::create_dir_all(dir.join("src")).unwrap();
+        fs::create_dir_all(dir.join("dst")).unwrap();
+        fs::write(dir.join("src/test.txt"), "hello").unwrap();
+
+        let result = copy_entry_overwrite(&dir.join("src/test.txt"), &dir.join("dst"), &PLAIN);
+        assert!(result.is_ok());
+        assert_eq!(fs::read_to_string(dir.join("dst/test.txt")).unwrap(), "hello");
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_delete_dir() {
+        let dir = test_dir("delete_dir");
+        fs::create_dir_all(dir.join("doomed/sub")).unwrap();
+        fs::write(dir.join("doomed/sub/file.txt"), "bye").unwrap();
+
+        let result = delete_entry(&dir.join("doomed"));
+        assert!(result.is_ok());
+        assert!(!dir.join("doomed").exists());
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_copy_rel_entry_creates_parents() {
+        let dir = test_dir("copy_rel_parents");
+        fs::create_dir_all(dir.join("left/sub/dir")).unwrap();
+        fs::create_dir_all(dir.join("right")).unwrap();
+        fs::write(dir.join("left/sub/dir/file.txt"), "new").unwrap();
+
+        let outcome =
+            copy_rel_entry(&dir.join("left"), &dir.join("right"), "sub/dir/file.txt", &PLAIN)
+                .unwrap();
+        assert_eq!(outcome.dest, dir.join("right/sub/dir/file.txt"));
+        assert_eq!(fs::read_to_string(dir.join("right/sub/dir/file.txt")).unwrap(), "new");
+
+        // Existing entries are replaced
+        fs::write(dir.join("left/sub/dir/file.txt"), "newer").unwrap();
+        copy_rel_entry(&dir.join("left"), &dir.join("right"), "sub/dir/file.txt", &PLAIN).unwrap();
+        assert_eq!(fs::read_to_string(dir.join("right/sub/dir/file.txt")).unwrap(), "newer");
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_copy_rel_entry_rejects_traversal() {
+        let dir = test_dir("copy_rel_traversal");
+        fs::create_dir_all(dir.join("left")).unwrap();
+        fs::create_dir_all(dir.join("right")).unwrap();
+        fs::write(dir.join("secret.txt"), "x").unwrap();
+
+        let result = copy_rel_entry(&dir.join("left"), &dir.join("right"), "../secret.txt", &PLAIN);
+        assert!(result.is_err());
+        assert!(copy_rel_entry(&dir.join("left"), &dir.join("right"), "", &PLAIN).is_err());
+        assert!(!dir.join("right/secret.txt").exists());
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_copy_rel_entry_rejects_parent_symlink_out_of_root() {
+        let dir = test_dir("copy_rel_symlink_parent");
+        fs::create_dir_all(dir.join("left/sub")).unwrap();
+        fs::create_dir_all(dir.join("right")).unwrap();
+        fs::create_dir_all(dir.join("outside")).unwrap();
+        fs::write(dir.join("left/sub/file.txt"), "data").unwrap();
+        std::os::unix::fs::symlink(dir.join("outside"), dir.join("right/sub")).unwrap();
+
+        let result = copy_rel_entry(&dir.join("left"), &dir.join("right"), "sub/file.txt", &PLAIN);
+        assert!(result.is_err());
+        assert!(!dir.join("outside/file.txt").exists());
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_write_atomic_replaces_file() {
+        let dir = test_dir("write_atomic");
+        let target = dir.join("state.json");
+        fs::write(&target, "old").unwrap();
+
+        write_atomic(&target, b"new").unwrap();
+        assert_eq!(fs::read_to_string(&target).unwrap(), "new");
+        assert!(!atomic_temp_path(&target).exists());
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_write_atomic_interrupted_keeps_old_contents() {
+        use std::io::Write;
+        let dir = test_dir("write_atomic_interrupted");
+        let target = dir.join("state.json");
+        fs::write(&target, "old").unwrap();
+        let tmp = atomic_temp_path(&target);
+        assert_eq!(tmp, dir.join("state.json.tmp"));
+
+        let result: Result<(), CommandError> = write_atomic_with(&target, |file| {
+            file.write_all(b"{\"trunc").unwrap();
+            // Mid-write, new bytes only exist in the temp file
+            assert!(tmp.exists());
+            assert_eq!(fs::read_to_string(&target).unwrap(), "old");
+            Err("disk full".into())
+        });
+
+        assert_eq!(result.unwrap_err().to_string(), "disk full");
+        assert_eq!(fs::read_to_string(&target).unwrap(), "old");
+        assert!(!tmp.exists());
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_set_permissions_counts_real_changes() {
+        use std::os::unix::fs::PermissionsExt;
+        let dir = test_dir("chmod_single");
+        let script = dir.join("deploy.sh");
+        fs::write(&script, "#!/bin/sh").unwrap();
+        fs::set_permissions(&script, fs::Permissions::from_mode(0o644)).unwrap();
+
+        let outcome = set_permissions(&script, 0o755, false, false).unwrap();
+        assert_eq!(outcome.changed, 1);
+        let mode = fs::metadata(&script).unwrap().permissions().mode();
+        assert_eq!(mode & 0o7777, 0o755);
+        assert_eq!(set_permissions(&script, 0o755, false, false).unwrap().changed, 0);
+        assert_eq!(
+            set_permissions(&script, 0o17777, false, false).unwrap_err().code(),
+            "invalidArgument"
+        );
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_set_permissions_conditional_execute() {
+        use std::os::unix::fs::PermissionsExt;
+        let dir = test_dir("chmod_recursive");
+        fs::create_dir_all(dir.join("tree/bin")).unwrap();
+        fs::write(dir.join("tree/bin/run"), "").unwrap();
+        fs::write(dir.join("tree/notes.txt"), "").unwrap();
+        fs::set_permissions(dir.join("tree/bin/run"), fs::Permissions::from_mode(0o700)).unwrap();
+        fs::set_permissions(dir.join("tree/notes.txt"), fs::Permissions::from_mode(0o600)).unwrap();
+        for folder in ["tree", "tree/bin"] {
+            fs::set_permissions(dir.join(folder), fs::Permissions::from_mode(0o700)).unwrap();
+        }
+        std::os::unix::fs::symlink("notes.txt", dir.join("tree/link")).unwrap();
+
+        let outcome = set_permissions(&dir.join("tree"), 0o755, true, true).unwrap();
+        assert!(outcome.errors.is_empty());
+        let mode = |rel: &str| fs::metadata(dir.join(rel)).unwrap().permissions().mode() & 0o7777;
+        assert_eq!(mode("tree/bin/run"), 0o755);
+        assert_eq!(mode("tree/notes.txt"), 0o644);
+        assert_eq!(mode("tree/bin"), 0o755);
+        // Both folders and both files; the link is left alone
+        assert_eq!(outcome.changed, 4);
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_set_modified_keeps_milliseconds() {
+        let dir = test_dir("set_modified");
+        fs::write(dir.join("a.txt"), "a").unwrap();
+
+        let meta = set_modified(&dir.join("a.txt"), 1_600_000_000_123).unwrap();
+        assert_eq!(meta.modified, Some(1_600_000_000_123));
+        let meta = set_modified(&dir, 1_500_000_000_000).unwrap();
+        assert_eq!(meta.kind, crate::model::EntryKind::Dir);
+        assert_eq!(meta.modified, Some(1_500_000_000_000));
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_copy_times_does_not_follow_links() {
+        let dir = test_dir("copy_times");
+        fs::write(dir.join("target.txt"), "t").unwrap();
+        fs::create_dir(dir.join("left")).unwrap();
+        fs::create_dir(dir.join("right")).unwrap();
+        std::os::unix::fs::symlink("target.txt", dir.join("link")).unwrap();
+        set_modified(&dir.join("target.txt"), 1_000_000_000_000).unwrap();
+        set_modified(&dir.join("left"), 1_234_567_890_000).unwrap();
+
+        let meta = copy_times(&dir.join("left"), &dir.join("right")).unwrap();
+        assert_eq!(meta.modified, Some(1_234_567_890_000));
+        let meta = copy_times(&dir.join("left"), &dir.join("link")).unwrap();
+        assert_eq!(meta.kind, crate::model::EntryKind::Symlink);
+        assert_eq!(meta.modified, Some(1_234_567_890_000));
+        let target = fs::metadata(dir.join("target.txt")).unwrap();
+        assert_eq!(FileTime::from_last_modification_time(&target).unix_seconds(), 1_000_000_000);
+        let missing = copy_times(&dir.join("gone"), &dir.join("right")).unwrap_err();
+        assert_eq!(missing.code(), "notFound");
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
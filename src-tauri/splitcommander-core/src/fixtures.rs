@@ -0,0 +1,346 @@
+//! Deterministic synthetic trees for benchmarks and stress tests.
+//!
+//! A [`FixtureSpec`] plus its seed fully determines the generated left/right pair, so
+//! numbers from `cargo bench` are comparable across runs and machines. Fixtures live
+//! under the system temp dir and are removed when the [`Fixture`] is dropped.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+#[derive(Debug, Clone)]
+pub struct FixtureSpec {
+    pub seed: u64,
+    /// Levels of subdirectories below the root
+    pub depth: usize,
+    /// Subdirectories per directory
+    pub fan_out: usize,
+    pub files_per_dir: usize,
+    /// File sizes are log-uniform between these bounds (inclusive)
+    pub min_file_size: u64,
+    pub max_file_size: u64,
+    /// Share of entries (0-100) created as symlinks to a sibling file
+    pub symlink_percent: u8,
+    /// Share of files (0-100) that differ between left and right
+    pub mutate_percent: u8,
+}
+
+impl Default for FixtureSpec {
+    fn default() -> Self {
+        Self {
+            seed: 42,
+            depth: 3,
+            fan_out: 4,
+            files_per_dir: 16,
+            min_file_size: 0,
+            max_file_size: 64 * 1024,
+            symlink_percent: 2,
+            mutate_percent: 5,
+        }
+    }
+}
+
+/// Counts of what was generated on the left side, and how many entries the right differs in.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FixtureStats {
+    pub dirs: usize,
+    pub files: usize,
+    pub symlinks: usize,
+    pub bytes: u64,
+    /// Files resized, rewritten with the same size, or missing on the right
+    pub mutated: usize,
+    /// Files present only on the right
+    pub added: usize,
+}
+
+/// A generated left/right tree pair. Dropping it deletes both trees.
+pub struct Fixture {
+    pub root: PathBuf,
+    pub left: PathBuf,
+    pub right: PathBuf,
+    pub stats: FixtureStats,
+}
+
+impl Drop for Fixture {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.root);
+    }
+}
+
+/// Generates the tree pair described by `spec` in a fresh temp directory.
+pub fn generate(spec: &FixtureSpec) -> io::Result<Fixture> {
+    static NEXT: AtomicUsize = AtomicUsize::new(0);
+    let root = std::env::temp_dir().join(format!(
+        "sc_fixture_{}_{}_{}",
+        spec.seed,
+        std::process::id(),
+        NEXT.fetch_add(1, Ordering::Relaxed)
+    ));
+    let _ = fs::remove_dir_all(&root);
+
+    // Construct first so a failure part-way still cleans up on drop
+    let mut fixture = Fixture {
+        left: root.join("left"),
+        right: root.join("right"),
+        root,
+        stats: FixtureStats::default(),
+    };
+    let mut generator = Generator {
+        spec,
+        rng: SplitMix64(spec.seed),
+        stats: FixtureStats::default(),
+    };
+    generator.dir(&fixture.left, &fixture.right, 0)?;
+    fixture.stats = generator.stats;
+    Ok(fixture)
+}
+
+struct Generator<'a> {
+    spec: &'a FixtureSpec,
+    rng: SplitMix64,
+    stats: FixtureStats,
+}
+
+impl Generator<'_> {
+    fn dir(&mut self, left: &Path, right: &Path, level: usize) -> io::Result<()> {
+        fs::create_dir_all(left)?;
+        fs::create_dir_all(right)?;
+        self.stats.dirs += 1;
+
+        let mut last_file: Option<String> = None;
+        for i in 0..self.spec.files_per_dir {
+            if let Some(target) = last_file.as_deref() {
+                if self.rng.percent(self.spec.symlink_percent) {
+                    let name = format!("link_{:04}", i);
+                    make_symlink(target, &left.join(&name))?;
+                    make_symlink(target, &right.join(&name))?;
+                    self.stats.symlinks += 1;
+                    continue;
+                }
+            }
+
+            let name = format!("file_{:04}.bin", i);
+            let size = self.rng.log_uniform(self.spec.min_file_size, self.spec.max_file_size);
+            let fill = self.rng.next() as u8;
+            let data = vec![fill; size as usize];
+            fs::write(left.join(&name), &data)?;
+            self.stats.files += 1;
+            self.stats.bytes += size;
+
+            if self.rng.percent(self.spec.mutate_percent) {
+                self.stats.mutated += 1;
+                match self.rng.below(3) {
+                    // Missing on the right
+                    0 => {}
+                    // Different size
+                    1 => fs::write(right.join(&name), vec![fill; size as usize + 1])?,
+                    // Same size, different bytes
+                    _ => {
+                        let mut changed = data;
+                        match changed.first_mut() {
+                            Some(b) => *b = b.wrapping_add(1),
+                            None => changed.push(0),
+                        }
+                        fs::write(right.join(&name), changed)?;
+                    }
+                }
+                // Every mutation also adds a right-only neighbour
+                fs::write(right.join(format!("added_{:04}.bin", i)), [fill])?;
+                self.stats.added += 1;
+            } else {
+                fs::write(right.join(&name), &data)?;
+            }
+            last_file = Some(name);
+        }
+
+        if level < self.spec.depth {
+            for i in 0..self.spec.fan_out {
+                let name = format!("dir_{:03}", i);
+                self.dir(&left.join(&name), &right.join(&name), level + 1)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(unix)]
+fn make_symlink(target: &str, link: &Path) -> io::Result<()> {
+    std::os::unix::fs::symlink(target, link)
+}
+
+#[cfg(windows)]
+fn make_symlink(target: &str, link: &Path) -> io::Result<()> {
+    // Creating symlinks needs developer mode on Windows; fall back to a copy-free marker
+    std::os::windows::fs::symlink_file(target, link).or_else(|_| fs::write(link, target))
+}
+
+/// Small, fast PRNG with a fixed algorithm, so a seed means the same tree forever
+/// (unlike `std`'s randomized hashers or an external crate that may change streams).
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    fn below(&mut self, n: u64) -> u64 {
+        self.next() % n
+    }
+
+    fn percent(&mut self, p: u8) -> bool {
+        self.below(100) < p as u64
+    }
+
+    /// Log-uniform in `[min, max]`: many small files, a long tail of large ones.
+    fn log_uniform(&mut self, min: u64, max: u64) -> u64 {
+        if max <= min {
+            return min;
+        }
+        let lo = ((min + 1) as f64).ln();
+        let hi = ((max + 1) as f64).ln();
+        let unit = (self.next() >> 11) as f64 / (1u64 << 53) as f64;
+        let value = (lo + unit * (hi - lo)).exp() as u64 - 1;
+        value.clamp(min, max)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compare::compare;
+    use crate::ignore::IgnoreRules;
+    use crate::model::CompareMode;
+    use crate::scan::scan_directory;
+    use std::sync::atomic::AtomicBool;
+    use std::sync::Mutex;
+    use std::time::{Duration, Instant};
+
+    fn small_spec(seed: u64) -> FixtureSpec {
+        FixtureSpec {
+            seed,
+            depth: 2,
+            fan_out: 2,
+            files_per_dir: 8,
+            max_file_size: 1024,
+            symlink_percent: 10,
+            mutate_percent: 20,
+            ..Default::default()
+        }
+    }
+
+    fn listing(root: &Path) -> Vec<(String, u64)> {
+        let rules = IgnoreRules::new(&[]);
+        let cancel = AtomicBool::new(false);
+        let scan = scan_directory(root, &rules, &[], &cancel, &|_| {}).unwrap();
+        let mut entries: Vec<(String, u64)> = scan
+            .entries
+            .iter()
+            .filter(|(_, meta)| meta.kind != crate::model::EntryKind::Dir)
+            .map(|(key, meta)| (key.clone(), meta.size))
+            .collect();
+        entries.sort();
+        entries
+    }
+
+    #[test]
+    fn test_same_seed_same_tree() {
+        let a = generate(&small_spec(7)).unwrap();
+        let b = generate(&small_spec(7)).unwrap();
+        let c = generate(&small_spec(8)).unwrap();
+
+        assert_eq!(a.stats, b.stats);
+        assert_eq!(listing(&a.left), listing(&b.left));
+        assert_eq!(listing(&a.right), listing(&b.right));
+        assert_ne!(listing(&a.left), listing(&c.left));
+    }
+
+    #[test]
+    fn test_stats_match_compare_result() {
+        let fixture = generate(&small_spec(3)).unwrap();
+        let stats = &fixture.stats;
+        // 1 + 2 + 4 directories, 8 entry slots each
+        assert_eq!(stats.dirs, 7);
+        assert_eq!(stats.files + stats.symlinks, 56);
+
+        let rules = IgnoreRules::new(&[]);
+        let cancel = AtomicBool::new(false);
+        let left = scan_directory(&fixture.left, &rules, &[], &cancel, &|_| {}).unwrap();
+        let right = scan_directory(&fixture.right, &rules, &[], &cancel, &|_| {}).unwrap();
+        let result = compare(&left, &right, CompareMode::Smart, &cancel).unwrap();
+        assert_eq!(result.summary.only_right, stats.added);
+        // Same-size rewrites are invisible to Smart mode
+        assert!(result.summary.only_left + result.summary.meta_diff <= stats.mutated);
+    }
+
+    #[test]
+    fn test_fixture_cleans_up_on_drop() {
+        let fixture = generate(&small_spec(1)).unwrap();
+        let root = fixture.root.clone();
+        assert!(root.exists());
+        drop(fixture);
+        assert!(!root.exists());
+    }
+
+    /// Large-tree stress run; `cargo test -- --ignored` to include it.
+    #[test]
+    #[ignore]
+    fn stress_scan_and_compare_large_tree() {
+        let fixture = generate(&FixtureSpec {
+            depth: 4,
+            fan_out: 6,
+            files_per_dir: 60,
+            max_file_size: 4096,
+            ..Default::default()
+        })
+        .unwrap();
+        let rules = IgnoreRules::new(&[]);
+        let cancel = AtomicBool::new(false);
+        let left = scan_directory(&fixture.left, &rules, &[], &cancel, &|_| {}).unwrap();
+        let right = scan_directory(&fixture.right, &rules, &[], &cancel, &|_| {}).unwrap();
+        let result = compare(&left, &right, CompareMode::Smart, &cancel).unwrap();
+        assert_eq!(left.count, fixture.stats.dirs - 1 + fixture.stats.files + fixture.stats.symlinks);
+        assert_eq!(result.summary.only_right, fixture.stats.added);
+    }
+
+    /// Cancel latency on a ~500k-file tree; `cargo test -- --ignored` to include it.
+    #[test]
+    #[ignore]
+    fn stress_cancel_stops_progress_promptly() {
+        let fixture = generate(&FixtureSpec {
+            depth: 4,
+            fan_out: 6,
+            files_per_dir: 320,
+            max_file_size: 0,
+            symlink_percent: 0,
+            mutate_percent: 0,
+            ..Default::default()
+        })
+        .unwrap();
+        let rules = IgnoreRules::new(&[]);
+        let cancel = AtomicBool::new(false);
+        let reports = Mutex::new(Vec::new());
+
+        let (cancelled_at, result) = std::thread::scope(|scope| {
+            let scan = scope.spawn(|| {
+                scan_directory(&fixture.left, &rules, &[], &cancel, &|_| {
+                    reports.lock().unwrap().push(Instant::now());
+                })
+            });
+            while reports.lock().unwrap().len() < 3 {
+                std::thread::sleep(Duration::from_millis(5));
+            }
+            cancel.store(true, Ordering::Relaxed);
+            (Instant::now(), scan.join().unwrap())
+        });
+
+        assert!(result.unwrap_err().contains("cancelled"));
+        let last = *reports.lock().unwrap().last().unwrap();
+        let late = last.saturating_duration_since(cancelled_at);
+        assert!(late <= Duration::from_millis(200), "progress {:?} after cancel", late);
+    }
+}
@@ -13,6 +13,7 @@ pub const MACOS_NOISE: &[&str] = &[
     "Thumbs.db",
 ];
 
+#[derive(Clone)]
 pub struct IgnoreRules {
     patterns: Vec<String>,
 }
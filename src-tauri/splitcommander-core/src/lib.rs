@@ -0,0 +1,32 @@
+//! Filesystem scanning, directory comparison and report export for SplitCommander.
+//!
+//! Nothing in this crate depends on Tauri; the desktop app wraps it with IPC
+//! commands in `src-tauri/src/core/commands.rs`.
+
+pub mod model;
+pub mod ignore;
+pub mod security;
+pub mod paths;
+pub mod scan;
+pub mod source;
+pub mod compare;
+pub mod export;
+pub mod verify;
+pub mod search;
+pub mod preview;
+pub mod textdiff;
+pub mod checksum;
+pub mod duplicates;
+pub mod fileops;
+pub mod archive;
+pub mod sync;
+pub mod refresh;
+pub mod sort;
+pub mod undo;
+pub mod fixtures;
+pub mod disk;
+pub mod error;
+pub mod platform;
+
+#[cfg(test)]
+mod slow_fs;
@@ -0,0 +1,531 @@
+use std::collections::{HashMap, HashSet};
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum EntryKind {
+    File,
+    Dir,
+    Symlink,
+    /// Named pipe; reading one blocks until a writer shows up
+    Fifo,
+    Socket,
+    BlockDevice,
+    CharDevice,
+}
+
+impl EntryKind {
+    /// The kind for `file_type`. Fifos, sockets and devices are only told apart on Unix;
+    /// elsewhere they count as files.
+    pub fn of(file_type: &std::fs::FileType) -> Self {
+        if file_type.is_dir() {
+            EntryKind::Dir
+        } else if file_type.is_symlink() {
+            EntryKind::Symlink
+        } else if file_type.is_file() {
+            EntryKind::File
+        } else {
+            Self::special(file_type)
+        }
+    }
+
+    #[cfg(unix)]
+    fn special(file_type: &std::fs::FileType) -> Self {
+        use std::os::unix::fs::FileTypeExt;
+        if file_type.is_fifo() {
+            EntryKind::Fifo
+        } else if file_type.is_socket() {
+            EntryKind::Socket
+        } else if file_type.is_block_device() {
+            EntryKind::BlockDevice
+        } else if file_type.is_char_device() {
+            EntryKind::CharDevice
+        } else {
+            EntryKind::File
+        }
+    }
+
+    #[cfg(not(unix))]
+    fn special(_file_type: &std::fs::FileType) -> Self {
+        EntryKind::File
+    }
+
+    /// Fifos, sockets and devices: their size says nothing about them and their
+    /// contents must never be read.
+    pub fn is_special(self) -> bool {
+        matches!(
+            self,
+            EntryKind::Fifo | EntryKind::Socket | EntryKind::BlockDevice | EntryKind::CharDevice
+        )
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EntryMeta {
+    pub kind: EntryKind,
+    pub size: u64,
+    /// Epoch milliseconds for JS interop
+    pub modified: Option<u64>,
+    pub symlink_target: Option<String>,
+    /// Unix permission bits (`0o755`, ...); `None` off Unix
+    pub mode: Option<u32>,
+    /// Set for files with more than one hard link (Unix only), so their size can be
+    /// counted once. Not sent to the frontend.
+    #[serde(skip)]
+    pub link: Option<LinkId>,
+}
+
+/// Device and inode number shared by all hard links to the same file.
+pub type LinkId = (u64, u64);
+
+/// The [`LinkId`] of a file with more than one hard link. Always `None` off Unix and
+/// for directories, so most entries never reach a seen-links set.
+#[cfg(unix)]
+pub fn link_id(meta: &std::fs::Metadata) -> Option<LinkId> {
+    use std::os::unix::fs::MetadataExt;
+    (meta.is_file() && meta.nlink() > 1).then(|| (meta.dev(), meta.ino()))
+}
+
+#[cfg(not(unix))]
+pub fn link_id(_meta: &std::fs::Metadata) -> Option<LinkId> {
+    None
+}
+
+/// Permission bits of an entry, including setuid, setgid and sticky; `None` off Unix.
+#[cfg(unix)]
+pub fn mode_bits(meta: &std::fs::Metadata) -> Option<u32> {
+    use std::os::unix::fs::PermissionsExt;
+    Some(meta.permissions().mode() & 0o7777)
+}
+
+#[cfg(not(unix))]
+pub fn mode_bits(_meta: &std::fs::Metadata) -> Option<u32> {
+    None
+}
+
+/// Permission bits for display: [`mode_bits`] on Unix; on Windows, read-only or
+/// read-write (plus execute for folders) from the read-only attribute.
+#[cfg(not(windows))]
+pub fn display_mode(meta: &std::fs::Metadata) -> Option<u32> {
+    mode_bits(meta)
+}
+
+#[cfg(windows)]
+pub fn display_mode(meta: &std::fs::Metadata) -> Option<u32> {
+    let mode = if meta.permissions().readonly() { 0o444 } else { 0o666 };
+    Some(if meta.is_dir() { mode | 0o111 } else { mode })
+}
+
+/// Whether Windows marks the entry hidden; dot names are hidden everywhere, and checked
+/// separately by name.
+#[cfg(windows)]
+pub fn hidden_attribute(meta: &std::fs::Metadata) -> bool {
+    use std::os::windows::fs::MetadataExt;
+    const FILE_ATTRIBUTE_HIDDEN: u32 = 0x2;
+    meta.file_attributes() & FILE_ATTRIBUTE_HIDDEN != 0
+}
+
+#[cfg(not(windows))]
+pub fn hidden_attribute(_meta: &std::fs::Metadata) -> bool {
+    false
+}
+
+/// Why the current user may not read an entry (or, for a folder, list it). The system
+/// is only asked when `meta` leaves someone without read access, so ordinary entries
+/// cost nothing extra. Always `None` off Linux and macOS.
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+pub fn read_denied(path: &std::path::Path, meta: &std::fs::Metadata) -> Option<std::io::Error> {
+    if mode_bits(meta)? & 0o444 == 0o444 {
+        return None;
+    }
+    crate::platform::check_readable(path, meta.is_dir()).err()
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+pub fn read_denied(_path: &std::path::Path, _meta: &std::fs::Metadata) -> Option<std::io::Error> {
+    None
+}
+
+/// Owner and group names by id, each looked up once; keep one per listing.
+#[derive(Debug, Default)]
+pub struct OwnerNames {
+    users: HashMap<u32, String>,
+    groups: HashMap<u32, String>,
+}
+
+impl OwnerNames {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Owner and group of an entry, as numbers when an id has no name; `None` off Unix.
+    #[cfg(unix)]
+    pub fn of(&mut self, meta: &std::fs::Metadata) -> (Option<String>, Option<String>) {
+        use crate::platform::{account_name, AccountKind};
+        use std::os::unix::fs::MetadataExt;
+        let (uid, gid) = (meta.uid(), meta.gid());
+        let user = self.users.entry(uid).or_insert_with(|| {
+            account_name(uid, AccountKind::User).unwrap_or_else(|| uid.to_string())
+        });
+        let user = user.clone();
+        let group = self.groups.entry(gid).or_insert_with(|| {
+            account_name(gid, AccountKind::Group).unwrap_or_else(|| gid.to_string())
+        });
+        (Some(user), Some(group.clone()))
+    }
+
+    #[cfg(not(unix))]
+    pub fn of(&mut self, _meta: &std::fs::Metadata) -> (Option<String>, Option<String>) {
+        (None, None)
+    }
+}
+
+/// Whether a file's size should count towards a unique (on-disk) total: true unless
+/// it is a hard link to a file already counted in `seen`.
+pub fn first_link(seen: &mut HashSet<LinkId>, link: Option<LinkId>) -> bool {
+    link.is_none_or(|link| seen.insert(link))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum DiffKind {
+    OnlyLeft,
+    OnlyRight,
+    TypeMismatch,
+    Same,
+    MetaDiff,
+    Error,
+    /// Names that differ only by case where one side cannot tell them apart
+    CaseCollision,
+    /// Fifos, sockets or devices of the same kind on both sides, whose contents
+    /// cannot be compared
+    Special,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DiffItem {
+    pub rel_path: String,
+    pub diff_kind: DiffKind,
+    pub left: Option<EntryMeta>,
+    pub right: Option<EntryMeta>,
+    pub error_message: Option<String>,
+    /// For modified entries: the side with the later modification time, when known
+    #[serde(default)]
+    pub newer: Option<Side>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Side {
+    Left,
+    Right,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum CompareMode {
+    Structure,
+    Smart,
+    /// Like Smart, but files whose modification times differ are changed even at equal size
+    Timestamp,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CompareSummary {
+    pub total_left: usize,
+    pub total_right: usize,
+    pub only_left: usize,
+    pub only_right: usize,
+    pub type_mismatch: usize,
+    pub same: usize,
+    pub meta_diff: usize,
+    pub errors: usize,
+    /// App-owned directories (live state, caches) skipped because they sit under a root
+    #[serde(default)]
+    pub excluded_by_self: usize,
+    /// Modified entries whose left/right copy is the newer one
+    #[serde(default)]
+    pub left_newer: usize,
+    #[serde(default)]
+    pub right_newer: usize,
+    #[serde(default)]
+    pub case_collisions: usize,
+    /// Fifos, sockets and devices on both sides, left uncompared
+    #[serde(default)]
+    pub special: usize,
+    #[serde(default)]
+    pub left_case_sensitive: Option<bool>,
+    #[serde(default)]
+    pub right_case_sensitive: Option<bool>,
+    /// Conditions the user should know about that don't belong to a single entry
+    #[serde(default)]
+    pub warnings: Vec<String>,
+    /// Directories whose status is still being resolved (single-level compare only)
+    #[serde(default)]
+    pub pending: usize,
+    /// File sizes of one-sided entries; directories add nothing themselves
+    #[serde(default)]
+    pub bytes_only_left: u64,
+    #[serde(default)]
+    pub bytes_only_right: u64,
+    /// The larger of the two sizes of each modified entry
+    #[serde(default)]
+    pub bytes_modified: u64,
+    /// The byte totals above with every hard-linked file counted once
+    #[serde(default)]
+    pub unique_bytes_only_left: u64,
+    #[serde(default)]
+    pub unique_bytes_only_right: u64,
+    #[serde(default)]
+    pub unique_bytes_modified: u64,
+    /// Directories at the depth limit whose contents were not compared
+    #[serde(default)]
+    pub depth_limited: usize,
+}
+
+impl CompareSummary {
+    /// Recomputes the per-kind counts and side totals after `diffs` changed.
+    pub fn recount(&mut self, diffs: &[DiffItem]) {
+        self.total_left = diffs.iter().filter(|d| d.left.is_some()).count();
+        self.total_right = diffs.iter().filter(|d| d.right.is_some()).count();
+        self.same = 0;
+        self.only_left = 0;
+        self.only_right = 0;
+        self.meta_diff = 0;
+        self.left_newer = 0;
+        self.right_newer = 0;
+        self.type_mismatch = 0;
+        self.case_collisions = 0;
+        self.errors = 0;
+        self.count_bytes(diffs);
+        for diff in diffs {
+            match diff.diff_kind {
+                DiffKind::Same => self.same += 1,
+                DiffKind::OnlyLeft => self.only_left += 1,
+                DiffKind::OnlyRight => self.only_right += 1,
+                DiffKind::MetaDiff => {
+                    self.meta_diff += 1;
+                    match diff.newer {
+                        Some(Side::Left) => self.left_newer += 1,
+                        Some(Side::Right) => self.right_newer += 1,
+                        None => {}
+                    }
+                }
+                DiffKind::TypeMismatch => self.type_mismatch += 1,
+                DiffKind::CaseCollision => self.case_collisions += 1,
+                DiffKind::Special => self.special += 1,
+                DiffKind::Error => self.errors += 1,
+            }
+        }
+    }
+
+    /// Recomputes the byte totals of one-sided and modified entries in `diffs`.
+    pub fn count_bytes(&mut self, diffs: &[DiffItem]) {
+        let file_size = |meta: &Option<EntryMeta>| match meta {
+            Some(m) if m.kind != EntryKind::Dir => m.size,
+            _ => 0,
+        };
+        // Counts a file only the first time its hard-link group shows up in `seen`
+        let unique_size = |meta: &Option<EntryMeta>, seen: &mut HashSet<LinkId>| match meta {
+            Some(m) if first_link(seen, m.link) => file_size(meta),
+            _ => 0,
+        };
+        let mut seen_left = HashSet::new();
+        let mut seen_right = HashSet::new();
+        let (mut seen_modified_left, mut seen_modified_right) = (HashSet::new(), HashSet::new());
+        self.bytes_only_left = 0;
+        self.bytes_only_right = 0;
+        self.bytes_modified = 0;
+        self.unique_bytes_only_left = 0;
+        self.unique_bytes_only_right = 0;
+        self.unique_bytes_modified = 0;
+        for diff in diffs {
+            match diff.diff_kind {
+                DiffKind::OnlyLeft => {
+                    self.bytes_only_left += file_size(&diff.left);
+                    self.unique_bytes_only_left += unique_size(&diff.left, &mut seen_left);
+                }
+                DiffKind::OnlyRight => {
+                    self.bytes_only_right += file_size(&diff.right);
+                    self.unique_bytes_only_right += unique_size(&diff.right, &mut seen_right);
+                }
+                DiffKind::MetaDiff => {
+                    self.bytes_modified += file_size(&diff.left).max(file_size(&diff.right));
+                    let left = unique_size(&diff.left, &mut seen_modified_left);
+                    let right = unique_size(&diff.right, &mut seen_modified_right);
+                    self.unique_bytes_modified += left.max(right);
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum CompareStatus {
+    Same,
+    Modified,
+    OnlyLeft,
+    OnlyRight,
+    TypeMismatch,
+    Pending,
+    /// No differences found, but parts of the tree lay beyond the depth limit
+    Partial,
+    /// One side or both could not be read, so nothing is known about the difference
+    Error,
+    /// A fifo, socket or device on both sides; its contents cannot be compared
+    Special,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CompareEntry {
+    pub name: String,
+    pub kind: EntryKind,
+    pub status: CompareStatus,
+    pub left_size: Option<u64>,
+    pub right_size: Option<u64>,
+    pub left_modified: Option<u64>,
+    pub right_modified: Option<u64>,
+    pub dir_info: Option<DirResolveInfo>,
+}
+
+/// What a recursive comparison of two same-named directories found.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DirResolveInfo {
+    /// Sum of file sizes in the left directory tree (apparent size: every hard link
+    /// counts in full)
+    pub left_size: u64,
+    pub right_size: u64,
+    /// Like `left_size`, but counting each hard-linked file once
+    #[serde(default)]
+    pub left_unique_size: u64,
+    #[serde(default)]
+    pub right_unique_size: u64,
+    /// Entries anywhere below that exist on one side only; a one-sided
+    /// directory counts once, not per file inside it
+    pub only_left: usize,
+    pub only_right: usize,
+    /// Entries on both sides that differ in size or kind
+    pub modified: usize,
+    /// Directories at the depth limit that were not walked; counts and sizes only
+    /// cover the levels above them
+    pub depth_limited: usize,
+    /// Folder pairs, this one or below, where a side could not be listed; counts and
+    /// sizes leave out what they hold
+    #[serde(default)]
+    pub unreadable: usize,
+}
+
+impl DirResolveInfo {
+    pub fn status(&self) -> CompareStatus {
+        if self.unreadable > 0 {
+            CompareStatus::Error
+        } else if self.only_left > 0 || self.only_right > 0 || self.modified > 0 {
+            CompareStatus::Modified
+        } else if self.depth_limited > 0 {
+            CompareStatus::Partial
+        } else {
+            CompareStatus::Same
+        }
+    }
+}
+
+/// Recursive size of one directory, for the browse view.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DirSize {
+    /// Sum of file sizes anywhere below the directory
+    pub total_size: u64,
+    /// Files, symlinks and other non-directories anywhere below it
+    pub file_count: usize,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_entry_meta_serialization_roundtrip() {
+        let meta = EntryMeta {
+            kind: EntryKind::File,
+            size: 1024,
+            modified: Some(1700000000000),
+            symlink_target: None,
+            mode: None,
+            link: None,
+        };
+        let json = serde_json::to_string(&meta).unwrap();
+        let deserialized: EntryMeta = serde_json::from_str(&json).unwrap();
+        assert_eq!(deserialized.kind, EntryKind::File);
+        assert_eq!(deserialized.size, 1024);
+        assert_eq!(deserialized.modified, Some(1700000000000));
+    }
+
+    #[test]
+    fn test_entry_kind_names() {
+        let names: Vec<String> = [EntryKind::BlockDevice, EntryKind::CharDevice, EntryKind::Fifo]
+            .iter()
+            .map(|kind| serde_json::to_string(kind).unwrap())
+            .collect();
+        assert_eq!(names, ["\"blockDevice\"", "\"charDevice\"", "\"fifo\""]);
+    }
+
+    #[test]
+    fn test_diff_item_serialization() {
+        let item = DiffItem {
+            rel_path: "src/main.rs".to_string(),
+            diff_kind: DiffKind::MetaDiff,
+            left: Some(EntryMeta {
+                kind: EntryKind::File,
+                size: 100,
+                modified: Some(1000),
+                symlink_target: None,
+                mode: None,
+                link: None,
+            }),
+            right: Some(EntryMeta {
+                kind: EntryKind::File,
+                size: 200,
+                modified: Some(2000),
+                symlink_target: None,
+                mode: None,
+                link: None,
+            }),
+            error_message: None,
+            newer: None,
+        };
+        let json = serde_json::to_string(&item).unwrap();
+        assert!(json.contains("metaDiff"));
+        assert!(json.contains("relPath"));
+    }
+
+    #[test]
+    fn test_compare_summary_default() {
+        let summary = CompareSummary::default();
+        assert_eq!(summary.total_left, 0);
+        assert_eq!(summary.same, 0);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_owner_names_resolve_and_cache() {
+        use std::os::unix::fs::MetadataExt;
+        let meta = std::fs::metadata(std::env::temp_dir()).unwrap();
+        let mut names = OwnerNames::new();
+        let (user, group) = names.of(&meta);
+        assert!(user.as_deref().is_some_and(|u| !u.is_empty()));
+        assert!(group.is_some());
+        assert_eq!(names.users.len(), 1);
+        assert_eq!(names.of(&meta), (user, group));
+        if meta.uid() == 0 {
+            assert_eq!(names.users[&0], "root");
+        }
+    }
+}
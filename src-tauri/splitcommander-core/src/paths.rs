@@ -0,0 +1,337 @@
+//! Turning what a user types into a path bar into a real path.
+//!
+//! A leading `~` stands for the home folder, and `$VAR` / `${VAR}` (and on Windows
+//! `%VAR%`) for environment variables. Variables that are not set stay as typed, so
+//! the path simply does not exist rather than silently pointing somewhere else.
+
+use std::path::{Component, Path, PathBuf};
+
+use serde::Serialize;
+
+use crate::error::CommandError;
+use crate::sort::natural_cmp;
+
+/// Where a typed path leads. `path` is absolute, with links and `..` resolved as far as
+/// the path exists.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ResolvedPath {
+    pub path: String,
+    pub exists: bool,
+    pub is_dir: bool,
+}
+
+/// Expands `~` and environment variables in `input`, looking variables up with `var`.
+/// Nothing else changes: the result may still be relative or contain `..`.
+pub fn expand(
+    input: &str,
+    home: Option<&Path>,
+    var: impl Fn(&str) -> Option<String>,
+) -> Result<PathBuf, CommandError> {
+    let input = input.trim();
+    if input.is_empty() {
+        return Err(CommandError::invalid_argument("Empty path"));
+    }
+    let expanded = expand_vars(input, &var);
+    // Windows reads a bare `C:` as that drive's current folder; a path bar means its root
+    let bare_drive = expanded.len() == 2
+        && expanded.ends_with(':')
+        && expanded.starts_with(|c: char| c.is_ascii_alphabetic());
+    if cfg!(windows) && bare_drive {
+        return Ok(PathBuf::from(format!(r"{}\", expanded)));
+    }
+    let rest = expanded
+        .strip_prefix('~')
+        .filter(|rest| rest.is_empty() || rest.starts_with(std::path::is_separator));
+    match rest {
+        Some(rest) => {
+            let home = home.ok_or_else(|| {
+                CommandError::invalid_argument("Could not determine home directory")
+            })?;
+            Ok(home.join(rest.trim_start_matches(std::path::is_separator)))
+        }
+        None => Ok(PathBuf::from(expanded)),
+    }
+}
+
+/// [`expand`], with relative paths taken from the home folder.
+fn expand_absolute(
+    input: &str,
+    home: Option<&Path>,
+    var: impl Fn(&str) -> Option<String>,
+) -> Result<PathBuf, CommandError> {
+    let expanded = expand(input, home, var)?;
+    if expanded.is_absolute() {
+        return Ok(expanded);
+    }
+    let home = home.ok_or_else(|| {
+        CommandError::invalid_argument(format!("Not an absolute path: {}", input.trim()))
+    })?;
+    Ok(home.join(expanded))
+}
+
+/// Expands `input` like [`expand`], takes relative paths from the home folder, and
+/// resolves the result. A path that does not exist is not an error: its longest
+/// existing ancestor is resolved and the rest appended with `.` and `..` applied.
+pub fn resolve(
+    input: &str,
+    home: Option<&Path>,
+    var: impl Fn(&str) -> Option<String>,
+) -> Result<ResolvedPath, CommandError> {
+    let absolute = expand_absolute(input, home, var)?;
+    let (path, exists) = match absolute.canonicalize() {
+        Ok(path) => (path, true),
+        Err(_) => (resolve_missing(&absolute), false),
+    };
+    Ok(ResolvedPath {
+        is_dir: exists && path.is_dir(),
+        path: strip_verbatim(&path.to_string_lossy()),
+        exists,
+    })
+}
+
+/// What [`complete`] suggests.
+#[derive(Debug, Clone, Copy)]
+pub struct CompletionOptions {
+    pub max: usize,
+    pub include_files: bool,
+    /// Dot-prefixed entries are offered anyway once the typed name starts with a dot
+    pub include_hidden: bool,
+}
+
+/// Completions for a partly typed path: entries of the folder it names so far whose
+/// names start with the last part, ignoring case, in natural order. Candidates are
+/// absolute, expanded like [`expand`]; folders end with a separator so typing can go
+/// on inside them. A folder that does not exist or cannot be read has no completions.
+pub fn complete(
+    partial: &str,
+    home: Option<&Path>,
+    var: impl Fn(&str) -> Option<String>,
+    options: CompletionOptions,
+) -> Result<Vec<String>, CommandError> {
+    let path = expand_absolute(partial, home, var)?;
+    let typed = partial.trim();
+    let (dir, prefix) = match path.file_name() {
+        Some(name) if !typed.ends_with(std::path::is_separator) && !typed.ends_with('~') => {
+            (path.parent().unwrap_or(&path), name.to_string_lossy().to_lowercase())
+        }
+        _ => (path.as_path(), String::new()),
+    };
+    let Ok(read_dir) = std::fs::read_dir(dir) else {
+        return Ok(Vec::new());
+    };
+    let show_hidden = options.include_hidden || prefix.starts_with('.');
+
+    let mut found: Vec<(String, bool)> = read_dir
+        .filter_map(|entry| {
+            let entry = entry.ok()?;
+            let name = entry.file_name().to_string_lossy().into_owned();
+            let wanted = name.to_lowercase().starts_with(&prefix)
+                && (show_hidden || !name.starts_with('.'));
+            if !wanted {
+                return None;
+            }
+            let file_type = entry.file_type().ok()?;
+            // Links count as what they point to
+            let is_dir = file_type.is_dir()
+                || (file_type.is_symlink() && entry.path().is_dir());
+            (is_dir || options.include_files).then_some((name, is_dir))
+        })
+        .collect();
+    found.sort_by(|(a, _), (b, _)| natural_cmp(a, b));
+    found.truncate(options.max);
+
+    Ok(found
+        .into_iter()
+        .map(|(name, is_dir)| {
+            let mut candidate = dir.join(name).to_string_lossy().into_owned();
+            if is_dir {
+                candidate.push(std::path::MAIN_SEPARATOR);
+            }
+            candidate
+        })
+        .collect())
+}
+
+/// Resolves the longest existing ancestor of `path` and appends the rest lexically.
+fn resolve_missing(path: &Path) -> PathBuf {
+    let mut resolved = PathBuf::new();
+    let mut rest = Vec::new();
+    for ancestor in path.ancestors() {
+        if let Ok(existing) = ancestor.canonicalize() {
+            resolved = existing;
+            break;
+        }
+        rest.extend(ancestor.file_name());
+        if ancestor.file_name().is_none() {
+            // `..` or a bare root: keep it as a component still to apply
+            rest.extend(ancestor.components().next_back().map(Component::as_os_str));
+        }
+    }
+    for part in rest.into_iter().rev() {
+        match Path::new(part).components().next() {
+            Some(Component::ParentDir) => {
+                resolved.pop();
+            }
+            Some(Component::CurDir) | None => {}
+            Some(_) => resolved.push(part),
+        }
+    }
+    resolved
+}
+
+/// Drops the `\\?\` prefix Windows puts on canonical paths, which most programs (and
+/// users) do not expect: `\\?\C:\x` becomes `C:\x` and `\\?\UNC\server\share` becomes
+/// `\\server\share`.
+fn strip_verbatim(path: &str) -> String {
+    match path.strip_prefix(r"\\?\") {
+        Some(unc) if unc.starts_with(r"UNC\") => format!(r"\\{}", &unc[4..]),
+        Some(local) => local.to_string(),
+        None => path.to_string(),
+    }
+}
+
+fn expand_vars(input: &str, var: &impl Fn(&str) -> Option<String>) -> String {
+    let mut out = String::with_capacity(input.len());
+    let mut rest = input;
+    while let Some(start) = rest.find(['$', '%']) {
+        out.push_str(&rest[..start]);
+        let (name, len) = var_at(&rest[start..]);
+        match name.and_then(var) {
+            Some(value) => out.push_str(&value),
+            None => out.push_str(&rest[start..start + len]),
+        }
+        rest = &rest[start + len..];
+    }
+    out.push_str(rest);
+    out
+}
+
+/// The variable name a `$` or `%` at the start of `text` refers to, if any, and how many
+/// bytes the reference (or just the sigil) takes.
+fn var_at(text: &str) -> (Option<&str>, usize) {
+    let is_name = |c: char| c.is_ascii_alphanumeric() || c == '_';
+    let after = &text[1..];
+    if text.starts_with('%') {
+        return match after.find('%') {
+            Some(end) if cfg!(windows) && end > 0 && after[..end].chars().all(is_name) => {
+                (Some(&after[..end]), end + 2)
+            }
+            _ => (None, 1),
+        };
+    }
+    if let Some(braced) = after.strip_prefix('{') {
+        return match braced.find('}') {
+            Some(end) if end > 0 => (Some(&braced[..end]), end + 3),
+            _ => (None, 1),
+        };
+    }
+    let end = after.find(|c| !is_name(c)).unwrap_or(after.len());
+    if end == 0 {
+        (None, 1)
+    } else {
+        (Some(&after[..end]), end + 1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vars(name: &str) -> Option<String> {
+        match name {
+            "PROJECTS" => Some("/srv/projects".to_string()),
+            "SUB" => Some("inner".to_string()),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn test_expand_tilde_and_vars() {
+        let home = Path::new("/home/ana");
+        let expand = |input| expand(input, Some(home), vars).unwrap();
+        assert_eq!(expand("~"), home);
+        assert_eq!(expand(" ~/Projects/../Downloads "), home.join("Projects/../Downloads"));
+        assert_eq!(expand("~ana/x"), Path::new("~ana/x"));
+        assert_eq!(expand("$PROJECTS/${SUB}/x"), Path::new("/srv/projects/inner/x"));
+        // Unset variables and stray sigils stay as typed
+        assert_eq!(expand("/a/$UNSET_VAR/${}/$/b"), Path::new("/a/$UNSET_VAR/${}/$/b"));
+        assert_eq!(expand("/100%/b"), Path::new("/100%/b"));
+        assert!(super::expand("  ", Some(home), vars).is_err());
+        assert!(super::expand("~/x", None, vars).is_err());
+    }
+
+    #[test]
+    fn test_resolve_existing_and_missing_paths() {
+        let base = std::env::temp_dir().join("sc_paths_resolve");
+        let _ = std::fs::remove_dir_all(&base);
+        std::fs::create_dir_all(base.join("Projects/app")).unwrap();
+        std::fs::create_dir_all(base.join("Downloads")).unwrap();
+        std::fs::write(base.join("notes.txt"), "n").unwrap();
+        let home = base.canonicalize().unwrap();
+        let resolve = |input| resolve(input, Some(&home), vars).unwrap();
+
+        let downloads = resolve("~/Projects/app/../../Downloads");
+        let file = resolve("notes.txt");
+        let missing = resolve("~/Projects/new/../later/$UNSET_VAR");
+        let home_only = resolve("~");
+        let _ = std::fs::remove_dir_all(&base);
+
+        assert_eq!(downloads.path, home.join("Downloads").to_string_lossy());
+        assert!(downloads.exists && downloads.is_dir);
+        assert!(file.exists && !file.is_dir);
+        assert_eq!(
+            missing.path,
+            home.join("Projects/later/$UNSET_VAR").to_string_lossy()
+        );
+        assert!(!missing.exists && !missing.is_dir);
+        assert_eq!(home_only.path, home.to_string_lossy());
+    }
+
+    #[test]
+    fn test_complete_lists_matching_entries() {
+        let base = std::env::temp_dir().join("sc_paths_complete");
+        let _ = std::fs::remove_dir_all(&base);
+        for dir in ["Projects", "photos2", "photos10", ".pki", "music"] {
+            std::fs::create_dir_all(base.join(dir)).unwrap();
+        }
+        std::fs::write(base.join("pic.txt"), "p").unwrap();
+        let options = CompletionOptions {
+            max: 10,
+            include_files: false,
+            include_hidden: false,
+        };
+        let complete = |partial: &str, options| {
+            let found = complete(partial, Some(&base), vars, options).unwrap();
+            let prefix = format!("{}/", base.display());
+            found
+                .iter()
+                .map(|c| c.strip_prefix(&prefix).unwrap().to_string())
+                .collect::<Vec<_>>()
+        };
+
+        let dirs = complete("~/p", options);
+        let with_files = complete("p", CompletionOptions { include_files: true, ..options });
+        let all = complete("~/", options);
+        let at_home = complete("~", CompletionOptions { max: 2, ..options });
+        let hidden = complete("~/.p", options);
+        let with_hidden = complete("~/", CompletionOptions { include_hidden: true, ..options });
+        let missing = complete("~/nothing/here/p", options);
+        let _ = std::fs::remove_dir_all(&base);
+
+        assert_eq!(dirs, ["photos2/", "photos10/", "Projects/"]);
+        assert_eq!(with_files, ["photos2/", "photos10/", "pic.txt", "Projects/"]);
+        assert_eq!(all, ["music/", "photos2/", "photos10/", "Projects/"]);
+        assert_eq!(at_home, ["music/", "photos2/"]);
+        assert_eq!(hidden, [".pki/"]);
+        assert_eq!(with_hidden.len(), 5);
+        assert!(missing.is_empty());
+    }
+
+    #[test]
+    fn test_strip_verbatim() {
+        assert_eq!(strip_verbatim(r"\\?\C:\Users\ana"), r"C:\Users\ana");
+        assert_eq!(strip_verbatim(r"\\?\UNC\server\share\x"), r"\\server\share\x");
+        assert_eq!(strip_verbatim("/home/ana"), "/home/ana");
+    }
+}
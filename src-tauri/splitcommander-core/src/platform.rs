@@ -0,0 +1,125 @@
+//! Calls into the operating system that the standard library does not offer, kept
+//! here so the rest of the crate stays free of `unsafe`.
+
+/// Whether an id names a user or a group.
+#[cfg(unix)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AccountKind {
+    User,
+    Group,
+}
+
+/// Name of a user or group id from the system's account database.
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+pub fn account_name(id: u32, kind: AccountKind) -> Option<String> {
+    use std::ffi::CStr;
+    use std::ptr;
+
+    let mut buf: Vec<libc::c_char> = vec![0; 1024];
+    loop {
+        // SAFETY: both records are plain C structs the call fills in; buf outlives the
+        // name pointer, which is copied out before buf is touched again
+        let (rc, name) = unsafe {
+            match kind {
+                AccountKind::User => {
+                    let mut record: libc::passwd = std::mem::zeroed();
+                    let mut found = ptr::null_mut();
+                    let rc = libc::getpwuid_r(
+                        id,
+                        &mut record,
+                        buf.as_mut_ptr(),
+                        buf.len(),
+                        &mut found,
+                    );
+                    (rc, (!found.is_null()).then_some(record.pw_name))
+                }
+                AccountKind::Group => {
+                    let mut record: libc::group = std::mem::zeroed();
+                    let mut found = ptr::null_mut();
+                    let rc = libc::getgrgid_r(
+                        id,
+                        &mut record,
+                        buf.as_mut_ptr(),
+                        buf.len(),
+                        &mut found,
+                    );
+                    (rc, (!found.is_null()).then_some(record.gr_name))
+                }
+            }
+        };
+        // Too small a buffer for this record; grow it, within reason
+        if rc == libc::ERANGE && buf.len() < 1 << 20 {
+            buf.resize(buf.len() * 4, 0);
+            continue;
+        }
+        let name = name.filter(|_| rc == 0)?;
+        // SAFETY: on success the name points at a NUL-terminated string inside buf
+        return Some(unsafe { CStr::from_ptr(name) }.to_string_lossy().into_owned());
+    }
+}
+
+#[cfg(all(unix, not(any(target_os = "linux", target_os = "macos"))))]
+pub fn account_name(_id: u32, _kind: AccountKind) -> Option<String> {
+    None
+}
+
+/// Asks the system whether the current user may read `path`, and for a folder also
+/// enter it: the error says why not. Unlike the permission bits this accounts for
+/// ownership, groups and ACLs.
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+pub fn check_readable(path: &std::path::Path, is_dir: bool) -> std::io::Result<()> {
+    use std::os::unix::ffi::OsStrExt;
+    let c_path = std::ffi::CString::new(path.as_os_str().as_bytes())?;
+    let wanted = if is_dir { libc::R_OK | libc::X_OK } else { libc::R_OK };
+    // SAFETY: c_path is NUL-terminated
+    if unsafe { libc::access(c_path.as_ptr(), wanted) } == 0 {
+        Ok(())
+    } else {
+        Err(std::io::Error::last_os_error())
+    }
+}
+
+/// This machine's host name, as shells put it in `file://host/path` folder reports.
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+pub fn host_name() -> Option<String> {
+    let mut buf = [0u8; 256];
+    // SAFETY: the call writes at most buf.len() bytes into buf
+    if unsafe { libc::gethostname(buf.as_mut_ptr().cast(), buf.len()) } != 0 {
+        return None;
+    }
+    let len = buf.iter().position(|&b| b == 0).unwrap_or(buf.len());
+    let name = String::from_utf8_lossy(&buf[..len]).into_owned();
+    Some(name).filter(|name| !name.is_empty())
+}
+
+#[cfg(windows)]
+pub fn host_name() -> Option<String> {
+    std::env::var("COMPUTERNAME").ok().filter(|name| !name.is_empty())
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos", windows)))]
+pub fn host_name() -> Option<String> {
+    None
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_account_names_of_root() {
+        if cfg!(any(target_os = "linux", target_os = "macos")) {
+            assert_eq!(account_name(0, AccountKind::User).as_deref(), Some("root"));
+            assert!(account_name(0, AccountKind::Group).is_some());
+        }
+        // An id nobody has is not an error, just nameless
+        assert_eq!(account_name(u32::MAX - 7, AccountKind::User), None);
+    }
+
+    #[test]
+    fn test_host_name_is_known() {
+        if cfg!(any(target_os = "linux", target_os = "macos")) {
+            assert!(host_name().is_some_and(|name| !name.contains('\0')));
+        }
+    }
+}
@@ -0,0 +1,175 @@
+//! Quick-view contents of a single file: text when it looks like text, otherwise a
+//! hex dump of its first bytes.
+
+use std::fmt::Write as _;
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+
+use serde::Serialize;
+
+use crate::error::CommandError;
+use crate::search::BINARY_SNIFF_LEN;
+
+/// Bytes of text shown when the caller does not ask for a limit.
+pub const DEFAULT_PREVIEW_BYTES: u64 = 256 * 1024;
+
+/// Bytes of a binary file shown in the hex dump, at most.
+const HEX_DUMP_LEN: usize = 4096;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum PreviewEncoding {
+    Utf8,
+    /// Not valid UTF-8; invalid sequences were replaced with U+FFFD
+    Utf8Lossy,
+    /// Binary; `text` is a hex dump
+    Hex,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FilePreview {
+    pub is_binary: bool,
+    pub encoding: PreviewEncoding,
+    /// Whether `text` covers less than the whole file
+    pub truncated: bool,
+    pub text: String,
+    /// Size of the whole file
+    pub byte_length: u64,
+}
+
+/// Reads up to `max_bytes` of the file at `path` for previewing. A NUL byte among the
+/// first 8 KiB marks it binary, and then only a hex dump of its first 4 KiB comes back.
+/// A text preview never ends in a partial UTF-8 sequence cut off by the limit.
+pub fn preview_file(path: &Path, max_bytes: u64) -> Result<FilePreview, CommandError> {
+    let context = || format!("Cannot preview {}", path.display());
+    let meta = std::fs::metadata(path).map_err(|e| CommandError::io(context(), path, &e))?;
+    if !meta.is_file() {
+        return Err(CommandError::InvalidArgument {
+            message: format!("Not a file: {}", path.display()),
+            path: Some(path.display().to_string()),
+        });
+    }
+    let byte_length = meta.len();
+
+    let mut bytes = Vec::new();
+    File::open(path)
+        .and_then(|f| f.take(max_bytes.max(BINARY_SNIFF_LEN)).read_to_end(&mut bytes))
+        .map_err(|e| CommandError::io(context(), path, &e))?;
+
+    let sniffed = &bytes[..bytes.len().min(BINARY_SNIFF_LEN as usize)];
+    if sniffed.contains(&0) {
+        let shown = &bytes[..bytes.len().min(HEX_DUMP_LEN)];
+        return Ok(FilePreview {
+            is_binary: true,
+            encoding: PreviewEncoding::Hex,
+            truncated: (shown.len() as u64) < byte_length,
+            text: hex_dump(shown),
+            byte_length,
+        });
+    }
+
+    bytes.truncate(max_bytes as usize);
+    let truncated = (bytes.len() as u64) < byte_length;
+    if truncated {
+        // Drop a character the limit cut in half, so it is not mistaken for bad UTF-8
+        if let Err(e) = std::str::from_utf8(&bytes) {
+            if e.error_len().is_none() {
+                bytes.truncate(e.valid_up_to());
+            }
+        }
+    }
+    let (text, encoding) = match String::from_utf8(bytes) {
+        Ok(text) => (text, PreviewEncoding::Utf8),
+        Err(e) => (
+            String::from_utf8_lossy(e.as_bytes()).into_owned(),
+            PreviewEncoding::Utf8Lossy,
+        ),
+    };
+    Ok(FilePreview {
+        is_binary: false,
+        encoding,
+        truncated,
+        text,
+        byte_length,
+    })
+}
+
+/// Classic 16-bytes-per-line dump: offset, hex bytes, then printable ASCII.
+fn hex_dump(bytes: &[u8]) -> String {
+    let mut out = String::new();
+    for (i, line) in bytes.chunks(16).enumerate() {
+        let _ = write!(out, "{:08x} ", i * 16);
+        for byte in line {
+            let _ = write!(out, " {:02x}", byte);
+        }
+        out.push_str(&"   ".repeat(16 - line.len()));
+        out.push_str("  |");
+        out.extend(line.iter().map(|&b| {
+            if b.is_ascii_graphic() || b == b' ' {
+                b as char
+            } else {
+                '.'
+            }
+        }));
+        out.push_str("|\n");
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fixture(name: &str, contents: &[u8]) -> std::path::PathBuf {
+        let path = std::env::temp_dir().join(name);
+        std::fs::write(&path, contents).unwrap();
+        path
+    }
+
+    #[test]
+    fn test_text_truncated_on_char_boundary() {
+        let path = fixture("sc_preview_text.txt", "héllo".as_bytes());
+        let whole = preview_file(&path, DEFAULT_PREVIEW_BYTES).unwrap();
+        // "h" plus the first byte of "é"
+        let cut = preview_file(&path, 2).unwrap();
+        let _ = std::fs::remove_file(&path);
+
+        assert_eq!(whole.text, "héllo");
+        assert_eq!(whole.encoding, PreviewEncoding::Utf8);
+        assert_eq!((whole.truncated, whole.byte_length), (false, 6));
+        assert_eq!((cut.text.as_str(), cut.truncated), ("h", true));
+        assert_eq!(cut.encoding, PreviewEncoding::Utf8);
+    }
+
+    #[test]
+    fn test_invalid_utf8_flagged_lossy() {
+        let path = fixture("sc_preview_latin1.txt", b"caf\xe9 au lait");
+        let preview = preview_file(&path, DEFAULT_PREVIEW_BYTES).unwrap();
+        let _ = std::fs::remove_file(&path);
+
+        assert_eq!(preview.encoding, PreviewEncoding::Utf8Lossy);
+        assert_eq!(preview.text, "caf\u{fffd} au lait");
+        assert!(!preview.is_binary);
+    }
+
+    #[test]
+    fn test_binary_returns_hex_dump() {
+        let path = fixture("sc_preview_blob.bin", b"AB\0\x7f");
+        let preview = preview_file(&path, DEFAULT_PREVIEW_BYTES).unwrap();
+        let _ = std::fs::remove_file(&path);
+
+        assert!(preview.is_binary && !preview.truncated);
+        assert_eq!(preview.encoding, PreviewEncoding::Hex);
+        assert_eq!(preview.text, format!("00000000  41 42 00 7f{}  |AB..|\n", "   ".repeat(12)));
+    }
+
+    #[test]
+    fn test_directory_rejected() {
+        let err = preview_file(&std::env::temp_dir(), DEFAULT_PREVIEW_BYTES).unwrap_err();
+        assert_eq!(err.code(), "invalidArgument");
+        let missing = std::env::temp_dir().join("sc_preview_missing.txt");
+        assert_eq!(preview_file(&missing, 10).unwrap_err().code(), "notFound");
+    }
+}
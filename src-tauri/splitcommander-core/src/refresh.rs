@@ -0,0 +1,573 @@
+//! Brings a stored compare result up to date after paths changed on disk, by comparing
+//! just those paths (and what lies below them) again instead of both whole trees.
+
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::AtomicBool;
+
+use crate::compare::{self, CompareOptions};
+use crate::ignore::IgnoreRules;
+use crate::model::{CompareMode, CompareSummary, DiffItem, EntryKind};
+use crate::scan::{self, KeyMode, ScanError, ScanOptions, ScanResult};
+use crate::source;
+use crate::sync::{is_within, omitted_same};
+
+/// How a live compare was run, kept with its result so that changed paths are
+/// compared again the same way.
+#[derive(Debug, Clone)]
+pub struct CompareSettings {
+    pub mode: CompareMode,
+    pub options: CompareOptions,
+    pub scan_options: ScanOptions,
+    pub key_mode: KeyMode,
+    /// Directories pruned from each side's scan, as passed to the scanner
+    pub left_excluded: Vec<PathBuf>,
+    pub right_excluded: Vec<PathBuf>,
+}
+
+/// What [`refresh_paths`] compared again.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Refreshed {
+    /// Each path once, none below another; just `""` when `full` is set
+    pub rel_paths: Vec<String>,
+    /// The whole result was redone
+    pub full: bool,
+}
+
+/// The Same entries a result leaves out below some paths, counted before a file
+/// operation changes them. With it, [`refresh_from`] updates a result made without Same
+/// entries by comparing just those paths again, where [`refresh_paths`] has to redo it
+/// in full.
+#[derive(Debug, Clone)]
+pub struct Baseline {
+    /// Each path once, none below another
+    rel_paths: Vec<String>,
+    /// `None` when not known, which only happens inside [`refresh_paths`]
+    omitted: Option<CompareSummary>,
+}
+
+impl Baseline {
+    /// Whether each of `rel_paths` lies at or below a path the baseline was taken of.
+    pub fn covers(&self, rel_paths: &[String], key_mode: KeyMode) -> bool {
+        let keys: Vec<String> = self.rel_paths.iter().map(|p| key_mode.key(p)).collect();
+        rel_paths.iter().all(|path| {
+            let key = key_mode.key(path);
+            keys.iter().any(|dir| is_within(dir, &key))
+        })
+    }
+}
+
+/// Whether a result leaves out Same entries (see `CompareOptions::include_same`), so
+/// that refreshing part of it needs a [`Baseline`].
+pub fn omits_same(summary: &CompareSummary, diffs: &[DiffItem]) -> bool {
+    omitted_same(summary, diffs).same > 0
+}
+
+/// Compares `rel_paths` and everything below them as they are now, for a later
+/// [`refresh_from`]. `summary` is the stored result's, for its case sensitivity.
+pub fn baseline(
+    left_root: &Path,
+    right_root: &Path,
+    rel_paths: &[String],
+    settings: &CompareSettings,
+    summary: &CompareSummary,
+    cancel_flag: &AtomicBool,
+) -> Result<Baseline, String> {
+    let rel_paths = topmost(rel_paths, settings.key_mode);
+    let omitted = if rel_paths.iter().any(|p| p.is_empty()) {
+        CompareSummary::default()
+    } else {
+        let partial = compare_paths(
+            left_root,
+            right_root,
+            &rel_paths,
+            settings,
+            summary,
+            cancel_flag,
+        )?;
+        omitted_same(&partial.summary, &partial.diffs)
+    };
+    Ok(Baseline {
+        rel_paths,
+        omitted: Some(omitted),
+    })
+}
+
+/// Compares `rel_paths` (`/`-separated and relative to both roots) and everything below
+/// them again, replacing their entries in `diffs` and recounting `summary`. The empty
+/// path is the whole tree. A result that leaves out Same entries (see
+/// `CompareOptions::include_same`) cannot tell what an unlisted path was before, so once
+/// it has any, it is redone in full; [`refresh_from`] avoids that.
+pub fn refresh_paths(
+    left_root: &Path,
+    right_root: &Path,
+    rel_paths: &[String],
+    settings: &CompareSettings,
+    diffs: &mut Vec<DiffItem>,
+    summary: &mut CompareSummary,
+    cancel_flag: &AtomicBool,
+) -> Result<Refreshed, String> {
+    let baseline = Baseline {
+        rel_paths: topmost(rel_paths, settings.key_mode),
+        omitted: (!omits_same(summary, diffs)).then(CompareSummary::default),
+    };
+    refresh(
+        left_root,
+        right_root,
+        baseline,
+        settings,
+        diffs,
+        summary,
+        cancel_flag,
+    )
+}
+
+/// [`refresh_paths`] for the paths `baseline` was taken of, which only compares those
+/// again even when the result leaves out Same entries.
+pub fn refresh_from(
+    left_root: &Path,
+    right_root: &Path,
+    baseline: &Baseline,
+    settings: &CompareSettings,
+    diffs: &mut Vec<DiffItem>,
+    summary: &mut CompareSummary,
+    cancel_flag: &AtomicBool,
+) -> Result<Refreshed, String> {
+    refresh(
+        left_root,
+        right_root,
+        baseline.clone(),
+        settings,
+        diffs,
+        summary,
+        cancel_flag,
+    )
+}
+
+/// Compares the paths of `baseline` again, or everything when it does not know what
+/// the result left out below them.
+fn refresh(
+    left_root: &Path,
+    right_root: &Path,
+    baseline: Baseline,
+    settings: &CompareSettings,
+    diffs: &mut Vec<DiffItem>,
+    summary: &mut CompareSummary,
+    cancel_flag: &AtomicBool,
+) -> Result<Refreshed, String> {
+    let key_mode = settings.key_mode;
+    let Baseline {
+        mut rel_paths,
+        omitted: before,
+    } = baseline;
+    let full = before.is_none() || rel_paths.iter().any(|p| p.is_empty());
+    if full {
+        rel_paths = vec![String::new()];
+    }
+    let before = before.unwrap_or_default();
+    let partial = compare_paths(
+        left_root,
+        right_root,
+        &rel_paths,
+        settings,
+        summary,
+        cancel_flag,
+    )?;
+
+    if full {
+        *diffs = partial.diffs;
+        *summary = partial.summary;
+        return Ok(Refreshed {
+            rel_paths: vec![String::new()],
+            full,
+        });
+    }
+
+    let omitted = omitted_same(summary, diffs);
+    let keys: Vec<String> = rel_paths.iter().map(|p| key_mode.key(p)).collect();
+    diffs.retain(|d| {
+        let key = key_mode.key(&d.rel_path);
+        !keys.iter().any(|p| is_within(p, &key))
+    });
+    let after = omitted_same(&partial.summary, &partial.diffs);
+    diffs.extend(partial.diffs);
+    diffs.sort_by_key(|d| d.rel_path.to_lowercase());
+    summary.recount(diffs);
+    // Left-out Same entries elsewhere stay; those below the paths are counted anew
+    let unlisted =
+        |omitted: usize, before: usize, after: usize| (omitted + after).saturating_sub(before);
+    summary.same += unlisted(omitted.same, before.same, after.same);
+    summary.total_left += unlisted(omitted.total_left, before.total_left, after.total_left);
+    summary.total_right += unlisted(omitted.total_right, before.total_right, after.total_right);
+    Ok(Refreshed { rel_paths, full })
+}
+
+/// Compares `rel_paths` on both sides the way the stored result was compared; the
+/// empty path compares both whole trees.
+fn compare_paths(
+    left_root: &Path,
+    right_root: &Path,
+    rel_paths: &[String],
+    settings: &CompareSettings,
+    summary: &CompareSummary,
+    cancel_flag: &AtomicBool,
+) -> Result<compare::CompareResult, String> {
+    let key_mode = settings.key_mode;
+    let full = rel_paths.iter().any(|p| p.is_empty());
+    let scan = |root: &Path, excluded: &[PathBuf], case_sensitive: Option<bool>| {
+        let mut result = if full {
+            scan::scan_directory_with_options(
+                root,
+                &IgnoreRules::new(&[]),
+                excluded,
+                &settings.scan_options,
+                cancel_flag,
+                &|_| {},
+            )?
+            .rekey(key_mode)
+        } else {
+            scan_paths(root, rel_paths, excluded, settings, cancel_flag)
+        };
+        result.case_sensitive = case_sensitive.unwrap_or(result.case_sensitive);
+        Ok::<_, String>(result)
+    };
+    let left = scan(
+        left_root,
+        &settings.left_excluded,
+        summary.left_case_sensitive,
+    )?;
+    let right = scan(
+        right_root,
+        &settings.right_excluded,
+        summary.right_case_sensitive,
+    )?;
+    compare::compare_with_options(&left, &right, settings.mode, &settings.options, cancel_flag)
+}
+
+/// `rel_paths` without duplicates and without paths below another one in the list,
+/// shortest first.
+fn topmost(rel_paths: &[String], key_mode: KeyMode) -> Vec<String> {
+    let mut by_key: Vec<(String, &String)> =
+        rel_paths.iter().map(|p| (key_mode.key(p), p)).collect();
+    by_key.sort_by(|a, b| a.0.len().cmp(&b.0.len()).then_with(|| a.0.cmp(&b.0)));
+    let mut kept: Vec<(String, &String)> = Vec::new();
+    for (key, path) in by_key {
+        if key.is_empty() {
+            return vec![String::new()];
+        }
+        if !kept.iter().any(|(dir, _)| is_within(dir, &key)) {
+            kept.push((key, path));
+        }
+    }
+    kept.into_iter().map(|(_, path)| path.clone()).collect()
+}
+
+/// Lists each of `rel_paths` under `root`, and the whole tree below those that are
+/// directories, the way a full scan with `settings` would have listed them.
+fn scan_paths(
+    root: &Path,
+    rel_paths: &[String],
+    excluded: &[PathBuf],
+    settings: &CompareSettings,
+    cancel_flag: &AtomicBool,
+) -> ScanResult {
+    let options = &settings.scan_options;
+    let mut result = ScanResult {
+        entries: HashMap::new(),
+        originals: HashMap::new(),
+        count: 0,
+        errors: Vec::new(),
+        excluded_dirs: Vec::new(),
+        depth_limited: Vec::new(),
+        case_sensitive: true,
+        case_collisions: HashMap::new(),
+        key_mode: settings.key_mode,
+    };
+    let add = |result: &mut ScanResult, rel_path: String, meta| {
+        let key = result.key_mode.key(&rel_path);
+        scan::insert_entry(
+            &mut result.entries,
+            &mut result.originals,
+            &mut result.case_collisions,
+            key,
+            rel_path,
+            meta,
+        );
+        result.count += 1;
+    };
+
+    for rel_path in rel_paths {
+        let path = root.join(rel_path);
+        let depth = rel_path.split('/').count();
+        let hidden = options.skip_hidden && rel_path.split('/').any(scan::is_hidden);
+        let too_deep = options.max_depth.is_some_and(|max| depth > max);
+        if hidden || too_deep || excluded.iter().any(|dir| path.starts_with(dir)) {
+            continue;
+        }
+        let meta = if options.follow_links {
+            fs::metadata(&path).or_else(|_| fs::symlink_metadata(&path))
+        } else {
+            fs::symlink_metadata(&path)
+        };
+        let meta = match meta {
+            Ok(meta) => source::entry_meta(&path, &meta),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+            Err(e) => {
+                result.errors.push(ScanError {
+                    path: rel_path.clone(),
+                    message: e.to_string(),
+                });
+                continue;
+            }
+        };
+        let is_dir = meta.kind == EntryKind::Dir;
+        add(&mut result, rel_path.clone(), meta);
+        if !is_dir {
+            continue;
+        }
+        if options.max_depth == Some(depth) {
+            result.depth_limited.push(rel_path.clone());
+            continue;
+        }
+
+        let below = ScanOptions {
+            max_depth: options.max_depth.map(|max| max - depth),
+            ..options.clone()
+        };
+        let scanned = scan::scan_directory_with_options(
+            &path,
+            &IgnoreRules::new(&[]),
+            excluded,
+            &below,
+            cancel_flag,
+            &|_| {},
+        );
+        let scanned = match scanned {
+            Ok(scanned) => scanned,
+            Err(message) => {
+                result.errors.push(ScanError {
+                    path: rel_path.clone(),
+                    message,
+                });
+                continue;
+            }
+        };
+        let under = |path: &str| format!("{}/{}", rel_path, path);
+        for (path, meta) in scanned.listed() {
+            add(&mut result, under(path), meta.clone());
+        }
+        result
+            .errors
+            .extend(scanned.errors.into_iter().map(|e| ScanError {
+                path: under(&e.path),
+                message: e.message,
+            }));
+        result
+            .excluded_dirs
+            .extend(scanned.excluded_dirs.iter().map(|p| under(p)));
+        result
+            .depth_limited
+            .extend(scanned.depth_limited.iter().map(|p| under(p)));
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::source::{LocalSource, TreeSource};
+
+    fn settings(include_same: bool) -> CompareSettings {
+        CompareSettings {
+            mode: CompareMode::Smart,
+            options: CompareOptions {
+                include_same,
+                ..Default::default()
+            },
+            scan_options: ScanOptions::default(),
+            key_mode: KeyMode::default(),
+            left_excluded: Vec::new(),
+            right_excluded: Vec::new(),
+        }
+    }
+
+    fn compare_roots(
+        left: &Path,
+        right: &Path,
+        settings: &CompareSettings,
+    ) -> compare::CompareResult {
+        let scan = |root: &Path| {
+            LocalSource::new(root)
+                .scan(
+                    &IgnoreRules::new(&[]),
+                    &[],
+                    &settings.scan_options,
+                    &AtomicBool::new(false),
+                    &|_| {},
+                )
+                .unwrap()
+        };
+        compare::compare_with_options(
+            &scan(left),
+            &scan(right),
+            settings.mode,
+            &settings.options,
+            &AtomicBool::new(false),
+        )
+        .unwrap()
+    }
+
+    fn listing(diffs: &[DiffItem]) -> Vec<(String, crate::model::DiffKind)> {
+        diffs
+            .iter()
+            .map(|d| (d.rel_path.clone(), d.diff_kind))
+            .collect()
+    }
+
+    fn tree_pair(name: &str) -> (PathBuf, PathBuf) {
+        let dir = std::env::temp_dir().join(format!("sc_refresh_{}", name));
+        let _ = fs::remove_dir_all(&dir);
+        let (left, right) = (dir.join("left"), dir.join("right"));
+        for root in [&left, &right] {
+            fs::create_dir_all(root.join("docs")).unwrap();
+            fs::write(root.join("docs/a.txt"), "same").unwrap();
+            fs::write(root.join("b.txt"), "same").unwrap();
+        }
+        (left, right)
+    }
+
+    #[test]
+    fn test_refreshed_paths_match_a_full_compare() {
+        let (left, right) = tree_pair("paths");
+        let settings = settings(true);
+        let before = compare_roots(&left, &right, &settings);
+        let (mut diffs, mut summary) = (before.diffs, before.summary);
+
+        fs::write(left.join("docs/a.txt"), "changed").unwrap();
+        fs::create_dir_all(left.join("new/deep")).unwrap();
+        fs::write(left.join("new/deep/c.txt"), "c").unwrap();
+        fs::remove_file(right.join("b.txt")).unwrap();
+        let changed = ["docs/a.txt", "new", "new/deep/c.txt", "b.txt"].map(String::from);
+        let refreshed = refresh_paths(
+            &left,
+            &right,
+            &changed,
+            &settings,
+            &mut diffs,
+            &mut summary,
+            &AtomicBool::new(false),
+        )
+        .unwrap();
+
+        assert!(!refreshed.full);
+        assert_eq!(refreshed.rel_paths, ["new", "b.txt", "docs/a.txt"]);
+        let after = compare_roots(&left, &right, &settings);
+        assert_eq!(listing(&diffs), listing(&after.diffs));
+        assert_eq!(summary.meta_diff, 1);
+        assert_eq!(
+            (
+                summary.only_left,
+                summary.same,
+                summary.total_left,
+                summary.total_right
+            ),
+            (
+                after.summary.only_left,
+                after.summary.same,
+                after.summary.total_left,
+                after.summary.total_right
+            )
+        );
+    }
+
+    #[test]
+    fn test_result_without_same_entries_is_redone_in_full() {
+        let (left, right) = tree_pair("omitted");
+        let settings = settings(false);
+        let before = compare_roots(&left, &right, &settings);
+        let (mut diffs, mut summary) = (before.diffs, before.summary);
+        assert!(diffs.is_empty());
+
+        fs::write(right.join("b.txt"), "changed").unwrap();
+        let refreshed = refresh_paths(
+            &left,
+            &right,
+            &["b.txt".to_string()],
+            &settings,
+            &mut diffs,
+            &mut summary,
+            &AtomicBool::new(false),
+        )
+        .unwrap();
+
+        assert!(refreshed.full);
+        assert_eq!(
+            listing(&diffs),
+            [("b.txt".to_string(), crate::model::DiffKind::MetaDiff)]
+        );
+        assert_eq!((summary.same, summary.meta_diff), (2, 1));
+    }
+
+    #[test]
+    fn test_baseline_refreshes_a_result_without_same_entries_in_part() {
+        let (left, right) = tree_pair("baseline");
+        let settings = settings(false);
+        let before = compare_roots(&left, &right, &settings);
+        let (mut diffs, mut summary) = (before.diffs, before.summary);
+        assert!(omits_same(&summary, &diffs));
+
+        let changed = ["b.txt", "docs"].map(String::from);
+        let baseline = baseline(
+            &left,
+            &right,
+            &changed,
+            &settings,
+            &summary,
+            &AtomicBool::new(false),
+        )
+        .unwrap();
+        fs::write(right.join("b.txt"), "changed").unwrap();
+        fs::remove_dir_all(right.join("docs")).unwrap();
+        let refreshed = refresh_from(
+            &left,
+            &right,
+            &baseline,
+            &settings,
+            &mut diffs,
+            &mut summary,
+            &AtomicBool::new(false),
+        )
+        .unwrap();
+
+        assert!(baseline.covers(&["docs/a.txt".to_string()], KeyMode::default()));
+        assert!(!baseline.covers(&["c.txt".to_string()], KeyMode::default()));
+        assert!(!refreshed.full);
+        assert_eq!(refreshed.rel_paths, ["docs", "b.txt"]);
+        let after = compare_roots(&left, &right, &settings);
+        assert_eq!(listing(&diffs), listing(&after.diffs));
+        assert_eq!(
+            (
+                summary.same,
+                summary.meta_diff,
+                summary.only_left,
+                summary.total_left,
+                summary.total_right
+            ),
+            (0, 1, 2, 3, 1)
+        );
+        assert_eq!(
+            (summary.total_left, summary.total_right),
+            (after.summary.total_left, after.summary.total_right)
+        );
+    }
+
+    #[test]
+    fn test_topmost_paths() {
+        let paths = ["a/b", "A", "c", "a/b/c", "cd"].map(String::from);
+        assert_eq!(topmost(&paths, KeyMode::default()), ["A", "c", "cd"]);
+        let paths = ["a", ""].map(String::from);
+        assert_eq!(topmost(&paths, KeyMode::default()), [""]);
+    }
+}
@@ -0,0 +1,1130 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf, MAIN_SEPARATOR};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, OnceLock, PoisonError};
+use std::time::{Duration, Instant, UNIX_EPOCH};
+
+use jwalk::WalkDirGeneric;
+use serde::{Deserialize, Serialize};
+use unicode_normalization::UnicodeNormalization;
+
+use crate::ignore::IgnoreRules;
+use crate::model::{link_id, mode_bits, EntryKind, EntryMeta};
+
+#[derive(Debug)]
+pub struct ScanResult {
+    /// Key (see [`KeyMode`]) → metadata
+    pub entries: HashMap<String, EntryMeta>,
+    /// Key → original relative path, exactly as on disk
+    pub originals: HashMap<String, String>,
+    pub count: usize,
+    pub errors: Vec<ScanError>,
+    /// Relative paths of directories pruned via `excluded_dirs` (not walked at all)
+    pub excluded_dirs: Vec<String>,
+    /// Relative paths of directories at `ScanOptions::max_depth`, listed but not walked
+    pub depth_limited: Vec<String>,
+    /// Whether the root's filesystem distinguishes `Foo` from `foo`
+    pub case_sensitive: bool,
+    /// Keys that more than one entry maps to (names differing only by case or Unicode
+    /// normalization) → every (original path, metadata) variant. `entries` holds just
+    /// one of them.
+    pub case_collisions: HashMap<String, Vec<(String, EntryMeta)>>,
+    /// How the keys were derived; both sides of a compare must agree
+    pub key_mode: KeyMode,
+}
+
+/// How relative paths are turned into the keys entries are matched by.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KeyMode {
+    /// Keep case, so `Readme.md` and `README.md` are different entries
+    pub case_sensitive: bool,
+    /// Compare names in Unicode NFC, so a macOS (NFD) `café` matches a Linux (NFC) one
+    pub normalize_unicode: bool,
+}
+
+impl Default for KeyMode {
+    fn default() -> Self {
+        Self {
+            case_sensitive: false,
+            normalize_unicode: true,
+        }
+    }
+}
+
+impl KeyMode {
+    /// The key for `rel_path`, which may use the native separator.
+    pub fn key(&self, rel_path: &str) -> String {
+        let rel_path = forward_slashes(rel_path, MAIN_SEPARATOR);
+        let normalized: String = if self.normalize_unicode {
+            rel_path.nfc().collect()
+        } else {
+            rel_path
+        };
+        if self.case_sensitive {
+            normalized
+        } else {
+            normalized.to_lowercase()
+        }
+    }
+}
+
+/// `rel_path` with `separator` replaced by `/`, the separator keys, ignore rules and
+/// the frontend use. Takes the separator so Windows paths can be tested anywhere.
+pub fn forward_slashes(rel_path: &str, separator: char) -> String {
+    if separator == '/' {
+        rel_path.to_string()
+    } else {
+        rel_path.replace(separator, "/")
+    }
+}
+
+fn slash_path(rel_path: &Path) -> String {
+    forward_slashes(&rel_path.to_string_lossy(), MAIN_SEPARATOR)
+}
+
+impl ScanResult {
+    /// Every listed entry as (original relative path, metadata). Case variants that
+    /// share a key in `entries` each come out on their own.
+    pub fn listed(&self) -> impl Iterator<Item = (&str, &EntryMeta)> {
+        self.entries
+            .iter()
+            .filter(|(key, _)| !self.case_collisions.contains_key(*key))
+            .map(|(key, meta)| (self.originals.get(key).unwrap_or(key).as_str(), meta))
+            .chain(
+                self.case_collisions
+                    .values()
+                    .flatten()
+                    .map(|(original, meta)| (original.as_str(), meta)),
+            )
+    }
+
+    /// Sum of the sizes of all listed files.
+    pub fn file_bytes(&self) -> u64 {
+        self.entries
+            .values()
+            .filter(|meta| meta.kind == EntryKind::File)
+            .map(|meta| meta.size)
+            .sum()
+    }
+
+    /// Re-derives every key under `key_mode`. Variants that shared a key get their own
+    /// entry again if the new mode tells them apart, and vice versa.
+    pub fn rekey(self, key_mode: KeyMode) -> Self {
+        if self.key_mode == key_mode {
+            return self;
+        }
+        let mut entries = HashMap::with_capacity(self.entries.len());
+        let mut originals = HashMap::with_capacity(self.originals.len());
+        let mut case_collisions = HashMap::new();
+        let mut all: Vec<(String, EntryMeta)> = Vec::with_capacity(self.entries.len());
+        for (key, meta) in self.entries {
+            if !self.case_collisions.contains_key(&key) {
+                all.push((self.originals.get(&key).cloned().unwrap_or(key), meta));
+            }
+        }
+        all.extend(self.case_collisions.into_values().flatten());
+        for (original, meta) in all {
+            insert_entry(
+                &mut entries,
+                &mut originals,
+                &mut case_collisions,
+                key_mode.key(&original),
+                original,
+                meta,
+            );
+        }
+        Self {
+            entries,
+            originals,
+            case_collisions,
+            key_mode,
+            ..self
+        }
+    }
+}
+
+/// Adds an entry under `key`, recording a collision if a differently named entry is
+/// already there. The later entry wins the `entries` slot.
+pub(crate) fn insert_entry(
+    entries: &mut HashMap<String, EntryMeta>,
+    originals: &mut HashMap<String, String>,
+    case_collisions: &mut HashMap<String, Vec<(String, EntryMeta)>>,
+    key: String,
+    rel_path: String,
+    meta: EntryMeta,
+) {
+    if let Some(existing) = originals.get(&key) {
+        if *existing != rel_path {
+            case_collisions
+                .entry(key.clone())
+                .or_insert_with(|| vec![(existing.clone(), entries[&key].clone())])
+                .push((rel_path.clone(), meta.clone()));
+        }
+    }
+    originals.insert(key.clone(), rel_path);
+    entries.insert(key, meta);
+}
+
+/// An entry that could not be read. `path` is relative to the scan root.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScanError {
+    pub path: String,
+    pub message: String,
+}
+
+/// Where a running scan is, as passed to the progress callback.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ScanProgress {
+    pub entries: usize,
+    /// Sum of the sizes of files listed so far
+    pub bytes: u64,
+    /// Directory being walked, relative to the root. Only set a few times a second;
+    /// `None` means unchanged since the last report.
+    pub current_path: Option<String>,
+}
+
+/// Least time between two progress reports. Time rather than an entry count keeps fast
+/// disks from flooding the event channel without going quiet on slow ones.
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Least time between two `ScanProgress::current_path` updates.
+const CURRENT_PATH_INTERVAL: Duration = Duration::from_millis(250);
+
+/// How often the walk's workers are told about a cancel, at most.
+const CANCEL_POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// How a tree is walked. The defaults list everything, hidden entries included.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ScanOptions {
+    /// Leave out dot-prefixed entries; hidden directories are not descended into
+    pub skip_hidden: bool,
+    /// Walk into symlinked directories and report links as their targets. A link back
+    /// to a directory above it is listed but not walked, and reported as a scan error.
+    pub follow_links: bool,
+    /// Deepest level to list; direct children of the root are depth 1
+    pub max_depth: Option<usize>,
+    /// Worker threads for the walk; defaults to the number of CPUs
+    pub parallelism: Option<usize>,
+}
+
+/// Whether a file name is hidden. Only the leading dot counts, on every platform, so
+/// the same entries are hidden on Windows and macOS as on Linux.
+pub fn is_hidden(name: &str) -> bool {
+    name.starts_with('.')
+}
+
+/// Scans a directory root with default options. See [`scan_directory_with_options`].
+pub fn scan_directory(
+    root: &Path,
+    ignore_rules: &IgnoreRules,
+    excluded_dirs: &[PathBuf],
+    cancel_flag: &AtomicBool,
+    progress_callback: &dyn Fn(&ScanProgress),
+) -> Result<ScanResult, String> {
+    scan_directory_with_options(
+        root,
+        ignore_rules,
+        excluded_dirs,
+        &ScanOptions::default(),
+        cancel_flag,
+        progress_callback,
+    )
+}
+
+/// Scans a directory root in parallel using jwalk.
+/// Returns a map of relative paths to metadata.
+///
+/// - `excluded_dirs`: absolute directories (as seen under `root`) to prune from the walk
+/// - `options`: which entries to list and how to walk
+/// - `cancel_flag`: set to true to abort scan
+/// - `progress_callback`: called with the entry and byte totals for the first entry,
+///   then at most every 100 ms, and once more when the scan ends
+pub fn scan_directory_with_options(
+    root: &Path,
+    ignore_rules: &IgnoreRules,
+    excluded_dirs: &[PathBuf],
+    options: &ScanOptions,
+    cancel_flag: &AtomicBool,
+    progress_callback: &dyn Fn(&ScanProgress),
+) -> Result<ScanResult, String> {
+    let mut entries: HashMap<String, EntryMeta> = HashMap::new();
+    let mut originals: HashMap<String, String> = HashMap::new();
+    let mut errors = Vec::new();
+    let mut case_collisions: HashMap<String, Vec<(String, EntryMeta)>> = HashMap::new();
+    let mut depth_limited = Vec::new();
+    let key_mode = KeyMode::default();
+    let mut count: usize = 0;
+    let mut bytes: u64 = 0;
+    let mut reported_at: Option<Instant> = None;
+    let mut path_reported_at: Option<Instant> = None;
+
+    let pruned = Arc::new(Mutex::new(Vec::new()));
+    // The caller's flag is borrowed, so the workers read this copy of it instead
+    let stop = Arc::new(AtomicBool::new(false));
+    // (link, directory above it that the link resolves to)
+    let loops = Arc::new(Mutex::new(Vec::new()));
+    let threads = options.parallelism.unwrap_or_else(num_cpus).max(1);
+    // Each directory read carries the identities of the directories above it, so
+    // followed links can be checked against their ancestors
+    let mut walker = WalkDirGeneric::<(Vec<(DirId, PathBuf)>, ())>::new(root)
+        .skip_hidden(options.skip_hidden)
+        .follow_links(options.follow_links)
+        .parallelism(jwalk::Parallelism::RayonExistingPool {
+            pool: walker_pool(threads)?,
+            // Nothing waits on the pool from inside it, so it cannot deadlock; a scan
+            // only queues behind others running at the same time
+            busy_timeout: None,
+        });
+    if let Some(max_depth) = options.max_depth {
+        walker = walker.max_depth(max_depth);
+    }
+
+    {
+        let excluded = excluded_dirs.to_vec();
+        let (skip_hidden, follow_links) = (options.skip_hidden, options.follow_links);
+        let (pruned, loops) = (Arc::clone(&pruned), Arc::clone(&loops));
+        let stop = Arc::clone(&stop);
+        walker = walker.process_read_dir(move |depth, dir, ancestors, children| {
+            // Workers read ahead of the consuming loop; once cancelled, list nothing
+            // more so they stop descending
+            if stop.load(Ordering::Relaxed) {
+                children.clear();
+                return;
+            }
+            children.retain(|child| match child {
+                Ok(entry) if excluded.contains(&entry.path()) => {
+                    pruned.lock().unwrap().push(entry.path());
+                    false
+                }
+                // jwalk only recognizes UTF-8 names; catch the rest the same way
+                Ok(entry) if skip_hidden => !is_hidden(&entry.file_name.to_string_lossy()),
+                _ => true,
+            });
+            if !follow_links {
+                return;
+            }
+            // jwalk's own loop check compares raw link text, which misses relative links.
+            // The first call lists the root itself and has no directory to record.
+            if depth.is_some() {
+                if let Some(id) = dir_id(dir) {
+                    ancestors.push((id, dir.to_path_buf()));
+                }
+            }
+            for child in children.iter_mut().flatten() {
+                if !child.path_is_symlink() || child.read_children_path.is_none() {
+                    continue;
+                }
+                let Some(id) = dir_id(&child.path()) else {
+                    continue;
+                };
+                if let Some((_, ancestor)) = ancestors.iter().find(|(seen, _)| *seen == id) {
+                    child.read_children_path = None;
+                    loops.lock().unwrap().push((child.path(), ancestor.clone()));
+                }
+            }
+        });
+    }
+
+    let walk_done = AtomicBool::new(false);
+    std::thread::scope(|scope| {
+        let relay = scope.spawn(|| relay_cancel(cancel_flag, &stop, &walk_done));
+        for entry_result in walker {
+            if cancel_flag.load(Ordering::Relaxed) {
+                break;
+            }
+
+            match entry_result {
+                Ok(entry) => {
+                    let path = entry.path();
+                    let native_path = match path.strip_prefix(root) {
+                        Ok(r) => r.to_string_lossy().to_string(),
+                        Err(_) => continue,
+                    };
+                    // Keys, ignore rules and reported paths use `/`; `originals` keeps
+                    // the native form for building absolute paths
+                    let rel_path = forward_slashes(&native_path, MAIN_SEPARATOR);
+
+                    if rel_path.is_empty() {
+                        continue;
+                    }
+
+                    if ignore_rules.is_ignored(&rel_path) {
+                        continue;
+                    }
+
+                    let kind = EntryKind::of(&entry.file_type());
+                    if kind == EntryKind::Dir && options.max_depth == Some(entry.depth) {
+                        depth_limited.push(rel_path.clone());
+                    }
+
+                    let (size, modified, link, mode) = match entry.metadata() {
+                        Ok(meta) => {
+                            let size = meta.len();
+                            let modified = meta
+                                .modified()
+                                .ok()
+                                .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+                                .map(|d| d.as_millis() as u64);
+                            (size, modified, link_id(&meta), mode_bits(&meta))
+                        }
+                        Err(e) => {
+                            errors.push(ScanError {
+                                path: rel_path.clone(),
+                                message: e.to_string(),
+                            });
+                            (0, None, None, None)
+                        }
+                    };
+
+                    // A followed link reports its target's kind and size but keeps the link
+                    let symlink_target = if kind == EntryKind::Symlink || entry.path_is_symlink() {
+                        std::fs::read_link(&path)
+                            .ok()
+                            .map(|t| t.to_string_lossy().to_string())
+                    } else {
+                        None
+                    };
+
+                    let meta = EntryMeta {
+                        kind,
+                        size,
+                        modified,
+                        symlink_target,
+                        mode,
+                        link,
+                    };
+
+                    insert_entry(
+                        &mut entries,
+                        &mut originals,
+                        &mut case_collisions,
+                        key_mode.key(&rel_path),
+                        native_path,
+                        meta,
+                    );
+
+                    if kind == EntryKind::File {
+                        bytes += size;
+                    }
+
+                    count += 1;
+                    if reported_at.is_none_or(|at| at.elapsed() >= PROGRESS_INTERVAL) {
+                        reported_at = Some(Instant::now());
+                        let report_path =
+                            path_reported_at.is_none_or(|at| at.elapsed() >= CURRENT_PATH_INTERVAL);
+                        let current_path = if report_path {
+                            path_reported_at = Some(Instant::now());
+                            entry
+                                .parent_path()
+                                .strip_prefix(root)
+                                .ok()
+                                .map(slash_path)
+                        } else {
+                            None
+                        };
+                        progress_callback(&ScanProgress {
+                            entries: count,
+                            bytes,
+                            current_path,
+                        });
+                    }
+                }
+                Err(e) => {
+                    let path = e
+                        .path()
+                        .and_then(|p| p.strip_prefix(root).ok())
+                        .map(slash_path)
+                        .unwrap_or_else(|| "unknown".to_string());
+                    errors.push(ScanError {
+                        path,
+                        message: e.to_string(),
+                    });
+                }
+            }
+        }
+        walk_done.store(true, Ordering::Relaxed);
+        relay.thread().unpark();
+    });
+    if cancel_flag.load(Ordering::Relaxed) {
+        return Err("Scan cancelled".to_string());
+    }
+
+    progress_callback(&ScanProgress {
+        entries: count,
+        bytes,
+        current_path: None,
+    });
+
+    for (link, ancestor) in loops.lock().unwrap().iter() {
+        if let Ok(rel) = link.strip_prefix(root) {
+            errors.push(ScanError {
+                path: slash_path(rel),
+                message: format!("Symlink loop: leads back to {}", ancestor.display()),
+            });
+        }
+    }
+
+    let excluded_dirs = pruned
+        .lock()
+        .unwrap()
+        .iter()
+        .filter_map(|p| p.strip_prefix(root).ok())
+        .map(slash_path)
+        .collect();
+
+    Ok(ScanResult {
+        entries,
+        originals,
+        count,
+        errors,
+        excluded_dirs,
+        depth_limited,
+        case_sensitive: detect_case_sensitive(root),
+        case_collisions,
+        key_mode,
+    })
+}
+
+/// Copies a set `cancel` into `stop` for the walk's workers, until `done` is set.
+fn relay_cancel(cancel: &AtomicBool, stop: &AtomicBool, done: &AtomicBool) {
+    while !done.load(Ordering::Relaxed) {
+        if cancel.load(Ordering::Relaxed) {
+            stop.store(true, Ordering::Relaxed);
+            return;
+        }
+        std::thread::park_timeout(CANCEL_POLL_INTERVAL);
+    }
+}
+
+/// Item counts and total size of a selection, as measured by [`measure_paths`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PathTotals {
+    /// Everything that is not a directory: files, symlinks, fifos and the like
+    pub files: usize,
+    pub dirs: usize,
+    /// Sum of regular file sizes; a symlink adds nothing for its target
+    pub bytes: u64,
+}
+
+/// Measures a selection. Files count directly; directories count themselves plus
+/// everything below them that `ignore_rules` lets through. Symlinks count as
+/// themselves and are never followed.
+///
+/// - `progress`: called with the items and bytes counted so far while directories are
+///   walked, at the scanner's pace
+pub fn measure_paths(
+    paths: &[PathBuf],
+    ignore_rules: &IgnoreRules,
+    cancel_flag: &AtomicBool,
+    progress: &dyn Fn(usize, u64),
+) -> Result<PathTotals, String> {
+    let mut totals = PathTotals::default();
+    for path in paths {
+        if cancel_flag.load(Ordering::Relaxed) {
+            return Err("Measurement cancelled".to_string());
+        }
+        let meta = std::fs::symlink_metadata(path)
+            .map_err(|e| format!("Cannot read {}: {}", path.display(), e))?;
+        if !meta.is_dir() {
+            totals.files += 1;
+            if meta.is_file() {
+                totals.bytes += meta.len();
+            }
+            continue;
+        }
+
+        totals.dirs += 1;
+        let (items, bytes) = (totals.files + totals.dirs, totals.bytes);
+        let scan = scan_directory(path, ignore_rules, &[], cancel_flag, &|scanned| {
+            progress(items + scanned.entries, bytes + scanned.bytes);
+        })?;
+        for (_, meta) in scan.listed() {
+            match meta.kind {
+                EntryKind::Dir => totals.dirs += 1,
+                EntryKind::File => {
+                    totals.files += 1;
+                    totals.bytes += meta.size;
+                }
+                _ => totals.files += 1,
+            }
+        }
+    }
+    progress(totals.files + totals.dirs, totals.bytes);
+    Ok(totals)
+}
+
+/// Probes whether the filesystem holding `root` is case-sensitive, without writing
+/// anything: an entry whose name has cased letters is looked up under the case-swapped
+/// name, to see whether that resolves to the same entry. Children of `root` are tried
+/// first, then `root` itself and its ancestors on the same volume. When none of them has
+/// a cased name, the platform default is assumed.
+pub fn detect_case_sensitive(root: &Path) -> bool {
+    let volume = volume_id(root);
+    let children = std::fs::read_dir(root)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .map(|entry| entry.path());
+    let ancestors = root
+        .ancestors()
+        .take_while(|dir| volume_id(dir) == volume)
+        .map(Path::to_path_buf);
+    children
+        .chain(ancestors)
+        .find_map(|path| probe_case_sensitive(&path))
+        .unwrap_or(!cfg!(any(target_os = "macos", windows)))
+}
+
+/// Whether `path`'s case-swapped name is a different entry, or `None` if swapping the
+/// case of its name changes nothing.
+fn probe_case_sensitive(path: &Path) -> Option<bool> {
+    let name = path.file_name()?.to_string_lossy();
+    let swapped = swap_case(&name);
+    if swapped == name {
+        return None;
+    }
+    Some(!same_entry(path, &path.with_file_name(swapped)))
+}
+
+/// The device `path` lives on, where the platform reports one.
+#[cfg(unix)]
+fn volume_id(path: &Path) -> Option<u64> {
+    use std::os::unix::fs::MetadataExt;
+    std::fs::metadata(path).ok().map(|m| m.dev())
+}
+
+#[cfg(not(unix))]
+fn volume_id(_path: &Path) -> Option<u64> {
+    None
+}
+
+fn swap_case(name: &str) -> String {
+    name.chars()
+        .map(|c| {
+            if c.is_uppercase() {
+                c.to_lowercase().next().unwrap_or(c)
+            } else {
+                c.to_uppercase().next().unwrap_or(c)
+            }
+        })
+        .collect()
+}
+
+/// Identity of the directory `path` resolves to, following symlinks.
+#[cfg(unix)]
+type DirId = (u64, u64);
+#[cfg(not(unix))]
+type DirId = PathBuf;
+
+/// Device and inode of the directory `path` resolves to.
+#[cfg(unix)]
+fn dir_id(path: &Path) -> Option<DirId> {
+    use std::os::unix::fs::MetadataExt;
+    std::fs::metadata(path).ok().map(|m| (m.dev(), m.ino()))
+}
+
+/// Without inode numbers, the canonical path stands in for the directory's identity.
+#[cfg(not(unix))]
+fn dir_id(path: &Path) -> Option<DirId> {
+    std::fs::canonicalize(path).ok()
+}
+
+/// True if `alias` exists and is the very same entry as `path`, not a distinct sibling.
+#[cfg(unix)]
+fn same_entry(path: &Path, alias: &Path) -> bool {
+    use std::os::unix::fs::MetadataExt;
+    match (std::fs::symlink_metadata(path), std::fs::symlink_metadata(alias)) {
+        (Ok(a), Ok(b)) => a.dev() == b.dev() && a.ino() == b.ino(),
+        _ => false,
+    }
+}
+
+/// Without inode numbers, an existing case-swapped alias is taken to be the same entry.
+#[cfg(not(unix))]
+fn same_entry(_path: &Path, alias: &Path) -> bool {
+    std::fs::symlink_metadata(alias).is_ok()
+}
+
+/// Returns `dir` expressed under `root` if it lives strictly inside `root`.
+/// Both paths are canonicalized so symlinked aliases are detected; the returned
+/// path is rebased onto `root` as given, matching the paths the walker produces.
+pub fn nested_dir_under(root: &Path, dir: &Path) -> Option<PathBuf> {
+    let canonical_root = root.canonicalize().ok()?;
+    let canonical_dir = dir.canonicalize().ok()?;
+    let rel = canonical_dir.strip_prefix(&canonical_root).ok()?;
+    if rel.as_os_str().is_empty() {
+        return None;
+    }
+    Some(root.join(rel))
+}
+
+/// The thread pool scans with `threads` workers read directories on. Pools are made on
+/// first use and shared by every later scan, rather than each scan starting and
+/// stopping threads of its own.
+fn walker_pool(threads: usize) -> Result<Arc<rayon::ThreadPool>, String> {
+    static POOLS: OnceLock<Mutex<HashMap<usize, Arc<rayon::ThreadPool>>>> = OnceLock::new();
+    let mut pools = POOLS
+        .get_or_init(Default::default)
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner);
+    if let Some(pool) = pools.get(&threads) {
+        return Ok(Arc::clone(pool));
+    }
+    let pool = rayon::ThreadPoolBuilder::new()
+        .num_threads(threads)
+        .thread_name(|i| format!("scan-{}", i))
+        .build()
+        .map_err(|e| format!("Cannot start scan threads: {}", e))?;
+    let pool = Arc::new(pool);
+    pools.insert(threads, Arc::clone(&pool));
+    Ok(pool)
+}
+
+fn num_cpus() -> usize {
+    std::thread::available_parallelism()
+        .map(|n| n.get())
+        .unwrap_or(4)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn no_cancel() -> AtomicBool {
+        AtomicBool::new(false)
+    }
+
+    #[test]
+    fn test_scan_empty_dir() {
+        let dir = std::env::temp_dir().join("sc_scan_empty");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+
+        let rules = IgnoreRules::new(&[]);
+        let cancel = no_cancel();
+        let result = scan_directory(&dir, &rules, &[], &cancel, &|_| {}).unwrap();
+
+        assert_eq!(result.count, 0);
+        assert!(result.entries.is_empty());
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_scan_with_files() {
+        let dir = std::env::temp_dir().join("sc_scan_files");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("subdir")).unwrap();
+        fs::write(dir.join("file1.txt"), "hello").unwrap();
+        fs::write(dir.join("subdir/file2.txt"), "world").unwrap();
+
+        let rules = IgnoreRules::new(&[]);
+        let cancel = no_cancel();
+        let result = scan_directory(&dir, &rules, &[], &cancel, &|_| {}).unwrap();
+
+        assert!(result.entries.contains_key("file1.txt"));
+        assert!(result.entries.contains_key("subdir/file2.txt"));
+        assert!(result.entries.contains_key("subdir"));
+        assert_eq!(result.entries["file1.txt"].kind, EntryKind::File);
+        assert_eq!(result.entries["file1.txt"].size, 5);
+        assert_eq!(result.entries["subdir"].kind, EntryKind::Dir);
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_scan_progress_reports_bytes_and_path() {
+        let dir = std::env::temp_dir().join("sc_scan_progress_bytes");
+        let _ = fs::remove_dir_all(&dir);
+        for sub in ["one", "two"] {
+            fs::create_dir_all(dir.join(sub)).unwrap();
+            for i in 0..600 {
+                fs::write(dir.join(sub).join(format!("{}.txt", i)), "abc").unwrap();
+            }
+        }
+
+        let rules = IgnoreRules::new(&[]);
+        let cancel = no_cancel();
+        let reports = Mutex::new(Vec::new());
+        let result = scan_directory(&dir, &rules, &[], &cancel, &|progress| {
+            reports.lock().unwrap().push(progress.clone());
+        })
+        .unwrap();
+        let _ = fs::remove_dir_all(&dir);
+
+        let reports = reports.into_inner().unwrap();
+        assert!(reports.len() >= 2);
+        // The first report always names a directory; the final one only totals
+        assert!(reports[0].current_path.is_some());
+        for path in reports.iter().filter_map(|r| r.current_path.as_deref()) {
+            assert!(["", "one", "two"].contains(&path), "unexpected path {:?}", path);
+        }
+        assert!(reports.windows(2).all(|w| w[0].bytes <= w[1].bytes));
+        let last = reports.last().unwrap();
+        assert_eq!((last.entries, last.bytes), (result.count, 3600));
+        assert_eq!(last.current_path, None);
+    }
+
+    #[test]
+    fn test_scan_progress_throttled_by_time() {
+        let dir = std::env::temp_dir().join("sc_scan_progress_time");
+        let _ = fs::remove_dir_all(&dir);
+        for sub in 0..10 {
+            let sub = dir.join(sub.to_string());
+            fs::create_dir_all(&sub).unwrap();
+            for i in 0..500 {
+                fs::write(sub.join(i.to_string()), "").unwrap();
+            }
+        }
+
+        let rules = IgnoreRules::new(&[]);
+        let cancel = no_cancel();
+        let calls = Mutex::new(Vec::new());
+        let start = Instant::now();
+        let result = scan_directory(&dir, &rules, &[], &cancel, &|_| {
+            calls.lock().unwrap().push(Instant::now());
+        })
+        .unwrap();
+        let elapsed = start.elapsed();
+        let _ = fs::remove_dir_all(&dir);
+
+        let calls = calls.into_inner().unwrap();
+        assert_eq!(result.count, 5010);
+        // First entry, one per elapsed interval, and the final report
+        let bound = (elapsed.as_millis() / PROGRESS_INTERVAL.as_millis()) as usize + 2;
+        assert!(calls.len() <= bound, "{} calls in {:?}", calls.len(), elapsed);
+        let interim = &calls[..calls.len() - 1];
+        assert!(interim.windows(2).all(|w| w[1] - w[0] >= PROGRESS_INTERVAL));
+    }
+
+    #[test]
+    fn test_scan_skip_hidden() {
+        let dir = std::env::temp_dir().join("sc_scan_hidden");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join(".cache/deep")).unwrap();
+        fs::create_dir_all(dir.join("src")).unwrap();
+        fs::write(dir.join(".cache/deep/blob"), "x").unwrap();
+        fs::write(dir.join("src/.env"), "x").unwrap();
+        fs::write(dir.join("src/main.rs"), "x").unwrap();
+
+        let rules = IgnoreRules::new(&[]);
+        let cancel = no_cancel();
+        let all = scan_directory(&dir, &rules, &[], &cancel, &|_| {}).unwrap();
+        assert_eq!(all.count, 6);
+        assert!(all.entries.contains_key(".cache/deep/blob"));
+
+        let options = ScanOptions {
+            skip_hidden: true,
+            ..Default::default()
+        };
+        let visible =
+            scan_directory_with_options(&dir, &rules, &[], &options, &cancel, &|_| {}).unwrap();
+        let mut paths: Vec<_> = visible.originals.values().cloned().collect();
+        paths.sort();
+        assert_eq!(paths, vec!["src", "src/main.rs"]);
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_scan_ignore_rules() {
+        let dir = std::env::temp_dir().join("sc_scan_ignore");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join(".DS_Store"), "").unwrap();
+        fs::write(dir.join("keep.txt"), "data").unwrap();
+
+        let rules = IgnoreRules::new(&[]);
+        let cancel = no_cancel();
+        let result = scan_directory(&dir, &rules, &[], &cancel, &|_| {}).unwrap();
+
+        assert!(!result.entries.contains_key(".ds_store"));
+        assert!(result.entries.contains_key("keep.txt"));
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_windows_separators_become_forward_slashes() {
+        let native = "node_modules\\lodash\\index.js";
+        let rel_path = forward_slashes(native, '\\');
+        assert_eq!(rel_path, "node_modules/lodash/index.js");
+        assert!(IgnoreRules::new(&["node_modules/**".to_string()]).is_ignored(&rel_path));
+
+        // On Unix a backslash is an ordinary name character and stays
+        assert_eq!(forward_slashes("a\\b/c", '/'), "a\\b/c");
+    }
+
+    #[test]
+    fn test_scan_cancellation() {
+        let dir = std::env::temp_dir().join("sc_scan_cancel");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        for i in 0..10 {
+            fs::write(dir.join(format!("file{}.txt", i)), "data").unwrap();
+        }
+
+        let rules = IgnoreRules::new(&[]);
+        // Pre-set cancel flag
+        let cancel = AtomicBool::new(true);
+        let result = scan_directory(&dir, &rules, &[], &cancel, &|_| {});
+
+        assert!(result.is_err());
+        assert!(result.unwrap_err().contains("cancelled"));
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_no_progress_after_cancel() {
+        let dir = std::env::temp_dir().join("sc_scan_cancel_progress");
+        let _ = fs::remove_dir_all(&dir);
+        for sub in 0..20 {
+            let sub = dir.join(sub.to_string());
+            fs::create_dir_all(&sub).unwrap();
+            for i in 0..100 {
+                fs::write(sub.join(i.to_string()), "").unwrap();
+            }
+        }
+
+        let rules = IgnoreRules::new(&[]);
+        let cancel = no_cancel();
+        let calls = Mutex::new(0);
+        // Cancel from the first report; nothing may be reported after it
+        let result = scan_directory(&dir, &rules, &[], &cancel, &|_| {
+            *calls.lock().unwrap() += 1;
+            cancel.store(true, Ordering::Relaxed);
+        });
+        let _ = fs::remove_dir_all(&dir);
+
+        assert!(result.unwrap_err().contains("cancelled"));
+        assert_eq!(calls.into_inner().unwrap(), 1);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_follow_links_reports_targets() {
+        use std::os::unix::fs::symlink;
+
+        let dir = std::env::temp_dir().join("sc_scan_follow");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("v2")).unwrap();
+        fs::write(dir.join("v2/app.bin"), "12345").unwrap();
+        symlink("v2", dir.join("current")).unwrap();
+        symlink("v2/app.bin", dir.join("app")).unwrap();
+
+        let rules = IgnoreRules::new(&[]);
+        let cancel = no_cancel();
+        let options = ScanOptions {
+            follow_links: true,
+            ..Default::default()
+        };
+        let result =
+            scan_directory_with_options(&dir, &rules, &[], &options, &cancel, &|_| {}).unwrap();
+        let _ = fs::remove_dir_all(&dir);
+
+        assert!(result.errors.is_empty());
+        let current = &result.entries["current"];
+        assert_eq!(current.kind, EntryKind::Dir);
+        assert_eq!(current.symlink_target.as_deref(), Some("v2"));
+        assert_eq!(result.entries["current/app.bin"].size, 5);
+        let app = &result.entries["app"];
+        assert_eq!((app.kind, app.size), (EntryKind::File, 5));
+        assert_eq!(app.symlink_target.as_deref(), Some("v2/app.bin"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_follow_links_stops_at_cycle() {
+        use std::os::unix::fs::symlink;
+
+        let dir = std::env::temp_dir().join("sc_scan_cycle");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("b")).unwrap();
+        symlink("b", dir.join("a")).unwrap();
+        symlink("../a", dir.join("b/inner")).unwrap();
+
+        let rules = IgnoreRules::new(&[]);
+        let cancel = no_cancel();
+        let options = ScanOptions {
+            follow_links: true,
+            ..Default::default()
+        };
+        let result =
+            scan_directory_with_options(&dir, &rules, &[], &options, &cancel, &|_| {}).unwrap();
+        let _ = fs::remove_dir_all(&dir);
+
+        let mut paths: Vec<_> = result.originals.values().cloned().collect();
+        paths.sort();
+        assert_eq!(paths, vec!["a", "a/inner", "b", "b/inner"]);
+        let mut looped: Vec<_> = result.errors.iter().map(|e| e.path.as_str()).collect();
+        looped.sort();
+        assert_eq!(looped, vec!["a/inner", "b/inner"]);
+        assert!(result.errors.iter().all(|e| e.message.contains("loop")));
+    }
+
+    #[test]
+    fn test_measure_paths_counts_selection() {
+        let dir = std::env::temp_dir().join("sc_measure_paths");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("folder/nested")).unwrap();
+        fs::write(dir.join("single.txt"), "12345").unwrap();
+        fs::write(dir.join("folder/a.bin"), vec![0u8; 100]).unwrap();
+        fs::write(dir.join("folder/nested/b.bin"), vec![0u8; 20]).unwrap();
+        fs::write(dir.join("folder/.DS_Store"), vec![0u8; 1000]).unwrap();
+        #[cfg(unix)]
+        std::os::unix::fs::symlink(dir.join("folder"), dir.join("folder/nested/up")).unwrap();
+
+        let rules = IgnoreRules::new(&[]);
+        let cancel = no_cancel();
+        let reports = Mutex::new(Vec::new());
+        let paths = [dir.join("single.txt"), dir.join("folder")];
+        let totals = measure_paths(&paths, &rules, &cancel, &|items, bytes| {
+            reports.lock().unwrap().push((items, bytes));
+        })
+        .unwrap();
+        let missing = measure_paths(&[dir.join("missing")], &rules, &cancel, &|_, _| {});
+        let _ = fs::remove_dir_all(&dir);
+
+        // The symlink counts as one item and its target's contents are not walked twice
+        let links = if cfg!(unix) { 1 } else { 0 };
+        assert_eq!(totals, PathTotals { files: 3 + links, dirs: 2, bytes: 125 });
+        let reports = reports.into_inner().unwrap();
+        assert_eq!(*reports.last().unwrap(), (5 + links, 125));
+        assert!(missing.unwrap_err().contains("missing"));
+    }
+
+    #[test]
+    fn test_scan_excluded_dirs() {
+        let dir = std::env::temp_dir().join("sc_scan_excluded");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("support/app.data")).unwrap();
+        fs::write(dir.join("support/app.data/state.json"), "{}").unwrap();
+        fs::write(dir.join("support/other.txt"), "data").unwrap();
+
+        let app_dir = nested_dir_under(&dir, &dir.join("support/app.data")).unwrap();
+        let rules = IgnoreRules::new(&[]);
+        let cancel = no_cancel();
+        let result = scan_directory(&dir, &rules, &[app_dir], &cancel, &|_| {}).unwrap();
+
+        assert!(result.entries.contains_key("support/other.txt"));
+        assert!(!result.entries.contains_key("support/app.data"));
+        assert!(!result.entries.contains_key("support/app.data/state.json"));
+        assert_eq!(result.excluded_dirs, vec!["support/app.data".to_string()]);
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_detect_case_sensitive_probe() {
+        let dir = std::env::temp_dir().join("sc_scan_case_probe");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("Readme.md"), "x").unwrap();
+
+        // Ground truth: does the swapped name resolve on this filesystem?
+        let expected = !dir.join("rEADME.MD").exists();
+        assert_eq!(detect_case_sensitive(&dir), expected);
+
+        // Same answer from the root's own name when no child has cased letters, and
+        // nothing is written to find out
+        fs::remove_file(dir.join("Readme.md")).unwrap();
+        fs::write(dir.join("123"), "x").unwrap();
+        assert_eq!(detect_case_sensitive(&dir), expected);
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_scan_records_case_collisions() {
+        let dir = std::env::temp_dir().join("sc_scan_case_collision");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("Foo"), "upper").unwrap();
+        fs::write(dir.join("foo"), "lower!").unwrap();
+        let rules = IgnoreRules::new(&[]);
+        let cancel = no_cancel();
+        let result = scan_directory(&dir, &rules, &[], &cancel, &|_| {}).unwrap();
+
+        if result.case_sensitive {
+            let mut variants: Vec<&str> = result.case_collisions["foo"]
+                .iter()
+                .map(|(path, _)| path.as_str())
+                .collect();
+            variants.sort();
+            assert_eq!(variants, vec!["Foo", "foo"]);
+
+            let exact = result.rekey(KeyMode {
+                case_sensitive: true,
+                ..Default::default()
+            });
+            assert_eq!(exact.entries["Foo"].size, 5);
+            assert_eq!(exact.entries["foo"].size, 6);
+            assert_eq!(exact.originals["Foo"], "Foo");
+            assert!(exact.case_collisions.is_empty());
+        } else {
+            // Case-insensitive volume: the second write replaced the first's contents
+            assert!(result.case_collisions.is_empty());
+        }
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_nested_dir_under() {
+        let dir = std::env::temp_dir().join("sc_scan_nested_dir");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("a/b")).unwrap();
+
+        assert_eq!(nested_dir_under(&dir, &dir.join("a/b")), Some(dir.join("a/b")));
+        // The root itself and directories outside it are not nested
+        assert_eq!(nested_dir_under(&dir, &dir), None);
+        assert_eq!(nested_dir_under(&dir.join("a/b"), &dir.join("a")), None);
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_scan_case_insensitive_keys() {
+        let dir = std::env::temp_dir().join("sc_scan_case");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("README.md"), "hello").unwrap();
+
+        let rules = IgnoreRules::new(&[]);
+        let cancel = no_cancel();
+        let result = scan_directory(&dir, &rules, &[], &cancel, &|_| {}).unwrap();
+
+        // Key should be lowercased
+        assert!(result.entries.contains_key("readme.md"));
+        // Original should preserve case
+        assert_eq!(result.originals["readme.md"], "README.md");
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_scans_share_one_pool_per_thread_count() {
+        let dir = std::env::temp_dir().join("sc_scan_shared_pool");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("sub")).unwrap();
+        fs::write(dir.join("sub/a.txt"), "a").unwrap();
+
+        let rules = IgnoreRules::new(&[]);
+        let cancel = no_cancel();
+        for _ in 0..3 {
+            let result = scan_directory(&dir, &rules, &[], &cancel, &|_| {}).unwrap();
+            assert!(result.entries.contains_key("sub/a.txt"));
+        }
+        let pool = walker_pool(3).unwrap();
+        assert!(Arc::ptr_eq(&pool, &walker_pool(3).unwrap()));
+        assert!(!Arc::ptr_eq(&pool, &walker_pool(2).unwrap()));
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
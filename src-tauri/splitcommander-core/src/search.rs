@@ -0,0 +1,562 @@
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Read};
+use std::path::{Path, MAIN_SEPARATOR};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant, UNIX_EPOCH};
+
+use glob_match::glob_match;
+use jwalk::{DirEntry, WalkDir};
+use regex::Regex;
+use serde::Serialize;
+
+use crate::ignore::IgnoreRules;
+use crate::model::EntryKind;
+use crate::scan::{forward_slashes, ScanError};
+
+/// Matches are handed over once this many have piled up...
+const BATCH_SIZE: usize = 200;
+
+/// ...or once the oldest pending one has waited this long, so slow walks still stream.
+const BATCH_INTERVAL: Duration = Duration::from_millis(100);
+
+/// What to look for in [`search_files`].
+#[derive(Debug, Clone)]
+pub struct SearchQuery {
+    /// Substring of the file name, or a glob over the relative path with `use_glob`.
+    /// Either way matched case-insensitively.
+    pub pattern: String,
+    pub use_glob: bool,
+    /// Stop walking after this many matches
+    pub max_results: usize,
+}
+
+impl SearchQuery {
+    fn matcher(&self) -> impl Fn(&str, &str) -> bool + '_ {
+        let pattern = self.pattern.to_lowercase();
+        move |rel_path: &str, name: &str| {
+            if self.use_glob {
+                glob_match(&pattern, &rel_path.to_lowercase())
+            } else {
+                name.to_lowercase().contains(&pattern)
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SearchMatch {
+    /// Relative to the search root, with `/` separators
+    pub rel_path: String,
+    pub kind: EntryKind,
+    pub size: u64,
+    /// Epoch milliseconds for JS interop
+    pub modified: Option<u64>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SearchSummary {
+    /// Matches handed to `on_batch`
+    pub total: usize,
+    /// Whether more than `max_results` entries matched; the walk stopped at the first
+    /// one over
+    pub truncated: bool,
+}
+
+/// Walks `root` for entries matching `query`, handing matches to `on_batch` in walk
+/// order as they are found. Symlinks are reported but never followed. Entries matched
+/// by `ignore_rules` are skipped, and ignored directories are not searched.
+///
+/// Unreadable directories are skipped silently. Returns an error only when cancelled,
+/// after any matches found before that have been handed over.
+pub fn search_files(
+    root: &Path,
+    query: &SearchQuery,
+    ignore_rules: &IgnoreRules,
+    cancel_flag: &AtomicBool,
+    on_batch: &dyn Fn(Vec<SearchMatch>),
+) -> Result<SearchSummary, String> {
+    let matches = query.matcher();
+    let mut batches = Batches::new(on_batch);
+    let mut truncated = false;
+
+    let walked = walk(root, ignore_rules, cancel_flag, &mut Vec::new(), |rel_path, entry| {
+        if matches(&rel_path, &entry.file_name().to_string_lossy()) {
+            if batches.len() >= query.max_results {
+                truncated = true;
+                return false;
+            }
+            let meta = entry.metadata().ok();
+            batches.push(SearchMatch {
+                rel_path,
+                kind: EntryKind::of(&entry.file_type()),
+                size: meta.as_ref().map_or(0, |m| m.len()),
+                modified: meta
+                    .and_then(|m| m.modified().ok())
+                    .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+                    .map(|d| d.as_millis() as u64),
+            });
+        }
+        batches.flush_if_due();
+        true
+    });
+
+    let total = batches.finish();
+    walked?;
+    Ok(SearchSummary { total, truncated })
+}
+
+/// Files whose first this many bytes hold a NUL are taken to be binary.
+pub(crate) const BINARY_SNIFF_LEN: u64 = 8 * 1024;
+
+/// Longer matching lines are cut to this many characters in [`ContentMatch::line_text`].
+const MAX_LINE_TEXT: usize = 500;
+
+/// What to look for in [`search_content`].
+#[derive(Debug, Clone)]
+pub struct ContentQuery {
+    matcher: LineMatcher,
+    /// Larger files are skipped without being opened
+    pub max_file_size: u64,
+    /// Stop searching after this many matching lines
+    pub max_results: usize,
+}
+
+#[derive(Debug, Clone)]
+enum LineMatcher {
+    Literal(String),
+    Regex(Regex),
+}
+
+impl ContentQuery {
+    /// Matches lines containing `pattern`, or matching it as a regular expression with
+    /// `regex`. Either way case-sensitively; a regex can opt out with `(?i)`.
+    /// Fails if `pattern` is not a valid regex.
+    pub fn new(
+        pattern: &str,
+        regex: bool,
+        max_file_size: u64,
+        max_results: usize,
+    ) -> Result<Self, String> {
+        let matcher = if regex {
+            let compiled =
+                Regex::new(pattern).map_err(|e| format!("Invalid regex {:?}: {}", pattern, e))?;
+            LineMatcher::Regex(compiled)
+        } else {
+            LineMatcher::Literal(pattern.to_string())
+        };
+        Ok(Self {
+            matcher,
+            max_file_size,
+            max_results,
+        })
+    }
+
+    fn matches(&self, line: &str) -> bool {
+        match &self.matcher {
+            LineMatcher::Literal(text) => line.contains(text.as_str()),
+            LineMatcher::Regex(regex) => regex.is_match(line),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ContentMatch {
+    /// Relative to the search root, with `/` separators
+    pub rel_path: String,
+    /// 1-based
+    pub line_number: usize,
+    /// The line without its line break, cut to 500 characters
+    pub line_text: String,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ContentSummary {
+    /// Matching lines handed to `on_batch`
+    pub total: usize,
+    /// Whether more lines matched than `max_results` allowed
+    pub truncated: bool,
+    pub files_searched: usize,
+    pub skipped_binary: usize,
+    /// Files over `max_file_size`
+    pub skipped_large: usize,
+    /// Files and directories that could not be read
+    pub errors: Vec<ScanError>,
+}
+
+/// Walks `root` for text files with lines matching `query`, handing matches to
+/// `on_batch` as they are found. Files are read line by line, so their size only
+/// matters through `max_file_size`. Symlinks are never followed; binary files and
+/// anything `ignore_rules` matches are skipped.
+///
+/// A file that cannot be read is recorded in the summary's `errors` and the search
+/// goes on. Returns an error only when cancelled, after any matches found before that
+/// have been handed over.
+pub fn search_content(
+    root: &Path,
+    query: &ContentQuery,
+    ignore_rules: &IgnoreRules,
+    cancel_flag: &AtomicBool,
+    on_batch: &dyn Fn(Vec<ContentMatch>),
+) -> Result<ContentSummary, String> {
+    let mut summary = ContentSummary::default();
+    let mut batches = Batches::new(on_batch);
+    let mut walk_errors = Vec::new();
+
+    let walked = walk(root, ignore_rules, cancel_flag, &mut walk_errors, |rel_path, entry| {
+        if !entry.file_type().is_file() {
+            return true;
+        }
+        let size = entry.metadata().map_or(0, |m| m.len());
+        if size > query.max_file_size {
+            summary.skipped_large += 1;
+            return true;
+        }
+
+        let read = search_file(&entry.path(), query, cancel_flag, |line_number, line| {
+            if batches.len() >= query.max_results {
+                summary.truncated = true;
+                return false;
+            }
+            batches.push(ContentMatch {
+                rel_path: rel_path.clone(),
+                line_number,
+                line_text: line.chars().take(MAX_LINE_TEXT).collect(),
+            });
+            true
+        });
+        match read {
+            Ok(FileSearch::Binary) => summary.skipped_binary += 1,
+            Ok(FileSearch::Searched) => summary.files_searched += 1,
+            Err(e) => summary.errors.push(ScanError {
+                path: rel_path,
+                message: e.to_string(),
+            }),
+        }
+        batches.flush_if_due();
+        !summary.truncated
+    });
+
+    summary.total = batches.finish();
+    summary.errors.extend(walk_errors);
+    walked?;
+    Ok(summary)
+}
+
+enum FileSearch {
+    Searched,
+    Binary,
+}
+
+/// Feeds each line of the file at `path` that matches `query` to `on_match` (with its
+/// 1-based number) until that returns `false`. Lines that are not UTF-8 are matched
+/// with invalid bytes replaced.
+fn search_file(
+    path: &Path,
+    query: &ContentQuery,
+    cancel_flag: &AtomicBool,
+    mut on_match: impl FnMut(usize, &str) -> bool,
+) -> io::Result<FileSearch> {
+    let mut file = File::open(path)?;
+    let mut head = Vec::new();
+    (&mut file).take(BINARY_SNIFF_LEN).read_to_end(&mut head)?;
+    if head.contains(&0) {
+        return Ok(FileSearch::Binary);
+    }
+
+    let mut reader = BufReader::new(io::Cursor::new(head).chain(file));
+    let mut line = Vec::new();
+    let mut line_number = 0;
+    while !cancel_flag.load(Ordering::Relaxed) {
+        line.clear();
+        if reader.read_until(b'\n', &mut line)? == 0 {
+            break;
+        }
+        line_number += 1;
+        let text = String::from_utf8_lossy(&line);
+        let text = text.trim_end_matches(['\n', '\r']);
+        if query.matches(text) && !on_match(line_number, text) {
+            break;
+        }
+    }
+    Ok(FileSearch::Searched)
+}
+
+/// Walks `root` without following symlinks, calling `visit` with the `/`-separated
+/// relative path of every entry `ignore_rules` lets through, until it returns `false`.
+/// Unreadable entries are added to `errors`.
+fn walk(
+    root: &Path,
+    ignore_rules: &IgnoreRules,
+    cancel_flag: &AtomicBool,
+    errors: &mut Vec<ScanError>,
+    mut visit: impl FnMut(String, &DirEntry<((), ())>) -> bool,
+) -> Result<(), String> {
+    for entry_result in walker(root, ignore_rules) {
+        if cancel_flag.load(Ordering::Relaxed) {
+            return Err("Search cancelled".to_string());
+        }
+        let entry = match entry_result {
+            Ok(entry) => entry,
+            Err(e) => {
+                errors.push(ScanError {
+                    path: e
+                        .path()
+                        .and_then(|path| relative(root, path))
+                        .unwrap_or_else(|| "unknown".to_string()),
+                    message: e.to_string(),
+                });
+                continue;
+            }
+        };
+        let Some(rel_path) = relative(root, &entry.path()) else {
+            continue;
+        };
+        if rel_path.is_empty() || ignore_rules.is_ignored(&rel_path) {
+            continue;
+        }
+        if !visit(rel_path, &entry) {
+            break;
+        }
+    }
+    // A file search may have stopped early for the cancel rather than running out
+    if cancel_flag.load(Ordering::Relaxed) {
+        return Err("Search cancelled".to_string());
+    }
+    Ok(())
+}
+
+/// A walk of `root` that does not follow symlinks or read ignored directories. They
+/// are still listed, for `walk` to drop, but nothing below them is.
+fn walker(root: &Path, ignore_rules: &IgnoreRules) -> WalkDir {
+    let base = root.to_path_buf();
+    let rules = ignore_rules.clone();
+    WalkDir::new(root)
+        .skip_hidden(false)
+        .follow_links(false)
+        .process_read_dir(move |_, _, _, children| {
+            for child in children.iter_mut().flatten() {
+                if child.read_children_path.is_none() {
+                    continue;
+                }
+                if relative(&base, &child.path()).is_some_and(|rel| rules.is_ignored(&rel)) {
+                    child.read_children_path = None;
+                }
+            }
+        })
+}
+
+/// The `/`-separated path of `path` below `root`, empty for the root itself.
+fn relative(root: &Path, path: &Path) -> Option<String> {
+    let native = path.strip_prefix(root).ok()?.to_string_lossy().to_string();
+    Some(forward_slashes(&native, MAIN_SEPARATOR))
+}
+
+/// Collects matches into batches for `on_batch`: a batch goes out once it is full or
+/// its oldest match has waited long enough.
+struct Batches<'a, T> {
+    pending: Vec<T>,
+    started: Instant,
+    sent: usize,
+    on_batch: &'a dyn Fn(Vec<T>),
+}
+
+impl<'a, T> Batches<'a, T> {
+    fn new(on_batch: &'a dyn Fn(Vec<T>)) -> Self {
+        Self {
+            pending: Vec::new(),
+            started: Instant::now(),
+            sent: 0,
+            on_batch,
+        }
+    }
+
+    /// Matches collected so far, sent or not.
+    fn len(&self) -> usize {
+        self.sent + self.pending.len()
+    }
+
+    fn push(&mut self, item: T) {
+        if self.pending.is_empty() {
+            self.started = Instant::now();
+        }
+        self.pending.push(item);
+    }
+
+    fn flush_if_due(&mut self) {
+        if self.pending.len() >= BATCH_SIZE
+            || (!self.pending.is_empty() && self.started.elapsed() >= BATCH_INTERVAL)
+        {
+            self.flush();
+        }
+    }
+
+    fn flush(&mut self) {
+        self.sent += self.pending.len();
+        (self.on_batch)(std::mem::take(&mut self.pending));
+    }
+
+    /// Sends what is left and returns the total sent.
+    fn finish(mut self) -> usize {
+        if !self.pending.is_empty() {
+            self.flush();
+        }
+        self.sent
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    fn tree(name: &str) -> std::path::PathBuf {
+        let root = std::env::temp_dir().join(name);
+        let _ = std::fs::remove_dir_all(&root);
+        for file in ["Report.TXT", "src/main.rs", "src/report.rs", "node_modules/report.js"] {
+            let path = root.join(file);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, b"data").unwrap();
+        }
+        root
+    }
+
+    fn search(root: &Path, pattern: &str, use_glob: bool, max_results: usize) -> Vec<String> {
+        let query = SearchQuery {
+            pattern: pattern.to_string(),
+            use_glob,
+            max_results,
+        };
+        let rules = IgnoreRules::new(&["node_modules".to_string()]);
+        let found = Mutex::new(Vec::new());
+        let cancel = AtomicBool::new(false);
+        search_files(root, &query, &rules, &cancel, &|batch| {
+            found.lock().unwrap().extend(batch.into_iter().map(|m| m.rel_path));
+        })
+        .unwrap();
+        let mut found = found.into_inner().unwrap();
+        found.sort();
+        found
+    }
+
+    #[test]
+    fn test_name_match_is_case_insensitive_and_skips_ignored_dirs() {
+        let root = tree("sc_search_name");
+        let found = search(&root, "report", false, 100);
+        let _ = std::fs::remove_dir_all(&root);
+
+        assert_eq!(found, vec!["Report.TXT", "src/report.rs"]);
+    }
+
+    #[test]
+    fn test_ignored_dirs_are_not_read() {
+        let root = tree("sc_search_pruned");
+        std::fs::create_dir_all(root.join("node_modules/pkg/lib")).unwrap();
+        let rules = IgnoreRules::new(&["node_modules".to_string()]);
+        let listed: Vec<String> = walker(&root, &rules)
+            .into_iter()
+            .filter_map(|entry| relative(&root, &entry.unwrap().path()))
+            .collect();
+        let _ = std::fs::remove_dir_all(&root);
+
+        assert!(listed.contains(&"node_modules".to_string()));
+        assert!(listed.contains(&"src/main.rs".to_string()));
+        assert!(!listed.iter().any(|rel| rel.starts_with("node_modules/")));
+    }
+
+    #[test]
+    fn test_glob_matches_relative_path() {
+        let root = tree("sc_search_glob");
+        let found = search(&root, "src/*.rs", true, 100);
+        let capped = search(&root, "**/*", true, 2);
+        let _ = std::fs::remove_dir_all(&root);
+
+        assert_eq!(found, vec!["src/main.rs", "src/report.rs"]);
+        assert_eq!(capped.len(), 2);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_symlinks_not_followed() {
+        let root = tree("sc_search_links");
+        std::os::unix::fs::symlink(root.join("src"), root.join("linked")).unwrap();
+        let found = search(&root, "main", false, 100);
+        let _ = std::fs::remove_dir_all(&root);
+
+        assert_eq!(found, vec!["src/main.rs"]);
+    }
+
+    #[test]
+    fn test_cancelled_search_errors() {
+        let root = tree("sc_search_cancel");
+        let query = SearchQuery {
+            pattern: "report".to_string(),
+            use_glob: false,
+            max_results: 100,
+        };
+        let cancel = AtomicBool::new(true);
+        let result = search_files(&root, &query, &IgnoreRules::new(&[]), &cancel, &|_| {});
+        let _ = std::fs::remove_dir_all(&root);
+
+        assert_eq!(result, Err("Search cancelled".to_string()));
+    }
+
+    fn grep(root: &Path, query: &ContentQuery) -> (Vec<ContentMatch>, ContentSummary) {
+        let found = Mutex::new(Vec::new());
+        let cancel = AtomicBool::new(false);
+        let summary = search_content(root, query, &IgnoreRules::new(&[]), &cancel, &|batch| {
+            found.lock().unwrap().extend(batch);
+        })
+        .unwrap();
+        (found.into_inner().unwrap(), summary)
+    }
+
+    #[test]
+    fn test_content_search_reports_matching_lines() {
+        let root = std::env::temp_dir().join("sc_search_content");
+        let _ = std::fs::remove_dir_all(&root);
+        std::fs::create_dir_all(root.join("logs")).unwrap();
+        std::fs::write(root.join("logs/app.log"), "start\r\nerror: disk full\r\nok\nerror 42\n")
+            .unwrap();
+        std::fs::write(root.join("blob.bin"), b"error\0\x01\x02").unwrap();
+        std::fs::write(root.join("big.txt"), "error\n".repeat(100)).unwrap();
+
+        let query = ContentQuery::new("error", false, 100, 10).unwrap();
+        let (found, summary) = grep(&root, &query);
+        let regex = ContentQuery::new(r"error \d+$", true, 100, 10).unwrap();
+        let (by_regex, _) = grep(&root, &regex);
+        let _ = std::fs::remove_dir_all(&root);
+
+        let lines: Vec<_> = found.iter().map(|m| (m.line_number, m.line_text.as_str())).collect();
+        assert_eq!(lines, vec![(2, "error: disk full"), (4, "error 42")]);
+        assert_eq!(found[0].rel_path, "logs/app.log");
+        assert_eq!((summary.skipped_binary, summary.skipped_large), (1, 1));
+        assert_eq!(summary.files_searched, 1);
+        assert_eq!(by_regex.len(), 1);
+        assert_eq!(by_regex[0].line_number, 4);
+    }
+
+    #[test]
+    fn test_content_search_caps_results() {
+        let root = std::env::temp_dir().join("sc_search_content_cap");
+        let _ = std::fs::remove_dir_all(&root);
+        std::fs::create_dir_all(&root).unwrap();
+        std::fs::write(root.join("many.txt"), "hit\n".repeat(50)).unwrap();
+
+        let query = ContentQuery::new("hit", false, u64::MAX, 5).unwrap();
+        let (found, summary) = grep(&root, &query);
+        let _ = std::fs::remove_dir_all(&root);
+
+        assert_eq!((found.len(), summary.total), (5, 5));
+        assert!(summary.truncated);
+    }
+
+    #[test]
+    fn test_invalid_regex_rejected() {
+        let err = ContentQuery::new("(unclosed", true, 100, 10).unwrap_err();
+        assert!(err.starts_with("Invalid regex \"(unclosed\""), "{}", err);
+    }
+}
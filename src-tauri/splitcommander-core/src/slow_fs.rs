@@ -0,0 +1,351 @@
+//! Test-only `TreeSource` shim that simulates a slow, flaky filesystem (NAS, SMB,
+//! sleeping USB disks). It wraps another source and injects per-operation latency,
+//! periodic failures and stalled reads, so cancellation and progress guarantees can be
+//! asserted without real network mounts.
+
+use std::collections::HashMap;
+use std::io::{self, Read};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use crate::ignore::IgnoreRules;
+use crate::model::{EntryKind, EntryMeta};
+use crate::scan::{ScanError, ScanOptions, ScanProgress, ScanResult};
+use crate::source::TreeSource;
+
+#[derive(Debug, Clone)]
+pub struct SlowConfig {
+    /// Delay for every entry listed during a scan and every `stat`
+    pub entry_latency: Duration,
+    /// Delay for every `read` call on an opened file
+    pub read_latency: Duration,
+    /// Fail every Nth operation (listing an entry, opening a file); `None` never fails
+    pub fail_every: Option<usize>,
+    /// Scan progress is reported after this many listed entries
+    pub progress_every: usize,
+    /// The first read of this file blocks until the [`Stall`] is released
+    pub stall: Option<(String, Arc<Stall>)>,
+}
+
+impl Default for SlowConfig {
+    fn default() -> Self {
+        Self {
+            entry_latency: Duration::ZERO,
+            read_latency: Duration::ZERO,
+            fail_every: None,
+            progress_every: 1000,
+            stall: None,
+        }
+    }
+}
+
+/// A read held up the way a sleeping disk or a dropped mount holds it, until the test
+/// lets it go.
+#[derive(Debug, Default)]
+pub struct Stall {
+    /// (a read is waiting, it was released)
+    state: Mutex<(bool, bool)>,
+    changed: Condvar,
+}
+
+impl Stall {
+    fn hold(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.0 = true;
+        self.changed.notify_all();
+        while !state.1 {
+            state = self.changed.wait(state).unwrap();
+        }
+    }
+
+    /// Blocks until a read is stalled.
+    pub fn wait_reached(&self) {
+        let mut state = self.state.lock().unwrap();
+        while !state.0 {
+            state = self.changed.wait(state).unwrap();
+        }
+    }
+
+    pub fn release(&self) {
+        self.state.lock().unwrap().1 = true;
+        self.changed.notify_all();
+    }
+}
+
+pub struct SlowSource<S: TreeSource> {
+    inner: S,
+    config: SlowConfig,
+    ops: AtomicUsize,
+    reads: Arc<AtomicUsize>,
+}
+
+impl<S: TreeSource> SlowSource<S> {
+    pub fn new(inner: S, config: SlowConfig) -> Self {
+        Self {
+            inner,
+            config,
+            ops: AtomicUsize::new(0),
+            reads: Arc::new(AtomicUsize::new(0)),
+        }
+    }
+
+    /// How many `read` calls were made on the files this source opened.
+    pub fn reads(&self) -> usize {
+        self.reads.load(Ordering::Relaxed)
+    }
+
+    /// Counts one operation and returns true if it should fail.
+    fn next_op_fails(&self) -> bool {
+        let n = self.ops.fetch_add(1, Ordering::Relaxed) + 1;
+        self.config.fail_every.is_some_and(|every| n.is_multiple_of(every))
+    }
+}
+
+impl<S: TreeSource> TreeSource for SlowSource<S> {
+    fn root_label(&self) -> String {
+        format!("slow:{}", self.inner.root_label())
+    }
+
+    /// Replays the inner listing one entry at a time, paying the latency per entry the
+    /// way a directory walk over a slow mount does.
+    fn scan(
+        &self,
+        ignore_rules: &IgnoreRules,
+        excluded_dirs: &[PathBuf],
+        options: &ScanOptions,
+        cancel_flag: &AtomicBool,
+        progress_callback: &dyn Fn(&ScanProgress),
+    ) -> Result<ScanResult, String> {
+        let listing = self
+            .inner
+            .scan(ignore_rules, excluded_dirs, options, cancel_flag, &|_| {})?;
+        let mut keys: Vec<&String> = listing.entries.keys().collect();
+        keys.sort();
+
+        let mut entries = HashMap::new();
+        let mut originals = HashMap::new();
+        let mut errors = listing.errors.clone();
+        let mut progress = ScanProgress::default();
+        for key in keys {
+            if cancel_flag.load(Ordering::Relaxed) {
+                return Err("Scan cancelled".to_string());
+            }
+            thread::sleep(self.config.entry_latency);
+            let original = listing.originals[key].clone();
+            if self.next_op_fails() {
+                errors.push(ScanError {
+                    path: original,
+                    message: "Simulated I/O error".to_string(),
+                });
+                continue;
+            }
+            let meta = listing.entries[key].clone();
+            if meta.kind == EntryKind::File {
+                progress.bytes += meta.size;
+            }
+            entries.insert(key.clone(), meta);
+            originals.insert(key.clone(), original);
+            progress.entries += 1;
+            if progress.entries.is_multiple_of(self.config.progress_every) {
+                progress_callback(&progress);
+            }
+        }
+        progress_callback(&progress);
+        let count = progress.entries;
+
+        Ok(ScanResult {
+            entries,
+            originals,
+            count,
+            errors,
+            excluded_dirs: listing.excluded_dirs,
+            depth_limited: listing.depth_limited,
+            case_sensitive: listing.case_sensitive,
+            case_collisions: listing.case_collisions,
+            key_mode: listing.key_mode,
+        })
+    }
+
+    fn open(&self, rel_path: &str) -> io::Result<Box<dyn Read + Send>> {
+        thread::sleep(self.config.entry_latency);
+        if self.next_op_fails() {
+            return Err(io::Error::other("Simulated I/O error"));
+        }
+        let stall = match &self.config.stall {
+            Some((path, stall)) if path == rel_path => Some(Arc::clone(stall)),
+            _ => None,
+        };
+        Ok(Box::new(SlowReader {
+            inner: self.inner.open(rel_path)?,
+            latency: self.config.read_latency,
+            stall,
+            reads: Arc::clone(&self.reads),
+        }))
+    }
+
+    fn stat(&self, rel_path: &str) -> io::Result<EntryMeta> {
+        thread::sleep(self.config.entry_latency);
+        self.inner.stat(rel_path)
+    }
+}
+
+struct SlowReader {
+    inner: Box<dyn Read + Send>,
+    latency: Duration,
+    stall: Option<Arc<Stall>>,
+    reads: Arc<AtomicUsize>,
+}
+
+impl Read for SlowReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.reads.fetch_add(1, Ordering::Relaxed);
+        if let Some(stall) = self.stall.take() {
+            stall.hold();
+        }
+        thread::sleep(self.latency);
+        self.inner.read(buf)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compare::compare;
+    use crate::model::CompareMode;
+    use crate::source::memory::MemorySource;
+    use crate::verify::{verify_trees, MismatchKind};
+    use std::time::Instant;
+
+    /// Cancellation must be observed within this long after the flag is set.
+    const CANCEL_DEADLINE: Duration = Duration::from_millis(500);
+
+    fn tree(files: usize) -> MemorySource {
+        (0..files).fold(MemorySource::new(), |tree, i| {
+            tree.file(&format!("dir{}/file{}.txt", i % 10, i), "payload")
+        })
+    }
+
+    fn verify(
+        left: &SlowSource<MemorySource>,
+        right: &SlowSource<MemorySource>,
+        cancel: &AtomicBool,
+    ) -> Result<crate::verify::VerifyResult, String> {
+        verify_trees(left, right, &IgnoreRules::new(&[]), 100, cancel, &|_, _| {})
+    }
+
+    /// Runs `work` on a thread, sets the cancel flag after `delay`, and returns how long
+    /// the work kept running once cancellation was requested, plus its result.
+    fn cancel_after<T: Send + 'static>(
+        delay: Duration,
+        work: impl FnOnce(Arc<AtomicBool>) -> T + Send + 'static,
+    ) -> (Duration, T) {
+        let cancel = Arc::new(AtomicBool::new(false));
+        let worker_cancel = Arc::clone(&cancel);
+        let handle = thread::spawn(move || {
+            let result = work(worker_cancel);
+            (Instant::now(), result)
+        });
+        thread::sleep(delay);
+        let requested = Instant::now();
+        cancel.store(true, Ordering::Relaxed);
+        let (finished, result) = handle.join().unwrap();
+        (finished.saturating_duration_since(requested), result)
+    }
+
+    #[test]
+    fn test_cancel_during_slow_verify_reads_is_prompt() {
+        let big = "x".repeat(4 * 1024 * 1024);
+        let make = || {
+            SlowSource::new(
+                MemorySource::new().file("big.bin", &big),
+                SlowConfig {
+                    read_latency: Duration::from_millis(50),
+                    ..Default::default()
+                },
+            )
+        };
+        let (left, right) = (Arc::new(make()), make());
+        let reader = Arc::clone(&left);
+        let (latency, result) = cancel_after(Duration::from_millis(200), move |cancel| {
+            verify(&reader, &right, &cancel)
+        });
+
+        // The only file was being read: the run is cancelled, not a failed check
+        assert!(result.unwrap_err().contains("cancelled"));
+        assert!(latency < CANCEL_DEADLINE, "cancel took {:?}", latency);
+        // 16 chunks of 256 KiB at 50ms a read would take 800ms
+        assert!(left.reads() < 16, "read {} chunks", left.reads());
+    }
+
+    #[test]
+    fn test_cancel_while_a_read_is_stalled() {
+        let big = "x".repeat(1024 * 1024);
+        let stall = Arc::new(Stall::default());
+        let left = SlowSource::new(
+            MemorySource::new().file("a.bin", &big).file("b.bin", &big),
+            SlowConfig {
+                stall: Some(("a.bin".to_string(), Arc::clone(&stall))),
+                ..Default::default()
+            },
+        );
+        let right = SlowSource::new(
+            MemorySource::new().file("a.bin", &big).file("b.bin", &big),
+            SlowConfig::default(),
+        );
+        let cancel = Arc::new(AtomicBool::new(false));
+
+        let result = thread::scope(|scope| {
+            let run = scope.spawn(|| verify(&left, &right, &cancel));
+            stall.wait_reached();
+            cancel.store(true, Ordering::Relaxed);
+            stall.release();
+            run.join().unwrap()
+        });
+
+        assert!(result.unwrap_err().contains("cancelled"));
+        // The stalled read finishes, but no further chunk and no other file is read
+        assert_eq!(left.reads(), 1);
+        assert_eq!(right.reads(), 1);
+    }
+
+    #[test]
+    fn test_failures_surface_as_errors_not_as_missing() {
+        let left = SlowSource::new(
+            tree(50),
+            SlowConfig {
+                fail_every: Some(7),
+                ..Default::default()
+            },
+        );
+        let rules = IgnoreRules::new(&[]);
+        let cancel = AtomicBool::new(false);
+        let options = ScanOptions::default();
+        let scanned = left.scan(&rules, &[], &options, &cancel, &|_| {}).unwrap();
+        assert!(!scanned.errors.is_empty());
+
+        let right = tree(50).scan(&rules, &[], &options, &cancel, &|_| {}).unwrap();
+        let result = compare(&scanned, &right, CompareMode::Smart, &cancel).unwrap();
+        // Failed entries surface as errors rather than as missing on the left
+        assert_eq!(result.summary.errors, scanned.errors.len());
+        assert_eq!(result.summary.only_right, 0);
+        assert_eq!(result.summary.same, scanned.entries.len());
+
+        // Verifying pays for failed listings and failed opens alike, and neither reads
+        // as an entry missing on one side
+        let right = SlowSource::new(tree(50), SlowConfig::default());
+        let left = SlowSource::new(
+            tree(50),
+            SlowConfig {
+                fail_every: Some(7),
+                ..Default::default()
+            },
+        );
+        let verified = verify(&left, &right, &cancel).unwrap();
+        assert!(!verified.passed);
+        assert!(verified.files_compared > 0);
+        assert!(verified.mismatches.iter().all(|m| m.kind == MismatchKind::Error));
+    }
+}
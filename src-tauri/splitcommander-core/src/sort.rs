@@ -0,0 +1,202 @@
+//! Listing order: directories first, then by a chosen column.
+//!
+//! Names sort "naturally": runs of ASCII digits compare by value, so `file2` comes
+//! before `file10`, and the rest compares case-insensitively.
+
+use std::cmp::Ordering;
+use std::iter::Peekable;
+use std::str::Chars;
+
+use crate::error::CommandError;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SortKey {
+    #[default]
+    Name,
+    Size,
+    Modified,
+    Extension,
+}
+
+/// How a listing is ordered. Directories stay ahead of everything else either way.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SortOrder {
+    pub key: SortKey,
+    pub ascending: bool,
+}
+
+impl Default for SortOrder {
+    fn default() -> Self {
+        Self {
+            key: SortKey::Name,
+            ascending: true,
+        }
+    }
+}
+
+/// The parts of an entry the order looks at.
+#[derive(Debug, Clone, Copy)]
+pub struct SortFields<'a> {
+    pub name: &'a str,
+    pub is_dir: bool,
+    pub size: u64,
+    pub modified: Option<u64>,
+}
+
+impl SortOrder {
+    /// Reads the `sort_by` / `ascending` command parameters; missing ones default to
+    /// name, ascending.
+    pub fn parse(sort_by: Option<&str>, ascending: Option<bool>) -> Result<Self, CommandError> {
+        let key = match sort_by.unwrap_or("name") {
+            "name" => SortKey::Name,
+            "size" => SortKey::Size,
+            "modified" => SortKey::Modified,
+            "extension" => SortKey::Extension,
+            other => {
+                return Err(CommandError::invalid_argument(format!(
+                    "Unknown sort column: {}",
+                    other
+                )))
+            }
+        };
+        Ok(Self {
+            key,
+            ascending: ascending.unwrap_or(true),
+        })
+    }
+
+    /// Directories first, then the sort column, with equal values ordered by name.
+    /// Entries without a modification time sort as the oldest.
+    pub fn compare(&self, a: SortFields, b: SortFields) -> Ordering {
+        let by_key = match self.key {
+            SortKey::Name => Ordering::Equal,
+            SortKey::Size => a.size.cmp(&b.size),
+            SortKey::Modified => a.modified.cmp(&b.modified),
+            SortKey::Extension => natural_cmp_loose(extension(a.name), extension(b.name)),
+        };
+        let order = by_key.then_with(|| natural_cmp(a.name, b.name));
+        let order = if self.ascending { order } else { order.reverse() };
+        b.is_dir.cmp(&a.is_dir).then(order)
+    }
+}
+
+/// Text after the last dot, ignoring a leading one (`.bashrc` has none).
+fn extension(name: &str) -> &str {
+    match name.rfind('.') {
+        Some(i) if i > 0 => &name[i + 1..],
+        _ => "",
+    }
+}
+
+/// Compares names with digit runs by value and letters case-insensitively. Names that
+/// only differ in case or leading zeros still get a fixed order, by their raw text.
+pub fn natural_cmp(a: &str, b: &str) -> Ordering {
+    natural_cmp_loose(a, b).then_with(|| a.cmp(b))
+}
+
+/// [`natural_cmp`] without the raw-text tie-break.
+fn natural_cmp_loose(a: &str, b: &str) -> Ordering {
+    let (mut a_chars, mut b_chars) = (a.chars().peekable(), b.chars().peekable());
+    loop {
+        let (ca, cb) = match (a_chars.peek(), b_chars.peek()) {
+            (None, None) => return Ordering::Equal,
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(&ca), Some(&cb)) => (ca, cb),
+        };
+        let order = if ca.is_ascii_digit() && cb.is_ascii_digit() {
+            compare_numbers(&digit_run(&mut a_chars), &digit_run(&mut b_chars))
+        } else {
+            a_chars.next();
+            b_chars.next();
+            ca.to_lowercase().cmp(cb.to_lowercase())
+        };
+        if order != Ordering::Equal {
+            return order;
+        }
+    }
+}
+
+fn digit_run(chars: &mut Peekable<Chars>) -> String {
+    let mut run = String::new();
+    while let Some(c) = chars.next_if(char::is_ascii_digit) {
+        run.push(c);
+    }
+    run
+}
+
+/// Compares digit strings by value, without parsing, so any length works.
+fn compare_numbers(a: &str, b: &str) -> Ordering {
+    let (a, b) = (a.trim_start_matches('0'), b.trim_start_matches('0'));
+    a.len().cmp(&b.len()).then_with(|| a.cmp(b))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sorted(names: &[&str]) -> Vec<String> {
+        let mut names: Vec<String> = names.iter().map(|s| s.to_string()).collect();
+        names.sort_by(|a, b| natural_cmp(a, b));
+        names
+    }
+
+    #[test]
+    fn test_natural_numbers_and_case() {
+        assert_eq!(
+            sorted(&["file10.txt", "File2.txt", "file1.txt", "file01.txt", "file"]),
+            ["file", "file01.txt", "file1.txt", "File2.txt", "file10.txt"]
+        );
+        assert_eq!(sorted(&["b", "A", "a", "B"]), ["A", "a", "B", "b"]);
+        assert_eq!(
+            natural_cmp("v99999999999999999999999", "v100000000000000000000000"),
+            Ordering::Less
+        );
+        assert_eq!(natural_cmp("x", "x"), Ordering::Equal);
+    }
+
+    #[test]
+    fn test_natural_unicode() {
+        assert_eq!(
+            sorted(&["Épisode 10", "épisode 9", "Ärger", "ärger 2", "Zebra"]),
+            ["Zebra", "Ärger", "ärger 2", "épisode 9", "Épisode 10"]
+        );
+        // Only ASCII digits count as numbers
+        assert_eq!(sorted(&["٣", "10", "2"]), ["2", "10", "٣"]);
+    }
+
+    #[test]
+    fn test_order_keeps_dirs_first() {
+        let entries = [
+            ("b.txt", false, 5, Some(1)),
+            ("a.md", false, 9, None),
+            ("c.TXT", false, 5, Some(3)),
+            ("zdir", true, 0, Some(2)),
+            ("dir2", true, 0, Some(9)),
+        ];
+        let order_of = |sort_by: &str, ascending: bool| {
+            let order = SortOrder::parse(Some(sort_by), Some(ascending)).unwrap();
+            let mut entries = entries.to_vec();
+            entries.sort_by(|a, b| {
+                let fields = |e: &(&'static str, bool, u64, Option<u64>)| SortFields {
+                    name: e.0,
+                    is_dir: e.1,
+                    size: e.2,
+                    modified: e.3,
+                };
+                order.compare(fields(a), fields(b))
+            });
+            entries.iter().map(|e| e.0).collect::<Vec<_>>()
+        };
+        assert_eq!(order_of("name", true), ["dir2", "zdir", "a.md", "b.txt", "c.TXT"]);
+        assert_eq!(order_of("name", false), ["zdir", "dir2", "c.TXT", "b.txt", "a.md"]);
+        assert_eq!(order_of("size", false), ["zdir", "dir2", "a.md", "c.TXT", "b.txt"]);
+        assert_eq!(order_of("modified", true), ["zdir", "dir2", "a.md", "b.txt", "c.TXT"]);
+        assert_eq!(order_of("extension", true), ["dir2", "zdir", "a.md", "b.txt", "c.TXT"]);
+        assert_eq!(
+            SortOrder::parse(Some("colour"), None).unwrap_err().code(),
+            "invalidArgument"
+        );
+        assert_eq!(SortOrder::parse(None, None).unwrap(), SortOrder::default());
+    }
+}
@@ -0,0 +1,547 @@
+use std::fs::{self, File};
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::AtomicBool;
+use std::time::UNIX_EPOCH;
+
+use crate::ignore::IgnoreRules;
+use crate::model::{link_id, mode_bits, EntryKind, EntryMeta};
+use crate::scan::{self, KeyMode, ScanOptions, ScanProgress, ScanResult};
+
+/// One side of a comparison. The compare pipeline only talks to sources through this
+/// trait, so alternate backends (archives, remote hosts) can stand in for `std::fs`.
+/// Paths passed to `open` and `stat` are `/`-separated and relative to the source root;
+/// the empty path is the root itself.
+pub trait TreeSource: Send + Sync {
+    /// Human-readable location of the root, for messages.
+    fn root_label(&self) -> String;
+
+    /// Lists the whole tree, keyed the same way as `scan::scan_directory`.
+    ///
+    /// - `excluded_dirs`: directories (as seen under the root) to prune from the listing
+    /// - `options`: which entries to list and how to walk
+    fn scan(
+        &self,
+        ignore_rules: &IgnoreRules,
+        excluded_dirs: &[PathBuf],
+        options: &ScanOptions,
+        cancel_flag: &AtomicBool,
+        progress_callback: &dyn Fn(&ScanProgress),
+    ) -> Result<ScanResult, String>;
+
+    /// Opens a file for streaming reads.
+    fn open(&self, rel_path: &str) -> io::Result<Box<dyn Read + Send>>;
+
+    /// Metadata for a single entry. Symlinks are reported as links, except for the root.
+    fn stat(&self, rel_path: &str) -> io::Result<EntryMeta>;
+}
+
+/// The local filesystem, rooted at a directory.
+pub struct LocalSource {
+    root: PathBuf,
+}
+
+impl LocalSource {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+
+    fn resolve(&self, rel_path: &str) -> PathBuf {
+        if rel_path.is_empty() {
+            self.root.clone()
+        } else {
+            self.root.join(rel_path)
+        }
+    }
+}
+
+impl TreeSource for LocalSource {
+    fn root_label(&self) -> String {
+        self.root.to_string_lossy().to_string()
+    }
+
+    fn scan(
+        &self,
+        ignore_rules: &IgnoreRules,
+        excluded_dirs: &[PathBuf],
+        options: &ScanOptions,
+        cancel_flag: &AtomicBool,
+        progress_callback: &dyn Fn(&ScanProgress),
+    ) -> Result<ScanResult, String> {
+        scan::scan_directory_with_options(
+            &self.root,
+            ignore_rules,
+            excluded_dirs,
+            options,
+            cancel_flag,
+            progress_callback,
+        )
+    }
+
+    fn open(&self, rel_path: &str) -> io::Result<Box<dyn Read + Send>> {
+        Ok(Box::new(File::open(self.resolve(rel_path))?))
+    }
+
+    fn stat(&self, rel_path: &str) -> io::Result<EntryMeta> {
+        let path = self.resolve(rel_path);
+        // The root may legitimately be a symlink to a directory (e.g. /tmp on macOS)
+        let meta = if rel_path.is_empty() {
+            fs::metadata(&path)?
+        } else {
+            fs::symlink_metadata(&path)?
+        };
+        Ok(entry_meta(&path, &meta))
+    }
+}
+
+/// A single file standing in for a tree, so two files can be compared like two
+/// folders. It lists the file as its one entry, named `name` rather than after the file
+/// itself, so that two differently named files still pair up.
+pub struct FileSource {
+    path: PathBuf,
+    name: String,
+}
+
+impl FileSource {
+    pub fn new(path: impl Into<PathBuf>, name: impl Into<String>) -> Self {
+        Self {
+            path: path.into(),
+            name: name.into(),
+        }
+    }
+
+    fn check(&self, rel_path: &str) -> io::Result<()> {
+        if rel_path.is_empty() || rel_path == self.name {
+            Ok(())
+        } else {
+            Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("{} is the only entry", self.name),
+            ))
+        }
+    }
+}
+
+impl TreeSource for FileSource {
+    fn root_label(&self) -> String {
+        self.path.to_string_lossy().to_string()
+    }
+
+    fn scan(
+        &self,
+        _ignore_rules: &IgnoreRules,
+        _excluded_dirs: &[PathBuf],
+        _options: &ScanOptions,
+        _cancel_flag: &AtomicBool,
+        progress_callback: &dyn Fn(&ScanProgress),
+    ) -> Result<ScanResult, String> {
+        let meta = self
+            .stat("")
+            .map_err(|e| format!("Cannot read {}: {}", self.root_label(), e))?;
+        let (mut entries, mut originals, mut case_collisions) = Default::default();
+        let key_mode = KeyMode::default();
+        scan::insert_entry(
+            &mut entries,
+            &mut originals,
+            &mut case_collisions,
+            key_mode.key(&self.name),
+            self.name.clone(),
+            meta,
+        );
+        let result = ScanResult {
+            entries,
+            originals,
+            count: 1,
+            errors: Vec::new(),
+            excluded_dirs: Vec::new(),
+            depth_limited: Vec::new(),
+            // The one name is the same on both sides
+            case_sensitive: true,
+            case_collisions,
+            key_mode,
+        };
+        progress_callback(&ScanProgress {
+            entries: 1,
+            bytes: result.file_bytes(),
+            current_path: None,
+        });
+        Ok(result)
+    }
+
+    fn open(&self, rel_path: &str) -> io::Result<Box<dyn Read + Send>> {
+        self.check(rel_path)?;
+        Ok(Box::new(File::open(&self.path)?))
+    }
+
+    fn stat(&self, rel_path: &str) -> io::Result<EntryMeta> {
+        self.check(rel_path)?;
+        Ok(entry_meta(&self.path, &fs::metadata(&self.path)?))
+    }
+}
+
+/// The [`EntryMeta`] of the entry at `path`, whose metadata `meta` was read without
+/// following it if it is a link.
+pub fn entry_meta(path: &Path, meta: &fs::Metadata) -> EntryMeta {
+    let kind = EntryKind::of(&meta.file_type());
+    let symlink_target = if kind == EntryKind::Symlink {
+        fs::read_link(path)
+            .ok()
+            .map(|t| t.to_string_lossy().to_string())
+    } else {
+        None
+    };
+    EntryMeta {
+        kind,
+        size: meta.len(),
+        modified: meta
+            .modified()
+            .ok()
+            .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+            .map(|d| d.as_millis() as u64),
+        symlink_target,
+        mode: mode_bits(meta),
+        link: link_id(meta),
+    }
+}
+
+/// Returns a clear error if the source root is missing or not a directory.
+pub fn check_root(source: &dyn TreeSource) -> Result<(), String> {
+    match source.stat("") {
+        Ok(meta) if meta.kind == EntryKind::Dir => Ok(()),
+        Ok(_) => Err(format!("Not a directory: {}", source.root_label())),
+        Err(e) => Err(format!("Cannot read {}: {}", source.root_label(), e)),
+    }
+}
+
+/// In-memory tree used by tests to exercise the compare pipeline without touching disk.
+#[cfg(test)]
+pub mod memory {
+    use super::*;
+    use std::collections::{BTreeMap, HashMap};
+    use std::io::Cursor;
+    use std::path::Path;
+    use crate::scan::KeyMode;
+    use std::sync::atomic::Ordering;
+
+    #[derive(Clone)]
+    enum Node {
+        Dir,
+        File { data: Vec<u8>, modified: u64 },
+        Symlink(String),
+    }
+
+    /// Entries are added by relative path; parent directories are created implicitly.
+    #[derive(Clone, Default)]
+    pub struct MemorySource {
+        nodes: BTreeMap<String, Node>,
+    }
+
+    /// Virtual root that `excluded_dirs` are resolved against.
+    pub const MEMORY_ROOT: &str = "/memory";
+
+    impl MemorySource {
+        pub fn new() -> Self {
+            Self::default()
+        }
+
+        pub fn dir(mut self, rel_path: &str) -> Self {
+            self.add_parents(rel_path);
+            self.nodes.insert(rel_path.to_string(), Node::Dir);
+            self
+        }
+
+        pub fn file(self, rel_path: &str, data: &str) -> Self {
+            self.file_with_mtime(rel_path, data, 1000)
+        }
+
+        pub fn file_with_mtime(mut self, rel_path: &str, data: &str, modified: u64) -> Self {
+            self.add_parents(rel_path);
+            self.nodes.insert(
+                rel_path.to_string(),
+                Node::File {
+                    data: data.as_bytes().to_vec(),
+                    modified,
+                },
+            );
+            self
+        }
+
+        pub fn symlink(mut self, rel_path: &str, target: &str) -> Self {
+            self.add_parents(rel_path);
+            self.nodes
+                .insert(rel_path.to_string(), Node::Symlink(target.to_string()));
+            self
+        }
+
+        /// Writes the tree under `dir`, so the same fixture can drive `LocalSource`.
+        pub fn materialize(&self, dir: &Path) -> io::Result<()> {
+            fs::create_dir_all(dir)?;
+            for (rel_path, node) in &self.nodes {
+                let path = dir.join(rel_path);
+                match node {
+                    Node::Dir => fs::create_dir_all(&path)?,
+                    Node::File { data, .. } => fs::write(&path, data)?,
+                    #[cfg(unix)]
+                    Node::Symlink(target) => std::os::unix::fs::symlink(target, &path)?,
+                    #[cfg(not(unix))]
+                    Node::Symlink(_) => {}
+                }
+            }
+            Ok(())
+        }
+
+        fn add_parents(&mut self, rel_path: &str) {
+            let mut end = 0;
+            while let Some(pos) = rel_path[end..].find('/') {
+                end += pos;
+                self.nodes
+                    .entry(rel_path[..end].to_string())
+                    .or_insert(Node::Dir);
+                end += 1;
+            }
+        }
+
+        fn meta(node: &Node) -> EntryMeta {
+            match node {
+                Node::Dir => EntryMeta {
+                    kind: EntryKind::Dir,
+                    size: 0,
+                    modified: None,
+                    symlink_target: None,
+                    mode: None,
+                    link: None,
+                },
+                Node::File { data, modified } => EntryMeta {
+                    kind: EntryKind::File,
+                    size: data.len() as u64,
+                    modified: Some(*modified),
+                    symlink_target: None,
+                    mode: None,
+                    link: None,
+                },
+                Node::Symlink(target) => EntryMeta {
+                    kind: EntryKind::Symlink,
+                    size: target.len() as u64,
+                    modified: None,
+                    symlink_target: Some(target.clone()),
+                    mode: None,
+                    link: None,
+                },
+            }
+        }
+    }
+
+    impl TreeSource for MemorySource {
+        fn root_label(&self) -> String {
+            MEMORY_ROOT.to_string()
+        }
+
+        fn scan(
+            &self,
+            ignore_rules: &IgnoreRules,
+            excluded_dirs: &[PathBuf],
+            options: &ScanOptions,
+            cancel_flag: &AtomicBool,
+            progress_callback: &dyn Fn(&ScanProgress),
+        ) -> Result<ScanResult, String> {
+            let excluded: Vec<String> = excluded_dirs
+                .iter()
+                .filter_map(|p| p.strip_prefix(MEMORY_ROOT).ok())
+                .map(|p| p.to_string_lossy().to_string())
+                .collect();
+
+            let mut entries: HashMap<String, EntryMeta> = HashMap::new();
+            let mut originals: HashMap<String, String> = HashMap::new();
+            let mut pruned = Vec::new();
+            let mut depth_limited = Vec::new();
+            let mut case_collisions: HashMap<String, Vec<(String, EntryMeta)>> = HashMap::new();
+            for (rel_path, node) in &self.nodes {
+                if cancel_flag.load(Ordering::Relaxed) {
+                    return Err("Scan cancelled".to_string());
+                }
+                if let Some(dir) = excluded
+                    .iter()
+                    .find(|d| Path::new(rel_path).starts_with(d.as_str()))
+                {
+                    if dir == rel_path {
+                        pruned.push(rel_path.clone());
+                    }
+                    continue;
+                }
+                if ignore_rules.is_ignored(rel_path) {
+                    continue;
+                }
+                if options.skip_hidden && rel_path.split('/').any(scan::is_hidden) {
+                    continue;
+                }
+                let depth = rel_path.split('/').count();
+                match options.max_depth {
+                    Some(max) if depth > max => continue,
+                    Some(max) if depth == max && matches!(node, Node::Dir) => {
+                        depth_limited.push(rel_path.clone());
+                    }
+                    _ => {}
+                }
+                scan::insert_entry(
+                    &mut entries,
+                    &mut originals,
+                    &mut case_collisions,
+                    KeyMode::default().key(rel_path),
+                    rel_path.clone(),
+                    Self::meta(node),
+                );
+            }
+
+            let count = entries.len();
+            let result = ScanResult {
+                entries,
+                originals,
+                count,
+                errors: Vec::new(),
+                excluded_dirs: pruned,
+                depth_limited,
+                // Node names are exact keys, like a case-sensitive volume
+                case_sensitive: true,
+                case_collisions,
+                key_mode: KeyMode::default(),
+            };
+            progress_callback(&ScanProgress {
+                entries: count,
+                bytes: result.file_bytes(),
+                current_path: None,
+            });
+            Ok(result)
+        }
+
+        fn open(&self, rel_path: &str) -> io::Result<Box<dyn Read + Send>> {
+            match self.nodes.get(rel_path) {
+                Some(Node::File { data, .. }) => Ok(Box::new(Cursor::new(data.clone()))),
+                Some(_) => Err(io::Error::other(format!("Not a file: {}", rel_path))),
+                None => Err(io::Error::from(io::ErrorKind::NotFound)),
+            }
+        }
+
+        fn stat(&self, rel_path: &str) -> io::Result<EntryMeta> {
+            if rel_path.is_empty() {
+                return Ok(Self::meta(&Node::Dir));
+            }
+            self.nodes
+                .get(rel_path)
+                .map(Self::meta)
+                .ok_or_else(|| io::Error::from(io::ErrorKind::NotFound))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::memory::MemorySource;
+    use super::*;
+
+    #[test]
+    fn test_local_stat_and_open() {
+        let dir = std::env::temp_dir().join("sc_source_local");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("sub")).unwrap();
+        fs::write(dir.join("sub/a.txt"), "hello").unwrap();
+
+        let source = LocalSource::new(&dir);
+        assert!(check_root(&source).is_ok());
+        assert_eq!(source.stat("sub").unwrap().kind, EntryKind::Dir);
+        assert_eq!(source.stat("sub/a.txt").unwrap().size, 5);
+
+        let mut content = String::new();
+        source
+            .open("sub/a.txt")
+            .unwrap()
+            .read_to_string(&mut content)
+            .unwrap();
+        assert_eq!(content, "hello");
+
+        assert!(check_root(&LocalSource::new(dir.join("sub/a.txt"))).is_err());
+        assert!(check_root(&LocalSource::new(dir.join("missing"))).is_err());
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_file_sources_pair_up() {
+        let dir = std::env::temp_dir().join("sc_source_files");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("export-v1.csv"), "a,b\n1,2\n").unwrap();
+        fs::write(dir.join("export-v2.csv"), "a,b\n1,2\n3,4\n").unwrap();
+
+        let rules = IgnoreRules::new(&[]);
+        let cancel = AtomicBool::new(false);
+        let scan = |file: &str| {
+            let source = FileSource::new(dir.join(file), "export-v1.csv");
+            source.scan(&rules, &[], &ScanOptions::default(), &cancel, &|_| {})
+        };
+        let (left, right) = (scan("export-v1.csv").unwrap(), scan("export-v2.csv").unwrap());
+        let result = crate::compare::compare(
+            &left,
+            &right,
+            crate::model::CompareMode::Smart,
+            &cancel,
+        )
+        .unwrap();
+        assert_eq!((result.summary.total_left, result.summary.total_right), (1, 1));
+        assert_eq!(result.summary.meta_diff, 1);
+        assert_eq!(result.diffs[0].rel_path, "export-v1.csv");
+
+        let source = FileSource::new(dir.join("export-v2.csv"), "export-v1.csv");
+        let mut content = String::new();
+        source.open("export-v1.csv").unwrap().read_to_string(&mut content).unwrap();
+        assert_eq!(content.lines().count(), 3);
+        assert!(source.stat("other.csv").is_err());
+        assert!(scan("missing.csv").is_err());
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_memory_scan_matches_local_scan() {
+        let dir = std::env::temp_dir().join("sc_source_parity");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("Docs")).unwrap();
+        fs::write(dir.join("Docs/Readme.md"), "read me").unwrap();
+        fs::write(dir.join("top.txt"), "top").unwrap();
+        fs::write(dir.join(".DS_Store"), "").unwrap();
+        fs::create_dir_all(dir.join(".git")).unwrap();
+        fs::write(dir.join(".git/HEAD"), "ref").unwrap();
+
+        let memory = MemorySource::new()
+            .file("Docs/Readme.md", "read me")
+            .file("top.txt", "top")
+            .file(".DS_Store", "")
+            .file(".git/HEAD", "ref");
+
+        let rules = IgnoreRules::new(&[]);
+        let cancel = AtomicBool::new(false);
+        for skip_hidden in [false, true] {
+            let options = ScanOptions {
+                skip_hidden,
+                ..Default::default()
+            };
+            let local = LocalSource::new(&dir)
+                .scan(&rules, &[], &options, &cancel, &|_| {})
+                .unwrap();
+            let mem = memory.scan(&rules, &[], &options, &cancel, &|_| {}).unwrap();
+
+            let mut local_keys: Vec<_> = local.originals.values().collect();
+            let mut mem_keys: Vec<_> = mem.originals.values().collect();
+            local_keys.sort();
+            mem_keys.sort();
+            assert_eq!(local_keys, mem_keys);
+            assert_eq!(local.entries.len(), if skip_hidden { 3 } else { 5 });
+            for (key, meta) in &mem.entries {
+                assert_eq!(local.entries[key].kind, meta.kind);
+                if meta.kind == EntryKind::File {
+                    assert_eq!(local.entries[key].size, meta.size);
+                }
+            }
+        }
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,710 @@
+//! Applies compare results to the filesystem: makes one root match the other for a
+//! selected set of diffs, and keeps the stored diff list in step with what was done.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use serde::Serialize;
+
+use crate::error::CommandError;
+use crate::fileops::{self, CopyOptions};
+use crate::model::{CompareSummary, DiffItem, DiffKind};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SyncDirection {
+    LeftToRight,
+    RightToLeft,
+}
+
+impl SyncDirection {
+    pub fn parse(direction: &str) -> Result<Self, String> {
+        match direction {
+            "leftToRight" => Ok(Self::LeftToRight),
+            "rightToLeft" => Ok(Self::RightToLeft),
+            _ => Err(format!("Invalid sync direction: {}", direction)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SyncFailure {
+    pub rel_path: String,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SyncReport {
+    pub copied: usize,
+    pub deleted: usize,
+    /// Requested paths that needed no action (already the same, or extras kept)
+    pub skipped: usize,
+    pub failed: Vec<SyncFailure>,
+    pub cancelled: bool,
+    /// For a dry run, what would have been done; nothing was changed and the counts
+    /// above are zero
+    pub plan: Option<SyncPlan>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum PlannedAction {
+    Copy,
+    Delete,
+    Skip,
+}
+
+/// One requested path of a planned sync.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PlannedStep {
+    pub action: PlannedAction,
+    pub rel_path: String,
+    /// Entry copied from; `None` unless copying
+    pub source: Option<String>,
+    /// Entry written, deleted, or (when skipping) left alone
+    pub destination: String,
+    /// File data copied, or deleted from the destination
+    pub bytes: u64,
+    /// A copy's destination exists and would be replaced, with everything below it
+    pub conflict: bool,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SyncPlan {
+    pub steps: Vec<PlannedStep>,
+    pub copies: usize,
+    pub deletes: usize,
+    pub skips: usize,
+    /// Copies that would replace an existing entry
+    pub conflicts: usize,
+    pub bytes_to_copy: u64,
+    pub bytes_to_delete: u64,
+    /// Paths the real run would report as failed before changing anything
+    pub failed: Vec<SyncFailure>,
+}
+
+enum Action {
+    Copy,
+    Delete,
+    Skip,
+}
+
+/// Makes the destination root match the source root for each of `rel_paths`.
+///
+/// Entries missing on the destination or differing in metadata are copied over (with
+/// times and permissions), replacing whatever is there. Entries only on the destination
+/// are deleted when `delete_extras` is set. Every touched path is checked to stay inside
+/// its root. Successful items are reflected in `diffs` and `summary`; failures are
+/// collected and do not stop the run.
+///
+/// - `copy_options`: whether and how copied files are verified; metadata is always kept
+/// - `progress`: called before each item with (done, total, rel_path)
+#[allow(clippy::too_many_arguments)]
+pub fn sync_diffs(
+    left_root: &Path,
+    right_root: &Path,
+    direction: SyncDirection,
+    rel_paths: &[String],
+    delete_extras: bool,
+    diffs: &mut Vec<DiffItem>,
+    summary: &mut CompareSummary,
+    copy_options: &CopyOptions,
+    cancel_flag: &AtomicBool,
+    progress: &dyn Fn(usize, usize, &str),
+) -> SyncReport {
+    let (src_root, dest_root) = match direction {
+        SyncDirection::LeftToRight => (left_root, right_root),
+        SyncDirection::RightToLeft => (right_root, left_root),
+    };
+    let mut report = SyncReport::default();
+    let omitted = omitted_same(summary, diffs);
+    let copy_options = CopyOptions {
+        preserve_metadata: true,
+        ..*copy_options
+    };
+
+    for (done, rel_path) in rel_paths.iter().enumerate() {
+        if cancel_flag.load(Ordering::Relaxed) {
+            report.cancelled = true;
+            break;
+        }
+        progress(done, rel_paths.len(), rel_path);
+
+        let Some(diff) = diffs.iter().find(|d| d.rel_path == *rel_path) else {
+            report.failed.push(SyncFailure {
+                rel_path: rel_path.clone(),
+                message: "Not in the last comparison result".to_string(),
+            });
+            continue;
+        };
+
+        let result = match action_for(diff.diff_kind, direction, delete_extras) {
+            Action::Copy => copy_item(src_root, dest_root, rel_path, &copy_options).map(|_| {
+                report.copied += 1;
+                mark_synced(diffs, rel_path, direction);
+            }),
+            Action::Delete => delete_item(dest_root, rel_path).map(|_| {
+                report.deleted += 1;
+                remove_synced(diffs, rel_path);
+            }),
+            Action::Skip => {
+                report.skipped += 1;
+                Ok(())
+            }
+        };
+        if let Err(message) = result {
+            report.failed.push(SyncFailure {
+                rel_path: rel_path.clone(),
+                message,
+            });
+        }
+    }
+
+    if !report.cancelled {
+        progress(rel_paths.len(), rel_paths.len(), "");
+    }
+    summary.recount(diffs);
+    summary.same += omitted.same;
+    summary.total_left += omitted.total_left;
+    summary.total_right += omitted.total_right;
+    report
+}
+
+/// What [`sync_diffs`] would do for each of `rel_paths`, without changing anything.
+/// Every path goes through the same lookups and root-confinement checks as the real
+/// run, so a path that fails them lands in the plan's `failed` instead of its steps.
+pub fn plan_sync(
+    left_root: &Path,
+    right_root: &Path,
+    direction: SyncDirection,
+    rel_paths: &[String],
+    delete_extras: bool,
+    diffs: &[DiffItem],
+) -> SyncPlan {
+    let (src_root, dest_root) = match direction {
+        SyncDirection::LeftToRight => (left_root, right_root),
+        SyncDirection::RightToLeft => (right_root, left_root),
+    };
+    let mut plan = SyncPlan::default();
+
+    for rel_path in rel_paths {
+        let Some(diff) = diffs.iter().find(|d| d.rel_path == *rel_path) else {
+            plan.failed.push(SyncFailure {
+                rel_path: rel_path.clone(),
+                message: "Not in the last comparison result".to_string(),
+            });
+            continue;
+        };
+
+        let step = match action_for(diff.diff_kind, direction, delete_extras) {
+            Action::Copy => plan_copy(src_root, dest_root, rel_path),
+            Action::Delete => delete_target(dest_root, rel_path).map(|target| PlannedStep {
+                action: PlannedAction::Delete,
+                rel_path: rel_path.clone(),
+                source: None,
+                bytes: fileops::tree_bytes(&target),
+                destination: target.display().to_string(),
+                conflict: false,
+            }),
+            Action::Skip => Ok(PlannedStep {
+                action: PlannedAction::Skip,
+                rel_path: rel_path.clone(),
+                source: None,
+                destination: dest_root.join(rel_path).display().to_string(),
+                bytes: 0,
+                conflict: false,
+            }),
+        };
+        match step {
+            Ok(step) => {
+                match step.action {
+                    PlannedAction::Copy => {
+                        plan.copies += 1;
+                        plan.conflicts += step.conflict as usize;
+                        plan.bytes_to_copy += step.bytes;
+                    }
+                    PlannedAction::Delete => {
+                        plan.deletes += 1;
+                        plan.bytes_to_delete += step.bytes;
+                    }
+                    PlannedAction::Skip => plan.skips += 1,
+                }
+                plan.steps.push(step);
+            }
+            Err(message) => plan.failed.push(SyncFailure {
+                rel_path: rel_path.clone(),
+                message,
+            }),
+        }
+    }
+    plan
+}
+
+fn plan_copy(src_root: &Path, dest_root: &Path, rel_path: &str) -> Result<PlannedStep, String> {
+    let (src, dest) =
+        fileops::plan_rel_copy(src_root, dest_root, rel_path).map_err(|e| e.to_string())?;
+    Ok(PlannedStep {
+        action: PlannedAction::Copy,
+        rel_path: rel_path.to_string(),
+        bytes: fileops::tree_bytes(&src),
+        conflict: fs::symlink_metadata(&dest).is_ok(),
+        source: Some(src.display().to_string()),
+        destination: dest.display().to_string(),
+    })
+}
+
+fn action_for(kind: DiffKind, direction: SyncDirection, delete_extras: bool) -> Action {
+    let (missing_on_dest, extra_on_dest) = match direction {
+        SyncDirection::LeftToRight => (DiffKind::OnlyLeft, DiffKind::OnlyRight),
+        SyncDirection::RightToLeft => (DiffKind::OnlyRight, DiffKind::OnlyLeft),
+    };
+    if kind == missing_on_dest || kind == DiffKind::MetaDiff {
+        Action::Copy
+    } else if kind == extra_on_dest && delete_extras {
+        Action::Delete
+    } else {
+        Action::Skip
+    }
+}
+
+fn copy_item(
+    src_root: &Path,
+    dest_root: &Path,
+    rel_path: &str,
+    options: &CopyOptions,
+) -> Result<(), String> {
+    let outcome =
+        fileops::copy_rel_entry(src_root, dest_root, rel_path, options).map_err(|e| e.to_string())?;
+    // A partly copied folder still differs from its source, so the item has failed
+    match outcome.skipped.as_slice() {
+        [] => Ok(()),
+        [first, rest @ ..] => Err(format!(
+            "{} entries could not be copied, e.g. {}: {}",
+            rest.len() + 1,
+            first.path,
+            first.message
+        )),
+    }
+}
+
+fn delete_item(dest_root: &Path, rel_path: &str) -> Result<(), String> {
+    let target = delete_target(dest_root, rel_path)?;
+    fileops::delete_entry(&target).map_err(|e| e.to_string())
+}
+
+/// The confined path a delete of `rel_path` removes, which must exist.
+fn delete_target(dest_root: &Path, rel_path: &str) -> Result<PathBuf, String> {
+    let target = fileops::confined_parent(dest_root, rel_path, false)
+        .map_err(|e| e.to_string())?
+        .join(file_name(rel_path));
+    fs::symlink_metadata(&target)
+        .map_err(|e| CommandError::io("Delete failed", &target, &e).to_string())?;
+    Ok(target)
+}
+
+fn file_name(rel_path: &str) -> &str {
+    rel_path.rsplit('/').next().unwrap_or(rel_path)
+}
+
+pub(crate) fn is_within(rel_path: &str, item: &str) -> bool {
+    item == rel_path
+        || (item.len() > rel_path.len()
+            && item.starts_with(rel_path)
+            && item.as_bytes()[rel_path.len()] == b'/')
+}
+
+/// A copied entry (and everything below it) now matches its source.
+fn mark_synced(diffs: &mut Vec<DiffItem>, rel_path: &str, direction: SyncDirection) {
+    let source_meta = |diff: &DiffItem| match direction {
+        SyncDirection::LeftToRight => diff.left.clone(),
+        SyncDirection::RightToLeft => diff.right.clone(),
+    };
+    // Destination-only children were replaced along with their parent
+    diffs.retain(|d| !is_within(rel_path, &d.rel_path) || source_meta(d).is_some());
+    for diff in diffs
+        .iter_mut()
+        .filter(|d| is_within(rel_path, &d.rel_path))
+    {
+        let meta = source_meta(diff);
+        diff.left = meta.clone();
+        diff.right = meta;
+        diff.diff_kind = DiffKind::Same;
+        diff.error_message = None;
+        diff.newer = None;
+    }
+}
+
+/// A deleted entry (and everything below it) no longer exists on either side.
+fn remove_synced(diffs: &mut Vec<DiffItem>, rel_path: &str) {
+    diffs.retain(|d| !is_within(rel_path, &d.rel_path));
+}
+
+/// Same entries counted in `summary` but left out of `diffs` (see
+/// `CompareOptions::include_same`); they are unaffected by a sync, so they are added
+/// back after recounting.
+pub(crate) fn omitted_same(summary: &CompareSummary, diffs: &[DiffItem]) -> CompareSummary {
+    let listed = |f: fn(&DiffItem) -> bool| diffs.iter().filter(|d| f(d)).count();
+    CompareSummary {
+        same: summary
+            .same
+            .saturating_sub(listed(|d| d.diff_kind == DiffKind::Same)),
+        total_left: summary.total_left.saturating_sub(listed(|d| d.left.is_some())),
+        total_right: summary
+            .total_right
+            .saturating_sub(listed(|d| d.right.is_some())),
+        ..Default::default()
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compare::{compare, compare_with_options, CompareOptions};
+    use crate::ignore::IgnoreRules;
+    use crate::model::CompareMode;
+    use crate::scan::scan_directory;
+    use std::fs;
+    use std::path::PathBuf;
+
+    fn setup(name: &str) -> (PathBuf, PathBuf, PathBuf) {
+        let base = std::env::temp_dir().join(format!("sc_sync_{}", name));
+        let _ = fs::remove_dir_all(&base);
+        let left = base.join("left");
+        let right = base.join("right");
+        fs::create_dir_all(&left).unwrap();
+        fs::create_dir_all(&right).unwrap();
+        (base, left, right)
+    }
+
+    fn compare_roots(left: &Path, right: &Path) -> (Vec<DiffItem>, CompareSummary) {
+        let rules = IgnoreRules::new(&[]);
+        let cancel = AtomicBool::new(false);
+        let l = scan_directory(left, &rules, &[], &cancel, &|_| {}).unwrap();
+        let r = scan_directory(right, &rules, &[], &cancel, &|_| {}).unwrap();
+        let result = compare(&l, &r, CompareMode::Smart, &cancel).unwrap();
+        (result.diffs, result.summary)
+    }
+
+    fn run(
+        left: &Path,
+        right: &Path,
+        direction: SyncDirection,
+        rel_paths: &[&str],
+        delete_extras: bool,
+        diffs: &mut Vec<DiffItem>,
+        summary: &mut CompareSummary,
+    ) -> SyncReport {
+        let rel_paths: Vec<String> = rel_paths.iter().map(|p| p.to_string()).collect();
+        let cancel = AtomicBool::new(false);
+        sync_diffs(
+            left,
+            right,
+            direction,
+            &rel_paths,
+            delete_extras,
+            diffs,
+            summary,
+            &CopyOptions::default(),
+            &cancel,
+            &|_, _, _| {},
+        )
+    }
+
+    #[test]
+    fn test_sync_left_to_right() {
+        let (base, left, right) = setup("ltr");
+        fs::create_dir_all(left.join("new/deep")).unwrap();
+        fs::write(left.join("new/deep/a.txt"), "a").unwrap();
+        fs::write(left.join("changed.txt"), "longer").unwrap();
+        fs::write(right.join("changed.txt"), "short").unwrap();
+        fs::write(right.join("extra.txt"), "x").unwrap();
+
+        let (mut diffs, mut summary) = compare_roots(&left, &right);
+        let report = run(
+            &left,
+            &right,
+            SyncDirection::LeftToRight,
+            &["new", "changed.txt", "extra.txt"],
+            true,
+            &mut diffs,
+            &mut summary,
+        );
+
+        assert!(report.failed.is_empty(), "{:?}", report.failed);
+        assert_eq!(report.copied, 2);
+        assert_eq!(report.deleted, 1);
+        assert_eq!(
+            fs::read_to_string(right.join("new/deep/a.txt")).unwrap(),
+            "a"
+        );
+        assert_eq!(
+            fs::read_to_string(right.join("changed.txt")).unwrap(),
+            "longer"
+        );
+        assert!(!right.join("extra.txt").exists());
+
+        // Stored diffs now agree with a fresh compare
+        let (_, fresh) = compare_roots(&left, &right);
+        assert!(diffs.iter().all(|d| d.diff_kind == DiffKind::Same));
+        assert_eq!(summary.same, fresh.same);
+        assert_eq!(
+            summary.only_left + summary.only_right + summary.meta_diff,
+            0
+        );
+
+        let _ = fs::remove_dir_all(&base);
+    }
+
+    #[test]
+    fn test_plan_matches_real_run() {
+        let (base, left, right) = setup("plan");
+        fs::create_dir_all(left.join("new/deep")).unwrap();
+        fs::write(left.join("new/deep/a.txt"), "aaaa").unwrap();
+        fs::write(left.join("changed.txt"), "longer").unwrap();
+        fs::write(right.join("changed.txt"), "short").unwrap();
+        fs::write(right.join("extra.txt"), "xx").unwrap();
+        fs::write(left.join("gone.txt"), "g").unwrap();
+
+        let (mut diffs, mut summary) = compare_roots(&left, &right);
+        fs::remove_file(left.join("gone.txt")).unwrap();
+        let requested = ["new", "changed.txt", "extra.txt", "gone.txt", "../escape", "nope"];
+        let rel_paths: Vec<String> = requested.iter().map(|p| p.to_string()).collect();
+        let plan = plan_sync(
+            &left,
+            &right,
+            SyncDirection::LeftToRight,
+            &rel_paths,
+            true,
+            &diffs,
+        );
+
+        // Nothing touched by the dry run
+        assert!(!right.join("new").exists());
+        assert_eq!(fs::read_to_string(right.join("changed.txt")).unwrap(), "short");
+        assert!(right.join("extra.txt").exists());
+
+        let actions: Vec<_> = plan.steps.iter().map(|s| (s.rel_path.as_str(), s.action)).collect();
+        assert_eq!(
+            actions,
+            [
+                ("new", PlannedAction::Copy),
+                ("changed.txt", PlannedAction::Copy),
+                ("extra.txt", PlannedAction::Delete),
+            ]
+        );
+        assert_eq!((plan.copies, plan.deletes, plan.conflicts), (2, 1, 1));
+        assert_eq!((plan.bytes_to_copy, plan.bytes_to_delete), (10, 2));
+        assert!(plan.steps[1].conflict && !plan.steps[0].conflict);
+
+        let report = run(
+            &left,
+            &right,
+            SyncDirection::LeftToRight,
+            &requested,
+            true,
+            &mut diffs,
+            &mut summary,
+        );
+        assert_eq!(
+            (report.copied, report.deleted, report.skipped),
+            (plan.copies, plan.deletes, plan.skips)
+        );
+        let failed = |f: &[SyncFailure]| f.iter().map(|f| f.rel_path.clone()).collect::<Vec<_>>();
+        assert_eq!(failed(&report.failed), failed(&plan.failed));
+        for step in &plan.steps {
+            let exists = Path::new(&step.destination).exists();
+            assert_eq!(exists, step.action == PlannedAction::Copy, "{}", step.rel_path);
+        }
+
+        let _ = fs::remove_dir_all(&base);
+    }
+
+    #[test]
+    fn test_extras_kept_without_delete_flag() {
+        let (base, left, right) = setup("keep_extras");
+        fs::write(right.join("extra.txt"), "x").unwrap();
+
+        let (mut diffs, mut summary) = compare_roots(&left, &right);
+        let report = run(
+            &left,
+            &right,
+            SyncDirection::LeftToRight,
+            &["extra.txt"],
+            false,
+            &mut diffs,
+            &mut summary,
+        );
+
+        assert_eq!(report.skipped, 1);
+        assert!(right.join("extra.txt").exists());
+        assert_eq!(summary.only_right, 1);
+
+        let _ = fs::remove_dir_all(&base);
+    }
+
+    #[test]
+    fn test_sync_right_to_left() {
+        let (base, left, right) = setup("rtl");
+        fs::write(right.join("r.txt"), "r").unwrap();
+        fs::write(left.join("l.txt"), "l").unwrap();
+
+        let (mut diffs, mut summary) = compare_roots(&left, &right);
+        let report = run(
+            &left,
+            &right,
+            SyncDirection::RightToLeft,
+            &["r.txt", "l.txt"],
+            true,
+            &mut diffs,
+            &mut summary,
+        );
+
+        assert_eq!((report.copied, report.deleted), (1, 1));
+        assert!(left.join("r.txt").exists());
+        assert!(!left.join("l.txt").exists());
+        assert_eq!(summary.same, 1);
+
+        let _ = fs::remove_dir_all(&base);
+    }
+
+    #[test]
+    fn test_failures_are_collected() {
+        let (base, left, right) = setup("failures");
+        fs::write(left.join("ok.txt"), "ok").unwrap();
+        fs::write(left.join("gone.txt"), "gone").unwrap();
+
+        let (mut diffs, mut summary) = compare_roots(&left, &right);
+        fs::remove_file(left.join("gone.txt")).unwrap();
+        let report = run(
+            &left,
+            &right,
+            SyncDirection::LeftToRight,
+            &["gone.txt", "../escape", "missing.txt", "ok.txt"],
+            false,
+            &mut diffs,
+            &mut summary,
+        );
+
+        assert_eq!(report.copied, 1);
+        assert_eq!(report.failed.len(), 3);
+        assert!(right.join("ok.txt").exists());
+        assert_eq!(summary.only_left, 1);
+
+        let _ = fs::remove_dir_all(&base);
+    }
+
+    #[test]
+    fn test_failed_verification_is_a_failure_not_a_skip() {
+        let (base, left, right) = setup("verify_failure");
+        fs::create_dir_all(left.join("photos")).unwrap();
+        fs::write(left.join("photos/a.raw"), "a").unwrap();
+        fs::write(left.join("photos/b.raw"), "b").unwrap();
+
+        let (mut diffs, mut summary) = compare_roots(&left, &right);
+        // Stands in for a disk that writes something other than what it was given
+        let corrupt = |path: &Path| {
+            if path.ends_with("b.raw") {
+                fs::write(path, "damaged!").unwrap();
+            }
+        };
+        let options = CopyOptions {
+            verify: true,
+            on_verify: Some(&corrupt),
+            fail_fast: false,
+            ..CopyOptions::default()
+        };
+        let report = sync_diffs(
+            &left,
+            &right,
+            SyncDirection::LeftToRight,
+            &["photos".to_string()],
+            false,
+            &mut diffs,
+            &mut summary,
+            &options,
+            &AtomicBool::new(false),
+            &|_, _, _| {},
+        );
+        let _ = fs::remove_dir_all(&base);
+
+        assert_eq!((report.copied, report.skipped), (0, 0));
+        assert_eq!(report.failed.len(), 1);
+        assert_eq!(report.failed[0].rel_path, "photos");
+        assert!(report.failed[0].message.starts_with("Verification failed"), "{:?}", report);
+        assert_eq!(summary.only_left, 3);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_rejects_parent_symlink_escaping_root() {
+        let (base, left, right) = setup("escape");
+        let outside = base.join("outside");
+        fs::create_dir_all(&outside).unwrap();
+        fs::create_dir_all(left.join("link")).unwrap();
+        fs::write(left.join("link/file.txt"), "data").unwrap();
+        // On the destination, the same directory name leads out of the root
+        std::os::unix::fs::symlink(&outside, right.join("link")).unwrap();
+
+        let (mut diffs, mut summary) = compare_roots(&left, &right);
+        let report = run(
+            &left,
+            &right,
+            SyncDirection::LeftToRight,
+            &["link/file.txt"],
+            false,
+            &mut diffs,
+            &mut summary,
+        );
+
+        assert_eq!(report.copied, 0);
+        assert_eq!(report.failed.len(), 1);
+        assert!(!outside.join("file.txt").exists());
+
+        let _ = fs::remove_dir_all(&base);
+    }
+
+    #[test]
+    fn test_recount_keeps_omitted_same_entries() {
+        let (base, left, right) = setup("omitted_same");
+        for name in ["a.txt", "b.txt", "c.txt"] {
+            fs::write(left.join(name), name).unwrap();
+            fs::write(right.join(name), name).unwrap();
+        }
+        fs::write(left.join("new.txt"), "new").unwrap();
+
+        let rules = IgnoreRules::new(&[]);
+        let cancel = AtomicBool::new(false);
+        let l = scan_directory(&left, &rules, &[], &cancel, &|_| {}).unwrap();
+        let r = scan_directory(&right, &rules, &[], &cancel, &|_| {}).unwrap();
+        let options = CompareOptions {
+            include_same: false,
+            ..Default::default()
+        };
+        let result = compare_with_options(&l, &r, CompareMode::Smart, &options, &cancel).unwrap();
+        let (mut diffs, mut summary) = (result.diffs, result.summary);
+        assert_eq!(diffs.len(), 1);
+
+        let report = run(
+            &left,
+            &right,
+            SyncDirection::LeftToRight,
+            &["new.txt"],
+            false,
+            &mut diffs,
+            &mut summary,
+        );
+
+        assert_eq!(report.copied, 1);
+        let (_, fresh) = compare_roots(&left, &right);
+        assert_eq!(summary.same, fresh.same);
+        assert_eq!(summary.total_left, fresh.total_left);
+        assert_eq!(summary.total_right, fresh.total_right);
+
+        let _ = fs::remove_dir_all(&base);
+    }
+}
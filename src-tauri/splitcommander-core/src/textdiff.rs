@@ -0,0 +1,304 @@
+//! Line-level diff of one file present under both compare roots, for the preview panel
+//! in the diff view.
+
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+
+use serde::Serialize;
+use similar::{Algorithm, ChangeTag, TextDiff};
+
+use crate::error::CommandError;
+use crate::fileops::confined_parent;
+use crate::search::BINARY_SNIFF_LEN;
+
+/// Files larger than this are not diffed unless the caller says otherwise.
+pub const DEFAULT_DIFF_MAX_FILE_SIZE: u64 = 8 * 1024 * 1024;
+
+/// Diff lines returned before the remaining hunks are cut to their headers.
+pub const DEFAULT_DIFF_MAX_LINES: usize = 5000;
+
+#[derive(Debug, Clone, Copy)]
+pub struct DiffOptions {
+    /// Either file larger than this is reported as `too_large` without being read
+    pub max_file_size: u64,
+    /// Once the hunks hold this many lines, later hunks come with headers only
+    pub max_lines: usize,
+    /// Unchanged lines shown around each change
+    pub context_lines: usize,
+}
+
+impl Default for DiffOptions {
+    fn default() -> Self {
+        Self {
+            max_file_size: DEFAULT_DIFF_MAX_FILE_SIZE,
+            max_lines: DEFAULT_DIFF_MAX_LINES,
+            context_lines: 3,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum DiffLineKind {
+    Context,
+    Added,
+    Removed,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DiffLine {
+    pub kind: DiffLineKind,
+    /// 1-based line number on the left; `None` for added lines
+    pub old_line: Option<usize>,
+    /// 1-based line number on the right; `None` for removed lines
+    pub new_line: Option<usize>,
+    /// The line without its line break
+    pub text: String,
+}
+
+/// A run of changes with its context, like a `@@ -a,b +c,d @@` block.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DiffHunk {
+    /// 1-based first line of the hunk on the left (where it would be, if it has none)
+    pub old_start: usize,
+    pub old_lines: usize,
+    pub new_start: usize,
+    pub new_lines: usize,
+    pub added: usize,
+    pub removed: usize,
+    /// Empty for hunks past `max_lines`
+    pub lines: Vec<DiffLine>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FileDiff {
+    /// Either side looks binary; no hunks
+    pub is_binary: bool,
+    /// Either side is over `max_file_size`; no hunks
+    pub too_large: bool,
+    /// No hunks means the contents are identical
+    pub hunks: Vec<DiffHunk>,
+    /// Some hunks were cut to their headers by `max_lines`
+    pub truncated: bool,
+    pub left_size: u64,
+    pub right_size: u64,
+}
+
+/// Diffs `rel_path` under `left_root` against the same path under `right_root`, line by
+/// line with the Myers algorithm. A NUL byte among a file's first 8 KiB marks it
+/// binary; text that is not UTF-8 is compared with invalid sequences replaced.
+pub fn diff_rel_file(
+    left_root: &Path,
+    right_root: &Path,
+    rel_path: &str,
+    options: &DiffOptions,
+) -> Result<FileDiff, CommandError> {
+    let left_path = side_path(left_root, rel_path)?;
+    let right_path = side_path(right_root, rel_path)?;
+    diff_files(&left_path, &right_path, options)
+}
+
+/// Diffs two files the way [`diff_rel_file`] does, wherever they are.
+pub fn diff_files(
+    left_path: &Path,
+    right_path: &Path,
+    options: &DiffOptions,
+) -> Result<FileDiff, CommandError> {
+    let left_size = file_size(left_path)?;
+    let right_size = file_size(right_path)?;
+    let mut diff = FileDiff {
+        left_size,
+        right_size,
+        ..FileDiff::default()
+    };
+    if left_size > options.max_file_size || right_size > options.max_file_size {
+        diff.too_large = true;
+        return Ok(diff);
+    }
+
+    let left = read_all(left_path)?;
+    let right = read_all(right_path)?;
+    let sniff = |bytes: &[u8]| bytes[..bytes.len().min(BINARY_SNIFF_LEN as usize)].contains(&0);
+    if sniff(&left) || sniff(&right) {
+        diff.is_binary = true;
+        return Ok(diff);
+    }
+
+    let (left, right) = (String::from_utf8_lossy(&left), String::from_utf8_lossy(&right));
+    (diff.hunks, diff.truncated) = line_hunks(&left, &right, options);
+    Ok(diff)
+}
+
+fn line_hunks(left: &str, right: &str, options: &DiffOptions) -> (Vec<DiffHunk>, bool) {
+    let text_diff = TextDiff::configure()
+        .algorithm(Algorithm::Myers)
+        .diff_lines(left, right);
+    let mut hunks = Vec::new();
+    let mut lines_left = options.max_lines;
+    let mut truncated = false;
+
+    for group in text_diff.grouped_ops(options.context_lines) {
+        let (Some(first), Some(last)) = (group.first(), group.last()) else {
+            continue;
+        };
+        let mut hunk = DiffHunk {
+            old_start: first.old_range().start + 1,
+            old_lines: last.old_range().end - first.old_range().start,
+            new_start: first.new_range().start + 1,
+            new_lines: last.new_range().end - first.new_range().start,
+            added: 0,
+            removed: 0,
+            lines: Vec::new(),
+        };
+        let mut lines = Vec::new();
+        for change in group.iter().flat_map(|op| text_diff.iter_changes(op)) {
+            let kind = match change.tag() {
+                ChangeTag::Equal => DiffLineKind::Context,
+                ChangeTag::Insert => DiffLineKind::Added,
+                ChangeTag::Delete => DiffLineKind::Removed,
+            };
+            match kind {
+                DiffLineKind::Added => hunk.added += 1,
+                DiffLineKind::Removed => hunk.removed += 1,
+                DiffLineKind::Context => {}
+            }
+            lines.push(DiffLine {
+                kind,
+                old_line: change.old_index().map(|i| i + 1),
+                new_line: change.new_index().map(|i| i + 1),
+                text: change.value().trim_end_matches(['\n', '\r']).to_string(),
+            });
+        }
+
+        if lines.len() <= lines_left {
+            lines_left -= lines.len();
+            hunk.lines = lines;
+        } else {
+            lines_left = 0;
+            truncated = true;
+        }
+        hunks.push(hunk);
+    }
+    (hunks, truncated)
+}
+
+fn side_path(root: &Path, rel_path: &str) -> Result<std::path::PathBuf, CommandError> {
+    let name = rel_path.rsplit('/').next().unwrap_or(rel_path);
+    Ok(confined_parent(root, rel_path, false)?.join(name))
+}
+
+fn file_size(path: &Path) -> Result<u64, CommandError> {
+    let meta = std::fs::metadata(path)
+        .map_err(|e| CommandError::io(format!("Cannot read {}", path.display()), path, &e))?;
+    if !meta.is_file() {
+        return Err(CommandError::InvalidArgument {
+            message: format!("Not a file: {}", path.display()),
+            path: Some(path.display().to_string()),
+        });
+    }
+    Ok(meta.len())
+}
+
+fn read_all(path: &Path) -> Result<Vec<u8>, CommandError> {
+    let mut bytes = Vec::new();
+    File::open(path)
+        .and_then(|mut f| f.read_to_end(&mut bytes))
+        .map_err(|e| CommandError::io(format!("Cannot read {}", path.display()), path, &e))?;
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn roots(name: &str, left: &[u8], right: &[u8]) -> std::path::PathBuf {
+        let base = std::env::temp_dir().join(name);
+        let _ = std::fs::remove_dir_all(&base);
+        std::fs::create_dir_all(base.join("l/conf")).unwrap();
+        std::fs::create_dir_all(base.join("r/conf")).unwrap();
+        std::fs::write(base.join("l/conf/config.yaml"), left).unwrap();
+        std::fs::write(base.join("r/conf/config.yaml"), right).unwrap();
+        base
+    }
+
+    fn diff(base: &Path, options: &DiffOptions) -> FileDiff {
+        diff_rel_file(&base.join("l"), &base.join("r"), "conf/config.yaml", options).unwrap()
+    }
+
+    #[test]
+    fn test_changed_line_in_hunk_with_context() {
+        let left = "a\nb\nc\nd\ne\nf\ng\nh\n";
+        let right = "a\nb\nc\nd\nE\nf\ng\nh\n";
+        let base = roots("sc_textdiff_hunk", left.as_bytes(), right.as_bytes());
+        let result = diff(&base, &DiffOptions::default());
+        let _ = std::fs::remove_dir_all(&base);
+
+        assert_eq!(result.hunks.len(), 1);
+        let hunk = &result.hunks[0];
+        assert_eq!((hunk.old_start, hunk.old_lines, hunk.new_start, hunk.new_lines), (2, 7, 2, 7));
+        assert_eq!((hunk.added, hunk.removed), (1, 1));
+        let changed: Vec<_> = hunk
+            .lines
+            .iter()
+            .filter(|l| l.kind != DiffLineKind::Context)
+            .map(|l| (l.kind, l.old_line, l.new_line, l.text.as_str()))
+            .collect();
+        assert_eq!(
+            changed,
+            vec![
+                (DiffLineKind::Removed, Some(5), None, "e"),
+                (DiffLineKind::Added, None, Some(5), "E"),
+            ]
+        );
+        assert!(!result.truncated);
+    }
+
+    #[test]
+    fn test_hunks_past_line_limit_keep_headers_only() {
+        let left: String = (0..100).map(|i| format!("{}\n", i)).collect();
+        let right = left.replace("10\n", "ten\n").replace("80\n", "eighty\n");
+        let base = roots("sc_textdiff_limit", left.as_bytes(), right.as_bytes());
+        let options = DiffOptions {
+            max_lines: 10,
+            ..DiffOptions::default()
+        };
+        let result = diff(&base, &options);
+        let _ = std::fs::remove_dir_all(&base);
+
+        assert_eq!(result.hunks.len(), 2);
+        assert_eq!(result.hunks[0].lines.len(), 8);
+        assert!(result.hunks[1].lines.is_empty());
+        assert_eq!((result.hunks[1].added, result.hunks[1].removed), (1, 1));
+        assert!(result.truncated);
+    }
+
+    #[test]
+    fn test_binary_and_oversized_files_flagged() {
+        let base = roots("sc_textdiff_binary", b"a\0b", b"a\0c");
+        let binary = diff(&base, &DiffOptions::default());
+        let options = DiffOptions {
+            max_file_size: 2,
+            ..DiffOptions::default()
+        };
+        let large = diff(&base, &options);
+        let _ = std::fs::remove_dir_all(&base);
+
+        assert!(binary.is_binary && binary.hunks.is_empty());
+        assert!(large.too_large && !large.is_binary);
+        assert_eq!((large.left_size, large.right_size), (3, 3));
+    }
+
+    #[test]
+    fn test_traversal_rejected() {
+        let base = roots("sc_textdiff_escape", b"a", b"b");
+        let (left, right) = (base.join("l"), base.join("r"));
+        let result = diff_rel_file(&left, &right, "../r/conf/config.yaml", &DiffOptions::default());
+        let _ = std::fs::remove_dir_all(&base);
+
+        assert!(result.is_err());
+    }
+}
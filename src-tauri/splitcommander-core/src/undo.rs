@@ -0,0 +1,420 @@
+//! Journal of recent reversible file operations, so the last one can be undone.
+//!
+//! Moves and renames are undone by putting the entry back, which never destroys
+//! anything. Undoing a copy or a created file deletes it, so those entries also keep a
+//! stamp of what was created (entry count, bytes, latest modification time); if it no
+//! longer matches, the entry was changed since and the undo is refused. A created
+//! folder is only removed while it is still empty. Deletes are permanent and never
+//! journaled. The journal lives in memory and is gone after a restart.
+
+use std::collections::VecDeque;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::Serialize;
+
+use crate::error::CommandError;
+use crate::fileops;
+
+/// Most operations the journal remembers; older ones drop off.
+pub const UNDO_JOURNAL_CAPACITY: usize = 50;
+
+/// A reversible operation, by where things were and where they ended up.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "kind", rename_all = "camelCase")]
+pub enum UndoAction {
+    /// Moved to another folder, possibly under a new name
+    Move {
+        from: PathBuf,
+        to: PathBuf,
+    },
+    /// Renamed within its folder
+    Rename {
+        from: PathBuf,
+        to: PathBuf,
+    },
+    /// Copied to `dest`, where nothing was before
+    Copy {
+        dest: PathBuf,
+    },
+    CreateDirectory {
+        path: PathBuf,
+    },
+    CreateFile {
+        path: PathBuf,
+    },
+}
+
+impl UndoAction {
+    /// Every path undoing this touches.
+    pub fn paths(&self) -> Vec<&Path> {
+        match self {
+            Self::Move { from, to } | Self::Rename { from, to } => vec![from, to],
+            Self::Copy { dest: path }
+            | Self::CreateDirectory { path }
+            | Self::CreateFile { path } => vec![path],
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UndoEntry {
+    pub id: u64,
+    #[serde(flatten)]
+    pub action: UndoAction,
+    /// Epoch milliseconds
+    pub recorded_at: u64,
+    /// What a copy or created file looked like when recorded
+    #[serde(skip)]
+    stamp: Option<TreeStamp>,
+}
+
+/// Enough of a tree to notice a change: added or removed entries bump a folder's
+/// modification time, and rewritten files their own.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct TreeStamp {
+    entries: u64,
+    bytes: u64,
+    latest_modified: Option<SystemTime>,
+}
+
+impl TreeStamp {
+    /// Walks `path` without following links.
+    fn of(path: &Path) -> io::Result<Self> {
+        let mut stamp = TreeStamp {
+            entries: 0,
+            bytes: 0,
+            latest_modified: None,
+        };
+        stamp.add(path)?;
+        Ok(stamp)
+    }
+
+    fn add(&mut self, path: &Path) -> io::Result<()> {
+        let meta = fs::symlink_metadata(path)?;
+        self.entries += 1;
+        if meta.is_file() {
+            self.bytes += meta.len();
+        }
+        if let Ok(modified) = meta.modified() {
+            self.latest_modified = self.latest_modified.max(Some(modified));
+        }
+        if meta.is_dir() {
+            for child in fs::read_dir(path)? {
+                self.add(&child?.path())?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The last [`UNDO_JOURNAL_CAPACITY`] operations, newest last.
+#[derive(Debug, Default)]
+pub struct UndoJournal {
+    entries: VecDeque<UndoEntry>,
+    next_id: u64,
+    /// The entry an undo is running for, claimed by [`UndoJournal::claim_last`]
+    undoing: Option<u64>,
+}
+
+impl UndoJournal {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds an operation that just completed. Copies and created files are stamped
+    /// now, so a copy of a large folder takes a walk of it; one that cannot be read is
+    /// not recorded, since it could never be undone safely.
+    pub fn record(&mut self, action: UndoAction) {
+        let stamp = match &action {
+            UndoAction::Copy { dest: path } | UndoAction::CreateFile { path } => {
+                match TreeStamp::of(path) {
+                    Ok(stamp) => Some(stamp),
+                    Err(_) => return,
+                }
+            }
+            _ => None,
+        };
+        self.next_id += 1;
+        let recorded_at = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis() as u64)
+            .unwrap_or(0);
+        self.entries.push_back(UndoEntry {
+            id: self.next_id,
+            action,
+            recorded_at,
+            stamp,
+        });
+        if self.entries.len() > UNDO_JOURNAL_CAPACITY {
+            self.entries.pop_front();
+        }
+    }
+
+    /// Recorded operations, newest first.
+    pub fn entries(&self) -> Vec<UndoEntry> {
+        self.entries.iter().rev().cloned().collect()
+    }
+
+    pub fn last(&self) -> Option<&UndoEntry> {
+        self.entries.back()
+    }
+
+    pub fn remove(&mut self, id: u64) {
+        self.entries.retain(|entry| entry.id != id);
+    }
+
+    /// The newest entry, claimed for an undo until [`UndoJournal::release`]. Fails with
+    /// `busy` while another undo runs, so two undos never run the same entry.
+    pub fn claim_last(&mut self) -> Result<UndoEntry, CommandError> {
+        if let Some(id) = self.undoing {
+            return Err(CommandError::Busy {
+                message: format!("Operation {} is being undone", id),
+                path: None,
+            });
+        }
+        let entry = self.entries.back().cloned().ok_or("Nothing to undo")?;
+        self.undoing = Some(entry.id);
+        Ok(entry)
+    }
+
+    /// Ends the undo of a claimed entry. It leaves the journal only when it was
+    /// `undone`; after a failed undo it stays, to be tried again.
+    pub fn release(&mut self, id: u64, undone: bool) {
+        if self.undoing == Some(id) {
+            self.undoing = None;
+        }
+        if undone {
+            self.remove(id);
+        }
+    }
+}
+
+/// Reverses a recorded operation, refusing when that would overwrite or delete
+/// anything that was not there, or not like that, right after it.
+pub fn undo(entry: &UndoEntry) -> Result<(), CommandError> {
+    match &entry.action {
+        UndoAction::Move { from, to } => {
+            require_present(to)?;
+            fileops::move_to(to, from).map(|_| ())
+        }
+        UndoAction::Rename { from, to } => {
+            require_present(to)?;
+            let name = from
+                .file_name()
+                .ok_or_else(|| CommandError::invalid_argument("Invalid path"))?;
+            fileops::rename_entry(to, &name.to_string_lossy()).map(|_| ())
+        }
+        UndoAction::Copy { dest: path } | UndoAction::CreateFile { path } => {
+            require_present(path)?;
+            let now = TreeStamp::of(path).map_err(|e| unreadable(path, &e))?;
+            if Some(now) != entry.stamp {
+                return Err(CommandError::invalid_argument(format!(
+                    "{} was changed since; not deleting it",
+                    path.display()
+                )));
+            }
+            fileops::delete_entry(path)
+        }
+        UndoAction::CreateDirectory { path } => {
+            require_present(path)?;
+            let empty = fs::read_dir(path)
+                .map(|mut children| children.next().is_none())
+                .map_err(|e| unreadable(path, &e))?;
+            if !empty {
+                return Err(CommandError::invalid_argument(format!(
+                    "{} is no longer empty; not deleting it",
+                    path.display()
+                )));
+            }
+            fs::remove_dir(path).map_err(|e| CommandError::io("Delete failed", path, &e))
+        }
+    }
+}
+
+fn unreadable(path: &Path, e: &io::Error) -> CommandError {
+    CommandError::io(format!("Cannot read {}", path.display()), path, e)
+}
+
+fn require_present(path: &Path) -> Result<(), CommandError> {
+    fs::symlink_metadata(path)
+        .map(|_| ())
+        .map_err(|e| CommandError::io(format!("{} is gone", path.display()), path, &e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("sc_undo_{}", name));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn undo_last(journal: &mut UndoJournal) -> Result<(), CommandError> {
+        let entry = journal.last().cloned().unwrap();
+        journal.remove(entry.id);
+        undo(&entry)
+    }
+
+    #[test]
+    fn test_move_and_rename_are_put_back() {
+        let dir = test_dir("move");
+        fs::create_dir_all(dir.join("a/photos")).unwrap();
+        fs::create_dir_all(dir.join("b")).unwrap();
+        fs::write(dir.join("a/photos/1.jpg"), "jpg").unwrap();
+        let mut journal = UndoJournal::new();
+
+        let moved = fileops::move_entry(&dir.join("a/photos"), &dir.join("b")).unwrap();
+        journal.record(UndoAction::Move {
+            from: dir.join("a/photos"),
+            to: moved,
+        });
+        let renamed = fileops::rename_entry(&dir.join("b/photos"), "pics").unwrap();
+        journal.record(UndoAction::Rename {
+            from: dir.join("b/photos"),
+            to: renamed,
+        });
+        assert_eq!(journal.entries().len(), 2);
+
+        undo_last(&mut journal).unwrap();
+        assert!(dir.join("b/photos").is_dir());
+        undo_last(&mut journal).unwrap();
+        assert_eq!(
+            fs::read_to_string(dir.join("a/photos/1.jpg")).unwrap(),
+            "jpg"
+        );
+        assert!(!dir.join("b/photos").exists());
+        assert!(journal.entries().is_empty());
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_move_back_refused_when_origin_taken() {
+        let dir = test_dir("move_taken");
+        fs::create_dir_all(dir.join("b")).unwrap();
+        fs::write(dir.join("a.txt"), "moved").unwrap();
+        let mut journal = UndoJournal::new();
+        let to = fileops::move_entry(&dir.join("a.txt"), &dir.join("b")).unwrap();
+        journal.record(UndoAction::Move {
+            from: dir.join("a.txt"),
+            to,
+        });
+        fs::write(dir.join("a.txt"), "newer").unwrap();
+
+        assert_eq!(undo_last(&mut journal).unwrap_err().code(), "alreadyExists");
+        assert_eq!(fs::read_to_string(dir.join("a.txt")).unwrap(), "newer");
+        assert!(dir.join("b/a.txt").exists());
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_changed_copy_and_file_are_kept() {
+        let dir = test_dir("copy");
+        fs::create_dir_all(dir.join("src/inner")).unwrap();
+        fs::write(dir.join("src/inner/a.txt"), "a").unwrap();
+        fs::create_dir_all(dir.join("dest")).unwrap();
+        let mut journal = UndoJournal::new();
+
+        let outcome =
+            fileops::copy_entry(&dir.join("src"), &dir.join("dest"), &Default::default()).unwrap();
+        journal.record(UndoAction::Copy { dest: outcome.dest });
+        let file = fileops::create_file(&dir, "notes.txt", None).unwrap();
+        journal.record(UndoAction::CreateFile { path: file.clone() });
+
+        fs::write(&file, "edited since").unwrap();
+        assert_eq!(
+            undo_last(&mut journal).unwrap_err().code(),
+            "invalidArgument"
+        );
+        assert!(file.exists());
+
+        // An untouched copy is deleted
+        undo_last(&mut journal).unwrap();
+        assert!(!dir.join("dest/src").exists());
+        assert!(dir.join("src/inner/a.txt").exists());
+
+        let copy = fileops::copy_entry(&dir.join("src"), &dir.join("dest"), &Default::default());
+        journal.record(UndoAction::Copy {
+            dest: copy.unwrap().dest,
+        });
+        fs::write(dir.join("dest/src/inner/b.txt"), "b").unwrap();
+        assert!(undo_last(&mut journal).is_err());
+        assert!(dir.join("dest/src/inner/b.txt").exists());
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_created_folder_removed_only_while_empty() {
+        let dir = test_dir("mkdir");
+        let mut journal = UndoJournal::new();
+        for name in ["kept", "empty"] {
+            let path = fileops::create_directory(&dir, name).unwrap();
+            journal.record(UndoAction::CreateDirectory { path });
+        }
+        fs::write(dir.join("kept/x.txt"), "x").unwrap();
+
+        undo_last(&mut journal).unwrap();
+        assert!(!dir.join("empty").exists());
+        assert_eq!(
+            undo_last(&mut journal).unwrap_err().code(),
+            "invalidArgument"
+        );
+        assert!(dir.join("kept/x.txt").exists());
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_claimed_entry_leaves_only_when_undone() {
+        let mut journal = UndoJournal::new();
+        journal.record(UndoAction::CreateDirectory {
+            path: PathBuf::from("/first"),
+        });
+        journal.record(UndoAction::CreateDirectory {
+            path: PathBuf::from("/second"),
+        });
+
+        let claimed = journal.claim_last().unwrap();
+        assert_eq!(journal.claim_last().unwrap_err().code(), "busy");
+        journal.release(claimed.id, false);
+        assert_eq!(journal.entries().len(), 2);
+
+        let again = journal.claim_last().unwrap();
+        assert_eq!(again.id, claimed.id);
+        journal.release(again.id, true);
+        let next = journal.claim_last().unwrap();
+        assert_eq!(next.action.paths(), [Path::new("/first")]);
+        journal.release(next.id, true);
+        assert_eq!(
+            journal.claim_last().unwrap_err().to_string(),
+            "Nothing to undo"
+        );
+    }
+
+    #[test]
+    fn test_journal_keeps_the_newest() {
+        let mut journal = UndoJournal::new();
+        for i in 0..UNDO_JOURNAL_CAPACITY + 5 {
+            journal.record(UndoAction::Rename {
+                from: PathBuf::from(format!("/x/{}", i)),
+                to: PathBuf::from("/x/y"),
+            });
+        }
+        let entries = journal.entries();
+        assert_eq!(entries.len(), UNDO_JOURNAL_CAPACITY);
+        assert_eq!(entries[0].action, journal.last().unwrap().action);
+        assert_eq!(entries[0].id, UNDO_JOURNAL_CAPACITY as u64 + 5);
+        let json = serde_json::to_value(&entries[0]).unwrap();
+        assert_eq!(json["kind"], "rename");
+        assert!(json.get("stamp").is_none());
+    }
+}
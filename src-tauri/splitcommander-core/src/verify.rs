@@ -0,0 +1,511 @@
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use serde::Serialize;
+
+use crate::error::CommandError;
+use crate::ignore::IgnoreRules;
+use crate::model::EntryKind;
+use crate::scan::{ScanOptions, ScanResult};
+use crate::source::TreeSource;
+
+const CHUNK_SIZE: usize = 256 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum MismatchKind {
+    OnlyLeft,
+    OnlyRight,
+    TypeMismatch,
+    SizeDiffers,
+    ContentDiffers,
+    SymlinkTargetDiffers,
+    Error,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VerifyMismatch {
+    pub rel_path: String,
+    pub kind: MismatchKind,
+    pub message: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VerifyResult {
+    pub passed: bool,
+    pub files_compared: usize,
+    pub bytes_compared: u64,
+    /// Fifos, sockets and devices of the same kind on both sides; never read
+    pub special_skipped: usize,
+    /// Total number of mismatches found, including those not listed
+    pub mismatch_count: usize,
+    /// The first `max_mismatches` mismatches in path order
+    pub mismatches: Vec<VerifyMismatch>,
+}
+
+/// Verifies that two trees are byte-identical: every entry exists on both sides with
+/// the same kind, symlinks point at the same target, and files have identical bytes.
+/// Files are compared by streaming both in chunks (no hashing).
+///
+/// - `progress`: called with (entries_checked, total_entries) as verification proceeds
+pub fn verify_trees(
+    left: &dyn TreeSource,
+    right: &dyn TreeSource,
+    ignore_rules: &IgnoreRules,
+    max_mismatches: usize,
+    cancel_flag: &AtomicBool,
+    progress: &dyn Fn(usize, usize),
+) -> Result<VerifyResult, String> {
+    let left_scan = left.scan(ignore_rules, &[], &ScanOptions::default(), cancel_flag, &|_| {})?;
+    let right_scan = right.scan(ignore_rules, &[], &ScanOptions::default(), cancel_flag, &|_| {})?;
+
+    let mut result = VerifyResult {
+        passed: true,
+        files_compared: 0,
+        bytes_compared: 0,
+        special_skipped: 0,
+        mismatch_count: 0,
+        mismatches: Vec::new(),
+    };
+    let mut record = |rel_path: &str, kind: MismatchKind, message: Option<String>| {
+        result.passed = false;
+        result.mismatch_count += 1;
+        if result.mismatches.len() < max_mismatches {
+            result.mismatches.push(VerifyMismatch {
+                rel_path: rel_path.to_string(),
+                kind,
+                message,
+            });
+        }
+    };
+
+    // Anything the scanner could not read cannot be verified, and is not missing either
+    let mut unreadable = HashSet::new();
+    for scan in [&left_scan, &right_scan] {
+        for err in &scan.errors {
+            record(&err.path, MismatchKind::Error, Some(err.message.clone()));
+            unreadable.insert(scan.key_mode.key(&err.path));
+        }
+    }
+
+    let mut keys: Vec<&String> = left_scan
+        .entries
+        .keys()
+        .chain(right_scan.entries.keys().filter(|k| !left_scan.entries.contains_key(*k)))
+        .filter(|k| !unreadable.contains(*k))
+        .collect();
+    keys.sort();
+    let total = keys.len();
+
+    let mut files_compared = 0;
+    let mut bytes_compared = 0;
+    let mut special_skipped = 0;
+    for (i, key) in keys.iter().enumerate() {
+        if cancel_flag.load(Ordering::Relaxed) {
+            return Err("Verification cancelled".to_string());
+        }
+        if i % 100 == 0 {
+            progress(i, total);
+        }
+
+        let rel_path = original_path(&left_scan, &right_scan, key);
+        let (l, r) = match (left_scan.entries.get(*key), right_scan.entries.get(*key)) {
+            (Some(l), Some(r)) => (l, r),
+            (Some(_), None) => {
+                record(&rel_path, MismatchKind::OnlyLeft, None);
+                continue;
+            }
+            (None, Some(_)) => {
+                record(&rel_path, MismatchKind::OnlyRight, None);
+                continue;
+            }
+            (None, None) => unreachable!(),
+        };
+
+        if l.kind != r.kind {
+            record(&rel_path, MismatchKind::TypeMismatch, None);
+            continue;
+        }
+        match l.kind {
+            EntryKind::Dir => {}
+            EntryKind::Fifo | EntryKind::Socket | EntryKind::BlockDevice | EntryKind::CharDevice => {
+                special_skipped += 1;
+            }
+            EntryKind::Symlink => {
+                if l.symlink_target != r.symlink_target {
+                    record(&rel_path, MismatchKind::SymlinkTargetDiffers, None);
+                }
+            }
+            EntryKind::File => {
+                if l.size != r.size {
+                    record(
+                        &rel_path,
+                        MismatchKind::SizeDiffers,
+                        Some(format!("{} vs {} bytes", l.size, r.size)),
+                    );
+                    continue;
+                }
+                let identical = left.open(&left_scan.originals[*key]).and_then(|mut a| {
+                    let mut b = right.open(&right_scan.originals[*key])?;
+                    readers_identical(&mut a, &mut b, cancel_flag)
+                });
+                match identical {
+                    Ok(true) => {
+                        files_compared += 1;
+                        bytes_compared += l.size;
+                    }
+                    Ok(false) => record(&rel_path, MismatchKind::ContentDiffers, None),
+                    // A read cut short by cancelling says nothing about the file
+                    Err(_) if cancel_flag.load(Ordering::Relaxed) => {
+                        return Err("Verification cancelled".to_string());
+                    }
+                    Err(e) => record(&rel_path, MismatchKind::Error, Some(e.to_string())),
+                }
+            }
+        }
+    }
+    progress(total, total);
+
+    result.files_compared = files_compared;
+    result.bytes_compared = bytes_compared;
+    result.special_skipped = special_skipped;
+    Ok(result)
+}
+
+fn original_path(left: &ScanResult, right: &ScanResult, key: &str) -> String {
+    left.originals
+        .get(key)
+        .or_else(|| right.originals.get(key))
+        .cloned()
+        .unwrap_or_else(|| key.to_string())
+}
+
+/// Streams both readers in chunks and returns false at the first differing byte.
+pub fn readers_identical(
+    fa: &mut dyn Read,
+    fb: &mut dyn Read,
+    cancel_flag: &AtomicBool,
+) -> io::Result<bool> {
+    first_difference(fa, fb, cancel_flag).map(|offset| offset.is_none())
+}
+
+/// Streams both readers in chunks and returns the offset of the first differing byte,
+/// or where the shorter one ends; `None` if they are identical.
+pub fn first_difference(
+    fa: &mut dyn Read,
+    fb: &mut dyn Read,
+    cancel_flag: &AtomicBool,
+) -> io::Result<Option<u64>> {
+    let mut buf_a = vec![0u8; CHUNK_SIZE];
+    let mut buf_b = vec![0u8; CHUNK_SIZE];
+    let mut offset: u64 = 0;
+
+    loop {
+        if cancel_flag.load(Ordering::Relaxed) {
+            return Err(io::Error::new(io::ErrorKind::Interrupted, "cancelled"));
+        }
+        let na = read_full(fa, &mut buf_a)?;
+        let nb = read_full(fb, &mut buf_b)?;
+        let common = na.min(nb);
+        // Equal chunks, by far the common case, compare a word at a time; only a chunk
+        // that differs is searched for the offset
+        if buf_a[..common] != buf_b[..common] {
+            let i = (0..common).find(|&i| buf_a[i] != buf_b[i]).unwrap_or(common);
+            return Ok(Some(offset + i as u64));
+        }
+        if na != nb {
+            return Ok(Some(offset + common as u64));
+        }
+        if na == 0 {
+            return Ok(None);
+        }
+        offset += na as u64;
+    }
+}
+
+/// How [`compare_files`] reached its answer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ComparisonMethod {
+    /// The sizes differ, so neither file was read
+    Size,
+    /// Read both until the first differing byte or the end
+    Content,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FileComparison {
+    pub same: bool,
+    pub left_size: u64,
+    pub right_size: u64,
+    /// Only found when the contents were compared
+    pub first_difference_offset: Option<u64>,
+    pub method: ComparisonMethod,
+}
+
+/// Compares two files byte for byte, stopping at the first difference. Files of
+/// different sizes are reported as different without being read. Errors say which side
+/// failed.
+pub fn compare_files(
+    left: &Path,
+    right: &Path,
+    cancel_flag: &AtomicBool,
+) -> Result<FileComparison, CommandError> {
+    let left_size = file_len(left, "left")?;
+    let right_size = file_len(right, "right")?;
+    if left_size != right_size {
+        return Ok(FileComparison {
+            same: false,
+            left_size,
+            right_size,
+            first_difference_offset: None,
+            method: ComparisonMethod::Size,
+        });
+    }
+
+    let open = |path: &Path, side: &str| {
+        File::open(path).map_err(|e| side_error(path, side, &e))
+    };
+    let (mut fa, mut fb) = (open(left, "left")?, open(right, "right")?);
+    let offset = first_difference(&mut fa, &mut fb, cancel_flag).map_err(|e| {
+        if cancel_flag.load(Ordering::Relaxed) {
+            CommandError::cancelled("File comparison cancelled")
+        } else {
+            let message =
+                format!("Cannot compare {} with {}: {}", left.display(), right.display(), e);
+            CommandError::Io {
+                message,
+                path: None,
+            }
+        }
+    })?;
+    Ok(FileComparison {
+        same: offset.is_none(),
+        left_size,
+        right_size,
+        first_difference_offset: offset,
+        method: ComparisonMethod::Content,
+    })
+}
+
+fn file_len(path: &Path, side: &str) -> Result<u64, CommandError> {
+    let meta = std::fs::metadata(path).map_err(|e| side_error(path, side, &e))?;
+    if !meta.is_file() {
+        return Err(CommandError::InvalidArgument {
+            message: format!("The {} side is not a file: {}", side, path.display()),
+            path: Some(path.display().to_string()),
+        });
+    }
+    Ok(meta.len())
+}
+
+fn side_error(path: &Path, side: &str, err: &io::Error) -> CommandError {
+    CommandError::io(format!("Cannot read {} file {}", side, path.display()), path, err)
+}
+
+/// Reads until the buffer is full or EOF, so chunk boundaries line up on both sides.
+fn read_full(file: &mut dyn Read, buf: &mut [u8]) -> io::Result<usize> {
+    let mut filled = 0;
+    while filled < buf.len() {
+        match file.read(&mut buf[filled..]) {
+            Ok(0) => break,
+            Ok(n) => filled += n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(filled)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::source::memory::MemorySource;
+    use crate::source::LocalSource;
+    use std::fs;
+    use std::fs::File;
+    use std::path::{Path, PathBuf};
+
+    fn make_pair(name: &str) -> (PathBuf, PathBuf, PathBuf) {
+        let dir = std::env::temp_dir().join(format!("sc_verify_{}", name));
+        let _ = fs::remove_dir_all(&dir);
+        let left = dir.join("left");
+        let right = dir.join("right");
+        for side in [&left, &right] {
+            fs::create_dir_all(side.join("sub")).unwrap();
+            fs::write(side.join("a.txt"), "alpha").unwrap();
+            fs::write(side.join("sub/b.txt"), "bravo").unwrap();
+        }
+        (dir, left, right)
+    }
+
+    fn verify(left: &Path, right: &Path, max: usize) -> VerifyResult {
+        let rules = IgnoreRules::new(&[]);
+        let cancel = AtomicBool::new(false);
+        verify_trees(
+            &LocalSource::new(left),
+            &LocalSource::new(right),
+            &rules,
+            max,
+            &cancel,
+            &|_, _| {},
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_identical_trees_pass() {
+        let (dir, left, right) = make_pair("identical");
+
+        let result = verify(&left, &right, 10);
+        assert!(result.passed);
+        assert_eq!(result.files_compared, 2);
+        assert_eq!(result.bytes_compared, 10);
+        assert_eq!(result.mismatch_count, 0);
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_special_files_not_read() {
+        let (dir, left, right) = make_pair("special");
+        let _sockets = [
+            std::os::unix::net::UnixListener::bind(left.join("app.sock")).unwrap(),
+            std::os::unix::net::UnixListener::bind(right.join("app.sock")).unwrap(),
+        ];
+
+        let result = verify(&left, &right, 10);
+        assert!(result.passed);
+        assert_eq!(result.files_compared, 2);
+        assert_eq!(result.special_skipped, 1);
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_same_size_different_bytes_fails() {
+        let (dir, left, right) = make_pair("content");
+        fs::write(right.join("sub/b.txt"), "BRAVO").unwrap();
+
+        let result = verify(&left, &right, 10);
+        assert!(!result.passed);
+        assert_eq!(result.mismatches.len(), 1);
+        assert_eq!(result.mismatches[0].rel_path, "sub/b.txt");
+        assert_eq!(result.mismatches[0].kind, MismatchKind::ContentDiffers);
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_missing_files_and_mismatch_cap() {
+        let (dir, left, right) = make_pair("missing");
+        fs::write(left.join("only_left1.txt"), "x").unwrap();
+        fs::write(left.join("only_left2.txt"), "x").unwrap();
+        fs::write(right.join("only_right.txt"), "x").unwrap();
+
+        let result = verify(&left, &right, 2);
+        assert!(!result.passed);
+        assert_eq!(result.mismatch_count, 3);
+        assert_eq!(result.mismatches.len(), 2);
+        assert_eq!(result.mismatches[0].kind, MismatchKind::OnlyLeft);
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_verify_cancellation() {
+        let (dir, left, right) = make_pair("cancel");
+        let rules = IgnoreRules::new(&[]);
+        let cancel = AtomicBool::new(true);
+
+        let result = verify_trees(
+            &LocalSource::new(&left),
+            &LocalSource::new(&right),
+            &rules,
+            10,
+            &cancel,
+            &|_, _| {},
+        );
+        assert!(result.is_err());
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_readers_identical_across_chunks() {
+        let (dir, left, right) = make_pair("chunks");
+        let mut data = vec![7u8; CHUNK_SIZE * 2 + 10];
+        fs::write(left.join("big.bin"), &data).unwrap();
+        data[CHUNK_SIZE + 5] = 8;
+        fs::write(right.join("big.bin"), &data).unwrap();
+
+        let cancel = AtomicBool::new(false);
+        let open = |p: PathBuf| File::open(p).unwrap();
+        let same = readers_identical(
+            &mut open(left.join("big.bin")),
+            &mut open(left.join("big.bin")),
+            &cancel,
+        );
+        assert!(same.unwrap());
+        let differs = readers_identical(
+            &mut open(left.join("big.bin")),
+            &mut open(right.join("big.bin")),
+            &cancel,
+        );
+        assert!(!differs.unwrap());
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_compare_files_reports_first_difference() {
+        let (dir, left, right) = make_pair("compare_files");
+        let mut data = vec![7u8; CHUNK_SIZE + 100];
+        fs::write(left.join("a.bin"), &data).unwrap();
+        data[CHUNK_SIZE + 42] = 8;
+        fs::write(right.join("b.bin"), &data).unwrap();
+        fs::write(right.join("short.bin"), b"77").unwrap();
+
+        let cancel = AtomicBool::new(false);
+        let differs = compare_files(&left.join("a.bin"), &right.join("b.bin"), &cancel).unwrap();
+        let same = compare_files(&left.join("a.bin"), &left.join("a.bin"), &cancel).unwrap();
+        let sized = compare_files(&left.join("a.bin"), &right.join("short.bin"), &cancel).unwrap();
+        let missing = compare_files(&left.join("a.bin"), &right.join("none"), &cancel);
+        let _ = fs::remove_dir_all(&dir);
+
+        assert_eq!(differs.first_difference_offset, Some(CHUNK_SIZE as u64 + 42));
+        assert_eq!((differs.same, differs.method), (false, ComparisonMethod::Content));
+        assert!(same.same && same.first_difference_offset.is_none());
+        assert_eq!((sized.method, sized.right_size), (ComparisonMethod::Size, 2));
+        let err = missing.unwrap_err();
+        assert_eq!(err.code(), "notFound");
+        assert!(err.to_string().starts_with("Cannot read right file"), "{}", err);
+    }
+
+    #[test]
+    fn test_verify_memory_sources() {
+        let left = MemorySource::new()
+            .file("a.txt", "alpha")
+            .file("sub/b.txt", "bravo")
+            .symlink("link", "a.txt");
+        let right = left.clone().file("sub/b.txt", "BRAVO");
+        let rules = IgnoreRules::new(&[]);
+        let cancel = AtomicBool::new(false);
+
+        let same = verify_trees(&left, &left.clone(), &rules, 10, &cancel, &|_, _| {}).unwrap();
+        assert!(same.passed);
+        assert_eq!(same.files_compared, 2);
+
+        let result = verify_trees(&left, &right, &rules, 10, &cancel, &|_, _| {}).unwrap();
+        assert!(!result.passed);
+        assert_eq!(result.mismatches[0].rel_path, "sub/b.txt");
+        assert_eq!(result.mismatches[0].kind, MismatchKind::ContentDiffers);
+    }
+}
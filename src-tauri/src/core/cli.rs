@@ -0,0 +1,121 @@
+use std::sync::atomic::AtomicBool;
+
+use crate::core::ignore::IgnoreRules;
+use crate::core::source::LocalSource;
+use crate::core::verify;
+
+const USAGE: &str = "usage: split-commander verify <left> <right> [--max-mismatches N]";
+
+/// Exit codes for headless invocations.
+pub const EXIT_OK: i32 = 0;
+pub const EXIT_MISMATCH: i32 = 1;
+pub const EXIT_ERROR: i32 = 2;
+
+/// Runs a headless subcommand if `args` (without the program name) names one.
+/// Returns `None` when the GUI should start instead.
+pub fn run(args: &[String]) -> Option<i32> {
+    match args.first().map(String::as_str) {
+        Some("verify") => Some(verify_command(&args[1..])),
+        _ => None,
+    }
+}
+
+fn verify_command(args: &[String]) -> i32 {
+    let mut paths = Vec::new();
+    let mut max_mismatches = 20;
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        if arg == "--max-mismatches" {
+            match iter.next().and_then(|n| n.parse().ok()) {
+                Some(n) => max_mismatches = n,
+                None => {
+                    eprintln!("{}", USAGE);
+                    return EXIT_ERROR;
+                }
+            }
+        } else {
+            paths.push(arg);
+        }
+    }
+    let [left, right] = paths.as_slice() else {
+        eprintln!("{}", USAGE);
+        return EXIT_ERROR;
+    };
+
+    let ignore_rules = IgnoreRules::new(&[]);
+    let cancel = AtomicBool::new(false);
+    let result = match verify::verify_trees(
+        &LocalSource::new(left),
+        &LocalSource::new(right),
+        &ignore_rules,
+        max_mismatches,
+        &cancel,
+        &|_, _| {},
+    ) {
+        Ok(r) => r,
+        Err(e) => {
+            eprintln!("verify failed: {}", e);
+            return EXIT_ERROR;
+        }
+    };
+
+    for m in &result.mismatches {
+        match &m.message {
+            Some(msg) => println!("{:?}\t{}\t{}", m.kind, m.rel_path, msg),
+            None => println!("{:?}\t{}", m.kind, m.rel_path),
+        }
+    }
+    println!(
+        "{}: {} files ({} bytes) identical, {} mismatches",
+        if result.passed { "PASS" } else { "FAIL" },
+        result.files_compared,
+        result.bytes_compared,
+        result.mismatch_count
+    );
+
+    if result.passed {
+        EXIT_OK
+    } else {
+        EXIT_MISMATCH
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn args(list: &[&str]) -> Vec<String> {
+        list.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn test_no_subcommand_starts_gui() {
+        assert_eq!(run(&[]), None);
+        assert_eq!(run(&args(&["--some-tauri-flag"])), None);
+    }
+
+    #[test]
+    fn test_verify_usage_error() {
+        assert_eq!(run(&args(&["verify", "/only-one"])), Some(EXIT_ERROR));
+    }
+
+    #[test]
+    fn test_verify_exit_codes() {
+        let dir = std::env::temp_dir().join("sc_cli_verify");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("left")).unwrap();
+        fs::create_dir_all(dir.join("right")).unwrap();
+        fs::write(dir.join("left/a.txt"), "same").unwrap();
+        fs::write(dir.join("right/a.txt"), "same").unwrap();
+
+        let left = dir.join("left").to_string_lossy().to_string();
+        let right = dir.join("right").to_string_lossy().to_string();
+        assert_eq!(run(&args(&["verify", &left, &right])), Some(EXIT_OK));
+
+        fs::write(dir.join("right/a.txt"), "diff").unwrap();
+        assert_eq!(run(&args(&["verify", &left, &right])), Some(EXIT_MISMATCH));
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -1,124 +1,359 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::io::Read;
-use std::path::PathBuf;
-use std::sync::atomic::{AtomicBool, Ordering};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use std::sync::{Arc, Mutex};
-use std::time::UNIX_EPOCH;
+use std::time::{Duration, UNIX_EPOCH};
 
 use serde::{Deserialize, Serialize};
-use tauri::{AppHandle, Emitter, Manager, State};
+use tauri::{AppHandle, Emitter, Manager, Runtime, State};
 
+use crate::core::archive::{self, ArchiveFormat, ArchiveReport, ArchiveSource, ExtractReport};
+use crate::core::checksum::{self, ChecksumReport, HashAlgorithm};
 use crate::core::compare;
+use crate::core::dir_cache::{DirResolveCache, DirSizeCache, DirStamp, Stamped};
+use crate::core::disk;
+use crate::core::duplicates::{self, DuplicateSummary};
+use crate::core::error::CommandError;
 use crate::core::events::*;
 use crate::core::export;
 use crate::core::fileops;
 use crate::core::ignore::IgnoreRules;
+use crate::core::locations::{self, Locations};
+use crate::core::lock::LockExt;
+use crate::core::maintenance::{self, MaintenanceReport};
 use crate::core::model::*;
+use crate::core::operations::{JobSlot, OperationInfo, OperationRegistry};
+use crate::core::pane_state::{PersistedState, ScreenRect, WindowState};
+use crate::core::panes::PaneId;
+use crate::core::paths::{self, CompletionOptions, ResolvedPath};
+use crate::core::platform;
 use crate::core::pty;
+use crate::core::refresh::{self, CompareSettings};
+use crate::core::preview::{self, FilePreview};
 use crate::core::scan;
-
-/// Cache key for resolved directory statuses: (left_path, right_path).
-pub type DirCacheKey = (String, String);
-/// Cache value: (status, total_size).
-pub type DirCacheValue = (CompareStatus, u64);
+use crate::core::search::{self, ContentQuery, ContentSummary, SearchQuery, SearchSummary};
+use crate::core::security;
+use crate::core::settings::{self, Session, SessionOptions, Settings};
+use crate::core::sort::{SortFields, SortOrder};
+use crate::core::source::{self, FileSource, LocalSource, TreeSource};
+use crate::core::sync;
+use crate::core::textdiff::{self, DiffOptions, FileDiff};
+use crate::core::undo::{self, UndoAction, UndoEntry, UndoJournal};
+use crate::core::verify::{self, FileComparison, VerifyResult};
+use crate::core::watch::{self, DirWatcher, RootWatcher};
 
 /// Shared application state managed by Tauri.
 pub struct AppState {
-    pub left_root: Mutex<Option<PathBuf>>,
-    pub right_root: Mutex<Option<PathBuf>>,
-    pub cancel_flag: Arc<AtomicBool>,
-    pub dir_resolve_cancel: Arc<AtomicBool>,
+    /// Root of each pane tab that has one
+    pub roots: Mutex<HashMap<PaneId, PathBuf>>,
+    /// The tabs `start_compare` last compared, `left:0` and `right:0` until then. The
+    /// commands acting on compare results use their roots.
+    pub compared: Mutex<(PaneId, PaneId)>,
+    pub compare_job: JobSlot,
+    /// The other cancellable background jobs, one of each kind at a time; starting one
+    /// cancels the run it replaces
+    pub dir_resolve_job: JobSlot,
+    pub measure_job: JobSlot,
+    pub search_job: JobSlot,
+    pub content_search_job: JobSlot,
+    pub file_compare_job: JobSlot,
+    pub checksum_job: JobSlot,
+    pub duplicates_job: JobSlot,
     pub last_result: Mutex<Option<LastCompareResult>>,
-    pub dir_resolve_cache: Arc<Mutex<HashMap<DirCacheKey, DirCacheValue>>>,
-    pub pty_left: Mutex<Option<pty::PtyState>>,
-    pub pty_right: Mutex<Option<pty::PtyState>>,
+    pub dir_resolve_cache: Arc<Mutex<DirResolveCache>>,
+    pub dir_size_cache: Arc<Mutex<DirSizeCache>>,
+    /// Terminal of each pane tab; cloned out so no command holds the map while it
+    /// talks to a shell
+    pub ptys: Mutex<HashMap<PaneId, Arc<pty::PtyState>>>,
+    pub operations: Arc<OperationRegistry>,
+    /// Folders file operations may act in besides the pane roots; the home folder
+    pub allowed_roots: Mutex<Vec<PathBuf>>,
+    /// Set at startup from the command line or environment, never from the settings:
+    /// no path checks at all; see `settings::unrestricted_fileops`
+    pub unrestricted_fileops: AtomicBool,
+    /// Set by `set_read_only` and the `read_only` setting: refuse every change
+    pub read_only: AtomicBool,
+    /// Set from the `shell` setting: what new terminals run
+    pub shell: Mutex<Option<String>>,
+    /// Set from the `terminal_scrollback_bytes` setting: output each new terminal keeps
+    pub terminal_scrollback_bytes: AtomicUsize,
+    pub undo_journal: Arc<Mutex<UndoJournal>>,
+    /// Recent locations and favorites; read from `state.json` on first use
+    pub locations: Arc<Mutex<Option<Locations>>>,
+    /// Set while `watch_roots` keeps the compare result in step with the disk
+    pub watcher: Mutex<Option<RootWatcher>>,
+    /// The folder each pane tab shows, while `watch_directory` watches it
+    pub dir_watchers: Mutex<HashMap<PaneId, DirWatcher>>,
 }
 
 pub struct LastCompareResult {
+    /// `start_compare` job that produced this result; 0 for a loaded report
+    pub job_id: u64,
+    /// Bumped by each in-place update of the diffs, so a write-back can tell whether
+    /// they changed since it took its copy
+    pub revision: u64,
     pub diffs: Vec<DiffItem>,
     pub summary: CompareSummary,
     pub left_root: String,
     pub right_root: String,
     pub mode: CompareMode,
+    pub scan_errors: ScanErrors,
+    /// Report file this result was loaded from; `None` for a live compare
+    pub loaded_from: Option<String>,
+    /// How the live compare was run, so watch mode can compare changed paths again;
+    /// `None` for a loaded report
+    pub settings: Option<CompareSettings>,
+}
+
+/// Entries each side's scan could not read, as reported by the scanner.
+#[derive(Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScanErrors {
+    pub left: Vec<scan::ScanError>,
+    pub right: Vec<scan::ScanError>,
 }
 
 impl AppState {
     pub fn new() -> Self {
         Self {
-            left_root: Mutex::new(None),
-            right_root: Mutex::new(None),
-            cancel_flag: Arc::new(AtomicBool::new(false)),
-            dir_resolve_cancel: Arc::new(AtomicBool::new(false)),
+            roots: Mutex::new(HashMap::new()),
+            compared: Mutex::new((PaneId::LEFT, PaneId::RIGHT)),
+            compare_job: JobSlot::new("comparison"),
+            dir_resolve_job: JobSlot::new("folder status resolution"),
+            measure_job: JobSlot::new("measurement"),
+            search_job: JobSlot::new("search"),
+            content_search_job: JobSlot::new("content search"),
+            file_compare_job: JobSlot::new("file comparison"),
+            checksum_job: JobSlot::new("checksum run"),
+            duplicates_job: JobSlot::new("duplicate search"),
             last_result: Mutex::new(None),
-            dir_resolve_cache: Arc::new(Mutex::new(HashMap::new())),
-            pty_left: Mutex::new(None),
-            pty_right: Mutex::new(None),
+            dir_resolve_cache: Arc::new(Mutex::new(DirResolveCache::default())),
+            dir_size_cache: Arc::new(Mutex::new(DirSizeCache::default())),
+            ptys: Mutex::new(HashMap::new()),
+            operations: Arc::new(OperationRegistry::new()),
+            allowed_roots: Mutex::new(dirs::home_dir().into_iter().collect()),
+            unrestricted_fileops: AtomicBool::new(false),
+            read_only: AtomicBool::new(false),
+            shell: Mutex::new(None),
+            terminal_scrollback_bytes: AtomicUsize::new(pty::DEFAULT_SCROLLBACK_BYTES),
+            undo_journal: Arc::new(Mutex::new(UndoJournal::new())),
+            locations: Arc::new(Mutex::new(None)),
+            watcher: Mutex::new(None),
+            dir_watchers: Mutex::new(HashMap::new()),
         }
     }
+
+    pub fn root(&self, pane: PaneId) -> Option<PathBuf> {
+        self.roots.lock_or_recover().get(&pane).cloned()
+    }
+
+    /// Roots of the compared tabs.
+    pub fn compared_roots(&self) -> Result<(PathBuf, PathBuf), CommandError> {
+        let (left, right) = *self.compared.lock_or_recover();
+        Ok((self.pane_root(left)?, self.pane_root(right)?))
+    }
+
+    fn pane_root(&self, pane: PaneId) -> Result<PathBuf, CommandError> {
+        self.root(pane).ok_or_else(|| format!("Root of pane {} not set", pane).into())
+    }
+}
+
+/// Sets the root of one pane: a folder, or a zip or tar.gz archive, which is then
+/// compared by its contents without being extracted. Archive roots are read-only, so
+/// syncing and copying between the roots refuse them. Any other file can be a root too,
+/// to compare it with a file in the other pane. File operations are allowed
+/// under the pane roots, besides the home folder. A compare still running over the
+/// old root is cancelled and has stopped before the root changes, so it never reports
+/// or stores a result for roots that are no longer shown. `path` is taken as it is:
+/// `resolve_path` expands what the user typed. `pane_id` picks a tab on `side`, tab 0
+/// by default.
+#[tauri::command]
+pub async fn set_root(
+    side: String,
+    pane_id: Option<String>,
+    path: String,
+    state: State<'_, AppState>,
+) -> Result<(), CommandError> {
+    let pane = PaneId::of(&side, pane_id.as_deref())?;
+    let path = PathBuf::from(path);
+    replace_root(&state, pane, path.clone()).await?;
+    record_recent(&state, path.to_string_lossy().into_owned());
+    Ok(())
 }
 
+/// Resolves a path typed into the path bar: expands `~` and environment variables,
+/// takes relative paths from the home folder, and resolves links and `..`. A path that
+/// does not exist is reported with `exists` false rather than failing.
 #[tauri::command]
-pub async fn set_root(side: String, path: String, state: State<'_, AppState>) -> Result<(), String> {
-    let path_buf = PathBuf::from(&path);
-    if !path_buf.is_dir() {
-        return Err(format!("Not a directory: {}", path));
+pub async fn resolve_path(input: String) -> Result<ResolvedPath, CommandError> {
+    tokio::task::spawn_blocking(move || {
+        paths::resolve(&input, dirs::home_dir().as_deref(), |name| std::env::var(name).ok())
+    })
+    .await
+    .map_err(task_failed)?
+}
+
+/// Up to `max` completions for a partly typed path: absolute paths of the folders (and
+/// with `include_files`, files) in the folder typed so far whose names start with the
+/// last part, ignoring case. Folders end with a separator. Dot-prefixed entries follow
+/// `include_hidden` (default true), like `list_directory`.
+#[tauri::command]
+pub async fn complete_path(
+    partial: String,
+    max: usize,
+    include_files: Option<bool>,
+    include_hidden: Option<bool>,
+) -> Result<Vec<String>, CommandError> {
+    let options = CompletionOptions {
+        max,
+        include_files: include_files.unwrap_or(false),
+        include_hidden: include_hidden.unwrap_or(true),
+    };
+    tokio::task::spawn_blocking(move || {
+        let var = |name: &str| std::env::var(name).ok();
+        paths::complete(&partial, dirs::home_dir().as_deref(), var, options)
+    })
+    .await
+    .map_err(task_failed)?
+}
+
+async fn replace_root(state: &AppState, pane: PaneId, path: PathBuf) -> Result<(), CommandError> {
+    check_root(&path)?;
+    if state.root(pane).as_ref() == Some(&path) {
+        return Ok(());
     }
 
-    match side.as_str() {
-        "left" => *state.left_root.lock().unwrap() = Some(path_buf),
-        "right" => *state.right_root.lock().unwrap() = Some(path_buf),
-        _ => return Err(format!("Invalid side: {}", side)),
+    let (left, right) = *state.compared.lock_or_recover();
+    if pane == left || pane == right {
+        state.compare_job.stop().await;
+        stop_watching(state);
+    }
+    state.roots.lock_or_recover().insert(pane, path);
+    Ok(())
+}
+
+/// Fails unless `path` can be a pane root: a folder or a file.
+fn check_root(path: &Path) -> Result<(), CommandError> {
+    if !path.is_dir() && !path.is_file() {
+        return Err(CommandError::invalid_argument(format!(
+            "Not a directory or file: {}",
+            path.display()
+        )));
     }
     Ok(())
 }
 
+/// A root that is a file but not an archive, compared as a single file.
+fn is_plain_file(root: &Path) -> bool {
+    root.is_file() && ArchiveFormat::detect(root).is_err()
+}
+
+/// Settings of `start_compare` besides its mode; each may be left out.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct StartCompareOptions {
+    pub left_pane: Option<String>,
+    pub right_pane: Option<String>,
+    pub left_path: Option<String>,
+    pub right_path: Option<String>,
+    pub include_self: bool,
+    pub mtime_tolerance_ms: Option<u64>,
+    pub case_sensitive: bool,
+    /// On when left out
+    pub normalize_unicode: Option<bool>,
+    /// On when left out
+    pub include_same: Option<bool>,
+    pub scan_options: scan::ScanOptions,
+    pub force: bool,
+}
+
+/// Starts a full recursive comparison of the two roots, with the settings below given as
+/// fields of `options`.
+/// The app's own data directory is pruned from either scan when it lives under a root,
+/// since its live files change during the compare; pass `include_self` to keep it.
+/// `mtime_tolerance_ms` (default 2000) is how far apart modification times may be and
+/// still count as equal. With `case_sensitive`, paths are matched by exact name, so
+/// `Readme.md` and `README.md` are different entries. Names are compared in Unicode NFC
+/// unless `normalize_unicode` is false. With `include_same: false`, identical entries
+/// are only counted in the summary and never returned by `get_diffs` or exported.
+/// `scan_options` controls how both roots are walked, e.g. leaving out hidden entries;
+/// by default everything is listed.
+/// Only one compare runs at a time: while one is in progress this fails with `busy`,
+/// unless `force` is set, which cancels the running compare and waits for it to stop.
+/// `left_pane` and `right_pane` pick the tabs to compare, `left:0` and `right:0` by
+/// default; once started, the commands acting on the result use their roots.
+/// `left_path` and `right_path` compare those folders (or archives) instead of the tabs'
+/// current roots, and become the tabs' roots as the compare starts, before its job
+/// runs, so a script needs no `set_root` first and nothing can change the roots in
+/// between.
+/// Returns the job id that tags this compare's events.
 #[tauri::command]
 pub async fn start_compare(
     mode: CompareMode,
+    options: Option<StartCompareOptions>,
     app: AppHandle,
     state: State<'_, AppState>,
-) -> Result<(), String> {
-    let left_root = state
-        .left_root
-        .lock()
-        .unwrap()
-        .clone()
-        .ok_or("Left root not set")?;
-    let right_root = state
-        .right_root
-        .lock()
-        .unwrap()
-        .clone()
-        .ok_or("Right root not set")?;
-
-    state.cancel_flag.store(false, Ordering::Relaxed);
-    let cancel_flag = Arc::clone(&state.cancel_flag);
+) -> Result<u64, CommandError> {
+    let StartCompareOptions {
+        left_pane,
+        right_pane,
+        left_path,
+        right_path,
+        include_self,
+        mtime_tolerance_ms,
+        case_sensitive,
+        normalize_unicode,
+        include_same,
+        scan_options,
+        force,
+    } = options.unwrap_or_default();
+    let pane = |id: Option<String>, default| id.map_or(Ok(default), |id| PaneId::parse(&id));
+    let panes = (pane(left_pane, PaneId::LEFT)?, pane(right_pane, PaneId::RIGHT)?);
+    let roots = ComparedRoots::resolve(&state, panes, left_path, right_path)?;
+    let (left_root, right_root) = (roots.left.clone(), roots.right.clone());
 
     let app_handle = app.clone();
     let left_str = left_root.to_string_lossy().to_string();
     let right_str = right_root.to_string_lossy().to_string();
 
-    tokio::task::spawn_blocking(move || {
+    let (left_excluded, right_excluded) = if include_self {
+        (Vec::new(), Vec::new())
+    } else {
+        (self_exclusions(&left_root), self_exclusions(&right_root))
+    };
+
+    let (left_source, right_source) = tree_sources(&left_root, &right_root)?;
+
+    let work = move |job_id: u64, cancel: &AtomicBool| {
         let ignore_rules = IgnoreRules::new(&[]);
-        let cancel = cancel_flag.as_ref();
 
         // Scan left
         let app_left = app_handle.clone();
-        let left_result = match scan::scan_directory(&left_root, &ignore_rules, cancel, &|count| {
-            let _ = app_left.emit(
-                EVENT_SCAN_PROGRESS,
-                ScanProgressPayload {
-                    side: "left".to_string(),
-                    entries_scanned: count,
-                    phase: "scanning".to_string(),
-                },
-            );
-        }) {
+        let left_result = match left_source.scan(
+            &ignore_rules,
+            &left_excluded,
+            &scan_options,
+            cancel,
+            &|progress| {
+                let _ = app_left.emit(
+                    EVENT_SCAN_PROGRESS,
+                    ScanProgressPayload {
+                        job_id,
+                        side: "left".to_string(),
+                        entries_scanned: progress.entries,
+                        bytes_scanned: progress.bytes,
+                        current_path: progress.current_path.clone(),
+                        phase: "scanning".to_string(),
+                    },
+                );
+            },
+        ) {
             Ok(r) => r,
             Err(e) => {
                 let _ = app_handle.emit(
                     EVENT_COMPARE_ERROR,
-                    CompareErrorPayload { message: e },
+                    CompareErrorPayload { job_id, message: e },
                 );
                 return;
             }
@@ -127,29 +362,41 @@ pub async fn start_compare(
         let _ = app_handle.emit(
             EVENT_SCAN_PROGRESS,
             ScanProgressPayload {
+                job_id,
                 side: "left".to_string(),
                 entries_scanned: left_result.count,
+                bytes_scanned: left_result.file_bytes(),
+                current_path: None,
                 phase: "done".to_string(),
             },
         );
 
         // Scan right
         let app_right = app_handle.clone();
-        let right_result = match scan::scan_directory(&right_root, &ignore_rules, cancel, &|count| {
-            let _ = app_right.emit(
-                EVENT_SCAN_PROGRESS,
-                ScanProgressPayload {
-                    side: "right".to_string(),
-                    entries_scanned: count,
-                    phase: "scanning".to_string(),
-                },
-            );
-        }) {
+        let right_result = match right_source.scan(
+            &ignore_rules,
+            &right_excluded,
+            &scan_options,
+            cancel,
+            &|progress| {
+                let _ = app_right.emit(
+                    EVENT_SCAN_PROGRESS,
+                    ScanProgressPayload {
+                        job_id,
+                        side: "right".to_string(),
+                        entries_scanned: progress.entries,
+                        bytes_scanned: progress.bytes,
+                        current_path: progress.current_path.clone(),
+                        phase: "scanning".to_string(),
+                    },
+                );
+            },
+        ) {
             Ok(r) => r,
             Err(e) => {
                 let _ = app_handle.emit(
                     EVENT_COMPARE_ERROR,
-                    CompareErrorPayload { message: e },
+                    CompareErrorPayload { job_id, message: e },
                 );
                 return;
             }
@@ -158,938 +405,5152 @@ pub async fn start_compare(
         let _ = app_handle.emit(
             EVENT_SCAN_PROGRESS,
             ScanProgressPayload {
+                job_id,
                 side: "right".to_string(),
                 entries_scanned: right_result.count,
+                bytes_scanned: right_result.file_bytes(),
+                current_path: None,
                 phase: "done".to_string(),
             },
         );
 
         // Compare
-        match compare::compare(&left_result, &right_result, mode, cancel) {
+        let key_mode = scan::KeyMode {
+            case_sensitive,
+            normalize_unicode: normalize_unicode.unwrap_or(true),
+        };
+        let (left_result, right_result) = (left_result.rekey(key_mode), right_result.rekey(key_mode));
+
+        let mut options = compare::CompareOptions::default();
+        if let Some(tolerance) = mtime_tolerance_ms {
+            options.mtime_tolerance_ms = tolerance;
+        }
+        options.include_same = include_same.unwrap_or(true);
+        match compare::compare_with_options(&left_result, &right_result, mode, &options, cancel) {
+            // Cancelled after the compare finished: a root may be changing, so the
+            // result must not be reported or stored
+            Ok(_) if cancel.load(Ordering::Relaxed) => {
+                let _ = app_handle.emit(
+                    EVENT_COMPARE_ERROR,
+                    CompareErrorPayload {
+                        job_id,
+                        message: "Compare cancelled".to_string(),
+                    },
+                );
+            }
             Ok(result) => {
                 let _ = app_handle.emit(
                     EVENT_COMPARE_DONE,
                     CompareDonePayload {
+                        job_id,
                         summary: result.summary.clone(),
                     },
                 );
 
                 // Store result for later retrieval
                 if let Some(app_state) = app_handle.try_state::<AppState>() {
-                    *app_state.last_result.lock().unwrap() = Some(LastCompareResult {
+                    *app_state.last_result.lock_or_recover() = Some(LastCompareResult {
+                        job_id,
+                        revision: 0,
                         diffs: result.diffs,
                         summary: result.summary,
                         left_root: left_str,
                         right_root: right_str,
                         mode,
+                        scan_errors: ScanErrors {
+                            left: left_result.errors,
+                            right: right_result.errors,
+                        },
+                        loaded_from: None,
+                        settings: Some(CompareSettings {
+                            mode,
+                            options,
+                            scan_options,
+                            key_mode,
+                            left_excluded,
+                            right_excluded,
+                        }),
                     });
                 }
             }
             Err(e) => {
                 let _ = app_handle.emit(
                     EVENT_COMPARE_ERROR,
-                    CompareErrorPayload { message: e },
+                    CompareErrorPayload { job_id, message: e },
                 );
             }
         }
-    });
+    };
 
-    Ok(())
+    let claim = || roots.claim(&state);
+    state
+        .compare_job
+        .start(force, claim, work)
+        .await
 }
 
-#[tauri::command]
-pub async fn cancel_compare(state: State<'_, AppState>) -> Result<(), String> {
-    state.cancel_flag.store(true, Ordering::Relaxed);
-    Ok(())
+/// The panes `start_compare` compares and the roots it compares them at.
+struct ComparedRoots {
+    panes: (PaneId, PaneId),
+    /// `left_path` and `right_path`, which become the panes' roots
+    given: [Option<PathBuf>; 2],
+    left: PathBuf,
+    right: PathBuf,
 }
 
-#[tauri::command]
-pub async fn get_diffs(state: State<'_, AppState>) -> Result<Vec<DiffItem>, String> {
-    let result = state.last_result.lock().unwrap();
-    match result.as_ref() {
-        Some(r) => Ok(r.diffs.clone()),
-        None => Err("No comparison result available".to_string()),
+impl ComparedRoots {
+    /// `left_path` and `right_path` where given, checked as roots, else the panes'
+    /// current roots.
+    fn resolve(
+        state: &AppState,
+        panes: (PaneId, PaneId),
+        left_path: Option<String>,
+        right_path: Option<String>,
+    ) -> Result<Self, CommandError> {
+        let given = |path: Option<String>| {
+            path.map(|path| {
+                let path = PathBuf::from(path);
+                check_root(&path)?;
+                Ok::<_, CommandError>(path)
+            })
+            .transpose()
+        };
+        let given = [given(left_path)?, given(right_path)?];
+        let root = |pane: PaneId, given: &Option<PathBuf>| match given {
+            Some(path) => Ok(path.clone()),
+            None => state.pane_root(pane),
+        };
+        Ok(Self {
+            left: root(panes.0, &given[0])?,
+            right: root(panes.1, &given[1])?,
+            panes,
+            given,
+        })
+    }
+
+    /// Makes these the compared panes and their roots, and stops watching other roots.
+    /// Runs as the compare starts, before its job is spawned, so the stored roots are
+    /// those being compared by the time anything can change them.
+    fn claim(&self, state: &AppState) {
+        let mut roots = state.roots.lock_or_recover();
+        for (pane, given) in [self.panes.0, self.panes.1].into_iter().zip(&self.given) {
+            if let Some(root) = given {
+                roots.insert(pane, root.clone());
+            }
+        }
+        drop(roots);
+        *state.compared.lock_or_recover() = self.panes;
+        let mut watcher = state.watcher.lock_or_recover();
+        if watcher
+            .as_ref()
+            .is_some_and(|w| (&w.left_root, &w.right_root) != (&self.left, &self.right))
+        {
+            *watcher = None;
+        }
     }
 }
 
-#[tauri::command]
-pub async fn get_summary(state: State<'_, AppState>) -> Result<CompareSummary, String> {
-    let result = state.last_result.lock().unwrap();
-    match result.as_ref() {
-        Some(r) => Ok(r.summary.clone()),
-        None => Err("No comparison result available".to_string()),
+/// The tree behind a pane root: the folder itself, or the contents of an archive file.
+fn tree_source(root: &std::path::Path) -> Result<Box<dyn TreeSource>, CommandError> {
+    if root.is_file() {
+        Ok(Box::new(ArchiveSource::new(root)?))
+    } else {
+        Ok(Box::new(LocalSource::new(root)))
     }
 }
 
-#[tauri::command]
-pub async fn export_report(path: String, state: State<'_, AppState>) -> Result<(), String> {
-    let result = state.last_result.lock().unwrap();
-    match result.as_ref() {
-        Some(r) => {
-            let json = export::generate_json_report(
-                &r.left_root,
-                &r.right_root,
-                r.mode,
-                r.summary.clone(),
-                r.diffs.clone(),
-            )?;
-            std::fs::write(&path, json).map_err(|e| e.to_string())
+type SourcePair = (Box<dyn TreeSource>, Box<dyn TreeSource>);
+
+/// The trees behind both compare roots. Two plain files are compared as one entry,
+/// named after the left file; a plain file cannot be compared with a folder or archive.
+fn tree_sources(left: &Path, right: &Path) -> Result<SourcePair, CommandError> {
+    match (is_plain_file(left), is_plain_file(right)) {
+        (false, false) => Ok((tree_source(left)?, tree_source(right)?)),
+        (true, true) => {
+            let name = left
+                .file_name()
+                .map(|n| n.to_string_lossy().into_owned())
+                .unwrap_or_default();
+            Ok((
+                Box::new(FileSource::new(left, name.clone())),
+                Box::new(FileSource::new(right, name)),
+            ))
+        }
+        (left_is_file, _) => {
+            let (file, other) = if left_is_file { (left, right) } else { (right, left) };
+            Err(CommandError::invalid_argument(format!(
+                "Cannot compare the file {} with the folder {}",
+                file.display(),
+                other.display()
+            )))
         }
-        None => Err("No comparison result to export".to_string()),
     }
 }
 
-/// A single entry for directory browsing (not comparison).
-#[derive(Clone, Serialize)]
-#[serde(rename_all = "camelCase")]
-pub struct BrowseEntry {
-    pub name: String,
-    pub kind: EntryKind,
-    pub size: u64,
-    pub modified: Option<u64>,
+/// Fails with `permissionDenied` unless each path lies under the home folder or a pane
+/// root, so a misbehaving frontend cannot reach anywhere else. `entries` are acted on
+/// themselves (a link there is not followed); `dirs` are written into. Everything is
+/// allowed when the app was started with unrestricted file operations.
+fn check_allowed(
+    state: &AppState,
+    entries: &[&std::path::Path],
+    dirs: &[&std::path::Path],
+) -> Result<(), CommandError> {
+    if state.unrestricted_fileops.load(Ordering::Relaxed) {
+        return Ok(());
+    }
+    let mut roots = state.allowed_roots.lock_or_recover().clone();
+    roots.extend(state.roots.lock_or_recover().values().cloned());
+    for entry in entries {
+        security::validate_within_roots(&roots, entry, false)?;
+    }
+    for dir in dirs {
+        security::validate_within_roots(&roots, dir, true)?;
+    }
+    Ok(())
 }
 
-/// Result of init_browse: home path + initial directory listing in one IPC call.
-#[derive(Clone, Serialize)]
-#[serde(rename_all = "camelCase")]
-pub struct InitBrowseResult {
-    pub home: String,
-    pub entries: Vec<BrowseEntry>,
+/// Fails for a root that is a file: an archive can be compared but not written to, and
+/// a single file has no entries to copy or sync one by one.
+fn writable_root(root: &std::path::Path) -> Result<(), CommandError> {
+    if !root.is_file() {
+        return Ok(());
+    }
+    let message = if is_plain_file(root) {
+        format!(
+            "{} is a single file; copying and syncing entries needs two folders",
+            root.display()
+        )
+    } else {
+        format!(
+            "{} is an archive; extract it to sync or copy its entries",
+            root.display()
+        )
+    };
+    Err(CommandError::invalid_argument(message))
 }
 
-/// Returns home directory path + its listing in a single IPC call for fast startup.
-#[tauri::command]
-pub async fn init_browse() -> Result<InitBrowseResult, String> {
-    let home = dirs::home_dir()
-        .map(|p| p.to_string_lossy().to_string())
-        .ok_or_else(|| "Could not determine home directory".to_string())?;
-    let entries = list_directory_impl(&home)?;
-    Ok(InitBrowseResult { home, entries })
+type SyncedResult = (u64, u64, String, String, Vec<DiffItem>, CompareSummary);
+
+/// A copy of the stored result for `sync_diffs` to work from: its job, revision, roots,
+/// diffs and summary. Fails for a loaded report and for file or archive roots.
+fn synced_result(state: &AppState) -> Result<SyncedResult, CommandError> {
+    let synced = {
+        let result = state.last_result.lock_or_recover();
+        let r = result.as_ref().ok_or("No comparison result available")?;
+        if let Some(report) = &r.loaded_from {
+            return Err(format!(
+                "The current result was loaded from {}; run a compare before syncing",
+                report
+            )
+            .into());
+        }
+        (
+            r.job_id,
+            r.revision,
+            r.left_root.clone(),
+            r.right_root.clone(),
+            r.diffs.clone(),
+            r.summary.clone(),
+        )
+    };
+    writable_root(std::path::Path::new(&synced.2))?;
+    writable_root(std::path::Path::new(&synced.3))?;
+    Ok(synced)
 }
 
-/// Lists the contents of a directory for browsing.
-/// Returns entries sorted: directories first, then files, alphabetically.
-#[tauri::command]
-pub async fn list_directory(path: String) -> Result<Vec<BrowseEntry>, String> {
-    list_directory_impl(&path)
+/// The roots `copy_rel_entry` copies from and to, for a copy from `from_side`. Fails
+/// for file or archive roots.
+fn rel_copy_roots(state: &AppState, from_side: &str) -> Result<(PathBuf, PathBuf), CommandError> {
+    let (left_root, right_root) = state.compared_roots()?;
+    let (src_root, dest_root) = match from_side {
+        "left" => (left_root, right_root),
+        "right" => (right_root, left_root),
+        _ => {
+            return Err(CommandError::invalid_argument(format!(
+                "Invalid side: {}",
+                from_side
+            )))
+        }
+    };
+    writable_root(&src_root)?;
+    writable_root(&dest_root)?;
+    Ok((src_root, dest_root))
 }
 
-/// Opens a file with the OS default application.
-#[tauri::command]
-pub async fn open_file(path: String) -> Result<(), String> {
-    let p = path.clone();
-    tokio::task::spawn_blocking(move || {
-        open::that(&p).map_err(|e| format!("Cannot open {}: {}", p, e))
-    })
-    .await
-    .map_err(|e| format!("Task failed: {}", e))?
+/// Directories owned by the app itself that fall under `root` and should not be compared.
+fn self_exclusions(root: &std::path::Path) -> Vec<PathBuf> {
+    settings::app_data_dir()
+        .ok()
+        .and_then(|data_dir| scan::nested_dir_under(root, &data_dir))
+        .into_iter()
+        .collect()
 }
 
-/// Copies a file or directory from source to the destination directory.
 #[tauri::command]
-pub async fn copy_entry(source_path: String, dest_dir: String) -> Result<(), String> {
-    let src = PathBuf::from(&source_path);
-    let dst = PathBuf::from(&dest_dir);
+pub async fn cancel_compare(state: State<'_, AppState>) -> Result<(), CommandError> {
+    state.compare_job.cancel();
+    Ok(())
+}
 
-    if !src.exists() {
-        return Err(format!("Source does not exist: {}", source_path));
+/// Turns watch mode on or off. While on, changes under either compared root are
+/// collected for half a second, then just the changed paths are compared again: the
+/// stored diffs and summary are updated in place and `diffs-updated` names the paths.
+/// Events the system dropped, or a burst of renames, compare everything again instead.
+/// Watching stops when a compared root changes or another pair is compared, and needs
+/// a live result of two folders. A refresh that fails, e.g. because a root was removed,
+/// leaves the result as it was.
+#[tauri::command]
+pub async fn watch_roots(
+    enabled: bool,
+    app: AppHandle,
+    state: State<'_, AppState>,
+) -> Result<(), CommandError> {
+    stop_watching(&state);
+    if !enabled {
+        return Ok(());
     }
-    if !dst.is_dir() {
-        return Err(format!("Destination is not a directory: {}", dest_dir));
+    let (left_root, right_root) = {
+        let result = state.last_result.lock_or_recover();
+        let r = result.as_ref().ok_or("No comparison result available")?;
+        if let Some(report) = &r.loaded_from {
+            return Err(format!(
+                "The current result was loaded from {}; run a compare before watching",
+                report
+            )
+            .into());
+        }
+        (PathBuf::from(&r.left_root), PathBuf::from(&r.right_root))
+    };
+    for root in [&left_root, &right_root] {
+        if !root.is_dir() {
+            return Err(CommandError::invalid_argument(format!(
+                "Only folders can be watched: {}",
+                root.display()
+            )));
+        }
     }
 
-    tokio::task::spawn_blocking(move || fileops::copy_entry(&src, &dst))
-        .await
-        .map_err(|e| format!("Task failed: {}", e))?
-        .map(|_| ())
+    // Adding a watch per folder takes a while on big trees
+    let watcher = tokio::task::spawn_blocking(move || {
+        RootWatcher::start(&left_root, &right_root, move |changes, cancel| {
+            apply_changes(&app, changes, cancel)
+        })
+    })
+    .await
+    .map_err(task_failed)??;
+    *state.watcher.lock_or_recover() = Some(watcher);
+    Ok(())
 }
 
-/// Copies a file or directory, overwriting destination if it exists.
-#[tauri::command]
-pub async fn copy_entry_overwrite(source_path: String, dest_dir: String) -> Result<(), String> {
-    let src = PathBuf::from(&source_path);
-    let dst = PathBuf::from(&dest_dir);
+/// Ends watch mode, if on.
+pub fn stop_watching(state: &AppState) {
+    state.watcher.lock_or_recover().take();
+}
 
-    if !src.exists() {
-        return Err(format!("Source does not exist: {}", source_path));
-    }
-    if !dst.is_dir() {
-        return Err(format!("Destination is not a directory: {}", dest_dir));
-    }
+/// Drops every watcher, the roots' and the panes'; on shutdown.
+pub fn drop_watchers(state: &AppState) {
+    stop_watching(state);
+    state.dir_watchers.lock_or_recover().clear();
+}
 
-    tokio::task::spawn_blocking(move || fileops::copy_entry_overwrite(&src, &dst))
-        .await
-        .map_err(|e| format!("Task failed: {}", e))?
-        .map(|_| ())
+/// Compares the paths watch mode saw change again, stores the updated result and
+/// announces it. `cancel` is set once watching stops.
+fn apply_changes(app: &AppHandle, changes: watch::Changes, cancel: &AtomicBool) {
+    let Some(state) = app.try_state::<AppState>() else {
+        return;
+    };
+    let rel_paths = if changes.rescan {
+        vec![String::new()]
+    } else {
+        changes.rel_paths.into_iter().collect()
+    };
+    if let Some(update) = refresh_stored(&state, rel_paths, None, cancel) {
+        let _ = app.emit(EVENT_DIFFS_UPDATED, update);
+    }
 }
 
-/// Moves a file or directory from source to the destination directory.
-#[tauri::command]
-pub async fn move_entry(source_path: String, dest_dir: String) -> Result<(), String> {
-    let src = PathBuf::from(&source_path);
-    let dst = PathBuf::from(&dest_dir);
+/// Before a file operation in the app: when the stored live result leaves out Same
+/// entries, counts those at the `paths` the operation is about to change, so that the
+/// refresh after it compares just the affected pairs again instead of both trees.
+async fn baseline_before_operation<R: Runtime>(
+    app: &AppHandle<R>,
+    paths: Vec<PathBuf>,
+) -> Option<refresh::Baseline> {
+    let app = app.clone();
+    tokio::task::spawn_blocking(move || {
+        let state = app.try_state::<AppState>()?;
+        let rel_paths = stored_rel_paths(&state, &paths);
+        let (left_root, right_root, settings, summary) = {
+            let result = state.last_result.lock_or_recover();
+            let r = result.as_ref()?;
+            if rel_paths.is_empty() || !refresh::omits_same(&r.summary, &r.diffs) {
+                return None;
+            }
+            (
+                PathBuf::from(&r.left_root),
+                PathBuf::from(&r.right_root),
+                r.settings.clone()?,
+                r.summary.clone(),
+            )
+        };
+        refresh::baseline(
+            &left_root,
+            &right_root,
+            &rel_paths,
+            &settings,
+            &summary,
+            &AtomicBool::new(false),
+        )
+        .ok()
+    })
+    .await
+    .ok()
+    .flatten()
+}
 
-    if !src.exists() {
-        return Err(format!("Source does not exist: {}", source_path));
-    }
-    if !dst.is_dir() {
-        return Err(format!("Destination is not a directory: {}", dest_dir));
-    }
+/// After a file operation in the app: compares the `paths` it changed again where they
+/// lie under the stored result's roots, and announces the updated diffs. `baseline` is
+/// what `baseline_before_operation` took.
+async fn refresh_after_operation<R: Runtime>(
+    app: &AppHandle<R>,
+    paths: Vec<PathBuf>,
+    baseline: Option<refresh::Baseline>,
+) {
+    let app = app.clone();
+    let _ = tokio::task::spawn_blocking(move || {
+        let Some(state) = app.try_state::<AppState>() else {
+            return;
+        };
+        let rel_paths = stored_rel_paths(&state, &paths);
+        let never = AtomicBool::new(false);
+        if let Some(update) = refresh_stored(&state, rel_paths, baseline.as_ref(), &never) {
+            let _ = app.emit(EVENT_DIFFS_UPDATED, update);
+        }
+    })
+    .await;
+}
 
-    tokio::task::spawn_blocking(move || fileops::move_entry(&src, &dst))
-        .await
-        .map_err(|e| format!("Task failed: {}", e))?
-        .map(|_| ())
+/// `paths` relative to whichever root of the stored result they lie under, `/`-separated.
+fn stored_rel_paths(state: &AppState, paths: &[PathBuf]) -> Vec<String> {
+    let result = state.last_result.lock_or_recover();
+    let Some(r) = result.as_ref() else {
+        return Vec::new();
+    };
+    let roots = [Path::new(&r.left_root), Path::new(&r.right_root)];
+    paths
+        .iter()
+        .flat_map(|path| roots.map(|root| path.strip_prefix(root).ok()))
+        .flatten()
+        .map(|rel| scan::forward_slashes(&rel.to_string_lossy(), std::path::MAIN_SEPARATOR))
+        .collect()
 }
 
-/// Creates a new directory inside parent_path with the given name.
-#[tauri::command]
-pub async fn create_directory(parent_path: String, name: String) -> Result<(), String> {
-    let parent = PathBuf::from(&parent_path);
+/// Times `refresh_stored` compares the paths again when the stored diffs keep changing
+/// under it, before giving up.
+const REFRESH_ATTEMPTS: usize = 3;
 
-    if !parent.is_dir() {
-        return Err(format!("Parent is not a directory: {}", parent_path));
+/// Compares `rel_paths` of the stored live result again and writes the updated diffs
+/// back, unless another compare replaced the result meanwhile. When something else,
+/// e.g. a sync, updated the stored diffs while the paths were compared, they are
+/// compared again over those diffs rather than overwriting them. Paths in the app's own
+/// data folder are skipped, as the compare left them out. A `baseline` covering the
+/// paths lets a result without Same entries be updated in part; it only holds for the
+/// diffs it was taken against, so a retry goes without. Returns the `diffs-updated`
+/// payload, or `None` if there was nothing to do or the refresh failed or was cancelled.
+fn refresh_stored(
+    state: &AppState,
+    rel_paths: Vec<String>,
+    mut baseline: Option<&refresh::Baseline>,
+    cancel: &AtomicBool,
+) -> Option<DiffsUpdatedPayload> {
+    for _ in 0..REFRESH_ATTEMPTS {
+        let (job_id, revision, left_root, right_root, settings, mut diffs, mut summary) = {
+            let result = state.last_result.lock_or_recover();
+            let r = result.as_ref()?;
+            (
+                r.job_id,
+                r.revision,
+                PathBuf::from(&r.left_root),
+                PathBuf::from(&r.right_root),
+                r.settings.clone()?,
+                r.diffs.clone(),
+                r.summary.clone(),
+            )
+        };
+        let excluded = |rel_path: &String| {
+            let excluded_under = |root: &Path, dirs: &[PathBuf]| {
+                let path = root.join(rel_path);
+                dirs.iter().any(|dir| path.starts_with(dir))
+            };
+            excluded_under(&left_root, &settings.left_excluded)
+                || excluded_under(&right_root, &settings.right_excluded)
+        };
+        let rel_paths: Vec<String> = rel_paths.iter().filter(|p| !excluded(p)).cloned().collect();
+        if rel_paths.is_empty() {
+            return None;
+        }
+
+        let refreshed = match baseline.take() {
+            Some(baseline) if baseline.covers(&rel_paths, settings.key_mode) => {
+                refresh::refresh_from(
+                    &left_root,
+                    &right_root,
+                    baseline,
+                    &settings,
+                    &mut diffs,
+                    &mut summary,
+                    cancel,
+                )
+            }
+            _ => refresh::refresh_paths(
+                &left_root,
+                &right_root,
+                &rel_paths,
+                &settings,
+                &mut diffs,
+                &mut summary,
+                cancel,
+            ),
+        }
+        .ok()?;
+        if refreshed.full {
+            state.dir_resolve_cache.lock_or_recover().clear();
+            state.dir_size_cache.lock_or_recover().clear();
+        } else {
+            let changed: Vec<PathBuf> = refreshed
+                .rel_paths
+                .iter()
+                .flat_map(|p| [left_root.join(p), right_root.join(p)])
+                .collect();
+            let changed: Vec<&Path> = changed.iter().map(PathBuf::as_path).collect();
+            invalidate_dir_cache(&state.dir_resolve_cache, &changed);
+            invalidate_dir_sizes(&state.dir_size_cache, &changed);
+        }
+
+        let paths = if refreshed.full {
+            Vec::new()
+        } else {
+            updated_paths(&diffs, refreshed.rel_paths, settings.key_mode)
+        };
+        let mut stored = state.last_result.lock_or_recover();
+        match stored.as_mut() {
+            Some(r)
+                if r.job_id == job_id
+                    && Path::new(&r.left_root) == left_root
+                    && Path::new(&r.right_root) == right_root =>
+            {
+                if r.revision != revision {
+                    continue;
+                }
+                r.diffs = diffs;
+                r.summary = summary.clone();
+                r.revision += 1;
+            }
+            _ => return None,
+        }
+        return Some(DiffsUpdatedPayload {
+            job_id,
+            paths,
+            full: refreshed.full,
+            summary,
+        });
     }
+    None
+}
 
-    tokio::task::spawn_blocking(move || fileops::create_directory(&parent, &name))
-        .await
-        .map_err(|e| format!("Task failed: {}", e))?
-        .map(|_| ())
+/// Each of `rel_paths` with the kind `diffs` now gives it.
+fn updated_paths(
+    diffs: &[DiffItem],
+    rel_paths: Vec<String>,
+    key_mode: scan::KeyMode,
+) -> Vec<UpdatedPath> {
+    rel_paths
+        .into_iter()
+        .map(|rel_path| {
+            let key = key_mode.key(&rel_path);
+            let kind = diffs
+                .iter()
+                .find(|d| key_mode.key(&d.rel_path) == key)
+                .map(|d| d.diff_kind);
+            UpdatedPath { rel_path, kind }
+        })
+        .collect()
 }
 
-/// Deletes a file or directory (recursively for directories).
+/// Returns all diffs of the last comparison, in path order. With `job_id`, fails unless
+/// the stored result came from that compare. `get_diffs_page` returns them a page at a
+/// time.
 #[tauri::command]
-pub async fn delete_entry(target_path: String) -> Result<(), String> {
-    let target = PathBuf::from(&target_path);
-
-    if !target.exists() {
-        return Err(format!("Does not exist: {}", target_path));
-    }
-
-    tokio::task::spawn_blocking(move || fileops::delete_entry(&target))
-        .await
-        .map_err(|e| format!("Task failed: {}", e))?
+pub async fn get_diffs(
+    job_id: Option<u64>,
+    state: State<'_, AppState>,
+) -> Result<Vec<DiffItem>, CommandError> {
+    let result = state.last_result.lock_or_recover();
+    Ok(stored_result(&result, job_id)?.diffs.clone())
 }
 
-/// Persisted pane state saved across app restarts.
-#[derive(Clone, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
-pub struct PersistedState {
-    pub left_path: String,
-    pub right_path: String,
-    pub left_selected_index: i32,
-    pub left_scroll_top: f64,
-    pub right_selected_index: i32,
-    pub right_scroll_top: f64,
-    pub left_show_hidden: bool,
-    pub right_show_hidden: bool,
+/// Returns a page of the last comparison's diffs, in path order, with the number of
+/// diffs matching the filter. Without `limit`, everything from `offset` on is returned.
+/// `path_filter` is a case-insensitive substring. `job_id` is checked as in `get_diffs`.
+#[tauri::command]
+pub async fn get_diffs_page(
+    offset: Option<usize>,
+    limit: Option<usize>,
+    kinds: Option<Vec<DiffKind>>,
+    path_filter: Option<String>,
+    job_id: Option<u64>,
+    state: State<'_, AppState>,
+) -> Result<compare::DiffPage, CommandError> {
+    let result = state.last_result.lock_or_recover();
+    let r = stored_result(&result, job_id)?;
+    Ok(compare::page_diffs(
+        &r.diffs,
+        offset.unwrap_or(0),
+        limit,
+        kinds.as_deref(),
+        path_filter.as_deref(),
+    ))
 }
 
-fn state_file_path() -> Result<PathBuf, String> {
-    let data_dir = dirs::data_dir()
-        .ok_or_else(|| "Could not determine data directory".to_string())?;
-    Ok(data_dir.join("com.splitcommander.app").join("state.json"))
+/// The stored result, checked against the compare job the caller expects.
+fn stored_result(
+    result: &Option<LastCompareResult>,
+    job_id: Option<u64>,
+) -> Result<&LastCompareResult, CommandError> {
+    let r = result.as_ref().ok_or("No comparison result available")?;
+    match job_id {
+        Some(expected) if expected != r.job_id => Err(format!(
+            "Comparison {} is no longer the current result",
+            expected
+        )
+        .into()),
+        _ => Ok(r),
+    }
 }
 
+/// Raw scan errors (unreadable entries) from the last comparison, per side.
 #[tauri::command]
-pub async fn load_app_state() -> Result<Option<PersistedState>, String> {
-    let path = state_file_path()?;
-    if !path.exists() {
-        return Ok(None);
-    }
-    let contents = std::fs::read_to_string(&path).map_err(|e| e.to_string())?;
-    let state: PersistedState = match serde_json::from_str(&contents) {
-        Ok(s) => s,
-        Err(_) => return Ok(None),
-    };
-    // Validate that saved paths still exist
-    if !PathBuf::from(&state.left_path).is_dir() || !PathBuf::from(&state.right_path).is_dir() {
-        return Ok(None);
+pub async fn get_scan_errors(state: State<'_, AppState>) -> Result<ScanErrors, CommandError> {
+    let result = state.last_result.lock_or_recover();
+    match result.as_ref() {
+        Some(r) => Ok(r.scan_errors.clone()),
+        None => Err("No comparison result available".into()),
     }
-    Ok(Some(state))
 }
 
+/// Summary of the last comparison; `job_id` is checked as in `get_diffs`.
 #[tauri::command]
-pub async fn save_app_state(state: PersistedState) -> Result<(), String> {
-    let path = state_file_path()?;
-    if let Some(parent) = path.parent() {
-        std::fs::create_dir_all(parent).map_err(|e| e.to_string())?;
-    }
-    let json = serde_json::to_string_pretty(&state).map_err(|e| e.to_string())?;
-    std::fs::write(&path, json).map_err(|e| e.to_string())
+pub async fn get_summary(
+    job_id: Option<u64>,
+    state: State<'_, AppState>,
+) -> Result<CompareSummary, CommandError> {
+    summary_of(&state, job_id)
 }
 
-/// Result of comparing a single directory level between two paths.
-#[derive(Clone, Serialize)]
-#[serde(rename_all = "camelCase")]
-pub struct CompareDirectoryResult {
-    pub entries: Vec<CompareEntry>,
-    pub left_path: String,
-    pub right_path: String,
-    pub summary: CompareSummary,
+fn summary_of(state: &AppState, job_id: Option<u64>) -> Result<CompareSummary, CommandError> {
+    let result = state.last_result.lock_or_recover();
+    Ok(stored_result(&result, job_id)?.summary.clone())
 }
 
-/// Compares a single directory level from two paths, returning merged entries.
-/// Directories present on both sides use cached status if available, otherwise marked Pending.
+/// Makes a JSON report written by `export_report` the active result, so `get_diffs`,
+/// `get_summary` and exports work on it as after a live compare. Returns its summary.
+/// The panes' roots are left alone, and syncing is refused until a live compare replaces
+/// it, since the trees have likely changed since the report was written.
 #[tauri::command]
-pub async fn compare_directory(
-    left_path: String,
-    right_path: String,
+pub async fn load_report(
+    path: String,
     state: State<'_, AppState>,
-) -> Result<CompareDirectoryResult, String> {
-    let lp = left_path.clone();
-    let rp = right_path.clone();
-    let cache = Arc::clone(&state.dir_resolve_cache);
-
-    // Run on blocking thread since dir listing does I/O
-    let result = tokio::task::spawn_blocking(move || {
-        compare_directory_impl(&lp, &rp, &cache)
+) -> Result<CompareSummary, CommandError> {
+    let source = path.clone();
+    let report = tokio::task::spawn_blocking(move || {
+        let file =
+            std::fs::File::open(&path).map_err(|e| format!("Cannot open {}: {}", path, e))?;
+        export::read_json_report(file)
     })
     .await
-    .map_err(|e| format!("Task failed: {}", e))?;
+    .map_err(task_failed)??;
 
-    let (entries, summary) = result;
+    let summary = report.summary.clone();
+    *state.last_result.lock_or_recover() = Some(LastCompareResult {
+        job_id: 0,
+        revision: 0,
+        diffs: report.diffs,
+        summary: report.summary,
+        left_root: report.left_root,
+        right_root: report.right_root,
+        mode: report.mode,
+        scan_errors: ScanErrors::default(),
+        loaded_from: Some(source),
+        settings: None,
+    });
+    stop_watching(&state);
+    Ok(summary)
+}
 
-    Ok(CompareDirectoryResult {
-        entries,
-        left_path,
-        right_path,
-        summary,
+/// Loads two JSON reports and lists the paths whose diff kind changed, appeared or
+/// disappeared from `old_path` to `new_path`.
+#[tauri::command]
+pub async fn compare_reports(
+    old_path: String,
+    new_path: String,
+) -> Result<export::ReportComparison, CommandError> {
+    tokio::task::spawn_blocking(move || {
+        let read = |path: &str| {
+            let file =
+                std::fs::File::open(path).map_err(|e| format!("Cannot open {}: {}", path, e))?;
+            export::read_json_report(file).map_err(|e| format!("{}: {}", path, e))
+        };
+        let old = read(&old_path)?;
+        let new = read(&new_path)?;
+        Ok(export::compare_reports(&old, &new))
     })
+    .await
+    .map_err(task_failed)?
 }
 
-/// Compare one directory level. Dirs on both sides use cache or are marked Pending.
-fn compare_directory_impl(
-    left_path: &str,
-    right_path: &str,
-    cache: &Arc<Mutex<HashMap<DirCacheKey, DirCacheValue>>>,
-) -> (Vec<CompareEntry>, CompareSummary) {
-    let left_entries = list_directory_impl(left_path).unwrap_or_default();
-    let right_entries = list_directory_impl(right_path).unwrap_or_default();
-
-    let left_map: HashMap<String, &BrowseEntry> = left_entries
-        .iter()
-        .map(|e| (e.name.to_lowercase(), e))
-        .collect();
-    let right_map: HashMap<String, &BrowseEntry> = right_entries
-        .iter()
-        .map(|e| (e.name.to_lowercase(), e))
-        .collect();
+/// Writes the last comparison result to `path` in `format` (JSON by default), streamed
+/// as a registered operation. Emits `export-progress` while writing and
+/// `export-done`/`export-error` at the end. Fails up front if the destination volume
+/// lacks room for the report.
+///
+/// `kinds` and `path_prefix` restrict the report to matching diffs; its summary then
+/// counts only those, and JSON reports keep the whole comparison's under `fullSummary`.
+/// `delete_extras` makes a sync script remove right-only entries too.
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+pub async fn export_report<R: Runtime>(
+    path: String,
+    format: Option<export::ExportFormat>,
+    kinds: Option<Vec<DiffKind>>,
+    path_prefix: Option<String>,
+    delete_extras: Option<bool>,
+    app: AppHandle<R>,
+    state: State<'_, AppState>,
+) -> Result<(), CommandError> {
+    let (left_root, right_root, mode, summary, full_summary, filter, diffs) = {
+        let result = state.last_result.lock_or_recover();
+        let r = result.as_ref().ok_or("No comparison result to export")?;
+        if kinds.is_none() && path_prefix.is_none() {
+            (
+                r.left_root.clone(),
+                r.right_root.clone(),
+                r.mode,
+                r.summary.clone(),
+                None,
+                None,
+                r.diffs.clone(),
+            )
+        } else {
+            let diffs = export::filter_diffs(&r.diffs, kinds.as_deref(), path_prefix.as_deref());
+            let mut summary = r.summary.clone();
+            summary.recount(&diffs);
+            (
+                r.left_root.clone(),
+                r.right_root.clone(),
+                r.mode,
+                summary,
+                Some(r.summary.clone()),
+                Some(export::ReportFilter { kinds, path_prefix }),
+                diffs,
+            )
+        }
+    };
 
-    let mut all_keys: Vec<String> = left_map
-        .keys()
-        .chain(right_map.keys())
-        .cloned()
-        .collect::<std::collections::HashSet<_>>()
-        .into_iter()
-        .collect();
-    all_keys.sort();
+    let operations = Arc::clone(&state.operations);
+    tokio::task::spawn_blocking(move || {
+        let dest = PathBuf::from(&path);
+        let (op_id, cancel) = operations.register("export", vec![dest.clone()]);
+        let result = (|| {
+            let format = format.unwrap_or_default();
+            let needed = export::estimate_report_size(format, &diffs);
+            disk::ensure_space(&disk::SystemSpace, &dest, needed)?;
 
-    let mut entries = Vec::new();
-    let mut summary = CompareSummary::default();
-    summary.total_left = left_entries.len();
-    summary.total_right = right_entries.len();
+            fileops::write_atomic_with(&dest, |file| {
+                export::write_report(
+                    format,
+                    file,
+                    &left_root,
+                    &right_root,
+                    mode,
+                    &summary,
+                    full_summary.as_ref(),
+                    filter.as_ref(),
+                    delete_extras.unwrap_or(false),
+                    &diffs,
+                    &cancel,
+                    &|entries, bytes| {
+                        operations.update(op_id, entries as u64, diffs.len() as u64, "writing");
+                        let _ = app.emit(
+                            EVENT_EXPORT_PROGRESS,
+                            ExportProgressPayload {
+                                operation_id: op_id,
+                                entries_written: entries,
+                                total_entries: diffs.len(),
+                                bytes_written: bytes,
+                            },
+                        );
+                    },
+                )
+                .map_err(|e| cancelled_or(&cancel, e))
+            })
+        })();
+        operations.finish(op_id);
 
-    for key in &all_keys {
-        let left = left_map.get(key);
-        let right = right_map.get(key);
+        match result {
+            Ok(bytes_written) => {
+                let _ = app.emit(
+                    EVENT_EXPORT_DONE,
+                    ExportDonePayload {
+                        operation_id: op_id,
+                        path,
+                        bytes_written,
+                    },
+                );
+                Ok(())
+            }
+            Err(err) => {
+                let _ = app.emit(
+                    EVENT_EXPORT_ERROR,
+                    ExportErrorPayload {
+                        operation_id: op_id,
+                        path,
+                        message: err.to_string(),
+                    },
+                );
+                Err(err)
+            }
+        }
+    })
+    .await
+    .map_err(task_failed)?
+}
 
-        let (name, kind, status, left_size, right_size, left_modified, right_modified, dir_info) =
-            match (left, right) {
-                (Some(l), Some(r)) => {
-                    if l.kind != r.kind {
-                        summary.type_mismatch += 1;
-                        (
-                            l.name.clone(),
-                            l.kind,
-                            CompareStatus::TypeMismatch,
-                            Some(l.size),
-                            Some(r.size),
-                            l.modified,
-                            r.modified,
-                            None,
-                        )
-                    } else if l.kind == EntryKind::Dir {
-                        let sub_left = format!("{}/{}", left_path, l.name);
-                        let sub_right = format!("{}/{}", right_path, r.name);
-                        let cache_key = (sub_left, sub_right);
-                        let cached = cache.lock().unwrap().get(&cache_key).cloned();
-                        if let Some((cached_status, cached_size)) = cached {
-                            if cached_status == CompareStatus::Same {
-                                summary.same += 1;
-                            } else {
-                                summary.meta_diff += 1;
-                            }
-                            (
-                                l.name.clone(),
-                                EntryKind::Dir,
-                                cached_status,
-                                None,
-                                None,
-                                l.modified,
-                                r.modified,
-                                Some(DirResolveInfo { total_size: cached_size }),
-                            )
-                        } else {
-                            // No cache hit — mark as pending
-                            (
-                                l.name.clone(),
-                                EntryKind::Dir,
-                                CompareStatus::Pending,
-                                None,
-                                None,
-                                l.modified,
-                                r.modified,
-                                None,
-                            )
-                        }
-                    } else if l.size == r.size {
-                        summary.same += 1;
-                        (
-                            l.name.clone(),
-                            l.kind,
-                            CompareStatus::Same,
-                            Some(l.size),
-                            Some(r.size),
-                            l.modified,
-                            r.modified,
-                            None,
-                        )
+/// How `sync_diffs` runs; each is off when left out.
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct SyncOptions {
+    pub dry_run: bool,
+    pub verify: bool,
+    pub ignore_space_check: bool,
+}
+
+/// Applies selected diffs of the last compare to disk: `direction` is `"leftToRight"` or
+/// `"rightToLeft"`. Missing and modified entries are copied over the destination;
+/// destination-only entries are deleted when `delete_extras` is set. Runs as a registered
+/// operation with per-item progress, and updates the stored diffs to match, announced
+/// with `diffs-updated`.
+/// In `options`: with `dry_run`, nothing is changed: the report's `plan` lists what
+/// would be copied, replaced or deleted, after the same path checks as a real run. With
+/// `verify`, copied files are read back as for `copy_entry`, and a mismatch fails that
+/// item. Unless `ignore_space_check` is set, a sync whose copies would not fit on the
+/// destination volume fails with `insufficientSpace` before anything is changed.
+#[tauri::command]
+pub async fn sync_diffs<R: Runtime>(
+    direction: String,
+    rel_paths: Vec<String>,
+    delete_extras: bool,
+    options: Option<SyncOptions>,
+    app: AppHandle<R>,
+    state: State<'_, AppState>,
+) -> Result<sync::SyncReport, CommandError> {
+    let direction = sync::SyncDirection::parse(&direction)?;
+    let (job_id, revision, left_root, right_root, mut diffs, mut summary) = synced_result(&state)?;
+    let options = options.unwrap_or_default();
+
+    if options.dry_run {
+        return tokio::task::spawn_blocking(move || sync::SyncReport {
+            plan: Some(sync::plan_sync(
+                std::path::Path::new(&left_root),
+                std::path::Path::new(&right_root),
+                direction,
+                &rel_paths,
+                delete_extras,
+                &diffs,
+            )),
+            ..Default::default()
+        })
+        .await
+        .map_err(task_failed);
+    }
+
+    ensure_writable(&state)?;
+    let operations = Arc::clone(&state.operations);
+    let dest_root = match direction {
+        sync::SyncDirection::LeftToRight => &right_root,
+        sync::SyncDirection::RightToLeft => &left_root,
+    };
+    let dest_path = PathBuf::from(dest_root);
+    tokio::task::spawn_blocking(move || {
+        if !options.ignore_space_check {
+            let plan = sync::plan_sync(
+                std::path::Path::new(&left_root),
+                std::path::Path::new(&right_root),
+                direction,
+                &rel_paths,
+                delete_extras,
+                &diffs,
+            );
+            disk::ensure_space(&disk::SystemSpace, &dest_path, plan.bytes_to_copy)?;
+        }
+
+        let (op_id, cancel) = operations.register("sync", vec![dest_path]);
+        let on_verify = |path: &std::path::Path| {
+            let message = path.display().to_string();
+            if let Some(info) = operations.update_phase(op_id, "verifying", &message) {
+                let _ = app.emit(EVENT_OPERATION_PROGRESS, info);
+            }
+        };
+        let copy_options = fileops::CopyOptions {
+            verify: options.verify,
+            on_verify: Some(&on_verify),
+            ..Default::default()
+        };
+        let report = sync::sync_diffs(
+            std::path::Path::new(&left_root),
+            std::path::Path::new(&right_root),
+            direction,
+            &rel_paths,
+            delete_extras,
+            &mut diffs,
+            &mut summary,
+            &copy_options,
+            &cancel,
+            &|done, total, rel_path| {
+                if let Some(info) = operations.update(op_id, done as u64, total as u64, rel_path) {
+                    let _ = app.emit(EVENT_OPERATION_PROGRESS, info);
+                }
+            },
+        );
+        operations.finish(op_id);
+
+        // Skip the write-back if another compare replaced the result meanwhile. If
+        // something else updated its diffs, compare the synced paths again over those
+        // instead of overwriting them.
+        if let Some(app_state) = app.try_state::<AppState>() {
+            let mut stored = app_state.last_result.lock_or_recover();
+            let update = match stored.as_mut() {
+                Some(r)
+                    if r.job_id == job_id
+                        && r.left_root == left_root
+                        && r.right_root == right_root =>
+                {
+                    let key_mode = r.settings.as_ref().map(|s| s.key_mode).unwrap_or_default();
+                    if r.revision == revision {
+                        let update = DiffsUpdatedPayload {
+                            job_id: r.job_id,
+                            paths: updated_paths(&diffs, rel_paths, key_mode),
+                            full: false,
+                            summary: summary.clone(),
+                        };
+                        r.diffs = diffs;
+                        r.summary = summary;
+                        r.revision += 1;
+                        Some(update)
                     } else {
-                        summary.meta_diff += 1;
-                        (
-                            l.name.clone(),
-                            l.kind,
-                            CompareStatus::Modified,
-                            Some(l.size),
-                            Some(r.size),
-                            l.modified,
-                            r.modified,
-                            None,
-                        )
+                        drop(stored);
+                        refresh_stored(&app_state, rel_paths, None, &AtomicBool::new(false))
                     }
                 }
-                (Some(l), None) => {
-                    summary.only_left += 1;
-                    (
-                        l.name.clone(),
-                        l.kind,
-                        CompareStatus::OnlyLeft,
-                        Some(l.size),
-                        None,
-                        l.modified,
-                        None,
-                        None,
-                    )
-                }
-                (None, Some(r)) => {
-                    summary.only_right += 1;
-                    (
-                        r.name.clone(),
-                        r.kind,
-                        CompareStatus::OnlyRight,
-                        None,
-                        Some(r.size),
-                        None,
-                        r.modified,
-                        None,
-                    )
-                }
-                (None, None) => unreachable!(),
+                _ => None,
             };
+            if let Some(update) = update {
+                let _ = app.emit(EVENT_DIFFS_UPDATED, update);
+            }
+        }
+        Ok(report)
+    })
+    .await
+    .map_err(task_failed)?
+}
 
-        entries.push(CompareEntry {
-            name,
-            kind,
-            status,
-            left_size,
-            right_size,
-            left_modified,
-            right_modified,
-            dir_info,
-        });
+/// A blocking task that panicked or was aborted.
+fn task_failed(err: tokio::task::JoinError) -> CommandError {
+    CommandError::task_failed(format!("Task failed: {}", err))
+}
+
+/// Reports `message` as a cancellation when `cancel` was set, since the core returns
+/// plain messages for those.
+fn cancelled_or(cancel: &AtomicBool, message: String) -> CommandError {
+    if cancel.load(Ordering::Relaxed) {
+        CommandError::cancelled(message)
+    } else {
+        message.into()
     }
+}
+
+/// A single entry for directory browsing (not comparison).
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BrowseEntry {
+    pub name: String,
+    pub kind: EntryKind,
+    pub size: u64,
+    pub modified: Option<u64>,
+    /// Set for directories whose recursive size is known; `size` is then that size
+    /// rather than the directory's own
+    pub file_count: Option<usize>,
+    /// Unix permission bits; on Windows only the read-only attribute, as 0o444 or 0o666
+    /// (0o555/0o777 for folders). `None` in comparison listings
+    pub mode: Option<u32>,
+    /// Owner and group names, or ids where they have no name; `None` off Unix
+    pub owner: Option<String>,
+    pub group: Option<String>,
+    /// Dot-prefixed, or marked hidden on Windows
+    pub hidden: bool,
+    /// Why the entry cannot be read; its kind is then a best guess and its size 0
+    pub error: Option<String>,
+    /// Hard-link identity, for counting linked files once; not sent to the frontend
+    #[serde(skip)]
+    pub link: Option<LinkId>,
+}
 
-    // Sort: dirs first, then alphabetically
-    entries.sort_by(|a, b| {
-        let a_is_dir = a.kind == EntryKind::Dir;
-        let b_is_dir = b.kind == EntryKind::Dir;
-        b_is_dir
-            .cmp(&a_is_dir)
-            .then_with(|| a.name.to_lowercase().cmp(&b.name.to_lowercase()))
+/// Result of init_browse: home path + initial directory listing in one IPC call.
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InitBrowseResult {
+    pub home: String,
+    pub entries: Vec<BrowseEntry>,
+    /// Mounted volumes, as `list_volumes` returns them; empty if they cannot be listed
+    pub volumes: Vec<disk::Volume>,
+    /// As `get_known_folders` returns them
+    pub known_folders: Vec<KnownFolder>,
+}
+
+/// A standard folder for the sidebar, e.g. Downloads.
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct KnownFolder {
+    /// `home`, `desktop`, `documents`, `downloads`, `pictures`, `music`, `videos`,
+    /// `leftRoot`, `rightRoot` or `temp`
+    pub id: &'static str,
+    /// The folder's own name, which is localized on some systems
+    pub name: String,
+    pub path: String,
+    pub exists: bool,
+}
+
+/// How long a volume may take to report its size before it is shown as unavailable.
+const VOLUME_PROBE_TIMEOUT: Duration = Duration::from_millis(750);
+
+/// Returns home directory path + its listing in a single IPC call for fast startup.
+#[tauri::command]
+pub async fn init_browse(state: State<'_, AppState>) -> Result<InitBrowseResult, CommandError> {
+    let home = dirs::home_dir()
+        .map(|p| p.to_string_lossy().to_string())
+        .ok_or_else(|| "Could not determine home directory".to_string())?;
+    let volumes = tokio::task::spawn_blocking(|| {
+        disk::list_volumes(VOLUME_PROBE_TIMEOUT).unwrap_or_default()
     });
+    let entries = list_directory_impl(&home, true, SortOrder::default())?;
+    let volumes = volumes.await.map_err(task_failed)?;
+    Ok(InitBrowseResult {
+        home,
+        entries,
+        volumes,
+        known_folders: known_folders_for(&state),
+    })
+}
+
+/// The user's standard folders as the platform names them (following XDG user dirs on
+/// Linux), then the pane roots while set, then the temp folder. Folders the platform
+/// has no location for are left out; ones that do not exist are flagged.
+#[tauri::command]
+pub async fn get_known_folders(
+    state: State<'_, AppState>,
+) -> Result<Vec<KnownFolder>, CommandError> {
+    Ok(known_folders_for(&state))
+}
 
-    (entries, summary)
+fn known_folders_for(state: &AppState) -> Vec<KnownFolder> {
+    known_folders(vec![
+        ("home", dirs::home_dir()),
+        ("desktop", dirs::desktop_dir()),
+        ("documents", dirs::document_dir()),
+        ("downloads", dirs::download_dir()),
+        ("pictures", dirs::picture_dir()),
+        ("music", dirs::audio_dir()),
+        ("videos", dirs::video_dir()),
+        ("leftRoot", state.root(state.compared.lock_or_recover().0)),
+        ("rightRoot", state.root(state.compared.lock_or_recover().1)),
+        ("temp", Some(std::env::temp_dir())),
+    ])
 }
 
-/// Recursively checks whether two directories have identical contents.
-/// Returns (is_same, total_size) where total_size sums file sizes from the left side.
-/// Accepts a cancellation flag that is checked between subdirectories.
-fn dirs_are_same_recursive_counted(
-    left_path: &str,
-    right_path: &str,
-    cancel: &AtomicBool,
-) -> (bool, u64) {
-    if cancel.load(Ordering::Relaxed) {
-        return (false, 0);
+fn known_folders(candidates: Vec<(&'static str, Option<PathBuf>)>) -> Vec<KnownFolder> {
+    candidates
+        .into_iter()
+        .filter_map(|(id, path)| {
+            let path = path?;
+            let name = path.file_name().map_or_else(
+                || path.to_string_lossy().into_owned(),
+                |name| name.to_string_lossy().into_owned(),
+            );
+            Some(KnownFolder {
+                id,
+                name,
+                exists: path.is_dir(),
+                path: path.to_string_lossy().into_owned(),
+            })
+        })
+        .collect()
+}
+
+/// Mounted volumes, drives and network shares to offer as pane roots. A volume that
+/// does not report its size quickly, like a stale network mount, comes back with
+/// `available` false instead of holding up the call.
+#[tauri::command]
+pub async fn list_volumes() -> Result<Vec<disk::Volume>, CommandError> {
+    tokio::task::spawn_blocking(|| {
+        disk::list_volumes(VOLUME_PROBE_TIMEOUT)
+            .map_err(|e| CommandError::Io {
+                message: format!("Cannot list volumes: {}", e),
+                path: None,
+            })
+    })
+    .await
+    .map_err(task_failed)?
+}
+
+/// Lists the contents of a directory for browsing; `path` is taken as `set_root` takes
+/// it. Returns directories first, then files, each ordered by `sort_by` (`name`, `size`,
+/// `modified` or `extension`; default `name`, compared naturally) and `ascending`
+/// (default true).
+/// Hidden entries are left out when `include_hidden` is false (default true). Entries
+/// that cannot be read are kept, with `error` set.
+/// Directories whose size `resolve_dir_sizes` has cached, and that have not changed
+/// since, come with that size.
+#[tauri::command]
+pub async fn list_directory(
+    path: String,
+    include_hidden: Option<bool>,
+    sort_by: Option<String>,
+    ascending: Option<bool>,
+    state: State<'_, AppState>,
+) -> Result<Vec<BrowseEntry>, CommandError> {
+    let order = SortOrder::parse(sort_by.as_deref(), ascending)?;
+    let entries = list_with_cached_sizes(
+        &path,
+        include_hidden.unwrap_or(true),
+        order,
+        &state.dir_size_cache,
+    )?;
+    record_recent(&state, path);
+    Ok(entries)
+}
+
+/// Lists `path` with the cached sizes of its folders filled in before sorting, so
+/// sort-by-size ranks folders by them.
+fn list_with_cached_sizes(
+    path: &str,
+    include_hidden: bool,
+    order: SortOrder,
+    cache: &Mutex<DirSizeCache>,
+) -> Result<Vec<BrowseEntry>, CommandError> {
+    let mut entries = read_directory_entries(path, include_hidden)?;
+    apply_cached_dir_sizes(path, &mut entries, cache);
+    sort_browse_entries(&mut entries, order);
+    Ok(entries)
+}
+
+fn apply_cached_dir_sizes(parent: &str, entries: &mut [BrowseEntry], cache: &Mutex<DirSizeCache>) {
+    let mut cache = cache.lock_or_recover();
+    for entry in entries.iter_mut().filter(|e| e.kind == EntryKind::Dir) {
+        if let Some(size) = cache.get(&format!("{}/{}", parent, entry.name)) {
+            entry.size = size.total_size;
+            entry.file_count = Some(size.file_count);
+        }
+    }
+}
+
+/// Watches the folder a pane tab shows, not what lies below it, and emits
+/// `directory-changed` when entries in it are created, removed, renamed or modified, so
+/// the pane can list it again. Events are collected for half a second, and a flood of
+/// them is reported as one `modified` without names. `pane` is a pane id like `left` or
+/// `right:1`. The tab's previous watcher, if any, is replaced in one step, so no change
+/// is reported for the folder it left once this returns.
+#[tauri::command]
+pub async fn watch_directory(
+    pane: String,
+    path: String,
+    app: AppHandle,
+    state: State<'_, AppState>,
+) -> Result<(), CommandError> {
+    let pane = PaneId::parse(&pane)?;
+    let path = PathBuf::from(path);
+    if !path.is_dir() {
+        return Err(CommandError::invalid_argument(format!(
+            "Not a directory: {}",
+            path.display()
+        )));
+    }
+    let mut watchers = state.dir_watchers.lock_or_recover();
+    if watchers.get(&pane).is_some_and(|w| w.path == path) {
+        return Ok(());
     }
+    let shown = path.to_string_lossy().into_owned();
+    let watcher = DirWatcher::start(&path, move |batch| {
+        for change in batch.changes {
+            let _ = app.emit(
+                EVENT_DIRECTORY_CHANGED,
+                DirectoryChangedPayload {
+                    pane: pane.to_string(),
+                    path: shown.clone(),
+                    kind: change.kind.to_string(),
+                    names: change.names,
+                },
+            );
+        }
+    })?;
+    watchers.insert(pane, watcher);
+    Ok(())
+}
+
+/// Stops watching the folder a pane tab shows; nothing happens if it was not watched.
+#[tauri::command]
+pub async fn unwatch_directory(
+    pane: String,
+    state: State<'_, AppState>,
+) -> Result<(), CommandError> {
+    let pane = PaneId::parse(&pane)?;
+    state.dir_watchers.lock_or_recover().remove(&pane);
+    Ok(())
+}
+
+/// Opens a file with the OS default application.
+#[tauri::command]
+pub async fn open_file(path: String) -> Result<(), CommandError> {
+    let p = path.clone();
+    tokio::task::spawn_blocking(move || {
+        open::that(&p).map_err(|e| CommandError::io(format!("Cannot open {}", p), p.as_ref(), &e))
+    })
+    .await
+    .map_err(task_failed)?
+}
+
+/// Reads the start of a file for a quick-view panel: up to `max_bytes` (default
+/// 256 KiB) of text, or a hex dump if the file looks binary.
+#[tauri::command]
+pub async fn preview_file(
+    path: String,
+    max_bytes: Option<u64>,
+) -> Result<FilePreview, CommandError> {
+    let max_bytes = max_bytes.unwrap_or(preview::DEFAULT_PREVIEW_BYTES);
+    tokio::task::spawn_blocking(move || preview::preview_file(path.as_ref(), max_bytes))
+        .await
+        .map_err(task_failed)?
+}
+
+/// How `copy_entry` and `copy_entry_overwrite` copy; each is off when left out.
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct CopyEntryOptions {
+    pub preserve_metadata: bool,
+    pub verify: bool,
+    pub ignore_space_check: bool,
+    pub fail_fast: bool,
+    pub dry_run: bool,
+}
+
+/// Copies a file or directory from source to the destination directory.
+/// With `preserve_metadata`, modification times and permissions are carried over.
+/// `conflict` ("fail", the default, "overwrite", "rename" or "merge") decides what
+/// happens when the destination exists; `rename` keeps both as `name (1).ext`, ... and
+/// `merge` copies into an existing folder, keeping files the source does not have.
+/// The outcome reports the destination used, how many files were cloned, and for a
+/// merge which paths were copied, overwritten, skipped or in conflict.
+/// With `verify`, each copied file is read back and compared by hash; see `run_copy`.
+/// Fails with `insufficientSpace` before copying anything if the destination volume
+/// cannot hold the source, unless `ignore_space_check` is set.
+/// Entries of a folder that cannot be copied are listed in the outcome's `skipped`
+/// while the rest is copied, so a non-empty list means a partial copy; `fail_fast`
+/// instead stops at the first one with its error.
+/// With `dry_run`, nothing is copied: the outcome's `plan` gives the destination, the
+/// bytes to copy and whether something there would be replaced, after the same checks
+/// as a real copy.
+/// `preserve_metadata`, `verify`, `ignore_space_check`, `fail_fast` and `dry_run` are
+/// fields of `options`.
+#[tauri::command]
+pub async fn copy_entry<R: Runtime>(
+    source_path: String,
+    dest_dir: String,
+    conflict: Option<String>,
+    options: Option<CopyEntryOptions>,
+    app: AppHandle<R>,
+    state: State<'_, AppState>,
+) -> Result<fileops::CopyOutcome, CommandError> {
+    let options = options.unwrap_or_default();
+    if !options.dry_run {
+        ensure_writable(&state)?;
+    }
+    let src = PathBuf::from(&source_path);
+    let dst = PathBuf::from(&dest_dir);
+
+    if std::fs::symlink_metadata(&src).is_err() {
+        return Err(CommandError::not_found(
+            format!("Source does not exist: {}", source_path),
+            &src,
+        ));
+    }
+    if !dst.is_dir() {
+        return Err(CommandError::invalid_argument(format!(
+            "Destination is not a directory: {}",
+            dest_dir
+        )));
+    }
+    check_allowed(&state, &[&src], &[&dst])?;
+
+    let policy = conflict_policy(conflict.as_deref())?;
+    if options.dry_run {
+        return tokio::task::spawn_blocking(move || {
+            fileops::plan_copy_on_conflict(&src, &dst, policy).map(fileops::CopyOutcome::planned)
+        })
+        .await
+        .map_err(task_failed)?;
+    }
+    let operations = Arc::clone(&state.operations);
+    let journal = Arc::clone(&state.undo_journal);
+    tokio::task::spawn_blocking(move || {
+        check_copy_space(
+            &app,
+            &operations,
+            std::slice::from_ref(&src),
+            &dst,
+            options.ignore_space_check,
+        )?;
+        let dest = src.file_name().map_or(dst.clone(), |name| dst.join(name));
+        journal_copy(&journal, &dest, || {
+            run_copy(&app, &operations, &dst, options, |copy_options| {
+                fileops::copy_entry_on_conflict(&src, &dst, copy_options, policy)
+            })
+        })
+    })
+    .await
+    .map_err(task_failed)?
+}
+
+/// Runs `copy` and journals it for undo, unless it wrote into `dest` while that already
+/// existed (an overwrite or merge), which deleting the copy could not bring back.
+fn journal_copy(
+    journal: &Mutex<UndoJournal>,
+    dest: &std::path::Path,
+    copy: impl FnOnce() -> Result<fileops::CopyOutcome, CommandError>,
+) -> Result<fileops::CopyOutcome, CommandError> {
+    let existed = std::fs::symlink_metadata(dest).is_ok();
+    let outcome = copy()?;
+    if !existed || outcome.dest != dest {
+        let action = UndoAction::Copy {
+            dest: outcome.dest.clone(),
+        };
+        journal.lock_or_recover().record(action);
+    }
+    Ok(outcome)
+}
+
+/// Fails with `insufficientSpace` when `dest` is on a volume with less free space than
+/// the files and folders in `sources` take up. Skipped with `ignore`. Measuring the
+/// sources is registered as a "copy" operation in phase "measuring", whose total grows
+/// as they are walked, so a large tree shows progress and `cancel_operation` stops it.
+fn check_copy_space<R: Runtime>(
+    app: &AppHandle<R>,
+    operations: &OperationRegistry,
+    sources: &[PathBuf],
+    dest: &std::path::Path,
+    ignore: bool,
+) -> Result<(), CommandError> {
+    if ignore {
+        return Ok(());
+    }
+    let (op_id, cancel) = operations.register("copy", vec![dest.to_path_buf()]);
+    let progress = |_, bytes| {
+        operations.update(op_id, 0, bytes, "");
+        if let Some(info) = operations.update_phase(op_id, "measuring", "") {
+            let _ = app.emit(EVENT_OPERATION_PROGRESS, info);
+        }
+    };
+    let totals = scan::measure_paths(sources, &IgnoreRules::new(&[]), &cancel, &progress);
+    operations.finish(op_id);
+    let totals = totals.map_err(|e| cancelled_or(&cancel, e))?;
+    disk::ensure_space(&disk::SystemSpace, dest, totals.bytes)
+}
+
+/// Runs `copy` with fileops options made from `options`. A verified copy is registered as a "copy"
+/// operation, and every file it verifies is reported as `operation-progress` with
+/// phase "verifying"; a file that reads back differently fails the copy with
+/// `verificationFailed` and is removed, leaving any entry it was to replace in place.
+fn run_copy<T, R: Runtime>(
+    app: &AppHandle<R>,
+    operations: &OperationRegistry,
+    dest_dir: &std::path::Path,
+    options: CopyEntryOptions,
+    copy: impl FnOnce(&fileops::CopyOptions) -> Result<T, CommandError>,
+) -> Result<T, CommandError> {
+    let copy_options = fileops::CopyOptions {
+        preserve_metadata: options.preserve_metadata,
+        fail_fast: options.fail_fast,
+        ..Default::default()
+    };
+    if !options.verify {
+        return copy(&copy_options);
+    }
+
+    let (op_id, _cancel) = operations.register("copy", vec![dest_dir.to_path_buf()]);
+    let on_verify = |path: &std::path::Path| {
+        let message = path.display().to_string();
+        if let Some(info) = operations.update_phase(op_id, "verifying", &message) {
+            let _ = app.emit(EVENT_OPERATION_PROGRESS, info);
+        }
+    };
+    let result = copy(&fileops::CopyOptions {
+        verify: true,
+        on_verify: Some(&on_verify),
+        ..copy_options
+    });
+    operations.finish(op_id);
+    result
+}
+
+/// Copies a file or directory next to itself as `name copy.ext`, `name copy 2.ext`, ...
+/// and returns the new path.
+#[tauri::command]
+pub async fn duplicate_entry(
+    path: String,
+    state: State<'_, AppState>,
+) -> Result<String, CommandError> {
+    ensure_writable(&state)?;
+    let src = PathBuf::from(&path);
+
+    if std::fs::symlink_metadata(&src).is_err() {
+        return Err(CommandError::not_found(
+            format!("Source does not exist: {}", path),
+            &src,
+        ));
+    }
+    check_allowed(&state, &[&src], &[])?;
+
+    let journal = Arc::clone(&state.undo_journal);
+    tokio::task::spawn_blocking(move || {
+        let dest = fileops::duplicate_entry(&src)?;
+        let action = UndoAction::Copy { dest: dest.clone() };
+        journal.lock_or_recover().record(action);
+        Ok(dest.to_string_lossy().to_string())
+    })
+    .await
+    .map_err(task_failed)?
+}
+
+/// Copies a file or directory, overwriting destination if it exists. `options` are as
+/// for `copy_entry`.
+#[tauri::command]
+pub async fn copy_entry_overwrite<R: Runtime>(
+    source_path: String,
+    dest_dir: String,
+    options: Option<CopyEntryOptions>,
+    app: AppHandle<R>,
+    state: State<'_, AppState>,
+) -> Result<fileops::CopyOutcome, CommandError> {
+    let options = options.unwrap_or_default();
+    if !options.dry_run {
+        ensure_writable(&state)?;
+    }
+    let src = PathBuf::from(&source_path);
+    let dst = PathBuf::from(&dest_dir);
+
+    if std::fs::symlink_metadata(&src).is_err() {
+        return Err(CommandError::not_found(
+            format!("Source does not exist: {}", source_path),
+            &src,
+        ));
+    }
+    if !dst.is_dir() {
+        return Err(CommandError::invalid_argument(format!(
+            "Destination is not a directory: {}",
+            dest_dir
+        )));
+    }
+    check_allowed(&state, &[&src], &[&dst])?;
+
+    if options.dry_run {
+        return tokio::task::spawn_blocking(move || {
+            let policy = fileops::ConflictPolicy::Overwrite;
+            fileops::plan_copy_on_conflict(&src, &dst, policy).map(fileops::CopyOutcome::planned)
+        })
+        .await
+        .map_err(task_failed)?;
+    }
+    let operations = Arc::clone(&state.operations);
+    let journal = Arc::clone(&state.undo_journal);
+    tokio::task::spawn_blocking(move || {
+        check_copy_space(
+            &app,
+            &operations,
+            std::slice::from_ref(&src),
+            &dst,
+            options.ignore_space_check,
+        )?;
+        let dest = src.file_name().map_or(dst.clone(), |name| dst.join(name));
+        journal_copy(&journal, &dest, || {
+            run_copy(&app, &operations, &dst, options, |copy_options| {
+                fileops::copy_entry_overwrite(&src, &dst, copy_options)
+            })
+        })
+    })
+    .await
+    .map_err(task_failed)?
+}
+
+/// Copies one entry from `from_side` ("left" or "right") to the same relative path under
+/// the other root, creating missing parent directories and overwriting what is there.
+/// Cached statuses of directory pairs containing the entry are dropped, and the stored
+/// compare result is updated for it as described for `delete_entry`. `verify` and
+/// `ignore_space_check` are as for `copy_entry`; failing entries of a folder end up in
+/// `skipped` as they do there.
+#[tauri::command]
+pub async fn copy_rel_entry<R: Runtime>(
+    from_side: String,
+    rel_path: String,
+    verify: Option<bool>,
+    ignore_space_check: Option<bool>,
+    app: AppHandle<R>,
+    state: State<'_, AppState>,
+) -> Result<fileops::CopyOutcome, CommandError> {
+    ensure_writable(&state)?;
+    let (src_root, dest_root) = rel_copy_roots(&state, &from_side)?;
+
+    let src = src_root.join(&rel_path);
+    let baseline = baseline_before_operation(&app, vec![dest_root.join(&rel_path)]).await;
+    let operations = Arc::clone(&state.operations);
+    let journal = Arc::clone(&state.undo_journal);
+    let handle = app.clone();
+    let outcome = tokio::task::spawn_blocking(move || {
+        let (checked_src, dest) = fileops::plan_rel_copy(&src_root, &dest_root, &rel_path)?;
+        let options = CopyEntryOptions {
+            verify: verify.unwrap_or(false),
+            ignore_space_check: ignore_space_check.unwrap_or(false),
+            ..Default::default()
+        };
+        check_copy_space(&app, &operations, &[checked_src], &dest, options.ignore_space_check)?;
+        journal_copy(&journal, &dest, || {
+            run_copy(&app, &operations, &dest_root, options, |copy_options| {
+                fileops::copy_rel_entry(&src_root, &dest_root, &rel_path, copy_options)
+            })
+        })
+    })
+    .await
+    .map_err(task_failed)??;
+
+    invalidate_dir_cache(&state.dir_resolve_cache, &[&src, &outcome.dest]);
+    invalidate_dir_sizes(&state.dir_size_cache, &[&src, &outcome.dest]);
+    refresh_after_operation(&handle, vec![outcome.dest.clone()], baseline).await;
+    Ok(outcome)
+}
+
+/// Checks whether two files, anywhere, have the same contents. Files of different sizes
+/// are answered without reading; otherwise both are read until the first differing
+/// byte. `cancel_compare_files` stops the read.
+#[tauri::command]
+pub async fn compare_files(
+    left_path: String,
+    right_path: String,
+    state: State<'_, AppState>,
+) -> Result<FileComparison, CommandError> {
+    state
+        .file_compare_job
+        .run(move |cancel| verify::compare_files(left_path.as_ref(), right_path.as_ref(), cancel))
+        .await?
+}
+
+/// Cancels a running `compare_files`.
+#[tauri::command]
+pub async fn cancel_compare_files(state: State<'_, AppState>) -> Result<(), CommandError> {
+    state.file_compare_job.cancel();
+    Ok(())
+}
+
+/// Hashes each file in `paths` with `algorithm` ("sha256" or "xxh3"), reading in chunks.
+/// Directories are not hashed: they, like unreadable files, come back in the report's
+/// `errors` with the reason. Emits `checksum-progress` while hashing files of a few MB or
+/// more; `cancel_checksums` stops it.
+#[tauri::command]
+pub async fn compute_checksums(
+    paths: Vec<String>,
+    algorithm: String,
+    app: AppHandle,
+    state: State<'_, AppState>,
+) -> Result<ChecksumReport, CommandError> {
+    let algorithm = HashAlgorithm::parse(&algorithm)?;
+    state
+        .checksum_job
+        .run(move |cancel| {
+            checksum::compute_checksums(&paths, algorithm, cancel, &|path, hashed, total| {
+                let _ = app.emit(
+                    EVENT_CHECKSUM_PROGRESS,
+                    ChecksumProgressPayload {
+                        path: path.to_string(),
+                        bytes_hashed: hashed,
+                        total_bytes: total,
+                    },
+                );
+            })
+            .map_err(|e| cancelled_or(cancel, e))
+        })
+        .await?
+}
+
+/// Cancels a running `compute_checksums`.
+#[tauri::command]
+pub async fn cancel_checksums(state: State<'_, AppState>) -> Result<(), CommandError> {
+    state.checksum_job.cancel();
+    Ok(())
+}
+
+/// Line-by-line diff of `rel_path` between the compare roots, for the diff view's preview.
+/// Binary and oversized files are flagged rather than diffed; past the `diffMaxLines`
+/// setting, hunks come with headers and counts only.
+#[tauri::command]
+pub async fn diff_file_contents(
+    rel_path: String,
+    state: State<'_, AppState>,
+) -> Result<FileDiff, CommandError> {
+    let (left_root, right_root) = state.compared_roots()?;
+    let options = DiffOptions {
+        max_lines: settings::load_settings().diff_max_lines(),
+        ..DiffOptions::default()
+    };
+    tokio::task::spawn_blocking(move || {
+        // Two file roots are the compare's one entry
+        if is_plain_file(&left_root) && is_plain_file(&right_root) {
+            return textdiff::diff_files(&left_root, &right_root, &options);
+        }
+        textdiff::diff_rel_file(&left_root, &right_root, &rel_path, &options)
+    })
+    .await
+    .map_err(task_failed)?
+}
+
+/// Whether a cached directory status or size for `dir` may be stale after `path`
+/// changed: `dir` contains it, or lies inside it, as when a folder was replaced.
+fn affected_by(dir: &str, path: &Path) -> bool {
+    path.starts_with(dir) || Path::new(dir).starts_with(path)
+}
+
+/// Drops cached directory statuses whose left or right path contains any of `paths`,
+/// or lies under one of them.
+fn invalidate_dir_cache(cache: &Mutex<DirResolveCache>, paths: &[&std::path::Path]) {
+    cache.lock_or_recover().retain(|(left, right)| {
+        !paths
+            .iter()
+            .any(|p| affected_by(left, p) || affected_by(right, p))
+    });
+}
+
+/// Drops cached sizes of directories containing any of `paths`, or under one of them.
+/// A folder's own mtime does not change when something deeper below it does.
+fn invalidate_dir_sizes(cache: &Mutex<DirSizeCache>, paths: &[&std::path::Path]) {
+    cache
+        .lock_or_recover()
+        .retain(|dir| !paths.iter().any(|p| affected_by(dir, p)));
+}
+
+/// Where `move_entry` put an entry, or with `dry_run` where it would go.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MoveOutcome {
+    pub dest: String,
+    /// What the dry run found the move would do; `None` when it was made
+    pub plan: Option<fileops::EntryPlan>,
+}
+
+/// Moves a file or directory from source to the destination directory and returns
+/// where it ended up. `conflict` is as for `copy_entry`, except that "merge" is refused.
+/// The stored compare result is updated for both places as described for
+/// `delete_entry`. With `dry_run`, nothing is moved and the outcome's `plan` says what
+/// would be, as for `copy_entry`. The move is journaled for undo unless it replaced an
+/// existing entry.
+#[tauri::command]
+pub async fn move_entry<R: Runtime>(
+    source_path: String,
+    dest_dir: String,
+    conflict: Option<String>,
+    dry_run: Option<bool>,
+    app: AppHandle<R>,
+    state: State<'_, AppState>,
+) -> Result<MoveOutcome, CommandError> {
+    let dry_run = dry_run.unwrap_or(false);
+    if !dry_run {
+        ensure_writable(&state)?;
+    }
+    let src = PathBuf::from(&source_path);
+    let dst = PathBuf::from(&dest_dir);
+
+    if std::fs::symlink_metadata(&src).is_err() {
+        return Err(CommandError::not_found(
+            format!("Source does not exist: {}", source_path),
+            &src,
+        ));
+    }
+    if !dst.is_dir() {
+        return Err(CommandError::invalid_argument(format!(
+            "Destination is not a directory: {}",
+            dest_dir
+        )));
+    }
+    check_allowed(&state, &[&src], &[&dst])?;
+
+    let policy = conflict_policy(conflict.as_deref())?;
+    if dry_run {
+        let plan = tokio::task::spawn_blocking(move || {
+            fileops::plan_move_on_conflict(&src, &dst, policy)
+        })
+        .await
+        .map_err(task_failed)??;
+        return Ok(MoveOutcome {
+            dest: plan.destination.to_string_lossy().to_string(),
+            plan: Some(plan),
+        });
+    }
+    // Where it lands unless a conflict renames it; the refresh compares everything again
+    // when it lands elsewhere
+    let landing = src.file_name().map(|name| dst.join(name));
+    // Moving back could not bring back what an overwrite replaced
+    let replaced = landing
+        .as_ref()
+        .is_some_and(|landing| std::fs::symlink_metadata(landing).is_ok());
+    let baseline = baseline_before_operation(
+        &app,
+        [Some(src.clone()), landing.clone()].into_iter().flatten().collect(),
+    )
+    .await;
+    let dest = tokio::task::spawn_blocking({
+        let src = src.clone();
+        move || fileops::move_entry_on_conflict(&src, &dst, policy)
+    })
+    .await
+    .map_err(task_failed)??;
+    refresh_after_operation(&app, vec![src.clone(), dest.clone()], baseline).await;
+    if !replaced || landing.as_ref() != Some(&dest) {
+        let action = UndoAction::Move {
+            from: src,
+            to: dest.clone(),
+        };
+        state.undo_journal.lock_or_recover().record(action);
+    }
+    Ok(MoveOutcome {
+        dest: dest.to_string_lossy().to_string(),
+        plan: None,
+    })
+}
+
+/// Parses a `conflict` argument; fail when omitted.
+fn conflict_policy(conflict: Option<&str>) -> Result<fileops::ConflictPolicy, CommandError> {
+    conflict.map_or(Ok(fileops::ConflictPolicy::Fail), fileops::ConflictPolicy::parse)
+}
+
+/// Packs files and folders into a new archive at `dest_path`; `format` is "zip" or
+/// "tar.gz". Runs as an "archive" operation reporting the bytes stored and the current
+/// entry, and can be cancelled via `cancel_operation`, which removes the partial
+/// archive. `conflict` is as for `copy_entry`, except that "merge" is rejected.
+#[tauri::command]
+pub async fn create_archive<R: Runtime>(
+    sources: Vec<String>,
+    dest_path: String,
+    format: String,
+    conflict: Option<String>,
+    app: AppHandle<R>,
+    state: State<'_, AppState>,
+) -> Result<ArchiveReport, CommandError> {
+    ensure_writable(&state)?;
+    let format = ArchiveFormat::parse(&format)?;
+    let policy = conflict_policy(conflict.as_deref())?;
+    let sources: Vec<PathBuf> = sources.into_iter().map(PathBuf::from).collect();
+    let dest = PathBuf::from(dest_path);
+    let mut entries: Vec<&std::path::Path> = sources.iter().map(PathBuf::as_path).collect();
+    entries.push(&dest);
+    check_allowed(&state, &entries, &[])?;
+
+    let operations = Arc::clone(&state.operations);
+    tokio::task::spawn_blocking(move || {
+        let (op_id, cancel) = operations.register("archive", vec![dest.clone()]);
+        let result =
+            archive::create_archive(&sources, &dest, format, policy, &cancel, &|done, total, name| {
+                if let Some(info) = operations.update(op_id, done, total, name) {
+                    let _ = app.emit(EVENT_OPERATION_PROGRESS, info);
+                }
+            });
+        operations.finish(op_id);
+        result
+    })
+    .await
+    .map_err(task_failed)?
+}
+
+/// Unpacks a zip or tar.gz archive (recognized by content) into `dest_dir`, as an
+/// "extract" operation that reports progress and can be cancelled. Entries that would
+/// land outside `dest_dir` are rejected and listed rather than written. `conflict`
+/// decides about existing files: "fail" (default) skips them, "overwrite" and "merge"
+/// replace them, "rename" extracts next to them.
+#[tauri::command]
+pub async fn extract_archive<R: Runtime>(
+    archive_path: String,
+    dest_dir: String,
+    conflict: Option<String>,
+    app: AppHandle<R>,
+    state: State<'_, AppState>,
+) -> Result<ExtractReport, CommandError> {
+    ensure_writable(&state)?;
+    let policy = conflict_policy(conflict.as_deref())?;
+    let archive = PathBuf::from(archive_path);
+    let dest = PathBuf::from(dest_dir);
+    check_allowed(&state, &[&archive], &[&dest])?;
+
+    let operations = Arc::clone(&state.operations);
+    tokio::task::spawn_blocking(move || {
+        let (op_id, cancel) = operations.register("extract", vec![dest.clone()]);
+        let progress = |done, total, name: &str| {
+            if let Some(info) = operations.update(op_id, done, total, name) {
+                let _ = app.emit(EVENT_OPERATION_PROGRESS, info);
+            }
+        };
+        let result = archive::extract_archive(&archive, &dest, policy, &cancel, &progress);
+        operations.finish(op_id);
+        result
+    })
+    .await
+    .map_err(task_failed)?
+}
+
+/// Creates a new directory inside parent_path with the given name.
+#[tauri::command]
+pub async fn create_directory(
+    parent_path: String,
+    name: String,
+    state: State<'_, AppState>,
+) -> Result<(), CommandError> {
+    ensure_writable(&state)?;
+    let parent = PathBuf::from(&parent_path);
+
+    if !parent.is_dir() {
+        return Err(CommandError::invalid_argument(format!(
+            "Parent is not a directory: {}",
+            parent_path
+        )));
+    }
+    check_allowed(&state, &[], &[&parent])?;
+
+    let path = tokio::task::spawn_blocking(move || fileops::create_directory(&parent, &name))
+        .await
+        .map_err(task_failed)??;
+    state
+        .undo_journal
+        .lock_or_recover()
+        .record(UndoAction::CreateDirectory { path });
+    Ok(())
+}
+
+/// Creates a file named `name` inside parent_path, empty unless `initial_content` is
+/// given, and returns its absolute path. Fails if anything of that name exists.
+#[tauri::command]
+pub async fn create_file(
+    parent_path: String,
+    name: String,
+    initial_content: Option<String>,
+    state: State<'_, AppState>,
+) -> Result<String, CommandError> {
+    ensure_writable(&state)?;
+    let parent = PathBuf::from(&parent_path);
+
+    if !parent.is_dir() {
+        return Err(CommandError::invalid_argument(format!(
+            "Parent is not a directory: {}",
+            parent_path
+        )));
+    }
+    check_allowed(&state, &[], &[&parent])?;
+
+    let journal = Arc::clone(&state.undo_journal);
+    tokio::task::spawn_blocking(move || {
+        let path = fileops::create_file(&parent, &name, initial_content.as_deref())?;
+        let action = UndoAction::CreateFile { path: path.clone() };
+        journal.lock_or_recover().record(action);
+        Ok(path.to_string_lossy().to_string())
+    })
+    .await
+    .map_err(task_failed)?
+}
+
+/// Renames a file or directory in place. Supports case-only renames.
+#[tauri::command]
+pub async fn rename_entry(
+    path: String,
+    new_name: String,
+    state: State<'_, AppState>,
+) -> Result<(), CommandError> {
+    ensure_writable(&state)?;
+    let src = PathBuf::from(&path);
+
+    if std::fs::symlink_metadata(&src).is_err() {
+        return Err(CommandError::not_found(format!("Does not exist: {}", path), &src));
+    }
+    check_allowed(&state, &[&src], &[])?;
+
+    let dest = tokio::task::spawn_blocking({
+        let src = src.clone();
+        move || fileops::rename_entry(&src, &new_name)
+    })
+    .await
+    .map_err(task_failed)??;
+    if dest != src {
+        let action = UndoAction::Rename { from: src, to: dest };
+        state.undo_journal.lock_or_recover().record(action);
+    }
+    Ok(())
+}
+
+/// Sets the permission bits of a file or directory to `mode`, e.g. `0o755`, and with
+/// `recursive` of everything inside it. `conditional_execute` gives the execute bits
+/// only to folders and already executable files, as `chmod -R a+X` does. On Windows
+/// only the read-only attribute follows `mode`. Entries inside that fail are listed in
+/// the outcome's `errors` while the rest are changed.
+#[tauri::command]
+pub async fn set_permissions(
+    path: String,
+    mode: u32,
+    recursive: bool,
+    conditional_execute: Option<bool>,
+    state: State<'_, AppState>,
+) -> Result<fileops::PermissionsOutcome, CommandError> {
+    ensure_writable(&state)?;
+    let target = PathBuf::from(&path);
+
+    if std::fs::symlink_metadata(&target).is_err() {
+        return Err(CommandError::not_found(format!("Does not exist: {}", path), &target));
+    }
+    check_allowed(&state, &[&target], &[])?;
+
+    let conditional_execute = conditional_execute.unwrap_or(false);
+    tokio::task::spawn_blocking(move || {
+        fileops::set_permissions(&target, mode, recursive, conditional_execute)
+    })
+    .await
+    .map_err(task_failed)?
+}
+
+/// Sets the modification time of a file, directory or symlink (the link itself) to
+/// `modified_ms`, in epoch milliseconds. Returns the entry's new metadata, so a listing
+/// or comparison row can be patched without reloading.
+#[tauri::command]
+pub async fn set_modified(
+    path: String,
+    modified_ms: u64,
+    state: State<'_, AppState>,
+) -> Result<EntryMeta, CommandError> {
+    ensure_writable(&state)?;
+    let target = PathBuf::from(path);
+    check_allowed(&state, &[&target], &[])?;
+    tokio::task::spawn_blocking(move || fileops::set_modified(&target, modified_ms))
+        .await
+        .map_err(task_failed)?
+}
+
+/// Gives `to_path` the access and modification times of `from_path`, e.g. to make a
+/// pair that differs only in time compare equal. Links are not followed on either
+/// side. Returns the new metadata of `to_path`.
+#[tauri::command]
+pub async fn copy_times(
+    from_path: String,
+    to_path: String,
+    state: State<'_, AppState>,
+) -> Result<EntryMeta, CommandError> {
+    ensure_writable(&state)?;
+    let (from, to) = (PathBuf::from(from_path), PathBuf::from(to_path));
+    check_allowed(&state, &[&from, &to], &[])?;
+    tokio::task::spawn_blocking(move || fileops::copy_times(&from, &to))
+        .await
+        .map_err(task_failed)?
+}
+
+/// Deletes a file or directory (recursively for directories). When it lay under the
+/// roots of the stored compare result, that path is compared again and the result
+/// updated in place, announced with `diffs-updated`. With `dry_run`, nothing is deleted
+/// and the plan of what would be is returned; otherwise `None`.
+#[tauri::command]
+pub async fn delete_entry<R: Runtime>(
+    target_path: String,
+    dry_run: Option<bool>,
+    app: AppHandle<R>,
+    state: State<'_, AppState>,
+) -> Result<Option<fileops::EntryPlan>, CommandError> {
+    let dry_run = dry_run.unwrap_or(false);
+    if !dry_run {
+        ensure_writable(&state)?;
+    }
+    let target = PathBuf::from(&target_path);
+
+    // Not `exists()`, which follows links: a dangling link can still be deleted
+    if std::fs::symlink_metadata(&target).is_err() {
+        return Err(CommandError::not_found(
+            format!("Does not exist: {}", target_path),
+            &target,
+        ));
+    }
+    check_allowed(&state, &[&target], &[])?;
+    if dry_run {
+        return tokio::task::spawn_blocking(move || fileops::plan_delete(&target).map(Some))
+            .await
+            .map_err(task_failed)?;
+    }
+
+    let baseline = baseline_before_operation(&app, vec![target.clone()]).await;
+    let deleted = target.clone();
+    tokio::task::spawn_blocking(move || fileops::delete_entry(&deleted))
+        .await
+        .map_err(task_failed)??;
+    refresh_after_operation(&app, vec![target], baseline).await;
+    Ok(None)
+}
+
+/// Operations `undo_last_operation` can reverse, newest first: moves, renames, copies
+/// to a destination that did not exist, and created files and folders. Deletes cannot
+/// be undone. The journal holds the last 50 and is not kept across restarts.
+#[tauri::command]
+pub async fn get_undo_stack(state: State<'_, AppState>) -> Result<Vec<UndoEntry>, CommandError> {
+    Ok(state.undo_journal.lock_or_recover().entries())
+}
+
+/// Reverses the newest journaled operation and returns it: moves and renames are put
+/// back, copies and created files deleted, created folders removed if still empty.
+/// Anything changed since is left alone and the undo fails saying why; the entry then
+/// stays in the journal. Fails with `busy` while another undo is running.
+#[tauri::command]
+pub async fn undo_last_operation(state: State<'_, AppState>) -> Result<UndoEntry, CommandError> {
+    ensure_writable(&state)?;
+    let journal = Arc::clone(&state.undo_journal);
+    let entry = journal.lock_or_recover().claim_last()?;
+    let id = entry.id;
+    if let Err(e) = check_allowed(&state, &entry.action.paths(), &[]) {
+        journal.lock_or_recover().release(id, false);
+        return Err(e);
+    }
+
+    let undone = {
+        let journal = Arc::clone(&journal);
+        tokio::task::spawn_blocking(move || {
+            let result = undo::undo(&entry);
+            journal.lock_or_recover().release(entry.id, result.is_ok());
+            result.map(|()| entry)
+        })
+        .await
+    };
+    undone.map_err(|e| {
+        journal.lock_or_recover().release(id, false);
+        task_failed(e)
+    })?
+}
+
+fn state_file_path() -> Result<PathBuf, String> {
+    Ok(settings::app_data_dir()?.join("state.json"))
+}
+
+#[tauri::command]
+pub async fn load_app_state() -> Result<Option<PersistedState>, CommandError> {
+    let path = state_file_path()?;
+    if !path.exists() {
+        return Ok(None);
+    }
+    let contents = std::fs::read_to_string(&path).map_err(|e| e.to_string())?;
+    Ok(restored_state(&contents, dirs::home_dir().as_deref()))
+}
+
+/// The saved state to restore from `contents`, with tabs whose folder is gone opened
+/// at `home` instead. `None` only when the text cannot be read as a state at all.
+fn restored_state(contents: &str, home: Option<&Path>) -> Option<PersistedState> {
+    let mut state = PersistedState::parse(contents)?;
+    let home = home.map_or_else(|| "/".to_string(), |home| home.to_string_lossy().into_owned());
+    state.replace_missing_roots(&home);
+    Some(state)
+}
+
+/// Saves the pane state, keeping the recent locations and favorites stored alongside.
+/// Pane fields left out of `state`, like the sort and history of a frontend that does
+/// not send them, keep their saved values.
+#[tauri::command]
+pub async fn save_app_state(
+    state: serde_json::Map<String, serde_json::Value>,
+) -> Result<(), CommandError> {
+    let path = state_file_path()?;
+    let saved = std::fs::read_to_string(&path)
+        .ok()
+        .and_then(|contents| PersistedState::parse(&contents));
+    let mut state =
+        PersistedState::with_saved(saved, state).map_err(CommandError::invalid_argument)?;
+    state.prepare_for_save();
+    // The window geometry is `save_window_state`'s to write
+    state.window = None;
+    let fields = serde_json::to_value(&state).map_err(|e| e.to_string())?;
+    Ok(locations::merge_into_state_file(&path, fields)?)
+}
+
+/// Saves the main window's outer position and inner size, in physical pixels. The frontend
+/// calls this, debounced, as the window moves or resizes.
+#[tauri::command]
+pub async fn save_window_state(
+    x: i32,
+    y: i32,
+    width: u32,
+    height: u32,
+    maximized: bool,
+) -> Result<(), CommandError> {
+    let window = WindowState {
+        x,
+        y,
+        width,
+        height,
+        maximized,
+    };
+    let fields = serde_json::json!({ "window": window });
+    Ok(locations::merge_into_state_file(&state_file_path()?, fields)?)
+}
+
+/// The saved window geometry, for the frontend to apply before showing the window.
+/// `None` when nothing was saved, or when the window would not be reachable on the
+/// monitors connected now, e.g. after the one it was on was unplugged; the window
+/// then keeps its default size and position.
+#[tauri::command]
+pub async fn load_window_state(app: AppHandle) -> Result<Option<WindowState>, CommandError> {
+    let path = state_file_path()?;
+    let Some(window) = std::fs::read_to_string(&path)
+        .ok()
+        .and_then(|contents| PersistedState::parse(&contents))
+        .and_then(|state| state.window)
+    else {
+        return Ok(None);
+    };
+    let monitors: Vec<ScreenRect> = app
+        .available_monitors()
+        .unwrap_or_default()
+        .iter()
+        .map(|m| ScreenRect {
+            x: m.position().x,
+            y: m.position().y,
+            width: m.size().width,
+            height: m.size().height,
+        })
+        .collect();
+    Ok(Some(window).filter(|w| w.is_reachable_on(&monitors)))
+}
+
+/// Applies `change` to the recent locations and favorites, saving them when it reports
+/// a change. Returns what exists of them afterwards.
+fn update_locations(
+    locations: &Mutex<Option<Locations>>,
+    change: impl FnOnce(&mut Locations) -> bool,
+) -> Result<Locations, CommandError> {
+    let file = state_file_path()?;
+    let mut guard = locations.lock_or_recover();
+    let locations = guard.get_or_insert_with(|| Locations::load(&file));
+    if change(locations) {
+        locations.save(&file)?;
+    }
+    Ok(locations.existing())
+}
+
+/// Puts a folder just listed or made a root at the front of the recent locations. The
+/// state file is written on a blocking thread, so navigating never waits for it, and
+/// failing to save never fails the listing.
+fn record_recent(state: &AppState, path: String) {
+    let locations = Arc::clone(&state.locations);
+    tokio::task::spawn_blocking(move || {
+        let _ = update_locations(&locations, |locations| locations.record(&path));
+    });
+}
+
+/// Recently visited folders (most recent first, up to `RECENT_PATHS_CAPACITY`) and
+/// favorites. Ones that do not exist right now are left out but kept, in case their
+/// volume comes back.
+#[tauri::command]
+pub async fn get_locations(state: State<'_, AppState>) -> Result<Locations, CommandError> {
+    update_locations(&state.locations, |_| false)
+}
+
+/// Adds `path` to the favorites, named `name` or else after the folder; a favorite
+/// already there is renamed. Returns the locations as `get_locations` does.
+#[tauri::command]
+pub async fn add_favorite(
+    path: String,
+    name: Option<String>,
+    state: State<'_, AppState>,
+) -> Result<Locations, CommandError> {
+    let path = PathBuf::from(path);
+    let name = name
+        .filter(|n| !n.trim().is_empty())
+        .or_else(|| path.file_name().map(|n| n.to_string_lossy().into_owned()))
+        .unwrap_or_else(|| path.to_string_lossy().into_owned());
+    let path = path.to_string_lossy().into_owned();
+    update_locations(&state.locations, |locations| {
+        locations.add_favorite(name, path);
+        true
+    })
+}
+
+/// Removes the favorite pointing at `path`. Returns the locations as `get_locations`
+/// does.
+#[tauri::command]
+pub async fn remove_favorite(
+    path: String,
+    state: State<'_, AppState>,
+) -> Result<Locations, CommandError> {
+    update_locations(&state.locations, |locations| locations.remove_favorite(&path))
+}
+
+#[tauri::command]
+pub async fn load_settings() -> Result<Settings, CommandError> {
+    Ok(settings::read_settings()?)
+}
+
+#[tauri::command]
+pub async fn save_settings(
+    mut settings: Settings,
+    state: State<'_, AppState>,
+) -> Result<(), CommandError> {
+    settings.sessions = settings::read_settings()?.sessions;
+    settings::save_settings(&settings)?;
+    apply_settings(&state, &settings);
+    Ok(())
+}
+
+/// Applies the settings that live in `AppState`, at startup and after each save.
+pub fn apply_settings(state: &AppState, settings: &Settings) {
+    let mut cache = state.dir_resolve_cache.lock_or_recover();
+    state.read_only.store(settings.read_only, Ordering::Relaxed);
+    *state.shell.lock_or_recover() = settings.shell.clone();
+    state
+        .terminal_scrollback_bytes
+        .store(settings.terminal_scrollback_bytes(), Ordering::Relaxed);
+    cache.set_max_age(settings.dir_cache_max_age());
+    cache.set_capacity(settings.dir_cache_capacity());
+    let mut sizes = state.dir_size_cache.lock_or_recover();
+    sizes.set_max_age(settings.dir_cache_max_age());
+    sizes.set_capacity(settings.dir_cache_capacity());
+}
+
+/// Turns read-only mode on or off and saves it in the settings, so it survives a
+/// restart. While it is on, every command that changes files fails with
+/// `readOnlyMode`, and so do starting and typing into a terminal; browsing, comparing
+/// and exporting reports keep working.
+#[tauri::command]
+pub async fn set_read_only(enabled: bool, state: State<'_, AppState>) -> Result<(), CommandError> {
+    // Takes effect even if the settings file cannot be written
+    state.read_only.store(enabled, Ordering::Relaxed);
+    let mut settings = settings::read_settings()?;
+    settings.read_only = enabled;
+    settings::save_settings(&settings)?;
+    Ok(())
+}
+
+/// Saves the compared roots under `name`, with the compare mode, ignore patterns and pane
+/// options the frontend has in effect. A session already saved under that name is
+/// replaced.
+#[tauri::command]
+pub async fn save_session(
+    name: String,
+    options: Option<SessionOptions>,
+    state: State<'_, AppState>,
+) -> Result<(), CommandError> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err(CommandError::invalid_argument("Session name is empty"));
+    }
+    let (left_root, right_root) = state.compared_roots()?;
+    let session = Session {
+        name: name.to_string(),
+        left_root: left_root.to_string_lossy().into_owned(),
+        right_root: right_root.to_string_lossy().into_owned(),
+        options: options.unwrap_or_default(),
+    };
+    let mut settings = settings::read_settings()?;
+    settings.put_session(session);
+    Ok(settings::save_settings(&settings)?)
+}
+
+#[tauri::command]
+pub async fn list_sessions() -> Result<Vec<Session>, CommandError> {
+    Ok(settings::read_settings()?.sessions)
+}
+
+/// Makes a saved session's folders the roots of tabs `left:0` and `right:0`, which are
+/// compared from then on, and returns it, for the frontend to apply its options. Fails
+/// with `notFound`, naming the side, when either root is gone or is no longer a folder
+/// or file; both are checked first, so the roots are then left as they were.
+#[tauri::command]
+pub async fn load_session(
+    name: String,
+    state: State<'_, AppState>,
+) -> Result<Session, CommandError> {
+    let name = name.trim();
+    let session = settings::read_settings()?
+        .session(name)
+        .cloned()
+        .ok_or_else(|| CommandError::invalid_argument(format!("No session named {}", name)))?;
+    check_session_roots(&session)?;
+    replace_root(&state, PaneId::LEFT, PathBuf::from(&session.left_root)).await?;
+    replace_root(&state, PaneId::RIGHT, PathBuf::from(&session.right_root)).await?;
+    let compared = (PaneId::LEFT, PaneId::RIGHT);
+    if *state.compared.lock_or_recover() != compared {
+        state.compare_job.stop().await;
+        *state.compared.lock_or_recover() = compared;
+    }
+    Ok(session)
+}
+
+fn check_session_roots(session: &Session) -> Result<(), CommandError> {
+    let missing: Vec<(&str, &str)> = [("left", &session.left_root), ("right", &session.right_root)]
+        .into_iter()
+        .filter(|(_, root)| check_root(Path::new(root)).is_err())
+        .map(|(side, root)| (side, root.as_str()))
+        .collect();
+    match missing[..] {
+        [] => Ok(()),
+        [(side, root)] => Err(CommandError::not_found(
+            format!("The {} folder of session {} no longer exists", side, session.name),
+            Path::new(root),
+        )),
+        _ => Err(CommandError::not_found(
+            format!("Both folders of session {} no longer exist", session.name),
+            Path::new(&session.left_root),
+        )),
+    }
+}
+
+/// Returns false when there was no session by that name.
+#[tauri::command]
+pub async fn delete_session(name: String) -> Result<bool, CommandError> {
+    let mut settings = settings::read_settings()?;
+    if !settings.remove_session(name.trim()) {
+        return Ok(false);
+    }
+    settings::save_settings(&settings)?;
+    Ok(true)
+}
+
+#[tauri::command]
+pub async fn get_read_only(state: State<'_, AppState>) -> Result<bool, CommandError> {
+    Ok(state.read_only.load(Ordering::Relaxed))
+}
+
+/// Fails with `readOnlyMode` while read-only mode is on.
+fn ensure_writable(state: &AppState) -> Result<(), CommandError> {
+    if state.read_only.load(Ordering::Relaxed) {
+        return Err(CommandError::read_only_mode(
+            "Read-only mode is on; turn it off to change files",
+        ));
+    }
+    Ok(())
+}
+
+// --- Long-running operations ---
+
+/// Lists the background operations currently running.
+#[tauri::command]
+pub async fn list_operations(
+    state: State<'_, AppState>,
+) -> Result<Vec<OperationInfo>, CommandError> {
+    Ok(state.operations.list())
+}
+
+/// Requests cancellation of a running background operation.
+#[tauri::command]
+pub async fn cancel_operation(id: u64, state: State<'_, AppState>) -> Result<(), CommandError> {
+    if state.operations.cancel(id) {
+        Ok(())
+    } else {
+        Err(format!("No running operation with id {}", id).into())
+    }
+}
+
+/// Prunes oversized caches and stale temp/quarantine files in the app data directory.
+#[tauri::command]
+pub async fn run_maintenance(
+    app: AppHandle,
+    state: State<'_, AppState>,
+) -> Result<MaintenanceReport, CommandError> {
+    let operations = Arc::clone(&state.operations);
+    Ok(
+        tokio::task::spawn_blocking(move || run_maintenance_impl(&app, &operations))
+            .await
+            .map_err(task_failed)??,
+    )
+}
+
+/// Runs maintenance as a registered operation, emitting progress events.
+/// Also used by the on-startup trigger.
+pub fn run_maintenance_impl(
+    app: &AppHandle,
+    operations: &OperationRegistry,
+) -> Result<MaintenanceReport, String> {
+    let data_dir = settings::app_data_dir()?;
+    let (op_id, cancel) = operations.register("maintenance", Vec::new());
+
+    let report = maintenance::run_maintenance(
+        &data_dir,
+        &maintenance::MaintenanceConfig::default(),
+        &|path| operations.references(path),
+        &cancel,
+        &|step, total, category| {
+            if let Some(info) = operations.update(op_id, step as u64, total as u64, category) {
+                let _ = app.emit(EVENT_OPERATION_PROGRESS, info);
+            }
+        },
+    );
+
+    operations.finish(op_id);
+    Ok(report)
+}
+
+/// Verifies that two trees are byte-identical (streamed content comparison). Either
+/// path may be a zip or tar.gz archive, whose files are read without extracting it.
+/// Runs as a registered operation, so it can be cancelled via `cancel_operation`.
+#[tauri::command]
+pub async fn verify_trees(
+    left_path: String,
+    right_path: String,
+    max_mismatches: Option<usize>,
+    app: AppHandle,
+    state: State<'_, AppState>,
+) -> Result<VerifyResult, CommandError> {
+    let left = tree_source(std::path::Path::new(&left_path))?;
+    let right = tree_source(std::path::Path::new(&right_path))?;
+    source::check_root(&*left)?;
+    source::check_root(&*right)?;
+
+    let operations = Arc::clone(&state.operations);
+    tokio::task::spawn_blocking(move || {
+        let (op_id, cancel) = operations.register("verify", Vec::new());
+        let ignore_rules = IgnoreRules::new(&[]);
+        let result = verify::verify_trees(
+            &*left,
+            &*right,
+            &ignore_rules,
+            max_mismatches.unwrap_or(100),
+            &cancel,
+            &|checked, total| {
+                if let Some(info) =
+                    operations.update(op_id, checked as u64, total as u64, "verifying")
+                {
+                    let _ = app.emit(EVENT_OPERATION_PROGRESS, info);
+                }
+            },
+        );
+        operations.finish(op_id);
+        result.map_err(|e| cancelled_or(&cancel, e))
+    })
+    .await
+    .map_err(task_failed)?
+}
+
+/// Result of comparing a single directory level between two paths.
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CompareDirectoryResult {
+    pub entries: Vec<CompareEntry>,
+    pub left_path: String,
+    pub right_path: String,
+    pub summary: CompareSummary,
+    /// Why the left directory could not be listed; its entries are then missing
+    pub error_left: Option<CommandError>,
+    pub error_right: Option<CommandError>,
+}
+
+/// Compares a single directory level from two paths, returning merged entries.
+/// Directories present on both sides use cached status if available, otherwise marked Pending.
+/// Names match case-insensitively and, unless `normalize_unicode` is false, in Unicode NFC.
+/// If one side cannot be listed, the other side's entries come back with that side's
+/// error set; if neither can, this fails.
+/// Entries are ordered like `list_directory`, by `sort_by` and `ascending`; sizes and
+/// times are the left side's where it has the entry. A pair where either entry cannot
+/// be read has status `Error`.
+#[tauri::command]
+pub async fn compare_directory(
+    left_path: String,
+    right_path: String,
+    normalize_unicode: Option<bool>,
+    sort_by: Option<String>,
+    ascending: Option<bool>,
+    state: State<'_, AppState>,
+) -> Result<CompareDirectoryResult, CommandError> {
+    let order = SortOrder::parse(sort_by.as_deref(), ascending)?;
+    let key_mode = scan::KeyMode {
+        normalize_unicode: normalize_unicode.unwrap_or(true),
+        ..Default::default()
+    };
+    let cache = Arc::clone(&state.dir_resolve_cache);
+
+    // Run on blocking thread since dir listing does I/O
+    tokio::task::spawn_blocking(move || {
+        compare_directory_impl(&left_path, &right_path, key_mode, order, &cache)
+    })
+    .await
+    .map_err(task_failed)?
+}
+
+/// Compare one directory level. Dirs on both sides use cache or are marked Pending.
+/// A side that cannot be listed is reported in `error_left`/`error_right` rather than
+/// treated as empty; when both fail, the error names both.
+pub fn compare_directory_impl(
+    left_path: &str,
+    right_path: &str,
+    key_mode: scan::KeyMode,
+    order: SortOrder,
+    cache: &Mutex<DirResolveCache>,
+) -> Result<CompareDirectoryResult, CommandError> {
+    let left_listing = list_directory_impl(left_path, true, SortOrder::default())
+        .map_err(|e| e.map_message(|m| format!("Left side: {}", m)));
+    let right_listing = list_directory_impl(right_path, true, SortOrder::default())
+        .map_err(|e| e.map_message(|m| format!("Right side: {}", m)));
+    let (left_entries, right_entries, error_left, error_right) =
+        match (left_listing, right_listing) {
+            (Ok(l), Ok(r)) => (l, r, None, None),
+            (Ok(l), Err(e)) => (l, Vec::new(), None, Some(e)),
+            (Err(e), Ok(r)) => (Vec::new(), r, Some(e), None),
+            (Err(l), Err(r)) => return Err(l.map_message(|m| format!("{}; {}", m, r))),
+        };
+
+    let left_map: HashMap<String, &BrowseEntry> = left_entries
+        .iter()
+        .map(|e| (key_mode.key(&e.name), e))
+        .collect();
+    let right_map: HashMap<String, &BrowseEntry> = right_entries
+        .iter()
+        .map(|e| (key_mode.key(&e.name), e))
+        .collect();
+
+    let mut all_keys: Vec<String> = left_map
+        .keys()
+        .chain(right_map.keys())
+        .cloned()
+        .collect::<std::collections::HashSet<_>>()
+        .into_iter()
+        .collect();
+    all_keys.sort();
+
+    let mut entries = Vec::new();
+    let mut summary = CompareSummary {
+        total_left: left_entries.len(),
+        total_right: right_entries.len(),
+        ..Default::default()
+    };
+    let (mut seen_only, mut seen_modified) = (SeenLinks::default(), SeenLinks::default());
+    let unique_size = |seen: &mut HashSet<LinkId>, entry: &BrowseEntry| {
+        if first_link(seen, entry.link) {
+            entry.size
+        } else {
+            0
+        }
+    };
+
+    for key in &all_keys {
+        let left = left_map.get(key);
+        let right = right_map.get(key);
+
+        let (name, kind, status, left_size, right_size, left_modified, right_modified, dir_info) =
+            match (left, right) {
+                (Some(l), Some(r)) => {
+                    if l.error.is_some() || r.error.is_some() {
+                        summary.errors += 1;
+                        (
+                            l.name.clone(),
+                            l.kind,
+                            CompareStatus::Error,
+                            Some(l.size),
+                            Some(r.size),
+                            l.modified,
+                            r.modified,
+                            None,
+                        )
+                    } else if l.kind != r.kind {
+                        summary.type_mismatch += 1;
+                        (
+                            l.name.clone(),
+                            l.kind,
+                            CompareStatus::TypeMismatch,
+                            Some(l.size),
+                            Some(r.size),
+                            l.modified,
+                            r.modified,
+                            None,
+                        )
+                    } else if l.kind == EntryKind::Dir {
+                        let sub_left = format!("{}/{}", left_path, l.name);
+                        let sub_right = format!("{}/{}", right_path, r.name);
+                        let cache_key = (sub_left, sub_right);
+                        let cached = cache.lock_or_recover().get(&cache_key);
+                        if let Some(cached_info) = cached {
+                            let cached_status = cached_info.status();
+                            match cached_status {
+                                CompareStatus::Same => summary.same += 1,
+                                CompareStatus::Partial => summary.depth_limited += 1,
+                                _ => summary.meta_diff += 1,
+                            }
+                            (
+                                l.name.clone(),
+                                EntryKind::Dir,
+                                cached_status,
+                                None,
+                                None,
+                                l.modified,
+                                r.modified,
+                                Some(cached_info),
+                            )
+                        } else {
+                            // No cache hit — mark as pending
+                            summary.pending += 1;
+                            (
+                                l.name.clone(),
+                                EntryKind::Dir,
+                                CompareStatus::Pending,
+                                None,
+                                None,
+                                l.modified,
+                                r.modified,
+                                None,
+                            )
+                        }
+                    } else if l.kind.is_special() {
+                        summary.special += 1;
+                        (
+                            l.name.clone(),
+                            l.kind,
+                            CompareStatus::Special,
+                            Some(l.size),
+                            Some(r.size),
+                            l.modified,
+                            r.modified,
+                            None,
+                        )
+                    } else if l.size == r.size {
+                        summary.same += 1;
+                        (
+                            l.name.clone(),
+                            l.kind,
+                            CompareStatus::Same,
+                            Some(l.size),
+                            Some(r.size),
+                            l.modified,
+                            r.modified,
+                            None,
+                        )
+                    } else {
+                        summary.meta_diff += 1;
+                        summary.bytes_modified += l.size.max(r.size);
+                        let l_unique = unique_size(&mut seen_modified.left, l);
+                        let r_unique = unique_size(&mut seen_modified.right, r);
+                        summary.unique_bytes_modified += l_unique.max(r_unique);
+                        (
+                            l.name.clone(),
+                            l.kind,
+                            CompareStatus::Modified,
+                            Some(l.size),
+                            Some(r.size),
+                            l.modified,
+                            r.modified,
+                            None,
+                        )
+                    }
+                }
+                (Some(l), None) => {
+                    summary.only_left += 1;
+                    if l.kind != EntryKind::Dir {
+                        summary.bytes_only_left += l.size;
+                        summary.unique_bytes_only_left += unique_size(&mut seen_only.left, l);
+                    }
+                    (
+                        l.name.clone(),
+                        l.kind,
+                        CompareStatus::OnlyLeft,
+                        Some(l.size),
+                        None,
+                        l.modified,
+                        None,
+                        None,
+                    )
+                }
+                (None, Some(r)) => {
+                    summary.only_right += 1;
+                    if r.kind != EntryKind::Dir {
+                        summary.bytes_only_right += r.size;
+                        summary.unique_bytes_only_right += unique_size(&mut seen_only.right, r);
+                    }
+                    (
+                        r.name.clone(),
+                        r.kind,
+                        CompareStatus::OnlyRight,
+                        None,
+                        Some(r.size),
+                        None,
+                        r.modified,
+                        None,
+                    )
+                }
+                (None, None) => unreachable!(),
+            };
+
+        entries.push(CompareEntry {
+            name,
+            kind,
+            status,
+            left_size,
+            right_size,
+            left_modified,
+            right_modified,
+            dir_info,
+        });
+    }
+
+    fn fields(e: &CompareEntry) -> SortFields<'_> {
+        SortFields {
+            name: &e.name,
+            is_dir: e.kind == EntryKind::Dir,
+            size: e.left_size.or(e.right_size).unwrap_or(0),
+            modified: e.left_modified.or(e.right_modified),
+        }
+    }
+    entries.sort_by(|a, b| order.compare(fields(a), fields(b)));
+
+    Ok(CompareDirectoryResult {
+        entries,
+        left_path: left_path.to_string(),
+        right_path: right_path.to_string(),
+        summary,
+        error_left,
+        error_right,
+    })
+}
+
+/// Recursively compares two directories, counting the entries that differ and summing
+/// each side's file sizes. Entries are matched by `key_mode`, as `compare_directory_impl`
+/// matches them, and filtered by the default ignore rules. Accepts a cancellation flag that is checked between entries;
+/// a cancelled walk returns partial counts.
+///
+/// - `max_depth`: levels below the pair to walk (the pair's own entries are level 1);
+///   directories past it are counted in `depth_limited` instead of walked
+pub fn dirs_are_same_recursive_counted(
+    left_path: &str,
+    right_path: &str,
+    key_mode: scan::KeyMode,
+    max_depth: Option<usize>,
+    cancel: &AtomicBool,
+) -> DirResolveInfo {
+    let mut seen = SeenLinks::default();
+    walk_dir_pair(left_path, right_path, key_mode, max_depth, &mut seen, cancel)
+}
+
+/// Hard-linked files already counted during one walk, per side, so the unique sizes
+/// count each of them once.
+#[derive(Default)]
+struct SeenLinks {
+    left: HashSet<LinkId>,
+    right: HashSet<LinkId>,
+}
+
+fn walk_dir_pair(
+    left_path: &str,
+    right_path: &str,
+    key_mode: scan::KeyMode,
+    max_depth: Option<usize>,
+    seen: &mut SeenLinks,
+    cancel: &AtomicBool,
+) -> DirResolveInfo {
+    let mut info = DirResolveInfo::default();
+    if cancel.load(Ordering::Relaxed) {
+        return info;
+    }
+    if max_depth == Some(0) {
+        info.depth_limited = 1;
+        return info;
+    }
+    let below = max_depth.map(|depth| depth - 1);
+
+    let ignore_rules = IgnoreRules::new(&[]);
+
+    // An unlisted side is not an empty one: the pair is unknown, not different
+    let (left_entries, right_entries) =
+        match (std::fs::read_dir(left_path), std::fs::read_dir(right_path)) {
+            (Ok(left), Ok(right)) => (
+                collect_entries(left, &ignore_rules, cancel),
+                collect_entries(right, &ignore_rules, cancel),
+            ),
+            _ => {
+                info.unreadable = 1;
+                return info;
+            }
+        };
+
+    let left_map: HashMap<String, &BrowseEntry> = left_entries
+        .iter()
+        .map(|e| (key_mode.key(&e.name), e))
+        .collect();
+    let right_map: HashMap<String, &BrowseEntry> = right_entries
+        .iter()
+        .map(|e| (key_mode.key(&e.name), e))
+        .collect();
+
+    for (key, l) in &left_map {
+        if cancel.load(Ordering::Relaxed) {
+            return info;
+        }
+        match right_map.get(key) {
+            Some(r) if l.kind == EntryKind::Dir && r.kind == EntryKind::Dir => {
+                let sub = walk_dir_pair(
+                    &format!("{}/{}", left_path, l.name),
+                    &format!("{}/{}", right_path, r.name),
+                    key_mode,
+                    below,
+                    seen,
+                    cancel,
+                );
+                info.left_size += sub.left_size;
+                info.right_size += sub.right_size;
+                info.left_unique_size += sub.left_unique_size;
+                info.right_unique_size += sub.right_unique_size;
+                info.only_left += sub.only_left;
+                info.only_right += sub.only_right;
+                info.modified += sub.modified;
+                info.depth_limited += sub.depth_limited;
+                info.unreadable += sub.unreadable;
+            }
+            Some(r) => {
+                if l.kind != r.kind || l.size != r.size {
+                    info.modified += 1;
+                }
+                add_entry_size(&mut info, Side::Left, left_path, l, below, seen, cancel);
+                add_entry_size(&mut info, Side::Right, right_path, r, below, seen, cancel);
+            }
+            None => {
+                info.only_left += 1;
+                add_entry_size(&mut info, Side::Left, left_path, l, below, seen, cancel);
+            }
+        }
+    }
+
+    for (key, r) in &right_map {
+        if cancel.load(Ordering::Relaxed) {
+            return info;
+        }
+        if left_map.contains_key(key) {
+            continue;
+        }
+        info.only_right += 1;
+        add_entry_size(&mut info, Side::Right, right_path, r, below, seen, cancel);
+    }
+
+    info
+}
+
+/// Adds one side's entry to that side's sizes in `info`.
+fn add_entry_size(
+    info: &mut DirResolveInfo,
+    side: Side,
+    parent: &str,
+    entry: &BrowseEntry,
+    max_depth: Option<usize>,
+    seen: &mut SeenLinks,
+    cancel: &AtomicBool,
+) {
+    let (seen, apparent, unique) = match side {
+        Side::Left => (&mut seen.left, &mut info.left_size, &mut info.left_unique_size),
+        Side::Right => (&mut seen.right, &mut info.right_size, &mut info.right_unique_size),
+    };
+    let size = entry_size(parent, entry, max_depth, seen, cancel);
+    *apparent += size.apparent;
+    *unique += size.unique;
+    info.depth_limited += size.depth_limited;
+}
+
+/// Sizes summed by `entry_size`.
+#[derive(Default)]
+struct EntrySize {
+    /// Every hard link in full
+    apparent: u64,
+    /// Hard-linked files only the first time they are seen
+    unique: u64,
+    /// Directories past the depth limit that were not walked
+    depth_limited: usize,
+}
+
+/// Size of one listed entry of `parent`: its file size, or for a directory the sum of
+/// file sizes below it. Used where there is no counterpart to compare against.
+/// Directories more than `max_depth` levels below the entry are not walked; each adds
+/// to `depth_limited` instead.
+fn entry_size(
+    parent: &str,
+    entry: &BrowseEntry,
+    max_depth: Option<usize>,
+    seen: &mut HashSet<LinkId>,
+    cancel: &AtomicBool,
+) -> EntrySize {
+    if entry.kind != EntryKind::Dir {
+        let unique = if first_link(seen, entry.link) { entry.size } else { 0 };
+        return EntrySize {
+            apparent: entry.size,
+            unique,
+            depth_limited: 0,
+        };
+    }
+    if max_depth == Some(0) {
+        return EntrySize {
+            depth_limited: 1,
+            ..Default::default()
+        };
+    }
+    let path = format!("{}/{}", parent, entry.name);
+    let entries = match std::fs::read_dir(&path) {
+        Ok(rd) => collect_entries(rd, &IgnoreRules::new(&[]), cancel),
+        Err(_) => return EntrySize::default(),
+    };
+    let below = max_depth.map(|depth| depth - 1);
+    let mut size = EntrySize::default();
+    for child in &entries {
+        if cancel.load(Ordering::Relaxed) {
+            break;
+        }
+        let child = entry_size(&path, child, below, seen, cancel);
+        size.apparent += child.apparent;
+        size.unique += child.unique;
+        size.depth_limited += child.depth_limited;
+    }
+    size
+}
+
+/// Most recursive walkers `resolve_dir_statuses` runs at once; more only contend for
+/// the same disk or network share.
+const MAX_DIR_RESOLVE_WORKERS: usize = 8;
+
+fn dir_resolve_workers() -> usize {
+    std::thread::available_parallelism()
+        .map_or(1, |n| n.get())
+        .min(MAX_DIR_RESOLVE_WORKERS)
+}
+
+/// Runs `resolve` for every pending directory on at most `workers` threads, which pull
+/// from a shared queue. Workers stop taking directories once `cancel` is set.
+fn for_each_pending_dir<T: Send>(
+    pending: Vec<T>,
+    workers: usize,
+    cancel: &AtomicBool,
+    resolve: impl Fn(T) + Sync,
+) {
+    let workers = workers.clamp(1, pending.len().max(1));
+    let (tx, rx) = std::sync::mpsc::channel();
+    for dir in pending {
+        let _ = tx.send(dir);
+    }
+    drop(tx);
+    let queue = Mutex::new(rx);
+
+    std::thread::scope(|s| {
+        for _ in 0..workers {
+            s.spawn(|| loop {
+                if cancel.load(Ordering::Relaxed) {
+                    return;
+                }
+                // Holding the lock only for `recv` keeps the other workers walking
+                let next = queue.lock_or_recover().recv();
+                match next {
+                    Ok(dir) => resolve(dir),
+                    Err(_) => return,
+                }
+            });
+        }
+    });
+}
+
+/// Fails when either path is a file or an archive; only folders have statuses to
+/// resolve.
+fn resolvable_roots(left_path: &str, right_path: &str) -> Result<(), CommandError> {
+    for path in [left_path, right_path].map(Path::new) {
+        if !path.is_file() {
+            continue;
+        }
+        let what = if is_plain_file(path) { "a file" } else { "an archive" };
+        return Err(CommandError::invalid_argument(format!(
+            "{} is {}; only folders have statuses to resolve",
+            path.display(),
+            what
+        )));
+    }
+    Ok(())
+}
+
+/// Resolves pending directory statuses on a bounded worker pool, emitting an event for
+/// each as it finishes (in no particular order). With `max_depth`, each directory is
+/// walked at most that many levels deep and may resolve as `partial`. Names match as
+/// `compare_directory` matches them, with the same `normalize_unicode`. File roots,
+/// plain or archives, have no directories to resolve and are refused.
+#[tauri::command]
+pub async fn resolve_dir_statuses(
+    left_path: String,
+    right_path: String,
+    max_depth: Option<usize>,
+    normalize_unicode: Option<bool>,
+    app: AppHandle,
+    state: State<'_, AppState>,
+) -> Result<(), CommandError> {
+    resolvable_roots(&left_path, &right_path)?;
+    let cache = Arc::clone(&state.dir_resolve_cache);
+
+    let key_mode = scan::KeyMode {
+        normalize_unicode: normalize_unicode.unwrap_or(true),
+        ..Default::default()
+    };
+
+    let resolve = move |_, cancel: &AtomicBool| {
+        let pending_dirs = match pending_dir_pairs(&left_path, &right_path, key_mode, cancel) {
+            Ok(pending_dirs) => pending_dirs,
+            Err(e) => {
+                let _ = app.emit(
+                    EVENT_DIR_RESOLVE_ERROR,
+                    DirResolveErrorPayload {
+                        left_path: left_path.clone(),
+                        right_path: right_path.clone(),
+                        message: e.to_string(),
+                    },
+                );
+                return;
+            }
+        };
+
+        // Resolve pending dirs on a small pool — small dirs finish fast, and a folder
+        // with hundreds of subfolders must not start hundreds of walkers at once
+        let workers = dir_resolve_workers();
+        for_each_pending_dir(pending_dirs, workers, cancel, |(name, sub_left, sub_right)| {
+            let key = (sub_left, sub_right);
+            let stamp = DirStamp::of(&key);
+            let info =
+                dirs_are_same_recursive_counted(&key.0, &key.1, key_mode, max_depth, cancel);
+
+            if cancel.load(Ordering::Relaxed) {
+                return;
+            }
+            let status = info.status();
+
+            // Cache the result for reuse on re-navigation. A depth-limited walk would
+            // pass for a complete one there, and an unreadable folder may be readable
+            // next time, so those are resolved again.
+            if info.depth_limited == 0 && info.unreadable == 0 {
+                cache.lock_or_recover().insert(key, info.clone(), stamp);
+            }
+
+            let _ = app.emit(
+                EVENT_DIR_STATUS_RESOLVED,
+                DirStatusResolvedPayload {
+                    name,
+                    status,
+                    left_path: left_path.clone(),
+                    right_path: right_path.clone(),
+                    left_size: info.left_size,
+                    right_size: info.right_size,
+                    left_unique_size: info.left_unique_size,
+                    right_unique_size: info.right_unique_size,
+                    only_left: info.only_left,
+                    only_right: info.only_right,
+                    modified: info.modified,
+                    depth_limited: info.depth_limited,
+                    unreadable: info.unreadable,
+                },
+            );
+        });
+    };
+    state.dir_resolve_job.start(true, || {}, resolve).await?;
+    Ok(())
+}
+
+/// The folders on both sides of a compare level, matched by `key_mode` as
+/// `compare_directory_impl` matches them: the ones it shows as pending.
+/// Returns (name, left path, right path) for each, or why a side could not be listed.
+fn pending_dir_pairs(
+    left_path: &str,
+    right_path: &str,
+    key_mode: scan::KeyMode,
+    cancel: &AtomicBool,
+) -> Result<Vec<(String, String, String)>, CommandError> {
+    let ignore_rules = IgnoreRules::new(&[]);
+    let list = |path: &str| {
+        std::fs::read_dir(path)
+            .map(|rd| collect_entries(rd, &ignore_rules, cancel))
+            .map_err(|e| CommandError::io(format!("Cannot read {}", path), Path::new(path), &e))
+    };
+    let left_entries = list(left_path)?;
+    let right_entries = list(right_path)?;
+    let right_map: HashMap<String, &BrowseEntry> = right_entries
+        .iter()
+        .map(|e| (key_mode.key(&e.name), e))
+        .collect();
+    let pairs = left_entries
+        .iter()
+        .filter_map(|l| {
+            let r = right_map.get(&key_mode.key(&l.name))?;
+            (l.kind == EntryKind::Dir && r.kind == EntryKind::Dir).then(|| {
+                let sub_left = format!("{}/{}", left_path, l.name);
+                let sub_right = format!("{}/{}", right_path, r.name);
+                (l.name.clone(), sub_left, sub_right)
+            })
+        })
+        .collect();
+    Ok(pairs)
+}
+
+/// Cancels any running directory resolution background task.
+#[tauri::command]
+pub async fn cancel_dir_resolve(state: State<'_, AppState>) -> Result<(), CommandError> {
+    state.dir_resolve_job.cancel();
+    Ok(())
+}
+
+/// Walks each subdirectory of `parent_path` on a bounded worker pool, emitting its
+/// recursive size as each finishes (in no particular order). Sizes are cached by path
+/// and modification time; cached ones are emitted without walking again. Each call
+/// runs as a "dirSizes" operation, so both panes can resolve at once; `cancel_dir_sizes`
+/// stops all of them.
+#[tauri::command]
+pub async fn resolve_dir_sizes(
+    parent_path: String,
+    app: AppHandle,
+    state: State<'_, AppState>,
+) -> Result<(), CommandError> {
+    let cache = Arc::clone(&state.dir_size_cache);
+    // Both panes may be resolving at once, so these are operations rather than a job slot
+    let operations = Arc::clone(&state.operations);
+    let paths = vec![PathBuf::from(&parent_path)];
+    let (op_id, cancel) = operations.register(DIR_SIZES_OPERATION, paths);
+
+    tokio::task::spawn_blocking(move || {
+        let cancel = &*cancel;
+        let ignore_rules = IgnoreRules::new(&[]);
+        let dirs: Vec<String> = match std::fs::read_dir(&parent_path) {
+            Ok(rd) => collect_entries(rd, &ignore_rules, cancel)
+                .into_iter()
+                .filter(|e| e.kind == EntryKind::Dir)
+                .map(|e| e.name)
+                .collect(),
+            Err(_) => Vec::new(),
+        };
+
+        for_each_pending_dir(dirs, dir_resolve_workers(), cancel, |name| {
+            let path = format!("{}/{}", parent_path, name);
+            let cached = cache.lock_or_recover().get(&path);
+            let size = match cached {
+                Some(size) => size,
+                None => {
+                    let stamp = path.stamp();
+                    let Some(size) = dir_size(&path, &ignore_rules, cancel) else {
+                        return;
+                    };
+                    cache.lock_or_recover().insert(path, size, stamp);
+                    size
+                }
+            };
+
+            let _ = app.emit(
+                EVENT_DIR_SIZE_RESOLVED,
+                DirSizeResolvedPayload {
+                    parent_path: parent_path.clone(),
+                    name,
+                    total_size: size.total_size,
+                    file_count: size.file_count,
+                },
+            );
+        });
+        operations.finish(op_id);
+    });
+    Ok(())
+}
+
+/// Operation kind of each `resolve_dir_sizes` walk.
+const DIR_SIZES_OPERATION: &str = "dirSizes";
+
+/// Recursive size of the directory at `path`; `None` if it cannot be read or the walk
+/// was cancelled.
+fn dir_size(path: &str, ignore_rules: &IgnoreRules, cancel: &AtomicBool) -> Option<DirSize> {
+    let totals = scan::measure_paths(&[PathBuf::from(path)], ignore_rules, cancel, &|_, _| {});
+    totals.ok().map(|totals| DirSize {
+        total_size: totals.bytes,
+        file_count: totals.files,
+    })
+}
+
+/// Cancels any running directory size resolution.
+#[tauri::command]
+pub async fn cancel_dir_sizes(state: State<'_, AppState>) -> Result<(), CommandError> {
+    state.operations.cancel_kind(DIR_SIZES_OPERATION);
+    Ok(())
+}
+
+/// Clears the directory size cache.
+#[tauri::command]
+pub async fn clear_dir_size_cache(state: State<'_, AppState>) -> Result<(), CommandError> {
+    state.dir_size_cache.lock_or_recover().clear();
+    Ok(())
+}
+
+/// Size, free space and filesystem of the volume holding `path`, e.g. for a pane's
+/// status bar. `path` need not exist; its nearest existing ancestor is asked.
+#[tauri::command]
+pub async fn get_volume_info(path: String) -> Result<disk::VolumeInfo, CommandError> {
+    let path = PathBuf::from(path);
+    tokio::task::spawn_blocking(move || {
+        disk::volume_info(&path).map_err(|e| {
+            CommandError::io(format!("Cannot query volume of {}", path.display()), &path, &e)
+        })
+    })
+    .await
+    .map_err(task_failed)?
+}
+
+/// Counts and sizes a selection of files and folders, e.g. for a copy confirmation.
+/// Folders are walked with the default ignore rules; symlinks count as themselves.
+/// Emits `measure-progress` while walking; `cancel_measure` stops it.
+#[tauri::command]
+pub async fn measure_paths(
+    paths: Vec<String>,
+    app: AppHandle,
+    state: State<'_, AppState>,
+) -> Result<scan::PathTotals, CommandError> {
+    state
+        .measure_job
+        .run(move |cancel| {
+            let paths: Vec<PathBuf> = paths.iter().map(PathBuf::from).collect();
+            let ignore_rules = IgnoreRules::new(&[]);
+            scan::measure_paths(&paths, &ignore_rules, cancel, &|items, bytes| {
+                let _ = app.emit(
+                    EVENT_MEASURE_PROGRESS,
+                    MeasureProgressPayload {
+                        items_counted: items,
+                        bytes_counted: bytes,
+                    },
+                );
+            })
+            .map_err(|e| cancelled_or(cancel, e))
+        })
+        .await?
+}
+
+/// Cancels a running `measure_paths`.
+#[tauri::command]
+pub async fn cancel_measure(state: State<'_, AppState>) -> Result<(), CommandError> {
+    state.measure_job.cancel();
+    Ok(())
+}
+
+/// Searches the tree under `root` for file names containing `query` (any case), or for
+/// relative paths matching it as a glob with `use_glob`. Walks with the default ignore
+/// rules and never follows symlinks. Matches stream as `search-result` events in
+/// batches, then `search-done` carries the total; `cancel_search` stops the walk.
+#[tauri::command]
+pub async fn search_files(
+    root: String,
+    query: String,
+    use_glob: bool,
+    max_results: usize,
+    app: AppHandle,
+    state: State<'_, AppState>,
+) -> Result<SearchSummary, CommandError> {
+    let root_path = PathBuf::from(&root);
+    if !root_path.is_dir() {
+        return Err(CommandError::not_found(
+            format!("Not a directory: {}", root),
+            &root_path,
+        ));
+    }
+    state
+        .search_job
+        .run(move |cancel| {
+            let query = SearchQuery {
+                pattern: query,
+                use_glob,
+                max_results,
+            };
+            let ignore_rules = IgnoreRules::new(&[]);
+            let on_batch = |batch| {
+                let _ = app.emit(
+                    EVENT_SEARCH_RESULT,
+                    SearchResultPayload {
+                        root: root.clone(),
+                        matches: batch,
+                    },
+                );
+            };
+            let summary = search::search_files(&root_path, &query, &ignore_rules, cancel, &on_batch)
+                .map_err(|e| cancelled_or(cancel, e))?;
+
+            let _ = app.emit(
+                EVENT_SEARCH_DONE,
+                SearchDonePayload {
+                    root,
+                    total: summary.total,
+                    truncated: summary.truncated,
+                },
+            );
+            Ok(summary)
+        })
+        .await?
+}
+
+/// Matching lines `search_content` reports before it stops.
+const MAX_CONTENT_MATCHES: usize = 5000;
+
+/// Searches text files under `root` for lines containing `pattern`, or matching it as a
+/// regex with `regex`. Binary files and files over `max_file_size` bytes are skipped.
+/// Matches stream as `content-search-result` events in batches, then
+/// `content-search-done` carries the summary, including files that could not be read.
+/// `cancel_search` stops the walk. Fails up front on an invalid regex.
+#[tauri::command]
+pub async fn search_content(
+    root: String,
+    pattern: String,
+    regex: bool,
+    max_file_size: u64,
+    app: AppHandle,
+    state: State<'_, AppState>,
+) -> Result<ContentSummary, CommandError> {
+    let query = ContentQuery::new(&pattern, regex, max_file_size, MAX_CONTENT_MATCHES)
+        .map_err(CommandError::invalid_argument)?;
+    let root_path = PathBuf::from(&root);
+    if !root_path.is_dir() {
+        return Err(CommandError::not_found(
+            format!("Not a directory: {}", root),
+            &root_path,
+        ));
+    }
+    state
+        .content_search_job
+        .run(move |cancel| {
+            let ignore_rules = IgnoreRules::new(&[]);
+            let on_batch = |batch| {
+                let _ = app.emit(
+                    EVENT_CONTENT_SEARCH_RESULT,
+                    ContentSearchResultPayload {
+                        root: root.clone(),
+                        matches: batch,
+                    },
+                );
+            };
+            let summary = search::search_content(&root_path, &query, &ignore_rules, cancel, &on_batch)
+                .map_err(|e| cancelled_or(cancel, e))?;
+
+            let _ = app.emit(
+                EVENT_CONTENT_SEARCH_DONE,
+                ContentSearchDonePayload {
+                    root,
+                    summary: summary.clone(),
+                },
+            );
+            Ok(summary)
+        })
+        .await?
+}
+
+/// Cancels a running `search_files` or `search_content`.
+#[tauri::command]
+pub async fn cancel_search(state: State<'_, AppState>) -> Result<(), CommandError> {
+    state.search_job.cancel();
+    state.content_search_job.cancel();
+    Ok(())
+}
+
+/// Finds files with identical contents under the left root, the right root, or both
+/// (`scope` is "left", "right" or "both"). Each confirmed group is emitted as
+/// `duplicate-group`, and the summary as `duplicates-done` before it is returned. Hard
+/// links to one file are listed once; `cancel_duplicates` stops the search.
+#[tauri::command]
+pub async fn find_duplicates(
+    scope: String,
+    app: AppHandle,
+    state: State<'_, AppState>,
+) -> Result<DuplicateSummary, CommandError> {
+    let (left_pane, right_pane) = *state.compared.lock_or_recover();
+    let left = || state.pane_root(left_pane);
+    let right = || state.pane_root(right_pane);
+    let roots = match scope.as_str() {
+        "left" => vec![left()?],
+        "right" => vec![right()?],
+        "both" => vec![left()?, right()?],
+        _ => {
+            return Err(CommandError::invalid_argument(format!(
+                "Unknown duplicate scope: {} (expected left, right or both)",
+                scope
+            )))
+        }
+    };
+    state
+        .duplicates_job
+        .run(move |cancel| {
+            let ignore_rules = IgnoreRules::new(&[]);
+            let workers = dir_resolve_workers();
+            let on_group = |group| {
+                let _ = app.emit(EVENT_DUPLICATE_GROUP, group);
+            };
+            let summary =
+                duplicates::find_duplicates(&roots, &ignore_rules, workers, cancel, &on_group)
+                    .map_err(|e| cancelled_or(cancel, e))?;
+            let _ = app.emit(EVENT_DUPLICATES_DONE, summary);
+            Ok(summary)
+        })
+        .await?
+}
+
+/// Cancels a running `find_duplicates`.
+#[tauri::command]
+pub async fn cancel_duplicates(state: State<'_, AppState>) -> Result<(), CommandError> {
+    state.duplicates_job.cancel();
+    Ok(())
+}
+
+/// Clears the directory resolve cache. Called when starting a new comparison or returning to browse.
+#[tauri::command]
+pub async fn clear_dir_resolve_cache(state: State<'_, AppState>) -> Result<(), CommandError> {
+    state.dir_resolve_cache.lock_or_recover().clear();
+    Ok(())
+}
+
+// --- Terminal commands ---
+
+/// Stops a terminal's reader and kills its shell; its exit event says `killed`.
+fn end_terminal(pty_state: Arc<pty::PtyState>) {
+    pty_state
+        .reader_active
+        .store(false, std::sync::atomic::Ordering::Relaxed);
+    pty_state.killed.store(true, Ordering::Relaxed);
+    pty_state.scrollback.lock_or_recover().clear();
+    let mut child = pty_state.child.lock_or_recover();
+    let _ = child.kill();
+    let _ = child.wait();
+}
+
+/// Which terminal `spawn_terminal` starts and with what shell; each may be left out.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct SpawnTerminalOptions {
+    pub pane_id: Option<String>,
+    pub shell: Option<String>,
+}
+
+/// Spawns a PTY shell in the given working directory and starts streaming output events.
+/// Each pane tab has its own terminal; `options.pane_id` picks a tab on `side`, tab 0 by
+/// default. Events carry both the side and the pane id. `options.shell` overrides the
+/// `shell` setting, which overrides the platform's default shell.
+#[tauri::command]
+pub async fn spawn_terminal<R: Runtime>(
+    side: String,
+    cwd: String,
+    rows: u16,
+    cols: u16,
+    options: Option<SpawnTerminalOptions>,
+    app: AppHandle<R>,
+    state: State<'_, AppState>,
+) -> Result<(), CommandError> {
+    ensure_writable(&state)?;
+    let SpawnTerminalOptions { pane_id, shell } = options.unwrap_or_default();
+    let pane = PaneId::of(&side, pane_id.as_deref())?;
+    // Clean up any previous PTY session (e.g. shell exited via Ctrl+D / exit)
+    let old = state.ptys.lock_or_recover().remove(&pane);
+    if let Some(old) = old {
+        end_terminal(old);
+    }
+
+    let shell = shell.or_else(|| state.shell.lock_or_recover().clone());
+    let shell = pty::shell_or_default(shell.as_deref());
+    let scrollback_bytes = state.terminal_scrollback_bytes.load(Ordering::Relaxed);
+    let (pty_state, mut reader) = pty::spawn_pty(&shell, &cwd, rows, cols, scrollback_bytes)?;
+    let reader_active = Arc::clone(&pty_state.reader_active);
+    let child = Arc::clone(&pty_state.child);
+    let killed = Arc::clone(&pty_state.killed);
+    let cwd = Arc::clone(&pty_state.cwd);
+    let scrollback = Arc::clone(&pty_state.scrollback);
+    state.ptys.lock_or_recover().insert(pane, Arc::new(pty_state));
+
+    let app_handle = app.clone();
+    let side_clone = pane.side.as_str().to_string();
+    let pane_id = pane.to_string();
+    tokio::task::spawn_blocking(move || {
+        let mut buf = [0u8; 4096];
+        let mut decoder = pty::Utf8Decoder::default();
+        let mut scanner = pty::CwdScanner::new(platform::host_name());
+        let emit_output = |data: String| {
+            let seq = scrollback.lock_or_recover().push(&data);
+            let _ = app_handle.emit(
+                EVENT_TERMINAL_OUTPUT,
+                TerminalOutputPayload {
+                    side: side_clone.clone(),
+                    pane_id: pane_id.clone(),
+                    data,
+                    seq,
+                },
+            );
+        };
+        loop {
+            if !reader_active.load(std::sync::atomic::Ordering::Relaxed) {
+                break;
+            }
+            match reader.read(&mut buf) {
+                Ok(0) => break,
+                Ok(n) => {
+                    let data = decoder.decode(&buf[..n]);
+                    if let Some(dir) = scanner.scan(&data) {
+                        let previous = cwd.lock_or_recover().replace(dir.clone());
+                        if previous.as_ref() != Some(&dir) {
+                            let _ = app_handle.emit(
+                                EVENT_TERMINAL_CWD_CHANGED,
+                                TerminalCwdChangedPayload {
+                                    side: side_clone.clone(),
+                                    pane_id: pane_id.clone(),
+                                    cwd: dir,
+                                },
+                            );
+                        }
+                    }
+                    if !data.is_empty() {
+                        emit_output(data);
+                    }
+                }
+                Err(_) => break,
+            }
+        }
+        let rest = decoder.finish();
+        if !rest.is_empty() {
+            emit_output(rest);
+        }
+        // The output also ends when the PTY fails, so only the shell's status tells
+        // an `exit` from a crash
+        let exit = pty::wait_exit(&child, &killed);
+        let _ = app_handle.emit(
+            EVENT_TERMINAL_EXIT,
+            TerminalExitPayload {
+                side: side_clone,
+                pane_id,
+                exit_code: exit.code,
+                reason: exit.reason.to_string(),
+            },
+        );
+    });
+
+    Ok(())
+}
+
+/// Writes data (keystrokes) to the PTY stdin.
+#[tauri::command]
+pub async fn write_terminal(
+    side: String,
+    pane_id: Option<String>,
+    data: String,
+    state: State<'_, AppState>,
+) -> Result<(), CommandError> {
+    ensure_writable(&state)?;
+    let pane = PaneId::of(&side, pane_id.as_deref())?;
+    let pty_state = terminal(&state, pane)?;
+    // A shell that stops reading blocks the write; only this terminal waits for it
+    tokio::task::spawn_blocking(move || {
+        let mut writer = pty_state.writer.lock_or_recover();
+        use std::io::Write;
+        writer.write_all(data.as_bytes()).map_err(|e: std::io::Error| e.to_string())?;
+        writer.flush().map_err(|e: std::io::Error| e.to_string())?;
+        Ok(())
+    })
+    .await
+    .map_err(task_failed)?
+}
+
+/// The terminal of `pane`, without keeping the terminal map locked.
+fn terminal(state: &AppState, pane: PaneId) -> Result<Arc<pty::PtyState>, CommandError> {
+    let ptys = state.ptys.lock_or_recover();
+    ptys.get(&pane).cloned().ok_or_else(|| "No terminal running".into())
+}
+
+/// Notifies the PTY of a terminal size change.
+#[tauri::command]
+pub async fn resize_terminal(
+    side: String,
+    pane_id: Option<String>,
+    rows: u16,
+    cols: u16,
+    state: State<'_, AppState>,
+) -> Result<(), CommandError> {
+    let pane = PaneId::of(&side, pane_id.as_deref())?;
+    let pty_state = terminal(&state, pane)?;
+    let master = pty_state.master.lock_or_recover();
+    master
+        .resize(portable_pty::PtySize {
+            rows,
+            cols,
+            pixel_width: 0,
+            pixel_height: 0,
+        })
+        .map_err(|e| format!("Resize failed: {}", e))?;
+    Ok(())
+}
+
+/// A terminal's recent output as `get_terminal_buffer` returns it.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TerminalBuffer {
+    pub data: String,
+    /// The `seq` of the last output event `data` holds; a view skips events up to it
+    pub seq: u64,
+}
+
+/// The terminal's recent output, so a view attached again can show its history. Up to
+/// the `terminal_scrollback_bytes` setting is kept; `kill_terminal` drops it.
+#[tauri::command]
+pub async fn get_terminal_buffer(
+    side: String,
+    pane_id: Option<String>,
+    state: State<'_, AppState>,
+) -> Result<TerminalBuffer, CommandError> {
+    let pane = PaneId::of(&side, pane_id.as_deref())?;
+    let scrollback = Arc::clone(&terminal(&state, pane)?.scrollback);
+    let scrollback = scrollback.lock_or_recover();
+    Ok(TerminalBuffer {
+        data: scrollback.contents(),
+        seq: scrollback.seq(),
+    })
+}
+
+/// The folder the terminal's shell is in: the one it last reported through OSC 7 or
+/// OSC 1337, else the shell process's own where the platform tells it (Linux, macOS).
+/// `None` when neither is known.
+#[tauri::command]
+pub async fn get_terminal_cwd(
+    side: String,
+    pane_id: Option<String>,
+    state: State<'_, AppState>,
+) -> Result<Option<String>, CommandError> {
+    let pane = PaneId::of(&side, pane_id.as_deref())?;
+    let pty_state = terminal(&state, pane)?;
+    if let Some(cwd) = pty_state.cwd.lock_or_recover().clone() {
+        return Ok(Some(cwd));
+    }
+    let pid = pty_state.child.lock_or_recover().process_id();
+    Ok(pid
+        .and_then(pty::process_cwd)
+        .map(|cwd| cwd.to_string_lossy().into_owned()))
+}
+
+/// Kills the PTY process and cleans up state.
+#[tauri::command]
+pub async fn kill_terminal(
+    side: String,
+    pane_id: Option<String>,
+    state: State<'_, AppState>,
+) -> Result<(), CommandError> {
+    let pane = PaneId::of(&side, pane_id.as_deref())?;
+    let pty_state = state.ptys.lock_or_recover().remove(&pane);
+    if let Some(pty_state) = pty_state {
+        end_terminal(pty_state);
+    }
+    Ok(())
+}
+
+/// Forgets a closed pane tab: its root, so file operations may no longer act there
+/// unless another tab shows it, its terminal, which is killed, and its folder watcher.
+/// A compare of the tab is stopped first.
+#[tauri::command]
+pub async fn close_pane(pane_id: String, state: State<'_, AppState>) -> Result<(), CommandError> {
+    let pane = PaneId::parse(&pane_id)?;
+    forget_pane(&state, pane).await;
+    Ok(())
+}
+
+async fn forget_pane(state: &AppState, pane: PaneId) {
+    let (left, right) = *state.compared.lock_or_recover();
+    if pane == left || pane == right {
+        state.compare_job.stop().await;
+        stop_watching(state);
+    }
+    state.roots.lock_or_recover().remove(&pane);
+    state.dir_watchers.lock_or_recover().remove(&pane);
+    let pty_state = state.ptys.lock_or_recover().remove(&pane);
+    if let Some(pty_state) = pty_state {
+        end_terminal(pty_state);
+    }
+}
+
+/// Lightweight entry collection for recursive comparison (no modified time needed).
+/// Stops early once `cancel` is set, so huge directories do not hold up a cancel.
+fn collect_entries(
+    read_dir: std::fs::ReadDir,
+    ignore_rules: &IgnoreRules,
+    cancel: &AtomicBool,
+) -> Vec<BrowseEntry> {
+    let mut entries = Vec::new();
+    for entry_result in read_dir {
+        if cancel.load(Ordering::Relaxed) {
+            break;
+        }
+        let entry = match entry_result {
+            Ok(e) => e,
+            Err(_) => continue,
+        };
+        let name = entry.file_name().to_string_lossy().to_string();
+        if ignore_rules.is_ignored(&name) {
+            continue;
+        }
+        let file_type = match entry.file_type() {
+            Ok(ft) => ft,
+            Err(_) => continue,
+        };
+        let is_symlink = file_type.is_symlink();
+        let metadata = entry.metadata().or_else(|_| entry.path().symlink_metadata());
+        let (kind, size, link) = match metadata {
+            Ok(m) if is_symlink => (EntryKind::Symlink, m.len(), None),
+            Ok(m) => (EntryKind::of(&m.file_type()), m.len(), link_id(&m)),
+            Err(_) => (EntryKind::of(&file_type), 0, None),
+        };
+        entries.push(BrowseEntry {
+            hidden: scan::is_hidden(&name),
+            name,
+            kind,
+            size,
+            modified: None,
+            file_count: None,
+            mode: None,
+            owner: None,
+            group: None,
+            error: None,
+            link,
+        });
+    }
+    entries
+}
+
+fn list_directory_impl(
+    path: &str,
+    include_hidden: bool,
+    order: SortOrder,
+) -> Result<Vec<BrowseEntry>, CommandError> {
+    let mut entries = read_directory_entries(path, include_hidden)?;
+    sort_browse_entries(&mut entries, order);
+    Ok(entries)
+}
+
+/// Paths that listings report as unreadable in tests, whatever their permissions, since
+/// a test run as root can read everything.
+#[cfg(test)]
+static DENIED_IN_TESTS: Mutex<Vec<PathBuf>> = Mutex::new(Vec::new());
+
+/// Why the current user cannot read a listed entry, if they cannot.
+fn entry_read_error(path: &Path, meta: &std::fs::Metadata) -> Option<std::io::Error> {
+    #[cfg(test)]
+    if DENIED_IN_TESTS.lock_or_recover().iter().any(|denied| denied == path) {
+        return Some(std::io::ErrorKind::PermissionDenied.into());
+    }
+    read_denied(path, meta)
+}
+
+/// The entries of `path` in the order the OS returns them.
+fn read_directory_entries(
+    path: &str,
+    include_hidden: bool,
+) -> Result<Vec<BrowseEntry>, CommandError> {
+    let dir = PathBuf::from(path);
+    let cannot_read =
+        |e: &std::io::Error| CommandError::io(format!("Cannot read {}", path), &dir, e);
+    let metadata = std::fs::metadata(&dir).map_err(|e| cannot_read(&e))?;
+    if !metadata.is_dir() {
+        return Err(CommandError::InvalidArgument {
+            message: format!("Not a directory: {}", path),
+            path: Some(path.to_string()),
+        });
+    }
+
+    let ignore_rules = IgnoreRules::new(&[]);
+    let mut owner_names = OwnerNames::new();
+    let mut entries = Vec::with_capacity(64);
+
+    let read_dir = std::fs::read_dir(&dir).map_err(|e| cannot_read(&e))?;
+
+    for entry_result in read_dir {
+        let entry = match entry_result {
+            Ok(e) => e,
+            Err(_) => continue,
+        };
+
+        let name = entry.file_name().to_string_lossy().to_string();
+
+        if ignore_rules.is_ignored(&name) || (!include_hidden && scan::is_hidden(&name)) {
+            continue;
+        }
+
+        // file_type() doesn't follow symlinks — reliable for detecting symlinks
+        let file_type = entry.file_type();
+        let is_symlink = file_type.as_ref().is_ok_and(|ft| ft.is_symlink());
+
+        // metadata() follows symlinks. Fall back to symlink_metadata for broken links.
+        let metadata = entry.metadata().or_else(|_| entry.path().symlink_metadata());
+
+        let (mut owner, mut group, mut hidden) = (None, None, scan::is_hidden(&name));
+        let error;
+        let (kind, size, modified, mode, link) = match metadata {
+            Ok(m) => {
+                (owner, group) = owner_names.of(&m);
+                hidden |= hidden_attribute(&m);
+                error = entry_read_error(&entry.path(), &m).map(|e| format!("Cannot read: {}", e));
+                let (kind, link) = if is_symlink {
+                    (EntryKind::Symlink, None)
+                } else {
+                    (EntryKind::of(&m.file_type()), link_id(&m))
+                };
+                let modified = m
+                    .modified()
+                    .ok()
+                    .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+                    .map(|d| d.as_millis() as u64);
+                (kind, m.len(), modified, display_mode(&m), link)
+            }
+            Err(e) => {
+                // No metadata at all — still show the entry, marked unreadable
+                error = Some(format!("Cannot read: {}", e));
+                let kind = file_type.map_or(EntryKind::File, |ft| EntryKind::of(&ft));
+                (kind, 0, None, None, None)
+            }
+        };
+        if hidden && !include_hidden {
+            continue;
+        }
+
+        entries.push(BrowseEntry {
+            name,
+            kind,
+            size,
+            modified,
+            file_count: None,
+            mode,
+            owner,
+            group,
+            hidden,
+            error,
+            link,
+        });
+    }
+
+    Ok(entries)
+}
+
+fn sort_browse_entries(entries: &mut [BrowseEntry], order: SortOrder) {
+    fn fields(e: &BrowseEntry) -> SortFields<'_> {
+        SortFields {
+            name: &e.name,
+            is_dir: e.kind == EntryKind::Dir,
+            size: e.size,
+            modified: e.modified,
+        }
+    }
+    entries.sort_by(|a, b| order.compare(fields(a), fields(b)));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    #[test]
+    fn test_get_summary_after_poisoned_lock() {
+        let state = Arc::new(AppState::new());
+        let poisoner = Arc::clone(&state);
+        let _ = std::thread::spawn(move || {
+            let _guard = poisoner.last_result.lock().unwrap();
+            panic!("simulated panic while holding last_result");
+        })
+        .join();
+        assert!(state.last_result.is_poisoned());
+
+        let err = summary_of(&state, None).unwrap_err();
+        assert_eq!(err.to_string(), "No comparison result available");
+
+        // The lock stays usable for later results
+        *state.last_result.lock_or_recover() = Some(LastCompareResult {
+            job_id: 2,
+            revision: 0,
+            diffs: Vec::new(),
+            summary: CompareSummary {
+                same: 3,
+                ..Default::default()
+            },
+            left_root: "/l".to_string(),
+            right_root: "/r".to_string(),
+            mode: CompareMode::Smart,
+            scan_errors: ScanErrors::default(),
+            loaded_from: None,
+            settings: None,
+        });
+        assert_eq!(summary_of(&state, None).unwrap().same, 3);
+    }
+
+    #[test]
+    fn test_stale_job_id_is_rejected() {
+        let state = AppState::new();
+        *state.last_result.lock_or_recover() = Some(LastCompareResult {
+            job_id: 2,
+            revision: 0,
+            diffs: Vec::new(),
+            summary: CompareSummary::default(),
+            left_root: "/l".to_string(),
+            right_root: "/r".to_string(),
+            mode: CompareMode::Smart,
+            scan_errors: ScanErrors::default(),
+            loaded_from: None,
+            settings: None,
+        });
+
+        assert!(summary_of(&state, Some(2)).is_ok());
+        let err = summary_of(&state, Some(1)).unwrap_err();
+        assert_eq!(err.to_string(), "Comparison 1 is no longer the current result");
+    }
+
+    fn dir_pair(name: &str) -> (PathBuf, PathBuf) {
+        let base = std::env::temp_dir().join(name);
+        let _ = std::fs::remove_dir_all(&base);
+        let (left, right) = (base.join("left"), base.join("right"));
+        for dir in [&left, &right] {
+            std::fs::create_dir_all(dir).unwrap();
+            std::fs::write(dir.join("same.txt"), "s").unwrap();
+        }
+        std::fs::write(left.join("extra.txt"), "e").unwrap();
+        (left, right)
+    }
+
+    /// Stores a live compare of `left` and `right` as job 1, as `start_compare` would.
+    fn store_compare(state: &AppState, left: &Path, right: &Path) {
+        let settings = CompareSettings {
+            mode: CompareMode::Smart,
+            options: compare::CompareOptions::default(),
+            scan_options: scan::ScanOptions::default(),
+            key_mode: scan::KeyMode::default(),
+            left_excluded: Vec::new(),
+            right_excluded: Vec::new(),
+        };
+        let no_cancel = AtomicBool::new(false);
+        let scan = |root: &Path| {
+            let rules = IgnoreRules::new(&[]);
+            let source = LocalSource::new(root);
+            source.scan(&rules, &[], &settings.scan_options, &no_cancel, &|_| {}).unwrap()
+        };
+        let (left_scan, right_scan) = (scan(left), scan(right));
+        let result = compare::compare_with_options(
+            &left_scan,
+            &right_scan,
+            settings.mode,
+            &settings.options,
+            &no_cancel,
+        )
+        .unwrap();
+        *state.last_result.lock_or_recover() = Some(LastCompareResult {
+            job_id: 1,
+            revision: 0,
+            diffs: result.diffs,
+            summary: result.summary,
+            left_root: left.to_string_lossy().into_owned(),
+            right_root: right.to_string_lossy().into_owned(),
+            mode: settings.mode,
+            scan_errors: ScanErrors::default(),
+            loaded_from: None,
+            settings: Some(settings),
+        });
+    }
+
+    #[test]
+    fn test_copied_entry_updates_the_stored_result() {
+        let (left, right) = dir_pair("sc_cmd_refresh_copy");
+        let state = AppState::new();
+        store_compare(&state, &left, &right);
+        assert_eq!(summary_of(&state, None).unwrap().only_left, 1);
+
+        std::fs::copy(left.join("extra.txt"), right.join("extra.txt")).unwrap();
+        let rel_paths = stored_rel_paths(&state, &[right.join("extra.txt")]);
+        assert_eq!(rel_paths, ["extra.txt"]);
+        let update = refresh_stored(&state, rel_paths, None, &AtomicBool::new(false)).unwrap();
+
+        let copied = UpdatedPath {
+            rel_path: "extra.txt".to_string(),
+            kind: Some(DiffKind::Same),
+        };
+        assert_eq!((update.job_id, update.paths, update.full), (1, vec![copied], false));
+        let summary = summary_of(&state, Some(1)).unwrap();
+        assert_eq!(
+            (summary.only_left, summary.same, summary.total_right),
+            (0, 2, 2)
+        );
+        let stored = state.last_result.lock_or_recover();
+        let diffs = &stored.as_ref().unwrap().diffs;
+        assert!(diffs.iter().all(|d| d.diff_kind == DiffKind::Same));
+        drop(stored);
+
+        let elsewhere = stored_rel_paths(&state, &[std::env::temp_dir().join("elsewhere")]);
+        assert!(refresh_stored(&state, elsewhere, None, &AtomicBool::new(false)).is_none());
+    }
+
+    #[test]
+    fn test_cancelled_refresh_leaves_the_stored_result() {
+        let (left, right) = dir_pair("sc_cmd_refresh_cancelled");
+        let state = AppState::new();
+        store_compare(&state, &left, &right);
+        std::fs::copy(left.join("extra.txt"), right.join("extra.txt")).unwrap();
+        let rel_paths = stored_rel_paths(&state, &[right.join("extra.txt")]);
+        let update = refresh_stored(&state, rel_paths, None, &AtomicBool::new(true));
+        let _ = std::fs::remove_dir_all(left.parent().unwrap());
+
+        assert!(update.is_none());
+        assert_eq!(summary_of(&state, Some(1)).unwrap().only_left, 1);
+        assert_eq!(state.last_result.lock_or_recover().as_ref().unwrap().revision, 0);
+    }
+
+    #[test]
+    fn test_delete_updates_a_result_without_same_entries_in_part() {
+        let (left, right) = dir_pair("sc_cmd_refresh_baseline");
+        let state = AppState::new();
+        store_compare(&state, &left, &right);
+        let settings = {
+            let mut stored = state.last_result.lock_or_recover();
+            let r = stored.as_mut().unwrap();
+            r.diffs.retain(|d| d.diff_kind != DiffKind::Same);
+            let settings = r.settings.as_mut().unwrap();
+            settings.options.include_same = false;
+            settings.clone()
+        };
+        let summary = summary_of(&state, Some(1)).unwrap();
+        let rel_paths = stored_rel_paths(&state, &[right.join("same.txt")]);
+        let never = AtomicBool::new(false);
+        let baseline =
+            refresh::baseline(&left, &right, &rel_paths, &settings, &summary, &never).unwrap();
+        std::fs::remove_file(right.join("same.txt")).unwrap();
+        let update = refresh_stored(&state, rel_paths, Some(&baseline), &never).unwrap();
+        let _ = std::fs::remove_dir_all(left.parent().unwrap());
+
+        assert!(!update.full);
+        assert_eq!(update.paths[0].kind, Some(DiffKind::OnlyLeft));
+        let summary = summary_of(&state, Some(1)).unwrap();
+        assert_eq!(
+            (summary.same, summary.only_left, summary.total_left, summary.total_right),
+            (0, 2, 2, 0)
+        );
+    }
+
+    fn listed(dir: &Path) -> Vec<BrowseEntry> {
+        list_directory_impl(&dir.to_string_lossy(), true, SortOrder::default()).unwrap()
+    }
+
+    fn compare_dirs(left: &Path, right: &Path) -> Result<CompareDirectoryResult, CommandError> {
+        let cache = Mutex::new(DirResolveCache::default());
+        compare_directory_impl(
+            &left.to_string_lossy(),
+            &right.to_string_lossy(),
+            scan::KeyMode::default(),
+            SortOrder::default(),
+            &cache,
+        )
+    }
+
+    #[test]
+    fn test_list_directory_include_hidden() {
+        let (left, _) = dir_pair("sc_list_hidden");
+        write_sized(&left.join(".env"), 1);
+        write_sized(&left.join(".git/HEAD"), 1);
+        let path = left.to_string_lossy().to_string();
+        let names = |include_hidden| {
+            let listing = list_directory_impl(&path, include_hidden, SortOrder::default());
+            let mut names: Vec<_> = listing
+                .unwrap()
+                .into_iter()
+                .map(|e| e.name)
+                .collect();
+            names.sort();
+            names
+        };
+        let (all, visible) = (names(true), names(false));
+        let _ = std::fs::remove_dir_all(left.parent().unwrap());
+
+        assert!(all.contains(&".env".to_string()) && all.contains(&".git".to_string()));
+        assert!(visible.iter().all(|name| !name.starts_with('.')));
+        assert_eq!(all.len(), visible.len() + 2);
+    }
+
+    #[test]
+    fn test_listing_and_comparison_share_sort_order() {
+        let (left, right) = dir_pair("sc_list_sorted");
+        write_sized(&left.join("file10.txt"), 3);
+        write_sized(&left.join("File2.txt"), 30);
+        write_sized(&right.join("file10.txt"), 3);
+        write_sized(&left.join("sub/a.txt"), 1);
+        let by_size = SortOrder::parse(Some("size"), Some(false)).unwrap();
+        let listing = list_directory_impl(&left.to_string_lossy(), true, by_size);
+        let listed_names: Vec<_> = listing.unwrap().into_iter().map(|e| e.name).collect();
+        let compared = compare_directory_impl(
+            &left.to_string_lossy(),
+            &right.to_string_lossy(),
+            scan::KeyMode::default(),
+            by_size,
+            &Mutex::new(DirResolveCache::default()),
+        )
+        .unwrap();
+        let compared_names: Vec<_> = compared.entries.into_iter().map(|e| e.name).collect();
+        let natural: Vec<_> = listed(&left).into_iter().map(|e| e.name).collect();
+        let _ = std::fs::remove_dir_all(left.parent().unwrap());
+
+        assert_eq!(listed_names, ["sub", "File2.txt", "file10.txt", "same.txt", "extra.txt"]);
+        assert_eq!(compared_names, listed_names);
+        assert_eq!(natural, ["sub", "extra.txt", "File2.txt", "file10.txt", "same.txt"]);
+    }
+
+    #[test]
+    fn test_list_directory_reports_mode_owner_and_hidden() {
+        use std::os::unix::fs::PermissionsExt;
+        let (left, _) = dir_pair("sc_list_details");
+        let script = left.join("run.sh");
+        write_sized(&script, 1);
+        std::fs::set_permissions(&script, std::fs::Permissions::from_mode(0o754)).unwrap();
+        write_sized(&left.join(".env"), 1);
+        let entries = listed(&left);
+        let _ = std::fs::remove_dir_all(left.parent().unwrap());
+
+        let entry = |name: &str| entries.iter().find(|e| e.name == name).unwrap();
+        let run = entry("run.sh");
+        assert_eq!(run.mode.unwrap() & 0o111, 0o110);
+        assert!(run.owner.as_deref().is_some_and(|o| !o.is_empty()) && run.group.is_some());
+        assert_eq!(run.owner, entry("same.txt").owner);
+        assert!(entry(".env").hidden && !run.hidden);
+        let json = serde_json::to_value(run).unwrap();
+        assert_eq!(json["hidden"], false);
+        assert!(json["owner"].is_string());
+    }
+
+    #[test]
+    fn test_unreadable_entries_are_kept_and_compared_as_errors() {
+        let (left, right) = dir_pair("sc_list_unreadable");
+        let locked = left.join("same.txt");
+        DENIED_IN_TESTS.lock_or_recover().push(locked.clone());
+        let entries = listed(&left);
+        let compared = compare_dirs(&left, &right).unwrap();
+        DENIED_IN_TESTS.lock_or_recover().retain(|denied| *denied != locked);
+        let _ = std::fs::remove_dir_all(left.parent().unwrap());
+
+        let same = entries.iter().find(|e| e.name == "same.txt").unwrap();
+        let status = compared.entries.iter().find(|e| e.name == "same.txt").unwrap().status;
+        assert_eq!(entries.len(), 2);
+        assert!(same.error.as_deref().unwrap().starts_with("Cannot read: "));
+        assert_eq!(same.kind, EntryKind::File);
+        assert_eq!(status, CompareStatus::Error);
+        assert_eq!((compared.summary.errors, compared.summary.same), (1, 0));
+    }
+
+    #[test]
+    fn test_known_folders_flag_missing_and_skip_unknown() {
+        let (left, _) = dir_pair("sc_known_folders");
+        let folders = known_folders(vec![
+            ("home", Some(left.clone())),
+            ("desktop", None),
+            ("downloads", Some(left.join("Téléchargements"))),
+            ("temp", Some(PathBuf::from("/"))),
+        ]);
+        let _ = std::fs::remove_dir_all(left.parent().unwrap());
+
+        let summary: Vec<_> = folders.iter().map(|f| (f.id, f.name.as_str(), f.exists)).collect();
+        assert_eq!(
+            summary,
+            [("home", "left", true), ("downloads", "Téléchargements", false), ("temp", "/", true)]
+        );
+        assert_eq!(folders[1].path, left.join("Téléchargements").to_string_lossy());
+    }
+
+    #[test]
+    fn test_session_roots_report_missing_side() {
+        let (left, right) = dir_pair("sc_session_roots");
+        let mut session = Session {
+            name: "weekly".to_string(),
+            left_root: left.to_string_lossy().into_owned(),
+            right_root: right.to_string_lossy().into_owned(),
+            options: SessionOptions::default(),
+        };
+        assert!(check_session_roots(&session).is_ok());
+
+        session.right_root = right.join("gone").to_string_lossy().into_owned();
+        let err = check_session_roots(&session).unwrap_err();
+        assert_eq!(err.code(), "notFound");
+        assert!(err.to_string().contains("right folder"));
+        assert_eq!(err.path(), Some(session.right_root.as_str()));
+
+        // Exists, but cannot be a root
+        #[cfg(unix)]
+        {
+            let socket = right.join("app.sock");
+            let _listener = std::os::unix::net::UnixListener::bind(&socket).unwrap();
+            session.right_root = socket.to_string_lossy().into_owned();
+            let err = check_session_roots(&session).unwrap_err();
+            assert_eq!(err.code(), "notFound");
+        }
+
+        session.left_root = left.join("gone").to_string_lossy().into_owned();
+        let err = check_session_roots(&session).unwrap_err();
+        let _ = std::fs::remove_dir_all(left.parent().unwrap());
+        assert!(err.to_string().contains("Both folders"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_list_directory_reports_sockets() {
+        let (left, right) = dir_pair("sc_list_socket");
+        let _sockets = [
+            std::os::unix::net::UnixListener::bind(left.join("app.sock")).unwrap(),
+            std::os::unix::net::UnixListener::bind(right.join("app.sock")).unwrap(),
+        ];
+        let entries = listed(&left);
+        let compared = compare_directory_impl(
+            &left.to_string_lossy(),
+            &right.to_string_lossy(),
+            scan::KeyMode::default(),
+            SortOrder::default(),
+            &Mutex::new(DirResolveCache::default()),
+        )
+        .unwrap();
+        let _ = std::fs::remove_dir_all(left.parent().unwrap());
+
+        let socket = entries.iter().find(|e| e.name == "app.sock").unwrap();
+        assert_eq!(socket.kind, EntryKind::Socket);
+        // Never called the same: there are no contents to compare
+        let pair = compared.entries.iter().find(|e| e.name == "app.sock").unwrap();
+        assert_eq!(pair.status, CompareStatus::Special);
+        assert_eq!(compared.summary.special, 1);
+    }
+
+    #[test]
+    fn test_set_modified_round_trips_through_listing() {
+        let (left, right) = dir_pair("sc_set_modified");
+        fileops::set_modified(&left.join("same.txt"), 1_700_000_000_456).unwrap();
+        fileops::copy_times(&left.join("same.txt"), &right.join("same.txt")).unwrap();
+        let (left_entries, right_entries) = (listed(&left), listed(&right));
+        let _ = std::fs::remove_dir_all(left.parent().unwrap());
+
+        for entries in [left_entries, right_entries] {
+            let same = entries.iter().find(|e| e.name == "same.txt").unwrap();
+            assert_eq!(same.modified, Some(1_700_000_000_456));
+        }
+    }
+
+    #[test]
+    fn test_list_directory_fills_cached_dir_sizes() {
+        let base = std::env::temp_dir().join("sc_list_dir_sizes");
+        let _ = std::fs::remove_dir_all(&base);
+        write_sized(&base.join("sub/a.bin"), 10);
+        write_sized(&base.join("sub/deep/b.bin"), 5);
+        let parent = base.to_string_lossy().to_string();
+        let sub = format!("{}/sub", parent);
+
+        let cache = Mutex::new(DirSizeCache::default());
+        let cancel = AtomicBool::new(false);
+        let size = dir_size(&sub, &IgnoreRules::new(&[]), &cancel).unwrap();
+        cache.lock().unwrap().insert(sub.clone(), size, sub.stamp());
+        let mut entries = list_directory_impl(&parent, true, SortOrder::default()).unwrap();
+        apply_cached_dir_sizes(&parent, &mut entries, &cache);
+        invalidate_dir_sizes(&cache, &[&base.join("sub/deep/c.bin")]);
+        let mut relisted = list_directory_impl(&parent, true, SortOrder::default()).unwrap();
+        apply_cached_dir_sizes(&parent, &mut relisted, &cache);
+        let _ = std::fs::remove_dir_all(&base);
+
+        assert_eq!((entries[0].size, entries[0].file_count), (15, Some(2)));
+        assert_eq!(relisted[0].file_count, None);
+    }
+
+    #[test]
+    fn test_replaced_folder_drops_cached_sizes_below_it() {
+        let base = std::env::temp_dir().join("sc_list_dir_sizes_below");
+        let _ = std::fs::remove_dir_all(&base);
+        write_sized(&base.join("sub/deep/b.bin"), 5);
+        let sub = format!("{}/sub", base.to_string_lossy());
+        let deep = format!("{}/deep", sub);
+
+        let cache = Mutex::new(DirSizeCache::default());
+        let cancel = AtomicBool::new(false);
+        let size = dir_size(&deep, &IgnoreRules::new(&[]), &cancel).unwrap();
+        cache.lock().unwrap().insert(deep.clone(), size, deep.stamp());
+        let cached = list_with_cached_sizes(&sub, true, SortOrder::default(), &cache).unwrap();
+        invalidate_dir_sizes(&cache, &[&base.join("sub")]);
+        let relisted = list_with_cached_sizes(&sub, true, SortOrder::default(), &cache).unwrap();
+        let _ = std::fs::remove_dir_all(&base);
+
+        assert_eq!(cached[0].file_count, Some(1));
+        assert_eq!(relisted[0].file_count, None);
+    }
+
+    #[test]
+    fn test_sort_by_size_uses_cached_dir_sizes() {
+        let base = std::env::temp_dir().join("sc_list_sort_cached");
+        let _ = std::fs::remove_dir_all(&base);
+        write_sized(&base.join("big/a.bin"), 50);
+        write_sized(&base.join("small/a.bin"), 5);
+        let parent = base.to_string_lossy().to_string();
+
+        let cache = Mutex::new(DirSizeCache::default());
+        let cancel = AtomicBool::new(false);
+        for name in ["big", "small"] {
+            let dir = format!("{}/{}", parent, name);
+            let size = dir_size(&dir, &IgnoreRules::new(&[]), &cancel).unwrap();
+            cache.lock().unwrap().insert(dir.clone(), size, dir.stamp());
+        }
+        let by_size = SortOrder::parse(Some("size"), Some(false)).unwrap();
+        let entries = list_with_cached_sizes(&parent, true, by_size, &cache).unwrap();
+        let _ = std::fs::remove_dir_all(&base);
+
+        let names: Vec<_> = entries.iter().map(|e| e.name.as_str()).collect();
+        assert_eq!(names, ["big", "small"]);
+        assert_eq!(entries[0].size, 50);
+    }
+
+    #[test]
+    fn test_compare_directory_both_sides_listed() {
+        let (left, right) = dir_pair("sc_cmp_dir_ok");
+        let result = compare_dirs(&left, &right).unwrap();
+        let _ = std::fs::remove_dir_all(left.parent().unwrap());
+
+        assert!(result.error_left.is_none() && result.error_right.is_none());
+        assert_eq!(result.summary.same, 1);
+        assert_eq!(result.summary.only_left, 1);
+    }
+
+    #[test]
+    fn test_compare_directory_pending_and_bytes() {
+        let (left, right) = dir_pair("sc_cmp_dir_bytes");
+        write_sized(&left.join("changed.bin"), 10);
+        write_sized(&right.join("changed.bin"), 30);
+        write_sized(&right.join("right_only.bin"), 7);
+        write_sized(&left.join("sub/a.txt"), 1);
+        write_sized(&right.join("sub/a.txt"), 1);
+        write_sized(&left.join("left_dir/big.bin"), 500);
+        let result = compare_dirs(&left, &right).unwrap();
+        let _ = std::fs::remove_dir_all(left.parent().unwrap());
+
+        let summary = result.summary;
+        assert_eq!(summary.pending, 1);
+        // `extra.txt`; a one-sided directory's contents are not walked here
+        assert_eq!(summary.bytes_only_left, 1);
+        assert_eq!(summary.bytes_only_right, 7);
+        assert_eq!(summary.bytes_modified, 30);
+    }
+
+    #[test]
+    fn test_compare_directory_missing_side() {
+        let (left, right) = dir_pair("sc_cmp_dir_missing");
+        let missing = right.with_file_name("gone");
+        let result = compare_dirs(&left, &missing).unwrap();
+        let both = compare_dirs(&missing, &missing.with_file_name("gone2"));
+        let _ = std::fs::remove_dir_all(left.parent().unwrap());
+
+        // The left listing is still shown, with the right side's error alongside
+        assert!(result.error_left.is_none());
+        let err = result.error_right.unwrap();
+        assert_eq!(err.code(), "notFound");
+        assert!(err.to_string().starts_with("Right side: Cannot read "));
+        assert_eq!(result.entries.len(), 2);
+        assert_eq!(result.summary.total_right, 0);
+
+        let err = both.err().expect("neither side can be listed");
+        assert!(err.to_string().starts_with("Left side: "));
+        assert!(err.to_string().contains("; Right side: "));
+    }
+
+    #[test]
+    fn test_new_file_invalidates_cached_dir_status() {
+        let (left, right) = dir_pair("sc_cmp_dir_cache_stale");
+        for dir in [&left, &right] {
+            std::fs::create_dir_all(dir.join("build")).unwrap();
+        }
+        let key = (
+            left.join("build").to_string_lossy().to_string(),
+            right.join("build").to_string_lossy().to_string(),
+        );
+        let cache = Mutex::new(DirResolveCache::default());
+        let build_status = |cache: &Mutex<DirResolveCache>| {
+            let result = compare_directory_impl(
+                &left.to_string_lossy(),
+                &right.to_string_lossy(),
+                scan::KeyMode::default(),
+                SortOrder::default(),
+                cache,
+            )
+            .unwrap();
+            result.entries.into_iter().find(|e| e.name == "build").unwrap().status
+        };
+
+        let stamp = DirStamp::of(&key);
+        cache
+            .lock_or_recover()
+            .insert(key.clone(), DirResolveInfo::default(), stamp);
+        assert_eq!(build_status(&cache), CompareStatus::Same);
+
+        // Stand-in for a build writing into the cached directory
+        std::fs::write(left.join("build/out.o"), "o").unwrap();
+        let stale = DirStamp::of(&key) != stamp;
+        let status = build_status(&cache);
+        let _ = std::fs::remove_dir_all(left.parent().unwrap());
+
+        // Skip where the filesystem's mtime granularity hid the write
+        if stale {
+            assert_eq!(status, CompareStatus::Pending);
+            assert!(cache.lock_or_recover().is_empty());
+        }
+    }
+
+    #[test]
+    fn test_compare_directory_unreadable_side() {
+        let (left, right) = dir_pair("sc_cmp_dir_unreadable");
+        // A file in place of the folder cannot be listed, whoever runs the test
+        std::fs::remove_dir_all(&left).unwrap();
+        std::fs::write(&left, "not a folder").unwrap();
+        let result = compare_dirs(&left, &right).unwrap();
+        let _ = std::fs::remove_dir_all(left.parent().unwrap());
+
+        let err = result.error_left.unwrap();
+        assert_eq!(err.code(), "invalidArgument");
+        assert!(err.to_string().starts_with("Left side: Not a directory"), "{}", err);
+        assert!(result.error_right.is_none());
+        assert_eq!(result.summary.only_right, 1);
+    }
+
+    fn write_sized(path: &Path, size: usize) {
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, vec![b'x'; size]).unwrap();
+    }
+
+    #[test]
+    fn test_dir_sizes_counted_per_side() {
+        let base = std::env::temp_dir().join("sc_dir_sizes_same");
+        let _ = std::fs::remove_dir_all(&base);
+        for side in ["l", "r"] {
+            write_sized(&base.join(side).join("a.txt"), 10);
+            write_sized(&base.join(side).join("sub/deep/b.bin"), 300);
+        }
+        // Ignored noise neither differs nor counts towards the size
+        write_sized(&base.join("l/sub/.DS_Store"), 50);
+        let cancel = AtomicBool::new(false);
+        let result = dirs_are_same_recursive_counted(
+            &base.join("l").to_string_lossy(),
+            &base.join("r").to_string_lossy(),
+            scan::KeyMode::default(),
+            None,
+            &cancel,
+        );
+        let _ = std::fs::remove_dir_all(&base);
+
+        assert_eq!(
+            result,
+            DirResolveInfo {
+                left_size: 310,
+                right_size: 310,
+                left_unique_size: 310,
+                right_unique_size: 310,
+                ..Default::default()
+            }
+        );
+        assert_eq!(result.status(), CompareStatus::Same);
+    }
+
+    #[test]
+    fn test_dir_diff_counts_and_sizes() {
+        let base = std::env::temp_dir().join("sc_dir_diff_counts");
+        let _ = std::fs::remove_dir_all(&base);
+        let (left, right) = (base.join("l"), base.join("r"));
+        write_sized(&left.join("only_left.txt"), 1);
+        write_sized(&left.join("big/one.bin"), 1000);
+        write_sized(&left.join("big/nested/two.bin"), 2000);
+        write_sized(&right.join("big/one.bin"), 5000);
+        write_sized(&right.join("big/nested/two.bin"), 7000);
+        write_sized(&right.join("big/nested/three.bin"), 9000);
+        write_sized(&right.join("only_right/four.bin"), 400);
+
+        let cancel = AtomicBool::new(false);
+        let result = dirs_are_same_recursive_counted(
+            &left.to_string_lossy(),
+            &right.to_string_lossy(),
+            scan::KeyMode::default(),
+            None,
+            &cancel,
+        );
+        let _ = std::fs::remove_dir_all(&base);
 
-    let ignore_rules = IgnoreRules::new(&[]);
+        assert_eq!(
+            result,
+            DirResolveInfo {
+                left_size: 3001,
+                right_size: 21_400,
+                left_unique_size: 3001,
+                right_unique_size: 21_400,
+                only_left: 1,
+                // `only_right/` counts once, however many files it holds
+                only_right: 2,
+                modified: 2,
+                depth_limited: 0,
+                unreadable: 0,
+            }
+        );
+        assert_eq!(result.status(), CompareStatus::Modified);
+    }
 
-    let left_entries = match std::fs::read_dir(left_path) {
-        Ok(rd) => collect_entries(rd, &ignore_rules),
-        Err(_) => Vec::new(),
-    };
-    let right_entries = match std::fs::read_dir(right_path) {
-        Ok(rd) => collect_entries(rd, &ignore_rules),
-        Err(_) => Vec::new(),
-    };
+    #[cfg(unix)]
+    #[test]
+    fn test_dir_sizes_count_hard_links_once() {
+        let base = std::env::temp_dir().join("sc_dir_hard_links");
+        let _ = std::fs::remove_dir_all(&base);
+        let (left, right) = (base.join("l"), base.join("r"));
+        write_sized(&left.join("snap1/data.bin"), 1000);
+        std::fs::create_dir_all(left.join("snap2")).unwrap();
+        std::fs::create_dir_all(&right).unwrap();
+        std::fs::hard_link(left.join("snap1/data.bin"), left.join("snap2/data.bin")).unwrap();
+        std::fs::hard_link(left.join("snap1/data.bin"), left.join("snap2/copy.bin")).unwrap();
 
-    let left_map: HashMap<String, (EntryKind, u64)> = left_entries
-        .iter()
-        .map(|e| (e.name.to_lowercase(), (e.kind, e.size)))
-        .collect();
-    let right_map: HashMap<String, (EntryKind, u64)> = right_entries
-        .iter()
-        .map(|e| (e.name.to_lowercase(), (e.kind, e.size)))
-        .collect();
+        let cancel = AtomicBool::new(false);
+        let result = dirs_are_same_recursive_counted(
+            &left.to_string_lossy(),
+            &right.to_string_lossy(),
+            scan::KeyMode::default(),
+            None,
+            &cancel,
+        );
+        let listed = compare_dirs(&left.join("snap2"), &right).unwrap();
+        let _ = std::fs::remove_dir_all(&base);
 
-    let mut total_size = 0u64;
-    let mut is_same = left_map.len() == right_map.len();
+        assert_eq!((result.left_size, result.left_unique_size), (3000, 1000));
+        let summary = listed.summary;
+        assert_eq!((summary.bytes_only_left, summary.unique_bytes_only_left), (2000, 1000));
+    }
 
-    for (key, (l_kind, l_size)) in &left_map {
-        if cancel.load(Ordering::Relaxed) {
-            return (false, total_size);
+    #[test]
+    fn test_dir_resolve_depth_limit() {
+        let base = std::env::temp_dir().join("sc_dir_resolve_depth");
+        let _ = std::fs::remove_dir_all(&base);
+        let (left, right) = (base.join("l"), base.join("r"));
+        // Four levels below the pair; the only difference is at the bottom
+        for (side, deep_size) in [(&left, 10), (&right, 20)] {
+            write_sized(&side.join("a/mid.bin"), 5);
+            write_sized(&side.join("a/b/c/deep.bin"), deep_size);
         }
+        let (left, right) = (left.to_string_lossy(), right.to_string_lossy());
+        let cancel = AtomicBool::new(false);
+        let shallow = dirs_are_same_recursive_counted(&left, &right, scan::KeyMode::default(), Some(2), &cancel);
+        let full = dirs_are_same_recursive_counted(&left, &right, scan::KeyMode::default(), None, &cancel);
+        let _ = std::fs::remove_dir_all(&base);
 
-        if *l_kind != EntryKind::Dir {
-            total_size += l_size;
-        }
+        // `a/b` is listed but not walked
+        assert_eq!(shallow.depth_limited, 1);
+        assert_eq!(shallow.modified, 0);
+        assert_eq!(shallow.status(), CompareStatus::Partial);
+        assert_eq!(full.depth_limited, 0);
+        assert_eq!(full.status(), CompareStatus::Modified);
+    }
 
-        if !is_same {
-            // Already different, but keep accumulating size
-            if *l_kind == EntryKind::Dir {
-                let l_name = left_entries
-                    .iter()
-                    .find(|e| e.name.to_lowercase() == *key)
-                    .map(|e| &e.name)
-                    .unwrap();
-                let sub_left = format!("{}/{}", left_path, l_name);
-                let (_, sub_size) = dirs_are_same_recursive_counted(&sub_left, &sub_left, cancel);
-                total_size += sub_size;
-            }
-            continue;
-        }
+    #[test]
+    fn test_dir_resolve_honors_cancel() {
+        let (left, right) = dir_pair("sc_dir_resolve_cancel");
+        let cancel = AtomicBool::new(true);
+        let result = dirs_are_same_recursive_counted(
+            &left.to_string_lossy(),
+            &right.to_string_lossy(),
+            scan::KeyMode::default(),
+            None,
+            &cancel,
+        );
+        let _ = std::fs::remove_dir_all(left.parent().unwrap());
 
-        match right_map.get(key) {
-            None => {
-                is_same = false;
-            }
-            Some((r_kind, r_size)) => {
-                if l_kind != r_kind {
-                    is_same = false;
-                } else if *l_kind == EntryKind::Dir {
-                    let l_name = left_entries
-                        .iter()
-                        .find(|e| e.name.to_lowercase() == *key)
-                        .map(|e| &e.name)
-                        .unwrap();
-                    let r_name = right_entries
-                        .iter()
-                        .find(|e| e.name.to_lowercase() == *key)
-                        .map(|e| &e.name)
-                        .unwrap();
-                    let sub_left = format!("{}/{}", left_path, l_name);
-                    let sub_right = format!("{}/{}", right_path, r_name);
-                    let (sub_same, sub_size) =
-                        dirs_are_same_recursive_counted(&sub_left, &sub_right, cancel);
-                    total_size += sub_size;
-                    if !sub_same {
-                        is_same = false;
-                    }
-                } else if l_size != r_size {
-                    is_same = false;
-                }
-            }
-        }
+        assert_eq!(result, DirResolveInfo::default());
     }
 
-    (is_same, total_size)
-}
+    #[test]
+    fn test_collect_entries_stops_on_cancel() {
+        let (left, _) = dir_pair("sc_collect_cancel");
+        let rules = IgnoreRules::new(&[]);
+        let listed = |cancelled: bool| {
+            let read_dir = std::fs::read_dir(&left).unwrap();
+            collect_entries(read_dir, &rules, &AtomicBool::new(cancelled)).len()
+        };
+        let (all, cancelled) = (listed(false), listed(true));
+        let _ = std::fs::remove_dir_all(left.parent().unwrap());
 
-/// Resolves pending directory statuses one-by-one, emitting events for each.
-#[tauri::command]
-pub async fn resolve_dir_statuses(
-    left_path: String,
-    right_path: String,
-    app: AppHandle,
-    state: State<'_, AppState>,
-) -> Result<(), String> {
-    state.dir_resolve_cancel.store(false, Ordering::Relaxed);
-    let cancel = Arc::clone(&state.dir_resolve_cancel);
-    let cache = Arc::clone(&state.dir_resolve_cache);
+        assert!(all > 0);
+        assert_eq!(cancelled, 0);
+    }
 
-    tokio::task::spawn_blocking(move || {
-        let ignore_rules = IgnoreRules::new(&[]);
+    #[test]
+    fn test_unreadable_folder_is_an_error_not_empty() {
+        let (left, right) = dir_pair("sc_dir_resolve_unreadable");
+        let gone = left.parent().unwrap().join("gone");
+        let (left, right, gone) = (
+            left.to_string_lossy().into_owned(),
+            right.to_string_lossy().into_owned(),
+            gone.to_string_lossy().into_owned(),
+        );
+        let cancel = AtomicBool::new(false);
+        let key_mode = scan::KeyMode::default();
+        let info = dirs_are_same_recursive_counted(&left, &gone, key_mode, None, &cancel);
+        let pending = pending_dir_pairs(&left, &gone, key_mode, &cancel);
+        let readable = pending_dir_pairs(&left, &right, key_mode, &cancel);
+        let _ = std::fs::remove_dir_all(Path::new(&left).parent().unwrap());
 
-        // Re-read directory to find pending dirs (both sides have same-named dirs)
-        let left_entries = match std::fs::read_dir(&left_path) {
-            Ok(rd) => collect_entries(rd, &ignore_rules),
-            Err(_) => return,
-        };
-        let right_entries = match std::fs::read_dir(&right_path) {
-            Ok(rd) => collect_entries(rd, &ignore_rules),
-            Err(_) => return,
-        };
+        assert_eq!(info.status(), CompareStatus::Error);
+        assert_eq!((info.only_left, info.unreadable), (0, 1));
+        assert_eq!(pending.unwrap_err().code(), "notFound");
+        assert!(readable.unwrap().is_empty());
+    }
 
-        let left_map: HashMap<String, &BrowseEntry> = left_entries
-            .iter()
-            .map(|e| (e.name.to_lowercase(), e))
-            .collect();
-        let right_map: HashMap<String, &BrowseEntry> = right_entries
+    #[test]
+    fn test_nfc_and_nfd_folders_resolve_as_same() {
+        let base = std::env::temp_dir().join("sc_dir_resolve_nfd");
+        let _ = std::fs::remove_dir_all(&base);
+        let (nfc, nfd) = ("caf\u{e9}", "cafe\u{301}");
+        write_sized(&base.join("l").join(nfc).join(format!("{nfc}.txt")), 4);
+        write_sized(&base.join("r").join(nfd).join(format!("{nfd}.txt")), 4);
+        let (left, right) = (
+            base.join("l").to_string_lossy().into_owned(),
+            base.join("r").to_string_lossy().into_owned(),
+        );
+        let cancel = AtomicBool::new(false);
+        let key_mode = scan::KeyMode::default();
+        let pending = pending_dir_pairs(&left, &right, key_mode, &cancel).unwrap();
+        let infos: Vec<_> = pending
             .iter()
-            .map(|e| (e.name.to_lowercase(), e))
+            .map(|(_, l, r)| dirs_are_same_recursive_counted(l, r, key_mode, None, &cancel))
             .collect();
+        let exact = scan::KeyMode { normalize_unicode: false, ..key_mode };
+        let unmatched = pending_dir_pairs(&left, &right, exact, &cancel).unwrap();
+        let _ = std::fs::remove_dir_all(&base);
 
-        // Collect dirs that exist on both sides with same kind
-        let mut pending_dirs: Vec<(String, String, String)> = Vec::new(); // (name, sub_left, sub_right)
-        for (key, l) in &left_map {
-            if let Some(r) = right_map.get(key) {
-                if l.kind == EntryKind::Dir && r.kind == EntryKind::Dir {
-                    let sub_left = format!("{}/{}", left_path, l.name);
-                    let sub_right = format!("{}/{}", right_path, r.name);
-                    pending_dirs.push((l.name.clone(), sub_left, sub_right));
-                }
-            }
-        }
+        assert_eq!(pending.len(), 1);
+        assert_eq!(pending[0].0, nfc);
+        assert_eq!(infos[0].status(), CompareStatus::Same);
+        assert_eq!((infos[0].only_left, infos[0].only_right), (0, 0));
+        assert!(unmatched.is_empty());
+    }
 
-        // Resolve all pending dirs in parallel — small dirs finish fast
-        std::thread::scope(|s| {
-            for (name, sub_left, sub_right) in pending_dirs {
-                let cancel = &cancel;
-                let cache = &cache;
-                let app = &app;
-                let left_path = &left_path;
-                let right_path = &right_path;
-
-                s.spawn(move || {
-                    if cancel.load(Ordering::Relaxed) {
-                        return;
-                    }
+    #[test]
+    fn test_pending_dirs_resolve_on_bounded_pool() {
+        use std::sync::atomic::AtomicUsize;
 
-                    let (is_same, total_size) =
-                        dirs_are_same_recursive_counted(&sub_left, &sub_right, cancel);
+        let base = std::env::temp_dir().join("sc_resolve_pool");
+        let _ = std::fs::remove_dir_all(&base);
+        let pending: Vec<(String, String)> = (0..120)
+            .map(|i| {
+                let left = base.join(format!("l/d{}", i));
+                let right = base.join(format!("r/d{}", i));
+                for dir in [&left, &right] {
+                    std::fs::create_dir_all(dir).unwrap();
+                    std::fs::write(dir.join("f.txt"), "x").unwrap();
+                }
+                (left.to_string_lossy().to_string(), right.to_string_lossy().to_string())
+            })
+            .collect();
 
-                    if cancel.load(Ordering::Relaxed) {
-                        return;
-                    }
+        let (active, peak) = (AtomicUsize::new(0), AtomicUsize::new(0));
+        let resolved = Mutex::new(Vec::new());
+        let cancel = AtomicBool::new(false);
+        for_each_pending_dir(pending, 4, &cancel, |(left, right)| {
+            let now = active.fetch_add(1, Ordering::SeqCst) + 1;
+            peak.fetch_max(now, Ordering::SeqCst);
+            let info = dirs_are_same_recursive_counted(&left, &right, scan::KeyMode::default(), None, &cancel);
+            std::thread::sleep(std::time::Duration::from_millis(1));
+            resolved.lock().unwrap().push((left, info.status()));
+            active.fetch_sub(1, Ordering::SeqCst);
+        });
+        let _ = std::fs::remove_dir_all(&base);
 
-                    let status = if is_same {
-                        CompareStatus::Same
-                    } else {
-                        CompareStatus::Modified
-                    };
+        let resolved = resolved.into_inner().unwrap();
+        assert_eq!(resolved.len(), 120);
+        assert!(resolved.iter().all(|(_, status)| *status == CompareStatus::Same));
+        assert!(peak.load(Ordering::SeqCst) <= 4);
+    }
 
-                    // Cache the result for reuse on re-navigation
-                    cache.lock().unwrap().insert(
-                        (sub_left, sub_right),
-                        (status, total_size),
-                    );
-
-                    let _ = app.emit(
-                        EVENT_DIR_STATUS_RESOLVED,
-                        DirStatusResolvedPayload {
-                            name,
-                            status,
-                            left_path: left_path.clone(),
-                            right_path: right_path.clone(),
-                            total_size,
-                        },
-                    );
-                });
-            }
+    #[test]
+    fn test_pending_dirs_stop_on_cancel() {
+        let cancel = AtomicBool::new(false);
+        let resolved = Mutex::new(0);
+        for_each_pending_dir((0..50).collect(), 2, &cancel, |_: i32| {
+            *resolved.lock().unwrap() += 1;
+            cancel.store(true, Ordering::Relaxed);
         });
-    });
+        // Each worker finishes the directory it took, then stops
+        assert!(*resolved.lock().unwrap() <= 2);
+    }
 
-    Ok(())
-}
+    #[cfg(unix)]
+    #[test]
+    fn test_dangling_link_is_copied_and_moved_as_a_link() {
+        let (left, right) = dir_pair("sc_cmd_dangling_source");
+        let base = left.parent().unwrap().to_path_buf();
+        let target = base.join("gone");
+        std::os::unix::fs::symlink(&target, left.join("link")).unwrap();
+        std::fs::create_dir(base.join("moved")).unwrap();
+        let app = tauri::test::mock_app();
+        app.manage(AppState::new());
+        let state = app.state::<AppState>();
+        state.allowed_roots.lock_or_recover().push(base.clone());
+        let path = |p: &Path| p.to_string_lossy().into_owned();
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+        let (h, s) = (|| app.handle().clone(), || state.clone());
+        let link = path(&left.join("link"));
+        let (copied, moved) = runtime.block_on(async {
+            let copied = copy_entry(link.clone(), path(&right), None, None, h(), s()).await;
+            let moved = move_entry(link, path(&base.join("moved")), None, None, h(), s()).await;
+            (copied, moved)
+        });
+        let read_link = |p: PathBuf| std::fs::read_link(p).ok();
+        let links = (
+            read_link(right.join("link")),
+            read_link(base.join("moved/link")),
+            std::fs::symlink_metadata(left.join("link")).is_err(),
+        );
+        let _ = std::fs::remove_dir_all(&base);
 
-/// Cancels any running directory resolution background task.
-#[tauri::command]
-pub async fn cancel_dir_resolve(state: State<'_, AppState>) -> Result<(), String> {
-    state.dir_resolve_cancel.store(true, Ordering::Relaxed);
-    Ok(())
-}
+        assert_eq!(copied.unwrap().dest, right.join("link"));
+        assert_eq!(moved.unwrap().dest, path(&base.join("moved/link")));
+        assert_eq!(links, (Some(target.clone()), Some(target), true));
+    }
 
-/// Clears the directory resolve cache. Called when starting a new comparison or returning to browse.
-#[tauri::command]
-pub async fn clear_dir_resolve_cache(state: State<'_, AppState>) -> Result<(), String> {
-    state.dir_resolve_cache.lock().unwrap().clear();
-    Ok(())
-}
+    #[test]
+    fn test_state_with_a_missing_root_is_kept() {
+        let (left, right) = dir_pair("sc_restore_missing_root");
+        let base = left.parent().unwrap().to_path_buf();
+        let gone = base.join("unplugged");
+        let saved = serde_json::json!({
+            "version": 5,
+            "leftPath": gone,
+            "rightPath": right,
+            "leftSelectedIndex": 4,
+            "leftSort": { "sortBy": "size", "ascending": false },
+            "leftHistory": { "back": [left], "forward": [] },
+            "rightSelectedIndex": 2,
+            "rightTabs": [{ "path": right, "selectedIndex": 2 }, { "path": left }],
+        });
+        let state = restored_state(&saved.to_string(), Some(&base)).unwrap();
+        assert!(restored_state("not json", Some(&base)).is_none());
+        let _ = std::fs::remove_dir_all(&base);
 
-// --- Terminal commands ---
+        let path = |p: &Path| p.to_string_lossy().into_owned();
+        assert_eq!(state.left_path, path(&base));
+        assert_eq!(state.left_selected_index, 0);
+        assert_eq!(state.left_history.back, vec![path(&left)]);
+        assert!(!state.left_sort.ascending);
+        assert_eq!((state.right_path.clone(), state.right_selected_index), (path(&right), 2));
+        assert_eq!(state.right_tabs.len(), 2);
+    }
 
-/// Returns a reference to the PTY mutex for the given side.
-fn get_pty_mutex<'a>(state: &'a AppState, side: &str) -> Result<&'a Mutex<Option<pty::PtyState>>, String> {
-    match side {
-        "left" => Ok(&state.pty_left),
-        "right" => Ok(&state.pty_right),
-        _ => Err(format!("Invalid terminal side: {}", side)),
+    #[test]
+    fn test_overwriting_move_is_not_journaled() {
+        let (left, right) = dir_pair("sc_cmd_move_journal");
+        let app = tauri::test::mock_app();
+        app.manage(AppState::new());
+        let state = app.state::<AppState>();
+        state.allowed_roots.lock_or_recover().push(left.parent().unwrap().to_path_buf());
+        let path = |p: &Path| p.to_string_lossy().into_owned();
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+        let (h, s) = (|| app.handle().clone(), || state.clone());
+        let journaled = runtime.block_on(async {
+            let same = path(&left.join("same.txt"));
+            let overwrite = Some("overwrite".to_string());
+            move_entry(same, path(&right), overwrite, None, h(), s()).await.unwrap();
+            let after_overwrite = state.undo_journal.lock_or_recover().entries().len();
+            let extra = path(&left.join("extra.txt"));
+            move_entry(extra, path(&right), None, None, h(), s()).await.unwrap();
+            (after_overwrite, state.undo_journal.lock_or_recover().entries())
+        });
+        let _ = std::fs::remove_dir_all(left.parent().unwrap());
+
+        assert_eq!(journaled.0, 0);
+        assert_eq!(journaled.1.len(), 1);
+        assert!(matches!(
+            &journaled.1[0].action,
+            UndoAction::Move { to, .. } if to == &right.join("extra.txt")
+        ));
     }
-}
 
-/// Spawns a PTY shell in the given working directory and starts streaming output events.
-#[tauri::command]
-pub async fn spawn_terminal(
-    side: String,
-    cwd: String,
-    rows: u16,
-    cols: u16,
-    app: AppHandle,
-    state: State<'_, AppState>,
-) -> Result<(), String> {
-    let pty_mutex = get_pty_mutex(&state, &side)?;
-    {
-        // Clean up any previous PTY session (e.g. shell exited via Ctrl+D / exit)
-        let mut pty_lock = pty_mutex.lock().unwrap();
-        if let Some(old) = pty_lock.take() {
-            old.reader_active
-                .store(false, std::sync::atomic::Ordering::Relaxed);
-            let mut child = old.child.lock().unwrap();
-            let _ = child.kill();
-            let _ = child.wait();
+    #[test]
+    fn test_read_only_refuses_changes_but_not_browsing() {
+        fn refused<T: std::fmt::Debug>(result: Result<T, CommandError>) {
+            assert_eq!(result.unwrap_err().code(), "readOnlyMode");
         }
+
+        let (left, right) = dir_pair("sc_cmd_read_only");
+        let base = left.parent().unwrap().to_path_buf();
+        let app = tauri::test::mock_app();
+        app.manage(AppState::new());
+        let handle = app.handle().clone();
+        let state = app.state::<AppState>();
+        assert!(ensure_writable(&state).is_ok());
+        store_compare(&state, &left, &right);
+        state.allowed_roots.lock_or_recover().push(base.clone());
+
+        apply_settings(&state, &Settings { read_only: true, ..Default::default() });
+        let path = |p: &Path| p.to_string_lossy().into_owned();
+        let (extra, same) = (path(&left.join("extra.txt")), path(&right.join("same.txt")));
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+        runtime.block_on(async {
+            let (h, s) = (|| handle.clone(), || state.clone());
+            refused(copy_entry(extra.clone(), path(&right), None, None, h(), s()).await);
+            refused(copy_entry_overwrite(extra.clone(), path(&right), None, h(), s()).await);
+            refused(copy_rel_entry("left".into(), "extra.txt".into(), None, None, h(), s()).await);
+            refused(duplicate_entry(extra.clone(), s()).await);
+            refused(move_entry(extra.clone(), path(&right), None, None, h(), s()).await);
+            refused(delete_entry(extra.clone(), None, h(), s()).await);
+            refused(create_directory(path(&right), "new".into(), s()).await);
+            refused(create_file(path(&right), "new.txt".into(), None, s()).await);
+            refused(rename_entry(same.clone(), "renamed.txt".into(), s()).await);
+            refused(set_permissions(same.clone(), 0o600, false, None, s()).await);
+            refused(set_modified(same.clone(), 0, s()).await);
+            refused(copy_times(extra.clone(), same.clone(), s()).await);
+            let zip = path(&base.join("out.zip"));
+            refused(
+                create_archive(
+                    vec![extra.clone()],
+                    zip.clone(),
+                    "zip".into(),
+                    None,
+                    h(),
+                    s(),
+                )
+                .await,
+            );
+            refused(extract_archive(zip, path(&right), None, h(), s()).await);
+            let all = vec!["extra.txt".to_string()];
+            refused(sync_diffs("leftToRight".into(), all.clone(), true, None, h(), s()).await);
+            refused(undo_last_operation(s()).await);
+            refused(spawn_terminal("left".into(), path(&left), 24, 80, None, h(), s()).await);
+            refused(write_terminal("left".into(), None, "ls\n".into(), s()).await);
+
+            // Dry runs and exporting the result change nothing under the roots
+            let dry_run = SyncOptions {
+                dry_run: true,
+                ..Default::default()
+            };
+            let plan = sync_diffs("leftToRight".into(), all, true, Some(dry_run), h(), s());
+            assert!(plan.await.unwrap().plan.is_some());
+            let dry_copy = CopyEntryOptions {
+                dry_run: true,
+                ..Default::default()
+            };
+            let copy = copy_entry(extra.clone(), path(&right), None, Some(dry_copy), h(), s());
+            assert!(copy.await.unwrap().plan.is_some());
+            let moved = move_entry(extra.clone(), path(&right), None, Some(true), h(), s());
+            assert!(moved.await.unwrap().plan.is_some());
+            let deleted = delete_entry(extra.clone(), Some(true), h(), s()).await;
+            assert!(deleted.unwrap().is_some());
+            let report = path(&base.join("report.json"));
+            export_report(report.clone(), None, None, None, None, h(), s())
+                .await
+                .unwrap();
+            assert!(Path::new(&report).is_file());
+        });
+
+        // Nothing under either root changed
+        assert_eq!(listed(&left).len(), 2);
+        assert_eq!(listed(&right).len(), 1);
+        assert_eq!(std::fs::read_to_string(&same).unwrap(), "s");
+        let compared = compare_directory_impl(
+            &left.to_string_lossy(),
+            &right.to_string_lossy(),
+            scan::KeyMode::default(),
+            SortOrder::default(),
+            &state.dir_resolve_cache,
+        )
+        .unwrap();
+        let extra = compared.entries.iter().find(|e| e.name == "extra.txt").unwrap();
+        assert_eq!(extra.status, CompareStatus::OnlyLeft);
+
+        state.read_only.store(false, Ordering::Relaxed);
+        assert!(ensure_writable(&state).is_ok());
+        let _ = std::fs::remove_dir_all(&base);
     }
 
-    let (pty_state, mut reader) = pty::spawn_pty(&cwd, rows, cols)?;
-    let reader_active = Arc::clone(&pty_state.reader_active);
-    *pty_mutex.lock().unwrap() = Some(pty_state);
+    #[test]
+    fn test_fileops_confined_to_allowed_roots() {
+        let dir = std::env::temp_dir().join("sc_cmd_allowed_roots");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(dir.join("left")).unwrap();
+        std::fs::create_dir_all(dir.join("right")).unwrap();
+        let state = AppState::new();
+        state.allowed_roots.lock_or_recover().clear();
+        let mut roots = state.roots.lock_or_recover();
+        roots.insert(PaneId::LEFT, dir.join("left"));
+        // Roots of tabs that are not compared count too
+        roots.insert(PaneId::parse("right:3").unwrap(), dir.join("right"));
+        drop(roots);
 
-    let app_handle = app.clone();
-    let side_clone = side.clone();
-    tokio::task::spawn_blocking(move || {
-        let mut buf = [0u8; 4096];
-        loop {
-            if !reader_active.load(std::sync::atomic::Ordering::Relaxed) {
-                break;
-            }
-            match reader.read(&mut buf) {
-                Ok(0) => break,
-                Ok(n) => {
-                    let data = String::from_utf8_lossy(&buf[..n]).to_string();
-                    let _ = app_handle.emit(
-                        EVENT_TERMINAL_OUTPUT,
-                        TerminalOutputPayload {
-                            side: side_clone.clone(),
-                            data,
-                        },
-                    );
-                }
-                Err(_) => break,
+        let (left, right) = (dir.join("left/a.txt"), dir.join("right"));
+        assert!(check_allowed(&state, &[&left], &[&right]).is_ok());
+        if std::path::Path::new("/etc").is_dir() {
+            let (etc, hosts) = (std::path::Path::new("/etc"), PathBuf::from("/etc/hosts"));
+            let cases: [(&[&std::path::Path], &[&std::path::Path]); 3] =
+                [(&[&hosts], &[]), (&[&left], &[etc]), (&[], &[etc])];
+            for (entries, dirs) in cases {
+                let err = check_allowed(&state, entries, dirs).unwrap_err();
+                assert_eq!(err.code(), "permissionDenied");
             }
+            state.unrestricted_fileops.store(true, Ordering::Relaxed);
+            assert!(check_allowed(&state, &[&hosts], &[etc]).is_ok());
         }
-        let _ = app_handle.emit(
-            EVENT_TERMINAL_EXIT,
-            TerminalExitPayload {
-                side: side_clone,
-            },
-        );
-    });
 
-    Ok(())
-}
+        let _ = std::fs::remove_dir_all(&dir);
+    }
 
-/// Writes data (keystrokes) to the PTY stdin.
-#[tauri::command]
-pub async fn write_terminal(
-    side: String,
-    data: String,
-    state: State<'_, AppState>,
-) -> Result<(), String> {
-    let pty_mutex = get_pty_mutex(&state, &side)?;
-    let pty_lock = pty_mutex.lock().unwrap();
-    let pty_state = pty_lock.as_ref().ok_or("No terminal running")?;
-    let mut writer = pty_state.writer.lock().unwrap();
-    use std::io::Write;
-    writer.write_all(data.as_bytes()).map_err(|e: std::io::Error| e.to_string())?;
-    writer.flush().map_err(|e: std::io::Error| e.to_string())?;
-    Ok(())
-}
+    #[test]
+    fn test_archive_roots_are_compared_but_not_written() {
+        let dir = std::env::temp_dir().join("sc_cmd_archive_root");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(dir.join("src")).unwrap();
+        std::fs::write(dir.join("src/a.txt"), "a").unwrap();
+        std::fs::write(dir.join("plain.txt"), "not an archive").unwrap();
+        let zip = dir.join("release.zip");
+        archive::create_archive(
+            &[dir.join("src/a.txt")],
+            &zip,
+            ArchiveFormat::Zip,
+            fileops::ConflictPolicy::Fail,
+            &AtomicBool::new(false),
+            &|_, _, _| {},
+        )
+        .unwrap();
 
-/// Notifies the PTY of a terminal size change.
-#[tauri::command]
-pub async fn resize_terminal(
-    side: String,
-    rows: u16,
-    cols: u16,
-    state: State<'_, AppState>,
-) -> Result<(), String> {
-    let pty_mutex = get_pty_mutex(&state, &side)?;
-    let pty_lock = pty_mutex.lock().unwrap();
-    let pty_state = pty_lock.as_ref().ok_or("No terminal running")?;
-    let master = pty_state.master.lock().unwrap();
-    master
-        .resize(portable_pty::PtySize {
-            rows,
-            cols,
-            pixel_width: 0,
-            pixel_height: 0,
-        })
-        .map_err(|e| format!("Resize failed: {}", e))?;
-    Ok(())
-}
+        let state = AppState::new();
+        let runtime = tokio::runtime::Builder::new_current_thread().build().unwrap();
+        runtime.block_on(async {
+            replace_root(&state, PaneId::RIGHT, zip.clone()).await.unwrap();
+            let err = replace_root(&state, PaneId::LEFT, dir.join("none")).await.unwrap_err();
+            assert_eq!(err.code(), "invalidArgument");
+        });
 
-/// Kills the PTY process and cleans up state.
-#[tauri::command]
-pub async fn kill_terminal(
-    side: String,
-    state: State<'_, AppState>,
-) -> Result<(), String> {
-    let pty_mutex = get_pty_mutex(&state, &side)?;
-    let mut pty_lock = pty_mutex.lock().unwrap();
-    if let Some(pty_state) = pty_lock.take() {
-        pty_state
-            .reader_active
-            .store(false, std::sync::atomic::Ordering::Relaxed);
-        let mut child = pty_state.child.lock().unwrap();
-        let _ = child.kill();
-        let _ = child.wait();
-    }
-    Ok(())
-}
+        let never = AtomicBool::new(false);
+        let source = tree_source(&zip).unwrap();
+        let scan = source
+            .scan(&IgnoreRules::new(&[]), &[], &Default::default(), &never, &|_| {})
+            .unwrap();
+        assert_eq!(scan.originals.values().collect::<Vec<_>>(), ["a.txt"]);
+        let err = writable_root(&zip).unwrap_err();
+        assert_eq!(err.code(), "invalidArgument");
+        assert!(err.to_string().contains("is an archive"));
+        let err = writable_root(&dir.join("src/a.txt")).unwrap_err();
+        assert!(err.to_string().contains("is a single file"), "{}", err);
+        assert!(writable_root(&dir.join("src")).is_ok());
 
-/// Lightweight entry collection for recursive comparison (no modified time needed).
-fn collect_entries(
-    read_dir: std::fs::ReadDir,
-    ignore_rules: &IgnoreRules,
-) -> Vec<BrowseEntry> {
-    let mut entries = Vec::new();
-    for entry_result in read_dir {
-        let entry = match entry_result {
-            Ok(e) => e,
-            Err(_) => continue,
-        };
-        let name = entry.file_name().to_string_lossy().to_string();
-        if ignore_rules.is_ignored(&name) {
-            continue;
-        }
-        let file_type = match entry.file_type() {
-            Ok(ft) => ft,
-            Err(_) => continue,
-        };
-        let is_symlink = file_type.is_symlink();
-        let metadata = entry.metadata().or_else(|_| entry.path().symlink_metadata());
-        let (kind, size) = match metadata {
-            Ok(m) => {
-                let kind = if is_symlink {
-                    EntryKind::Symlink
-                } else if m.is_dir() {
-                    EntryKind::Dir
-                } else {
-                    EntryKind::File
-                };
-                (kind, m.len())
-            }
-            Err(_) => {
-                let kind = if is_symlink {
-                    EntryKind::Symlink
-                } else if file_type.is_dir() {
-                    EntryKind::Dir
-                } else {
-                    EntryKind::File
-                };
-                (kind, 0)
-            }
-        };
-        entries.push(BrowseEntry {
-            name,
-            kind,
-            size,
-            modified: None,
+        // Copying, syncing and resolving statuses each refuse the archive root
+        let src = dir.join("src").to_string_lossy().into_owned();
+        runtime.block_on(async {
+            replace_root(&state, PaneId::LEFT, dir.join("src")).await.unwrap();
         });
-    }
-    entries
-}
+        let err = rel_copy_roots(&state, "left").unwrap_err();
+        assert!(err.to_string().contains("is an archive"), "{}", err);
+        store_compare(&state, &dir.join("src"), &dir.join("src"));
+        assert!(synced_result(&state).is_ok());
+        if let Some(stored) = state.last_result.lock_or_recover().as_mut() {
+            stored.right_root = zip.to_string_lossy().into_owned();
+        }
+        let err = synced_result(&state).unwrap_err();
+        assert!(err.to_string().contains("is an archive"), "{}", err);
+        let err = resolvable_roots(&src, &zip.to_string_lossy()).unwrap_err();
+        assert_eq!(err.code(), "invalidArgument");
+        assert!(err.to_string().contains("is an archive"), "{}", err);
+        assert!(resolvable_roots(&src, &src).is_ok());
 
-fn list_directory_impl(path: &str) -> Result<Vec<BrowseEntry>, String> {
-    let dir = PathBuf::from(path);
-    if !dir.is_dir() {
-        return Err(format!("Not a directory: {}", path));
+        let _ = std::fs::remove_dir_all(&dir);
     }
 
-    let ignore_rules = IgnoreRules::new(&[]);
-    let mut entries = Vec::with_capacity(64);
-
-    let read_dir = std::fs::read_dir(&dir).map_err(|e| format!("Cannot read {}: {}", path, e))?;
+    #[test]
+    fn test_two_file_roots_compare_as_one_entry() {
+        let (left, right) = dir_pair("sc_cmd_file_roots");
+        std::fs::write(right.join("other.txt"), "longer").unwrap();
+        let (left_file, right_file) = (left.join("same.txt"), right.join("other.txt"));
 
-    for entry_result in read_dir {
-        let entry = match entry_result {
-            Ok(e) => e,
-            Err(_) => continue,
+        let (left_source, right_source) = tree_sources(&left_file, &right_file).unwrap();
+        let never = AtomicBool::new(false);
+        let scan = |source: Box<dyn TreeSource>| {
+            source
+                .scan(&IgnoreRules::new(&[]), &[], &Default::default(), &never, &|_| {})
+                .unwrap()
         };
+        let (left_scan, right_scan) = (scan(left_source), scan(right_source));
+        let result = compare::compare(&left_scan, &right_scan, CompareMode::Smart, &never).unwrap();
+        assert_eq!((result.summary.total_left, result.summary.total_right), (1, 1));
+        assert_eq!(result.diffs.len(), 1);
+        assert_eq!(result.diffs[0].rel_path, "same.txt");
+        assert_eq!(result.diffs[0].diff_kind, DiffKind::MetaDiff);
 
-        let name = entry.file_name().to_string_lossy().to_string();
-
-        if ignore_rules.is_ignored(&name) {
-            continue;
-        }
+        let diff = textdiff::diff_files(&left_file, &right_file, &DiffOptions::default()).unwrap();
+        assert_eq!((diff.left_size, diff.right_size), (1, 6));
 
-        // file_type() doesn't follow symlinks — reliable for detecting symlinks
-        let file_type = match entry.file_type() {
-            Ok(ft) => ft,
-            Err(_) => continue,
-        };
-        let is_symlink = file_type.is_symlink();
+        let err = tree_sources(&left_file, &right).err().unwrap();
+        let _ = std::fs::remove_dir_all(left.parent().unwrap());
+        assert_eq!(err.code(), "invalidArgument");
+        assert!(err.to_string().contains("Cannot compare the file"));
+    }
 
-        // metadata() follows symlinks. Fall back to symlink_metadata for broken links.
-        let metadata = entry.metadata().or_else(|_| entry.path().symlink_metadata());
+    #[test]
+    fn test_root_change_stops_running_compare() {
+        let (left, right) = dir_pair("sc_root_change_stops");
+        let state = Arc::new(AppState::new());
+        state.roots.lock_or_recover().insert(PaneId::LEFT, left.clone());
+        state.roots.lock_or_recover().insert(PaneId::RIGHT, right.clone());
 
-        let (kind, size, modified) = match metadata {
-            Ok(m) => {
-                let kind = if is_symlink {
-                    EntryKind::Symlink
-                } else if m.is_dir() {
-                    EntryKind::Dir
-                } else {
-                    EntryKind::File
+        // Scans and compares both roots like the compare task does. The first progress
+        // report holds the left scan until the job is cancelled, so the root change is
+        // sure to land while it runs.
+        let outcome = Arc::new(Mutex::new(None::<Result<usize, String>>));
+        let scanning = Arc::new(AtomicBool::new(false));
+        let work = {
+            let (outcome, scanning) = (Arc::clone(&outcome), Arc::clone(&scanning));
+            let (left, right) = (left.clone(), right.clone());
+            move |_job_id: u64, cancel: &AtomicBool| {
+                let options = scan::ScanOptions::default();
+                let ignore = IgnoreRules::new(&[]);
+                let held = |_: &scan::ScanProgress| {
+                    scanning.store(true, Ordering::Relaxed);
+                    while !cancel.load(Ordering::Relaxed) {
+                        std::thread::sleep(std::time::Duration::from_millis(1));
+                    }
                 };
-                let modified = m
-                    .modified()
-                    .ok()
-                    .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
-                    .map(|d| d.as_millis() as u64);
-                (kind, m.len(), modified)
+                let compared = LocalSource::new(&left)
+                    .scan(&ignore, &[], &options, cancel, &held)
+                    .and_then(|l| {
+                        let r =
+                            LocalSource::new(&right).scan(&ignore, &[], &options, cancel, &|_| {})?;
+                        compare::compare(&l, &r, CompareMode::Smart, cancel)
+                    })
+                    .map(|result| result.diffs.len());
+                *outcome.lock().unwrap() = Some(compared);
             }
-            Err(_) => {
-                // No metadata at all — still show the entry
-                let kind = if is_symlink {
-                    EntryKind::Symlink
-                } else if file_type.is_dir() {
-                    EntryKind::Dir
-                } else {
-                    EntryKind::File
-                };
-                (kind, 0, None)
+        };
+
+        let runtime = tokio::runtime::Builder::new_current_thread().build().unwrap();
+        runtime.block_on(async {
+            state.compare_job.start(false, || {}, work).await.unwrap();
+            while !scanning.load(Ordering::Relaxed) {
+                tokio::task::yield_now().await;
+            }
+            replace_root(&state, PaneId::LEFT, right.clone()).await.unwrap();
+        });
+        // Taken right away: replace_root must have waited for the job to end
+        let outcome = outcome.lock().unwrap().take();
+        let _ = std::fs::remove_dir_all(left.parent().unwrap());
+
+        assert_eq!(state.root(PaneId::LEFT), Some(right));
+        assert_eq!(outcome, Some(Err("Scan cancelled".to_string())));
+    }
+
+    #[test]
+    fn test_explicit_compare_paths_become_the_roots_as_the_compare_starts() {
+        let (left, right) = dir_pair("sc_compare_paths");
+        let state = Arc::new(AppState::new());
+        state.roots.lock_or_recover().insert(PaneId::LEFT, right.clone());
+        let tab = PaneId::parse("right:1").unwrap();
+        let panes = (PaneId::LEFT, tab);
+        let path = |p: &Path| Some(p.to_string_lossy().into_owned());
+        let roots = ComparedRoots::resolve(&state, panes, path(&left), path(&right)).unwrap();
+        assert_eq!((&roots.left, &roots.right), (&left, &right));
+        let missing = ComparedRoots::resolve(&state, panes, path(&left.join("gone")), None);
+        assert_eq!(missing.err().map(|e| e.code()), Some("invalidArgument"));
+
+        // What the job sees as it starts: the roots are claimed before it runs
+        let seen = Arc::new(Mutex::new(None));
+        let work = {
+            let (state, seen) = (Arc::clone(&state), Arc::clone(&seen));
+            move |_: u64, _: &AtomicBool| {
+                let compared = *state.compared.lock_or_recover();
+                *seen.lock().unwrap() = Some((state.root(PaneId::LEFT), state.root(tab), compared));
             }
         };
+        let runtime = tokio::runtime::Builder::new_current_thread().build().unwrap();
+        runtime.block_on(async {
+            let claim = || roots.claim(&state);
+            state.compare_job.start(false, claim, work).await.unwrap();
+            state.compare_job.stop().await;
+        });
+        let _ = std::fs::remove_dir_all(left.parent().unwrap());
+        assert_eq!(
+            seen.lock().unwrap().take(),
+            Some((Some(left), Some(right), panes))
+        );
+    }
 
-        entries.push(BrowseEntry {
-            name,
-            kind,
-            size,
-            modified,
+    #[test]
+    fn test_bare_sides_use_first_tabs() {
+        let (left, right) = dir_pair("sc_pane_tabs");
+        let state = AppState::new();
+        let runtime = tokio::runtime::Builder::new_current_thread().build().unwrap();
+        runtime.block_on(async {
+            let tab = PaneId::of("left", None).unwrap();
+            replace_root(&state, tab, left.clone()).await.unwrap();
+            assert!(state.compared_roots().is_err());
+            replace_root(&state, PaneId::of("right", None).unwrap(), right.clone())
+                .await
+                .unwrap();
+            let other = PaneId::of("right", Some("right:1")).unwrap();
+            replace_root(&state, other, left.clone()).await.unwrap();
         });
+        let _ = std::fs::remove_dir_all(left.parent().unwrap());
+
+        assert_eq!(state.compared_roots().unwrap(), (left.clone(), right));
+        assert_eq!(state.root(PaneId::parse("right:1").unwrap()), Some(left));
+        assert_eq!(state.roots.lock_or_recover().len(), 3);
+        assert!(state.ptys.lock_or_recover().is_empty());
     }
 
-    // Sort: dirs first, then alphabetically
-    entries.sort_by(|a, b| {
-        let a_is_dir = a.kind == EntryKind::Dir;
-        let b_is_dir = b.kind == EntryKind::Dir;
-        b_is_dir
-            .cmp(&a_is_dir)
-            .then_with(|| a.name.to_lowercase().cmp(&b.name.to_lowercase()))
-    });
+    #[test]
+    fn test_closed_tab_root_is_no_longer_allowed() {
+        let (left, right) = dir_pair("sc_close_pane");
+        let state = AppState::new();
+        state.allowed_roots.lock_or_recover().clear();
+        let tab = PaneId::parse("right:1").unwrap();
+        let runtime = tokio::runtime::Builder::new_current_thread().build().unwrap();
+        let (before, after) = runtime.block_on(async {
+            replace_root(&state, tab, right.clone()).await.unwrap();
+            let before = check_allowed(&state, &[&right.join("same.txt")], &[]);
+            forget_pane(&state, tab).await;
+            (before, check_allowed(&state, &[&right.join("same.txt")], &[]))
+        });
+        let _ = std::fs::remove_dir_all(left.parent().unwrap());
 
-    Ok(entries)
+        assert!(before.is_ok());
+        assert_eq!(after.unwrap_err().code(), "permissionDenied");
+        assert_eq!(state.root(tab), None);
+    }
+
+    #[test]
+    fn test_navigation_paths_are_not_expanded() {
+        // Folders whose names only look like `~` or a variable are opened as named
+        let (left, _) = dir_pair("sc_literal_names");
+        for name in ["~", "$HOME", "%PATH%"] {
+            std::fs::create_dir(left.join(name)).unwrap();
+            write_sized(&left.join(name).join("inside.txt"), 1);
+        }
+        let app = tauri::test::mock_app();
+        app.manage(AppState::new());
+        let state = app.state::<AppState>();
+        let runtime = tokio::runtime::Builder::new_current_thread().build().unwrap();
+        let results: Vec<_> = ["~", "$HOME", "%PATH%"]
+            .iter()
+            .map(|name| {
+                let path = left.join(name).to_string_lossy().into_owned();
+                runtime.block_on(async {
+                    let listed = list_directory(path.clone(), None, None, None, state.clone())
+                        .await
+                        .map(|entries| entries.into_iter().map(|e| e.name).collect::<Vec<_>>());
+                    set_root("left".into(), None, path, state.clone()).await.unwrap();
+                    (listed, state.root(PaneId::parse("left").unwrap()))
+                })
+            })
+            .collect();
+        let _ = std::fs::remove_dir_all(left.parent().unwrap());
+
+        for ((listed, root), name) in results.into_iter().zip(["~", "$HOME", "%PATH%"]) {
+            assert_eq!(listed.unwrap(), vec!["inside.txt".to_string()]);
+            assert_eq!(root, Some(left.join(name)));
+        }
+    }
 }
@@ -0,0 +1,302 @@
+//! Caches of resolved directory statuses (compare view) and directory sizes (browse
+//! view).
+//!
+//! Each entry remembers the modification times its directories had when their walk
+//! started. A directory's mtime changes when entries are added, removed or renamed
+//! directly inside it, so a lookup after such a change misses and the pair is resolved
+//! again. Changes deeper down do not bump it; `max_age` bounds how long those can go
+//! unnoticed on filesystems where that matters.
+//!
+//! A cache holds at most `capacity` entries; inserting beyond that evicts the least
+//! recently used ones, so long browsing sessions do not grow it without bound.
+
+use std::collections::{BTreeMap, HashMap};
+use std::hash::Hash;
+use std::time::{Duration, Instant, SystemTime};
+
+use crate::core::model::{DirResolveInfo, DirSize};
+
+/// Cache key for resolved directory statuses: (left_path, right_path).
+pub type DirCacheKey = (String, String);
+
+/// Resolved statuses of directory pairs, for the compare view.
+pub type DirResolveCache = StampedCache<DirCacheKey, DirResolveInfo>;
+
+/// Recursive sizes of directories by absolute path, for the browse view.
+pub type DirSizeCache = StampedCache<String, DirSize>;
+
+/// A cache key whose directories can be stamped with their modification times.
+pub trait Stamped: Eq + Hash + Clone {
+    type Stamp: PartialEq + Copy;
+
+    /// The key's directories as they look now.
+    fn stamp(&self) -> Self::Stamp;
+}
+
+impl Stamped for DirCacheKey {
+    type Stamp = DirStamp;
+
+    fn stamp(&self) -> DirStamp {
+        DirStamp::of(self)
+    }
+}
+
+impl Stamped for String {
+    type Stamp = Option<SystemTime>;
+
+    fn stamp(&self) -> Option<SystemTime> {
+        dir_mtime(self)
+    }
+}
+
+/// Pairs kept when no capacity is configured.
+pub const DEFAULT_DIR_CACHE_CAPACITY: usize = 4096;
+
+/// Modification times of a directory pair, taken before walking it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DirStamp {
+    left: Option<SystemTime>,
+    right: Option<SystemTime>,
+}
+
+impl DirStamp {
+    pub fn of(key: &DirCacheKey) -> Self {
+        Self {
+            left: dir_mtime(&key.0),
+            right: dir_mtime(&key.1),
+        }
+    }
+}
+
+fn dir_mtime(path: &str) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+struct CachedDir<K: Stamped, V> {
+    info: V,
+    stamp: K::Stamp,
+    stored_at: Instant,
+    /// Position in `recency`; larger is more recently used
+    last_used: u64,
+}
+
+pub struct StampedCache<K: Stamped, V> {
+    entries: HashMap<K, CachedDir<K, V>>,
+    /// Keys by `last_used`, oldest first
+    recency: BTreeMap<u64, K>,
+    next_use: u64,
+    capacity: usize,
+    /// Entries older than this are resolved again even if no mtime changed
+    max_age: Option<Duration>,
+}
+
+impl<K: Stamped, V: Clone> Default for StampedCache<K, V> {
+    fn default() -> Self {
+        Self::new(DEFAULT_DIR_CACHE_CAPACITY, None)
+    }
+}
+
+impl<K: Stamped, V: Clone> StampedCache<K, V> {
+    pub fn new(capacity: usize, max_age: Option<Duration>) -> Self {
+        Self {
+            entries: HashMap::new(),
+            recency: BTreeMap::new(),
+            next_use: 0,
+            capacity: capacity.max(1),
+            max_age,
+        }
+    }
+
+    pub fn set_max_age(&mut self, max_age: Option<Duration>) {
+        self.max_age = max_age;
+    }
+
+    /// Changes the capacity, evicting least recently used pairs that no longer fit.
+    pub fn set_capacity(&mut self, capacity: usize) {
+        self.capacity = capacity.max(1);
+        self.evict_over_capacity();
+    }
+
+    /// Stores `info` for a key whose walk started when the directories looked like `stamp`.
+    pub fn insert(&mut self, key: K, info: V, stamp: K::Stamp) {
+        let last_used = self.next_use();
+        self.recency.insert(last_used, key.clone());
+        let cached = CachedDir {
+            info,
+            stamp,
+            stored_at: Instant::now(),
+            last_used,
+        };
+        if let Some(replaced) = self.entries.insert(key, cached) {
+            self.recency.remove(&replaced.last_used);
+        }
+        self.evict_over_capacity();
+    }
+
+    /// The cached result for a key, unless any of its directories' mtimes changed since
+    /// its walk started or it is older than `max_age`. Stale entries are dropped; a hit
+    /// marks the key as recently used.
+    pub fn get(&mut self, key: &K) -> Option<V> {
+        let cached = self.entries.get(key)?;
+        let expired = self
+            .max_age
+            .is_some_and(|max_age| cached.stored_at.elapsed() > max_age);
+        if expired || cached.stamp != key.stamp() {
+            self.remove(key);
+            return None;
+        }
+
+        let last_used = self.next_use();
+        let cached = self.entries.get_mut(key)?;
+        self.recency.remove(&cached.last_used);
+        self.recency.insert(last_used, key.clone());
+        cached.last_used = last_used;
+        Some(cached.info.clone())
+    }
+
+    /// Keeps only the pairs for which `keep` returns true.
+    pub fn retain(&mut self, mut keep: impl FnMut(&K) -> bool) {
+        self.entries.retain(|key, _| keep(key));
+        let entries = &self.entries;
+        self.recency.retain(|_, key| entries.contains_key(key));
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.recency.clear();
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    fn next_use(&mut self) -> u64 {
+        self.next_use += 1;
+        self.next_use
+    }
+
+    fn remove(&mut self, key: &K) {
+        if let Some(cached) = self.entries.remove(key) {
+            self.recency.remove(&cached.last_used);
+        }
+    }
+
+    fn evict_over_capacity(&mut self) {
+        while self.entries.len() > self.capacity {
+            let Some((_, oldest)) = self.recency.pop_first() else {
+                break;
+            };
+            self.entries.remove(&oldest);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dir_pair(name: &str) -> DirCacheKey {
+        let base = std::env::temp_dir().join(name);
+        let _ = std::fs::remove_dir_all(&base);
+        let (left, right) = (base.join("left"), base.join("right"));
+        std::fs::create_dir_all(&left).unwrap();
+        std::fs::create_dir_all(&right).unwrap();
+        (
+            left.to_string_lossy().to_string(),
+            right.to_string_lossy().to_string(),
+        )
+    }
+
+    fn cleanup(key: &DirCacheKey) {
+        let base = std::path::Path::new(&key.0).parent().unwrap();
+        let _ = std::fs::remove_dir_all(base);
+    }
+
+    #[test]
+    fn test_hit_while_unchanged() {
+        let key = dir_pair("sc_dir_cache_hit");
+        let mut cache = DirResolveCache::default();
+        cache.insert(key.clone(), DirResolveInfo::default(), DirStamp::of(&key));
+
+        assert!(cache.get(&key).is_some());
+        assert!(cache.get(&key).is_some());
+        cleanup(&key);
+    }
+
+    #[test]
+    fn test_changed_mtime_invalidates() {
+        let key = dir_pair("sc_dir_cache_mtime");
+        let mut cache = DirResolveCache::default();
+        let stamp = DirStamp::of(&key);
+        cache.insert(key.clone(), DirResolveInfo::default(), stamp);
+
+        // Back-date the stamp rather than waiting out coarse mtime granularity
+        let old = SystemTime::UNIX_EPOCH + Duration::from_secs(1);
+        cache.entries.get_mut(&key).unwrap().stamp.right = Some(old);
+
+        assert!(cache.get(&key).is_none());
+        assert!(cache.is_empty());
+        cleanup(&key);
+    }
+
+    #[test]
+    fn test_max_age_expires() {
+        let key = dir_pair("sc_dir_cache_age");
+        let mut cache = DirResolveCache::new(DEFAULT_DIR_CACHE_CAPACITY, Some(Duration::ZERO));
+        cache.insert(key.clone(), DirResolveInfo::default(), DirStamp::of(&key));
+        std::thread::sleep(Duration::from_millis(5));
+
+        assert!(cache.get(&key).is_none());
+        cleanup(&key);
+    }
+
+    #[test]
+    fn test_least_recently_used_evicted() {
+        let key = dir_pair("sc_dir_cache_lru");
+        let pair = |i: usize| (format!("{}/{}", key.0, i), key.1.clone());
+        let mut cache = DirResolveCache::new(10, None);
+        for i in 0..10 {
+            cache.insert(pair(i), DirResolveInfo::default(), DirStamp::of(&pair(i)));
+        }
+        // Touch the two oldest so they outlive the pairs inserted after them
+        assert!(cache.get(&pair(0)).is_some());
+        assert!(cache.get(&pair(1)).is_some());
+        for i in 10..15 {
+            cache.insert(pair(i), DirResolveInfo::default(), DirStamp::of(&pair(i)));
+        }
+
+        assert_eq!(cache.len(), 10);
+        assert_eq!(cache.recency.len(), 10);
+        for i in 2..7 {
+            assert!(cache.get(&pair(i)).is_none(), "pair {} kept", i);
+        }
+        for i in [0, 1, 7, 8, 9, 10, 14] {
+            assert!(cache.get(&pair(i)).is_some(), "pair {} evicted", i);
+        }
+
+        cache.clear();
+        assert!(cache.is_empty() && cache.recency.is_empty());
+        cleanup(&key);
+    }
+
+    #[test]
+    fn test_size_cache_keyed_by_single_dir() {
+        let key = dir_pair("sc_dir_cache_size");
+        let mut cache = DirSizeCache::default();
+        let size = DirSize {
+            total_size: 42,
+            file_count: 3,
+        };
+        cache.insert(key.0.clone(), size, key.0.stamp());
+        assert_eq!(cache.get(&key.0), Some(size));
+
+        let old = SystemTime::UNIX_EPOCH + Duration::from_secs(1);
+        cache.entries.get_mut(&key.0).unwrap().stamp = Some(old);
+        assert_eq!(cache.get(&key.0), None);
+        cleanup(&key);
+    }
+}
@@ -1,31 +1,169 @@
 use serde::Serialize;
 
-use crate::core::model::{CompareSummary, CompareStatus};
+use crate::core::model::{CompareStatus, CompareSummary, DiffKind};
+use crate::core::search::{ContentMatch, ContentSummary, SearchMatch};
 
 pub const EVENT_SCAN_PROGRESS: &str = "scan-progress";
 pub const EVENT_COMPARE_DONE: &str = "compare-done";
 pub const EVENT_COMPARE_ERROR: &str = "compare-error";
+/// Payload: `DiffsUpdatedPayload`
+pub const EVENT_DIFFS_UPDATED: &str = "diffs-updated";
+/// Payload: `DirectoryChangedPayload`; sent for folders `watch_directory` watches
+pub const EVENT_DIRECTORY_CHANGED: &str = "directory-changed";
 pub const EVENT_DIR_STATUS_RESOLVED: &str = "dir-status-resolved";
+/// Payload: `DirResolveErrorPayload`; a level whose folders could not be resolved
+pub const EVENT_DIR_RESOLVE_ERROR: &str = "dir-resolve-error";
+pub const EVENT_DIR_SIZE_RESOLVED: &str = "dir-size-resolved";
 pub const EVENT_TERMINAL_OUTPUT: &str = "terminal-output";
 pub const EVENT_TERMINAL_EXIT: &str = "terminal-exit";
+pub const EVENT_TERMINAL_CWD_CHANGED: &str = "terminal-cwd-changed";
+/// Payload: `operations::OperationInfo`
+pub const EVENT_OPERATION_PROGRESS: &str = "operation-progress";
+pub const EVENT_EXPORT_PROGRESS: &str = "export-progress";
+pub const EVENT_EXPORT_DONE: &str = "export-done";
+pub const EVENT_EXPORT_ERROR: &str = "export-error";
+pub const EVENT_MEASURE_PROGRESS: &str = "measure-progress";
+pub const EVENT_SEARCH_RESULT: &str = "search-result";
+pub const EVENT_SEARCH_DONE: &str = "search-done";
+pub const EVENT_CONTENT_SEARCH_RESULT: &str = "content-search-result";
+pub const EVENT_CONTENT_SEARCH_DONE: &str = "content-search-done";
+pub const EVENT_CHECKSUM_PROGRESS: &str = "checksum-progress";
+/// Payload: `duplicates::DuplicateGroup`
+pub const EVENT_DUPLICATE_GROUP: &str = "duplicate-group";
+/// Payload: `duplicates::DuplicateSummary`
+pub const EVENT_DUPLICATES_DONE: &str = "duplicates-done";
 
 #[derive(Clone, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct ScanProgressPayload {
+    /// Id returned by `start_compare`; events from a superseded compare can be dropped
+    pub job_id: u64,
     pub side: String,
     pub entries_scanned: usize,
+    /// Sum of the sizes of files listed so far
+    pub bytes_scanned: u64,
+    /// Directory being walked, relative to the root; sent a few times a second at most
+    pub current_path: Option<String>,
     pub phase: String,
 }
 
 #[derive(Clone, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct CompareDonePayload {
+    pub job_id: u64,
     pub summary: CompareSummary,
 }
 
+/// Paths compared again after they changed on disk, in watch mode or through a file
+/// operation in the app. The stored diffs under them, and the summary, match the disk
+/// again.
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DiffsUpdatedPayload {
+    pub job_id: u64,
+    /// Each path once, none below another; empty when `full` is set
+    pub paths: Vec<UpdatedPath>,
+    /// Everything was compared again, e.g. after the system dropped events
+    pub full: bool,
+    pub summary: CompareSummary,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UpdatedPath {
+    pub rel_path: String,
+    /// Its diff now; `None` when it is not listed, being gone from both sides or the
+    /// same on both in a result that leaves those out
+    pub kind: Option<DiffKind>,
+}
+
 #[derive(Clone, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct CompareErrorPayload {
+    pub job_id: u64,
+    pub message: String,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DirSizeResolvedPayload {
+    pub parent_path: String,
+    pub name: String,
+    /// Sum of file sizes anywhere below the directory
+    pub total_size: u64,
+    pub file_count: usize,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MeasureProgressPayload {
+    /// Files, folders and links counted so far
+    pub items_counted: usize,
+    pub bytes_counted: u64,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ChecksumProgressPayload {
+    /// File being hashed, as passed to `compute_checksums`
+    pub path: String,
+    pub bytes_hashed: u64,
+    pub total_bytes: u64,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SearchResultPayload {
+    /// Root the search was started from, as passed to `search_files`
+    pub root: String,
+    pub matches: Vec<SearchMatch>,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SearchDonePayload {
+    pub root: String,
+    pub total: usize,
+    /// More entries matched than `max_results` allowed
+    pub truncated: bool,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ContentSearchResultPayload {
+    pub root: String,
+    pub matches: Vec<ContentMatch>,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ContentSearchDonePayload {
+    pub root: String,
+    pub summary: ContentSummary,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExportProgressPayload {
+    pub operation_id: u64,
+    pub entries_written: usize,
+    pub total_entries: usize,
+    pub bytes_written: u64,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExportDonePayload {
+    pub operation_id: u64,
+    pub path: String,
+    pub bytes_written: u64,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExportErrorPayload {
+    pub operation_id: u64,
+    pub path: String,
     pub message: String,
 }
 
@@ -36,18 +174,75 @@ pub struct DirStatusResolvedPayload {
     pub status: CompareStatus,
     pub left_path: String,
     pub right_path: String,
-    pub total_size: u64,
+    /// Sum of file sizes in the left directory tree
+    pub left_size: u64,
+    pub right_size: u64,
+    /// The same sums with each hard-linked file counted once
+    pub left_unique_size: u64,
+    pub right_unique_size: u64,
+    /// Differing entries anywhere below, as in `DirResolveInfo`
+    pub only_left: usize,
+    pub only_right: usize,
+    pub modified: usize,
+    /// Directories past the depth limit that were not walked
+    pub depth_limited: usize,
+    /// Folder pairs below where a side could not be listed; the status is then `error`
+    pub unreadable: usize,
+}
+
+/// `resolve_dir_statuses` could not list one side of the level, so none of its pending
+/// folders resolve.
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DirResolveErrorPayload {
+    pub left_path: String,
+    pub right_path: String,
+    pub message: String,
 }
 
 #[derive(Clone, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct TerminalOutputPayload {
     pub side: String,
+    /// The tab the terminal belongs to, e.g. `left:0`
+    pub pane_id: String,
+    /// Output as text; a character split between reads arrives whole in the later event
     pub data: String,
+    /// Numbers the terminal's output events from 1, as `get_terminal_buffer` reports them
+    pub seq: u64,
+}
+
+/// Entries of a watched folder changed. One is sent per kind of change in a batch.
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DirectoryChangedPayload {
+    /// Pane id of the tab showing the folder, like `left:0`
+    pub pane: String,
+    pub path: String,
+    /// `created`, `removed`, `renamed` or `modified`
+    pub kind: String,
+    /// Names of the entries concerned; for a rename, the old name before the new one.
+    /// Empty when unknown: the listing may have changed anywhere
+    pub names: Vec<String>,
+}
+
+/// The shell reported a new current folder through OSC 7 or OSC 1337.
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TerminalCwdChangedPayload {
+    pub side: String,
+    pub pane_id: String,
+    pub cwd: String,
 }
 
 #[derive(Clone, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct TerminalExitPayload {
     pub side: String,
+    pub pane_id: String,
+    /// The shell's exit code, when it could be waited for
+    pub exit_code: Option<i32>,
+    /// `exited`, `killed` (by `kill_terminal` or a new terminal in the tab) or
+    /// `readError` (the output ended while the shell still ran)
+    pub reason: String,
 }
@@ -0,0 +1,216 @@
+use std::path::Path;
+use std::sync::Mutex;
+
+use serde::{Deserialize, Serialize};
+
+use crate::core::lock::LockExt;
+
+/// Most recent locations remembered; older ones drop off.
+pub const RECENT_PATHS_CAPACITY: usize = 20;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Favorite {
+    pub name: String,
+    pub path: String,
+}
+
+/// Recently visited folders and favorites, kept in `state.json` next to the pane
+/// state. Both default to empty, so state files from before they existed still load.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct Locations {
+    /// Most recent first
+    pub recent_paths: Vec<String>,
+    pub favorites: Vec<Favorite>,
+}
+
+impl Locations {
+    /// Reads the locations from a state file, empty when it is missing or unreadable.
+    pub fn load(state_file: &Path) -> Self {
+        std::fs::read_to_string(state_file)
+            .ok()
+            .and_then(|contents| serde_json::from_str(&contents).ok())
+            .unwrap_or_default()
+    }
+
+    /// Writes the locations into a state file, keeping whatever else it holds.
+    pub fn save(&self, state_file: &Path) -> Result<(), String> {
+        let fields = serde_json::to_value(self).map_err(|e| e.to_string())?;
+        merge_into_state_file(state_file, fields)
+    }
+
+    /// Moves `path` to the front of the recent list. Returns false when it already was
+    /// there, so nothing needs saving.
+    pub fn record(&mut self, path: &str) -> bool {
+        if self.recent_paths.first().map(String::as_str) == Some(path) {
+            return false;
+        }
+        self.recent_paths.retain(|p| p != path);
+        self.recent_paths.insert(0, path.to_string());
+        self.recent_paths.truncate(RECENT_PATHS_CAPACITY);
+        true
+    }
+
+    /// Adds a favorite, or renames the one already pointing at `path`.
+    pub fn add_favorite(&mut self, name: String, path: String) {
+        match self.favorites.iter_mut().find(|f| f.path == path) {
+            Some(existing) => existing.name = name,
+            None => self.favorites.push(Favorite { name, path }),
+        }
+    }
+
+    /// Returns false when no favorite pointed at `path`.
+    pub fn remove_favorite(&mut self, path: &str) -> bool {
+        let before = self.favorites.len();
+        self.favorites.retain(|f| f.path != path);
+        self.favorites.len() != before
+    }
+
+    /// The locations that exist right now. Missing ones are only hidden, not forgotten,
+    /// since they may be on a volume that is unplugged.
+    pub fn existing(&self) -> Self {
+        let exists = |path: &str| Path::new(path).exists();
+        Self {
+            recent_paths: self
+                .recent_paths
+                .iter()
+                .filter(|p| exists(p))
+                .cloned()
+                .collect(),
+            favorites: self
+                .favorites
+                .iter()
+                .filter(|f| exists(&f.path))
+                .cloned()
+                .collect(),
+        }
+    }
+}
+
+/// Held across each read-modify-write of the state file, so saves of different keys
+/// (pane state, window, locations) running at once do not drop each other's changes.
+static STATE_FILE_LOCK: Mutex<()> = Mutex::new(());
+
+/// Sets the top-level keys of `fields` in a JSON state file, leaving its other keys as
+/// they are. A missing or unreadable file starts out empty. Every write of the state
+/// file goes through here.
+pub fn merge_into_state_file(path: &Path, fields: serde_json::Value) -> Result<(), String> {
+    let _guard = STATE_FILE_LOCK.lock_or_recover();
+    let mut state = std::fs::read_to_string(path)
+        .ok()
+        .and_then(|contents| serde_json::from_str::<serde_json::Value>(&contents).ok())
+        .filter(serde_json::Value::is_object)
+        .unwrap_or_else(|| serde_json::json!({}));
+    if let (Some(state), serde_json::Value::Object(fields)) = (state.as_object_mut(), fields) {
+        state.extend(fields);
+    }
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+    }
+    let json = serde_json::to_string_pretty(&state).map_err(|e| e.to_string())?;
+    crate::core::fileops::write_atomic(path, json.as_bytes()).map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_recent_paths_most_recent_first_and_capped() {
+        let mut locations = Locations::default();
+        for i in 0..RECENT_PATHS_CAPACITY + 3 {
+            assert!(locations.record(&format!("/p/{}", i)));
+        }
+        assert!(locations.record("/p/5"));
+        assert!(!locations.record("/p/5"));
+        assert_eq!(locations.recent_paths.len(), RECENT_PATHS_CAPACITY);
+        assert_eq!(locations.recent_paths[..2], ["/p/5", "/p/22"]);
+        assert_eq!(
+            locations
+                .recent_paths
+                .iter()
+                .filter(|p| *p == "/p/5")
+                .count(),
+            1
+        );
+    }
+
+    #[test]
+    fn test_favorites_and_missing_paths() {
+        let dir = std::env::temp_dir().join("sc_locations_favorites");
+        std::fs::create_dir_all(&dir).unwrap();
+        let here = dir.to_string_lossy().to_string();
+        let mut locations = Locations::default();
+        locations.add_favorite("Work".into(), here.clone());
+        locations.add_favorite("USB".into(), "/nonexistent/usb".into());
+        locations.add_favorite("Projects".into(), here.clone());
+        locations.record("/nonexistent/old");
+        locations.record(&here);
+
+        let existing = locations.existing();
+        assert_eq!(
+            existing.favorites,
+            [Favorite {
+                name: "Projects".into(),
+                path: here.clone()
+            }]
+        );
+        assert_eq!(existing.recent_paths, [here]);
+        // Hidden, not forgotten
+        assert_eq!(locations.favorites.len(), 2);
+        assert!(locations.remove_favorite("/nonexistent/usb"));
+        assert!(!locations.remove_favorite("/nonexistent/usb"));
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_state_file_keeps_pane_state_and_loads_old_files() {
+        let dir = std::env::temp_dir().join("sc_locations_state");
+        let _ = std::fs::remove_dir_all(&dir);
+        let file = dir.join("state.json");
+        assert_eq!(Locations::load(&file), Locations::default());
+
+        // A state file from before locations were kept
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(&file, r#"{"leftPath":"/a","rightPath":"/b"}"#).unwrap();
+        assert_eq!(Locations::load(&file), Locations::default());
+
+        let mut locations = Locations::default();
+        locations.record("/a");
+        locations.save(&file).unwrap();
+        merge_into_state_file(&file, serde_json::json!({"leftPath": "/c"})).unwrap();
+
+        let saved: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(&file).unwrap()).unwrap();
+        let _ = std::fs::remove_dir_all(&dir);
+        assert_eq!(saved["leftPath"], "/c");
+        assert_eq!(saved["rightPath"], "/b");
+        assert_eq!(saved["recentPaths"][0], "/a");
+        assert_eq!(saved["favorites"], serde_json::json!([]));
+    }
+
+    #[test]
+    fn test_concurrent_merges_keep_every_key() {
+        let dir = std::env::temp_dir().join("sc_locations_concurrent");
+        let _ = std::fs::remove_dir_all(&dir);
+        let file = dir.join("state.json");
+        std::thread::scope(|scope| {
+            for i in 0..8 {
+                let file = &file;
+                scope.spawn(move || {
+                    for round in 0..10 {
+                        let key = format!("key{}", i);
+                        merge_into_state_file(file, serde_json::json!({ key: round })).unwrap();
+                    }
+                });
+            }
+        });
+        let saved: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(&file).unwrap()).unwrap();
+        let _ = std::fs::remove_dir_all(&dir);
+        for i in 0..8 {
+            assert_eq!(saved[format!("key{}", i)], 9);
+        }
+    }
+}
@@ -0,0 +1,38 @@
+//! Mutex locking that survives poisoning.
+
+use std::sync::{Mutex, MutexGuard};
+
+/// Locks the app's shared state without propagating poison. A panic inside one
+/// command's critical section must not make every later command panic too; the
+/// state behind these locks is replaced wholesale rather than edited in place, so
+/// it stays usable after such a panic.
+pub trait LockExt<T> {
+    fn lock_or_recover(&self) -> MutexGuard<'_, T>;
+}
+
+impl<T> LockExt<T> for Mutex<T> {
+    fn lock_or_recover(&self) -> MutexGuard<'_, T> {
+        self.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_lock_after_poison() {
+        let shared = Arc::new(Mutex::new(1));
+        let poisoner = Arc::clone(&shared);
+        let _ = std::thread::spawn(move || {
+            let _guard = poisoner.lock().unwrap();
+            panic!("poison the lock");
+        })
+        .join();
+
+        assert!(shared.is_poisoned());
+        *shared.lock_or_recover() += 1;
+        assert_eq!(*shared.lock_or_recover(), 2);
+    }
+}
@@ -0,0 +1,317 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, SystemTime};
+
+use serde::Serialize;
+
+pub use crate::core::fileops::PART_FILE_EXT;
+/// Extension given to quarantined state files that failed to parse.
+pub const CORRUPT_FILE_EXT: &str = "corrupt";
+
+/// A cache directory under the data dir and the size it may grow to.
+#[derive(Debug, Clone)]
+pub struct CacheCap {
+    pub category: String,
+    pub rel_dir: String,
+    pub max_bytes: u64,
+}
+
+#[derive(Debug, Clone)]
+pub struct MaintenanceConfig {
+    pub caches: Vec<CacheCap>,
+    /// Part files older than this are considered orphaned
+    pub part_file_max_age: Duration,
+    /// Quarantined `.corrupt` files older than this are deleted
+    pub corrupt_file_max_age: Duration,
+}
+
+impl Default for MaintenanceConfig {
+    fn default() -> Self {
+        const MB: u64 = 1024 * 1024;
+        const DAY: u64 = 24 * 60 * 60;
+        let cache = |category: &str, max_bytes: u64| CacheCap {
+            category: category.to_string(),
+            rel_dir: format!("cache/{}", category),
+            max_bytes,
+        };
+        Self {
+            caches: vec![
+                cache("thumbnails", 256 * MB),
+                cache("hashes", 128 * MB),
+                cache("dir-resolve", 64 * MB),
+            ],
+            part_file_max_age: Duration::from_secs(7 * DAY),
+            corrupt_file_max_age: Duration::from_secs(30 * DAY),
+        }
+    }
+}
+
+/// Outcome of one cleanup step. Errors are collected, never propagated.
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CategoryReport {
+    pub category: String,
+    pub files_removed: usize,
+    pub bytes_freed: u64,
+    pub errors: Vec<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MaintenanceReport {
+    pub categories: Vec<CategoryReport>,
+    pub bytes_freed: u64,
+    pub cancelled: bool,
+}
+
+/// Prunes caches past their caps and removes stale part/corrupt files under `data_dir`.
+///
+/// - `in_use`: returns true for paths a running operation still references; those are kept
+/// - `progress`: called before each step with (step, total_steps, category)
+pub fn run_maintenance(
+    data_dir: &Path,
+    config: &MaintenanceConfig,
+    in_use: &dyn Fn(&Path) -> bool,
+    cancel_flag: &AtomicBool,
+    progress: &dyn Fn(usize, usize, &str),
+) -> MaintenanceReport {
+    let total_steps = config.caches.len() + 2;
+    let mut report = MaintenanceReport::default();
+
+    for (step, cap) in config.caches.iter().enumerate() {
+        if cancel_flag.load(Ordering::Relaxed) {
+            report.cancelled = true;
+            return report;
+        }
+        progress(step, total_steps, &cap.category);
+        report
+            .categories
+            .push(prune_cache(&data_dir.join(&cap.rel_dir), cap));
+    }
+
+    let stale_steps = [
+        ("partFiles", PART_FILE_EXT, config.part_file_max_age),
+        ("corruptState", CORRUPT_FILE_EXT, config.corrupt_file_max_age),
+    ];
+    for (i, (category, ext, max_age)) in stale_steps.into_iter().enumerate() {
+        if cancel_flag.load(Ordering::Relaxed) {
+            report.cancelled = true;
+            return report;
+        }
+        progress(config.caches.len() + i, total_steps, category);
+        report
+            .categories
+            .push(remove_stale_files(data_dir, category, ext, max_age, in_use));
+    }
+
+    progress(total_steps, total_steps, "done");
+    report.bytes_freed = report.categories.iter().map(|c| c.bytes_freed).sum();
+    report
+}
+
+/// Deletes the least recently modified files in a cache dir until it fits its cap.
+fn prune_cache(dir: &Path, cap: &CacheCap) -> CategoryReport {
+    let mut report = CategoryReport {
+        category: cap.category.clone(),
+        ..Default::default()
+    };
+    if !dir.exists() {
+        return report;
+    }
+
+    let mut files = Vec::new();
+    collect_files(dir, &mut files, &mut report.errors);
+
+    let mut total: u64 = files.iter().map(|f| f.size).sum();
+    // Oldest first
+    files.sort_by_key(|f| f.modified);
+    for file in files {
+        if total <= cap.max_bytes {
+            break;
+        }
+        match fs::remove_file(&file.path) {
+            Ok(()) => {
+                total -= file.size;
+                report.files_removed += 1;
+                report.bytes_freed += file.size;
+            }
+            Err(e) => report
+                .errors
+                .push(format!("{}: {}", file.path.display(), e)),
+        }
+    }
+    report
+}
+
+/// Deletes files with the given extension older than `max_age` that no operation references.
+fn remove_stale_files(
+    dir: &Path,
+    category: &str,
+    ext: &str,
+    max_age: Duration,
+    in_use: &dyn Fn(&Path) -> bool,
+) -> CategoryReport {
+    let mut report = CategoryReport {
+        category: category.to_string(),
+        ..Default::default()
+    };
+    if !dir.exists() {
+        return report;
+    }
+
+    let mut files = Vec::new();
+    collect_files(dir, &mut files, &mut report.errors);
+
+    let now = SystemTime::now();
+    for file in files {
+        if file.path.extension().and_then(|e| e.to_str()) != Some(ext) {
+            continue;
+        }
+        let age = now.duration_since(file.modified).unwrap_or_default();
+        if age < max_age || in_use(&file.path) {
+            continue;
+        }
+        match fs::remove_file(&file.path) {
+            Ok(()) => {
+                report.files_removed += 1;
+                report.bytes_freed += file.size;
+            }
+            Err(e) => report
+                .errors
+                .push(format!("{}: {}", file.path.display(), e)),
+        }
+    }
+    report
+}
+
+struct FileInfo {
+    path: PathBuf,
+    size: u64,
+    modified: SystemTime,
+}
+
+/// Recursively lists regular files (symlinks are not followed).
+fn collect_files(dir: &Path, files: &mut Vec<FileInfo>, errors: &mut Vec<String>) {
+    let read_dir = match fs::read_dir(dir) {
+        Ok(rd) => rd,
+        Err(e) => {
+            errors.push(format!("{}: {}", dir.display(), e));
+            return;
+        }
+    };
+    for entry in read_dir {
+        let entry = match entry {
+            Ok(e) => e,
+            Err(e) => {
+                errors.push(format!("{}: {}", dir.display(), e));
+                continue;
+            }
+        };
+        let path = entry.path();
+        let meta = match fs::symlink_metadata(&path) {
+            Ok(m) => m,
+            Err(e) => {
+                errors.push(format!("{}: {}", path.display(), e));
+                continue;
+            }
+        };
+        if meta.is_dir() {
+            collect_files(&path, files, errors);
+        } else if meta.is_file() {
+            files.push(FileInfo {
+                path,
+                size: meta.len(),
+                modified: meta.modified().unwrap_or(SystemTime::UNIX_EPOCH),
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("sc_maint_{}", name));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn config(max_bytes: u64, max_age: Duration) -> MaintenanceConfig {
+        MaintenanceConfig {
+            caches: vec![CacheCap {
+                category: "hashes".to_string(),
+                rel_dir: "cache/hashes".to_string(),
+                max_bytes,
+            }],
+            part_file_max_age: max_age,
+            corrupt_file_max_age: max_age,
+        }
+    }
+
+    #[test]
+    fn test_prune_cache_over_cap() {
+        let dir = test_dir("prune");
+        fs::create_dir_all(dir.join("cache/hashes")).unwrap();
+        fs::write(dir.join("cache/hashes/a"), vec![0u8; 100]).unwrap();
+        fs::write(dir.join("cache/hashes/b"), vec![0u8; 100]).unwrap();
+        fs::write(dir.join("cache/hashes/c"), vec![0u8; 100]).unwrap();
+
+        let cancel = AtomicBool::new(false);
+        let report = run_maintenance(&dir, &config(150, Duration::MAX), &|_| false, &cancel, &|_, _, _| {});
+
+        let hashes = &report.categories[0];
+        assert_eq!(hashes.files_removed, 2);
+        assert_eq!(hashes.bytes_freed, 200);
+        assert_eq!(report.bytes_freed, 200);
+        assert_eq!(fs::read_dir(dir.join("cache/hashes")).unwrap().count(), 1);
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_stale_part_files_respect_age_and_usage() {
+        let dir = test_dir("part_files");
+        fs::write(dir.join("free.scpart"), "xx").unwrap();
+        fs::write(dir.join("busy.scpart"), "xx").unwrap();
+        fs::write(dir.join("state.json.corrupt"), "{").unwrap();
+        fs::write(dir.join("state.json"), "{}").unwrap();
+
+        let cancel = AtomicBool::new(false);
+        let busy = dir.join("busy.scpart");
+
+        // Nothing is old enough yet
+        let report = run_maintenance(&dir, &config(u64::MAX, Duration::from_secs(3600)), &|_| false, &cancel, &|_, _, _| {});
+        assert_eq!(report.bytes_freed, 0);
+
+        let in_use = |p: &Path| p == busy;
+        let report = run_maintenance(&dir, &config(u64::MAX, Duration::ZERO), &in_use, &cancel, &|_, _, _| {});
+        let part = report.categories.iter().find(|c| c.category == "partFiles").unwrap();
+        assert_eq!(part.files_removed, 1);
+        assert!(!dir.join("free.scpart").exists());
+        assert!(dir.join("busy.scpart").exists());
+        assert!(!dir.join("state.json.corrupt").exists());
+        assert!(dir.join("state.json").exists());
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_failing_step_does_not_abort_others() {
+        let dir = test_dir("fault_tolerant");
+        // Cache "directory" is actually a file, so listing it fails
+        fs::create_dir_all(dir.join("cache")).unwrap();
+        fs::write(dir.join("cache/hashes"), "not a dir").unwrap();
+        fs::write(dir.join("orphan.scpart"), "xx").unwrap();
+
+        let cancel = AtomicBool::new(false);
+        let report = run_maintenance(&dir, &config(0, Duration::ZERO), &|_| false, &cancel, &|_, _, _| {});
+
+        assert!(!report.categories[0].errors.is_empty());
+        assert!(!dir.join("orphan.scpart").exists());
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -1,10 +1,18 @@
-pub mod model;
-pub mod ignore;
-pub mod security;
-pub mod scan;
-pub mod compare;
+pub use splitcommander_core::{
+    archive, checksum, compare, disk, duplicates, error, export, fileops, ignore, model, paths,
+    platform, preview, refresh, scan, search, security, sort, source, sync, textdiff, undo, verify,
+};
+
 pub mod events;
-pub mod export;
-pub mod fileops;
+pub mod cli;
+pub mod settings;
+pub mod locations;
+pub mod pane_state;
+pub mod panes;
+pub mod operations;
+pub mod dir_cache;
+pub mod lock;
+pub mod maintenance;
 pub mod pty;
+pub mod watch;
 pub mod commands;
@@ -0,0 +1,452 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+use serde::Serialize;
+use tokio::sync::{oneshot, watch};
+
+use crate::core::error::CommandError;
+use crate::core::lock::LockExt;
+
+/// Snapshot of a long-running operation, as reported to the UI.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OperationInfo {
+    pub id: u64,
+    pub kind: String,
+    pub done: u64,
+    pub total: u64,
+    pub message: String,
+    /// Step within the current item, e.g. "verifying"; cleared by `update`
+    pub phase: Option<String>,
+}
+
+struct ActiveOperation {
+    info: OperationInfo,
+    /// Paths the operation reads or writes; consulted before cleaning up temp files
+    paths: Vec<PathBuf>,
+    cancel: Arc<AtomicBool>,
+}
+
+/// Registry of long-running background operations (maintenance, exports, ...).
+/// Each operation gets an id, a cancellation flag, and a progress snapshot.
+pub struct OperationRegistry {
+    next_id: AtomicU64,
+    active: Mutex<HashMap<u64, ActiveOperation>>,
+}
+
+impl OperationRegistry {
+    pub fn new() -> Self {
+        Self {
+            next_id: AtomicU64::new(1),
+            active: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Registers a new operation touching `paths`. Returns its id and cancel flag.
+    pub fn register(&self, kind: &str, paths: Vec<PathBuf>) -> (u64, Arc<AtomicBool>) {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let cancel = Arc::new(AtomicBool::new(false));
+        let op = ActiveOperation {
+            info: OperationInfo {
+                id,
+                kind: kind.to_string(),
+                done: 0,
+                total: 0,
+                message: String::new(),
+                phase: None,
+            },
+            paths,
+            cancel: Arc::clone(&cancel),
+        };
+        self.active.lock_or_recover().insert(id, op);
+        (id, cancel)
+    }
+
+    /// Updates progress for an operation and returns the new snapshot.
+    pub fn update(&self, id: u64, done: u64, total: u64, message: &str) -> Option<OperationInfo> {
+        let mut active = self.active.lock_or_recover();
+        let op = active.get_mut(&id)?;
+        op.info.done = done;
+        op.info.total = total;
+        op.info.message = message.to_string();
+        op.info.phase = None;
+        Some(op.info.clone())
+    }
+
+    /// Marks the step the operation is in, keeping its counts, and returns the snapshot.
+    pub fn update_phase(&self, id: u64, phase: &str, message: &str) -> Option<OperationInfo> {
+        let mut active = self.active.lock_or_recover();
+        let op = active.get_mut(&id)?;
+        op.info.phase = Some(phase.to_string());
+        op.info.message = message.to_string();
+        Some(op.info.clone())
+    }
+
+    /// Removes a finished (or failed) operation from the registry.
+    pub fn finish(&self, id: u64) {
+        self.active.lock_or_recover().remove(&id);
+    }
+
+    /// Requests cancellation. Returns false if no such operation is running.
+    pub fn cancel(&self, id: u64) -> bool {
+        match self.active.lock_or_recover().get(&id) {
+            Some(op) => {
+                op.cancel.store(true, Ordering::Relaxed);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Requests cancellation of every running operation of `kind`; returns how many.
+    pub fn cancel_kind(&self, kind: &str) -> usize {
+        let active = self.active.lock_or_recover();
+        let matching = active.values().filter(|op| op.info.kind == kind);
+        matching
+            .map(|op| op.cancel.store(true, Ordering::Relaxed))
+            .count()
+    }
+
+    pub fn list(&self) -> Vec<OperationInfo> {
+        let mut ops: Vec<OperationInfo> = self
+            .active
+            .lock_or_recover()
+            .values()
+            .map(|op| op.info.clone())
+            .collect();
+        ops.sort_by_key(|op| op.id);
+        ops
+    }
+
+    /// Returns true if any running operation references `path` or one of its ancestors.
+    pub fn references(&self, path: &Path) -> bool {
+        self.active
+            .lock_or_recover()
+            .values()
+            .any(|op| op.paths.iter().any(|p| path.starts_with(p)))
+    }
+}
+
+impl Default for OperationRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Runs at most one job of a kind at a time (e.g. the full compare). Every start gets a
+/// new, larger id, so events and results from a superseded job can be told apart.
+pub struct JobSlot {
+    /// What the job does, for the "already in progress" error
+    what: &'static str,
+    last_id: AtomicU64,
+    current: Mutex<Option<RunningJob>>,
+}
+
+struct RunningJob {
+    cancel: Arc<AtomicBool>,
+    /// Turns true when the job returns; its sender is dropped if the job panics
+    done: watch::Receiver<bool>,
+}
+
+impl RunningJob {
+    fn is_finished(&self) -> bool {
+        *self.done.borrow() || self.done.has_changed().is_err()
+    }
+}
+
+/// Waits until the job behind `done` has returned or panicked.
+async fn finished(mut done: watch::Receiver<bool>) {
+    let _ = done.wait_for(|done| *done).await;
+}
+
+impl JobSlot {
+    pub fn new(what: &'static str) -> Self {
+        Self {
+            what,
+            last_id: AtomicU64::new(0),
+            current: Mutex::new(None),
+        }
+    }
+
+    /// Starts `work` on the blocking pool with its job id and cancel flag, returning the
+    /// id. While another job runs this fails with `Busy`, unless `force` is set: then
+    /// that job is cancelled and has finished before `work` starts. `prepare` runs just
+    /// before `work` is spawned, with the slot held, so nothing waiting on the slot sees
+    /// the job start without it; it does not run when the start fails.
+    pub async fn start<F>(
+        &self,
+        force: bool,
+        prepare: impl FnOnce(),
+        work: F,
+    ) -> Result<u64, CommandError>
+    where
+        F: FnOnce(u64, &AtomicBool) + Send + 'static,
+    {
+        let mut prepare = Some(prepare);
+        let mut work = Some(work);
+        loop {
+            let superseded = {
+                let mut current = self.current.lock_or_recover();
+                match current.as_ref() {
+                    Some(job) if !job.is_finished() => {
+                        if !force {
+                            return Err(CommandError::Busy {
+                                message: format!("A {} is already in progress", self.what),
+                                path: None,
+                            });
+                        }
+                        job.cancel.store(true, Ordering::Relaxed);
+                        job.done.clone()
+                    }
+                    _ => {
+                        let id = self.last_id.fetch_add(1, Ordering::Relaxed) + 1;
+                        let cancel = Arc::new(AtomicBool::new(false));
+                        let flag = Arc::clone(&cancel);
+                        let (done_tx, done) = watch::channel(false);
+                        let work = work.take().expect("work is only taken when spawned");
+                        if let Some(prepare) = prepare.take() {
+                            prepare();
+                        }
+                        tokio::task::spawn_blocking(move || {
+                            work(id, &flag);
+                            done_tx.send_replace(true);
+                        });
+                        *current = Some(RunningJob { cancel, done });
+                        return Ok(id);
+                    }
+                }
+            };
+            // The superseded job stays in the slot until it has finished, so no other
+            // start can slip in beside it
+            finished(superseded).await;
+        }
+    }
+
+    /// Runs `work` as a new job, superseding any running one as a forced `start` does,
+    /// and returns what it returns. Fails with `taskFailed` if it panics.
+    pub async fn run<T, F>(&self, work: F) -> Result<T, CommandError>
+    where
+        T: Send + 'static,
+        F: FnOnce(&AtomicBool) -> T + Send + 'static,
+    {
+        let (result_tx, result) = oneshot::channel();
+        self.start(true, || {}, move |_, cancel| {
+            let _ = result_tx.send(work(cancel));
+        })
+        .await?;
+        result
+            .await
+            .map_err(|_| CommandError::task_failed(format!("The {} failed", self.what)))
+    }
+
+    /// Cancels the running job, if any, and waits until it has finished. Once this
+    /// returns, that job emits and stores nothing more. The job keeps the slot until
+    /// then, so a `start` made meanwhile fails with `Busy` or waits as well.
+    pub async fn stop(&self) {
+        let done = match self.current.lock_or_recover().as_ref() {
+            Some(job) => {
+                job.cancel.store(true, Ordering::Relaxed);
+                job.done.clone()
+            }
+            None => return,
+        };
+        finished(done).await;
+    }
+
+    /// Requests cancellation of the running job, if any.
+    pub fn cancel(&self) {
+        if let Some(job) = self.current.lock_or_recover().as_ref() {
+            job.cancel.store(true, Ordering::Relaxed);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_register_update_finish() {
+        let registry = OperationRegistry::new();
+        let (id, _cancel) = registry.register("maintenance", vec![]);
+
+        let info = registry.update(id, 1, 3, "cache").unwrap();
+        assert_eq!(info.done, 1);
+        assert_eq!(info.total, 3);
+        assert_eq!(registry.list().len(), 1);
+
+        let info = registry.update_phase(id, "verifying", "cache/a").unwrap();
+        assert_eq!((info.done, info.phase.as_deref()), (1, Some("verifying")));
+        assert_eq!(registry.update(id, 2, 3, "logs").unwrap().phase, None);
+
+        registry.finish(id);
+        assert!(registry.list().is_empty());
+        assert!(registry.update(id, 2, 3, "").is_none());
+    }
+
+    #[test]
+    fn test_cancel_sets_flag() {
+        let registry = OperationRegistry::new();
+        let (id, cancel) = registry.register("export", vec![]);
+
+        assert!(registry.cancel(id));
+        assert!(cancel.load(Ordering::Relaxed));
+        assert!(!registry.cancel(id + 100));
+
+        let (_, left) = registry.register("dirSizes", vec![]);
+        let (_, right) = registry.register("dirSizes", vec![]);
+        let (_, other) = registry.register("copy", vec![]);
+        assert_eq!(registry.cancel_kind("dirSizes"), 2);
+        assert!(left.load(Ordering::Relaxed) && right.load(Ordering::Relaxed));
+        assert!(!other.load(Ordering::Relaxed));
+    }
+
+    #[test]
+    fn test_references_paths() {
+        let registry = OperationRegistry::new();
+        let (id, _cancel) = registry.register("copy", vec![PathBuf::from("/dst/folder")]);
+
+        assert!(registry.references(Path::new("/dst/folder/file.scpart")));
+        assert!(!registry.references(Path::new("/dst/other.scpart")));
+
+        registry.finish(id);
+        assert!(!registry.references(Path::new("/dst/folder/file.scpart")));
+    }
+
+    /// Stands in for a long scan: runs until cancelled, then records its id.
+    fn long_job(finished: &Arc<Mutex<Vec<u64>>>) -> impl FnOnce(u64, &AtomicBool) + Send {
+        let finished = Arc::clone(finished);
+        move |id, cancel| {
+            while !cancel.load(Ordering::Relaxed) {
+                std::thread::sleep(std::time::Duration::from_millis(1));
+            }
+            finished.lock().unwrap().push(id);
+        }
+    }
+
+    fn runtime() -> tokio::runtime::Runtime {
+        tokio::runtime::Builder::new_current_thread().build().unwrap()
+    }
+
+    #[test]
+    fn test_job_slot_rejects_second_start() {
+        let slot = JobSlot::new("comparison");
+        let finished = Arc::new(Mutex::new(Vec::new()));
+        runtime().block_on(async {
+            let first = slot.start(false, || {}, long_job(&finished)).await.unwrap();
+            let prepared = AtomicBool::new(false);
+            let prepare = || prepared.store(true, Ordering::Relaxed);
+            let err = slot.start(false, prepare, long_job(&finished)).await.unwrap_err();
+            assert!(!prepared.load(Ordering::Relaxed));
+            assert_eq!(err.code(), "busy");
+            assert_eq!(err.to_string(), "A comparison is already in progress");
+            assert_eq!(first, 1);
+
+            slot.stop().await;
+        });
+        assert_eq!(*finished.lock().unwrap(), vec![1]);
+    }
+
+    #[test]
+    fn test_job_slot_force_supersedes_running_job() {
+        let slot = JobSlot::new("comparison");
+        let finished = Arc::new(Mutex::new(Vec::new()));
+        runtime().block_on(async {
+            let first = slot.start(false, || {}, long_job(&finished)).await.unwrap();
+            let second = slot.start(true, || {}, long_job(&finished)).await.unwrap();
+            assert!(second > first);
+            // The first job was cancelled and had finished before the second began
+            assert_eq!(*finished.lock().unwrap(), vec![first]);
+
+            slot.stop().await;
+        });
+        assert_eq!(*finished.lock().unwrap(), vec![1, 2]);
+    }
+
+    #[test]
+    fn test_job_slot_stop_waits_for_job() {
+        let slot = JobSlot::new("comparison");
+        let finished = Arc::new(Mutex::new(Vec::new()));
+        runtime().block_on(async {
+            let id = slot.start(false, || {}, long_job(&finished)).await.unwrap();
+            slot.stop().await;
+            assert_eq!(*finished.lock().unwrap(), vec![id]);
+
+            // Nothing left to stop, and the slot is free again
+            slot.stop().await;
+            let next = slot.start(false, || {}, long_job(&finished)).await.unwrap();
+            slot.stop().await;
+            assert_eq!(*finished.lock().unwrap(), vec![id, next]);
+        });
+    }
+
+    #[test]
+    fn test_job_slot_run_returns_result_and_supersedes() {
+        let slot = Arc::new(JobSlot::new("search"));
+        let finished = Arc::new(Mutex::new(Vec::new()));
+        runtime().block_on(async {
+            let first = tokio::spawn({
+                let slot = Arc::clone(&slot);
+                let job = long_job(&finished);
+                async move {
+                    slot.run(move |cancel| {
+                        job(0, cancel);
+                        cancel.load(Ordering::Relaxed)
+                    })
+                    .await
+                }
+            });
+            while slot.current.lock().unwrap().is_none() {
+                tokio::task::yield_now().await;
+            }
+
+            // A second run cancels the first, which still hands back its own result
+            let second = slot.run(|cancel| cancel.load(Ordering::Relaxed)).await;
+            assert!(first.await.unwrap().unwrap());
+            assert!(!second.unwrap());
+            // Cancelling the slot reaches only the job running then
+            slot.cancel();
+            assert!(!slot.run(|cancel| cancel.load(Ordering::Relaxed)).await.unwrap());
+
+            let panicked: Result<(), _> = slot.run(|_| panic!("job failed")).await;
+            assert_eq!(panicked.unwrap_err().code(), "taskFailed");
+        });
+    }
+
+    #[test]
+    fn test_job_slot_stays_busy_while_stopping() {
+        let slot = Arc::new(JobSlot::new("comparison"));
+        let finished = Arc::new(Mutex::new(Vec::new()));
+        // Lets the job return only once the test has tried to start another
+        let release = Arc::new(AtomicBool::new(false));
+        let slow_to_stop = {
+            let (finished, release) = (Arc::clone(&finished), Arc::clone(&release));
+            move |id, cancel: &AtomicBool| {
+                while !(cancel.load(Ordering::Relaxed) && release.load(Ordering::Relaxed)) {
+                    std::thread::sleep(std::time::Duration::from_millis(1));
+                }
+                finished.lock().unwrap().push(id);
+            }
+        };
+        runtime().block_on(async {
+            let first = slot.start(false, || {}, slow_to_stop).await.unwrap();
+            let stopping = tokio::spawn({
+                let slot = Arc::clone(&slot);
+                async move { slot.stop().await }
+            });
+            tokio::task::yield_now().await;
+
+            let err = slot.start(false, || {}, long_job(&finished)).await.unwrap_err();
+            assert_eq!(err.code(), "busy");
+            release.store(true, Ordering::Relaxed);
+            stopping.await.unwrap();
+            assert_eq!(*finished.lock().unwrap(), vec![first]);
+
+            let next = slot.start(false, || {}, long_job(&finished)).await.unwrap();
+            slot.stop().await;
+            assert_eq!(*finished.lock().unwrap(), vec![first, next]);
+        });
+    }
+}
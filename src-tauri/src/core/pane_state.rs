@@ -0,0 +1,572 @@
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use crate::core::panes::Side;
+
+/// Version written into `state.json`. Files without one are version 1.
+pub const STATE_VERSION: u32 = 5;
+
+/// Most back or forward steps kept per pane; older ones drop off.
+pub const HISTORY_CAPACITY: usize = 50;
+
+/// How much of a saved window's width must be on a monitor, along its top edge, for it
+/// to be restored there; enough to grab the title bar.
+pub const MIN_VISIBLE_WIDTH: u32 = 100;
+
+/// Persisted pane state saved across app restarts.
+///
+/// Missing fields take their defaults, so only a file that is not a JSON object at all
+/// loses the saved panes. Bump [`STATE_VERSION`] and add a step to [`MIGRATIONS`]
+/// whenever a field changes meaning or shape.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct PersistedState {
+    pub version: u32,
+    pub left_path: String,
+    pub right_path: String,
+    pub left_selected_index: i32,
+    pub left_scroll_top: f64,
+    pub right_selected_index: i32,
+    pub right_scroll_top: f64,
+    pub left_show_hidden: bool,
+    pub right_show_hidden: bool,
+    pub left_sort: PaneSort,
+    pub right_sort: PaneSort,
+    pub left_history: PaneHistory,
+    pub right_history: PaneHistory,
+    /// Every tab of each side, in order. The `left_*` and `right_*` fields above are
+    /// those of the active tab, for a frontend that does not know about tabs.
+    pub left_tabs: Vec<TabState>,
+    pub right_tabs: Vec<TabState>,
+    pub left_active_tab: usize,
+    pub right_active_tab: usize,
+    /// Saved by `save_window_state` on its own, so it is only written when set and the
+    /// pane saves leave it alone
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub window: Option<WindowState>,
+}
+
+/// One tab of a pane.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct TabState {
+    pub path: String,
+    pub selected_index: i32,
+    pub scroll_top: f64,
+    pub show_hidden: bool,
+    pub sort: PaneSort,
+    pub history: PaneHistory,
+}
+
+/// The main window's outer position and inner size, in physical pixels.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WindowState {
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+    pub maximized: bool,
+}
+
+/// A monitor's area, in physical pixels.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ScreenRect {
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl WindowState {
+    /// Whether the window's top edge is on one of `monitors` for at least
+    /// [`MIN_VISIBLE_WIDTH`] pixels (or its whole width, if narrower). A monitor that
+    /// was disconnected since the window was saved leaves it off-screen.
+    pub fn is_reachable_on(&self, monitors: &[ScreenRect]) -> bool {
+        if self.width == 0 || self.height == 0 {
+            return false;
+        }
+        let needed = i64::from(MIN_VISIBLE_WIDTH.min(self.width));
+        let (left, top) = (i64::from(self.x), i64::from(self.y));
+        let right = left + i64::from(self.width);
+        monitors.iter().any(|m| {
+            let (m_left, m_top) = (i64::from(m.x), i64::from(m.y));
+            let (m_right, m_bottom) = (m_left + i64::from(m.width), m_top + i64::from(m.height));
+            let overlap = right.min(m_right) - left.max(m_left);
+            (m_top..m_bottom).contains(&top) && overlap >= needed
+        })
+    }
+}
+
+/// A pane's sort column, as `list_directory` takes it, and direction.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct PaneSort {
+    pub sort_by: String,
+    pub ascending: bool,
+}
+
+impl Default for PaneSort {
+    fn default() -> Self {
+        Self {
+            sort_by: "name".to_string(),
+            ascending: true,
+        }
+    }
+}
+
+/// Folders a pane can go back or forward to, nearest last.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct PaneHistory {
+    pub back: Vec<String>,
+    pub forward: Vec<String>,
+}
+
+impl PaneHistory {
+    /// Drops the farthest steps beyond [`HISTORY_CAPACITY`].
+    fn cap(&mut self) {
+        for stack in [&mut self.back, &mut self.forward] {
+            let excess = stack.len().saturating_sub(HISTORY_CAPACITY);
+            stack.drain(..excess);
+        }
+    }
+}
+
+impl Default for PersistedState {
+    fn default() -> Self {
+        Self {
+            version: STATE_VERSION,
+            left_path: String::new(),
+            right_path: String::new(),
+            left_selected_index: 0,
+            left_scroll_top: 0.0,
+            right_selected_index: 0,
+            right_scroll_top: 0.0,
+            left_show_hidden: false,
+            right_show_hidden: false,
+            left_sort: PaneSort::default(),
+            right_sort: PaneSort::default(),
+            left_history: PaneHistory::default(),
+            right_history: PaneHistory::default(),
+            left_tabs: Vec::new(),
+            right_tabs: Vec::new(),
+            left_active_tab: 0,
+            right_active_tab: 0,
+            window: None,
+        }
+    }
+}
+
+/// Upgrades the fields of version `i + 1` to version `i + 2`.
+const MIGRATIONS: [fn(&mut Map<String, Value>); 4] = [v1_to_v2, v2_to_v3, v3_to_v4, v4_to_v5];
+
+fn v1_to_v2(state: &mut Map<String, Value>) {
+    for side in ["left", "right"] {
+        state
+            .entry(format!("{side}SortBy"))
+            .or_insert_with(|| "name".into());
+        state
+            .entry(format!("{side}Ascending"))
+            .or_insert(Value::Bool(true));
+    }
+}
+
+/// Each pane's sort column and direction move into one `leftSort` / `rightSort` object.
+fn v2_to_v3(state: &mut Map<String, Value>) {
+    for side in ["left", "right"] {
+        let mut sort = Map::new();
+        if let Some(sort_by) = state.remove(&format!("{side}SortBy")) {
+            sort.insert("sortBy".into(), sort_by);
+        }
+        if let Some(ascending) = state.remove(&format!("{side}Ascending")) {
+            sort.insert("ascending".into(), ascending);
+        }
+        state.insert(format!("{side}Sort"), Value::Object(sort));
+    }
+}
+
+/// Adds the optional window geometry; nothing older needs to change.
+fn v3_to_v4(_state: &mut Map<String, Value>) {}
+
+/// Each side's pane becomes its first and only tab.
+fn v4_to_v5(state: &mut Map<String, Value>) {
+    for side in ["left", "right"] {
+        let fields = [
+            ("path", "Path"),
+            ("selectedIndex", "SelectedIndex"),
+            ("scrollTop", "ScrollTop"),
+            ("showHidden", "ShowHidden"),
+            ("sort", "Sort"),
+            ("history", "History"),
+        ];
+        let tab: Map<String, Value> = fields
+            .into_iter()
+            .filter_map(|(key, field)| {
+                let value = state.get(&format!("{side}{field}"))?;
+                Some((key.to_string(), value.clone()))
+            })
+            .collect();
+        state.insert(
+            format!("{side}Tabs"),
+            Value::Array(vec![Value::Object(tab)]),
+        );
+        state.insert(format!("{side}ActiveTab"), 0.into());
+    }
+}
+
+impl PersistedState {
+    /// Reads a saved state of any version. Fields that are missing, or hold something
+    /// of the wrong type, take their defaults; `None` only when the text is not a JSON
+    /// object.
+    pub fn parse(contents: &str) -> Option<Self> {
+        let mut state = Self::parse_fields(contents)?;
+        state.sync_tabs();
+        Some(state)
+    }
+
+    /// The state to save from the fields a frontend `sent`, which may be only some of
+    /// them: the others keep their `saved` values rather than falling back to defaults.
+    /// A side whose tabs were not sent takes the sent fields into its active tab.
+    pub fn with_saved(saved: Option<Self>, sent: Map<String, Value>) -> Result<Self, String> {
+        let mut fields = match saved.map(serde_json::to_value) {
+            Some(Ok(Value::Object(fields))) => fields,
+            _ => Map::new(),
+        };
+        let tabs_sent = [Side::Left, Side::Right]
+            .map(|side| sent.contains_key(&format!("{}Tabs", side.as_str())));
+        fields.extend(sent);
+        let mut state: Self =
+            serde_json::from_value(Value::Object(fields)).map_err(|e| e.to_string())?;
+        for (side, tabs_sent) in [Side::Left, Side::Right].into_iter().zip(tabs_sent) {
+            if tabs_sent {
+                continue;
+            }
+            let tab = state.active_fields(side);
+            let (tabs, active) = state.tabs_mut(side);
+            if let Some(active_tab) = tabs.get_mut(*active) {
+                *active_tab = tab;
+            }
+        }
+        Ok(state)
+    }
+
+    /// Points every tab whose folder is gone, such as one on an unplugged drive, at
+    /// `fallback` with its selection and scroll reset. Everything else is kept.
+    pub fn replace_missing_roots(&mut self, fallback: &str) {
+        for side in [Side::Left, Side::Right] {
+            for tab in self.tabs_mut(side).0.iter_mut() {
+                if !std::path::Path::new(&tab.path).is_dir() {
+                    tab.path = fallback.to_string();
+                    tab.selected_index = 0;
+                    tab.scroll_top = 0.0;
+                }
+            }
+        }
+        self.sync_tabs();
+    }
+
+    /// Caps the history stacks, brings the tabs and the active tab's fields in line and
+    /// stamps the current version, ready to write.
+    pub fn prepare_for_save(&mut self) {
+        self.version = STATE_VERSION;
+        self.sync_tabs();
+    }
+
+    /// A side without tabs, as saved by a frontend that does not know about them, gets
+    /// one from the active tab's fields; otherwise those fields are set from the active
+    /// tab.
+    fn sync_tabs(&mut self) {
+        for side in [Side::Left, Side::Right] {
+            if self.tabs_mut(side).0.is_empty() {
+                let tab = self.active_fields(side);
+                let (tabs, active) = self.tabs_mut(side);
+                tabs.push(tab);
+                *active = 0;
+            }
+            let (tabs, active) = self.tabs_mut(side);
+            *active = (*active).min(tabs.len() - 1);
+            for tab in tabs.iter_mut() {
+                tab.history.cap();
+            }
+            let tab = tabs[*active].clone();
+            self.set_active_fields(side, tab);
+        }
+    }
+
+    fn tabs_mut(&mut self, side: Side) -> (&mut Vec<TabState>, &mut usize) {
+        match side {
+            Side::Left => (&mut self.left_tabs, &mut self.left_active_tab),
+            Side::Right => (&mut self.right_tabs, &mut self.right_active_tab),
+        }
+    }
+
+    fn active_fields(&self, side: Side) -> TabState {
+        match side {
+            Side::Left => TabState {
+                path: self.left_path.clone(),
+                selected_index: self.left_selected_index,
+                scroll_top: self.left_scroll_top,
+                show_hidden: self.left_show_hidden,
+                sort: self.left_sort.clone(),
+                history: self.left_history.clone(),
+            },
+            Side::Right => TabState {
+                path: self.right_path.clone(),
+                selected_index: self.right_selected_index,
+                scroll_top: self.right_scroll_top,
+                show_hidden: self.right_show_hidden,
+                sort: self.right_sort.clone(),
+                history: self.right_history.clone(),
+            },
+        }
+    }
+
+    fn set_active_fields(&mut self, side: Side, tab: TabState) {
+        let TabState {
+            path,
+            selected_index,
+            scroll_top,
+            show_hidden,
+            sort,
+            history,
+        } = tab;
+        match side {
+            Side::Left => {
+                self.left_path = path;
+                self.left_selected_index = selected_index;
+                self.left_scroll_top = scroll_top;
+                self.left_show_hidden = show_hidden;
+                self.left_sort = sort;
+                self.left_history = history;
+            }
+            Side::Right => {
+                self.right_path = path;
+                self.right_selected_index = selected_index;
+                self.right_scroll_top = scroll_top;
+                self.right_show_hidden = show_hidden;
+                self.right_sort = sort;
+                self.right_history = history;
+            }
+        }
+    }
+
+    fn parse_fields(contents: &str) -> Option<Self> {
+        let Ok(Value::Object(mut fields)) = serde_json::from_str(contents) else {
+            return None;
+        };
+        let version = fields.get("version").and_then(Value::as_u64).unwrap_or(1);
+        for migrate in MIGRATIONS.iter().skip(version.saturating_sub(1) as usize) {
+            migrate(&mut fields);
+        }
+        fields.insert("version".into(), STATE_VERSION.into());
+
+        if let Ok(state) = serde_json::from_value(Value::Object(fields.clone())) {
+            return Some(state);
+        }
+        // Keep each field that reads on its own
+        let mut state = Value::Object(Map::new());
+        for (key, value) in fields {
+            let mut with_field = state.clone();
+            with_field[&key] = value;
+            if serde_json::from_value::<Self>(with_field.clone()).is_ok() {
+                state = with_field;
+            }
+        }
+        serde_json::from_value(state).ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const V1: &str = r#"{
+        "leftPath": "/a", "rightPath": "/b",
+        "leftSelectedIndex": 3, "leftScrollTop": 12.5,
+        "rightSelectedIndex": 0, "rightScrollTop": 0,
+        "leftShowHidden": true, "rightShowHidden": false
+    }"#;
+
+    #[test]
+    fn test_v1_upgrades_with_defaults() {
+        let state = PersistedState::parse(V1).unwrap();
+        assert_eq!(state.version, STATE_VERSION);
+        assert_eq!(
+            (state.left_path.as_str(), state.right_path.as_str()),
+            ("/a", "/b")
+        );
+        assert_eq!(state.left_selected_index, 3);
+        assert_eq!(state.left_scroll_top, 12.5);
+        assert!(state.left_show_hidden);
+        assert_eq!(state.left_sort, PaneSort::default());
+        assert_eq!(state.right_history, PaneHistory::default());
+    }
+
+    #[test]
+    fn test_round_trip_caps_history() {
+        let mut state = PersistedState {
+            version: 1,
+            left_path: "/a".into(),
+            right_sort: PaneSort {
+                sort_by: "size".into(),
+                ascending: false,
+            },
+            left_history: PaneHistory {
+                back: (0..HISTORY_CAPACITY + 5)
+                    .map(|i| format!("/b/{}", i))
+                    .collect(),
+                forward: vec!["/f".into()],
+            },
+            ..PersistedState::default()
+        };
+        state.prepare_for_save();
+        let saved = serde_json::to_string(&state).unwrap();
+        assert!(saved.contains(r#""version":5"#));
+        assert!(!saved.contains("window"));
+        assert!(saved.contains(r#""rightSort":{"sortBy":"size","ascending":false}"#));
+
+        let loaded = PersistedState::parse(&saved).unwrap();
+        assert_eq!(loaded.left_history.back.len(), HISTORY_CAPACITY);
+        // The nearest steps stay
+        assert_eq!(loaded.left_history.back.last().unwrap(), "/b/54");
+        assert_eq!(loaded, state);
+    }
+
+    #[test]
+    fn test_partial_and_mistyped_fields_fall_back_to_defaults() {
+        let state = PersistedState::parse(
+            r#"{"version": 2, "leftPath": "/a", "leftSelectedIndex": "three",
+                "rightSortBy": "size", "rightAscending": false, "futureField": 1}"#,
+        )
+        .unwrap();
+        assert_eq!(state.left_path, "/a");
+        assert_eq!(state.left_selected_index, 0);
+        assert_eq!(state.right_path, "");
+        assert_eq!(state.right_sort.sort_by, "size");
+        assert!(!state.right_sort.ascending);
+        assert_eq!(state.left_sort, PaneSort::default());
+
+        let mut empty = PersistedState::default();
+        empty.prepare_for_save();
+        assert_eq!(PersistedState::parse("{}"), Some(empty));
+        assert_eq!(PersistedState::parse("{\"leftPath\": "), None);
+        assert_eq!(PersistedState::parse("[1, 2]"), None);
+    }
+
+    #[test]
+    fn test_fields_left_out_of_a_save_keep_their_saved_values() {
+        let mut saved = PersistedState::parse(V1).unwrap();
+        saved.left_tabs[0].sort = PaneSort {
+            sort_by: "size".into(),
+            ascending: false,
+        };
+        saved.right_tabs[0].history.back = vec!["/prev".into()];
+        saved.prepare_for_save();
+        let sent = serde_json::json!({ "leftPath": "/c", "leftSelectedIndex": 1 });
+        let Value::Object(sent) = sent else {
+            unreachable!()
+        };
+
+        let mut state = PersistedState::with_saved(Some(saved), sent).unwrap();
+        state.prepare_for_save();
+        assert_eq!(state.left_path, "/c");
+        assert_eq!(state.left_tabs[0].path, "/c");
+        assert_eq!(state.left_tabs[0].selected_index, 1);
+        assert_eq!(state.left_sort.sort_by, "size");
+        assert_eq!(state.right_history.back, ["/prev"]);
+        assert_eq!(state.right_path, "/b");
+
+        let mistyped = serde_json::json!({ "leftPath": 3 });
+        let Value::Object(mistyped) = mistyped else {
+            unreachable!()
+        };
+        assert!(PersistedState::with_saved(None, mistyped).is_err());
+    }
+
+    #[test]
+    fn test_tabs_from_older_state_and_tabless_saves() {
+        let state = PersistedState::parse(V1).unwrap();
+        assert_eq!(state.left_tabs.len(), 1);
+        assert_eq!(state.left_tabs[0].path, "/a");
+        assert_eq!(state.left_tabs[0].selected_index, 3);
+        assert!(state.left_tabs[0].show_hidden);
+        assert_eq!(state.right_tabs[0].path, "/b");
+
+        // The active tab's fields follow the tab
+        let mut state = PersistedState {
+            left_path: "/stale".into(),
+            left_tabs: vec![
+                TabState {
+                    path: "/one".into(),
+                    ..TabState::default()
+                },
+                TabState {
+                    path: "/two".into(),
+                    scroll_top: 40.0,
+                    ..TabState::default()
+                },
+            ],
+            left_active_tab: 7,
+            ..PersistedState::default()
+        };
+        state.prepare_for_save();
+        assert_eq!(state.left_active_tab, 1);
+        assert_eq!(
+            (state.left_path.as_str(), state.left_scroll_top),
+            ("/two", 40.0)
+        );
+
+        // A frontend without tabs saves only the active tab's fields
+        let saved = r#"{"version": 5, "leftPath": "/x", "rightPath": "/y"}"#;
+        let state = PersistedState::parse(saved).unwrap();
+        assert_eq!(state.left_tabs.len(), 1);
+        assert_eq!(state.right_tabs[0].path, "/y");
+    }
+
+    #[test]
+    fn test_window_must_stay_reachable() {
+        let laptop = ScreenRect {
+            x: 0,
+            y: 0,
+            width: 1440,
+            height: 900,
+        };
+        let external = ScreenRect {
+            x: 1440,
+            y: -200,
+            width: 2560,
+            height: 1440,
+        };
+        let window = |x, y| WindowState {
+            x,
+            y,
+            width: 1200,
+            height: 800,
+            maximized: false,
+        };
+
+        assert!(window(100, 50).is_reachable_on(&[laptop]));
+        // Saved on the external monitor, which is now unplugged
+        assert!(window(2000, 100).is_reachable_on(&[laptop, external]));
+        assert!(!window(2000, 100).is_reachable_on(&[laptop]));
+        // Mostly off the left edge, but the title bar can still be grabbed
+        assert!(window(-1100, 0).is_reachable_on(&[laptop]));
+        assert!(!window(-1150, 0).is_reachable_on(&[laptop]));
+        // Title bar above the screen
+        assert!(!window(100, -10).is_reachable_on(&[laptop]));
+        assert!(!window(100, 50).is_reachable_on(&[]));
+        let empty = WindowState {
+            width: 0,
+            ..window(100, 50)
+        };
+        assert!(!empty.is_reachable_on(&[laptop]));
+
+        let state = PersistedState::parse(
+            r#"{"version": 4, "window": {"x": 5, "y": 6, "width": 700, "height": 500,
+                "maximized": true}}"#,
+        )
+        .unwrap();
+        assert_eq!(state.window.map(|w| (w.x, w.maximized)), Some((5, true)));
+    }
+}
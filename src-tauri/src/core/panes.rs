@@ -0,0 +1,103 @@
+use std::fmt;
+
+use crate::core::error::CommandError;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Side {
+    Left,
+    Right,
+}
+
+impl Side {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Side::Left => "left",
+            Side::Right => "right",
+        }
+    }
+}
+
+/// One tab of one pane, written `left:0`, `right:2`. A bare `left` or `right` is tab 0,
+/// the only tab there was before panes had tabs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct PaneId {
+    pub side: Side,
+    pub tab: u32,
+}
+
+impl PaneId {
+    pub const LEFT: PaneId = PaneId {
+        side: Side::Left,
+        tab: 0,
+    };
+    pub const RIGHT: PaneId = PaneId {
+        side: Side::Right,
+        tab: 0,
+    };
+
+    pub fn parse(id: &str) -> Result<Self, CommandError> {
+        let invalid = || CommandError::invalid_argument(format!("Invalid pane: {}", id));
+        let (side, tab) = match id.split_once(':') {
+            Some((side, tab)) => (side, tab.parse().map_err(|_| invalid())?),
+            None => (id, 0),
+        };
+        let side = match side {
+            "left" => Side::Left,
+            "right" => Side::Right,
+            _ => return Err(invalid()),
+        };
+        Ok(Self { side, tab })
+    }
+
+    /// The pane a command acts on: `pane_id` when given, which must be on `side`, and
+    /// otherwise tab 0 of `side`.
+    pub fn of(side: &str, pane_id: Option<&str>) -> Result<Self, CommandError> {
+        let on_side = Self::parse(side)?;
+        let Some(pane_id) = pane_id else {
+            return Ok(on_side);
+        };
+        let pane = Self::parse(pane_id)?;
+        if pane.side != on_side.side {
+            return Err(CommandError::invalid_argument(format!(
+                "Pane {} is not on the {} side",
+                pane, side
+            )));
+        }
+        Ok(pane)
+    }
+}
+
+impl fmt::Display for PaneId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.side.as_str(), self.tab)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bare_sides_are_tab_zero() {
+        assert_eq!(PaneId::parse("left").unwrap(), PaneId::LEFT);
+        assert_eq!(PaneId::parse("right:0").unwrap(), PaneId::RIGHT);
+        let tab = PaneId::parse("right:2").unwrap();
+        assert_eq!((tab.side, tab.tab), (Side::Right, 2));
+        assert_eq!(tab.to_string(), "right:2");
+        for bad in ["middle", "left:", "left:x", "left:-1", ""] {
+            assert_eq!(
+                PaneId::parse(bad).unwrap_err().code(),
+                "invalidArgument",
+                "{bad}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_pane_of_side() {
+        assert_eq!(PaneId::of("left", None).unwrap(), PaneId::LEFT);
+        assert_eq!(PaneId::of("left", Some("left:3")).unwrap().tab, 3);
+        assert!(PaneId::of("left", Some("right:1")).is_err());
+        assert!(PaneId::of("up", None).is_err());
+    }
+}
@@ -1,21 +1,456 @@
 use portable_pty::{native_pty_system, CommandBuilder, MasterPty, PtySize};
+use std::collections::VecDeque;
 use std::io::{Read, Write};
-use std::sync::atomic::AtomicBool;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::core::lock::LockExt;
+
+/// Longest escape sequence held back while waiting for its end; anything longer is
+/// not a directory report.
+const MAX_OSC: usize = 4096;
+
+/// Bytes of recent output each terminal keeps for a view that attaches again.
+pub const DEFAULT_SCROLLBACK_BYTES: usize = 2 * 1024 * 1024;
+
+/// How long the reader waits for the shell to exit once its output has ended.
+const EXIT_WAIT: Duration = Duration::from_secs(1);
 
 pub struct PtyState {
     pub master: Arc<Mutex<Box<dyn MasterPty + Send>>>,
     pub writer: Arc<Mutex<Box<dyn Write + Send>>>,
     pub child: Arc<Mutex<Box<dyn portable_pty::Child + Send + Sync>>>,
     pub reader_active: Arc<AtomicBool>,
+    /// Set before the app kills the shell, so its exit is not taken for the user's
+    pub killed: Arc<AtomicBool>,
+    /// The shell's folder as it last reported it, for shells that do
+    pub cwd: Arc<Mutex<Option<String>>>,
+    /// Recent output, behind its own lock so reading it never waits on the writer
+    pub scrollback: Arc<Mutex<Scrollback>>,
+}
+
+/// The most recent output of a terminal, at most `capacity` bytes. The oldest output
+/// goes first, cut at a line break where one is near so lines are not shown half, and
+/// never inside an escape sequence. Each push is numbered, so a view that reads the
+/// contents can tell which output events they already hold.
+#[derive(Debug)]
+pub struct Scrollback {
+    chunks: VecDeque<String>,
+    len: usize,
+    capacity: usize,
+    seq: u64,
+    /// The start of an escape sequence dropped with an earlier chunk; its rest goes too
+    open: String,
+}
+
+impl Scrollback {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            chunks: VecDeque::new(),
+            len: 0,
+            capacity,
+            seq: 0,
+            open: String::new(),
+        }
+    }
+
+    /// Keeps `text` and returns its number, one more than the last push's.
+    pub fn push(&mut self, text: &str) -> u64 {
+        if text.is_empty() {
+            return self.seq;
+        }
+        self.seq += 1;
+        self.len += text.len();
+        self.chunks.push_back(text.to_string());
+        self.trim();
+        self.seq
+    }
+
+    /// The number of the last push the contents hold.
+    pub fn seq(&self) -> u64 {
+        self.seq
+    }
+
+    fn trim(&mut self) {
+        while self.len > self.capacity || !self.open.is_empty() {
+            let Some(front) = self.chunks.front_mut() else {
+                break;
+            };
+            let excess = self.len.saturating_sub(self.capacity);
+            let mut cut = front.len().min(excess);
+            if self.open.is_empty() && cut < front.len() {
+                while !front.is_char_boundary(cut) {
+                    cut += 1;
+                }
+                // Rather a few bytes less history than a broken first line
+                if let Some(newline) = front[cut..].find('\n') {
+                    if newline < 256 {
+                        cut += newline + 1;
+                    }
+                }
+            }
+            let joined = std::mem::take(&mut self.open) + front;
+            let offset = joined.len() - front.len();
+            if let Some(esc) = joined[..offset + cut].rfind('\x1b') {
+                match escape_end(&joined, esc) {
+                    Some(end) => cut = cut.max(end - offset),
+                    None => {
+                        cut = front.len();
+                        if joined.len() - esc <= MAX_OSC {
+                            self.open = joined[esc..].to_string();
+                        }
+                    }
+                }
+            }
+            if cut == front.len() {
+                self.chunks.pop_front();
+            } else {
+                front.drain(..cut);
+            }
+            self.len -= cut;
+        }
+    }
+
+    pub fn contents(&self) -> String {
+        let mut text = String::with_capacity(self.len);
+        for chunk in &self.chunks {
+            text.push_str(chunk);
+        }
+        text
+    }
+
+    pub fn clear(&mut self) {
+        self.chunks.clear();
+        self.len = 0;
+        self.open.clear();
+    }
+}
+
+/// How a terminal's shell ended.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Exit {
+    /// Unknown when the shell was still running or could not be waited for
+    pub code: Option<i32>,
+    /// `exited`, `killed` or `readError` (the output ended while the shell ran on)
+    pub reason: &'static str,
+}
+
+/// Waits up to `EXIT_WAIT` for the shell once the reader has stopped, and tells why
+/// the terminal ended.
+pub fn wait_exit(
+    child: &Mutex<Box<dyn portable_pty::Child + Send + Sync>>,
+    killed: &AtomicBool,
+) -> Exit {
+    let deadline = Instant::now() + EXIT_WAIT;
+    let status = loop {
+        let status = child.lock_or_recover().try_wait();
+        match status {
+            Ok(Some(status)) => break Some(status),
+            Ok(None) if Instant::now() < deadline => std::thread::sleep(Duration::from_millis(20)),
+            _ => break None,
+        }
+    };
+    // Windows exit codes are unsigned; NTSTATUS values come out negative as in `$?`
+    let code = status.as_ref().map(|status| status.exit_code() as i32);
+    let reason = if killed.load(Ordering::Relaxed) {
+        "killed"
+    } else if status.is_some() {
+        "exited"
+    } else {
+        "readError"
+    };
+    Exit { code, reason }
+}
+
+/// The shell a terminal runs: `configured` unless it is blank, else the platform's
+/// default as [`default_shell`] picks it from the real environment.
+pub fn shell_or_default(configured: Option<&str>) -> String {
+    match configured.map(str::trim) {
+        Some(shell) if !shell.is_empty() => shell.to_string(),
+        _ => default_shell(|name| std::env::var(name).ok(), on_path),
+    }
+}
+
+/// The user's shell from `SHELL`, else the system's. `var` reads an environment
+/// variable and `on_path` says whether a program is on `PATH`, for tests.
+#[cfg(not(windows))]
+pub fn default_shell(
+    var: impl Fn(&str) -> Option<String>,
+    _on_path: impl Fn(&str) -> bool,
+) -> String {
+    let fallback = if cfg!(target_os = "macos") {
+        "/bin/zsh"
+    } else {
+        "/bin/sh"
+    };
+    var("SHELL")
+        .filter(|shell| !shell.is_empty())
+        .unwrap_or_else(|| fallback.to_string())
+}
+
+/// PowerShell when it is on `PATH`, else the command interpreter `COMSPEC` names, else
+/// `cmd.exe`. `var` reads an environment variable and `on_path` says whether a program
+/// is on `PATH`, for tests.
+#[cfg(windows)]
+pub fn default_shell(
+    var: impl Fn(&str) -> Option<String>,
+    on_path: impl Fn(&str) -> bool,
+) -> String {
+    if on_path("powershell.exe") {
+        return "powershell.exe".to_string();
+    }
+    var("COMSPEC")
+        .filter(|shell| !shell.is_empty())
+        .unwrap_or_else(|| "cmd.exe".to_string())
+}
+
+/// Whether `program` is a file in one of the `PATH` directories.
+fn on_path(program: &str) -> bool {
+    std::env::var_os("PATH")
+        .is_some_and(|path| std::env::split_paths(&path).any(|dir| dir.join(program).is_file()))
+}
+
+/// Turns terminal output read in arbitrary chunks into text. A character split
+/// between two reads is held back until the rest of it arrives, instead of each half
+/// becoming a replacement character.
+#[derive(Debug, Default)]
+pub struct Utf8Decoder {
+    pending: Vec<u8>,
+}
+
+impl Utf8Decoder {
+    /// Text for `bytes` and whatever was held back before them. Bytes that can never
+    /// be UTF-8 become U+FFFD, as in `String::from_utf8_lossy`.
+    pub fn decode(&mut self, bytes: &[u8]) -> String {
+        self.pending.extend_from_slice(bytes);
+        let mut text = String::with_capacity(self.pending.len());
+        let mut rest = &self.pending[..];
+        loop {
+            match std::str::from_utf8(rest) {
+                Ok(valid) => {
+                    text.push_str(valid);
+                    rest = &[];
+                    break;
+                }
+                Err(e) => {
+                    let (valid, after) = rest.split_at(e.valid_up_to());
+                    // Checked by from_utf8 just now
+                    text.push_str(std::str::from_utf8(valid).unwrap());
+                    match e.error_len() {
+                        Some(len) => {
+                            text.push(char::REPLACEMENT_CHARACTER);
+                            rest = &after[len..];
+                        }
+                        // The start of a character the next read completes
+                        None => {
+                            rest = after;
+                            break;
+                        }
+                    }
+                }
+            }
+        }
+        let held = rest.len();
+        self.pending.drain(..self.pending.len() - held);
+        text
+    }
+
+    /// What is still held back once the output has ended.
+    pub fn finish(&mut self) -> String {
+        let text = String::from_utf8_lossy(&self.pending).into_owned();
+        self.pending.clear();
+        text
+    }
+}
+
+/// Picks the folder reports out of terminal output: `OSC 7 ; file://host/path` and
+/// iTerm2's `OSC 1337 ; CurrentDir=path`, ended by BEL or ST. A sequence split between
+/// reads is completed by the next one. OSC 7 reports naming another host (a shell
+/// over ssh) are ignored, as that folder is not on this machine.
+#[derive(Debug, Default)]
+pub struct CwdScanner {
+    partial: String,
+    local_host: Option<String>,
+}
+
+impl CwdScanner {
+    /// A scanner that takes OSC 7 reports from `local_host` (this machine's name) as
+    /// well as from an empty host or `localhost`.
+    pub fn new(local_host: Option<String>) -> Self {
+        Self { partial: String::new(), local_host }
+    }
+
+    /// The last folder reported in `text`, if any.
+    pub fn scan(&mut self, text: &str) -> Option<String> {
+        let joined;
+        let mut rest = if self.partial.is_empty() {
+            text
+        } else {
+            joined = std::mem::take(&mut self.partial) + text;
+            &joined
+        };
+        let mut cwd = None;
+        while let Some(start) = rest.find("\x1b]") {
+            let body = &rest[start + 2..];
+            let Some((end, terminator)) = osc_end(body) else {
+                if rest.len() - start <= MAX_OSC {
+                    self.partial = rest[start..].to_string();
+                }
+                break;
+            };
+            if let Some(dir) = osc_cwd(&body[..end], self.local_host.as_deref()) {
+                cwd = Some(dir);
+            }
+            rest = &body[end + terminator..];
+        }
+        // A lone ESC at the end may start the next read's sequence
+        if self.partial.is_empty() && rest.ends_with('\x1b') {
+            self.partial.push('\x1b');
+        }
+        cwd
+    }
+}
+
+/// Where the escape sequence starting at `esc` in `text` ends, or `None` when `text`
+/// ends first. Covers CSI, the string sequences (OSC, DCS, APC, PM) and the short
+/// ones such as `ESC ( B`.
+fn escape_end(text: &str, esc: usize) -> Option<usize> {
+    let bytes = &text.as_bytes()[esc + 1..];
+    match bytes.first()? {
+        b'[' => bytes[1..]
+            .iter()
+            .position(|b| (0x40..=0x7e).contains(b))
+            .map(|i| esc + i + 3),
+        b']' | b'P' | b'_' | b'^' => {
+            osc_end(&text[esc + 2..]).map(|(end, terminator)| esc + 2 + end + terminator)
+        }
+        _ => bytes
+            .iter()
+            .position(|b| !(0x20..=0x2f).contains(b))
+            .map(|i| esc + i + 2),
+    }
+}
+
+/// Where the OSC body ends and how long its terminator is.
+fn osc_end(body: &str) -> Option<(usize, usize)> {
+    let bel = body.find('\x07').map(|i| (i, 1));
+    let st = body.find("\x1b\\").map(|i| (i, 2));
+    match (bel, st) {
+        (Some(a), Some(b)) => Some(a.min(b)),
+        (a, b) => a.or(b),
+    }
+}
+
+/// The folder an OSC body reports, if it is a folder report about this machine.
+fn osc_cwd(body: &str, local_host: Option<&str>) -> Option<String> {
+    if let Some(dir) = body.strip_prefix("1337;CurrentDir=") {
+        return Some(dir.to_string()).filter(|dir| !dir.is_empty());
+    }
+    let url = body.strip_prefix("7;")?.strip_prefix("file://")?;
+    let (host, path) = url.split_at(url.find('/')?);
+    if !is_local_host(host, local_host) {
+        return None;
+    }
+    let path = percent_decode(path);
+    // file:///C:/Users comes from shells on Windows
+    let path = match path.as_bytes() {
+        [b'/', drive, b':', ..] if drive.is_ascii_alphabetic() => path[1..].to_string(),
+        _ => path,
+    };
+    Some(path)
+}
+
+/// Whether an OSC 7 host names this machine. Shells report either the full name or,
+/// like macOS's `mac.local`, the name with a domain the system may leave off.
+fn is_local_host(host: &str, local_host: Option<&str>) -> bool {
+    let short = |name: &str| name.split('.').next().unwrap_or(name).to_ascii_lowercase();
+    host.is_empty()
+        || host.eq_ignore_ascii_case("localhost")
+        || local_host.is_some_and(|local| short(local) == short(host))
+}
+
+fn percent_decode(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes
+            .get(i + 1..i + 3)
+            .and_then(|h| std::str::from_utf8(h).ok());
+        match hex.and_then(|h| u8::from_str_radix(h, 16).ok()) {
+            Some(byte) if bytes[i] == b'%' => {
+                out.push(byte);
+                i += 3;
+            }
+            _ => {
+                out.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&out).into_owned()
 }
 
-/// Spawns a PTY running the user's shell in the given working directory.
+/// The current folder of process `pid`, for shells that do not report it. Only Linux
+/// and macOS can tell.
+#[cfg(target_os = "linux")]
+pub fn process_cwd(pid: u32) -> Option<PathBuf> {
+    std::fs::read_link(format!("/proc/{}/cwd", pid)).ok()
+}
+
+/// The current folder of process `pid`, for shells that do not report it. Only Linux
+/// and macOS can tell.
+#[cfg(target_os = "macos")]
+pub fn process_cwd(pid: u32) -> Option<PathBuf> {
+    use std::os::unix::ffi::OsStrExt;
+
+    // SAFETY: a C struct of integers and chars, for which all zeroes is valid
+    let mut info: libc::proc_vnodepathinfo = unsafe { std::mem::zeroed() };
+    let size = std::mem::size_of::<libc::proc_vnodepathinfo>() as libc::c_int;
+    // SAFETY: `info` is a writable buffer of `size` bytes, the flavor's struct
+    let written = unsafe {
+        libc::proc_pidinfo(
+            pid as libc::c_int,
+            libc::PROC_PIDVNODEPATHINFO,
+            0,
+            &mut info as *mut _ as *mut libc::c_void,
+            size,
+        )
+    };
+    if written != size {
+        return None;
+    }
+    // The path is a NUL-terminated MAXPATHLEN buffer, split up by libc
+    let path: Vec<u8> = info
+        .pvi_cdir
+        .vip_path
+        .iter()
+        .flatten()
+        .map(|&c| c as u8)
+        .take_while(|&b| b != 0)
+        .collect();
+    (!path.is_empty()).then(|| PathBuf::from(std::ffi::OsStr::from_bytes(&path)))
+}
+
+/// The current folder of process `pid`, for shells that do not report it. Only Linux
+/// and macOS can tell.
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+pub fn process_cwd(_pid: u32) -> Option<PathBuf> {
+    None
+}
+
+/// Spawns a PTY running `shell` in the given working directory; on Windows it goes
+/// through ConPTY.
 /// Returns the PTY state (for writing/resizing) and a reader (for reading output).
+/// The state keeps up to `scrollback_bytes` of output once the reader stores it.
 pub fn spawn_pty(
+    shell: &str,
     cwd: &str,
     rows: u16,
     cols: u16,
+    scrollback_bytes: usize,
 ) -> Result<(PtyState, Box<dyn Read + Send>), String> {
     let pty_system = native_pty_system();
 
@@ -28,15 +463,17 @@ pub fn spawn_pty(
         })
         .map_err(|e| format!("Failed to open PTY: {}", e))?;
 
-    let shell = std::env::var("SHELL").unwrap_or_else(|_| "/bin/zsh".to_string());
-    let mut cmd = CommandBuilder::new(&shell);
+    let mut cmd = CommandBuilder::new(shell);
     cmd.cwd(cwd);
-    cmd.env("TERM", "xterm-256color");
+    // Console programs on Windows do not read TERM; ConPTY translates for them
+    if !cfg!(windows) {
+        cmd.env("TERM", "xterm-256color");
+    }
 
     let child = pair
         .slave
         .spawn_command(cmd)
-        .map_err(|e| format!("Failed to spawn shell: {}", e))?;
+        .map_err(|e| format!("Failed to spawn {}: {}", shell, e))?;
     drop(pair.slave);
 
     let reader = pair
@@ -53,6 +490,9 @@ pub fn spawn_pty(
         writer: Arc::new(Mutex::new(writer)),
         child: Arc::new(Mutex::new(child)),
         reader_active: Arc::new(AtomicBool::new(true)),
+        killed: Arc::new(AtomicBool::new(false)),
+        cwd: Arc::new(Mutex::new(None)),
+        scrollback: Arc::new(Mutex::new(Scrollback::new(scrollback_bytes))),
     };
 
     Ok((state, reader))
@@ -62,10 +502,52 @@ pub fn spawn_pty(
 mod tests {
     use super::*;
 
+    fn spawn_test_pty() -> (PtyState, Box<dyn Read + Send>) {
+        let cwd = std::env::temp_dir().to_string_lossy().into_owned();
+        spawn_pty(
+            &shell_or_default(None),
+            &cwd,
+            24,
+            80,
+            DEFAULT_SCROLLBACK_BYTES,
+        )
+        .expect("should spawn PTY")
+    }
+
+    #[test]
+    fn test_configured_shell_wins_unless_blank() {
+        assert_eq!(shell_or_default(Some("/usr/bin/fish")), "/usr/bin/fish");
+        assert_eq!(shell_or_default(Some("  ")), shell_or_default(None));
+    }
+
+    #[cfg(not(windows))]
+    #[test]
+    fn test_default_shell_on_unix() {
+        let shell = default_shell(|_| Some("/bin/bash".to_string()), |_| false);
+        assert_eq!(shell, "/bin/bash");
+        let fallback = default_shell(|_| None, |_| true);
+        assert!(
+            fallback == "/bin/zsh" || fallback == "/bin/sh",
+            "{fallback}"
+        );
+        assert_eq!(default_shell(|_| Some(String::new()), |_| false), fallback);
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn test_default_shell_on_windows() {
+        let comspec = |name: &str| (name == "COMSPEC").then(|| r"C:\Windows\cmd.exe".to_string());
+        assert_eq!(
+            default_shell(comspec, |p| p == "powershell.exe"),
+            "powershell.exe"
+        );
+        assert_eq!(default_shell(comspec, |_| false), r"C:\Windows\cmd.exe");
+        assert_eq!(default_shell(|_| None, |_| false), "cmd.exe");
+    }
+
     #[test]
     fn test_spawn_pty_creates_running_child() {
-        let home = std::env::var("HOME").unwrap_or_else(|_| "/tmp".to_string());
-        let (state, _reader) = spawn_pty(&home, 24, 80).expect("should spawn PTY");
+        let (state, _reader) = spawn_test_pty();
 
         let mut child = state.child.lock().unwrap();
         assert!(
@@ -79,8 +561,7 @@ mod tests {
 
     #[test]
     fn test_pty_resize() {
-        let home = std::env::var("HOME").unwrap_or_else(|_| "/tmp".to_string());
-        let (state, _reader) = spawn_pty(&home, 24, 80).expect("should spawn PTY");
+        let (state, _reader) = spawn_test_pty();
 
         let master = state.master.lock().unwrap();
         let result = master.resize(PtySize {
@@ -99,8 +580,7 @@ mod tests {
 
     #[test]
     fn test_pty_write_and_read() {
-        let home = std::env::var("HOME").unwrap_or_else(|_| "/tmp".to_string());
-        let (state, mut reader) = spawn_pty(&home, 24, 80).expect("should spawn PTY");
+        let (state, mut reader) = spawn_test_pty();
 
         // Write an echo command
         {
@@ -139,4 +619,163 @@ mod tests {
         let _ = child.kill();
         let _ = child.wait();
     }
+
+    #[test]
+    fn test_decoder_joins_characters_split_across_reads() {
+        let text = "héllo ✓ 日本 🦀";
+        let bytes = text.as_bytes();
+        for size in 1..=4 {
+            let mut decoder = Utf8Decoder::default();
+            let mut decoded: String = bytes.chunks(size).map(|c| decoder.decode(c)).collect();
+            decoded.push_str(&decoder.finish());
+            assert_eq!(decoded, text, "chunks of {size}");
+            assert!(!decoded.contains(char::REPLACEMENT_CHARACTER));
+        }
+    }
+
+    #[test]
+    fn test_decoder_replaces_invalid_bytes() {
+        let mut decoder = Utf8Decoder::default();
+        assert_eq!(decoder.decode(b"a\xffb\xe2\x9c"), "a\u{fffd}b");
+        assert_eq!(decoder.decode(b"\x93"), "\u{2713}");
+        assert_eq!(decoder.decode(b"\xe6"), "");
+        assert_eq!(decoder.finish(), "\u{fffd}");
+    }
+
+    #[test]
+    fn test_scanner_finds_osc7_and_osc1337() {
+        let mut scanner = CwdScanner::new(Some("mac.local".to_string()));
+        let osc7 = "ls\r\n\x1b]7;file://mac.local/Users/me/My%20Docs\x07$ ";
+        assert_eq!(scanner.scan(osc7).as_deref(), Some("/Users/me/My Docs"));
+        let osc1337 = "\x1b]1337;CurrentDir=/tmp/a\x1b\\\x1b]1337;CurrentDir=/tmp/b\x07";
+        assert_eq!(scanner.scan(osc1337).as_deref(), Some("/tmp/b"));
+        assert_eq!(scanner.scan("\x1b]0;window title\x07plain"), None);
+        let windows = "\x1b]7;file://localhost/C:/Users/me\x1b\\";
+        assert_eq!(scanner.scan(windows).as_deref(), Some("C:/Users/me"));
+    }
+
+    #[test]
+    fn test_scanner_ignores_other_hosts() {
+        let mut scanner = CwdScanner::new(Some("Workstation".to_string()));
+        assert_eq!(scanner.scan("\x1b]7;file://server.example.com/srv\x07"), None);
+        let local = "\x1b]7;file://workstation.lan/home/me\x07";
+        assert_eq!(scanner.scan(local).as_deref(), Some("/home/me"));
+        // Without a known name only reports that leave the host out are trusted
+        let mut scanner = CwdScanner::default();
+        assert_eq!(scanner.scan("\x1b]7;file://workstation/home/me\x07"), None);
+        assert_eq!(scanner.scan("\x1b]7;file:///home/me\x07").as_deref(), Some("/home/me"));
+    }
+
+    #[test]
+    fn test_scanner_joins_sequences_split_across_reads() {
+        let sequence = "out\x1b]7;file:///home/me/src\x1b\\more";
+        for split in 1..sequence.len() {
+            let mut scanner = CwdScanner::default();
+            let first = scanner.scan(&sequence[..split]);
+            let second = scanner.scan(&sequence[split..]);
+            assert_eq!(
+                first.or(second).as_deref(),
+                Some("/home/me/src"),
+                "split at {split}"
+            );
+        }
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_process_cwd_of_self() {
+        let cwd = process_cwd(std::process::id());
+        assert_eq!(cwd, std::env::current_dir().ok());
+    }
+
+    #[test]
+    fn test_scrollback_drops_the_oldest_output() {
+        let mut scrollback = Scrollback::new(16);
+        scrollback.push("one\ntwo\n");
+        scrollback.push("three\n");
+        assert_eq!(scrollback.contents(), "one\ntwo\nthree\n");
+        scrollback.push("four\n");
+        assert_eq!(scrollback.contents(), "two\nthree\nfour\n");
+        scrollback.push(&"x".repeat(40));
+        assert_eq!(scrollback.contents(), "x".repeat(16));
+        scrollback.clear();
+        assert_eq!(scrollback.contents(), "");
+    }
+
+    #[test]
+    fn test_scrollback_numbers_pushes() {
+        let mut scrollback = Scrollback::new(4);
+        assert_eq!(scrollback.push("ab"), 1);
+        assert_eq!(scrollback.push(""), 1);
+        assert_eq!(scrollback.push("cdef"), 2);
+        assert_eq!(scrollback.seq(), 2);
+        scrollback.clear();
+        assert_eq!(scrollback.push("g"), 3);
+    }
+
+    #[test]
+    fn test_scrollback_never_cuts_an_escape_sequence() {
+        // The cut falls inside a colour code, a title and a charset switch
+        let mut scrollback = Scrollback::new(12);
+        scrollback.push("xx\x1b[1;31mred");
+        scrollback.push("\x1b]0;a title\x07tail");
+        assert_eq!(scrollback.contents(), "tail");
+        let mut scrollback = Scrollback::new(6);
+        scrollback.push("\x1b(Bplain");
+        assert_eq!(scrollback.contents(), "plain");
+        // A sequence split between pushes goes whole, also from the newer one
+        let mut scrollback = Scrollback::new(8);
+        scrollback.push("ab\x1b]0;long ti");
+        scrollback.push("tle\x07after");
+        assert_eq!(scrollback.contents(), "after");
+        let mut scrollback = Scrollback::new(6);
+        scrollback.push("ab\x1b[");
+        scrollback.push("1;31mxyz");
+        assert_eq!(scrollback.contents(), "xyz");
+    }
+
+    #[test]
+    fn test_scrollback_cuts_at_character_boundaries() {
+        let mut scrollback = Scrollback::new(5);
+        scrollback.push("ééé");
+        assert_eq!(scrollback.contents(), "éé");
+        scrollback.push("✓");
+        assert_eq!(scrollback.contents(), "é✓");
+        scrollback.push("✓");
+        assert_eq!(scrollback.contents(), "✓");
+    }
+
+    /// Reads until the shell's output ends, as the terminal's reader task does.
+    fn drain(reader: &mut Box<dyn Read + Send>) {
+        let mut buf = [0u8; 4096];
+        while matches!(reader.read(&mut buf), Ok(n) if n > 0) {}
+    }
+
+    #[test]
+    fn test_exit_reports_code() {
+        let (state, mut reader) = spawn_test_pty();
+        {
+            let mut writer = state.writer.lock().unwrap();
+            writer.write_all(b"exit 3\r").unwrap();
+            writer.flush().unwrap();
+        }
+        drain(&mut reader);
+        let exit = wait_exit(&state.child, &state.killed);
+        assert_eq!(
+            exit,
+            Exit {
+                code: Some(3),
+                reason: "exited"
+            }
+        );
+    }
+
+    #[test]
+    fn test_exit_after_kill_reports_killed() {
+        let (state, mut reader) = spawn_test_pty();
+        state.killed.store(true, Ordering::Relaxed);
+        let _ = state.child.lock().unwrap().kill();
+        drain(&mut reader);
+        assert_eq!(wait_exit(&state.child, &state.killed).reason, "killed");
+    }
 }
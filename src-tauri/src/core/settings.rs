@@ -0,0 +1,255 @@
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use crate::core::model::CompareMode;
+use crate::core::pane_state::PaneSort;
+use crate::core::scan::ScanOptions;
+
+/// Returns the app's data directory (state, settings, caches).
+/// `SPLITCOMMANDER_DATA_DIR` overrides the platform default, e.g. for portable setups.
+pub fn app_data_dir() -> Result<PathBuf, String> {
+    if let Some(dir) = std::env::var_os("SPLITCOMMANDER_DATA_DIR") {
+        return Ok(PathBuf::from(dir));
+    }
+    let data_dir = dirs::data_dir()
+        .ok_or_else(|| "Could not determine data directory".to_string())?;
+    Ok(data_dir.join("com.splitcommander.app"))
+}
+
+/// Whether file operations may act on any path, not only under the home folder and the
+/// pane roots: on when the app is started with `--allow-unrestricted-fileops` or with
+/// `SPLITCOMMANDER_UNRESTRICTED_FILEOPS=1`. Not a setting, so the webview cannot lift
+/// its own confinement.
+pub fn unrestricted_fileops(args: &[String], env: Option<&std::ffi::OsStr>) -> bool {
+    args.iter().any(|arg| arg == "--allow-unrestricted-fileops")
+        || env.is_some_and(|value| value == "1" || value == "true")
+}
+
+/// User preferences persisted in `settings.json`. Every field has a default so
+/// settings files written by older versions keep loading.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct Settings {
+    /// Run cache/temp-file maintenance in the background at startup
+    pub maintenance_on_startup: bool,
+    /// Re-resolve cached directory statuses older than this, for filesystems that do
+    /// not reliably update directory modification times. Unset keeps them until the
+    /// directories' mtimes change.
+    pub dir_cache_max_age_secs: Option<u64>,
+    /// Most directory pairs whose status stays cached; unset uses the default
+    pub dir_cache_capacity: Option<usize>,
+    /// Diff lines shown for a file before the rest of its hunks are cut to their
+    /// headers; unset uses the default
+    pub diff_max_lines: Option<usize>,
+    /// Refuse everything that changes files, and the terminal
+    pub read_only: bool,
+    /// Program the terminal runs; unset picks the platform's shell
+    pub shell: Option<String>,
+    /// Bytes of recent output each terminal keeps for when its view is shown again;
+    /// unset uses the default
+    pub terminal_scrollback_bytes: Option<usize>,
+    /// Saved pairs of roots. Only the session commands change these; `save_settings`
+    /// keeps the stored ones.
+    pub sessions: Vec<Session>,
+}
+
+/// A named pair of roots with the options they are compared with.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Session {
+    /// Unique among the sessions
+    pub name: String,
+    pub left_root: String,
+    pub right_root: String,
+    #[serde(default)]
+    pub options: SessionOptions,
+}
+
+/// What the frontend applies when a session is loaded.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct SessionOptions {
+    pub mode: Option<CompareMode>,
+    pub ignore_patterns: Vec<String>,
+    pub scan_options: ScanOptions,
+    pub left: PaneOptions,
+    pub right: PaneOptions,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct PaneOptions {
+    pub show_hidden: bool,
+    pub sort: PaneSort,
+}
+
+impl Settings {
+    pub fn dir_cache_max_age(&self) -> Option<Duration> {
+        self.dir_cache_max_age_secs.map(Duration::from_secs)
+    }
+
+    pub fn dir_cache_capacity(&self) -> usize {
+        self.dir_cache_capacity.unwrap_or(crate::core::dir_cache::DEFAULT_DIR_CACHE_CAPACITY)
+    }
+
+    pub fn diff_max_lines(&self) -> usize {
+        self.diff_max_lines.unwrap_or(crate::core::textdiff::DEFAULT_DIFF_MAX_LINES)
+    }
+
+    pub fn terminal_scrollback_bytes(&self) -> usize {
+        self.terminal_scrollback_bytes
+            .unwrap_or(crate::core::pty::DEFAULT_SCROLLBACK_BYTES)
+    }
+
+    pub fn session(&self, name: &str) -> Option<&Session> {
+        self.sessions.iter().find(|s| s.name == name)
+    }
+
+    /// Adds a session, replacing the one with the same name in place.
+    pub fn put_session(&mut self, session: Session) {
+        match self.sessions.iter_mut().find(|s| s.name == session.name) {
+            Some(existing) => *existing = session,
+            None => self.sessions.push(session),
+        }
+    }
+
+    /// Returns false when there was no session by that name.
+    pub fn remove_session(&mut self, name: &str) -> bool {
+        let before = self.sessions.len();
+        self.sessions.retain(|s| s.name != name);
+        self.sessions.len() != before
+    }
+}
+
+fn settings_file_path() -> Result<PathBuf, String> {
+    Ok(app_data_dir()?.join("settings.json"))
+}
+
+/// Loads settings, falling back to defaults when the file is missing or unreadable.
+/// Anything that saves the settings back reads them with [`read_settings`] instead.
+pub fn load_settings() -> Settings {
+    read_settings().unwrap_or_default()
+}
+
+/// Reads the settings: defaults when the file does not exist yet, an error when it
+/// cannot be read or parsed, so that saving over it never wipes the sessions and
+/// settings it holds.
+pub fn read_settings() -> Result<Settings, String> {
+    read_settings_file(&settings_file_path()?)
+}
+
+fn read_settings_file(path: &Path) -> Result<Settings, String> {
+    let contents = match std::fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Settings::default()),
+        Err(e) => return Err(format!("Cannot read {}: {}", path.display(), e)),
+    };
+    serde_json::from_str(&contents).map_err(|e| {
+        format!(
+            "Cannot read {}: {}; fix or remove it to save settings again",
+            path.display(),
+            e
+        )
+    })
+}
+
+pub fn save_settings(settings: &Settings) -> Result<(), String> {
+    let path = settings_file_path()?;
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+    }
+    let json = serde_json::to_string_pretty(settings).map_err(|e| e.to_string())?;
+    crate::core::fileops::write_atomic(&path, json.as_bytes()).map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_settings_missing_fields_use_defaults() {
+        let settings: Settings = serde_json::from_str("{}").unwrap();
+        assert!(!settings.maintenance_on_startup);
+        assert_eq!(settings.dir_cache_max_age(), None);
+        assert_eq!(
+            settings.dir_cache_capacity(),
+            crate::core::dir_cache::DEFAULT_DIR_CACHE_CAPACITY
+        );
+    }
+
+    #[test]
+    fn test_settings_serialization() {
+        let settings = Settings {
+            maintenance_on_startup: true,
+            dir_cache_max_age_secs: Some(300),
+            dir_cache_capacity: None,
+            diff_max_lines: None,
+            read_only: true,
+            shell: Some("pwsh.exe".to_string()),
+            terminal_scrollback_bytes: None,
+            sessions: Vec::new(),
+        };
+        let json = serde_json::to_string(&settings).unwrap();
+        assert!(json.contains("maintenanceOnStartup"));
+        assert!(json.contains("\"dirCacheMaxAgeSecs\":300"));
+        assert!(json.contains("\"readOnly\":true"));
+        assert!(json.contains("\"shell\":\"pwsh.exe\""));
+    }
+
+    #[test]
+    fn test_unrestricted_fileops_only_from_command_line_or_environment() {
+        let settings: Settings =
+            serde_json::from_str(r#"{"allowUnrestrictedFileops": true}"#).unwrap();
+        let saved = serde_json::to_string(&settings).unwrap();
+        assert!(!saved.contains("nrestricted"));
+
+        let flag = ["--allow-unrestricted-fileops".to_string()];
+        assert!(unrestricted_fileops(&flag, None));
+        assert!(unrestricted_fileops(&[], Some("1".as_ref())));
+        assert!(!unrestricted_fileops(&[], Some("0".as_ref())));
+        assert!(!unrestricted_fileops(&[], None));
+    }
+
+    #[test]
+    fn test_unreadable_settings_file_is_an_error() {
+        let dir = std::env::temp_dir().join("sc_settings_unreadable");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let file = dir.join("settings.json");
+        assert!(read_settings_file(&file).unwrap().sessions.is_empty());
+
+        std::fs::write(&file, r#"{"readOnly": true, "sessions": [{"name": 1}]}"#).unwrap();
+        let err = read_settings_file(&file).unwrap_err();
+        let kept = std::fs::read_to_string(&file).unwrap();
+        let _ = std::fs::remove_dir_all(&dir);
+        assert!(err.contains("settings.json"), "{}", err);
+        assert!(kept.contains("sessions"));
+    }
+
+    #[test]
+    fn test_sessions_replace_by_name() {
+        let session = |name: &str, left: &str| Session {
+            name: name.to_string(),
+            left_root: left.to_string(),
+            right_root: "/r".to_string(),
+            options: SessionOptions::default(),
+        };
+        let mut settings = Settings::default();
+        settings.put_session(session("photos", "/a"));
+        settings.put_session(session("music", "/m"));
+        settings.put_session(session("photos", "/b"));
+        assert_eq!(settings.sessions.len(), 2);
+        assert_eq!(settings.sessions[0].left_root, "/b");
+
+        // Options left out take their defaults
+        let json = r#"{"sessions":[{"name":"music","leftRoot":"/m","rightRoot":"/r"}]}"#;
+        let loaded: Settings = serde_json::from_str(json).unwrap();
+        assert_eq!(loaded.session("music"), settings.session("music"));
+
+        assert!(settings.remove_session("photos"));
+        assert!(!settings.remove_session("photos"));
+        assert!(settings.session("photos").is_none());
+    }
+}
@@ -0,0 +1,380 @@
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf, MAIN_SEPARATOR};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use notify::event::{EventKind, ModifyKind};
+use notify::{Event, RecommendedWatcher, RecursiveMode, Watcher};
+
+use crate::core::scan;
+
+/// How long events are collected after the first one before the result is refreshed.
+pub const DEBOUNCE: Duration = Duration::from_millis(500);
+
+/// Renames in one batch past which everything is compared again; moving a folder tree
+/// around shows up as a flood of them.
+const RENAME_STORM: usize = 64;
+
+/// Changed paths in one batch past which comparing them one by one stops paying off.
+const MAX_PATHS: usize = 2000;
+
+/// Changes to one folder in a batch past which they are no longer told apart; a bulk
+/// copy into a watched folder would otherwise report every file.
+const MAX_DIR_EVENTS: usize = 500;
+
+/// Events collected over one debounce window.
+trait Batch: Default {
+    fn is_empty(&self) -> bool;
+}
+
+/// What changed under the watched roots during one debounce window.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Changes {
+    /// `/`-separated and relative to the roots
+    pub rel_paths: BTreeSet<String>,
+    /// Events were lost or too many to follow one by one; compare everything again
+    pub rescan: bool,
+    renames: usize,
+}
+
+impl Changes {
+    /// Records the paths of `event` that lie under one of `roots`.
+    pub fn add(&mut self, roots: &[PathBuf], event: notify::Result<Event>) {
+        let event = match event {
+            Ok(event) => event,
+            // The backend could not keep up or lost track of a folder
+            Err(_) => {
+                self.rescan = true;
+                return;
+            }
+        };
+        if event.need_rescan() {
+            self.rescan = true;
+        }
+        match event.kind {
+            EventKind::Access(_) => return,
+            EventKind::Modify(ModifyKind::Name(_)) => self.renames += 1,
+            _ => {}
+        }
+        for path in &event.paths {
+            for root in roots {
+                if let Ok(rel) = path.strip_prefix(root) {
+                    let rel = scan::forward_slashes(&rel.to_string_lossy(), MAIN_SEPARATOR);
+                    self.rel_paths.insert(rel);
+                }
+            }
+        }
+        if self.renames > RENAME_STORM || self.rel_paths.len() > MAX_PATHS {
+            self.rescan = true;
+        }
+    }
+}
+
+impl Batch for Changes {
+    fn is_empty(&self) -> bool {
+        !self.rescan && self.rel_paths.is_empty()
+    }
+}
+
+/// Watches both compared roots recursively. Dropping it stops the watching, and the
+/// thread handing out batches ends with it; a batch being handled is cancelled.
+pub struct RootWatcher {
+    pub left_root: PathBuf,
+    pub right_root: PathBuf,
+    cancel: Arc<AtomicBool>,
+    _watcher: RecommendedWatcher,
+}
+
+impl RootWatcher {
+    /// Starts watching; `on_changes` runs on a background thread once per batch, with a
+    /// flag that is set once the watcher is dropped.
+    pub fn start(
+        left_root: &Path,
+        right_root: &Path,
+        on_changes: impl Fn(Changes, &AtomicBool) + Send + 'static,
+    ) -> Result<Self, String> {
+        let (tx, rx) = mpsc::channel();
+        let mut watcher =
+            notify::recommended_watcher(tx).map_err(|e| format!("Cannot watch folders: {}", e))?;
+        for root in [left_root, right_root] {
+            watcher
+                .watch(root, RecursiveMode::Recursive)
+                .map_err(|e| format!("Cannot watch {}: {}", root.display(), e))?;
+        }
+        let roots = vec![left_root.to_path_buf(), right_root.to_path_buf()];
+        let cancel = Arc::new(AtomicBool::new(false));
+        let flag = Arc::clone(&cancel);
+        std::thread::spawn(move || {
+            debounce(
+                &rx,
+                |changes: &mut Changes, event| changes.add(&roots, event),
+                |changes| on_changes(changes, &flag),
+            )
+        });
+        Ok(Self {
+            left_root: left_root.to_path_buf(),
+            right_root: right_root.to_path_buf(),
+            cancel,
+            _watcher: watcher,
+        })
+    }
+}
+
+impl Drop for RootWatcher {
+    fn drop(&mut self) {
+        self.cancel.store(true, Ordering::Relaxed);
+    }
+}
+
+/// One change to a watched folder's listing.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DirChange {
+    /// `created`, `removed`, `renamed` or `modified`
+    pub kind: &'static str,
+    /// Names of the entries concerned, each once, in the order seen; a rename seen from
+    /// both ends gives the old name before the new one. Empty when unknown.
+    pub names: Vec<String>,
+}
+
+/// What changed in a watched folder during one debounce window, one entry per kind in
+/// the order first seen.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct DirChanges {
+    pub changes: Vec<DirChange>,
+    events: usize,
+}
+
+impl DirChanges {
+    /// Records `event` for the folder `dir`. Lost events, or too many of them, collapse
+    /// the batch into a single `modified` with no names: anything may have changed.
+    pub fn add(&mut self, dir: &Path, event: notify::Result<Event>) {
+        let event = match event {
+            Ok(event) if !event.need_rescan() => event,
+            _ => return self.overflow(),
+        };
+        let kind = match event.kind {
+            EventKind::Access(_) => return,
+            EventKind::Create(_) => "created",
+            EventKind::Remove(_) => "removed",
+            EventKind::Modify(ModifyKind::Name(_)) => "renamed",
+            _ => "modified",
+        };
+        self.events += 1;
+        if self.events > MAX_DIR_EVENTS {
+            return self.overflow();
+        }
+        let names = event
+            .paths
+            .iter()
+            .filter(|path| path.parent() == Some(dir))
+            .filter_map(|path| path.file_name())
+            .map(|name| name.to_string_lossy().into_owned());
+        let change = match self.changes.iter_mut().find(|c| c.kind == kind) {
+            Some(change) => change,
+            None => {
+                self.changes.push(DirChange {
+                    kind,
+                    names: Vec::new(),
+                });
+                self.changes.last_mut().unwrap()
+            }
+        };
+        for name in names {
+            if !change.names.contains(&name) {
+                change.names.push(name);
+            }
+        }
+    }
+
+    fn overflow(&mut self) {
+        self.events = MAX_DIR_EVENTS + 1;
+        self.changes = vec![DirChange {
+            kind: "modified",
+            names: Vec::new(),
+        }];
+    }
+}
+
+impl Batch for DirChanges {
+    fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+}
+
+/// Watches one folder, not what lies below it, for a browse pane. Dropping it stops
+/// the watching.
+pub struct DirWatcher {
+    pub path: PathBuf,
+    _watcher: RecommendedWatcher,
+}
+
+impl DirWatcher {
+    /// Starts watching `path`; `on_changes` runs on a background thread once per batch.
+    pub fn start(
+        path: &Path,
+        on_changes: impl Fn(DirChanges) + Send + 'static,
+    ) -> Result<Self, String> {
+        let (tx, rx) = mpsc::channel();
+        let mut watcher =
+            notify::recommended_watcher(tx).map_err(|e| format!("Cannot watch folders: {}", e))?;
+        watcher
+            .watch(path, RecursiveMode::NonRecursive)
+            .map_err(|e| format!("Cannot watch {}: {}", path.display(), e))?;
+        let dir = path.to_path_buf();
+        std::thread::spawn(move || {
+            debounce(
+                &rx,
+                |changes: &mut DirChanges, event| changes.add(&dir, event),
+                on_changes,
+            )
+        });
+        Ok(Self {
+            path: path.to_path_buf(),
+            _watcher: watcher,
+        })
+    }
+}
+
+/// Hands out the events from `rx` in batches, each collected by `add` over `DEBOUNCE`
+/// from its first event, until the watcher is dropped.
+fn debounce<B: Batch>(
+    rx: &Receiver<notify::Result<Event>>,
+    add: impl Fn(&mut B, notify::Result<Event>),
+    on_batch: impl Fn(B),
+) {
+    while let Ok(first) = rx.recv() {
+        let mut batch = B::default();
+        add(&mut batch, first);
+        let deadline = Instant::now() + DEBOUNCE;
+        loop {
+            match rx.recv_timeout(deadline.saturating_duration_since(Instant::now())) {
+                Ok(event) => add(&mut batch, event),
+                Err(RecvTimeoutError::Timeout) => break,
+                Err(RecvTimeoutError::Disconnected) => return,
+            }
+        }
+        if !batch.is_empty() {
+            on_batch(batch);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use notify::event::{AccessKind, CreateKind, Flag, RemoveKind, RenameMode};
+
+    fn event(kind: EventKind, path: &str) -> notify::Result<Event> {
+        Ok(Event::new(kind).add_path(PathBuf::from(path)))
+    }
+
+    #[test]
+    fn test_changes_are_relative_to_the_roots() {
+        let roots = [PathBuf::from("/l"), PathBuf::from("/r")];
+        let mut changes = Changes::default();
+        changes.add(
+            &roots,
+            event(EventKind::Create(CreateKind::File), "/l/a/b.txt"),
+        );
+        changes.add(&roots, event(EventKind::Remove(RemoveKind::File), "/r/c"));
+        changes.add(
+            &roots,
+            event(EventKind::Access(AccessKind::Any), "/l/read.txt"),
+        );
+        changes.add(&roots, event(EventKind::Any, "/elsewhere/x"));
+        assert_eq!(
+            changes.rel_paths.iter().collect::<Vec<_>>(),
+            ["a/b.txt", "c"]
+        );
+        assert!(!changes.rescan);
+
+        let mut empty = Changes::default();
+        empty.add(&roots, event(EventKind::Any, "/elsewhere/x"));
+        assert!(empty.is_empty());
+    }
+
+    #[test]
+    fn test_lost_events_and_rename_storms_rescan() {
+        let roots = [PathBuf::from("/l")];
+        let mut changes = Changes::default();
+        changes.add(&roots, Err(notify::Error::generic("queue overflow")));
+        assert!(changes.rescan);
+
+        let mut changes = Changes::default();
+        changes.add(
+            &roots,
+            Ok(Event::new(EventKind::Other).set_flag(Flag::Rescan)),
+        );
+        assert!(changes.rescan);
+
+        let mut changes = Changes::default();
+        let rename = EventKind::Modify(ModifyKind::Name(RenameMode::Any));
+        for i in 0..=RENAME_STORM {
+            changes.add(&roots, event(rename, &format!("/l/{}", i)));
+        }
+        assert!(changes.rescan);
+    }
+
+    #[test]
+    fn test_dir_changes_by_kind() {
+        let dir = Path::new("/d");
+        let mut changes = DirChanges::default();
+        changes.add(
+            dir,
+            event(EventKind::Create(CreateKind::File), "/d/new.txt"),
+        );
+        changes.add(
+            dir,
+            event(EventKind::Create(CreateKind::File), "/d/new.txt"),
+        );
+        let rename = EventKind::Modify(ModifyKind::Name(RenameMode::Both));
+        let renamed = Event::new(rename)
+            .add_path("/d/old".into())
+            .add_path("/d/moved".into());
+        changes.add(dir, Ok(renamed));
+        changes.add(dir, event(EventKind::Create(CreateKind::Folder), "/d/b"));
+        changes.add(
+            dir,
+            event(EventKind::Access(AccessKind::Any), "/d/read.txt"),
+        );
+        changes.add(dir, event(EventKind::Remove(RemoveKind::Folder), "/d"));
+        let summary: Vec<_> = changes
+            .changes
+            .iter()
+            .map(|c| (c.kind, c.names.join(",")))
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                ("created", "new.txt,b".to_string()),
+                ("renamed", "old,moved".to_string()),
+                ("removed", String::new()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_dir_event_storm_collapses() {
+        let dir = Path::new("/d");
+        let mut changes = DirChanges::default();
+        for i in 0..=MAX_DIR_EVENTS {
+            let path = format!("/d/{}", i);
+            changes.add(dir, event(EventKind::Create(CreateKind::File), &path));
+        }
+        let unknown = || {
+            vec![DirChange {
+                kind: "modified",
+                names: Vec::new(),
+            }]
+        };
+        assert_eq!(changes.changes, unknown());
+        changes.add(dir, event(EventKind::Remove(RemoveKind::File), "/d/x"));
+        assert_eq!(changes.changes, unknown());
+
+        let mut lost = DirChanges::default();
+        lost.add(dir, Err(notify::Error::generic("queue overflow")));
+        assert_eq!(lost.changes, unknown());
+    }
+}
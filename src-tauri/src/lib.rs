@@ -1,38 +1,135 @@
 mod core;
 
 use core::commands::AppState;
+use tauri::Manager;
+
+/// Runs a headless CLI subcommand (e.g. `verify <left> <right>`) if `args` names one,
+/// returning the process exit code. Returns `None` when the GUI should start.
+pub fn run_cli(args: &[String]) -> Option<i32> {
+    core::cli::run(args)
+}
+
+/// Internals driven by `benches/`; not a stable API.
+#[doc(hidden)]
+pub mod bench {
+    pub use crate::core::commands::{compare_directory_impl, dirs_are_same_recursive_counted};
+    pub use crate::core::dir_cache::DirResolveCache;
+}
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
     tauri::Builder::default()
         .plugin(tauri_plugin_dialog::init())
         .manage(AppState::new())
+        .setup(|app| {
+            let settings = core::settings::load_settings();
+            core::commands::apply_settings(&app.state::<AppState>(), &settings);
+            let args: Vec<String> = std::env::args().skip(1).collect();
+            let env = std::env::var_os("SPLITCOMMANDER_UNRESTRICTED_FILEOPS");
+            let unrestricted = core::settings::unrestricted_fileops(&args, env.as_deref());
+            app.state::<AppState>()
+                .unrestricted_fileops
+                .store(unrestricted, std::sync::atomic::Ordering::Relaxed);
+            if settings.maintenance_on_startup {
+                let handle = app.handle().clone();
+                std::thread::spawn(move || {
+                    let state = handle.state::<AppState>();
+                    let _ = core::commands::run_maintenance_impl(&handle, &state.operations);
+                });
+            }
+            Ok(())
+        })
         .invoke_handler(tauri::generate_handler![
             core::commands::set_root,
+            core::commands::resolve_path,
+            core::commands::complete_path,
             core::commands::start_compare,
             core::commands::cancel_compare,
+            core::commands::watch_roots,
             core::commands::get_diffs,
+            core::commands::get_diffs_page,
             core::commands::get_summary,
+            core::commands::get_scan_errors,
             core::commands::export_report,
+            core::commands::load_report,
+            core::commands::compare_reports,
             core::commands::init_browse,
             core::commands::list_directory,
+            core::commands::watch_directory,
+            core::commands::unwatch_directory,
             core::commands::open_file,
+            core::commands::preview_file,
             core::commands::copy_entry,
             core::commands::copy_entry_overwrite,
+            core::commands::copy_rel_entry,
+            core::commands::diff_file_contents,
+            core::commands::compare_files,
+            core::commands::cancel_compare_files,
+            core::commands::compute_checksums,
+            core::commands::cancel_checksums,
             core::commands::move_entry,
             core::commands::create_directory,
+            core::commands::create_file,
+            core::commands::duplicate_entry,
+            core::commands::create_archive,
+            core::commands::extract_archive,
+            core::commands::rename_entry,
+            core::commands::set_permissions,
+            core::commands::set_modified,
+            core::commands::copy_times,
             core::commands::delete_entry,
+            core::commands::get_undo_stack,
+            core::commands::undo_last_operation,
             core::commands::compare_directory,
             core::commands::resolve_dir_statuses,
             core::commands::cancel_dir_resolve,
             core::commands::clear_dir_resolve_cache,
+            core::commands::resolve_dir_sizes,
+            core::commands::cancel_dir_sizes,
+            core::commands::clear_dir_size_cache,
+            core::commands::measure_paths,
+            core::commands::get_volume_info,
+            core::commands::list_volumes,
+            core::commands::get_known_folders,
+            core::commands::cancel_measure,
+            core::commands::search_files,
+            core::commands::search_content,
+            core::commands::cancel_search,
+            core::commands::find_duplicates,
+            core::commands::cancel_duplicates,
             core::commands::spawn_terminal,
             core::commands::write_terminal,
             core::commands::resize_terminal,
             core::commands::kill_terminal,
+            core::commands::close_pane,
+            core::commands::get_terminal_cwd,
+            core::commands::get_terminal_buffer,
             core::commands::load_app_state,
             core::commands::save_app_state,
+            core::commands::save_window_state,
+            core::commands::load_window_state,
+            core::commands::get_locations,
+            core::commands::add_favorite,
+            core::commands::remove_favorite,
+            core::commands::load_settings,
+            core::commands::save_settings,
+            core::commands::set_read_only,
+            core::commands::get_read_only,
+            core::commands::save_session,
+            core::commands::list_sessions,
+            core::commands::load_session,
+            core::commands::delete_session,
+            core::commands::list_operations,
+            core::commands::cancel_operation,
+            core::commands::run_maintenance,
+            core::commands::verify_trees,
+            core::commands::sync_diffs,
         ])
-        .run(tauri::generate_context!())
-        .expect("error while running tauri application");
+        .build(tauri::generate_context!())
+        .expect("error while running tauri application")
+        .run(|app, event| {
+            if let tauri::RunEvent::Exit = event {
+                core::commands::drop_watchers(&app.state::<AppState>());
+            }
+        });
 }
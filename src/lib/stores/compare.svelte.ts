@@ -1,7 +1,13 @@
 import { invoke } from "@tauri-apps/api/core";
 import { listen, type UnlistenFn } from "@tauri-apps/api/event";
-import { getCurrentWindow } from "@tauri-apps/api/window";
+import {
+  getCurrentWindow,
+  PhysicalPosition,
+  PhysicalSize,
+  type Window,
+} from "@tauri-apps/api/window";
 import { open, save, ask } from "@tauri-apps/plugin-dialog";
+import { errorMessage, partialCopyMessage } from "../types";
 import type {
   AppMode,
   BrowseEntry,
@@ -9,12 +15,22 @@ import type {
   CompareEntry,
   ComparePhase,
   CompareSummary,
+  CopyOutcome,
   DiffItem,
+  KnownFolder,
+  ResolvedPath,
+  ExportFormat,
   ScanProgressPayload,
   CompareDonePayload,
   CompareErrorPayload,
+  DiffsUpdatedPayload,
+  DirectoryChangedPayload,
   DirStatusResolvedPayload,
+  DirResolveErrorPayload,
+  DirSizeResolvedPayload,
   SyncAction,
+  Volume,
+  WindowState,
 } from "../types";
 import { terminalStore } from "./terminal.svelte";
 
@@ -27,6 +43,9 @@ class CompareStore {
   rightPath = $state<string>("");
   leftEntries = $state<BrowseEntry[]>([]);
   rightEntries = $state<BrowseEntry[]>([]);
+  // Mounted volumes and standard folders, for picking a pane root
+  volumes = $state<Volume[]>([]);
+  knownFolders = $state<KnownFolder[]>([]);
 
   // Compare state
   leftRoot = $state<string | null>(null);
@@ -35,6 +54,8 @@ class CompareStore {
   diffs = $state<DiffItem[]>([]);
   summary = $state<CompareSummary | null>(null);
   scanProgress = $state({ left: 0, right: 0 });
+  scanBytes = $state({ left: 0, right: 0 });
+  scanPath = $state<{ left: string | null; right: string | null }>({ left: null, right: null });
   error = $state<string | null>(null);
   private errorTimer: ReturnType<typeof setTimeout> | null = null;
   selectedItem = $state<DiffItem | null>(null);
@@ -42,6 +63,8 @@ class CompareStore {
   showDetails = $state(false);
   filterText = $state("");
   loading = $state(false);
+  // Watch mode: the compare result follows changes on disk
+  watching = $state(false);
   leftShowHidden = $state(false);
   rightShowHidden = $state(false);
 
@@ -64,8 +87,20 @@ class CompareStore {
   private leftReported = { selectedIndex: -1, scrollTop: 0 };
   private rightReported = { selectedIndex: -1, scrollTop: 0 };
   private saveTimer: ReturnType<typeof setTimeout> | null = null;
+  private windowSaveTimer: ReturnType<typeof setTimeout> | null = null;
+  /** Window geometry while not maximized, kept so maximizing does not overwrite it */
+  private normalWindow: Omit<WindowState, "maximized"> | null = null;
 
   private unlisteners: UnlistenFn[] = [];
+  /** Highest compare job id seen; events from older jobs are stale */
+  private compareJobId = 0;
+
+  /** False for events of a compare that has since been superseded */
+  private isCurrentJob(jobId: number): boolean {
+    if (jobId < this.compareJobId) return false;
+    this.compareJobId = jobId;
+    return true;
+  }
 
   get canCompare(): boolean {
     return (
@@ -108,13 +143,21 @@ class CompareStore {
 
   async init() {
     // Fire all IPC calls in parallel: event listeners + init_browse + saved state
-    const [scanUn, doneUn, errorUn, dirResolvedUn, initResult, savedState] = await Promise.all([
+    const [
+      scanUn, doneUn, errorUn, updatedUn, dirResolvedUn, dirSizeUn, dirChangedUn,
+      initResult, savedState,
+    ] = await Promise.all([
       listen<ScanProgressPayload>("scan-progress", (event) => {
-        const { side, entriesScanned, phase } = event.payload;
+        const { jobId, side, entriesScanned, bytesScanned, currentPath, phase } = event.payload;
+        if (!this.isCurrentJob(jobId)) return;
         this.scanProgress = {
           ...this.scanProgress,
           [side]: entriesScanned,
         };
+        this.scanBytes = { ...this.scanBytes, [side]: bytesScanned };
+        if (currentPath !== null) {
+          this.scanPath = { ...this.scanPath, [side]: currentPath };
+        }
         if (phase === "done") {
           this.phase = side === "left" ? "scanning-right" : "comparing";
         } else {
@@ -122,29 +165,42 @@ class CompareStore {
         }
       }),
       listen<CompareDonePayload>("compare-done", async (event) => {
-        this.summary = event.payload.summary;
+        if (!this.isCurrentJob(event.payload.jobId)) return;
+        const { jobId, summary } = event.payload;
+        let diffs: DiffItem[];
         try {
-          this.diffs = await invoke<DiffItem[]>("get_diffs");
+          diffs = await invoke<DiffItem[]>("get_diffs", { jobId });
         } catch {
-          this.diffs = [];
+          diffs = [];
         }
+        // A newer compare may have started while the diffs were loading
+        if (jobId < this.compareJobId) return;
+        this.summary = summary;
+        this.diffs = diffs;
         this.phase = "done";
         this.appMode = "compare";
       }),
       listen<CompareErrorPayload>("compare-error", (event) => {
+        if (!this.isCurrentJob(event.payload.jobId)) return;
         this.setError(event.payload.message);
         this.phase = "error";
       }),
+      listen<DiffsUpdatedPayload>("diffs-updated", async (event) => {
+        const { jobId, summary } = event.payload;
+        if (jobId !== this.compareJobId) return;
+        try {
+          const diffs = await invoke<DiffItem[]>("get_diffs", { jobId });
+          if (jobId !== this.compareJobId) return;
+          this.diffs = diffs;
+          this.summary = summary;
+        } catch {
+          // Replaced by a newer compare meanwhile
+        }
+      }),
       listen<DirStatusResolvedPayload>("dir-status-resolved", (event) => {
         const p = event.payload;
         // Staleness check: payload paths must match current view
-        const leftFull = this.compareRelPath
-          ? this.leftRoot + "/" + this.compareRelPath
-          : this.leftRoot;
-        const rightFull = this.compareRelPath
-          ? this.rightRoot + "/" + this.compareRelPath
-          : this.rightRoot;
-        if (p.leftPath !== leftFull || p.rightPath !== rightFull) return;
+        if (!this.showsCompareLevel(p.leftPath, p.rightPath)) return;
 
         // Patch matching entry in-place
         this.compareEntries = this.compareEntries.map((e) => {
@@ -152,7 +208,17 @@ class CompareStore {
             return {
               ...e,
               status: p.status,
-              dirInfo: { totalSize: p.totalSize },
+              dirInfo: {
+                leftSize: p.leftSize,
+                rightSize: p.rightSize,
+                leftUniqueSize: p.leftUniqueSize,
+                rightUniqueSize: p.rightUniqueSize,
+                onlyLeft: p.onlyLeft,
+                onlyRight: p.onlyRight,
+                modified: p.modified,
+                depthLimited: p.depthLimited,
+                unreadable: p.unreadable,
+              },
             };
           }
           return e;
@@ -161,12 +227,57 @@ class CompareStore {
         // Update summary counts
         if (this.compareSummary) {
           const s = { ...this.compareSummary };
+          s.pending = Math.max(0, s.pending - 1);
           if (p.status === "same") s.same++;
           else if (p.status === "modified") s.metaDiff++;
+          else if (p.status === "partial") s.depthLimited++;
+          else if (p.status === "error") s.errors++;
+          this.compareSummary = s;
+        }
+      }),
+      listen<DirResolveErrorPayload>("dir-resolve-error", (event) => {
+        const p = event.payload;
+        if (!this.showsCompareLevel(p.leftPath, p.rightPath)) return;
+        // None of the level's pending folders will resolve now
+        const pending = this.compareEntries.filter((e) => e.status === "pending").length;
+        this.compareEntries = this.compareEntries.map((e) =>
+          e.status === "pending" ? { ...e, status: "error" } : e,
+        );
+        if (this.compareSummary) {
+          const s = { ...this.compareSummary };
+          s.pending = 0;
+          s.errors += pending;
           this.compareSummary = s;
         }
+        this.setError(`Cannot resolve folders: ${p.message}`);
       }),
-      invoke<{ home: string; entries: BrowseEntry[] }>("init_browse").catch(() => null),
+      listen<DirSizeResolvedPayload>("dir-size-resolved", (event) => {
+        const p = event.payload;
+        const patch = (entries: BrowseEntry[]) =>
+          entries.map((e) =>
+            e.name === p.name && e.kind === "dir"
+              ? { ...e, size: p.totalSize, fileCount: p.fileCount }
+              : e,
+          );
+        // Either pane (or both) may be showing the measured directory's parent
+        if (p.parentPath === this.leftPath) this.leftEntries = patch(this.leftEntries);
+        if (p.parentPath === this.rightPath) this.rightEntries = patch(this.rightEntries);
+      }),
+      listen<DirectoryChangedPayload>("directory-changed", (event) => {
+        const { pane, path, names } = event.payload;
+        const side = pane.startsWith("left") ? "left" : "right";
+        if (path !== (side === "left" ? this.leftPath : this.rightPath)) return;
+        // Changes to entries the pane leaves out need no new listing
+        const showHidden = side === "left" ? this.leftShowHidden : this.rightShowHidden;
+        if (!showHidden && names.length > 0 && names.every((n) => n.startsWith("."))) return;
+        this.loadDirectory(side);
+      }),
+      invoke<{
+        home: string;
+        entries: BrowseEntry[];
+        volumes: Volume[];
+        knownFolders: KnownFolder[];
+      }>("init_browse").catch(() => null),
       invoke<{
         leftPath: string; rightPath: string;
         leftSelectedIndex: number; leftScrollTop: number;
@@ -175,7 +286,11 @@ class CompareStore {
       } | null>("load_app_state").catch(() => null),
     ]);
 
-    this.unlisteners.push(scanUn, doneUn, errorUn, dirResolvedUn);
+    this.unlisteners.push(
+      scanUn, doneUn, errorUn, updatedUn, dirResolvedUn, dirSizeUn, dirChangedUn,
+    );
+    this.volumes = initResult?.volumes ?? [];
+    this.knownFolders = initResult?.knownFolders ?? [];
 
     if (savedState) {
       // Restore saved state — load directory listings for saved paths
@@ -205,16 +320,35 @@ class CompareStore {
       this.rightPath = initResult.home;
       this.leftEntries = initResult.entries;
       this.rightEntries = [...initResult.entries];
+      this.resolveDirSizes(initResult.home, initResult.entries);
     }
 
-    // Window starts hidden (tauri.conf.json visible:false) — show once content is ready
+    // Window starts hidden (tauri.conf.json visible:false) — show once content is ready,
+    // where it was last time
     try {
-      await getCurrentWindow().show();
+      const win = getCurrentWindow();
+      await this.restoreWindow(win);
+      await win.show();
+      this.unlisteners.push(
+        await win.onMoved(() => this.debouncedWindowSave()),
+        await win.onResized(() => this.debouncedWindowSave()),
+      );
     } catch {
       // In browser/test environments, this will fail silently
     }
   }
 
+  /** Whether the compare view shows the level these two folders make up. */
+  private showsCompareLevel(leftPath: string, rightPath: string): boolean {
+    const leftFull = this.compareRelPath
+      ? this.leftRoot + "/" + this.compareRelPath
+      : this.leftRoot;
+    const rightFull = this.compareRelPath
+      ? this.rightRoot + "/" + this.compareRelPath
+      : this.rightRoot;
+    return leftPath === leftFull && rightPath === rightFull;
+  }
+
   destroy() {
     for (const unlisten of this.unlisteners) {
       unlisten();
@@ -222,13 +356,29 @@ class CompareStore {
     this.unlisteners = [];
   }
 
+  /**
+   * Opens a path the user typed, like `~/Desktop` or `$HOME/x`, expanded and shown in
+   * full. Paths from navigation are real names and go to `loadDirectory` as they are.
+   */
+  async openTypedPath(side: "left" | "right", input: string): Promise<boolean> {
+    let resolved: ResolvedPath;
+    try {
+      resolved = await invoke<ResolvedPath>("resolve_path", { input });
+    } catch (e) {
+      this.setError(`Cannot open ${input}: ${errorMessage(e)}`);
+      return false;
+    }
+    return await this.loadDirectory(side, resolved.path);
+  }
+
   async loadDirectory(side: "left" | "right", pathOverride?: string): Promise<boolean> {
     const path = pathOverride ?? (side === "left" ? this.leftPath : this.rightPath);
     if (!path) return false;
 
     this.loading = true;
     try {
-      const entries = await invoke<BrowseEntry[]>("list_directory", { path });
+      const includeHidden = side === "left" ? this.leftShowHidden : this.rightShowHidden;
+      const entries = await invoke<BrowseEntry[]>("list_directory", { path, includeHidden });
       // Commit path + entries together — no breadcrumb flicker on failure
       if (side === "left") {
         this.leftPath = path;
@@ -238,15 +388,27 @@ class CompareStore {
         this.rightEntries = entries;
       }
       this.setError(null);
+      // File operations are only allowed under the home folder and the pane roots
+      invoke("set_root", { side, path }).catch(() => {});
+      // List again when something else changes the folder
+      invoke("watch_directory", { pane: side, path }).catch(() => {});
+      this.resolveDirSizes(path, entries);
       return true;
     } catch (e) {
-      this.setError(`Cannot open ${path}: ${e}`);
+      this.setError(`Cannot open ${path}: ${errorMessage(e)}`);
       return false;
     } finally {
       this.loading = false;
     }
   }
 
+  // Sizes of directories not already cached arrive as dir-size-resolved events
+  private resolveDirSizes(path: string, entries: BrowseEntry[]) {
+    if (entries.some((e) => e.kind === "dir" && e.fileCount === null)) {
+      invoke("resolve_dir_sizes", { parentPath: path }).catch(() => {});
+    }
+  }
+
   async navigateTo(side: "left" | "right", dirName: string): Promise<boolean> {
     const currentPath = side === "left" ? this.leftPath : this.rightPath;
     const newPath = currentPath + "/" + dirName;
@@ -278,6 +440,7 @@ class CompareStore {
   async startCompare() {
     // Clear cache from any previous comparison session
     invoke("clear_dir_resolve_cache").catch(() => {});
+    invoke("cancel_dir_sizes").catch(() => {});
     // Set roots from current browse paths
     this.leftRoot = this.leftPath;
     this.rightRoot = this.rightPath;
@@ -304,6 +467,10 @@ class CompareStore {
       });
       this.compareEntries = result.entries;
       this.compareSummary = result.summary;
+      const sideError = result.errorLeft ?? result.errorRight;
+      if (sideError) {
+        this.setError(`Partial listing: ${sideError.message}`);
+      }
 
       // Phase 2: resolve pending directories in background
       const hasPending = result.entries.some((e) => e.status === "pending");
@@ -314,7 +481,7 @@ class CompareStore {
         }).catch(() => {});
       }
     } catch (e) {
-      this.setError(`Compare failed: ${e}`);
+      this.setError(`Compare failed: ${errorMessage(e)}`);
     } finally {
       this.loading = false;
     }
@@ -358,9 +525,20 @@ class CompareStore {
     this.phase = "cancelled";
   }
 
+  async setWatching(enabled: boolean) {
+    try {
+      await invoke("watch_roots", { enabled });
+      this.watching = enabled;
+    } catch (e) {
+      this.watching = false;
+      this.setError(`Cannot watch: ${errorMessage(e)}`);
+    }
+  }
+
   backToBrowse() {
     invoke("cancel_dir_resolve").catch(() => {});
     invoke("clear_dir_resolve_cache").catch(() => {});
+    if (this.watching) this.setWatching(false);
     this.appMode = "browse";
     this.phase = "idle";
     this.diffs = [];
@@ -380,7 +558,7 @@ class CompareStore {
     this.showDetails = !this.showDetails;
   }
 
-  toggleHidden(side?: "left" | "right") {
+  async toggleHidden(side?: "left" | "right") {
     const s = side ?? this.activePane;
     if (s === "left") {
       this.leftShowHidden = !this.leftShowHidden;
@@ -388,6 +566,8 @@ class CompareStore {
       this.rightShowHidden = !this.rightShowHidden;
     }
     this.debouncedSave();
+    // Hidden entries are filtered by the backend, so they have to be fetched again
+    await this.loadDirectory(s);
   }
 
   async refresh() {
@@ -400,7 +580,7 @@ class CompareStore {
     try {
       await invoke("open_file", { path: fullPath });
     } catch (e) {
-      this.setError(`${e}`);
+      this.setError(`${errorMessage(e)}`);
     }
   }
 
@@ -418,6 +598,41 @@ class CompareStore {
     this.saveTimer = setTimeout(() => this.saveState(), 300);
   }
 
+  private async restoreWindow(win: Window) {
+    const saved = await invoke<WindowState | null>("load_window_state");
+    if (!saved) return;
+    const { maximized, ...normal } = saved;
+    this.normalWindow = normal;
+    await win.setSize(new PhysicalSize(normal.width, normal.height));
+    await win.setPosition(new PhysicalPosition(normal.x, normal.y));
+    if (maximized) await win.maximize();
+  }
+
+  private debouncedWindowSave() {
+    if (this.windowSaveTimer) clearTimeout(this.windowSaveTimer);
+    this.windowSaveTimer = setTimeout(() => this.saveWindow(), 500);
+  }
+
+  private async saveWindow() {
+    try {
+      const win = getCurrentWindow();
+      const maximized = await win.isMaximized();
+      if (!maximized) {
+        const [position, size] = await Promise.all([win.outerPosition(), win.innerSize()]);
+        this.normalWindow = {
+          x: position.x,
+          y: position.y,
+          width: size.width,
+          height: size.height,
+        };
+      }
+      if (!this.normalWindow) return;
+      await invoke("save_window_state", { ...this.normalWindow, maximized });
+    } catch {
+      // Silently ignore save failures
+    }
+  }
+
   private async saveState() {
     try {
       await invoke("save_app_state", {
@@ -446,11 +661,23 @@ class CompareStore {
 
   async exportReport() {
     const filePath = await save({
-      filters: [{ name: "JSON", extensions: ["json"] }],
+      filters: [
+        { name: "JSON", extensions: ["json"] },
+        { name: "HTML", extensions: ["html", "htm"] },
+        { name: "Markdown", extensions: ["md"] },
+        { name: "Sync script", extensions: ["sh"] },
+      ],
       defaultPath: "splitcommander-report.json",
     });
     if (filePath) {
-      await invoke("export_report", { path: filePath });
+      const format: ExportFormat = /\.html?$/i.test(filePath)
+        ? "html"
+        : /\.md$/i.test(filePath)
+          ? "markdown"
+          : /\.sh$/i.test(filePath)
+            ? "syncScript"
+            : "json";
+      await invoke("export_report", { path: filePath, format });
     }
   }
 
@@ -475,11 +702,13 @@ class CompareStore {
     if (!yes) return;
 
     try {
-      await invoke("copy_entry", { sourcePath: srcPath, destDir });
+      const outcome = await invoke<CopyOutcome>("copy_entry", { sourcePath: srcPath, destDir });
       const otherSide = this.activePane === "left" ? "right" : "left";
       await this.loadDirectory(otherSide);
+      const partial = partialCopyMessage(outcome);
+      if (partial) this.setError(partial);
     } catch (e) {
-      this.setError(`Copy failed: ${e}`);
+      this.setError(`Copy failed: ${errorMessage(e)}`);
     }
   }
 
@@ -504,7 +733,7 @@ class CompareStore {
         this.loadDirectory("right"),
       ]);
     } catch (e) {
-      this.setError(`Move failed: ${e}`);
+      this.setError(`Move failed: ${errorMessage(e)}`);
     }
   }
 
@@ -526,7 +755,7 @@ class CompareStore {
       await invoke("delete_entry", { targetPath });
       await this.loadDirectory(this.activePane);
     } catch (e) {
-      this.setError(`Delete failed: ${e}`);
+      this.setError(`Delete failed: ${errorMessage(e)}`);
     }
   }
 
@@ -555,7 +784,7 @@ class CompareStore {
       await invoke("create_directory", { parentPath, name });
       await this.loadDirectory(this.activePane);
     } catch (e) {
-      this.setError(`Create directory failed: ${e}`);
+      this.setError(`Create directory failed: ${errorMessage(e)}`);
     }
   }
 
@@ -579,7 +808,8 @@ class CompareStore {
 
   getSyncActions(entry: CompareEntry): SyncAction[] {
     const name = entry.name;
-    if (entry.status === "same" || entry.status === "pending" || entry.status === "typeMismatch") {
+    const inert = ["same", "pending", "partial", "typeMismatch", "error", "special"];
+    if (inert.includes(entry.status)) {
       return [];
     }
     // metaDiff directories: no actions (navigate inside instead)
@@ -613,19 +843,26 @@ class CompareStore {
     const leftPath = leftDir + "/" + action.entryName;
     const rightPath = rightDir + "/" + action.entryName;
 
+    let outcome: CopyOutcome | null = null;
     try {
       switch (action.kind) {
         case "copyToRight":
-          await invoke("copy_entry", { sourcePath: leftPath, destDir: rightDir });
+          outcome = await invoke("copy_entry", { sourcePath: leftPath, destDir: rightDir });
           break;
         case "copyToLeft":
-          await invoke("copy_entry", { sourcePath: rightPath, destDir: leftDir });
+          outcome = await invoke("copy_entry", { sourcePath: rightPath, destDir: leftDir });
           break;
         case "overwriteToRight":
-          await invoke("copy_entry_overwrite", { sourcePath: leftPath, destDir: rightDir });
+          outcome = await invoke("copy_entry_overwrite", {
+            sourcePath: leftPath,
+            destDir: rightDir,
+          });
           break;
         case "overwriteToLeft":
-          await invoke("copy_entry_overwrite", { sourcePath: rightPath, destDir: leftDir });
+          outcome = await invoke("copy_entry_overwrite", {
+            sourcePath: rightPath,
+            destDir: leftDir,
+          });
           break;
         case "deleteLeft":
           await invoke("delete_entry", { targetPath: leftPath });
@@ -636,9 +873,11 @@ class CompareStore {
       }
       // Refresh the current compare directory
       await this.loadCompareDirectory();
+      const partial = outcome && partialCopyMessage(outcome);
+      if (partial) this.setError(partial);
       return true;
     } catch (e) {
-      this.setError(`Sync failed: ${e}`);
+      this.setError(`Sync failed: ${errorMessage(e)}`);
       return false;
     }
   }
@@ -1,6 +1,11 @@
 import { invoke } from "@tauri-apps/api/core";
 import { listen, type UnlistenFn } from "@tauri-apps/api/event";
-import type { TerminalOutputPayload, TerminalExitPayload } from "../types";
+import type {
+  TerminalOutputPayload,
+  TerminalExitPayload,
+  TerminalCwdChangedPayload,
+  TerminalBuffer,
+} from "../types";
 
 const DOUBLE_ESC_MS = 400;
 
@@ -10,20 +15,23 @@ class TerminalStore {
   rightAlive = $state(false);
   activeSide = $state<"left" | "right">("left");
   heightPercent = $state(20);
+  /** Folder each side's shell last reported through OSC 7 / OSC 1337 */
+  leftCwd = $state<string | null>(null);
+  rightCwd = $state<string | null>(null);
 
-  private leftWriteCallback: ((data: string) => void) | null = null;
-  private rightWriteCallback: ((data: string) => void) | null = null;
+  private leftWriteCallback: ((data: string, seq: number) => void) | null = null;
+  private rightWriteCallback: ((data: string, seq: number) => void) | null = null;
   private unlisteners: UnlistenFn[] = [];
   private lastEscapeTime = 0;
 
   async initListeners() {
-    const [outputUn, exitUn] = await Promise.all([
+    const [outputUn, exitUn, cwdUn] = await Promise.all([
       listen<TerminalOutputPayload>("terminal-output", (event) => {
-        const { side, data } = event.payload;
+        const { side, data, seq } = event.payload;
         if (side === "left" && this.leftWriteCallback) {
-          this.leftWriteCallback(data);
+          this.leftWriteCallback(data, seq);
         } else if (side === "right" && this.rightWriteCallback) {
-          this.rightWriteCallback(data);
+          this.rightWriteCallback(data, seq);
         }
       }),
       listen<TerminalExitPayload>("terminal-exit", (event) => {
@@ -38,11 +46,22 @@ class TerminalStore {
           this.visible = false;
         }
       }),
+      listen<TerminalCwdChangedPayload>("terminal-cwd-changed", (event) => {
+        const { side, cwd } = event.payload;
+        if (side === "left") {
+          this.leftCwd = cwd;
+        } else {
+          this.rightCwd = cwd;
+        }
+      }),
     ]);
-    this.unlisteners.push(outputUn, exitUn);
+    this.unlisteners.push(outputUn, exitUn, cwdUn);
   }
 
-  setWriteCallback(side: "left" | "right", cb: ((data: string) => void) | null) {
+  setWriteCallback(
+    side: "left" | "right",
+    cb: ((data: string, seq: number) => void) | null,
+  ) {
     if (side === "left") {
       this.leftWriteCallback = cb;
     } else {
@@ -79,6 +98,11 @@ class TerminalStore {
   }
 
   async spawn(side: "left" | "right", cwd: string, rows = 24, cols = 80) {
+    if (side === "left") {
+      this.leftCwd = null;
+    } else {
+      this.rightCwd = null;
+    }
     try {
       await invoke("spawn_terminal", { side, cwd, rows, cols });
       if (side === "left") {
@@ -126,6 +150,29 @@ class TerminalStore {
     }
   }
 
+  /** Recent output of the side's terminal, to repopulate a view attached again. */
+  async buffer(side: "left" | "right"): Promise<TerminalBuffer> {
+    const alive = side === "left" ? this.leftAlive : this.rightAlive;
+    const none = { data: "", seq: 0 };
+    if (!alive) return none;
+    try {
+      return await invoke<TerminalBuffer>("get_terminal_buffer", { side });
+    } catch {
+      return none;
+    }
+  }
+
+  /** The folder the side's shell is in, or null when it cannot be told. */
+  async currentDir(side: "left" | "right"): Promise<string | null> {
+    const alive = side === "left" ? this.leftAlive : this.rightAlive;
+    if (!alive) return null;
+    try {
+      return await invoke<string | null>("get_terminal_cwd", { side });
+    } catch {
+      return null;
+    }
+  }
+
   async killAll() {
     await Promise.all([this.kill("left"), this.kill("right")]);
   }
@@ -1,10 +1,19 @@
-export type EntryKind = "file" | "dir" | "symlink";
+export type EntryKind =
+  | "file"
+  | "dir"
+  | "symlink"
+  | "fifo"
+  | "socket"
+  | "blockDevice"
+  | "charDevice";
 
 export interface EntryMeta {
   kind: EntryKind;
   size: number;
   modified: number | null;
   symlinkTarget: string | null;
+  /** Unix permission bits, e.g. 0o755; null off Unix */
+  mode: number | null;
 }
 
 export type DiffKind =
@@ -13,7 +22,9 @@ export type DiffKind =
   | "typeMismatch"
   | "same"
   | "metaDiff"
-  | "error";
+  | "error"
+  | "caseCollision"
+  | "special";
 
 export interface DiffItem {
   relPath: string;
@@ -21,6 +32,13 @@ export interface DiffItem {
   left: EntryMeta | null;
   right: EntryMeta | null;
   errorMessage: string | null;
+  newer: "left" | "right" | null;
+}
+
+export interface DiffPage {
+  items: DiffItem[];
+  /** Diffs matching the filter, across all pages */
+  total: number;
 }
 
 export interface CompareSummary {
@@ -32,22 +50,115 @@ export interface CompareSummary {
   same: number;
   metaDiff: number;
   errors: number;
+  excludedBySelf: number;
+  leftNewer: number;
+  rightNewer: number;
+  caseCollisions: number;
+  /** Fifos, sockets and devices on both sides, left uncompared */
+  special: number;
+  leftCaseSensitive: boolean | null;
+  rightCaseSensitive: boolean | null;
+  warnings: string[];
+  /** Directories still resolving (single-level compare only) */
+  pending: number;
+  bytesOnlyLeft: number;
+  bytesOnlyRight: number;
+  /** Larger of the two sizes of each modified entry */
+  bytesModified: number;
+  /** The byte totals above with hard-linked files counted once */
+  uniqueBytesOnlyLeft: number;
+  uniqueBytesOnlyRight: number;
+  uniqueBytesModified: number;
+  /** Directories at the depth limit whose contents were not compared */
+  depthLimited: number;
 }
 
 export interface ScanProgressPayload {
+  /** Id returned by start_compare; lower ids belong to superseded compares */
+  jobId: number;
   side: "left" | "right";
   entriesScanned: number;
+  /** Sum of the sizes of files listed so far */
+  bytesScanned: number;
+  /** Directory being walked, relative to the root; null when unchanged */
+  currentPath: string | null;
   phase: "scanning" | "done";
 }
 
+export interface ReportChange {
+  relPath: string;
+  /** null when the path is absent from that report */
+  oldKind: DiffKind | null;
+  newKind: DiffKind | null;
+}
+
+export interface ReportComparison {
+  oldLeftRoot: string;
+  oldRightRoot: string;
+  newLeftRoot: string;
+  newRightRoot: string;
+  rootsDiffer: boolean;
+  /** A report leaves entries out, so paths only the other lists were not all compared */
+  partial: boolean;
+  oldGeneratedAt: string;
+  newGeneratedAt: string;
+  changes: ReportChange[];
+}
+
+export type ExportFormat = "json" | "html" | "markdown" | "syncScript";
+
+export interface ExportProgressPayload {
+  operationId: number;
+  entriesWritten: number;
+  totalEntries: number;
+  bytesWritten: number;
+}
+
+export interface ExportDonePayload {
+  operationId: number;
+  path: string;
+  bytesWritten: number;
+}
+
+export interface ExportErrorPayload {
+  operationId: number;
+  path: string;
+  message: string;
+}
+
 export interface CompareDonePayload {
+  jobId: number;
   summary: CompareSummary;
 }
 
 export interface CompareErrorPayload {
+  jobId: number;
   message: string;
 }
 
+/** Entries of a folder `watch_directory` watches changed; `names` is empty when unknown */
+export interface DirectoryChangedPayload {
+  pane: PaneId;
+  path: string;
+  kind: "created" | "removed" | "renamed" | "modified";
+  names: string[];
+}
+
+/** Paths compared again after they changed, in watch mode or by a file operation */
+export interface DiffsUpdatedPayload {
+  jobId: number;
+  /** Empty when `full` is set */
+  paths: UpdatedPath[];
+  full: boolean;
+  summary: CompareSummary;
+}
+
+export interface UpdatedPath {
+  relPath: string;
+  /** `null` when the path is no longer listed */
+  kind: DiffKind | null;
+}
+
 export type ComparePhase =
   | "idle"
   | "scanning-left"
@@ -62,11 +173,38 @@ export interface BrowseEntry {
   kind: EntryKind;
   size: number;
   modified: number | null;
+  /** Set for directories whose recursive size is known; `size` is then that size */
+  fileCount: number | null;
+  /** Unix permission bits, e.g. 0o755; on Windows only read-only vs read-write */
+  mode: number | null;
+  /** Owner and group names (or ids); null off Unix */
+  owner: string | null;
+  group: string | null;
+  /** Dot-prefixed, or marked hidden on Windows */
+  hidden: boolean;
+  /** Why the entry cannot be read; its kind is then a best guess */
+  error: string | null;
+}
+
+/** Where a typed path leads; nonexistent paths resolve as far as they exist */
+export interface ResolvedPath {
+  path: string;
+  exists: boolean;
+  isDir: boolean;
 }
 
 export type AppMode = "browse" | "compare";
 
-export type CompareStatus = "same" | "modified" | "onlyLeft" | "onlyRight" | "typeMismatch" | "pending";
+export type CompareStatus =
+  | "same"
+  | "modified"
+  | "onlyLeft"
+  | "onlyRight"
+  | "typeMismatch"
+  | "pending"
+  | "partial"
+  | "error"
+  | "special";
 
 export interface CompareEntry {
   name: string;
@@ -76,7 +214,30 @@ export interface CompareEntry {
   rightSize: number | null;
   leftModified: number | null;
   rightModified: number | null;
-  dirInfo: { totalSize: number } | null;
+  dirInfo: DirResolveInfo | null;
+}
+
+export interface DirResolveInfo {
+  leftSize: number;
+  rightSize: number;
+  /** Sizes with hard-linked files counted once */
+  leftUniqueSize: number;
+  rightUniqueSize: number;
+  /** Differing entries anywhere below; a one-sided directory counts once */
+  onlyLeft: number;
+  onlyRight: number;
+  modified: number;
+  /** Directories past the depth limit that were not walked */
+  depthLimited: number;
+  /** Folder pairs where a side could not be listed; the status is then "error" */
+  unreadable?: number;
+}
+
+/** A compare level whose pending folders could not be resolved */
+export interface DirResolveErrorPayload {
+  leftPath: string;
+  rightPath: string;
+  message: string;
 }
 
 export interface DirStatusResolvedPayload {
@@ -84,7 +245,15 @@ export interface DirStatusResolvedPayload {
   status: CompareStatus;
   leftPath: string;
   rightPath: string;
-  totalSize: number;
+  leftSize: number;
+  rightSize: number;
+  leftUniqueSize: number;
+  rightUniqueSize: number;
+  onlyLeft: number;
+  onlyRight: number;
+  modified: number;
+  depthLimited: number;
+  unreadable: number;
 }
 
 export interface CompareDirectoryResult {
@@ -92,15 +261,40 @@ export interface CompareDirectoryResult {
   leftPath: string;
   rightPath: string;
   summary: CompareSummary;
+  /** Set when that side could not be listed; its entries are missing */
+  errorLeft: CommandError | null;
+  errorRight: CommandError | null;
 }
 
+/** A pane tab, `left:0`, `right:2`; a bare side is its tab 0 */
+export type PaneId = `${"left" | "right"}:${number}`;
+
 export interface TerminalOutputPayload {
   side: "left" | "right";
+  paneId: PaneId;
   data: string;
+  /** Numbers the terminal's output events from 1 */
+  seq: number;
+}
+
+/** A terminal's recent output; `seq` is that of the last output event it holds */
+export interface TerminalBuffer {
+  data: string;
+  seq: number;
+}
+
+export interface TerminalCwdChangedPayload {
+  side: "left" | "right";
+  paneId: PaneId;
+  cwd: string;
 }
 
 export interface TerminalExitPayload {
   side: "left" | "right";
+  paneId: PaneId;
+  /** The shell's exit code, when it could be waited for */
+  exitCode: number | null;
+  reason: "exited" | "killed" | "readError";
 }
 
 export type SyncActionKind = "copyToRight" | "copyToLeft" | "deleteLeft" | "deleteRight" | "overwriteToRight" | "overwriteToLeft";
@@ -110,3 +304,478 @@ export interface SyncAction {
   label: string;
   entryName: string;
 }
+
+export interface OperationInfo {
+  id: number;
+  kind: string;
+  done: number;
+  total: number;
+  message: string;
+  /** Step within the current item, e.g. "verifying" */
+  phase: string | null;
+}
+
+export interface MaintenanceCategoryReport {
+  category: string;
+  filesRemoved: number;
+  bytesFreed: number;
+  errors: string[];
+}
+
+export interface MaintenanceReport {
+  categories: MaintenanceCategoryReport[];
+  bytesFreed: number;
+  cancelled: boolean;
+}
+
+export type VerifyMismatchKind =
+  | "onlyLeft"
+  | "onlyRight"
+  | "typeMismatch"
+  | "sizeDiffers"
+  | "contentDiffers"
+  | "symlinkTargetDiffers"
+  | "error";
+
+export interface VerifyResult {
+  passed: boolean;
+  filesCompared: number;
+  bytesCompared: number;
+  /** Fifos, sockets and devices on both sides; never read */
+  specialSkipped: number;
+  mismatchCount: number;
+  mismatches: { relPath: string; kind: VerifyMismatchKind; message: string | null }[];
+}
+
+/** Result of `measure_paths` for a selection */
+export interface PathTotals {
+  /** Everything that is not a folder, symlinks included */
+  files: number;
+  dirs: number;
+  bytes: number;
+}
+
+/** Result of `get_volume_info` */
+export interface VolumeInfo {
+  totalBytes: number;
+  /** Available to the current user */
+  freeBytes: number;
+  /** E.g. "apfs", "ext4"; null when the platform does not say */
+  filesystem: string | null;
+}
+
+/** A mounted volume, drive or network share */
+export interface Volume {
+  name: string;
+  mountPoint: string;
+  /** null when unavailable or the platform does not say */
+  totalBytes: number | null;
+  freeBytes: number | null;
+  isRemovable: boolean;
+  isNetwork: boolean;
+  /** False when the volume did not answer in time, e.g. a stale network mount */
+  available: boolean;
+}
+
+/** A standard folder for the sidebar */
+export interface KnownFolder {
+  id:
+    | "home"
+    | "desktop"
+    | "documents"
+    | "downloads"
+    | "pictures"
+    | "music"
+    | "videos"
+    | "leftRoot"
+    | "rightRoot"
+    | "temp";
+  /** The folder's own name, localized on some systems */
+  name: string;
+  path: string;
+  exists: boolean;
+}
+
+export interface Favorite {
+  name: string;
+  path: string;
+}
+
+/** Recent folders and favorites; ones that do not exist right now are left out */
+export interface Locations {
+  /** Most recent first */
+  recentPaths: string[];
+  favorites: Favorite[];
+}
+
+export interface PaneOptions {
+  showHidden: boolean;
+  sort: { sortBy: "name" | "size" | "modified" | "extension"; ascending: boolean };
+}
+
+/** A saved pair of roots and the options they are compared with */
+export interface Session {
+  name: string;
+  leftRoot: string;
+  rightRoot: string;
+  options: {
+    mode: "structure" | "smart" | "timestamp" | null;
+    ignorePatterns: string[];
+    scanOptions: {
+      skipHidden: boolean;
+      followLinks: boolean;
+      maxDepth: number | null;
+      parallelism: number | null;
+    };
+    left: PaneOptions;
+    right: PaneOptions;
+  };
+}
+
+/** Main window geometry in physical pixels: outer position, inner size */
+export interface WindowState {
+  x: number;
+  y: number;
+  width: number;
+  height: number;
+  maximized: boolean;
+}
+
+export interface DirSizeResolvedPayload {
+  parentPath: string;
+  name: string;
+  totalSize: number;
+  fileCount: number;
+}
+
+export interface SearchMatch {
+  relPath: string;
+  kind: EntryKind;
+  size: number;
+  modified: number | null;
+}
+
+export interface SearchSummary {
+  total: number;
+  truncated: boolean;
+}
+
+export interface SearchResultPayload {
+  root: string;
+  matches: SearchMatch[];
+}
+
+export interface SearchDonePayload extends SearchSummary {
+  root: string;
+}
+
+export interface ContentMatch {
+  relPath: string;
+  /** 1-based */
+  lineNumber: number;
+  lineText: string;
+}
+
+export interface ContentSummary {
+  total: number;
+  truncated: boolean;
+  filesSearched: number;
+  skippedBinary: number;
+  skippedLarge: number;
+  errors: ScanError[];
+}
+
+export interface ContentSearchResultPayload {
+  root: string;
+  matches: ContentMatch[];
+}
+
+export interface ContentSearchDonePayload {
+  root: string;
+  summary: ContentSummary;
+}
+
+export type PreviewEncoding = "utf8" | "utf8Lossy" | "hex";
+
+export interface FilePreview {
+  isBinary: boolean;
+  encoding: PreviewEncoding;
+  /** Whether `text` covers less than the whole file */
+  truncated: boolean;
+  /** File contents, or a hex dump when `encoding` is "hex" */
+  text: string;
+  byteLength: number;
+}
+
+export type DiffLineKind = "context" | "added" | "removed";
+
+export interface DiffLine {
+  kind: DiffLineKind;
+  /** 1-based; null for added lines */
+  oldLine: number | null;
+  /** 1-based; null for removed lines */
+  newLine: number | null;
+  text: string;
+}
+
+export interface DiffHunk {
+  oldStart: number;
+  oldLines: number;
+  newStart: number;
+  newLines: number;
+  added: number;
+  removed: number;
+  /** Empty for hunks past the line limit */
+  lines: DiffLine[];
+}
+
+export interface FileDiff {
+  isBinary: boolean;
+  tooLarge: boolean;
+  /** Empty when the contents are identical */
+  hunks: DiffHunk[];
+  /** Some hunks were cut to their headers */
+  truncated: boolean;
+  leftSize: number;
+  rightSize: number;
+}
+
+export interface FileComparison {
+  same: boolean;
+  leftSize: number;
+  rightSize: number;
+  /** Only found when the contents were compared */
+  firstDifferenceOffset: number | null;
+  /** "size" when the sizes already differed, "content" when both were read */
+  method: "size" | "content";
+}
+
+export type HashAlgorithm = "sha256" | "xxh3";
+
+export interface FileChecksum {
+  path: string;
+  /** Lowercase hex digest */
+  hex: string;
+  bytes: number;
+}
+
+export interface ChecksumReport {
+  checksums: FileChecksum[];
+  /** Paths that could not be hashed, including directories */
+  errors: ScanError[];
+}
+
+export type DuplicateScope = "left" | "right" | "both";
+
+/** Payload of "duplicate-group" */
+export interface DuplicateGroup {
+  /** SHA-256, lowercase hex */
+  hash: string;
+  size: number;
+  /** Absolute paths, sorted; hard links appear once */
+  paths: string[];
+}
+
+/** Returned by find_duplicates and sent as "duplicates-done" */
+export interface DuplicateSummary {
+  groups: number;
+  duplicateFiles: number;
+  wastedBytes: number;
+  unreadable: number;
+}
+
+export interface ChecksumProgressPayload {
+  path: string;
+  bytesHashed: number;
+  totalBytes: number;
+}
+
+export interface MeasureProgressPayload {
+  itemsCounted: number;
+  bytesCounted: number;
+}
+
+/** What copy_entry / move_entry do when the destination exists; "fail" if omitted */
+export type ConflictPolicy = "fail" | "overwrite" | "rename" | "merge";
+
+/** The options of copy_entry / copy_entry_overwrite; each is off if omitted */
+export interface CopyEntryOptions {
+  preserveMetadata?: boolean;
+  verify?: boolean;
+  ignoreSpaceCheck?: boolean;
+  failFast?: boolean;
+  dryRun?: boolean;
+}
+
+/** The options of sync_diffs; each is off if omitted */
+export interface SyncOptions {
+  dryRun?: boolean;
+  verify?: boolean;
+  ignoreSpaceCheck?: boolean;
+}
+
+/** Destination paths of a merge copy, by what happened to them */
+export interface MergeSummary {
+  copied: string[];
+  overwritten: string[];
+  skipped: ScanError[];
+  /** File on one side, folder on the other; left untouched */
+  conflicts: string[];
+}
+
+export interface CopyOutcome {
+  dest: string;
+  filesCloned: number;
+  filesCopied: number;
+  /** Set for copies made with the "merge" policy */
+  merged: MergeSummary | null;
+  /** Entries of a copied folder that could not be copied; non-empty means partial */
+  skipped: ScanError[];
+  /** Set by a dry run, which copies nothing */
+  plan: EntryPlan | null;
+}
+
+/** What a dry-run copy, move or delete would do */
+export interface EntryPlan {
+  /** null for a delete */
+  source: string | null;
+  destination: string;
+  bytes: number;
+  /** The destination exists and would be replaced or merged into */
+  conflict: boolean;
+}
+
+/** Result of `move_entry` */
+export interface MoveOutcome {
+  dest: string;
+  /** Set by a dry run, which moves nothing */
+  plan: EntryPlan | null;
+}
+
+export type ArchiveFormat = "zip" | "tar.gz";
+
+/** Result of `create_archive` */
+export interface ArchiveReport {
+  /** Where the archive was written; differs from the requested path after a rename */
+  dest: string;
+  files: number;
+  dirs: number;
+  symlinks: number;
+  /** Uncompressed size of the files stored */
+  bytes: number;
+  archiveBytes: number;
+  /** Fifos, sockets and devices, which cannot be archived */
+  skipped: ScanError[];
+}
+
+/** Result of `extract_archive` */
+export interface ExtractReport {
+  /** Files, folders and links written */
+  extracted: number;
+  /** Existing files kept, unsupported entries and failed writes */
+  skipped: ScanError[];
+  /** Entries that would land outside the destination, by name in the archive */
+  rejected: ScanError[];
+}
+
+/** A journaled operation, as listed by `get_undo_stack` */
+export type UndoEntry = {
+  id: number;
+  /** Epoch milliseconds */
+  recordedAt: number;
+} & (
+  | { kind: "move" | "rename"; from: string; to: string }
+  | { kind: "copy"; dest: string }
+  | { kind: "createDirectory" | "createFile"; path: string }
+);
+
+/** Result of `set_permissions` */
+export interface PermissionsOutcome {
+  /** Entries whose permissions actually changed */
+  changed: number;
+  /** Entries inside a folder that could not be changed */
+  errors: ScanError[];
+}
+
+export type SyncDirection = "leftToRight" | "rightToLeft";
+
+export interface PlannedStep {
+  action: "copy" | "delete" | "skip";
+  relPath: string;
+  source: string | null;
+  destination: string;
+  bytes: number;
+  /** The copy would replace an existing entry */
+  conflict: boolean;
+}
+
+export interface SyncPlan {
+  steps: PlannedStep[];
+  copies: number;
+  deletes: number;
+  skips: number;
+  conflicts: number;
+  bytesToCopy: number;
+  bytesToDelete: number;
+  failed: { relPath: string; message: string }[];
+}
+
+export interface SyncReport {
+  copied: number;
+  deleted: number;
+  skipped: number;
+  failed: { relPath: string; message: string }[];
+  cancelled: boolean;
+  /** Set for dry runs, which change nothing */
+  plan: SyncPlan | null;
+}
+
+export interface ScanError {
+  path: string;
+  message: string;
+}
+
+export interface ScanErrors {
+  left: ScanError[];
+  right: ScanError[];
+}
+
+export type CommandErrorCode =
+  | "notFound"
+  | "alreadyExists"
+  | "permissionDenied"
+  | "busy"
+  | "cancelled"
+  | "invalidArgument"
+  | "io"
+  | "verificationFailed"
+  | "insufficientSpace"
+  | "readOnlyMode"
+  | "taskFailed"
+  | "other";
+
+/** Rejection value of every backend command. */
+export interface CommandError {
+  code: CommandErrorCode;
+  message: string;
+  path: string | null;
+  /** Bytes needed and free at the destination, for `insufficientSpace` only */
+  required?: number;
+  available?: number;
+}
+
+/** Human-readable text for a command rejection (or any other thrown value). */
+export function errorMessage(e: unknown): string {
+  if (e && typeof e === "object" && "message" in e) {
+    return String((e as { message: unknown }).message);
+  }
+  return String(e);
+}
+
+/** "Copied with 3 errors (first: ...)" for a partial copy, null when nothing was skipped. */
+export function partialCopyMessage(outcome: CopyOutcome): string | null {
+  const [first] = outcome.skipped;
+  if (!first) return null;
+  const count = outcome.skipped.length;
+  return `Copied with ${count} error${count === 1 ? "" : "s"} (first: ${first.message})`;
+}